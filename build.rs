@@ -0,0 +1,14 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    // protox parses the .proto in Rust, so building doesn't need protoc installed
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/minipool.proto");
+        let descriptors =
+            protox::compile(["proto/minipool.proto"], ["proto"]).expect("invalid minipool.proto");
+        tonic_build::configure()
+            .build_client(false)
+            .compile_fds(descriptors)
+            .expect("failed to generate gRPC code");
+    }
+}
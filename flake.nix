@@ -28,7 +28,12 @@
 
         rustToolchain = pkgs.rust-bin.stable.latest.default;
         craneLib = (crane.mkLib pkgs).overrideToolchain rustToolchain;
-        src = craneLib.cleanCargoSource ./.;
+        # Keep the embedded static assets next to the Cargo sources
+        src = pkgs.lib.cleanSourceWith {
+          src = ./.;
+          filter = path: type:
+            (builtins.match ".*/assets(/.*)?" path != null) || (craneLib.filterCargoSources path type);
+        };
 
         # Build dependencies
         buildInputs = [];
@@ -36,7 +41,7 @@
 
         # Common arguments that are used for both checking and building
         commonArgs = {
-          src = craneLib.cleanCargoSource ./.;
+          inherit src;
           inherit buildInputs nativeBuildInputs;
         };
 
@@ -0,0 +1,782 @@
+//! Typed async client for the minipool API.
+//!
+//! Every method maps to one route, its path taken from [`paths`], the same
+//! table the server mounts its routes from. Routes that are only mounted when
+//! an index or feature is enabled answer 404 otherwise, surfaced as
+//! [`Error::Status`].
+
+pub mod paths;
+pub mod types;
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use bitcoin::{BlockHash, OutPoint, Txid};
+use reqwest::header::ACCEPT;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+
+pub use reqwest::Url;
+
+use self::types::*;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The request could not be sent or its body not read
+    Http(reqwest::Error),
+    /// The server answered with a non-success status
+    Status { status: StatusCode, body: String },
+    /// The body did not parse as the expected type
+    Decode(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Http(e) => write!(f, "request failed: {}", e),
+            Error::Status { status, body } => write!(f, "server answered {}: {}", status, body),
+            Error::Decode(e) => write!(f, "invalid response: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Http(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Http(e)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Clone, Debug)]
+pub struct Client {
+    http: reqwest::Client,
+    base: Url,
+    admin_token: Option<String>,
+}
+
+/// Fills the `{..}` segments of a route path with `args`, in order
+fn fill(template: &str, args: &[&dyn Display]) -> String {
+    let mut path = String::with_capacity(template.len());
+    let mut args = args.iter();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .map_or(rest.len(), |end| start + end + 1);
+        path.push_str(&rest[..start]);
+        if let Some(arg) = args.next() {
+            path.push_str(&arg.to_string());
+        }
+        rest = &rest[end..];
+    }
+    path.push_str(rest);
+    path
+}
+
+async fn check(response: Response) -> Result<Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await.unwrap_or_default();
+    Err(Error::Status { status, body })
+}
+
+fn with_filter(mut request: RequestBuilder, filter: &MempoolFilter<'_>) -> RequestBuilder {
+    if !filter.categories.is_empty() {
+        request = request.query(&[("filter", filter.categories.join(","))]);
+    }
+    if let Some(rate) = filter.min_fee_rate {
+        request = request.query(&[("min_fee_rate", rate)]);
+    }
+    if let Some(rate) = filter.max_fee_rate {
+        request = request.query(&[("max_fee_rate", rate)]);
+    }
+    request
+}
+
+fn parse<T: FromStr>(text: &str) -> Result<T>
+where
+    T::Err: Display,
+{
+    text.trim()
+        .parse()
+        .map_err(|e: T::Err| Error::Decode(e.to_string()))
+}
+
+impl Client {
+    /// Client for the server at `base`, e.g. `http://localhost:3000`
+    pub fn new(base: Url) -> Self {
+        Self::with_http_client(reqwest::Client::new(), base)
+    }
+
+    /// Client sending its requests through `http`, for custom timeouts or proxies
+    pub fn with_http_client(http: reqwest::Client, base: Url) -> Self {
+        Self {
+            http,
+            base,
+            admin_token: None,
+        }
+    }
+
+    /// Token sent as a bearer token to admin routes
+    pub fn with_admin_token(mut self, token: impl Into<String>) -> Self {
+        self.admin_token = Some(token.into());
+        self
+    }
+
+    fn url(&self, template: &str, args: &[&dyn Display]) -> Url {
+        let mut url = self.base.clone();
+        let base_path = url.path().trim_end_matches('/').to_owned();
+        url.set_path(&format!("{}{}", base_path, fill(template, args)));
+        url
+    }
+
+    fn get(&self, template: &str, args: &[&dyn Display]) -> RequestBuilder {
+        self.http.get(self.url(template, args))
+    }
+
+    fn post(&self, template: &str, args: &[&dyn Display]) -> RequestBuilder {
+        self.http.post(self.url(template, args))
+    }
+
+    fn delete(&self, template: &str, args: &[&dyn Display]) -> RequestBuilder {
+        self.http.delete(self.url(template, args))
+    }
+
+    fn admin(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.admin_token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<Response> {
+        check(request.send().await?).await
+    }
+
+    async fn json<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T> {
+        let body = self.send(request).await?.bytes().await?;
+        serde_json::from_slice(&body).map_err(|e| Error::Decode(e.to_string()))
+    }
+
+    async fn text(&self, request: RequestBuilder) -> Result<String> {
+        Ok(self.send(request).await?.text().await?)
+    }
+
+    async fn bytes(&self, request: RequestBuilder) -> Result<Vec<u8>> {
+        Ok(self.send(request).await?.bytes().await?.to_vec())
+    }
+
+    /// Current tip height, answered by the health check route
+    pub async fn health(&self) -> Result<u64> {
+        parse(&self.text(self.get(paths::HEALTH, &[])).await?)
+    }
+
+    /// Whether the node RPC and every hard component are healthy
+    pub async fn readyz(&self) -> Result<bool> {
+        let response = self.get(paths::READYZ, &[]).send().await?;
+        match response.status() {
+            StatusCode::SERVICE_UNAVAILABLE => Ok(false),
+            _ => check(response).await.map(|_| true),
+        }
+    }
+
+    pub async fn health_details(&self) -> Result<HealthDetails> {
+        self.json(self.get(paths::HEALTH_DETAILS, &[])).await
+    }
+
+    /// Routes the server mounts and their policies; admin routes are only
+    /// listed when the client has the admin token
+    pub async fn features(&self) -> Result<Features> {
+        self.json(self.admin(self.get(paths::FEATURES, &[]))).await
+    }
+
+    /// Version and indexes of the node, and whether confirmed transactions need
+    /// their block to be looked up
+    pub async fn node_info(&self) -> Result<NodeInfo> {
+        self.json(self.get(paths::NODE_INFO, &[])).await
+    }
+
+    pub async fn tip_height(&self) -> Result<u64> {
+        parse(&self.text(self.get(paths::TIP_HEIGHT, &[])).await?)
+    }
+
+    pub async fn tip_hash(&self) -> Result<BlockHash> {
+        parse(&self.text(self.get(paths::TIP_HASH, &[])).await?)
+    }
+
+    pub async fn block_hash(&self, height: u64) -> Result<BlockHash> {
+        parse(&self.text(self.get(paths::BLOCK_HEIGHT, &[&height])).await?)
+    }
+
+    pub async fn block(&self, hash: &BlockHash) -> Result<Block> {
+        self.json(self.get(paths::BLOCK, &[hash])).await
+    }
+
+    /// The 10 most recent blocks
+    pub async fn blocks(&self) -> Result<Vec<Block>> {
+        self.json(self.get(paths::BLOCKS, &[])).await
+    }
+
+    /// 10 blocks descending from `start_height`
+    pub async fn blocks_from(&self, start_height: u64) -> Result<Vec<Block>> {
+        self.json(self.get(paths::BLOCKS_FROM, &[&start_height]))
+            .await
+    }
+
+    /// Serialized block header as hex
+    pub async fn block_header(&self, hash: &BlockHash) -> Result<String> {
+        self.text(self.get(paths::BLOCK_HEADER, &[hash])).await
+    }
+
+    pub async fn block_status(&self, hash: &BlockHash) -> Result<BlockStatus> {
+        self.json(self.get(paths::BLOCK_STATUS, &[hash])).await
+    }
+
+    pub async fn block_txids(&self, hash: &BlockHash) -> Result<Vec<Txid>> {
+        self.json(self.get(paths::BLOCK_TXIDS, &[hash])).await
+    }
+
+    /// 25 transactions of a block from `start_index`, a multiple of 25
+    pub async fn block_txs(&self, hash: &BlockHash, start_index: usize) -> Result<Vec<Tx>> {
+        self.json(self.get(paths::BLOCK_TXS, &[hash, &start_index]))
+            .await
+    }
+
+    /// Serialized block
+    /// Txid at `index` in the block; needs the server in a compatibility mode
+    pub async fn block_txid(&self, hash: &BlockHash, index: usize) -> Result<Txid> {
+        parse(
+            &self
+                .text(self.get(paths::BLOCK_TXID, &[hash, &index]))
+                .await?,
+        )
+    }
+
+    pub async fn block_raw(&self, hash: &BlockHash) -> Result<Vec<u8>> {
+        self.bytes(
+            self.get(paths::BLOCK_RAW, &[hash])
+                .header(ACCEPT, "application/octet-stream"),
+        )
+        .await
+    }
+
+    /// BIP158 basic filter of a block, needs the node's block filter index
+    pub async fn block_filter(&self, hash: &BlockHash) -> Result<BlockFilter> {
+        self.json(self.get(paths::BLOCK_FILTER, &[hash])).await
+    }
+
+    /// Up to `count` (at most 2000) filter headers from `start_height`
+    pub async fn filter_headers(&self, start_height: u64, count: u64) -> Result<FilterHeaders> {
+        self.json(
+            self.get(paths::FILTER_HEADERS, &[&start_height])
+                .query(&[("count", count)]),
+        )
+        .await
+    }
+
+    /// The 15 most recent blocks with fee statistics and mining pool
+    pub async fn v1_blocks(&self) -> Result<Vec<ExtendedBlock>> {
+        self.json(self.get(paths::V1_BLOCKS, &[])).await
+    }
+
+    /// A block with fee statistics and mining pool; needs the server in mempool mode
+    pub async fn v1_block(&self, hash: &BlockHash) -> Result<ExtendedBlock> {
+        self.json(self.get(paths::V1_BLOCK, &[hash])).await
+    }
+
+    /// 15 blocks with fee statistics and mining pool, descending from `height`
+    pub async fn v1_blocks_from(&self, height: u64) -> Result<Vec<ExtendedBlock>> {
+        self.json(self.get(paths::V1_BLOCKS_FROM, &[&height])).await
+    }
+
+    /// Fee rate histogram of a block, `id` being its hash or height
+    pub async fn block_fee_histogram(&self, id: impl Display) -> Result<Vec<FeeBucket>> {
+        self.json(self.get(paths::BLOCK_FEE_HISTOGRAM, &[&id]))
+            .await
+    }
+
+    pub async fn difficulty_adjustment(&self) -> Result<DifficultyAdjustment> {
+        self.json(self.get(paths::DIFFICULTY_ADJUSTMENT, &[])).await
+    }
+
+    /// `period` like `24h`, `3d` or `1w`
+    pub async fn hashrate(&self, period: &str) -> Result<Hashrates> {
+        self.json(self.get(paths::MINING_HASHRATE, &[&period]))
+            .await
+    }
+
+    /// Fee rates in sat/vB by confirmation target
+    pub async fn fee_estimates(&self) -> Result<BTreeMap<String, f64>> {
+        self.json(self.get(paths::FEE_ESTIMATES, &[])).await
+    }
+
+    pub async fn recommended_fees(&self) -> Result<RecommendedFees> {
+        self.json(self.get(paths::FEES_RECOMMENDED, &[])).await
+    }
+
+    /// Next blocks projected from the mempool, the last one holding the rest of it
+    /// Projected blocks counting only the transactions matching `filter`
+    pub async fn mempool_blocks(&self, filter: &MempoolFilter<'_>) -> Result<Vec<ProjectedBlock>> {
+        self.json(with_filter(self.get(paths::MEMPOOL_BLOCKS, &[]), filter))
+            .await
+    }
+
+    pub async fn fee_accuracy(&self) -> Result<Vec<FeeAccuracy>> {
+        self.json(self.get(paths::FEE_ACCURACY, &[])).await
+    }
+
+    /// `period` like `24h`, `3d` or `1w`
+    pub async fn block_utilization(&self, period: &str) -> Result<Vec<BlockUtilization>> {
+        self.json(self.get(paths::BLOCK_UTILIZATION, &[&period]))
+            .await
+    }
+
+    /// `period` like `24h`, `3d` or `1w`
+    pub async fn script_types(&self, period: &str) -> Result<Vec<DailyScriptTypes>> {
+        self.json(self.get(paths::SCRIPT_TYPES, &[&period])).await
+    }
+
+    pub async fn propagation(&self) -> Result<Propagation> {
+        self.json(self.get(paths::PROPAGATION, &[])).await
+    }
+
+    /// Broadcasts a hex encoded transaction
+    pub async fn broadcast(&self, tx_hex: &str) -> Result<Txid> {
+        parse(
+            &self
+                .text(self.post(paths::BROADCAST, &[]).body(tx_hex.to_owned()))
+                .await?,
+        )
+    }
+
+    pub async fn tx(&self, txid: &Txid) -> Result<Tx> {
+        self.json(self.get(paths::TX, &[txid])).await
+    }
+
+    /// A transaction confirmed in `block`, which nodes without txindex need to find it
+    pub async fn tx_in_block(&self, txid: &Txid, block: &BlockHash) -> Result<Tx> {
+        self.json(
+            self.get(paths::TX, &[txid])
+                .query(&[("block", block.to_string())]),
+        )
+        .await
+    }
+
+    pub async fn tx_status(&self, txid: &Txid) -> Result<TxStatus> {
+        self.json(self.get(paths::TX_STATUS, &[txid])).await
+    }
+
+    pub async fn tx_hex(&self, txid: &Txid) -> Result<String> {
+        self.text(self.get(paths::TX_HEX, &[txid])).await
+    }
+
+    pub async fn tx_raw(&self, txid: &Txid) -> Result<Vec<u8>> {
+        self.bytes(self.get(paths::TX_RAW, &[txid])).await
+    }
+
+    pub async fn tx_merkle_proof(&self, txid: &Txid) -> Result<MerkleProof> {
+        self.json(self.get(paths::TX_MERKLE_PROOF, &[txid])).await
+    }
+
+    /// BIP37 `merkleblock` as hex
+    pub async fn tx_merkleblock_proof(&self, txid: &Txid) -> Result<String> {
+        self.text(self.get(paths::TX_MERKLEBLOCK_PROOF, &[txid]))
+            .await
+    }
+
+    pub async fn tx_outspend(&self, outpoint: &OutPoint) -> Result<Outspend> {
+        self.json(self.get(paths::TX_OUTSPEND, &[&outpoint.txid, &outpoint.vout]))
+            .await
+    }
+
+    pub async fn tx_outspends(&self, txid: &Txid) -> Result<Vec<Outspend>> {
+        self.json(self.get(paths::TX_OUTSPENDS, &[txid])).await
+    }
+
+    pub async fn tx_conflicts(&self, txid: &Txid) -> Result<Conflicts> {
+        self.json(self.get(paths::TX_CONFLICTS, &[txid])).await
+    }
+
+    /// Weight and vsize of a transaction spending `inputs` to `outputs`, given by type
+    /// like `p2wpkh` or `p2wsh:2-of-3`
+    pub async fn estimate_size(&self, inputs: &[&str], outputs: &[&str]) -> Result<SizeEstimate> {
+        let request = EstimateSizeRequest { inputs, outputs };
+        self.json(self.post(paths::TX_ESTIMATE_SIZE, &[]).json(&request))
+            .await
+    }
+
+    pub async fn mempool_summary(&self) -> Result<MempoolSummary> {
+        self.json(self.get(paths::MEMPOOL_SUMMARY, &[])).await
+    }
+
+    /// Mempool transactions matching `filter`, which can't be empty
+    pub async fn mempool(&self, filter: &MempoolFilter<'_>) -> Result<FilteredMempool> {
+        self.json(with_filter(self.get(paths::MEMPOOL, &[]), filter))
+            .await
+    }
+
+    /// Txids in the node's mempool, at most `limit` of them
+    pub async fn mempool_txids(&self, limit: Option<usize>) -> Result<Vec<Txid>> {
+        let mut request = self.get(paths::MEMPOOL_TXIDS, &[]);
+        if let Some(limit) = limit {
+            request = request.query(&[("limit", limit)]);
+        }
+        self.json(request).await
+    }
+
+    /// The 10 transactions that entered the mempool last, newest first
+    pub async fn mempool_recent(&self, filter: &MempoolFilter<'_>) -> Result<Vec<RecentTx>> {
+        self.json(with_filter(self.get(paths::MEMPOOL_RECENT, &[]), filter))
+            .await
+    }
+
+    /// Txids added and removed since sequence number `since`, or every mempool
+    /// txid as added without one; a 410 status means the server no longer
+    /// remembers `since` and the caller has to resync
+    pub async fn mempool_diff(&self, since: Option<u64>) -> Result<MempoolDiff> {
+        let mut request = self.get(paths::MEMPOOL_DIFF, &[]);
+        if let Some(since) = since {
+            request = request.query(&[("since", since)]);
+        }
+        self.json(request).await
+    }
+
+    pub async fn mempool_min_fee(&self) -> Result<MinFee> {
+        self.json(self.get(paths::MEMPOOL_MIN_FEE, &[])).await
+    }
+
+    /// Journaled block, reorg and fee events after sequence number `since`, or
+    /// from the oldest retained one without it; a 410 status means the events
+    /// after `since` are no longer retained and the caller has to resync
+    pub async fn events_since(&self, since: Option<u64>) -> Result<EventJournal> {
+        let mut request = self.get(paths::EVENT_JOURNAL, &[]);
+        if let Some(since) = since {
+            request = request.query(&[("since", since)]);
+        }
+        self.json(request).await
+    }
+
+    /// The node's `validateaddress` answer; needs the server in mempool mode
+    pub async fn validate_address(&self, address: &str) -> Result<AddressValidation> {
+        self.json(self.get(paths::VALIDATE_ADDRESS, &[&address]))
+            .await
+    }
+
+    /// Whether `signature` (base64, BIP137 or BIP322 simple) signs `message` for `address`
+    pub async fn verify_message(
+        &self,
+        address: &str,
+        message: &str,
+        signature: &str,
+    ) -> Result<MessageVerification> {
+        let request = VerifyMessageRequest {
+            address,
+            message,
+            signature,
+        };
+        self.json(self.post(paths::VERIFY_MESSAGE, &[]).json(&request))
+            .await
+    }
+
+    pub async fn address(&self, address: &str) -> Result<AddressSummary> {
+        self.json(self.get(paths::ADDRESS, &[&address])).await
+    }
+
+    /// Up to 50 mempool and the 25 newest confirmed transactions
+    pub async fn address_txs(&self, address: &str) -> Result<Vec<Tx>> {
+        self.json(self.get(paths::ADDRESS_TXS, &[&address])).await
+    }
+
+    /// 25 confirmed transactions, newest first, older than `last_seen` when given
+    pub async fn address_chain_txs(
+        &self,
+        address: &str,
+        last_seen: Option<&Txid>,
+    ) -> Result<Vec<Tx>> {
+        let request = match last_seen {
+            Some(txid) => self.get(paths::ADDRESS_CHAIN_TXS_AFTER, &[&address, txid]),
+            None => self.get(paths::ADDRESS_CHAIN_TXS, &[&address]),
+        };
+        self.json(request).await
+    }
+
+    pub async fn address_mempool_txs(&self, address: &str) -> Result<Vec<Tx>> {
+        self.json(self.get(paths::ADDRESS_MEMPOOL_TXS, &[&address]))
+            .await
+    }
+
+    pub async fn address_utxos(&self, address: &str) -> Result<Vec<Utxo>> {
+        self.json(self.get(paths::ADDRESS_UTXO, &[&address])).await
+    }
+
+    /// New transactions of `addresses` and `scripthashes` since `cursor`; without
+    /// a cursor only a starting cursor is returned. A 410 status means the
+    /// cursor expired and the caller has to start over.
+    pub async fn addresses_activity(
+        &self,
+        addresses: &[&str],
+        scripthashes: &[&str],
+        cursor: Option<&str>,
+    ) -> Result<AddressActivity> {
+        let request = ActivityRequest {
+            addresses,
+            scripthashes,
+            cursor,
+        };
+        self.json(self.post(paths::ADDRESSES_ACTIVITY, &[]).json(&request))
+            .await
+    }
+
+    /// Inputs among the unspent outputs of `addresses` for paying `amount` sats at
+    /// `fee_rate` sat/vB, change going back to the first address
+    pub async fn coin_select(
+        &self,
+        addresses: &[&str],
+        amount: u64,
+        fee_rate: f64,
+    ) -> Result<CoinSelection> {
+        let request = CoinSelectRequest {
+            addresses,
+            amount,
+            fee_rate,
+        };
+        self.json(self.post(paths::COIN_SELECT, &[]).json(&request))
+            .await
+    }
+
+    /// `hash` is the hex SHA256 of the script, not reversed
+    pub async fn scripthash(&self, hash: &str) -> Result<AddressSummary> {
+        self.json(self.get(paths::SCRIPTHASH, &[&hash])).await
+    }
+
+    pub async fn scripthash_txs(&self, hash: &str) -> Result<Vec<Tx>> {
+        self.json(self.get(paths::SCRIPTHASH_TXS, &[&hash])).await
+    }
+
+    pub async fn scripthash_chain_txs(
+        &self,
+        hash: &str,
+        last_seen: Option<&Txid>,
+    ) -> Result<Vec<Tx>> {
+        let request = match last_seen {
+            Some(txid) => self.get(paths::SCRIPTHASH_CHAIN_TXS_AFTER, &[&hash, txid]),
+            None => self.get(paths::SCRIPTHASH_CHAIN_TXS, &[&hash]),
+        };
+        self.json(request).await
+    }
+
+    pub async fn scripthash_mempool_txs(&self, hash: &str) -> Result<Vec<Tx>> {
+        self.json(self.get(paths::SCRIPTHASH_MEMPOOL_TXS, &[&hash]))
+            .await
+    }
+
+    pub async fn scripthash_utxos(&self, hash: &str) -> Result<Vec<Utxo>> {
+        self.json(self.get(paths::SCRIPTHASH_UTXO, &[&hash])).await
+    }
+
+    /// Operator-provided address labels, needs the admin token
+    pub async fn labels(&self) -> Result<BTreeMap<String, String>> {
+        self.json(self.admin(self.get(paths::LABELS, &[]))).await
+    }
+
+    /// Settings, features, listeners and node the server runs with, secrets
+    /// redacted, needs the admin token
+    pub async fn admin_config(&self) -> Result<ConfigSummary> {
+        self.json(self.admin(self.get(paths::ADMIN_CONFIG, &[])))
+            .await
+    }
+
+    /// Registers a webhook notified when `outpoint` is spent, dropped after
+    /// `ttl` seconds with one, returning the watch id; needs the admin token
+    pub async fn watch_outpoint(
+        &self,
+        outpoint: &OutPoint,
+        webhook: &str,
+        ttl: Option<u64>,
+    ) -> Result<u64> {
+        let request = WatchRequest {
+            txid: outpoint.txid,
+            vout: outpoint.vout,
+            webhook,
+            ttl,
+        };
+        let created: WatchCreated = self
+            .json(self.admin(self.post(paths::WATCH_OUTPOINT, &[]).json(&request)))
+            .await?;
+        Ok(created.id)
+    }
+
+    /// Outpoint watches, oldest first, needs the admin token
+    pub async fn outpoint_watches(&self) -> Result<Vec<OutpointWatch>> {
+        self.json(self.admin(self.get(paths::WATCH_OUTPOINT, &[])))
+            .await
+    }
+
+    /// Subscribes `url` to `events` (`block`, `reorg` and `address`, the last
+    /// one for `addresses`), dropped after `ttl` seconds with one, returning
+    /// the subscription id; needs the admin token
+    pub async fn subscribe_webhook(
+        &self,
+        url: &str,
+        events: &[&str],
+        addresses: &[&str],
+        ttl: Option<u64>,
+    ) -> Result<u64> {
+        let request = WebhookRequest {
+            url,
+            events,
+            addresses,
+            ttl,
+        };
+        let created: WatchCreated = self
+            .json(self.admin(self.post(paths::WEBHOOKS, &[]).json(&request)))
+            .await?;
+        Ok(created.id)
+    }
+
+    /// Webhook subscriptions, needs the admin token
+    pub async fn webhooks(&self) -> Result<Vec<Webhook>> {
+        self.json(self.admin(self.get(paths::WEBHOOKS, &[]))).await
+    }
+
+    /// Removes a webhook subscription, needs the admin token
+    pub async fn delete_webhook(&self, id: u64) -> Result<()> {
+        self.send(self.admin(self.delete(paths::WEBHOOK, &[&id])))
+            .await?;
+        Ok(())
+    }
+
+    /// Broadcast attempts before sequence number `before`, or the newest
+    /// without it, only those of `txid` with one; needs the admin token
+    pub async fn broadcasts(
+        &self,
+        before: Option<u64>,
+        txid: Option<&Txid>,
+    ) -> Result<BroadcastAuditPage> {
+        let mut request = self.admin(self.get(paths::ADMIN_BROADCASTS, &[]));
+        if let Some(before) = before {
+            request = request.query(&[("before", before)]);
+        }
+        if let Some(txid) = txid {
+            request = request.query(&[("txid", txid.to_string())]);
+        }
+        self.json(request).await
+    }
+
+    /// The node's banned addresses and subnets, needs the admin token
+    pub async fn banned(&self) -> Result<Vec<Ban>> {
+        self.json(self.admin(self.get(paths::ADMIN_BANNED, &[])))
+            .await
+    }
+
+    /// Bans `subnet` for `bantime` seconds (the node's default without one), or
+    /// until `bantime` in seconds since epoch when `absolute`; needs the admin token
+    pub async fn ban(&self, subnet: &str, bantime: Option<u64>, absolute: bool) -> Result<()> {
+        let request = BanRequest {
+            subnet,
+            bantime,
+            absolute,
+        };
+        self.send(self.admin(self.post(paths::ADMIN_BAN, &[]).json(&request)))
+            .await?;
+        Ok(())
+    }
+
+    /// Lifts the ban of `subnet`, needs the admin token
+    pub async fn unban(&self, subnet: &str) -> Result<()> {
+        let request = UnbanRequest { subnet };
+        self.send(self.admin(self.post(paths::ADMIN_UNBAN, &[]).json(&request)))
+            .await?;
+        Ok(())
+    }
+
+    /// Disconnects the peer at `address`, needs the admin token
+    pub async fn disconnect_peer(&self, address: &str) -> Result<()> {
+        let request = DisconnectRequest {
+            address: Some(address),
+            nodeid: None,
+        };
+        self.send(self.admin(self.post(paths::ADMIN_DISCONNECT, &[]).json(&request)))
+            .await?;
+        Ok(())
+    }
+
+    /// Disconnects the peer with the node's id `nodeid`, needs the admin token
+    pub async fn disconnect_peer_id(&self, nodeid: u32) -> Result<()> {
+        let request = DisconnectRequest {
+            address: None,
+            nodeid: Some(nodeid),
+        };
+        self.send(self.admin(self.post(paths::ADMIN_DISCONNECT, &[]).json(&request)))
+            .await?;
+        Ok(())
+    }
+
+    /// Tip and block template of every backend node compared to the primary
+    /// node's, needs the admin token
+    pub async fn backend_consistency(&self) -> Result<BackendConsistency> {
+        self.json(self.admin(self.get(paths::BACKENDS_CONSISTENCY, &[])))
+            .await
+    }
+
+    /// The node's tip compared to that of the configured reference APIs
+    pub async fn consensus_check(&self) -> Result<ConsensusCheck> {
+        self.json(self.get(paths::CONSENSUS_CHECK, &[])).await
+    }
+
+    /// BIP119 template hash of each input of a serialized transaction
+    pub async fn template_hashes(&self, tx_hex: &str) -> Result<TemplateHashes> {
+        let request = TemplateHashRequest { tx: tx_hex };
+        self.json(self.post(paths::CTV_TEMPLATE_HASH, &[]).json(&request))
+            .await
+    }
+
+    /// Inputs matching `template` in blocks `start_height` to `end_height`,
+    /// by default the last 144 blocks
+    pub async fn template_spends(
+        &self,
+        template: &str,
+        start_height: Option<u64>,
+        end_height: Option<u64>,
+    ) -> Result<Vec<TemplateSpend>> {
+        let mut request = self.get(paths::CTV_SPENDS, &[&template]);
+        if let Some(height) = start_height {
+            request = request.query(&[("start_height", height)]);
+        }
+        if let Some(height) = end_height {
+            request = request.query(&[("end_height", height)]);
+        }
+        self.json(request).await
+    }
+
+    /// Mines `n` blocks to `address`, or to a fresh wallet address
+    pub async fn regtest_mine(&self, n: u64, address: Option<&str>) -> Result<Vec<BlockHash>> {
+        let mut request = self.post(paths::REGTEST_MINE, &[&n]);
+        if let Some(address) = address {
+            request = request.query(&[("address", address)]);
+        }
+        self.json(request).await
+    }
+
+    /// Sends `amount_btc` (or the server's default) from the node wallet to `address`
+    pub async fn regtest_fund(&self, address: &str, amount_btc: Option<f64>) -> Result<Txid> {
+        let mut request = self.post(paths::REGTEST_FUND, &[&address]);
+        if let Some(amount) = amount_btc {
+            request = request.query(&[("amount", amount)]);
+        }
+        parse(&self.text(request).await?)
+    }
+}
@@ -0,0 +1,101 @@
+//! API route paths, shared with the server's route table.
+//!
+//! Segments in braces are filled in by the client in order. Renaming a route
+//! here moves both the server and every client method using it.
+
+pub const HEALTH: &str = "/health";
+pub const READYZ: &str = "/readyz";
+pub const HEALTH_DETAILS: &str = "/api/v1/health/details";
+pub const FEATURES: &str = "/api/v1/features";
+pub const NODE_INFO: &str = "/api/v1/node-info";
+
+pub const TIP_HEIGHT: &str = "/api/blocks/tip/height";
+pub const TIP_HASH: &str = "/api/blocks/tip/hash";
+pub const BLOCK_HEIGHT: &str = "/api/block-height/{height}";
+pub const BLOCK: &str = "/api/block/{hash}";
+pub const BLOCKS: &str = "/api/blocks";
+pub const BLOCKS_FROM: &str = "/api/blocks/{start_height}";
+pub const BLOCK_HEADER: &str = "/api/block/{hash}/header";
+pub const BLOCK_STATUS: &str = "/api/block/{hash}/status";
+pub const BLOCK_TXIDS: &str = "/api/block/{hash}/txids";
+pub const BLOCK_TXS: &str = "/api/block/{hash}/txs/{start_index}";
+pub const BLOCK_RAW: &str = "/api/block/{hash}/raw";
+pub const BLOCK_TXID: &str = "/api/block/{hash}/txid/{index}";
+pub const BLOCK_FILTER: &str = "/api/block/{hash}/filter";
+pub const FILTER_HEADERS: &str = "/api/v1/filter-headers/{start_height}";
+pub const V1_BLOCKS: &str = "/api/v1/blocks";
+pub const V1_BLOCKS_FROM: &str = "/api/v1/blocks/{height}";
+pub const V1_BLOCK: &str = "/api/v1/block/{hash}";
+pub const BLOCK_FEE_HISTOGRAM: &str = "/api/v1/block/{id}/fee-histogram";
+pub const DIFFICULTY_ADJUSTMENT: &str = "/api/v1/difficulty-adjustment";
+pub const MINING_HASHRATE: &str = "/api/v1/mining/hashrate/{period}";
+
+pub const FEE_ESTIMATES: &str = "/api/fee-estimates";
+pub const FEES_RECOMMENDED: &str = "/api/v1/fees/recommended";
+pub const MEMPOOL_BLOCKS: &str = "/api/v1/fees/mempool-blocks";
+pub const FEE_ACCURACY: &str = "/api/v1/fees/accuracy";
+
+pub const BLOCK_UTILIZATION: &str = "/api/v1/statistics/block-utilization/{period}";
+pub const SCRIPT_TYPES: &str = "/api/v1/statistics/script-types/{period}";
+pub const PROPAGATION: &str = "/api/v1/statistics/propagation";
+
+pub const BROADCAST: &str = "/api/tx";
+pub const TX: &str = "/api/tx/{txid}";
+pub const TX_STATUS: &str = "/api/tx/{txid}/status";
+pub const TX_HEX: &str = "/api/tx/{txid}/hex";
+pub const TX_RAW: &str = "/api/tx/{txid}/raw";
+pub const TX_MERKLE_PROOF: &str = "/api/tx/{txid}/merkle-proof";
+pub const TX_MERKLEBLOCK_PROOF: &str = "/api/tx/{txid}/merkleblock-proof";
+pub const TX_OUTSPEND: &str = "/api/tx/{txid}/outspend/{vout}";
+pub const TX_OUTSPENDS: &str = "/api/tx/{txid}/outspends";
+pub const TX_CONFLICTS: &str = "/api/v1/tx/{txid}/conflicts";
+pub const TX_ESTIMATE_SIZE: &str = "/api/v1/tx/estimate-size";
+
+pub const MEMPOOL_SUMMARY: &str = "/api/mempool";
+pub const MEMPOOL_TXIDS: &str = "/api/mempool/txids";
+pub const MEMPOOL_RECENT: &str = "/api/mempool/recent";
+pub const MEMPOOL: &str = "/api/v1/mempool";
+pub const MEMPOOL_DIFF: &str = "/api/v1/mempool/diff";
+pub const MEMPOOL_MIN_FEE: &str = "/api/v1/mempool/min-fee";
+
+pub const LIVE: &str = "/ws";
+pub const EVENTS: &str = "/api/events";
+pub const EVENT_JOURNAL: &str = "/api/v1/events";
+pub const GRAPHQL: &str = "/graphql";
+
+pub const ADDRESS: &str = "/api/address/{address}";
+pub const ADDRESS_TXS: &str = "/api/address/{address}/txs";
+pub const ADDRESS_CHAIN_TXS: &str = "/api/address/{address}/txs/chain";
+pub const ADDRESS_CHAIN_TXS_AFTER: &str = "/api/address/{address}/txs/chain/{last_seen_txid}";
+pub const ADDRESS_MEMPOOL_TXS: &str = "/api/address/{address}/txs/mempool";
+pub const ADDRESS_UTXO: &str = "/api/address/{address}/utxo";
+pub const ADDRESSES_ACTIVITY: &str = "/api/v1/addresses/activity";
+pub const COIN_SELECT: &str = "/api/v1/coin-select";
+pub const VALIDATE_ADDRESS: &str = "/api/v1/validate-address/{address}";
+pub const VERIFY_MESSAGE: &str = "/api/v1/verify-message";
+
+pub const SCRIPTHASH: &str = "/api/scripthash/{hash}";
+pub const SCRIPTHASH_TXS: &str = "/api/scripthash/{hash}/txs";
+pub const SCRIPTHASH_CHAIN_TXS: &str = "/api/scripthash/{hash}/txs/chain";
+pub const SCRIPTHASH_CHAIN_TXS_AFTER: &str = "/api/scripthash/{hash}/txs/chain/{last_seen_txid}";
+pub const SCRIPTHASH_MEMPOOL_TXS: &str = "/api/scripthash/{hash}/txs/mempool";
+pub const SCRIPTHASH_UTXO: &str = "/api/scripthash/{hash}/utxo";
+
+pub const LABELS: &str = "/api/v1/labels";
+pub const ADMIN_CONFIG: &str = "/admin/config";
+pub const ADMIN_CONFIG_V1: &str = "/api/v1/admin/config";
+pub const WATCH_OUTPOINT: &str = "/api/v1/watch/outpoint";
+pub const WEBHOOKS: &str = "/api/v1/webhooks";
+pub const WEBHOOK: &str = "/api/v1/webhooks/{id}";
+pub const ADMIN_BROADCASTS: &str = "/api/v1/admin/broadcasts";
+pub const ADMIN_BANNED: &str = "/api/v1/admin/banned";
+pub const ADMIN_BAN: &str = "/api/v1/admin/ban";
+pub const ADMIN_UNBAN: &str = "/api/v1/admin/unban";
+pub const ADMIN_DISCONNECT: &str = "/api/v1/admin/disconnect";
+pub const BACKENDS_CONSISTENCY: &str = "/api/v1/backends/consistency";
+pub const CONSENSUS_CHECK: &str = "/api/v1/consensus-check";
+pub const CTV_TEMPLATE_HASH: &str = "/api/v1/ctv/template-hash";
+pub const CTV_SPENDS: &str = "/api/v1/ctv/{hash}/spends";
+
+pub const REGTEST_MINE: &str = "/regtest/mine/{n}";
+pub const REGTEST_FUND: &str = "/regtest/fund/{address}";
@@ -0,0 +1,953 @@
+//! Response bodies of the API.
+
+use std::collections::BTreeMap;
+
+use bitcoin::hashes::sha256;
+use bitcoin::{BlockHash, FilterHeader, OutPoint, TxMerkleNode, Txid};
+use serde::{Deserialize, Serialize};
+
+/// Transaction in the esplora format
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct Tx {
+    #[cfg_attr(feature = "schema", schema(value_type = String))]
+    pub txid: Txid,
+    pub version: i32,
+    pub locktime: u32,
+    pub vin: Vec<Vin>,
+    pub vout: Vec<Vout>,
+    pub size: usize,
+    pub weight: u64,
+    /// Fee in sats, 0 for coinbase transactions
+    pub fee: u64,
+    pub status: TxStatus,
+    pub locktime_info: LocktimeInfo,
+}
+
+/// Meaning of a transaction's locktime
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct LocktimeInfo {
+    pub height: Option<u32>,
+    /// Median time past in seconds since epoch
+    pub time: Option<u32>,
+    /// Whether an input has a non-final sequence, making the locktime apply
+    pub enforced: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct Vin {
+    #[cfg_attr(feature = "schema", schema(value_type = String))]
+    pub txid: Txid,
+    pub vout: u32,
+    /// Spent output, absent for coinbase inputs
+    pub prevout: Option<Vout>,
+    pub scriptsig: String,
+    pub scriptsig_asm: String,
+    #[serde(default)]
+    pub witness: Vec<String>,
+    pub is_coinbase: bool,
+    pub sequence: u32,
+    pub sequence_info: SequenceInfo,
+}
+
+/// Meaning of an input's sequence
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct SequenceInfo {
+    #[serde(rename = "final")]
+    pub is_final: bool,
+    /// Signals BIP125 replaceability
+    pub rbf: bool,
+    /// BIP68 relative lock in blocks
+    pub relative_blocks: Option<u16>,
+    /// BIP68 relative lock in seconds
+    pub relative_seconds: Option<u32>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct Vout {
+    pub scriptpubkey: String,
+    pub scriptpubkey_asm: String,
+    pub scriptpubkey_type: String,
+    pub scriptpubkey_address: Option<String>,
+    pub value: u64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct TxStatus {
+    pub confirmed: bool,
+    pub block_height: Option<u64>,
+    #[cfg_attr(feature = "schema", schema(value_type = Option<String>))]
+    pub block_hash: Option<BlockHash>,
+    pub block_time: Option<u64>,
+    /// When an unconfirmed transaction was first seen, in seconds since epoch
+    pub first_seen: Option<u64>,
+}
+
+/// Block summary in the esplora format
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct Block {
+    #[cfg_attr(feature = "schema", schema(value_type = String))]
+    pub id: BlockHash,
+    pub height: u64,
+    pub version: i32,
+    pub timestamp: u64,
+    pub tx_count: usize,
+    pub size: usize,
+    pub weight: usize,
+    pub merkle_root: String,
+    #[cfg_attr(feature = "schema", schema(value_type = Option<String>))]
+    pub previousblockhash: Option<BlockHash>,
+    pub mediantime: Option<u64>,
+    pub nonce: u32,
+    pub bits: u32,
+    pub difficulty: f64,
+    /// Local arrival in seconds since epoch, for blocks the server saw arrive
+    pub seen_at: Option<u64>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct BlockStatus {
+    pub in_best_chain: bool,
+    pub height: u64,
+    /// Next block on the active chain, absent for the tip and stale blocks
+    #[cfg_attr(feature = "schema", schema(value_type = Option<String>))]
+    pub next_best: Option<BlockHash>,
+}
+
+/// BIP158 basic filter of a block
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct BlockFilter {
+    /// Serialized filter, hex
+    pub filter: String,
+    #[cfg_attr(feature = "schema", schema(value_type = String))]
+    pub header: FilterHeader,
+}
+
+/// Chain of block filter headers from `start_height`
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct FilterHeaders {
+    pub start_height: u64,
+    /// Header of the filter below `start_height`, all zeros for genesis
+    #[cfg_attr(feature = "schema", schema(value_type = String))]
+    pub previous_header: FilterHeader,
+    #[cfg_attr(feature = "schema", schema(value_type = Vec<String>))]
+    pub headers: Vec<FilterHeader>,
+}
+
+/// Block with fee statistics and mining pool
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct ExtendedBlock {
+    #[cfg_attr(feature = "schema", schema(value_type = String))]
+    pub id: BlockHash,
+    pub height: u64,
+    pub version: i32,
+    pub timestamp: u64,
+    pub bits: u32,
+    pub nonce: u32,
+    pub difficulty: f64,
+    pub merkle_root: String,
+    pub tx_count: usize,
+    pub size: usize,
+    pub weight: usize,
+    #[cfg_attr(feature = "schema", schema(value_type = Option<String>))]
+    pub previousblockhash: Option<BlockHash>,
+    pub mediantime: Option<u64>,
+    pub seen_at: Option<u64>,
+    pub extras: BlockExtras,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct BlockExtras {
+    /// Total fees in sats
+    pub total_fees: u64,
+    /// Median fee rate in sat/vB
+    pub median_fee: u64,
+    /// Min, 10th, 25th, 50th, 75th, 90th percentile and max fee rate in sat/vB
+    pub fee_range: [u64; 7],
+    /// Subsidy plus fees in sats
+    pub reward: u64,
+    pub avg_fee: u64,
+    pub avg_fee_rate: u64,
+    pub coinbase_raw: Option<String>,
+    pub pool: Pool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct Pool {
+    pub id: usize,
+    pub name: String,
+    pub slug: String,
+}
+
+/// Progress through the retarget period, times in milliseconds
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct DifficultyAdjustment {
+    pub progress_percent: f64,
+    /// Estimated change of the next retarget, in percent
+    pub difficulty_change: f64,
+    pub estimated_retarget_date: u64,
+    pub remaining_blocks: u64,
+    pub remaining_time: u64,
+    /// Change of the last retarget, in percent
+    pub previous_retarget: f64,
+    /// Timestamp of the first block of the period, in seconds
+    pub previous_time: u64,
+    pub next_retarget_height: u64,
+    pub time_avg: u64,
+}
+
+/// Hashrate in hashes per second and difficulty over a period
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct Hashrates {
+    pub hashrates: Vec<HashratePoint>,
+    /// Retargets within the period, oldest first
+    pub difficulty: Vec<DifficultyPoint>,
+    pub current_hashrate: f64,
+    pub current_difficulty: f64,
+    pub windows: Vec<WindowHashrate>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct HashratePoint {
+    pub timestamp: u64,
+    pub avg_hashrate: f64,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct DifficultyPoint {
+    pub time: u64,
+    pub height: u64,
+    pub difficulty: f64,
+    /// Ratio to the previous period's difficulty
+    pub adjustment: f64,
+}
+
+/// Current hashrate over the last `blocks` blocks
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct WindowHashrate {
+    pub blocks: u64,
+    pub hashrate: f64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct FeeBucket {
+    /// Inclusive lower bound in sat/vB
+    pub min_fee_rate: f64,
+    /// Exclusive upper bound in sat/vB, absent for the top band
+    pub max_fee_rate: Option<f64>,
+    pub tx_count: usize,
+    pub vsize: u64,
+    /// Fees paid in sats
+    pub fees: u64,
+}
+
+/// Fee rates in sat/vB, as mempool.space recommends them
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct RecommendedFees {
+    pub fastest_fee: f64,
+    pub half_hour_fee: f64,
+    pub hour_fee: f64,
+    pub economy_fee: f64,
+    pub minimum_fee: f64,
+}
+
+/// Block projected from the mempool, in the mempool.space format
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct ProjectedBlock {
+    #[serde(rename = "blockVSize")]
+    pub vsize: u64,
+    #[serde(rename = "nTx")]
+    pub tx_count: usize,
+    /// Sum of fees in sats
+    #[serde(rename = "totalFees")]
+    pub total_fees: u64,
+    /// Median fee rate in sat/vB
+    #[serde(rename = "medianFee")]
+    pub median_fee: f64,
+    /// Min, 10th, 25th, 50th, 75th, 90th percentile and max fee rate in sat/vB
+    #[serde(rename = "feeRange")]
+    pub fee_range: [f64; 7],
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct FeeAccuracy {
+    /// `economical` or `conservative`
+    pub mode: String,
+    pub target: u16,
+    pub samples: u64,
+    pub hit_rate: f64,
+    pub mean_error_sat_vb: f64,
+    pub mean_abs_error_sat_vb: f64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct BlockUtilization {
+    pub height: u64,
+    pub timestamp: u64,
+    pub weight: u64,
+    pub weight_utilization: f64,
+    pub segwit_share: f64,
+    pub taproot_share: f64,
+    pub large_witness_txs: u64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct DailyScriptTypes {
+    /// Start of the UTC day
+    pub timestamp: u64,
+    pub blocks: u64,
+    /// Created outputs by script type
+    pub outputs: BTreeMap<String, u64>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct Propagation {
+    pub blocks: usize,
+    pub mean_delay_seconds: Option<f64>,
+    pub median_delay_seconds: Option<i64>,
+    pub p90_delay_seconds: Option<i64>,
+    /// Latest blocks first
+    pub recent: Vec<Sighting>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct Sighting {
+    pub height: u64,
+    #[cfg_attr(feature = "schema", schema(value_type = String))]
+    pub hash: BlockHash,
+    pub timestamp: u64,
+    pub seen_at: u64,
+    pub delay: i64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct HealthDetails {
+    /// `ok`, `degraded` or `down`
+    pub status: String,
+    pub components: Vec<ComponentStatus>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct ComponentStatus {
+    pub name: String,
+    pub hard: bool,
+    /// `ok`, `starting`, `stale` or `failing`
+    pub status: String,
+    pub error: Option<String>,
+    pub last_success: Option<u64>,
+}
+
+/// The node behind the server and how its transactions can be looked up
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct NodeInfo {
+    /// Bitcoin Core version, like 270100 for 27.1.0
+    pub version: u64,
+    pub subversion: String,
+    pub chain: String,
+    pub pruned: bool,
+    pub txindex: bool,
+    pub block_filter_index: bool,
+    /// `txindex` when any transaction is found by txid alone, `block-hint` when
+    /// confirmed ones need the block, see [`Client::tx_in_block`](crate::Client::tx_in_block)
+    pub tx_lookup: String,
+}
+
+/// What a deployment serves, for feature detection at runtime
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct Features {
+    /// Version of the server
+    pub version: String,
+    /// Whether the request carried the admin token
+    pub admin: bool,
+    /// Unset when requests aren't throttled
+    pub rate_limit: Option<RateLimit>,
+    pub endpoints: Vec<Endpoint>,
+}
+
+/// Request rate the server accepts right now, across all clients
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct RateLimit {
+    pub requests_per_second: f64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct Endpoint {
+    pub method: String,
+    /// Route path with `{name}` placeholders, as in [`crate::paths`]
+    pub path: String,
+    /// `N` of `/api/vN/` paths, unset for the esplora-compatible routes
+    pub version: Option<u32>,
+    /// Most items of a list per response
+    pub page_size: Option<usize>,
+    pub timeout_ms: u64,
+    /// Retries of idempotent requests failing with a server error
+    pub retries: u32,
+    pub max_response_bytes: Option<usize>,
+    pub admin: bool,
+}
+
+/// Electrum-format merkle branch of a confirmed transaction
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct MerkleProof {
+    pub block_height: u64,
+    /// Sibling hashes from the transaction up to the root
+    #[cfg_attr(feature = "schema", schema(value_type = Vec<String>))]
+    pub merkle: Vec<TxMerkleNode>,
+    pub pos: usize,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct Outspend {
+    pub spent: bool,
+    #[cfg_attr(feature = "schema", schema(value_type = Option<String>))]
+    pub txid: Option<Txid>,
+    pub vin: Option<u32>,
+    pub status: Option<TxStatus>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct Conflicts {
+    #[cfg_attr(feature = "schema", schema(value_type = String))]
+    pub txid: Txid,
+    pub conflicts: Vec<Conflict>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct Conflict {
+    #[cfg_attr(feature = "schema", schema(value_type = String))]
+    pub txid: Txid,
+    pub in_mempool: bool,
+    #[cfg_attr(feature = "schema", schema(value_type = Vec<String>))]
+    pub outpoints: Vec<OutPoint>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct SizeEstimate {
+    pub weight: u64,
+    pub vsize: u64,
+    /// Weight each input adds, outpoint and sequence included
+    pub input_weights: Vec<u64>,
+    pub output_weights: Vec<u64>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct MempoolSummary {
+    pub count: usize,
+    pub vsize: u64,
+    /// Sum of fees in sats
+    pub total_fee: u64,
+    /// `(fee_rate, vsize)` bands from the highest fee rate down
+    pub fee_histogram: Vec<(f64, u64)>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct RecentTx {
+    #[cfg_attr(feature = "schema", schema(value_type = String))]
+    pub txid: Txid,
+    pub fee: u64,
+    pub vsize: u64,
+    /// Sum of output values in sats
+    pub value: u64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct MempoolDiff {
+    /// Sequence number to pass as `since` next time
+    pub seq: u64,
+    #[cfg_attr(feature = "schema", schema(value_type = Vec<String>))]
+    pub added: Vec<Txid>,
+    #[cfg_attr(feature = "schema", schema(value_type = Vec<String>))]
+    pub removed: Vec<Txid>,
+}
+
+/// Fee rates in sat/vB below which the node rejects transactions
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct MinFee {
+    pub mempool_min_fee: f64,
+    pub min_relay_tx_fee: f64,
+    /// Whether a full mempool raised `mempool_min_fee` above `min_relay_tx_fee`
+    pub purging: bool,
+    /// Oldest first
+    pub history: Vec<MinFeeSample>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct MinFeeSample {
+    pub timestamp: u64,
+    pub mempool_min_fee: f64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct FilteredMempool {
+    pub count: usize,
+    pub transactions: Vec<MempoolTx>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct MempoolTx {
+    #[cfg_attr(feature = "schema", schema(value_type = String))]
+    pub txid: Txid,
+    pub fee: u64,
+    pub vsize: u64,
+    pub weight: u64,
+    pub witness_bytes: u64,
+    pub first_seen: u64,
+}
+
+/// Totals of an address or script, `address` or `scripthash` set depending on the route
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct AddressSummary {
+    pub address: Option<String>,
+    pub scripthash: Option<String>,
+    pub chain_stats: AddressStats,
+    pub mempool_stats: AddressStats,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct AddressStats {
+    pub funded_txo_count: u64,
+    pub funded_txo_sum: u64,
+    pub spent_txo_count: u64,
+    pub spent_txo_sum: u64,
+    pub tx_count: u64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct Utxo {
+    #[cfg_attr(feature = "schema", schema(value_type = String))]
+    pub txid: Txid,
+    pub vout: u32,
+    pub status: TxStatus,
+    pub value: u64,
+    /// Set on coinbase outputs that can't be spent in the next block
+    #[serde(default)]
+    pub immature: bool,
+    /// First block that may spend an immature coinbase output
+    pub spendable_height: Option<u64>,
+}
+
+/// Settings, features, listeners and node the server runs with
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct ConfigSummary {
+    pub version: String,
+    /// Cargo features the server was built with
+    pub features: Vec<String>,
+    /// By environment variable, or by flag for settings without one, with
+    /// secrets shown as `[redacted]`
+    pub settings: BTreeMap<String, Setting>,
+    pub listeners: Listeners,
+    /// Unset when the node can't be reached
+    pub node: Option<NodeInfo>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct Setting {
+    /// Comma-separated for settings taking several values
+    pub value: String,
+    /// `cli`, `env` or `default`
+    pub source: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct Listeners {
+    pub http: Vec<ListenerSummary>,
+    pub electrum: Vec<ListenerSummary>,
+    pub grpc: Option<String>,
+    pub prometheus: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct ListenerSummary {
+    /// `ip:port`
+    pub addr: String,
+    pub tls: bool,
+}
+
+/// New transactions reported by the batch activity endpoint
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct AddressActivity {
+    /// Cursor to pass to the next call
+    pub cursor: String,
+    /// Confirmed transactions first, oldest first
+    pub activity: Vec<ActivityEntry>,
+}
+
+/// A transaction of an address, or of a script by scripthash
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct ActivityEntry {
+    pub address: Option<String>,
+    pub scripthash: Option<String>,
+    #[cfg_attr(feature = "schema", schema(value_type = String))]
+    pub txid: Txid,
+    pub status: TxStatus,
+}
+
+/// Inputs suggested by the coin selection endpoint
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct CoinSelection {
+    pub inputs: Vec<SelectedInput>,
+    /// Change in sats, 0 without a change output
+    pub change: u64,
+    pub fee: u64,
+    pub vsize: u64,
+    /// `bnb` or `largest-first`
+    pub algorithm: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct SelectedInput {
+    #[cfg_attr(feature = "schema", schema(value_type = String))]
+    pub txid: Txid,
+    pub vout: u32,
+    pub value: u64,
+}
+
+/// Address validation, as the node's `validateaddress` RPC answers it
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct AddressValidation {
+    pub isvalid: bool,
+    pub address: Option<String>,
+    #[serde(rename = "scriptPubKey")]
+    pub script_pub_key: Option<String>,
+    pub isscript: Option<bool>,
+    pub iswitness: Option<bool>,
+    pub witness_version: Option<u8>,
+    pub witness_program: Option<String>,
+    /// Why an invalid address was rejected
+    pub error: Option<String>,
+}
+
+/// Outcome of checking a message signature
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct MessageVerification {
+    pub valid: bool,
+    /// `bip137` or `bip322`, the format the signature was read as
+    pub format: String,
+}
+
+/// A page of the broadcast audit log
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct BroadcastAuditPage {
+    /// Newest first, at most 100; ask again before the last one for more
+    pub records: Vec<BroadcastRecord>,
+}
+
+/// One attempt to broadcast a transaction
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct BroadcastRecord {
+    pub seq: u64,
+    /// When the node answered, seconds since epoch
+    pub time: u64,
+    /// `http`, `electrum` or `grpc`
+    pub interface: String,
+    /// Address of the client, when known
+    pub client: Option<String>,
+    /// Unset when the submitted hex isn't a transaction
+    #[cfg_attr(feature = "schema", schema(value_type = Option<String>))]
+    pub txid: Option<Txid>,
+    /// Bytes of the submitted transaction
+    pub size: usize,
+    /// sat/vB of an accepted transaction
+    pub fee_rate: Option<f64>,
+    /// `accepted`, `rejected` or `failed`
+    pub outcome: String,
+    /// Rejection reason, as in the broadcast error
+    pub reason: Option<String>,
+    /// The node's answer: the txid, or its error message
+    pub response: String,
+}
+
+/// A ban of the node, times in seconds since epoch
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct Ban {
+    /// Banned address or subnet
+    pub address: String,
+    pub banned_until: u64,
+    pub ban_created: u64,
+}
+
+/// Latest comparison of the backend nodes to the primary node
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct BackendConsistency {
+    /// Seconds since epoch, unset until the first check
+    pub checked_at: Option<u64>,
+    /// Whether every node is on the primary node's chain within the allowed lag
+    pub consistent: bool,
+    /// The primary node first
+    pub nodes: Vec<BackendNode>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct BackendNode {
+    /// `primary` or the node's `host:port`
+    pub name: String,
+    /// `primary`, `synced`, `lagging`, `ahead`, `forked` or `unreachable`
+    pub status: String,
+    pub height: Option<u64>,
+    #[cfg_attr(feature = "schema", schema(value_type = Option<String>))]
+    pub tip: Option<BlockHash>,
+    /// Blocks behind the primary node, negative when ahead
+    pub lag: Option<i64>,
+    /// Total fees of the node's block template in sats, unset when it can't build one
+    pub template_fees: Option<u64>,
+    pub template_tx_count: Option<u64>,
+    /// Template fees minus the primary node's template fees, in sats
+    pub template_fee_delta: Option<i64>,
+    pub error: Option<String>,
+}
+
+/// Latest comparison of the node's tip to the reference APIs
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct ConsensusCheck {
+    /// Seconds since epoch, unset until the first check
+    pub checked_at: Option<u64>,
+    /// Whether no reference has diverged from the node's chain
+    pub agrees: bool,
+    /// The node's tip when it was compared
+    pub height: Option<u64>,
+    #[cfg_attr(feature = "schema", schema(value_type = Option<String>))]
+    pub tip: Option<BlockHash>,
+    pub references: Vec<ReferenceCheck>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct ReferenceCheck {
+    /// The reference API's `host[:port]`
+    pub name: String,
+    /// `agrees`, `behind`, `ahead`, `diverged` or `unreachable`
+    pub status: String,
+    pub height: Option<u64>,
+    #[cfg_attr(feature = "schema", schema(value_type = Option<String>))]
+    pub tip: Option<BlockHash>,
+    /// Blocks the node is behind the reference, negative when ahead
+    pub lag: Option<i64>,
+    pub error: Option<String>,
+}
+
+/// Journaled `/api/events` events after a sequence number
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct EventJournal {
+    /// Newest journaled event, 0 before the first
+    pub last_seq: u64,
+    /// Oldest first, at most 500; ask again after the last one for more
+    pub events: Vec<JournalEvent>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct JournalEvent {
+    pub seq: u64,
+    /// `block`, `reorg` or `fees`
+    pub event: String,
+    /// When the event was journaled, seconds since epoch
+    pub time: u64,
+    /// What `/api/events` sent as the event's data
+    #[cfg_attr(feature = "schema", schema(value_type = Object))]
+    pub data: serde_json::Value,
+}
+
+/// BIP119 default template hashes of a transaction
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct TemplateHashes {
+    #[cfg_attr(feature = "schema", schema(value_type = String))]
+    pub txid: Txid,
+    /// Hash committed to by each input, in input order and script byte order
+    #[cfg_attr(feature = "schema", schema(value_type = Vec<String>))]
+    pub template_hashes: Vec<sha256::Hash>,
+}
+
+/// A confirmed input whose transaction matches a template hash
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct TemplateSpend {
+    #[cfg_attr(feature = "schema", schema(value_type = String))]
+    pub txid: Txid,
+    pub vin: u32,
+    pub height: u64,
+    #[cfg_attr(feature = "schema", schema(value_type = String))]
+    pub block_hash: BlockHash,
+}
+
+/// A webhook subscription
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct Webhook {
+    pub id: u64,
+    pub url: String,
+    /// `block`, `reorg` and `address`
+    pub events: Vec<String>,
+    pub addresses: Vec<String>,
+    /// Set in the server's configuration rather than through the API
+    pub configured: bool,
+    /// Seconds since epoch when the subscription is dropped
+    pub expires_at: Option<u64>,
+}
+
+/// A webhook notified when an outpoint is spent
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(utoipa::ToSchema))]
+pub struct OutpointWatch {
+    pub id: u64,
+    #[cfg_attr(feature = "schema", schema(value_type = String))]
+    pub txid: Txid,
+    pub vout: u32,
+    pub webhook: String,
+    /// Seconds since epoch when the watch is dropped even if the outpoint
+    /// wasn't spent
+    pub expires_at: Option<u64>,
+    /// The mempool spend was already notified
+    pub mempool_notified: bool,
+}
+
+/// Categories and fee rate band to filter mempool transactions by
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MempoolFilter<'a> {
+    /// `large-witness`, `consolidation`, `op-return` and `taproot`, every one
+    /// of which a transaction has to be in
+    pub categories: &'a [&'a str],
+    /// sat/vB, inclusive
+    pub min_fee_rate: Option<f64>,
+    /// sat/vB, inclusive
+    pub max_fee_rate: Option<f64>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct ActivityRequest<'a> {
+    pub addresses: &'a [&'a str],
+    pub scripthashes: &'a [&'a str],
+    pub cursor: Option<&'a str>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct CoinSelectRequest<'a> {
+    pub addresses: &'a [&'a str],
+    pub amount: u64,
+    pub fee_rate: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct TemplateHashRequest<'a> {
+    pub tx: &'a str,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct VerifyMessageRequest<'a> {
+    pub address: &'a str,
+    pub message: &'a str,
+    pub signature: &'a str,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct EstimateSizeRequest<'a> {
+    pub inputs: &'a [&'a str],
+    pub outputs: &'a [&'a str],
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct WatchRequest<'a> {
+    pub txid: Txid,
+    pub vout: u32,
+    pub webhook: &'a str,
+    pub ttl: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct WebhookRequest<'a> {
+    pub url: &'a str,
+    pub events: &'a [&'a str],
+    pub addresses: &'a [&'a str],
+    pub ttl: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct BanRequest<'a> {
+    pub subnet: &'a str,
+    pub bantime: Option<u64>,
+    pub absolute: bool,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct UnbanRequest<'a> {
+    pub subnet: &'a str,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct DisconnectRequest<'a> {
+    pub address: Option<&'a str>,
+    pub nodeid: Option<u32>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct WatchCreated {
+    pub id: u64,
+}
@@ -0,0 +1,101 @@
+//! Golden files of the server's response bodies.
+//!
+//! Every file in `tests/golden` is the body one endpoint returns, pretty-printed
+//! but otherwise byte for byte what the server's tests serialize, so field
+//! order and number formatting are the server's. Each must parse into the
+//! client type of its endpoint, so a change to the server's output or to the
+//! client's types that breaks existing parsers fails here. The files are
+//! written by the server's tests: after changing the shape of a response, run
+//! `UPDATE_GOLDEN=1 cargo test` at the workspace root in the same change.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::PathBuf;
+
+use bitcoin::Txid;
+use minipool_client::types::*;
+use serde::de::DeserializeOwned;
+
+fn golden_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+fn parse<T: DeserializeOwned>(name: &str) {
+    let path = golden_dir().join(format!("{}.json", name));
+    let body = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("failed to read {}: {}", path.display(), e));
+    if let Err(e) = serde_json::from_str::<T>(&body) {
+        panic!("{} no longer parses: {}", path.display(), e);
+    }
+}
+
+macro_rules! golden {
+    ($($name:ident: $ty:ty,)*) => {
+        $(
+            #[test]
+            fn $name() {
+                parse::<$ty>(stringify!($name));
+            }
+        )*
+
+        #[test]
+        fn every_golden_file_is_checked() {
+            let checked: BTreeSet<String> =
+                [$(stringify!($name)),*].iter().map(|name| format!("{}.json", name)).collect();
+            let files: BTreeSet<String> = fs::read_dir(golden_dir())
+                .expect("golden directory is readable")
+                .map(|entry| entry.expect("golden entry is readable").file_name())
+                .map(|name| name.to_string_lossy().into_owned())
+                .collect();
+            assert_eq!(files, checked);
+        }
+    };
+}
+
+golden! {
+    health_details: HealthDetails,
+    features: Features,
+    node_info: NodeInfo,
+    block: Block,
+    block_status: BlockStatus,
+    block_txids: Vec<Txid>,
+    block_filter: BlockFilter,
+    filter_headers: FilterHeaders,
+    v1_blocks: Vec<ExtendedBlock>,
+    block_fee_histogram: Vec<FeeBucket>,
+    difficulty_adjustment: DifficultyAdjustment,
+    hashrate: Hashrates,
+    fee_estimates: BTreeMap<String, f64>,
+    recommended_fees: RecommendedFees,
+    mempool_blocks: Vec<ProjectedBlock>,
+    fee_accuracy: Vec<FeeAccuracy>,
+    block_utilization: Vec<BlockUtilization>,
+    script_types: Vec<DailyScriptTypes>,
+    propagation: Propagation,
+    tx: Tx,
+    tx_status: TxStatus,
+    tx_merkle_proof: MerkleProof,
+    tx_outspends: Vec<Outspend>,
+    tx_conflicts: Conflicts,
+    estimate_size: SizeEstimate,
+    mempool_summary: MempoolSummary,
+    mempool: FilteredMempool,
+    event_journal: EventJournal,
+    mempool_txids: Vec<Txid>,
+    mempool_recent: Vec<RecentTx>,
+    mempool_diff: MempoolDiff,
+    mempool_min_fee: MinFee,
+    validate_address: AddressValidation,
+    verify_message: MessageVerification,
+    address: AddressSummary,
+    address_utxos: Vec<Utxo>,
+    addresses_activity: AddressActivity,
+    coin_select: CoinSelection,
+    outpoint_watches: Vec<OutpointWatch>,
+    webhooks: Vec<Webhook>,
+    broadcasts: BroadcastAuditPage,
+    backend_consistency: BackendConsistency,
+    consensus_check: ConsensusCheck,
+    ctv_template_hash: TemplateHashes,
+    ctv_spends: Vec<TemplateSpend>,
+}
@@ -0,0 +1,1378 @@
+//! Address index behind the esplora address endpoints.
+//!
+//! Every block from the configured start height on is scanned, and each
+//! transaction is recorded under the scripts it funds or spends, keyed by the
+//! SHA256 of the script. Running totals per script are kept next to the
+//! history so the address summary doesn't need a scan. Both live in the
+//! configured store under the data directory. Spent outputs are read from the node's
+//! undo data (`getblock` verbosity 3, Bitcoin Core 23 or later), so no
+//! `txindex` is needed to index. Unconfirmed activity comes from the mempool
+//! mirror.
+
+use std::cmp::Reverse;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::future::Future;
+use std::path::Path as FsPath;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use anyhow::Context;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use bitcoincore_rpc::bitcoin::hashes::{sha256, Hash};
+use bitcoincore_rpc::bitcoin::hex::FromHex;
+use bitcoincore_rpc::bitcoin::{Address, Amount, BlockHash, OutPoint, Script, ScriptBuf, Txid};
+use bitcoincore_rpc::json::ScanTxOutRequest;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::chain::ChainWatcher;
+use crate::health::{Health, Severity};
+use crate::migrations::{self, Migration, META, RECORD_START_HEIGHT};
+use crate::rpc::Rpc;
+use crate::storage::{self, decode_height, height_key, Backend, Batch, Store, Table};
+use crate::tx::{block_status, esplora_tx, esplora_tx_with_prevouts, EsploraStatus, EsploraTx};
+use crate::AppState;
+
+const HEALTH_COMPONENT: &str = "address_index";
+
+/// Script hash, big-endian height and big-endian position in the block → txid
+const HISTORY: Table = "history";
+
+/// Script hash → confirmed totals of the script
+const STATS: Table = "stats";
+
+/// Script hash, txid and big-endian vout of an unspent output → big-endian value and height
+const UTXOS: Table = "utxos";
+
+/// Big-endian height → hash of every indexed block, used to detect and undo reorgs
+const BLOCKS: Table = "blocks";
+
+/// Schema changes since the first release, see [`migrations`]
+const MIGRATIONS: &[Migration] = &[RECORD_START_HEIGHT];
+
+/// Indexed blocks between progress logs during catch-up
+const PROGRESS_INTERVAL: u64 = 1000;
+
+/// Confirmed transactions per page of address history
+pub const CHAIN_TXS_PER_PAGE: usize = 25;
+
+/// Most mempool transactions listed for an address
+pub const MEMPOOL_TXS_LIMIT: usize = 50;
+
+/// Most addresses and scripthashes in one activity request
+const ACTIVITY_SUBJECTS_LIMIT: usize = 100;
+
+/// Blocks a coinbase output waits before it can be spent, from Bitcoin Core's consensus/consensus.h
+const COINBASE_MATURITY: u64 = 100;
+
+/// Blocks an activity cursor may lag behind the index tip before it expires
+const ACTIVITY_CURSOR_DEPTH: u64 = 1008;
+
+pub type ScriptHash = [u8; 32];
+
+/// Key of a script in the index and the mempool mirror
+pub fn script_hash(script: &Script) -> ScriptHash {
+    sha256::Hash::hash(script.as_bytes()).to_byte_array()
+}
+
+fn history_key(script: &ScriptHash, height: u64, position: u32) -> [u8; 44] {
+    let mut key = [0; 44];
+    key[..32].copy_from_slice(script);
+    key[32..40].copy_from_slice(&height.to_be_bytes());
+    key[40..].copy_from_slice(&position.to_be_bytes());
+    key
+}
+
+fn utxo_key(script: &ScriptHash, outpoint: &OutPoint) -> [u8; 68] {
+    let mut key = [0; 68];
+    key[..32].copy_from_slice(script);
+    key[32..64].copy_from_slice(outpoint.txid.as_byte_array());
+    key[64..].copy_from_slice(&outpoint.vout.to_be_bytes());
+    key
+}
+
+fn encode_utxo(value: Amount, height: u64) -> [u8; 16] {
+    let mut encoded = [0; 16];
+    encoded[..8].copy_from_slice(&value.to_sat().to_be_bytes());
+    encoded[8..].copy_from_slice(&height.to_be_bytes());
+    encoded
+}
+
+#[derive(Clone, Copy, Default, Serialize)]
+pub struct AddressStats {
+    funded_txo_count: u64,
+    funded_txo_sum: u64,
+    spent_txo_count: u64,
+    spent_txo_sum: u64,
+    tx_count: u64,
+}
+
+impl AddressStats {
+    fn encode(&self) -> [u8; 40] {
+        let mut value = [0; 40];
+        let fields = [
+            self.funded_txo_count,
+            self.funded_txo_sum,
+            self.spent_txo_count,
+            self.spent_txo_sum,
+            self.tx_count,
+        ];
+        for (chunk, field) in value.chunks_exact_mut(8).zip(fields) {
+            chunk.copy_from_slice(&field.to_be_bytes());
+        }
+        value
+    }
+
+    fn decode(value: &[u8]) -> anyhow::Result<Self> {
+        anyhow::ensure!(value.len() == 40, "Corrupt address index entry");
+        let field = |i: usize| u64::from_be_bytes(value[i * 8..(i + 1) * 8].try_into().unwrap());
+        Ok(Self {
+            funded_txo_count: field(0),
+            funded_txo_sum: field(1),
+            spent_txo_count: field(2),
+            spent_txo_sum: field(3),
+            tx_count: field(4),
+        })
+    }
+
+    fn apply(&mut self, delta: &Self, connect: bool) {
+        let pairs = [
+            (&mut self.funded_txo_count, delta.funded_txo_count),
+            (&mut self.funded_txo_sum, delta.funded_txo_sum),
+            (&mut self.spent_txo_count, delta.spent_txo_count),
+            (&mut self.spent_txo_sum, delta.spent_txo_sum),
+            (&mut self.tx_count, delta.tx_count),
+        ];
+        for (total, change) in pairs {
+            *total = if connect {
+                *total + change
+            } else {
+                total.saturating_sub(change)
+            };
+        }
+    }
+}
+
+/// Block as returned by `getblock` with verbosity 3
+#[derive(Deserialize)]
+struct VerboseBlock {
+    tx: Vec<VerboseTx>,
+}
+
+#[derive(Deserialize)]
+struct VerboseTx {
+    txid: Txid,
+    vin: Vec<VerboseInput>,
+    vout: Vec<VerboseOutput>,
+}
+
+#[derive(Deserialize)]
+struct VerboseInput {
+    coinbase: Option<String>,
+    txid: Option<Txid>,
+    vout: Option<u32>,
+    prevout: Option<VerboseOutput>,
+}
+
+#[derive(Deserialize)]
+struct VerboseOutput {
+    #[serde(with = "bitcoincore_rpc::bitcoin::amount::serde::as_btc")]
+    value: Amount,
+    #[serde(rename = "scriptPubKey")]
+    script_pub_key: VerboseScript,
+    /// Height of the block that created a spent output
+    height: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct VerboseScript {
+    hex: String,
+}
+
+impl VerboseOutput {
+    fn script_hash(&self) -> anyhow::Result<ScriptHash> {
+        let script = ScriptBuf::from_hex(&self.script_pub_key.hex)
+            .context("Node returned an invalid script")?;
+        Ok(script_hash(&script))
+    }
+}
+
+/// Scripts each transaction of a block funds or spends, in block order and
+/// leaving out coinbase inputs; needs Bitcoin Core 23 or later for spent outputs
+pub async fn block_scripts(
+    rpc: &Rpc,
+    hash: &BlockHash,
+) -> anyhow::Result<Vec<(Txid, HashSet<ScriptHash>)>> {
+    let block: VerboseBlock = rpc.call("getblock", &[json!(hash), json!(3)]).await?;
+    block
+        .tx
+        .iter()
+        .map(|tx| {
+            let mut scripts = HashSet::new();
+            for input in tx.vin.iter().filter(|input| input.coinbase.is_none()) {
+                let prevout = input.prevout.as_ref().context(
+                    "Node didn't return spent outputs, address activity needs Bitcoin Core 23 or later",
+                )?;
+                scripts.insert(prevout.script_hash()?);
+            }
+            for output in &tx.vout {
+                scripts.insert(output.script_hash()?);
+            }
+            Ok((tx.txid, scripts))
+        })
+        .collect()
+}
+
+enum UtxoChange {
+    Created([u8; 68], [u8; 16]),
+    Spent([u8; 68], [u8; 16]),
+}
+
+/// Index changes made by one block
+#[derive(Default)]
+struct BlockChanges {
+    history: Vec<([u8; 44], Txid)>,
+    stats: HashMap<ScriptHash, AddressStats>,
+    /// In block order, so outputs spent in their own block are created first
+    utxos: Vec<UtxoChange>,
+}
+
+fn block_changes(height: u64, block: &VerboseBlock) -> anyhow::Result<BlockChanges> {
+    let mut changes = BlockChanges::default();
+    for (position, tx) in block.tx.iter().enumerate() {
+        let mut touched = HashSet::new();
+        for input in tx.vin.iter().filter(|input| input.coinbase.is_none()) {
+            let (Some(prevout), Some(txid), Some(vout), Some(prevout_height)) = (
+                &input.prevout,
+                input.txid,
+                input.vout,
+                input.prevout.as_ref().and_then(|prevout| prevout.height),
+            ) else {
+                anyhow::bail!(
+                    "Node didn't return spent outputs, the address index needs Bitcoin Core 23 or later"
+                );
+            };
+            let script = prevout.script_hash()?;
+            let stats = changes.stats.entry(script).or_default();
+            stats.spent_txo_count += 1;
+            stats.spent_txo_sum += prevout.value.to_sat();
+            touched.insert(script);
+            changes.utxos.push(UtxoChange::Spent(
+                utxo_key(&script, &OutPoint::new(txid, vout)),
+                encode_utxo(prevout.value, prevout_height),
+            ));
+        }
+        for (vout, output) in tx.vout.iter().enumerate() {
+            let script = output.script_hash()?;
+            let stats = changes.stats.entry(script).or_default();
+            stats.funded_txo_count += 1;
+            stats.funded_txo_sum += output.value.to_sat();
+            touched.insert(script);
+            changes.utxos.push(UtxoChange::Created(
+                utxo_key(&script, &OutPoint::new(tx.txid, vout as u32)),
+                encode_utxo(output.value, height),
+            ));
+        }
+        for script in touched {
+            changes.stats.entry(script).or_default().tx_count += 1;
+            changes
+                .history
+                .push((history_key(&script, height, position as u32), tx.txid));
+        }
+    }
+    Ok(changes)
+}
+
+pub struct AddressIndex {
+    store: Arc<dyn Store>,
+    start_height: u64,
+    /// Set once the initial catch-up reached the tip
+    synced: AtomicBool,
+}
+
+impl AddressIndex {
+    pub fn open(
+        backend: Backend,
+        path: &FsPath,
+        start_height: u64,
+        migrate: bool,
+    ) -> anyhow::Result<Self> {
+        let store = backend
+            .open(path, &[HISTORY, STATS, UTXOS, BLOCKS, META])
+            .with_context(|| format!("Failed to open address index at {}", path.display()))?;
+        migrations::migrate(
+            store.as_ref(),
+            "address index",
+            &[HISTORY, STATS, UTXOS, BLOCKS],
+            MIGRATIONS,
+            migrate,
+        )?;
+        let start_height = migrations::start_height(store.as_ref(), "address index", start_height)?;
+        Ok(Self {
+            store,
+            start_height,
+            synced: AtomicBool::new(false),
+        })
+    }
+
+    pub async fn run(
+        self: Arc<Self>,
+        rpc: Arc<Rpc>,
+        watcher: Arc<ChainWatcher>,
+        health: Arc<Health>,
+    ) {
+        health.register(HEALTH_COMPONENT, Severity::Soft, None);
+        let mut blocks = watcher.subscribe();
+        loop {
+            match self.sync(&rpc).await {
+                Ok(()) => {
+                    if !self.synced.swap(true, Ordering::Relaxed) {
+                        info!("Address index caught up with the chain tip");
+                    }
+                    health.success(HEALTH_COMPONENT);
+                }
+                Err(e) => {
+                    warn!("Failed to update address index: {:#}", e);
+                    health.failure(HEALTH_COMPONENT, &e);
+                }
+            }
+            // Every sync catches up to the tip, so lagging behind the watcher is harmless
+            match blocks.recv().await {
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => return,
+            }
+        }
+    }
+
+    /// Last indexed block
+    pub fn tip(&self) -> anyhow::Result<Option<(u64, BlockHash)>> {
+        let Some((height, hash)) = self.store.last(BLOCKS)? else {
+            return Ok(None);
+        };
+        let tip = (decode_height(&height)?, BlockHash::from_slice(&hash)?);
+        Ok(Some(tip))
+    }
+
+    /// Catches up with the node's best chain. Blocks come over RPC on the
+    /// async runtime, index reads and writes run on the blocking pool.
+    async fn sync(self: &Arc<Self>, rpc: &Rpc) -> anyhow::Result<()> {
+        // Undo indexed blocks that are no longer part of the best chain
+        while let Some((height, hash)) = self.blocking(|index| index.tip()).await? {
+            if rpc.get_block_hash(height).await.ok() == Some(hash) {
+                break;
+            }
+            let block: VerboseBlock = rpc.call("getblock", &[json!(hash), json!(3)]).await?;
+            self.blocking(move |index| {
+                index.apply(height, &hash, &block_changes(height, &block)?, false, true)
+            })
+            .await?;
+            info!(
+                "Address index disconnected block {} at height {}",
+                hash, height
+            );
+        }
+
+        let from = match self.blocking(|index| index.tip()).await? {
+            Some((height, _)) => height + 1,
+            None => self.start_height,
+        };
+        let tip = rpc.get_block_count().await?;
+        for height in from..=tip {
+            let hash = rpc.get_block_hash(height).await?;
+            let block: VerboseBlock = rpc.call("getblock", &[json!(hash), json!(3)]).await?;
+            // Only the last block of a catch-up pays for an fsync
+            self.blocking(move |index| {
+                index.apply(
+                    height,
+                    &hash,
+                    &block_changes(height, &block)?,
+                    true,
+                    height == tip,
+                )
+            })
+            .await?;
+            if (height - from) % PROGRESS_INTERVAL == PROGRESS_INTERVAL - 1 {
+                info!("Address index reached height {} of {}", height, tip);
+            }
+        }
+        metrics::gauge!("address_index_height").set(tip as f64);
+        Ok(())
+    }
+
+    /// Runs `work` on the index off the async runtime
+    async fn blocking<T: Send + 'static>(
+        self: &Arc<Self>,
+        work: impl FnOnce(&Self) -> anyhow::Result<T> + Send + 'static,
+    ) -> anyhow::Result<T> {
+        let index = self.clone();
+        storage::blocking(move || work(&index)).await
+    }
+
+    /// Connects or disconnects the changes of the block at `height`
+    fn apply(
+        &self,
+        height: u64,
+        hash: &BlockHash,
+        changes: &BlockChanges,
+        connect: bool,
+        durable: bool,
+    ) -> anyhow::Result<()> {
+        let mut batch = Batch::default();
+        for (key, txid) in &changes.history {
+            if connect {
+                batch.put(HISTORY, key, txid.as_byte_array());
+            } else {
+                batch.delete(HISTORY, key);
+            }
+        }
+        // Each script appears once, so reading the totals ahead of the batch is safe
+        for (script, delta) in &changes.stats {
+            let mut totals = match self.store.get(STATS, script)? {
+                Some(value) => AddressStats::decode(&value)?,
+                None => AddressStats::default(),
+            };
+            totals.apply(delta, connect);
+            if totals.tx_count == 0 {
+                batch.delete(STATS, script);
+            } else {
+                batch.put(STATS, script, &totals.encode());
+            }
+        }
+        if connect {
+            for change in &changes.utxos {
+                match change {
+                    UtxoChange::Created(key, value) => batch.put(UTXOS, key, value),
+                    UtxoChange::Spent(key, _) => batch.delete(UTXOS, key),
+                }
+            }
+        } else {
+            for change in changes.utxos.iter().rev() {
+                match change {
+                    UtxoChange::Created(key, _) => batch.delete(UTXOS, key),
+                    // Outputs from before the start height were never indexed
+                    UtxoChange::Spent(key, value) => {
+                        let height = u64::from_be_bytes(value[8..].try_into()?);
+                        if height >= self.start_height {
+                            batch.put(UTXOS, key, value);
+                        }
+                    }
+                }
+            }
+        }
+        if connect {
+            batch.put(BLOCKS, &height_key(height), hash.as_byte_array());
+        } else {
+            batch.delete(BLOCKS, &height_key(height));
+        }
+        self.store.write(batch, durable)
+    }
+
+    fn block_hash(&self, height: u64) -> anyhow::Result<Option<BlockHash>> {
+        match self.store.get(BLOCKS, &height_key(height))? {
+            Some(hash) => Ok(Some(BlockHash::from_slice(&hash)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Confirmed transactions of `script` in blocks from `height` up, oldest first,
+    /// with the height of the block each is in
+    pub fn chain_txs_from(
+        &self,
+        script: &ScriptHash,
+        height: u64,
+    ) -> anyhow::Result<Vec<(Txid, u64)>> {
+        let first = history_key(script, height, 0);
+        let last = history_key(script, u64::MAX, u32::MAX);
+        let mut txs = Vec::new();
+        self.store
+            .scan(HISTORY, &first, &last, false, &mut |key, txid| {
+                txs.push((
+                    Txid::from_slice(txid)?,
+                    u64::from_be_bytes(key[32..40].try_into()?),
+                ));
+                Ok(true)
+            })?;
+        Ok(txs)
+    }
+
+    fn chain_stats(&self, script: &ScriptHash) -> anyhow::Result<AddressStats> {
+        match self.store.get(STATS, script)? {
+            Some(value) => AddressStats::decode(&value),
+            None => Ok(AddressStats::default()),
+        }
+    }
+
+    /// Confirmed unspent outputs of `script`, with their value and height
+    pub fn chain_utxos(&self, script: &ScriptHash) -> anyhow::Result<Vec<(OutPoint, u64, u64)>> {
+        let first = utxo_key(script, &OutPoint::new(Txid::all_zeros(), 0));
+        let last = utxo_key(
+            script,
+            &OutPoint::new(Txid::from_byte_array([0xff; 32]), u32::MAX),
+        );
+        let mut found = Vec::new();
+        self.store
+            .scan(UTXOS, &first, &last, false, &mut |key, value| {
+                let outpoint = OutPoint::new(
+                    Txid::from_slice(&key[32..64])?,
+                    u32::from_be_bytes(key[64..].try_into()?),
+                );
+                found.push((
+                    outpoint,
+                    u64::from_be_bytes(value[..8].try_into()?),
+                    u64::from_be_bytes(value[8..].try_into()?),
+                ));
+                Ok(true)
+            })?;
+        Ok(found)
+    }
+
+    /// A page of confirmed transactions of `script`, newest first, with the hash of
+    /// the block each is in. `None` when `after` isn't in the script's history.
+    fn chain_txs(
+        &self,
+        script: &ScriptHash,
+        after: Option<&Txid>,
+    ) -> anyhow::Result<Option<Vec<(Txid, BlockHash)>>> {
+        let first = history_key(script, 0, 0);
+        let last = history_key(script, u64::MAX, u32::MAX);
+        let mut found = after.is_none();
+        let mut entries = Vec::with_capacity(CHAIN_TXS_PER_PAGE);
+        self.store
+            .scan(HISTORY, &first, &last, true, &mut |key, txid| {
+                if !found {
+                    found = after.is_some_and(|after| txid == after.as_byte_array());
+                    return Ok(true);
+                }
+                entries.push((
+                    Txid::from_slice(txid)?,
+                    u64::from_be_bytes(key[32..40].try_into()?),
+                ));
+                Ok(entries.len() < CHAIN_TXS_PER_PAGE)
+            })?;
+        if !found {
+            return Ok(None);
+        }
+        let mut page = Vec::with_capacity(entries.len());
+        for (txid, height) in entries {
+            let hash = self
+                .store
+                .get(BLOCKS, &height_key(height))?
+                .context("Address index entry without its block")?;
+            page.push((txid, BlockHash::from_slice(&hash)?));
+        }
+        Ok(Some(page))
+    }
+}
+
+/// Index of the request, unless it can't answer yet
+pub fn ready_index(state: &AppState) -> Result<Arc<AddressIndex>, (StatusCode, &'static str)> {
+    match &state.addresses {
+        Some(index) if index.synced.load(Ordering::Relaxed) => Ok(index.clone()),
+        Some(_) => Err((StatusCode::SERVICE_UNAVAILABLE, "Address index is syncing")),
+        None => Err((StatusCode::NOT_FOUND, "Address index disabled")),
+    }
+}
+
+/// What an address or scripthash route is asked about
+#[derive(Clone, Serialize)]
+enum Subject {
+    #[serde(rename = "address")]
+    Address(String),
+    /// Hex SHA256 of the script, in esplora's byte order (not reversed like Electrum's)
+    #[serde(rename = "scripthash")]
+    ScriptHash(String),
+}
+
+impl fmt::Display for Subject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Address(address) => write!(f, "address {}", address),
+            Self::ScriptHash(hash) => write!(f, "scripthash {}", hash),
+        }
+    }
+}
+
+pub fn parse_address(
+    state: &AppState,
+    address: &str,
+) -> Result<Address, (StatusCode, &'static str)> {
+    Address::from_str(address)
+        .ok()
+        .and_then(|address| address.require_network(state.network).ok())
+        .ok_or((StatusCode::BAD_REQUEST, "Invalid address"))
+}
+
+fn subject_script(
+    state: &AppState,
+    subject: &Subject,
+) -> Result<ScriptHash, (StatusCode, &'static str)> {
+    match subject {
+        Subject::Address(address) => {
+            Ok(script_hash(&parse_address(state, address)?.script_pubkey()))
+        }
+        Subject::ScriptHash(hash) => {
+            <[u8; 32]>::from_hex(hash).map_err(|_| (StatusCode::BAD_REQUEST, "Invalid scripthash"))
+        }
+    }
+}
+
+/// Script hash of the subject, with the index to query
+fn lookup(
+    state: &AppState,
+    subject: &Subject,
+) -> Result<(ScriptHash, Arc<AddressIndex>), (StatusCode, &'static str)> {
+    let script = subject_script(state, subject)?;
+    let index = ready_index(state)?;
+    Ok((script, index))
+}
+
+fn mempool_stats(state: &AppState, script: &ScriptHash) -> AddressStats {
+    let mut stats = AddressStats::default();
+    for tx in state.mempool.script_txs(script) {
+        stats.tx_count += 1;
+        for (_, value) in tx.outputs.iter().filter(|(s, _)| s == script) {
+            stats.funded_txo_count += 1;
+            stats.funded_txo_sum += value.to_sat();
+        }
+        for (_, value) in tx.prevouts.iter().filter(|(s, _)| s == script) {
+            stats.spent_txo_count += 1;
+            stats.spent_txo_sum += value.to_sat();
+        }
+    }
+    stats
+}
+
+/// Confirmed transactions in the esplora format. Prevouts of transactions not in
+/// the mempool are looked up by txid, which needs `txindex` on the node.
+async fn esplora_chain_txs(
+    state: &AppState,
+    txs: &[(Txid, BlockHash)],
+) -> anyhow::Result<Vec<EsploraTx>> {
+    let mut statuses = HashMap::new();
+    let mut parents = HashMap::new();
+    let mut esplora = Vec::with_capacity(txs.len());
+    for (txid, hash) in txs {
+        let status = match statuses.entry(*hash) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(block_status(&state.rpc, hash).await?),
+        };
+        let tx = state.rpc.get_raw_transaction(txid, Some(hash)).await?;
+        esplora.push(
+            esplora_tx_with_prevouts(&state.rpc, state.network, &tx, status.clone(), &mut parents)
+                .await?,
+        );
+    }
+    Ok(esplora)
+}
+
+async fn esplora_mempool_txs(
+    state: &AppState,
+    script: &ScriptHash,
+) -> anyhow::Result<Vec<EsploraTx>> {
+    let mut esplora = Vec::new();
+    for tx in state.mempool.script_txs(script) {
+        if esplora.len() == MEMPOOL_TXS_LIMIT {
+            break;
+        }
+        match esplora_tx(&state.rpc, state.network, &state.mempool, &tx.txid).await {
+            Ok(tx) => esplora.push(tx),
+            // Mined or evicted since the last mempool sync
+            Err(bitcoincore_rpc::Error::JsonRpc(_)) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(esplora)
+}
+
+/// Serializes the result of an index query
+async fn respond<T: Serialize>(
+    subject: Subject,
+    query: impl Future<Output = anyhow::Result<Result<T, Response>>>,
+) -> Response {
+    match query.await {
+        Ok(Ok(body)) => Json(body).into_response(),
+        Ok(Err(response)) => response,
+        Err(e) => {
+            warn!("Failed to look up {}: {:#}", subject, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Index error").into_response()
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Summary {
+    #[serde(flatten)]
+    subject: Subject,
+    chain_stats: AddressStats,
+    mempool_stats: AddressStats,
+}
+
+async fn summary(state: AppState, subject: Subject) -> Response {
+    let (script, index) = match lookup(&state, &subject) {
+        Ok(found) => found,
+        Err(response) => return response.into_response(),
+    };
+    respond(subject.clone(), async move {
+        Ok(Ok(Summary {
+            chain_stats: index
+                .blocking(move |index| index.chain_stats(&script))
+                .await?,
+            mempool_stats: mempool_stats(&state, &script),
+            subject,
+        }))
+    })
+    .await
+}
+
+async fn txs(state: AppState, subject: Subject) -> Response {
+    let (script, index) = match lookup(&state, &subject) {
+        Ok(found) => found,
+        Err(response) => return response.into_response(),
+    };
+    respond(subject, async move {
+        let mut txs = esplora_mempool_txs(&state, &script).await?;
+        let chain = index
+            .blocking(move |index| index.chain_txs(&script, None))
+            .await?
+            .unwrap_or_default();
+        txs.extend(esplora_chain_txs(&state, &chain).await?);
+        Ok(Ok(txs))
+    })
+    .await
+}
+
+async fn chain_txs(state: AppState, subject: Subject, after: Option<&str>) -> Response {
+    let after = match after.map(Txid::from_str).transpose() {
+        Ok(after) => after,
+        Err(_) => return (StatusCode::BAD_REQUEST, "Invalid txid").into_response(),
+    };
+    let (script, index) = match lookup(&state, &subject) {
+        Ok(found) => found,
+        Err(response) => return response.into_response(),
+    };
+    respond(subject, async move {
+        let Some(chain) = index
+            .blocking(move |index| index.chain_txs(&script, after.as_ref()))
+            .await?
+        else {
+            return Ok(Err((
+                StatusCode::BAD_REQUEST,
+                "last_seen_txid not in address history",
+            )
+                .into_response()));
+        };
+        Ok(Ok(esplora_chain_txs(&state, &chain).await?))
+    })
+    .await
+}
+
+async fn mempool_txs(state: AppState, subject: Subject) -> Response {
+    let (script, _) = match lookup(&state, &subject) {
+        Ok(found) => found,
+        Err(response) => return response.into_response(),
+    };
+    respond(subject, async move {
+        Ok(Ok(esplora_mempool_txs(&state, &script).await?))
+    })
+    .await
+}
+
+#[derive(Serialize)]
+struct Utxo {
+    txid: Txid,
+    vout: u32,
+    status: EsploraStatus,
+    value: u64,
+    /// Set on coinbase outputs that can't be spent in the next block
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    immature: bool,
+    /// First block that may spend an immature coinbase output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    spendable_height: Option<u64>,
+}
+
+/// Finds the coinbase outputs among confirmed outputs that can't be spent yet.
+/// Only outputs of the last 100 blocks can be immature, so older ones cost no RPC call.
+struct MaturityCheck {
+    next_height: u64,
+    /// Coinbase txid by height, for the blocks looked at so far
+    coinbases: HashMap<u64, Txid>,
+}
+
+impl MaturityCheck {
+    async fn new(rpc: &Rpc) -> Result<Self, bitcoincore_rpc::Error> {
+        Ok(Self {
+            next_height: rpc.get_block_count().await? + 1,
+            coinbases: HashMap::new(),
+        })
+    }
+
+    /// Height of the first block that may spend an output of `txid` confirmed at
+    /// `height`, if it is an immature coinbase output
+    async fn spendable_height(
+        &mut self,
+        rpc: &Rpc,
+        txid: &Txid,
+        height: u64,
+    ) -> Result<Option<u64>, bitcoincore_rpc::Error> {
+        let spendable_height = height + COINBASE_MATURITY;
+        if spendable_height <= self.next_height {
+            return Ok(None);
+        }
+        let coinbase = match self.coinbases.entry(height) {
+            Entry::Occupied(entry) => *entry.get(),
+            Entry::Vacant(entry) => {
+                let hash = rpc.get_block_hash(height).await?;
+                let block = rpc.get_block_info(&hash).await?;
+                *entry.insert(block.tx[0])
+            }
+        };
+        Ok((coinbase == *txid).then_some(spendable_height))
+    }
+}
+
+/// Unspent outputs from the index and the mempool, leaving out those spent in the mempool
+async fn indexed_utxos(
+    state: &AppState,
+    index: Arc<AddressIndex>,
+    script: ScriptHash,
+) -> anyhow::Result<Vec<Utxo>> {
+    let mut statuses = HashMap::new();
+    let mut utxos = Vec::new();
+    for tx in state.mempool.script_txs(&script) {
+        for (vout, (output_script, value)) in tx.outputs.iter().enumerate() {
+            let outpoint = OutPoint::new(tx.txid, vout as u32);
+            if *output_script == script && state.mempool.spent_by(&outpoint).is_none() {
+                utxos.push(Utxo {
+                    txid: tx.txid,
+                    vout: outpoint.vout,
+                    status: EsploraStatus::unconfirmed_since(Some(tx.first_seen)),
+                    value: value.to_sat(),
+                    immature: false,
+                    spendable_height: None,
+                });
+            }
+        }
+    }
+    let mut confirmed = index
+        .blocking(move |index| index.chain_utxos(&script))
+        .await?;
+    confirmed.sort_by_key(|(_, _, height)| Reverse(*height));
+    let mut maturity = MaturityCheck::new(&state.rpc).await?;
+    for (outpoint, value, height) in confirmed {
+        if state.mempool.spent_by(&outpoint).is_some() {
+            continue;
+        }
+        let status = match statuses.entry(height) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let hash = state.rpc.get_block_hash(height).await?;
+                entry.insert(block_status(&state.rpc, &hash).await?)
+            }
+        };
+        let spendable_height = maturity
+            .spendable_height(&state.rpc, &outpoint.txid, height)
+            .await?;
+        utxos.push(Utxo {
+            txid: outpoint.txid,
+            vout: outpoint.vout,
+            status: status.clone(),
+            value,
+            immature: spendable_height.is_some(),
+            spendable_height,
+        });
+    }
+    Ok(utxos)
+}
+
+/// Outpoints and values of the confirmed and mempool outputs of `script` not
+/// spent in the mempool, without their confirmation status. Immature coinbase
+/// outputs are left out, a transaction spending them would be rejected.
+pub async fn spendable(
+    state: &AppState,
+    index: Arc<AddressIndex>,
+    script: ScriptHash,
+) -> anyhow::Result<Vec<(OutPoint, u64)>> {
+    let mut spendable = Vec::new();
+    for tx in state.mempool.script_txs(&script) {
+        for (vout, (output_script, value)) in tx.outputs.iter().enumerate() {
+            let outpoint = OutPoint::new(tx.txid, vout as u32);
+            if *output_script == script && state.mempool.spent_by(&outpoint).is_none() {
+                spendable.push((outpoint, value.to_sat()));
+            }
+        }
+    }
+    let mut maturity = MaturityCheck::new(&state.rpc).await?;
+    let confirmed = index
+        .blocking(move |index| index.chain_utxos(&script))
+        .await?;
+    for (outpoint, value, height) in confirmed {
+        if state.mempool.spent_by(&outpoint).is_none()
+            && maturity
+                .spendable_height(&state.rpc, &outpoint.txid, height)
+                .await?
+                .is_none()
+        {
+            spendable.push((outpoint, value));
+        }
+    }
+    Ok(spendable)
+}
+
+/// Confirmed unspent outputs found by scanning the node's UTXO set, which takes
+/// minutes on mainnet and runs one scan at a time
+async fn scanned_utxos(state: &AppState, address: &Address) -> anyhow::Result<Vec<Utxo>> {
+    let descriptor = ScanTxOutRequest::Single(format!("addr({})", address));
+    let mut scan = state.rpc.scan_tx_out_set(&[descriptor]).await?;
+    scan.unspents.sort_by_key(|utxo| Reverse(utxo.height));
+    let mut statuses = HashMap::new();
+    let mut maturity = MaturityCheck::new(&state.rpc).await?;
+    let mut utxos = Vec::with_capacity(scan.unspents.len());
+    for utxo in scan.unspents {
+        if state
+            .mempool
+            .spent_by(&OutPoint::new(utxo.txid, utxo.vout))
+            .is_some()
+        {
+            continue;
+        }
+        let status = match statuses.entry(utxo.height) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let hash = state.rpc.get_block_hash(utxo.height).await?;
+                entry.insert(block_status(&state.rpc, &hash).await?)
+            }
+        };
+        let spendable_height = maturity
+            .spendable_height(&state.rpc, &utxo.txid, utxo.height)
+            .await?;
+        utxos.push(Utxo {
+            txid: utxo.txid,
+            vout: utxo.vout,
+            status: status.clone(),
+            value: utxo.amount.to_sat(),
+            immature: spendable_height.is_some(),
+            spendable_height,
+        });
+    }
+    Ok(utxos)
+}
+
+async fn utxos(state: AppState, subject: Subject) -> Response {
+    let script = match subject_script(&state, &subject) {
+        Ok(script) => script,
+        Err(response) => return response.into_response(),
+    };
+    // Scanning needs a descriptor, which a bare scripthash can't be turned into
+    if let (None, true, Subject::Address(address)) = (&state.addresses, state.utxo_scan, &subject) {
+        let address = match parse_address(&state, address) {
+            Ok(address) => address,
+            Err(response) => return response.into_response(),
+        };
+        return respond(subject, async move {
+            Ok(Ok(scanned_utxos(&state, &address).await?))
+        })
+        .await;
+    }
+    let index = match ready_index(&state) {
+        Ok(index) => index,
+        Err(response) => return response.into_response(),
+    };
+    respond(subject, async move {
+        Ok(Ok(indexed_utxos(&state, index, script).await?))
+    })
+    .await
+}
+
+/// Position in the chain and the mempool up to which activity was reported,
+/// written as `<height>:<block hash>:<mempool sequence number>`
+struct Cursor {
+    height: u64,
+    hash: BlockHash,
+    seq: u64,
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.height, self.hash, self.seq)
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let cursor = Self {
+            height: parts.next().ok_or(())?.parse().map_err(|_| ())?,
+            hash: parts.next().ok_or(())?.parse().map_err(|_| ())?,
+            seq: parts.next().ok_or(())?.parse().map_err(|_| ())?,
+        };
+        match parts.next() {
+            Some(_) => Err(()),
+            None => Ok(cursor),
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ActivityRequest {
+    #[serde(default)]
+    addresses: Vec<String>,
+    #[serde(default)]
+    scripthashes: Vec<String>,
+    /// Cursor returned by the previous call; without one only a cursor is returned
+    cursor: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Activity {
+    #[serde(flatten)]
+    subject: Subject,
+    txid: Txid,
+    status: EsploraStatus,
+}
+
+#[derive(Serialize)]
+struct ActivityResponse {
+    cursor: String,
+    activity: Vec<Activity>,
+}
+
+/// Index tip and the confirmed transactions of `scripts` after `since`, oldest
+/// first with the blocks they are in
+#[allow(clippy::type_complexity)]
+fn confirmed_activity_blocking(
+    index: &AddressIndex,
+    scripts: &[(ScriptHash, Subject)],
+    since: Option<&Cursor>,
+) -> anyhow::Result<Result<(u64, BlockHash, Vec<(BlockHash, Txid, Subject)>), Response>> {
+    let Some((height, hash)) = index.tip()? else {
+        return Ok(Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Address index is syncing",
+        )
+            .into_response()));
+    };
+    let Some(since) = since else {
+        return Ok(Ok((height, hash, Vec::new())));
+    };
+    // Blocks after the cursor may have been reorged away, or lie beyond what is worth scanning
+    if index.block_hash(since.height)? != Some(since.hash)
+        || height.saturating_sub(since.height) > ACTIVITY_CURSOR_DEPTH
+    {
+        return Ok(Err(cursor_expired()));
+    }
+    let mut confirmed = Vec::new();
+    for (script, subject) in scripts {
+        for (txid, height) in index.chain_txs_from(script, since.height + 1)? {
+            confirmed.push((height, txid, subject.clone()));
+        }
+    }
+    confirmed.sort_by_key(|(height, _, _)| *height);
+    let mut hashes = HashMap::new();
+    let mut txs = Vec::with_capacity(confirmed.len());
+    for (height, txid, subject) in confirmed {
+        let block_hash = match hashes.entry(height) {
+            Entry::Occupied(entry) => *entry.get(),
+            Entry::Vacant(entry) => *entry.insert(
+                index
+                    .block_hash(height)?
+                    .context("Address index entry without its block")?,
+            ),
+        };
+        txs.push((block_hash, txid, subject));
+    }
+    Ok(Ok((height, hash, txs)))
+}
+
+fn cursor_expired() -> Response {
+    (StatusCode::GONE, "Cursor expired, start over without one").into_response()
+}
+
+/// New transactions of `scripts` since `since`, confirmed ones first
+async fn activity(
+    state: &AppState,
+    index: Arc<AddressIndex>,
+    scripts: Arc<Vec<(ScriptHash, Subject)>>,
+    since: Option<Cursor>,
+) -> anyhow::Result<Result<ActivityResponse, Response>> {
+    let since_seq = since.as_ref().map(|since| since.seq);
+    let indexed = {
+        let scripts = scripts.clone();
+        index
+            .blocking(move |index| confirmed_activity_blocking(index, &scripts, since.as_ref()))
+            .await?
+    };
+    let (height, hash, confirmed) = match indexed {
+        Ok(indexed) => indexed,
+        Err(response) => return Ok(Err(response)),
+    };
+    let Some(since_seq) = since_seq else {
+        let cursor = Cursor {
+            height,
+            hash,
+            seq: state.mempool.seq(),
+        };
+        return Ok(Ok(ActivityResponse {
+            cursor: cursor.to_string(),
+            activity: Vec::new(),
+        }));
+    };
+    let Some((seq, added)) = state.mempool.added_since(since_seq) else {
+        return Ok(Err(cursor_expired()));
+    };
+
+    let mut activity = Vec::new();
+    let mut statuses = HashMap::new();
+    for (block_hash, txid, subject) in confirmed {
+        let status = match statuses.entry(block_hash) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(block_status(&state.rpc, &block_hash).await?),
+        };
+        activity.push(Activity {
+            subject,
+            txid,
+            status: status.clone(),
+        });
+    }
+    for tx in added {
+        let touched: HashSet<&ScriptHash> = tx.scripts().collect();
+        for (script, subject) in scripts.iter() {
+            if touched.contains(script) {
+                activity.push(Activity {
+                    subject: subject.clone(),
+                    txid: tx.txid,
+                    status: EsploraStatus::unconfirmed_since(Some(tx.first_seen)),
+                });
+            }
+        }
+    }
+    let cursor = Cursor { height, hash, seq };
+    Ok(Ok(ActivityResponse {
+        cursor: cursor.to_string(),
+        activity,
+    }))
+}
+
+/// New confirmed and mempool transactions of many addresses and scripthashes
+/// since a cursor, for wallets that would otherwise poll each of them
+pub async fn post_addresses_activity(
+    State(state): State<AppState>,
+    Json(request): Json<ActivityRequest>,
+) -> Response {
+    let subjects: Vec<Subject> = request
+        .addresses
+        .into_iter()
+        .map(Subject::Address)
+        .chain(request.scripthashes.into_iter().map(Subject::ScriptHash))
+        .collect();
+    if subjects.len() > ACTIVITY_SUBJECTS_LIMIT {
+        return (StatusCode::BAD_REQUEST, "Too many addresses").into_response();
+    }
+    let since = match request.cursor.as_deref().map(Cursor::from_str).transpose() {
+        Ok(since) => since,
+        Err(()) => return (StatusCode::BAD_REQUEST, "Invalid cursor").into_response(),
+    };
+    let mut scripts = Vec::with_capacity(subjects.len());
+    for subject in subjects {
+        match subject_script(&state, &subject) {
+            Ok(script) => scripts.push((script, subject)),
+            Err(response) => return response.into_response(),
+        }
+    }
+    let index = match ready_index(&state) {
+        Ok(index) => index,
+        Err(response) => return response.into_response(),
+    };
+    let scripts = Arc::new(scripts);
+    match activity(&state, index, scripts, since).await {
+        Ok(Ok(activity)) => Json(activity).into_response(),
+        Ok(Err(response)) => response,
+        Err(e) => {
+            warn!("Failed to look up address activity: {:#}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Index error").into_response()
+        }
+    }
+}
+
+pub async fn get_address(State(state): State<AppState>, Path(address): Path<String>) -> Response {
+    summary(state, Subject::Address(address)).await
+}
+
+pub async fn get_address_txs(
+    State(state): State<AppState>,
+    Path(address): Path<String>,
+) -> Response {
+    txs(state, Subject::Address(address)).await
+}
+
+pub async fn get_address_chain_txs(
+    State(state): State<AppState>,
+    Path(address): Path<String>,
+) -> Response {
+    chain_txs(state, Subject::Address(address), None).await
+}
+
+pub async fn get_address_chain_txs_after(
+    State(state): State<AppState>,
+    Path((address, last_seen)): Path<(String, String)>,
+) -> Response {
+    chain_txs(state, Subject::Address(address), Some(&last_seen)).await
+}
+
+pub async fn get_address_mempool_txs(
+    State(state): State<AppState>,
+    Path(address): Path<String>,
+) -> Response {
+    mempool_txs(state, Subject::Address(address)).await
+}
+
+pub async fn get_address_utxos(
+    State(state): State<AppState>,
+    Path(address): Path<String>,
+) -> Response {
+    utxos(state, Subject::Address(address)).await
+}
+
+pub async fn get_scripthash(State(state): State<AppState>, Path(hash): Path<String>) -> Response {
+    summary(state, Subject::ScriptHash(hash)).await
+}
+
+pub async fn get_scripthash_txs(
+    State(state): State<AppState>,
+    Path(hash): Path<String>,
+) -> Response {
+    txs(state, Subject::ScriptHash(hash)).await
+}
+
+pub async fn get_scripthash_chain_txs(
+    State(state): State<AppState>,
+    Path(hash): Path<String>,
+) -> Response {
+    chain_txs(state, Subject::ScriptHash(hash), None).await
+}
+
+pub async fn get_scripthash_chain_txs_after(
+    State(state): State<AppState>,
+    Path((hash, last_seen)): Path<(String, String)>,
+) -> Response {
+    chain_txs(state, Subject::ScriptHash(hash), Some(&last_seen)).await
+}
+
+pub async fn get_scripthash_mempool_txs(
+    State(state): State<AppState>,
+    Path(hash): Path<String>,
+) -> Response {
+    mempool_txs(state, Subject::ScriptHash(hash)).await
+}
+
+pub async fn get_scripthash_utxos(
+    State(state): State<AppState>,
+    Path(hash): Path<String>,
+) -> Response {
+    utxos(state, Subject::ScriptHash(hash)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden;
+
+    fn confirmed(height: u64, hash: &str) -> EsploraStatus {
+        EsploraStatus {
+            confirmed: true,
+            block_height: Some(height),
+            block_hash: Some(hash.parse().unwrap()),
+            block_time: Some(1713571767),
+            first_seen: None,
+        }
+    }
+
+    #[test]
+    fn golden_address() {
+        golden::check(
+            "address",
+            &Summary {
+                subject: Subject::Address("bc1q6rz28mcfaxtmd6v789l9rrlrusdprr9pqcpvkl".to_owned()),
+                chain_stats: AddressStats {
+                    funded_txo_count: 2,
+                    funded_txo_sum: 250000,
+                    spent_txo_count: 1,
+                    spent_txo_sum: 150000,
+                    tx_count: 3,
+                },
+                mempool_stats: AddressStats::default(),
+            },
+        );
+    }
+
+    #[test]
+    fn golden_address_utxos() {
+        golden::check(
+            "address_utxos",
+            &[
+                Utxo {
+                    txid: "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16"
+                        .parse()
+                        .unwrap(),
+                    vout: 0,
+                    status: EsploraStatus::unconfirmed_since(Some(1713571500)),
+                    value: 100000,
+                    immature: false,
+                    spendable_height: None,
+                },
+                Utxo {
+                    txid: "0e3e2357e806b6cdb1f70b54c3a3a17b6714ee1f0e68bebb44a74b1efd512098"
+                        .parse()
+                        .unwrap(),
+                    vout: 0,
+                    status: confirmed(
+                        840010,
+                        "00000000000000000002a7c4c1e48d76c5a37902165a270156b7a8d72728a054",
+                    ),
+                    value: 312500000,
+                    immature: true,
+                    spendable_height: Some(840110),
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn golden_addresses_activity() {
+        let block = "000000000000000000024bead8df69990852c202db0e0097c1a12ea637d7e96d";
+        golden::check(
+            "addresses_activity",
+            &ActivityResponse {
+                cursor: Cursor {
+                    height: 840001,
+                    hash: block.parse().unwrap(),
+                    seq: 1024,
+                }
+                .to_string(),
+                activity: vec![Activity {
+                    subject: Subject::ScriptHash(
+                        "6191c3b590bfcfa0475e877c302da1e323497acf3b42c08d8fa28e364edf018b"
+                            .to_owned(),
+                    ),
+                    txid: "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16"
+                        .parse()
+                        .unwrap(),
+                    status: confirmed(840001, block),
+                }],
+            },
+        );
+    }
+}
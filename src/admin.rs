@@ -0,0 +1,44 @@
+//! Gate for operator-only routes.
+
+use std::sync::Arc;
+
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use tracing::warn;
+
+/// Bearer token required by admin routes; `None` disables them altogether
+#[derive(Clone)]
+pub struct AdminToken(pub Option<Arc<str>>);
+
+impl AdminToken {
+    /// Whether the headers carry `Authorization: Bearer <admin token>`
+    pub fn authorizes(&self, headers: &HeaderMap) -> bool {
+        let Some(token) = &self.0 else {
+            return false;
+        };
+        headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|provided| constant_time_eq(provided.as_bytes(), token.as_bytes()))
+    }
+}
+
+/// Lets the request through only with `Authorization: Bearer <admin token>`
+pub async fn require_admin(State(token): State<AdminToken>, req: Request, next: Next) -> Response {
+    if token.0.is_none() {
+        return (StatusCode::NOT_FOUND, "Admin API disabled").into_response();
+    }
+    if token.authorizes(req.headers()) {
+        next.run(req).await
+    } else {
+        warn!("Rejected unauthorized request to {}", req.uri().path());
+        (StatusCode::UNAUTHORIZED, "Unauthorized").into_response()
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,59 @@
+//! Static files of the HTML pages, embedded into the binary.
+//!
+//! Assets are served under content-hashed names (`index.3f2a9c1e.css`) with
+//! long-lived cache headers; pages link to them through [`url`] so a new build
+//! never serves stale CSS or JS.
+
+use axum::{
+    extract::Path,
+    http::{header, StatusCode},
+    response::IntoResponse,
+};
+use bitcoincore_rpc::bitcoin::hex::DisplayHex;
+use rust_embed::RustEmbed;
+
+#[derive(RustEmbed)]
+#[folder = "assets/"]
+struct Assets;
+
+/// Bytes of the content hash kept in asset names
+const HASH_PREFIX_LEN: usize = 4;
+
+fn hashed_name(name: &str, hash: &[u8; 32]) -> String {
+    let hash = hash[..HASH_PREFIX_LEN].to_lower_hex_string();
+    match name.rsplit_once('.') {
+        Some((stem, extension)) => format!("{}.{}.{}", stem, hash, extension),
+        None => format!("{}.{}", name, hash),
+    }
+}
+
+/// URL of an embedded asset. Panics on unknown names, which are programming errors.
+pub fn url(name: &str) -> String {
+    let asset = Assets::get(name).unwrap_or_else(|| panic!("unknown asset {}", name));
+    format!(
+        "/static/{}",
+        hashed_name(name, &asset.metadata.sha256_hash())
+    )
+}
+
+pub async fn serve(Path(file): Path<String>) -> impl IntoResponse {
+    // Only a handful of assets, so scanning them beats keeping a reverse index in sync
+    let asset = Assets::iter().find_map(|name| {
+        let asset = Assets::get(&name)?;
+        (hashed_name(&name, &asset.metadata.sha256_hash()) == file).then_some(asset)
+    });
+    match asset {
+        Some(asset) => (
+            [
+                (header::CONTENT_TYPE, asset.metadata.mimetype().to_owned()),
+                (
+                    header::CACHE_CONTROL,
+                    "public, max-age=31536000, immutable".to_owned(),
+                ),
+            ],
+            asset.data,
+        )
+            .into_response(),
+        None => (StatusCode::NOT_FOUND, "Asset not found").into_response(),
+    }
+}
@@ -0,0 +1,335 @@
+//! Append-only audit log of transaction broadcasts.
+//!
+//! Every `sendrawtransaction` made for a client, over HTTP (`POST /api/tx`),
+//! Electrum or gRPC, is recorded with who asked, the transaction's txid and
+//! size, its fee rate once the node accepted it, what the node answered and
+//! the outcome. With a data directory the log is persisted in the configured
+//! store and never pruned; without one it lives in memory and keeps the newest
+//! [`MEMORY_RETENTION`] attempts. Operators page through it newest first at
+//! `/api/v1/admin/broadcasts`.
+
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Json;
+use bitcoincore_rpc::bitcoin::consensus::encode::deserialize_hex;
+use bitcoincore_rpc::bitcoin::{Transaction, Txid};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::json;
+use crate::migrations::{self, Migration, META};
+use crate::rpc::Rpc;
+use crate::storage::{Backend, Batch, Store, Table};
+use crate::tx::broadcast_rejection;
+use crate::AppState;
+
+/// Big-endian sequence number → JSON record
+const BROADCASTS: Table = "broadcasts";
+
+/// Schema changes since the first release, see [`migrations`]
+const MIGRATIONS: &[Migration] = &[];
+
+/// Attempts kept without a data directory
+const MEMORY_RETENTION: usize = 10_000;
+
+/// Records returned by one `/api/v1/admin/broadcasts` request
+const PAGE_SIZE: usize = 100;
+
+pub const BEFORE: &str =
+    "Sequence number to page back from, exclusive; without it records start at the newest";
+pub const TXID: &str = "Only return attempts to broadcast this transaction";
+
+/// Where a broadcast came in
+#[derive(Clone, Copy)]
+pub enum Interface {
+    Http,
+    Electrum,
+    #[cfg_attr(not(feature = "grpc"), allow(dead_code))]
+    Grpc,
+}
+
+impl Interface {
+    fn as_str(self) -> &'static str {
+        match self {
+            Interface::Http => "http",
+            Interface::Electrum => "electrum",
+            Interface::Grpc => "grpc",
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BroadcastRecord {
+    pub seq: u64,
+    /// Seconds since epoch when the node answered
+    pub time: u64,
+    /// `http`, `electrum` or `grpc`
+    pub interface: String,
+    /// Address of the client, when known
+    pub client: Option<String>,
+    /// Unset when the submitted hex isn't a transaction
+    pub txid: Option<Txid>,
+    /// Bytes of the submitted transaction
+    pub size: usize,
+    /// sat/vB of the accepted transaction, as its mempool entry reports
+    #[serde(serialize_with = "json::opt_rate")]
+    pub fee_rate: Option<f64>,
+    /// `accepted`, `rejected` by the node's policy or consensus rules, or
+    /// `failed` when the node couldn't be asked
+    pub outcome: String,
+    /// Rejection reason, as in the `POST /api/tx` error
+    pub reason: Option<String>,
+    /// The node's answer: the txid, or its error message
+    pub response: String,
+}
+
+#[derive(Serialize)]
+struct BroadcastPage {
+    /// Newest first, at most [`PAGE_SIZE`]
+    records: Vec<BroadcastRecord>,
+}
+
+pub struct BroadcastAudit {
+    store: Option<Arc<dyn Store>>,
+    /// Sequence number of the newest record, 0 before the first
+    last_seq: Mutex<u64>,
+    /// Records kept in memory without a store, oldest first
+    recent: Mutex<VecDeque<BroadcastRecord>>,
+}
+
+impl BroadcastAudit {
+    pub fn in_memory() -> Self {
+        Self {
+            store: None,
+            last_seq: Mutex::new(0),
+            recent: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn open(backend: Backend, path: &Path, migrate: bool) -> Result<Self> {
+        let store = backend
+            .open(path, &[BROADCASTS, META])
+            .with_context(|| format!("Failed to open broadcast audit log at {}", path.display()))?;
+        migrations::migrate(
+            store.as_ref(),
+            "broadcast audit log",
+            &[BROADCASTS],
+            MIGRATIONS,
+            migrate,
+        )?;
+        let last_seq = match store.last(BROADCASTS)? {
+            Some((key, _)) => u64::from_be_bytes(
+                key.as_slice()
+                    .try_into()
+                    .context("Corrupt broadcast audit log key")?,
+            ),
+            None => 0,
+        };
+        if last_seq > 0 {
+            info!("Broadcast audit log resumes after record {}", last_seq);
+        }
+        Ok(Self {
+            store: Some(store),
+            last_seq: Mutex::new(last_seq),
+            recent: Mutex::new(VecDeque::new()),
+        })
+    }
+
+    /// Records the node's answer to broadcasting `hex`, looking up the fee
+    /// rate of an accepted transaction in the mempool
+    pub async fn record(
+        &self,
+        rpc: &Rpc,
+        interface: Interface,
+        client: Option<SocketAddr>,
+        hex: &str,
+        result: &Result<Txid, bitcoincore_rpc::Error>,
+    ) {
+        let txid = match result {
+            Ok(txid) => Some(*txid),
+            Err(_) => deserialize_hex::<Transaction>(hex)
+                .ok()
+                .map(|tx| tx.compute_txid()),
+        };
+        let (outcome, reason, response, fee_rate) = match result {
+            Ok(txid) => {
+                let fee_rate = match rpc.get_mempool_entry(txid).await {
+                    Ok(entry) if entry.vsize > 0 => {
+                        Some(entry.fees.base.to_sat() as f64 / entry.vsize as f64)
+                    }
+                    Ok(_) => None,
+                    Err(e) => {
+                        warn!("Failed to get the fee rate of broadcast {}: {}", txid, e);
+                        None
+                    }
+                };
+                ("accepted", None, txid.to_string(), fee_rate)
+            }
+            Err(e) => match broadcast_rejection(e) {
+                Some((_, rejection)) => (
+                    "rejected",
+                    Some(rejection.error.to_owned()),
+                    rejection.message,
+                    None,
+                ),
+                None => ("failed", None, e.to_string(), None),
+            },
+        };
+        self.append(BroadcastRecord {
+            seq: 0,
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            interface: interface.as_str().to_owned(),
+            client: client.map(|client| client.to_string()),
+            txid,
+            size: hex.len() / 2,
+            fee_rate,
+            outcome: outcome.to_owned(),
+            reason,
+            response,
+        });
+    }
+
+    /// Numbers and appends a record. A record that fails to persist is logged
+    /// and counted, the broadcast itself already happened.
+    fn append(&self, mut record: BroadcastRecord) {
+        let mut last_seq = self.last_seq.lock().expect("broadcast audit lock poisoned");
+        record.seq = *last_seq + 1;
+        match &self.store {
+            Some(store) => {
+                let mut batch = Batch::default();
+                batch.put(
+                    BROADCASTS,
+                    &record.seq.to_be_bytes(),
+                    &serde_json::to_vec(&record).expect("broadcast records always serialize"),
+                );
+                if let Err(e) = store.write(batch, true) {
+                    warn!("Failed to record broadcast {}: {}", record.seq, e);
+                    metrics::counter!("broadcast_audit_write_failures_total").increment(1);
+                }
+            }
+            None => {
+                let mut recent = self.recent.lock().expect("broadcast audit lock poisoned");
+                recent.push_back(record.clone());
+                if recent.len() > MEMORY_RETENTION {
+                    recent.pop_front();
+                }
+            }
+        }
+        *last_seq = record.seq;
+        metrics::counter!("broadcast_audit_records_total", "outcome" => record.outcome)
+            .increment(1);
+    }
+
+    /// Records before `before`, newest first, only those of `txid` with one
+    fn page(&self, before: Option<u64>, txid: Option<Txid>) -> Result<Vec<BroadcastRecord>> {
+        let before = before.unwrap_or(u64::MAX);
+        let matches = |record: &BroadcastRecord| txid.is_none() || record.txid == txid;
+        let Some(store) = &self.store else {
+            let recent = self.recent.lock().expect("broadcast audit lock poisoned");
+            return Ok(recent
+                .iter()
+                .rev()
+                .filter(|record| record.seq < before && matches(record))
+                .take(PAGE_SIZE)
+                .cloned()
+                .collect());
+        };
+        let mut records = Vec::new();
+        if before == 0 {
+            return Ok(records);
+        }
+        store.scan(
+            BROADCASTS,
+            &0u64.to_be_bytes(),
+            &(before - 1).to_be_bytes(),
+            true,
+            &mut |_, value| {
+                let record: BroadcastRecord =
+                    serde_json::from_slice(value).context("Corrupt broadcast audit record")?;
+                if matches(&record) {
+                    records.push(record);
+                }
+                Ok(records.len() < PAGE_SIZE)
+            },
+        )?;
+        Ok(records)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct BroadcastQuery {
+    before: Option<u64>,
+    txid: Option<Txid>,
+}
+
+pub async fn get_broadcasts(
+    State(state): State<AppState>,
+    Query(query): Query<BroadcastQuery>,
+) -> impl IntoResponse {
+    match state.audit.page(query.before, query.txid) {
+        Ok(records) => Json(BroadcastPage { records }).into_response(),
+        Err(e) => {
+            warn!("Failed to read the broadcast audit log: {:#}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to read the broadcast audit log",
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden;
+
+    #[test]
+    fn golden_broadcasts() {
+        let audit = BroadcastAudit::in_memory();
+        let txid: Txid = "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b"
+            .parse()
+            .unwrap();
+        audit.append(BroadcastRecord {
+            seq: 0,
+            time: 1700000500,
+            interface: Interface::Electrum.as_str().to_owned(),
+            client: Some("198.51.100.2:40022".to_owned()),
+            txid: Some(txid),
+            size: 225,
+            fee_rate: None,
+            outcome: "rejected".to_owned(),
+            reason: Some("fee-too-low".to_owned()),
+            response: "min relay fee not met, 100 < 225".to_owned(),
+        });
+        audit.append(BroadcastRecord {
+            seq: 0,
+            time: 1700000600,
+            interface: Interface::Http.as_str().to_owned(),
+            client: Some("203.0.113.7:51234".to_owned()),
+            txid: Some(txid),
+            size: 225,
+            fee_rate: Some(2812.0 / 225.0),
+            outcome: "accepted".to_owned(),
+            reason: None,
+            response: txid.to_string(),
+        });
+        golden::check(
+            "broadcasts",
+            &BroadcastPage {
+                records: audit.page(None, None).unwrap(),
+            },
+        );
+    }
+}
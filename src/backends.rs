@@ -0,0 +1,526 @@
+//! Consistency of additional bitcoind nodes with the primary one.
+//!
+//! Every `BACKEND_CHECK_INTERVAL` each node is asked for its tip and a block
+//! template, and compared to the primary node: a node on the same chain is in
+//! sync, or lagging/ahead once its height differs by more than
+//! `BACKEND_MAX_LAG`, and a node whose tip isn't on the primary's chain (or the
+//! other way round) has forked off. Changes are logged, exported as
+//! `backend_*` metrics and degrade the `backends` health component.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, bail, Result};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use bitcoincore_rpc::bitcoin::BlockHash;
+use futures_util::future::join_all;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{debug, info, warn};
+
+use crate::health::{Health, Severity};
+use crate::pool::ConnectionPool;
+use crate::rpc::{self, HttpTransport, Rpc};
+use crate::AppState;
+
+const HEALTH_COMPONENT: &str = "backends";
+
+/// bitcoind node, configured as `<url>[;user=<user>;pass=<pass>]`
+#[derive(Clone)]
+pub struct BackendNode {
+    url: String,
+    /// Falls back to the primary node's credentials
+    credentials: Option<(String, String)>,
+}
+
+impl fmt::Debug for BackendNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.url)
+    }
+}
+
+impl FromStr for BackendNode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(';').map(str::trim);
+        let url = parts.next().unwrap_or_default();
+        reqwest::Url::parse(url).map_err(|e| anyhow!("Invalid node URL {:?}: {}", url, e))?;
+        let (mut user, mut pass) = (None, None);
+        for option in parts {
+            match option.split_once('=') {
+                Some(("user", value)) => user = Some(value.to_owned()),
+                Some(("pass", value)) => pass = Some(value.to_owned()),
+                _ => bail!(
+                    "Unknown node option {:?}, expected user=<user> or pass=<pass>",
+                    option
+                ),
+            }
+        }
+        let credentials = match (user, pass) {
+            (Some(user), Some(pass)) => Some((user, pass)),
+            (None, None) => None,
+            _ => bail!("Node {} needs both user= and pass=", url),
+        };
+        Ok(Self {
+            url: url.to_owned(),
+            credentials,
+        })
+    }
+}
+
+impl BackendNode {
+    /// Password of the node's own credentials or URL, for redaction
+    pub fn password(&self) -> Option<&str> {
+        match &self.credentials {
+            Some((_, pass)) => Some(pass),
+            None => self
+                .url
+                .split_once("://")
+                .and_then(|(_, rest)| rest.split('/').next())
+                .and_then(|authority| authority.rsplit_once('@'))
+                .and_then(|(userinfo, _)| userinfo.split_once(':'))
+                .map(|(_, pass)| pass),
+        }
+    }
+
+    fn credentials<'a>(
+        &'a self,
+        primary_user: &'a str,
+        primary_pass: &'a str,
+    ) -> (&'a str, &'a str) {
+        match &self.credentials {
+            Some((user, pass)) => (user, pass),
+            None => (primary_user, primary_pass),
+        }
+    }
+
+    pub fn connect(&self, primary_user: &str, primary_pass: &str) -> Result<Rpc> {
+        let (user, pass) = self.credentials(primary_user, primary_pass);
+        let http = reqwest::Client::builder()
+            .no_proxy()
+            .timeout(rpc::TIMEOUT)
+            .build()?;
+        let transport = HttpTransport::new(http, &self.url, user, pass)?;
+        Ok(Rpc::new(Arc::new(transport)))
+    }
+
+    /// Async transport of the node with `pool_size` calls at a time, for
+    /// building other transports on
+    pub fn pooled(
+        &self,
+        primary_user: &str,
+        primary_pass: &str,
+        pool_size: usize,
+    ) -> Result<ConnectionPool> {
+        let (user, pass) = self.credentials(primary_user, primary_pass);
+        ConnectionPool::new(self.name(), &self.url, user, pass, pool_size)
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Label of the node in the API and metrics, its URL without the path
+    pub fn name(&self) -> String {
+        match reqwest::Url::parse(&self.url) {
+            Ok(url) => format!(
+                "{}:{}",
+                url.host_str().unwrap_or_default(),
+                url.port_or_known_default().unwrap_or_default()
+            ),
+            Err(_) => self.url.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum NodeStatus {
+    /// The primary node itself
+    Primary,
+    Synced,
+    Lagging,
+    Ahead,
+    Forked,
+    Unreachable,
+}
+
+impl NodeStatus {
+    fn is_diverging(self) -> bool {
+        matches!(self, Self::Lagging | Self::Forked | Self::Unreachable)
+    }
+}
+
+/// What a node reported in one check
+struct Observation {
+    height: u64,
+    tip: BlockHash,
+    template: Option<Template>,
+}
+
+#[derive(Clone, Copy)]
+struct Template {
+    /// Total fees of the template's transactions in sats
+    fees: u64,
+    tx_count: u64,
+}
+
+#[derive(Deserialize)]
+struct TemplateTx {
+    fee: u64,
+}
+
+#[derive(Deserialize)]
+struct BlockTemplate {
+    transactions: Vec<TemplateTx>,
+}
+
+async fn observe(rpc: &Rpc) -> Result<Observation, bitcoincore_rpc::Error> {
+    let info = rpc.get_blockchain_info().await?;
+    // Nodes without peers or still syncing refuse to build templates, which
+    // says nothing about their chain
+    let template = match rpc
+        .call::<BlockTemplate>("getblocktemplate", &[json!({"rules": ["segwit"]})])
+        .await
+    {
+        Ok(template) => Some(Template {
+            fees: template.transactions.iter().map(|tx| tx.fee).sum(),
+            tx_count: template.transactions.len() as u64,
+        }),
+        Err(e) => {
+            debug!("Failed to get block template: {}", e);
+            None
+        }
+    };
+    Ok(Observation {
+        height: info.blocks,
+        tip: info.best_block_hash,
+        template,
+    })
+}
+
+#[derive(Clone, Serialize)]
+struct NodeReport {
+    name: String,
+    /// `primary`, `synced`, `lagging`, `ahead`, `forked` or `unreachable`
+    status: NodeStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    height: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tip: Option<BlockHash>,
+    /// Blocks behind the primary node, negative when ahead
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lag: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    template_fees: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    template_tx_count: Option<u64>,
+    /// Template fees minus the primary node's template fees, in sats
+    #[serde(skip_serializing_if = "Option::is_none")]
+    template_fee_delta: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Clone, Default, Serialize)]
+struct Consistency {
+    /// Seconds since epoch, unset until the first check
+    checked_at: Option<u64>,
+    /// Whether every node is on the primary node's chain within the allowed lag
+    consistent: bool,
+    nodes: Vec<NodeReport>,
+}
+
+struct Node {
+    name: String,
+    rpc: Rpc,
+}
+
+pub struct BackendMonitor {
+    nodes: Vec<Node>,
+    max_lag: u64,
+    latest: RwLock<Consistency>,
+}
+
+impl BackendMonitor {
+    pub fn new(nodes: Vec<(String, Rpc)>, max_lag: u64) -> Self {
+        Self {
+            nodes: nodes
+                .into_iter()
+                .map(|(name, rpc)| Node { name, rpc })
+                .collect(),
+            max_lag,
+            latest: RwLock::new(Consistency::default()),
+        }
+    }
+
+    pub async fn run(self: Arc<Self>, rpc: Arc<Rpc>, interval: Duration, health: Arc<Health>) {
+        health.register(HEALTH_COMPONENT, Severity::Soft, Some(interval * 3));
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let primary = match observe(&rpc).await {
+                Ok(primary) => primary,
+                Err(e) => {
+                    warn!("Failed to check the primary node for consistency: {}", e);
+                    continue;
+                }
+            };
+            // Nodes are checked in parallel so an unreachable one doesn't delay the rest
+            let checks = join_all(
+                self.nodes
+                    .iter()
+                    .map(|node| self.check(&rpc, &primary, &node.rpc)),
+            )
+            .await;
+            let previous = self.latest.read().expect("backends lock poisoned").clone();
+            let mut nodes = vec![NodeReport {
+                name: "primary".to_owned(),
+                status: NodeStatus::Primary,
+                height: Some(primary.height),
+                tip: Some(primary.tip),
+                lag: Some(0),
+                template_fees: primary.template.map(|template| template.fees),
+                template_tx_count: primary.template.map(|template| template.tx_count),
+                template_fee_delta: primary.template.map(|_| 0),
+                error: None,
+            }];
+            for (node, check) in self.nodes.iter().zip(checks) {
+                let report = report(&node.name, &primary, check.map_err(|e| e.to_string()));
+                let before = previous
+                    .nodes
+                    .iter()
+                    .find(|previous| previous.name == report.name)
+                    .map(|previous| previous.status);
+                log_change(&report, before);
+                record_metrics(&report);
+                nodes.push(report);
+            }
+            let diverging: Vec<&str> = nodes
+                .iter()
+                .filter(|node| node.status.is_diverging())
+                .map(|node| node.name.as_str())
+                .collect();
+            if diverging.is_empty() {
+                health.success(HEALTH_COMPONENT);
+            } else {
+                health.failure(
+                    HEALTH_COMPONENT,
+                    &format!("Diverging nodes: {}", diverging.join(", ")),
+                );
+            }
+            let consistency = Consistency {
+                checked_at: Some(
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs(),
+                ),
+                consistent: diverging.is_empty(),
+                nodes,
+            };
+            *self.latest.write().expect("backends lock poisoned") = consistency;
+        }
+    }
+
+    /// Observes a node and places it relative to the primary. Of two nodes on
+    /// the same chain, the one with the higher tip has the other's tip at its
+    /// height.
+    async fn check(
+        &self,
+        primary_rpc: &Rpc,
+        primary: &Observation,
+        rpc: &Rpc,
+    ) -> Result<(Observation, NodeStatus), bitcoincore_rpc::Error> {
+        let observed = observe(rpc).await?;
+        let status = if observed.height == primary.height {
+            if observed.tip == primary.tip {
+                NodeStatus::Synced
+            } else {
+                NodeStatus::Forked
+            }
+        } else if observed.height < primary.height {
+            if primary_rpc.get_block_hash(observed.height).await? != observed.tip {
+                NodeStatus::Forked
+            } else if primary.height - observed.height > self.max_lag {
+                NodeStatus::Lagging
+            } else {
+                NodeStatus::Synced
+            }
+        } else if rpc.get_block_hash(primary.height).await? != primary.tip {
+            NodeStatus::Forked
+        } else if observed.height - primary.height > self.max_lag {
+            NodeStatus::Ahead
+        } else {
+            NodeStatus::Synced
+        };
+        Ok((observed, status))
+    }
+}
+
+fn report(
+    name: &str,
+    primary: &Observation,
+    check: Result<(Observation, NodeStatus), String>,
+) -> NodeReport {
+    match check {
+        Ok((observed, status)) => NodeReport {
+            name: name.to_owned(),
+            status,
+            height: Some(observed.height),
+            tip: Some(observed.tip),
+            lag: Some(primary.height as i64 - observed.height as i64),
+            template_fees: observed.template.map(|template| template.fees),
+            template_tx_count: observed.template.map(|template| template.tx_count),
+            template_fee_delta: primary
+                .template
+                .zip(observed.template)
+                .map(|(primary, template)| template.fees as i64 - primary.fees as i64),
+            error: None,
+        },
+        Err(error) => NodeReport {
+            name: name.to_owned(),
+            status: NodeStatus::Unreachable,
+            height: None,
+            tip: None,
+            lag: None,
+            template_fees: None,
+            template_tx_count: None,
+            template_fee_delta: None,
+            error: Some(error),
+        },
+    }
+}
+
+fn log_change(report: &NodeReport, before: Option<NodeStatus>) {
+    if before == Some(report.status) {
+        return;
+    }
+    let height = report.height.unwrap_or_default();
+    match report.status {
+        NodeStatus::Forked => warn!(
+            "Node {} forked off the primary node's chain at tip {} (height {})",
+            report.name,
+            report.tip.map(|tip| tip.to_string()).unwrap_or_default(),
+            height
+        ),
+        NodeStatus::Lagging => warn!(
+            "Node {} lags the primary node by {} blocks",
+            report.name,
+            report.lag.unwrap_or_default()
+        ),
+        NodeStatus::Ahead => warn!(
+            "Node {} is {} blocks ahead of the primary node",
+            report.name,
+            -report.lag.unwrap_or_default()
+        ),
+        NodeStatus::Unreachable => warn!(
+            "Node {} is unreachable: {}",
+            report.name,
+            report.error.as_deref().unwrap_or_default()
+        ),
+        NodeStatus::Synced if before.is_some() => {
+            info!("Node {} is back in sync at height {}", report.name, height)
+        }
+        NodeStatus::Synced | NodeStatus::Primary => {}
+    }
+}
+
+fn record_metrics(report: &NodeReport) {
+    let backend = report.name.clone();
+    metrics::gauge!("backend_reachable", "backend" => backend.clone())
+        .set(f64::from(report.status != NodeStatus::Unreachable));
+    metrics::gauge!("backend_forked", "backend" => backend.clone())
+        .set(f64::from(report.status == NodeStatus::Forked));
+    if let (Some(height), Some(lag)) = (report.height, report.lag) {
+        metrics::gauge!("backend_height", "backend" => backend.clone()).set(height as f64);
+        metrics::gauge!("backend_lag_blocks", "backend" => backend.clone()).set(lag as f64);
+    }
+    if let Some(delta) = report.template_fee_delta {
+        metrics::gauge!("backend_template_fee_delta_sats", "backend" => backend).set(delta as f64);
+    }
+}
+
+pub async fn get_consistency(State(state): State<AppState>) -> impl IntoResponse {
+    match &state.backends {
+        Some(monitor) => Json(
+            monitor
+                .latest
+                .read()
+                .expect("backends lock poisoned")
+                .clone(),
+        )
+        .into_response(),
+        None => (StatusCode::NOT_FOUND, "No backend nodes configured").into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden;
+
+    #[test]
+    fn golden_backend_consistency() {
+        let tip: BlockHash = "0000000000000000000320283a032748cef8227873ff4872689bf23f1cda83a5"
+            .parse()
+            .unwrap();
+        let primary = Observation {
+            height: 840000,
+            tip,
+            template: Some(Template {
+                fees: 31528764,
+                tx_count: 3249,
+            }),
+        };
+        let synced = Observation {
+            height: 840000,
+            tip,
+            template: Some(Template {
+                fees: 31402115,
+                tx_count: 3241,
+            }),
+        };
+        let lagging = Observation {
+            height: 839996,
+            tip: "000000000000000000014b4c4dd9d7e0c3b0e2ae60f0d3b49fbb4fd87a8bd6b0"
+                .parse()
+                .unwrap(),
+            template: None,
+        };
+        golden::check(
+            "backend_consistency",
+            &Consistency {
+                checked_at: Some(1713571780),
+                consistent: false,
+                nodes: vec![
+                    NodeReport {
+                        name: "primary".to_owned(),
+                        status: NodeStatus::Primary,
+                        height: Some(primary.height),
+                        tip: Some(primary.tip),
+                        lag: Some(0),
+                        template_fees: Some(31528764),
+                        template_tx_count: Some(3249),
+                        template_fee_delta: Some(0),
+                        error: None,
+                    },
+                    report("10.0.0.2:8332", &primary, Ok((synced, NodeStatus::Synced))),
+                    report(
+                        "10.0.0.3:8332",
+                        &primary,
+                        Ok((lagging, NodeStatus::Lagging)),
+                    ),
+                    report(
+                        "10.0.0.4:8332",
+                        &primary,
+                        Err("JSON-RPC error: transport error: Couldn't connect to host".to_owned()),
+                    ),
+                ],
+            },
+        );
+    }
+}
@@ -0,0 +1,842 @@
+//! mempool.space-flavoured block endpoints: extended block objects with fee
+//! statistics and mining pool identification, and per-block fee histograms.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use bitcoincore_rpc::bitcoin::hex::FromHex;
+use bitcoincore_rpc::bitcoin::{Amount, BlockHash, Network, Transaction};
+use futures_util::future::try_join_all;
+use reqwest::{Method, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::{debug, warn};
+
+use crate::cache::BoundedCache;
+use crate::json;
+use crate::outbound::OutboundClient;
+use crate::rpc::Rpc;
+use crate::tx::{block_status, esplora_tx_with_prevouts, EsploraTx};
+use crate::AppState;
+
+/// Number of blocks returned per `/api/v1/blocks` page, same as mempool.space
+pub const BLOCKS_PER_PAGE: u64 = 15;
+
+/// Number of blocks returned per `/api/blocks` page, same as esplora
+pub const ESPLORA_BLOCKS_PER_PAGE: usize = 10;
+
+/// Number of transactions returned per `/api/block/{hash}/txs` page, same as esplora
+pub const TXS_PER_PAGE: usize = 25;
+
+/// Bytes per chunk when sending a raw block decoded from RPC hex
+const RAW_BLOCK_CHUNK: usize = 64 * 1024;
+
+/// Coinbase tags of well-known pools as `(name, slug, tags)`. The pool id is
+/// the position in this table, starting at 1; 0 stands for unknown.
+const POOLS: &[(&str, &str, &[&str])] = &[
+    ("Foundry USA", "foundryusa", &["Foundry USA"]),
+    ("AntPool", "antpool", &["AntPool"]),
+    ("F2Pool", "f2pool", &["F2Pool", "七彩神仙鱼"]),
+    ("ViaBTC", "viabtc", &["ViaBTC", "viabtc.com"]),
+    ("Binance Pool", "binancepool", &["Binance"]),
+    ("MARA Pool", "marapool", &["MARA Pool", "MARA Made in USA"]),
+    ("Luxor", "luxor", &["LUXOR"]),
+    ("SpiderPool", "spiderpool", &["SpiderPool"]),
+    ("Poolin", "poolin", &["poolin"]),
+    ("BTC.com", "btccom", &["BTC.COM"]),
+    ("SBI Crypto", "sbicrypto", &["SBICrypto"]),
+    ("Braiins Pool", "braiinspool", &["/slush/", "Braiins"]),
+    ("OCEAN", "ocean", &["OCEAN.XYZ"]),
+    ("SECPOOL", "secpool", &["SecPool"]),
+    ("ULTIMUSPOOL", "ultimuspool", &["ultimus"]),
+    ("Titan", "titan", &["Titan.io"]),
+    ("EMCDPool", "emcdpool", &["EMCD"]),
+];
+
+/// Lower bounds in sat/vB of the fee rate bands used by block fee histograms
+const FEE_HISTOGRAM_BANDS: &[f64] = &[
+    0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 8.0, 10.0, 12.0, 15.0, 20.0, 30.0, 40.0, 50.0, 60.0, 70.0,
+    80.0, 90.0, 100.0, 125.0, 150.0, 175.0, 200.0, 250.0, 300.0, 350.0, 400.0, 500.0, 600.0, 700.0,
+    800.0, 900.0, 1000.0, 1200.0, 1400.0, 1600.0, 1800.0, 2000.0,
+];
+
+#[derive(Clone, Serialize)]
+pub struct ExtendedBlock {
+    id: BlockHash,
+    height: u64,
+    version: i32,
+    timestamp: u64,
+    bits: u32,
+    nonce: u32,
+    difficulty: f64,
+    merkle_root: String,
+    tx_count: usize,
+    size: usize,
+    weight: usize,
+    previousblockhash: Option<BlockHash>,
+    mediantime: Option<u64>,
+    /// Local arrival in seconds since epoch, for blocks seen since startup
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seen_at: Option<u64>,
+    extras: BlockExtras,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BlockExtras {
+    /// Total fees in sats
+    total_fees: u64,
+    /// Median fee rate in sat/vB
+    median_fee: u64,
+    /// Min, 10th, 25th, 50th, 75th, 90th percentile and max fee rate in sat/vB
+    fee_range: [u64; 7],
+    /// Subsidy plus fees in sats
+    reward: u64,
+    avg_fee: u64,
+    avg_fee_rate: u64,
+    coinbase_raw: Option<String>,
+    pool: Pool,
+}
+
+#[derive(Clone, Serialize)]
+struct Pool {
+    id: usize,
+    name: &'static str,
+    slug: &'static str,
+}
+
+fn identify_pool(coinbase: Option<&Transaction>) -> Pool {
+    let script_sig = coinbase
+        .and_then(|tx| tx.input.first())
+        .map(|input| String::from_utf8_lossy(input.script_sig.as_bytes()).to_lowercase());
+    script_sig
+        .and_then(|script_sig| {
+            POOLS
+                .iter()
+                .enumerate()
+                .find(|(_, (_, _, tags))| {
+                    tags.iter()
+                        .any(|tag| script_sig.contains(&tag.to_lowercase()))
+                })
+                .map(|(i, &(name, slug, _))| Pool {
+                    id: i + 1,
+                    name,
+                    slug,
+                })
+        })
+        .unwrap_or(Pool {
+            id: 0,
+            name: "Unknown",
+            slug: "unknown",
+        })
+}
+
+/// mempool.space summary of a block, from the cache when it was built before
+pub async fn extended_block(
+    rpc: &Rpc,
+    cache: &BoundedCache<BlockHash, ExtendedBlock>,
+    hash: &BlockHash,
+) -> Result<ExtendedBlock, bitcoincore_rpc::Error> {
+    if let Some(block) = cache.get(hash) {
+        return Ok(block);
+    }
+    let (block, stats) = tokio::try_join!(rpc.get_block_info(hash), rpc.get_block_stats(hash))?;
+
+    // The genesis coinbase can't be fetched, its block simply has an unknown pool
+    let coinbase = match block.tx.first() {
+        Some(txid) => rpc.get_raw_transaction(txid, Some(hash)).await.ok(),
+        None => None,
+    };
+
+    let percentiles = &stats.fee_rate_percentiles;
+    let extended = ExtendedBlock {
+        id: block.hash,
+        height: block.height as u64,
+        version: block.version,
+        timestamp: block.time as u64,
+        bits: u32::from_str_radix(&block.bits, 16).unwrap_or_default(),
+        nonce: block.nonce,
+        difficulty: block.difficulty,
+        merkle_root: block.merkleroot.to_string(),
+        tx_count: block.n_tx,
+        size: block.size,
+        weight: block.weight,
+        previousblockhash: block.previousblockhash,
+        mediantime: block.mediantime.map(|time| time as u64),
+        seen_at: None,
+        extras: BlockExtras {
+            total_fees: stats.total_fee.to_sat(),
+            median_fee: percentiles.fr_50th.to_sat(),
+            fee_range: [
+                stats.min_fee_rate.to_sat(),
+                percentiles.fr_10th.to_sat(),
+                percentiles.fr_25th.to_sat(),
+                percentiles.fr_50th.to_sat(),
+                percentiles.fr_75th.to_sat(),
+                percentiles.fr_90th.to_sat(),
+                stats.max_fee_rate.to_sat(),
+            ],
+            reward: (stats.subsidy + stats.total_fee).to_sat(),
+            avg_fee: stats.avg_fee.to_sat(),
+            avg_fee_rate: stats.avg_fee_rate.to_sat(),
+            coinbase_raw: coinbase
+                .as_ref()
+                .and_then(|tx| tx.input.first())
+                .map(|input| input.script_sig.to_hex_string()),
+            pool: identify_pool(coinbase.as_ref()),
+        },
+    };
+    cache.insert(*hash, extended.clone());
+    Ok(extended)
+}
+
+/// A page of blocks down from the tip or `start_height`, their hashes looked
+/// up in one batch and the blocks not cached yet built concurrently
+async fn extended_blocks(
+    rpc: &Rpc,
+    cache: &BoundedCache<BlockHash, ExtendedBlock>,
+    start_height: Option<u64>,
+) -> Result<Vec<ExtendedBlock>, bitcoincore_rpc::Error> {
+    let start_height = match start_height {
+        Some(height) => height,
+        None => rpc.get_block_count().await?,
+    };
+    let end_height = start_height.saturating_sub(BLOCKS_PER_PAGE - 1);
+    let heights: Vec<Vec<Value>> = (end_height..=start_height)
+        .rev()
+        .map(|height| vec![json!(height)])
+        .collect();
+    let hashes = rpc
+        .batch::<BlockHash>("getblockhash", &heights)
+        .await?
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+    try_join_all(hashes.iter().map(|hash| extended_block(rpc, cache, hash))).await
+}
+
+async fn extended_blocks_page(state: AppState, start_height: Option<u64>) -> impl IntoResponse {
+    match extended_blocks(&state.rpc, &state.extended_blocks, start_height).await {
+        Ok(mut blocks) => {
+            for block in &mut blocks {
+                block.seen_at = state.propagation.seen_at(&block.id);
+            }
+            Json(blocks).into_response()
+        }
+        Err(e) => {
+            warn!("Failed to get blocks from height {:?}: {}", start_height, e);
+            (StatusCode::NOT_FOUND, "Block not found").into_response()
+        }
+    }
+}
+
+pub async fn get_v1_blocks(State(state): State<AppState>) -> impl IntoResponse {
+    extended_blocks_page(state, None).await
+}
+
+pub async fn get_v1_blocks_from(
+    State(state): State<AppState>,
+    Path(height): Path<u64>,
+) -> impl IntoResponse {
+    extended_blocks_page(state, Some(height)).await
+}
+
+/// A single block in the `/api/v1/blocks` format, served in mempool.space compatibility mode
+pub async fn get_v1_block(
+    State(state): State<AppState>,
+    Path(hash): Path<String>,
+) -> impl IntoResponse {
+    let Ok(block_hash) = BlockHash::from_str(&hash) else {
+        return (StatusCode::BAD_REQUEST, "Invalid block hash").into_response();
+    };
+    match extended_block(&state.rpc, &state.extended_blocks, &block_hash).await {
+        Ok(mut block) => {
+            block.seen_at = state.propagation.seen_at(&block.id);
+            Json(block).into_response()
+        }
+        Err(e) => {
+            warn!("Failed to get block {}: {}", hash, e);
+            (StatusCode::NOT_FOUND, "Block not found").into_response()
+        }
+    }
+}
+
+/// Block object in the esplora format
+#[derive(Clone, Serialize)]
+pub struct EsploraBlock {
+    pub id: BlockHash,
+    pub height: u64,
+    pub version: i32,
+    pub timestamp: u64,
+    pub tx_count: usize,
+    pub size: usize,
+    pub weight: usize,
+    pub merkle_root: String,
+    pub previousblockhash: Option<BlockHash>,
+    pub mediantime: Option<u64>,
+    pub nonce: u32,
+    pub bits: u32,
+    pub difficulty: f64,
+    /// Local arrival in seconds since epoch, for blocks seen since startup
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seen_at: Option<u64>,
+}
+
+/// Esplora summary of a block, from the cache when it was built before
+pub async fn esplora_block(
+    rpc: &Rpc,
+    cache: &BoundedCache<BlockHash, EsploraBlock>,
+    hash: &BlockHash,
+) -> Result<EsploraBlock, bitcoincore_rpc::Error> {
+    if let Some(block) = cache.get(hash) {
+        return Ok(block);
+    }
+    let block = rpc.get_block_info(hash).await?;
+    let summary = EsploraBlock {
+        id: block.hash,
+        height: block.height as u64,
+        version: block.version,
+        timestamp: block.time as u64,
+        tx_count: block.n_tx,
+        size: block.size,
+        weight: block.weight,
+        merkle_root: block.merkleroot.to_string(),
+        previousblockhash: block.previousblockhash,
+        mediantime: block.mediantime.map(|time| time as u64),
+        nonce: block.nonce,
+        bits: u32::from_str_radix(&block.bits, 16).unwrap_or_default(),
+        difficulty: block.difficulty,
+        seen_at: None,
+    };
+    cache.insert(*hash, summary.clone());
+    Ok(summary)
+}
+
+pub async fn get_block(
+    State(state): State<AppState>,
+    Path(hash): Path<String>,
+) -> impl IntoResponse {
+    let Ok(block_hash) = BlockHash::from_str(&hash) else {
+        return (StatusCode::BAD_REQUEST, "Invalid block hash").into_response();
+    };
+    match esplora_block(&state.rpc, &state.block_summaries, &block_hash).await {
+        Ok(mut block) => {
+            block.seen_at = state.propagation.seen_at(&block.id);
+            Json(block).into_response()
+        }
+        Err(e) => {
+            warn!("Failed to get block {}: {}", hash, e);
+            (StatusCode::NOT_FOUND, "Block not found").into_response()
+        }
+    }
+}
+
+/// Walks back from the tip or `start_height` along parent hashes, so a page of
+/// cached blocks costs a single RPC
+async fn esplora_blocks(
+    rpc: &Rpc,
+    cache: &BoundedCache<BlockHash, EsploraBlock>,
+    start_height: Option<u64>,
+) -> Result<Vec<EsploraBlock>, bitcoincore_rpc::Error> {
+    let mut hash = match start_height {
+        Some(height) => rpc.get_block_hash(height).await?,
+        None => rpc.get_best_block_hash().await?,
+    };
+    let mut blocks = Vec::with_capacity(ESPLORA_BLOCKS_PER_PAGE);
+    loop {
+        let block = esplora_block(rpc, cache, &hash).await?;
+        let previous = block.previousblockhash;
+        blocks.push(block);
+        match previous {
+            Some(previous) if blocks.len() < ESPLORA_BLOCKS_PER_PAGE => hash = previous,
+            _ => return Ok(blocks),
+        }
+    }
+}
+
+async fn esplora_blocks_page(state: AppState, start_height: Option<u64>) -> impl IntoResponse {
+    match esplora_blocks(&state.rpc, &state.block_summaries, start_height).await {
+        Ok(mut blocks) => {
+            for block in &mut blocks {
+                block.seen_at = state.propagation.seen_at(&block.id);
+            }
+            Json(blocks).into_response()
+        }
+        Err(e) => {
+            warn!("Failed to get blocks from height {:?}: {}", start_height, e);
+            (StatusCode::NOT_FOUND, "Block not found").into_response()
+        }
+    }
+}
+
+pub async fn get_blocks(State(state): State<AppState>) -> impl IntoResponse {
+    esplora_blocks_page(state, None).await
+}
+
+pub async fn get_blocks_from(
+    State(state): State<AppState>,
+    Path(height): Path<u64>,
+) -> impl IntoResponse {
+    esplora_blocks_page(state, Some(height)).await
+}
+
+pub async fn get_block_txids(
+    State(state): State<AppState>,
+    Path(hash): Path<String>,
+) -> impl IntoResponse {
+    let Ok(block_hash) = BlockHash::from_str(&hash) else {
+        return (StatusCode::BAD_REQUEST, "Invalid block hash").into_response();
+    };
+    match state.rpc.get_block_info(&block_hash).await {
+        Ok(block) => Json(block.tx).into_response(),
+        Err(e) => {
+            warn!("Failed to get txids of block {}: {}", hash, e);
+            (StatusCode::NOT_FOUND, "Block not found").into_response()
+        }
+    }
+}
+
+pub async fn get_block_header(
+    State(state): State<AppState>,
+    Path(hash): Path<String>,
+) -> impl IntoResponse {
+    let Ok(block_hash) = BlockHash::from_str(&hash) else {
+        return (StatusCode::BAD_REQUEST, "Invalid block hash").into_response();
+    };
+    if let Some(hex) = state
+        .headers
+        .as_ref()
+        .and_then(|chain| chain.header_hex(&block_hash))
+    {
+        return ([(header::CONTENT_TYPE, "text/plain")], hex).into_response();
+    }
+    match state
+        .rpc
+        .call::<String>("getblockheader", &[json!(block_hash), json!(false)])
+        .await
+    {
+        Ok(hex) => {
+            if let Some(chain) = &state.headers {
+                if let Err(e) = chain.verify_unknown(&block_hash, &hex) {
+                    warn!("Header of block {} failed verification: {:#}", hash, e);
+                    return (StatusCode::BAD_GATEWAY, "Header failed verification").into_response();
+                }
+            }
+            ([(header::CONTENT_TYPE, "text/plain")], hex).into_response()
+        }
+        Err(e) => {
+            warn!("Failed to get header of block {}: {}", hash, e);
+            (StatusCode::NOT_FOUND, "Block not found").into_response()
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct BlockStatus {
+    in_best_chain: bool,
+    height: u64,
+    /// Next block on the active chain, absent for the tip and stale blocks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_best: Option<BlockHash>,
+}
+
+pub async fn get_block_status(
+    State(state): State<AppState>,
+    Path(hash): Path<String>,
+) -> impl IntoResponse {
+    let Ok(block_hash) = BlockHash::from_str(&hash) else {
+        return (StatusCode::BAD_REQUEST, "Invalid block hash").into_response();
+    };
+    match state.rpc.get_block_header_info(&block_hash).await {
+        Ok(header) => {
+            // The node reports -1 confirmations for blocks off the active chain
+            let in_best_chain = header.confirmations >= 0;
+            Json(BlockStatus {
+                in_best_chain,
+                height: header.height as u64,
+                next_best: header.next_block_hash.filter(|_| in_best_chain),
+            })
+            .into_response()
+        }
+        Err(e) => {
+            warn!("Failed to get status of block {}: {}", hash, e);
+            (StatusCode::NOT_FOUND, "Block not found").into_response()
+        }
+    }
+}
+
+/// Up to `count` esplora transactions of a block from `start_index`, `None` when
+/// past the last one
+pub async fn block_txs(
+    rpc: &Rpc,
+    network: Network,
+    hash: &BlockHash,
+    start_index: usize,
+    count: usize,
+) -> Result<Option<Vec<EsploraTx>>, bitcoincore_rpc::Error> {
+    let block = rpc.get_block(hash).await?;
+    if start_index >= block.txdata.len() {
+        return Ok(None);
+    }
+    let status = block_status(rpc, hash).await?;
+    // Transactions within a block often spend each other's outputs
+    let mut parents = HashMap::new();
+    for tx in &block.txdata[..start_index] {
+        parents.insert(tx.compute_txid(), tx.clone());
+    }
+    let mut txs = Vec::with_capacity(count);
+    for tx in block.txdata.iter().skip(start_index).take(count) {
+        txs.push(esplora_tx_with_prevouts(rpc, network, tx, status.clone(), &mut parents).await?);
+        parents.insert(tx.compute_txid(), tx.clone());
+    }
+    Ok(Some(txs))
+}
+
+pub async fn get_block_txs(
+    State(state): State<AppState>,
+    Path((hash, start_index)): Path<(String, usize)>,
+) -> impl IntoResponse {
+    let Ok(block_hash) = BlockHash::from_str(&hash) else {
+        return (StatusCode::BAD_REQUEST, "Invalid block hash").into_response();
+    };
+    if start_index % TXS_PER_PAGE != 0 {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("start_index must be a multiple of {}", TXS_PER_PAGE),
+        )
+            .into_response();
+    }
+    match block_txs(
+        &state.rpc,
+        state.network,
+        &block_hash,
+        start_index,
+        TXS_PER_PAGE,
+    )
+    .await
+    {
+        Ok(Some(txs)) => Json(txs).into_response(),
+        Ok(None) => (StatusCode::BAD_REQUEST, "start_index out of range").into_response(),
+        Err(e) => {
+            warn!("Failed to get transactions of block {}: {}", hash, e);
+            (StatusCode::NOT_FOUND, "Block not found").into_response()
+        }
+    }
+}
+
+/// Accepts either a block hash or a height
+async fn resolve_block_id(
+    rpc: &Rpc,
+    id: &str,
+) -> Result<Option<BlockHash>, bitcoincore_rpc::Error> {
+    if let Ok(hash) = BlockHash::from_str(id) {
+        return Ok(Some(hash));
+    }
+    match id.parse::<u64>() {
+        Ok(height) => rpc.get_block_hash(height).await.map(Some),
+        Err(_) => Ok(None),
+    }
+}
+
+/// A fee rate band of a block's transactions, coinbase excluded
+#[derive(Clone, Serialize)]
+pub struct FeeBucket {
+    /// Inclusive lower bound in sat/vB
+    #[serde(serialize_with = "json::rate")]
+    min_fee_rate: f64,
+    /// Exclusive upper bound in sat/vB, absent for the top band
+    #[serde(serialize_with = "json::opt_rate")]
+    max_fee_rate: Option<f64>,
+    tx_count: usize,
+    vsize: u64,
+    /// Fees paid in sats
+    fees: u64,
+}
+
+#[derive(Deserialize)]
+struct VerboseBlock {
+    tx: Vec<VerboseBlockTx>,
+}
+
+#[derive(Deserialize)]
+struct VerboseBlockTx {
+    vsize: u64,
+    /// Only present when the node still has undo data to resolve the prevouts
+    #[serde(default, with = "bitcoincore_rpc::bitcoin::amount::serde::as_btc::opt")]
+    fee: Option<Amount>,
+}
+
+async fn fee_histogram(
+    rpc: &Rpc,
+    hash: &BlockHash,
+) -> Result<Vec<FeeBucket>, bitcoincore_rpc::Error> {
+    // Verbosity 2 lets the node resolve every input's prevout and report per-tx fees
+    let block: VerboseBlock = rpc.call("getblock", &[json!(hash), json!(2)]).await?;
+
+    let mut buckets: Vec<FeeBucket> = FEE_HISTOGRAM_BANDS
+        .iter()
+        .enumerate()
+        .map(|(i, &min_fee_rate)| FeeBucket {
+            min_fee_rate,
+            max_fee_rate: FEE_HISTOGRAM_BANDS.get(i + 1).copied(),
+            tx_count: 0,
+            vsize: 0,
+            fees: 0,
+        })
+        .collect();
+    for tx in block.tx.iter().skip(1) {
+        let Some(fee) = tx.fee else {
+            continue;
+        };
+        let fee_rate = fee.to_sat() as f64 / tx.vsize as f64;
+        let band = FEE_HISTOGRAM_BANDS
+            .iter()
+            .rposition(|&min| fee_rate >= min)
+            .unwrap_or(0);
+        let bucket = &mut buckets[band];
+        bucket.tx_count += 1;
+        bucket.vsize += tx.vsize;
+        bucket.fees += fee.to_sat();
+    }
+    buckets.retain(|bucket| bucket.tx_count > 0);
+    Ok(buckets)
+}
+
+/// Returns `None` if `id` is neither a block hash nor a height
+async fn cached_fee_histogram(
+    state: &AppState,
+    id: &str,
+) -> Result<Option<Arc<Vec<FeeBucket>>>, bitcoincore_rpc::Error> {
+    let Some(hash) = resolve_block_id(&state.rpc, id).await? else {
+        return Ok(None);
+    };
+    if let Some(histogram) = state.fee_histograms.get(&hash) {
+        return Ok(Some(histogram));
+    }
+    let histogram = Arc::new(fee_histogram(&state.rpc, &hash).await?);
+    state.fee_histograms.insert(hash, histogram.clone());
+    Ok(Some(histogram))
+}
+
+pub async fn get_block_fee_histogram(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match cached_fee_histogram(&state, &id).await {
+        Ok(Some(histogram)) => Json(histogram.as_ref().clone()).into_response(),
+        Ok(None) => (StatusCode::BAD_REQUEST, "Invalid block id").into_response(),
+        Err(e) => {
+            warn!("Failed to get fee histogram for block {}: {}", id, e);
+            (StatusCode::NOT_FOUND, "Block not found").into_response()
+        }
+    }
+}
+
+/// The node's REST interface (`-rest`), which serves raw blocks as binary
+pub struct NodeRest {
+    client: OutboundClient,
+    base: Url,
+}
+
+impl NodeRest {
+    pub fn new(client: OutboundClient, base: Url) -> Self {
+        Self { client, base }
+    }
+
+    /// Streams the block straight from the node, `None` when REST can't serve it
+    async fn block(&self, hash: &BlockHash) -> Option<Response> {
+        let url = self.base.join(&format!("rest/block/{}.bin", hash)).ok()?;
+        let request = self.client.request(Method::GET, url);
+        match self.client.send("node_rest", request).await {
+            Ok(response) if response.status().is_success() => Some(
+                (
+                    [(header::CONTENT_TYPE, "application/octet-stream")],
+                    Body::from_stream(response.bytes_stream()),
+                )
+                    .into_response(),
+            ),
+            Ok(response) => {
+                debug!(
+                    "Node REST answered block {} with {}",
+                    hash,
+                    response.status()
+                );
+                None
+            }
+            Err(e) => {
+                debug!("Node REST failed for block {}: {}", hash, e);
+                None
+            }
+        }
+    }
+}
+
+/// Serves a raw block as a chunked binary body. Streams from the node's REST
+/// interface when configured. Otherwise the RPC answer, the whole block as hex
+/// (twice its size), is still loaded into memory first; only the binary body
+/// is sent chunk by chunk, so memory is only bounded with REST.
+pub async fn raw_block_binary(state: &AppState, hash: BlockHash) -> Response {
+    if let Some(response) = match &state.rest {
+        Some(rest) => rest.block(&hash).await,
+        None => None,
+    } {
+        return response;
+    }
+    match state.rpc.get_block_hex(&hash).await {
+        Ok(hex) => {
+            let len = hex.len();
+            let chunks = (0..len).step_by(RAW_BLOCK_CHUNK * 2).map(move |start| {
+                let end = (start + RAW_BLOCK_CHUNK * 2).min(len);
+                let chunk = hex
+                    .get(start..end)
+                    .ok_or_else(|| anyhow!("Node returned non-hex block data"))?;
+                Ok::<_, anyhow::Error>(Vec::<u8>::from_hex(chunk)?)
+            });
+            (
+                [(header::CONTENT_TYPE, "application/octet-stream")],
+                Body::from_stream(futures_util::stream::iter(chunks)),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            warn!("Failed to get raw block for hash {}: {}", hash, e);
+            (StatusCode::NOT_FOUND, "Block not found").into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoincore_rpc::bitcoin::absolute::LockTime;
+    use bitcoincore_rpc::bitcoin::transaction::Version;
+    use bitcoincore_rpc::bitcoin::{ScriptBuf, TxIn, Txid};
+
+    use super::*;
+    use crate::golden;
+
+    const HASH: &str = "000000000000000000024bead8df69990852c202db0e0097c1a12ea637d7e96d";
+    const PREVIOUS: &str = "0000000000000000000320283a032748cef8227873ff4872689bf23f1cda83a5";
+    const MERKLE_ROOT: &str = "031b417c3a1828ddf3d6527fc210daafcc9218e81f98257f88d4d43bd7a5894f";
+
+    #[test]
+    fn golden_block() {
+        golden::check(
+            "block",
+            &EsploraBlock {
+                id: HASH.parse().unwrap(),
+                height: 840000,
+                version: 710926336,
+                timestamp: 1713571767,
+                tx_count: 3050,
+                size: 2325617,
+                weight: 3993281,
+                merkle_root: MERKLE_ROOT.to_owned(),
+                previousblockhash: Some(PREVIOUS.parse().unwrap()),
+                mediantime: Some(1713568954),
+                nonce: 3932395645,
+                bits: 0x17034219,
+                difficulty: 86388558925171.02,
+                seen_at: Some(1713571775),
+            },
+        );
+    }
+
+    #[test]
+    fn golden_v1_blocks() {
+        let mut script_sig = Vec::from_hex("03400d0c00").unwrap();
+        script_sig.extend_from_slice(b"/ViaBTC/Mined by satoshi/");
+        let coinbase = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                script_sig: ScriptBuf::from_bytes(script_sig),
+                ..TxIn::default()
+            }],
+            output: Vec::new(),
+        };
+        golden::check(
+            "v1_blocks",
+            &[ExtendedBlock {
+                id: HASH.parse().unwrap(),
+                height: 840000,
+                version: 710926336,
+                timestamp: 1713571767,
+                bits: 0x17034219,
+                nonce: 3932395645,
+                difficulty: 86388558925171.02,
+                merkle_root: MERKLE_ROOT.to_owned(),
+                tx_count: 3050,
+                size: 2325617,
+                weight: 3993281,
+                previousblockhash: Some(PREVIOUS.parse().unwrap()),
+                mediantime: Some(1713568954),
+                seen_at: None,
+                extras: BlockExtras {
+                    total_fees: 3762798213,
+                    median_fee: 300,
+                    fee_range: [40, 100, 150, 300, 600, 1200, 201000],
+                    reward: 3887798213,
+                    avg_fee: 1234100,
+                    avg_fee_rate: 3771,
+                    coinbase_raw: Some(coinbase.input[0].script_sig.to_hex_string()),
+                    pool: identify_pool(Some(&coinbase)),
+                },
+            }],
+        );
+    }
+
+    #[test]
+    fn golden_block_status() {
+        golden::check(
+            "block_status",
+            &BlockStatus {
+                in_best_chain: true,
+                height: 840000,
+                next_best: None,
+            },
+        );
+    }
+
+    #[test]
+    fn golden_block_txids() {
+        let txids: Vec<Txid> = [
+            "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16",
+            "a1075db55d416d3ca199f55b6084e2115b9345e16c5cf302fc80e9d5fbf5d48d",
+        ]
+        .iter()
+        .map(|txid| txid.parse().unwrap())
+        .collect();
+        golden::check("block_txids", &txids);
+    }
+
+    #[test]
+    fn golden_block_fee_histogram() {
+        golden::check(
+            "block_fee_histogram",
+            &[
+                FeeBucket {
+                    min_fee_rate: 900.0,
+                    max_fee_rate: Some(1000.0),
+                    tx_count: 3,
+                    vsize: 675,
+                    fees: 635120,
+                },
+                FeeBucket {
+                    min_fee_rate: 2000.0,
+                    max_fee_rate: None,
+                    tx_count: 12,
+                    vsize: 4511,
+                    fees: 8511232,
+                },
+            ],
+        );
+    }
+}
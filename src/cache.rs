@@ -0,0 +1,112 @@
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+
+use bitcoincore_rpc::bitcoin::BlockHash;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{debug, warn};
+
+use crate::chain::BlockEvent;
+
+/// Small thread-safe map that forgets its oldest entries beyond `capacity`.
+///
+/// Meant for data keyed by block hash, which never changes once computed. The
+/// entries of blocks a reorg disconnects are dropped all the same, see
+/// [`evict_reorged`], so nothing derived from a stale block outlives the reorg.
+pub struct BoundedCache<K, V> {
+    capacity: usize,
+    inner: Mutex<Inner<K, V>>,
+}
+
+struct Inner<K, V> {
+    entries: HashMap<K, V>,
+    order: VecDeque<K>,
+}
+
+impl<K: Clone + Eq + Hash, V: Clone> BoundedCache<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new(Inner {
+                entries: HashMap::with_capacity(capacity),
+                order: VecDeque::with_capacity(capacity),
+            }),
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let inner = self.inner.lock().expect("cache lock poisoned");
+        inner.entries.get(key).cloned()
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        let mut inner = self.inner.lock().expect("cache lock poisoned");
+        if inner.entries.insert(key.clone(), value).is_none() {
+            inner.order.push_back(key);
+        }
+        while inner.order.len() > self.capacity {
+            if let Some(oldest) = inner.order.pop_front() {
+                inner.entries.remove(&oldest);
+            }
+        }
+    }
+
+    pub fn remove(&self, key: &K) {
+        let mut inner = self.inner.lock().expect("cache lock poisoned");
+        if inner.entries.remove(key).is_some() {
+            inner.order.retain(|entry| entry != key);
+        }
+    }
+
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().expect("cache lock poisoned");
+        inner.entries.clear();
+        inner.order.clear();
+    }
+}
+
+/// Data derived under a block, dropped once the block leaves the best chain
+pub trait BlockCache: Send + Sync {
+    /// Drops whatever was derived under `hash`
+    fn evict(&self, hash: &BlockHash);
+
+    /// Drops everything, when it's unknown which blocks were disconnected
+    fn clear(&self);
+}
+
+impl<V: Clone + Send> BlockCache for BoundedCache<BlockHash, V> {
+    fn evict(&self, hash: &BlockHash) {
+        self.remove(hash);
+    }
+
+    fn clear(&self) {
+        BoundedCache::clear(self);
+    }
+}
+
+/// Evicts the blocks every reorg announced on `blocks` disconnects from
+/// `caches`, clearing them when events were missed
+pub async fn evict_reorged(
+    mut blocks: broadcast::Receiver<BlockEvent>,
+    caches: Vec<Arc<dyn BlockCache>>,
+) {
+    loop {
+        match blocks.recv().await {
+            Ok(block) => {
+                for hash in &block.disconnected {
+                    debug!("Evicting reorged block {} from caches", hash);
+                    for cache in &caches {
+                        cache.evict(hash);
+                    }
+                }
+            }
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("Cache eviction skipped {} blocks, clearing caches", skipped);
+                for cache in &caches {
+                    cache.clear();
+                }
+            }
+            Err(RecvError::Closed) => return,
+        }
+    }
+}
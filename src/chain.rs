@@ -0,0 +1,218 @@
+//! Polls the node for new blocks and fans them out to background subsystems.
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use bitcoincore_rpc::bitcoin::{BlockHash, Network};
+use tokio::sync::{broadcast, Notify};
+use tracing::{info, warn};
+
+use crate::health::{Health, Severity};
+use crate::rpc::Rpc;
+
+const HEALTH_COMPONENT: &str = "chain_watcher";
+
+/// Events buffered per subscriber before slow ones start lagging
+const EVENT_BUFFER: usize = 64;
+
+/// A block that became part of the best chain
+#[derive(Clone, Debug)]
+pub struct BlockEvent {
+    pub height: u64,
+    pub hash: BlockHash,
+    /// When the poll that found the block returned
+    pub seen_at: SystemTime,
+    /// Blocks of the previous best chain disconnected by a reorg, tip first,
+    /// set on the first block connected after it and empty otherwise
+    pub disconnected: Vec<BlockHash>,
+}
+
+pub struct ChainWatcher {
+    sender: broadcast::Sender<BlockEvent>,
+    /// Triggers a poll before the next tick, for block notifications
+    wake: Arc<Notify>,
+}
+
+impl ChainWatcher {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUFFER);
+        Self {
+            sender,
+            wake: Arc::new(Notify::new()),
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<BlockEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Handle that makes the watcher poll right away when notified
+    pub fn waker(&self) -> Arc<Notify> {
+        self.wake.clone()
+    }
+
+    /// Polls the best block hash every `interval`, and whenever woken, and emits
+    /// an event for every block connected since the previous poll. The tip at
+    /// startup is not emitted.
+    pub async fn run(self: Arc<Self>, rpc: Arc<Rpc>, interval: Duration, health: Arc<Health>) {
+        health.register(HEALTH_COMPONENT, Severity::Soft, Some(interval * 3));
+        let mut ticker = tokio::time::interval(interval);
+        let mut tip: Option<BlockHash> = None;
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = self.wake.notified() => {}
+            }
+            match self.poll(&rpc, &mut tip).await {
+                Ok(()) => health.success(HEALTH_COMPONENT),
+                Err(e) => {
+                    warn!("Failed to poll for new blocks: {}", e);
+                    health.failure(HEALTH_COMPONENT, &e);
+                }
+            }
+        }
+    }
+
+    /// Emits an event for every block connected on top of `tip`, moving it to
+    /// the best block. Nothing is emitted while `tip` is still unknown.
+    async fn poll(
+        &self,
+        rpc: &Rpc,
+        tip: &mut Option<BlockHash>,
+    ) -> Result<(), bitcoincore_rpc::Error> {
+        let (blocks, mut disconnected) = poll_new_blocks(rpc, *tip).await?;
+        let seen_at = SystemTime::now();
+        if !disconnected.is_empty() {
+            warn!("Reorg disconnected {} blocks", disconnected.len());
+        }
+        for (height, hash) in blocks {
+            if tip.is_some() {
+                info!("New block {} at height {}", hash, height);
+                // Nobody listening is fine, subscribers come and go
+                let _ = self.sender.send(BlockEvent {
+                    height,
+                    hash,
+                    seen_at,
+                    disconnected: std::mem::take(&mut disconnected),
+                });
+            }
+            *tip = Some(hash);
+        }
+        Ok(())
+    }
+}
+
+/// Network the backend node runs on
+pub async fn node_network(rpc: &Rpc) -> anyhow::Result<Network> {
+    Ok(rpc.get_blockchain_info().await?.chain)
+}
+
+/// Height and hash of the node's best block, from a single call so they can't
+/// disagree mid-reorg
+pub async fn tip(rpc: &Rpc) -> Result<(u64, BlockHash), bitcoincore_rpc::Error> {
+    let info = rpc.get_blockchain_info().await?;
+    Ok((info.blocks, info.best_block_hash))
+}
+
+/// Returns the blocks connected on top of `previous`, oldest first, and the
+/// blocks up to `previous` that were disconnected, tip first. After a reorg
+/// this restarts right above the last block of the previous chain that survived.
+async fn poll_new_blocks(
+    rpc: &Rpc,
+    previous: Option<BlockHash>,
+) -> Result<(Vec<(u64, BlockHash)>, Vec<BlockHash>), bitcoincore_rpc::Error> {
+    let best_hash = rpc.get_best_block_hash().await?;
+    if previous == Some(best_hash) {
+        return Ok((Vec::new(), Vec::new()));
+    }
+    let best_height = rpc.get_block_header_info(&best_hash).await?.height as u64;
+    let Some(previous_hash) = previous else {
+        return Ok((vec![(best_height, best_hash)], Vec::new()));
+    };
+
+    // Blocks that are no longer part of the best chain report -1 confirmations
+    let mut ancestor = rpc.get_block_header_info(&previous_hash).await?;
+    let mut disconnected = Vec::new();
+    while ancestor.confirmations < 0 {
+        disconnected.push(ancestor.hash);
+        match ancestor.previous_block_hash {
+            Some(hash) => ancestor = rpc.get_block_header_info(&hash).await?,
+            None => break,
+        }
+    }
+
+    let mut blocks = Vec::new();
+    for height in (ancestor.height as u64 + 1)..best_height {
+        blocks.push((height, rpc.get_block_hash(height).await?));
+    }
+    blocks.push((best_height, best_hash));
+    Ok((blocks, disconnected))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::esplora_block;
+    use crate::cache::{evict_reorged, BoundedCache};
+    use crate::mock::MockNode;
+
+    #[tokio::test]
+    async fn reports_blocks_disconnected_by_a_reorg() {
+        let node = MockNode::new(10);
+        let rpc = node.rpc();
+        let tip = node.tip();
+
+        let disconnected = node.reorg(2, 3);
+        let (blocks, reported) = poll_new_blocks(&rpc, Some(tip)).await.unwrap();
+        assert_eq!(reported, disconnected);
+        assert_eq!(reported[0], tip);
+        let heights: Vec<u64> = blocks.iter().map(|(height, _)| *height).collect();
+        assert_eq!(heights, [9, 10, 11]);
+        assert_eq!(blocks[2].1, node.tip());
+        assert!(blocks.iter().all(|(_, hash)| !disconnected.contains(hash)));
+    }
+
+    #[tokio::test]
+    async fn reports_nothing_disconnected_without_a_reorg() {
+        let node = MockNode::new(10);
+        let rpc = node.rpc();
+        let tip = node.tip();
+
+        let mined = node.mine(2);
+        let (blocks, disconnected) = poll_new_blocks(&rpc, Some(tip)).await.unwrap();
+        assert!(disconnected.is_empty());
+        assert_eq!(blocks, [(11, mined[0]), (12, mined[1])]);
+    }
+
+    #[tokio::test]
+    async fn reorg_evicts_stale_blocks_from_caches() {
+        let node = MockNode::new(10);
+        let rpc = node.rpc();
+        let watcher = ChainWatcher::new();
+        let summaries = Arc::new(BoundedCache::new(64));
+        for height in 0..=10 {
+            esplora_block(&rpc, &summaries, &node.block_hash(height))
+                .await
+                .unwrap();
+        }
+        let mut tip = None;
+        watcher.poll(&rpc, &mut tip).await.unwrap();
+        let eviction = tokio::spawn(evict_reorged(watcher.subscribe(), vec![summaries.clone()]));
+
+        let disconnected = node.reorg(2, 3);
+        watcher.poll(&rpc, &mut tip).await.unwrap();
+        // Closes the event channel, so eviction returns once it has seen every event
+        drop(watcher);
+        eviction.await.unwrap();
+
+        for hash in &disconnected {
+            assert!(summaries.get(hash).is_none(), "{} still cached", hash);
+        }
+        assert!(summaries.get(&node.block_hash(8)).is_some());
+        let block = esplora_block(&rpc, &summaries, &node.block_hash(10))
+            .await
+            .unwrap();
+        assert_eq!(block.previousblockhash, Some(node.block_hash(9)));
+        assert!(!disconnected.contains(&block.id));
+    }
+}
@@ -0,0 +1,197 @@
+//! Operator-pinned checkpoints.
+//!
+//! The backend's best chain is checked against known-good block hashes at
+//! startup and on every new block. Until a check passes, and whenever the node
+//! contradicts a checkpoint, API requests are refused so a wrong or attacked
+//! node can't feed clients a different chain. Health endpoints stay available.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use anyhow::{bail, Context};
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use bitcoincore_rpc::bitcoin::BlockHash;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{error, info, warn};
+
+use crate::chain::ChainWatcher;
+use crate::health::{Health, Severity};
+use crate::rpc::Rpc;
+
+const HEALTH_COMPONENT: &str = "checkpoints";
+
+/// Paths still served while the chain is untrusted, so the alert can be inspected
+const EXEMPT_PATHS: &[&str] = &["/readyz", "/api/v1/health/details"];
+
+/// Known-good block hashes by height
+#[derive(Clone, Debug)]
+pub struct Checkpoints(BTreeMap<u64, BlockHash>);
+
+/// Parses `height:hash` pairs separated by commas
+pub fn parse_checkpoints(s: &str) -> anyhow::Result<Checkpoints> {
+    let mut checkpoints = BTreeMap::new();
+    for entry in s
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        let Some((height, hash)) = entry.split_once(':') else {
+            bail!("Invalid checkpoint {:?}, expected height:hash", entry);
+        };
+        let height: u64 = height
+            .parse()
+            .with_context(|| format!("Invalid checkpoint height {:?}", height))?;
+        let hash = BlockHash::from_str(hash)
+            .with_context(|| format!("Invalid checkpoint hash {:?}", hash))?;
+        checkpoints.insert(height, hash);
+    }
+    Ok(Checkpoints(checkpoints))
+}
+
+pub struct CheckpointGuard {
+    checkpoints: Checkpoints,
+    /// Set while the latest check passed
+    trusted: AtomicBool,
+}
+
+impl CheckpointGuard {
+    pub fn new(checkpoints: Checkpoints) -> Self {
+        Self {
+            checkpoints,
+            trusted: AtomicBool::new(false),
+        }
+    }
+
+    pub async fn run(
+        self: Arc<Self>,
+        rpc: Arc<Rpc>,
+        watcher: Arc<ChainWatcher>,
+        health: Arc<Health>,
+    ) {
+        health.register(HEALTH_COMPONENT, Severity::Hard, None);
+        let mut blocks = watcher.subscribe();
+        loop {
+            match self.check(&rpc).await {
+                Ok(None) => {
+                    if !self.trusted.swap(true, Ordering::Relaxed) {
+                        info!(
+                            "Backend chain matches {} checkpoints",
+                            self.checkpoints.0.len()
+                        );
+                    }
+                    metrics::gauge!("checkpoint_mismatch").set(0.0);
+                    health.success(HEALTH_COMPONENT);
+                }
+                Ok(Some(contradiction)) => {
+                    self.trusted.store(false, Ordering::Relaxed);
+                    error!("Refusing to serve requests: {}", contradiction);
+                    metrics::gauge!("checkpoint_mismatch").set(1.0);
+                    health.failure(HEALTH_COMPONENT, &contradiction);
+                }
+                // The verdict stands until the node can be asked again
+                Err(e) => warn!("Failed to check checkpoints: {}", e),
+            }
+            // A reorg is announced as a new block too, so this sees every tip change
+            match blocks.recv().await {
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => return,
+            }
+        }
+    }
+
+    /// Describes the first checkpoint the node's best chain contradicts. Checkpoints
+    /// above the node's tip can't be contradicted yet.
+    async fn check(&self, rpc: &Rpc) -> Result<Option<String>, bitcoincore_rpc::Error> {
+        let tip = rpc.get_block_count().await?;
+        for (&height, expected) in self.checkpoints.0.range(..=tip) {
+            let actual = rpc.get_block_hash(height).await?;
+            if actual != *expected {
+                return Ok(Some(format!(
+                    "Backend has block {} at height {}, checkpoint is {}",
+                    actual, height, expected
+                )));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Refuses API requests while the backend chain isn't trusted
+pub async fn require_trusted_chain(
+    State(guard): State<Arc<CheckpointGuard>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if guard.trusted.load(Ordering::Relaxed) || EXEMPT_PATHS.contains(&req.uri().path()) {
+        return next.run(req).await;
+    }
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        "Backend chain not verified against checkpoints",
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockNode;
+
+    /// Mainnet genesis and Bitcoin Core's first checkpoint
+    const MAINNET: &str = "0:000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f, 11111:0000000069e244f73d78e8fd29ba2fd2ed618bd6fa2ee92559f542fdb26e7c1d,";
+
+    #[test]
+    fn parses_height_hash_pairs() {
+        let checkpoints = parse_checkpoints(MAINNET).unwrap();
+        let heights: Vec<u64> = checkpoints.0.keys().copied().collect();
+        assert_eq!(heights, [0, 11111]);
+        assert_eq!(
+            checkpoints.0[&11111].to_string(),
+            "0000000069e244f73d78e8fd29ba2fd2ed618bd6fa2ee92559f542fdb26e7c1d"
+        );
+        assert!(parse_checkpoints("").unwrap().0.is_empty());
+    }
+
+    #[test]
+    fn rejects_malformed_checkpoints() {
+        for invalid in [
+            "11111",
+            "x:0000000069e244f73d78e8fd29ba2fd2ed618bd6fa2ee92559f542fdb26e7c1d",
+            "11111:0000000069e244f7",
+        ] {
+            assert!(parse_checkpoints(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_the_first_contradicted_checkpoint() {
+        let node = MockNode::new(10);
+        let rpc = node.rpc();
+        let spec = format!(
+            "5:{},9:{},100:{}",
+            node.block_hash(5),
+            node.block_hash(9),
+            node.block_hash(1)
+        );
+        let guard = CheckpointGuard::new(parse_checkpoints(&spec).unwrap());
+        // Height 100 is beyond the tip, so it can't be contradicted yet
+        assert_eq!(guard.check(&rpc).await.unwrap(), None);
+
+        let stale = node.block_hash(9);
+        node.reorg(3, 4);
+        let contradiction = guard.check(&rpc).await.unwrap().unwrap();
+        assert_eq!(
+            contradiction,
+            format!(
+                "Backend has block {} at height 9, checkpoint is {}",
+                node.block_hash(9),
+                stale
+            )
+        );
+    }
+}
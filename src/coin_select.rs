@@ -0,0 +1,377 @@
+//! Coin selection for thin clients.
+//!
+//! Picks inputs among the unspent outputs of a set of addresses, as known to
+//! the address index and the mempool mirror. Branch and bound looks for an
+//! input set that pays the target without a change output, wasting at most the
+//! cost of creating and later spending change; when there is none, the largest
+//! outputs are taken until the target and a change output are covered.
+//!
+//! Sizes are estimates for single-key spends (key path for taproot) paying one
+//! recipient output of up to 43 vB, the size of a taproot or P2WSH output.
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use bitcoincore_rpc::bitcoin::{OutPoint, Script, Txid};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use utoipa::ToSchema;
+
+use crate::addresses::{self, script_hash};
+use crate::AppState;
+
+/// Most addresses whose outputs are considered in one request
+const ADDRESSES_LIMIT: usize = 100;
+
+/// Version, locktime, input and output counts and the segwit marker
+const TX_OVERHEAD_VSIZE: f64 = 10.5;
+
+/// The recipient output, sized for the largest standard script
+const RECIPIENT_OUTPUT_VSIZE: f64 = 43.0;
+
+/// Change below this is left to the fee rather than creating a dust output
+const DUST_LIMIT: u64 = 546;
+
+/// Search steps before branch and bound gives up
+const BNB_TRIES: u32 = 100_000;
+
+/// Vsize of spending an output of `script` and of creating one, for the script
+/// types coins can be selected from
+fn script_vsizes(script: &Script) -> Option<(f64, f64)> {
+    if script.is_p2wpkh() {
+        Some((68.0, 31.0))
+    } else if script.is_p2tr() {
+        Some((57.5, 43.0))
+    } else if script.is_p2pkh() {
+        Some((148.0, 34.0))
+    } else if script.is_p2sh() {
+        // Assumed to wrap P2WPKH
+        Some((91.0, 32.0))
+    } else {
+        None
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CoinSelectRequest {
+    addresses: Vec<String>,
+    /// Amount to pay in sats
+    amount: u64,
+    /// Fee rate in sat/vB
+    fee_rate: f64,
+}
+
+#[derive(Clone, Copy)]
+struct Candidate {
+    outpoint: OutPoint,
+    value: u64,
+    /// Value minus the fee of spending it
+    effective_value: u64,
+    input_vsize: f64,
+}
+
+#[derive(Serialize)]
+struct SelectedInput {
+    txid: Txid,
+    vout: u32,
+    value: u64,
+}
+
+#[derive(Serialize)]
+struct Selection {
+    inputs: Vec<SelectedInput>,
+    /// Change sent back to the first address, 0 without a change output
+    change: u64,
+    fee: u64,
+    vsize: u64,
+    /// `bnb` for a change-free match, `largest-first` otherwise
+    algorithm: &'static str,
+}
+
+/// Indexes of the candidates, sorted by decreasing effective value, whose
+/// effective values sum up to between `target` and `target + cost_of_change`,
+/// preferring the smallest excess
+fn branch_and_bound(
+    candidates: &[Candidate],
+    target: u64,
+    cost_of_change: u64,
+) -> Option<Vec<usize>> {
+    struct Search<'a> {
+        candidates: &'a [Candidate],
+        target: u64,
+        upper: u64,
+        tries: u32,
+        selected: Vec<usize>,
+        best: Option<(u64, Vec<usize>)>,
+    }
+
+    impl Search<'_> {
+        fn step(&mut self, i: usize, sum: u64, remaining: u64) {
+            if self.tries == 0 || sum > self.upper {
+                return;
+            }
+            self.tries -= 1;
+            if sum >= self.target {
+                let excess = sum - self.target;
+                if self.best.as_ref().is_none_or(|(best, _)| excess < *best) {
+                    self.best = Some((excess, self.selected.clone()));
+                }
+                return;
+            }
+            if i == self.candidates.len() || sum + remaining < self.target {
+                return;
+            }
+            let value = self.candidates[i].effective_value;
+            self.selected.push(i);
+            self.step(i + 1, sum + value, remaining - value);
+            self.selected.pop();
+            self.step(i + 1, sum, remaining - value);
+        }
+    }
+
+    let mut search = Search {
+        candidates,
+        target,
+        upper: target + cost_of_change,
+        tries: BNB_TRIES,
+        selected: Vec::new(),
+        best: None,
+    };
+    let total = candidates.iter().map(|c| c.effective_value).sum();
+    search.step(0, 0, total);
+    search.best.map(|(_, selected)| selected)
+}
+
+fn fee(vsize: f64, fee_rate: f64) -> u64 {
+    (vsize * fee_rate).ceil() as u64
+}
+
+/// Selects inputs among `candidates` (sorted by decreasing effective value) for
+/// paying `amount`; `None` when they don't cover it
+fn select(
+    candidates: &[Candidate],
+    amount: u64,
+    fee_rate: f64,
+    (change_spend_vsize, change_output_vsize): (f64, f64),
+) -> Option<Selection> {
+    let base_vsize = TX_OVERHEAD_VSIZE + RECIPIENT_OUTPUT_VSIZE;
+    let target = amount + fee(base_vsize, fee_rate);
+    let change_output_fee = fee(change_output_vsize, fee_rate);
+    let cost_of_change = change_output_fee + fee(change_spend_vsize, fee_rate);
+
+    let (selected, algorithm) = match branch_and_bound(candidates, target, cost_of_change) {
+        Some(selected) => (selected, "bnb"),
+        None => {
+            let mut selected = Vec::new();
+            let mut sum = 0;
+            for (i, candidate) in candidates.iter().enumerate() {
+                if sum >= target + change_output_fee + DUST_LIMIT {
+                    break;
+                }
+                selected.push(i);
+                sum += candidate.effective_value;
+            }
+            if sum < target {
+                return None;
+            }
+            (selected, "largest-first")
+        }
+    };
+
+    let inputs: Vec<&Candidate> = selected.iter().map(|&i| &candidates[i]).collect();
+    let input_value: u64 = inputs.iter().map(|c| c.value).sum();
+    let input_vsize: f64 = inputs.iter().map(|c| c.input_vsize).sum();
+    let effective: u64 = inputs.iter().map(|c| c.effective_value).sum();
+    let excess = effective - target;
+    let (change, vsize) =
+        if algorithm == "largest-first" && excess >= change_output_fee + DUST_LIMIT {
+            (
+                excess - change_output_fee,
+                base_vsize + input_vsize + change_output_vsize,
+            )
+        } else {
+            // Excess too small for change is left to the fee
+            (0, base_vsize + input_vsize)
+        };
+    Some(Selection {
+        inputs: inputs
+            .iter()
+            .map(|c| SelectedInput {
+                txid: c.outpoint.txid,
+                vout: c.outpoint.vout,
+                value: c.value,
+            })
+            .collect(),
+        change,
+        fee: input_value - amount - change,
+        vsize: vsize.ceil() as u64,
+        algorithm,
+    })
+}
+
+pub async fn post_coin_select(
+    State(state): State<AppState>,
+    Json(request): Json<CoinSelectRequest>,
+) -> impl IntoResponse {
+    if request.addresses.is_empty() || request.addresses.len() > ADDRESSES_LIMIT {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("Expected 1 to {} addresses", ADDRESSES_LIMIT),
+        )
+            .into_response();
+    }
+    if request.amount == 0 || !request.fee_rate.is_finite() || request.fee_rate < 0.0 {
+        return (
+            StatusCode::BAD_REQUEST,
+            "Invalid amount or fee rate".to_string(),
+        )
+            .into_response();
+    }
+    let mut scripts = Vec::with_capacity(request.addresses.len());
+    for address in &request.addresses {
+        let script = match addresses::parse_address(&state, address) {
+            Ok(address) => address.script_pubkey(),
+            Err(response) => return response.into_response(),
+        };
+        let Some(vsizes) = script_vsizes(&script) else {
+            return (
+                StatusCode::BAD_REQUEST,
+                format!("Unsupported address type {}", address),
+            )
+                .into_response();
+        };
+        scripts.push((script_hash(&script), vsizes));
+    }
+    let index = match addresses::ready_index(&state) {
+        Ok(index) => index,
+        Err(response) => return response.into_response(),
+    };
+
+    let fee_rate = request.fee_rate;
+    let selection = async {
+        let mut candidates = Vec::new();
+        for (script, (input_vsize, _)) in &scripts {
+            for (outpoint, value) in addresses::spendable(&state, index.clone(), *script).await? {
+                // Outputs costing more to spend than they are worth are left alone
+                let spend_fee = fee(*input_vsize, fee_rate);
+                if value > spend_fee {
+                    candidates.push(Candidate {
+                        outpoint,
+                        value,
+                        effective_value: value - spend_fee,
+                        input_vsize: *input_vsize,
+                    });
+                }
+            }
+        }
+        candidates.sort_by_key(|c| std::cmp::Reverse(c.effective_value));
+        Ok::<_, anyhow::Error>(select(&candidates, request.amount, fee_rate, scripts[0].1))
+    };
+    match selection.await {
+        Ok(Some(selection)) => Json(selection).into_response(),
+        Ok(None) => (StatusCode::BAD_REQUEST, "Insufficient funds".to_string()).into_response(),
+        Err(e) => {
+            warn!("Failed to select coins: {:#}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Index error".to_string()).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden;
+    use bitcoincore_rpc::bitcoin::hashes::Hash;
+
+    /// P2WPKH spend and output vsizes
+    const P2WPKH: (f64, f64) = (68.0, 31.0);
+
+    /// P2WPKH candidates of `values` at 1 sat/vB, sorted as the handler does
+    fn candidates(values: &[u64]) -> Vec<Candidate> {
+        let mut candidates: Vec<Candidate> = values
+            .iter()
+            .enumerate()
+            .map(|(vout, &value)| Candidate {
+                outpoint: OutPoint::new(Txid::all_zeros(), vout as u32),
+                value,
+                effective_value: value - 68,
+                input_vsize: 68.0,
+            })
+            .collect();
+        candidates.sort_by_key(|c| std::cmp::Reverse(c.effective_value));
+        candidates
+    }
+
+    fn values(selection: &Selection) -> Vec<u64> {
+        selection.inputs.iter().map(|input| input.value).collect()
+    }
+
+    #[test]
+    fn branch_and_bound_finds_an_exact_match() {
+        // Target: 100000 + ceil(10.5 + 43) = 100054, plus 68 to spend the input
+        let selection = select(
+            &candidates(&[200_000, 100_122, 50_000]),
+            100_000,
+            1.0,
+            P2WPKH,
+        )
+        .unwrap();
+        assert_eq!(selection.algorithm, "bnb");
+        assert_eq!(values(&selection), [100_122]);
+        assert_eq!(selection.change, 0);
+        assert_eq!(selection.fee, 122);
+        assert_eq!(selection.vsize, 122);
+    }
+
+    #[test]
+    fn branch_and_bound_prefers_the_smallest_excess() {
+        // Both are within the 99 sat cost of change of the 100054 target
+        let selection = select(&candidates(&[100_168, 100_128]), 100_000, 1.0, P2WPKH).unwrap();
+        assert_eq!(selection.algorithm, "bnb");
+        assert_eq!(values(&selection), [100_128]);
+        // The 6 sat excess goes to the fee
+        assert_eq!(selection.fee, 128);
+    }
+
+    #[test]
+    fn falls_back_to_largest_first_with_change() {
+        let selection = select(&candidates(&[50_068, 60_068]), 100_000, 1.0, P2WPKH).unwrap();
+        assert_eq!(selection.algorithm, "largest-first");
+        assert_eq!(values(&selection), [60_068, 50_068]);
+        // 110000 effective - 100054 target - 31 for the change output
+        assert_eq!(selection.change, 9_915);
+        // 10.5 + 43 + 2 * 68 + 31 vB at 1 sat/vB
+        assert_eq!(selection.vsize, 221);
+        assert_eq!(selection.fee, 221);
+    }
+
+    #[test]
+    fn leaves_dust_change_to_the_fee() {
+        // 232 sat over the target is less than a change output plus dust
+        let selection = select(&candidates(&[50_068, 50_354]), 100_000, 1.0, P2WPKH).unwrap();
+        assert_eq!(selection.algorithm, "largest-first");
+        assert_eq!(selection.change, 0);
+        assert_eq!(selection.fee, 422);
+        assert_eq!(selection.vsize, 190);
+    }
+
+    #[test]
+    fn reports_insufficient_funds() {
+        assert!(select(&candidates(&[50_000, 40_000]), 100_000, 1.0, P2WPKH).is_none());
+    }
+
+    #[test]
+    fn golden_coin_select() {
+        let candidate = Candidate {
+            outpoint: OutPoint::new(
+                "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16"
+                    .parse()
+                    .unwrap(),
+                0,
+            ),
+            value: 100_000,
+            effective_value: 100_000 - 68,
+            input_vsize: 68.0,
+        };
+        let selection = select(&[candidate], 60_000, 1.0, P2WPKH).unwrap();
+        golden::check("coin_select", &selection);
+    }
+}
@@ -0,0 +1,163 @@
+//! Drop-in compatibility with the esplora or mempool.space API (`COMPAT`).
+//!
+//! Without it minipool keeps its own conventions where they differ from the
+//! upstreams: fee estimates in BTC/kvB like bitcoind and JSON bodies for
+//! rejected broadcasts. In a compatibility mode those answer exactly as the
+//! chosen upstream does, and the endpoints of its surface that minipool
+//! doesn't otherwise serve are mounted. mempool.space serves esplora's routes
+//! as well, so its mode includes esplora's endpoints.
+//!
+//! Fields minipool adds to esplora objects, like `locktime_info` or
+//! `first_seen`, stay in both modes; upstream clients ignore unknown fields.
+
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::bail;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Json;
+use bitcoincore_rpc::bitcoin::BlockHash;
+use bitcoincore_rpc::jsonrpc::error::{Error as JsonRpcError, RpcError};
+use minipool_client::{paths, types};
+use serde_json::{json, Value};
+use tracing::warn;
+
+use crate::blocks;
+use crate::{openapi, AppState, RouteInfo};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compat {
+    Esplora,
+    Mempool,
+}
+
+impl FromStr for Compat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "esplora" => Ok(Self::Esplora),
+            "mempool" => Ok(Self::Mempool),
+            _ => bail!(
+                "Unknown compatibility mode {:?}, expected esplora or mempool",
+                s
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Compat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Esplora => "esplora",
+            Self::Mempool => "mempool",
+        })
+    }
+}
+
+/// Routes of the upstream's surface that minipool only serves in its mode
+pub fn routes(compat: Compat) -> Vec<RouteInfo> {
+    let mut routes = vec![RouteInfo::new(
+        paths::BLOCK_TXID,
+        "Get the txid at a position in a block.",
+        get(get_block_txid),
+    )
+    .returns(openapi::text)];
+    if compat == Compat::Mempool {
+        routes.extend([
+            RouteInfo::new(
+                paths::V1_BLOCK,
+                "Get a block with fee statistics and mining pool.",
+                get(blocks::get_v1_block),
+            )
+            .returns(openapi::json::<types::ExtendedBlock>),
+            RouteInfo::new(
+                paths::VALIDATE_ADDRESS,
+                "Validate an address, as the node's validateaddress RPC does.",
+                get(get_validate_address),
+            )
+            .returns(openapi::json::<types::AddressValidation>),
+        ]);
+    }
+    routes
+}
+
+/// esplora's answer to a rejected broadcast: the node's error as plain text
+pub fn broadcast_error(error: &bitcoincore_rpc::Error) -> Option<Response> {
+    let bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(RpcError { code, message, .. })) = error
+    else {
+        return None;
+    };
+    let error = json!({ "code": code, "message": message });
+    Some(
+        (
+            StatusCode::BAD_REQUEST,
+            format!("sendrawtransaction RPC error: {}", error),
+        )
+            .into_response(),
+    )
+}
+
+async fn get_block_txid(
+    State(state): State<AppState>,
+    Path((hash, index)): Path<(String, usize)>,
+) -> impl IntoResponse {
+    let Ok(block_hash) = BlockHash::from_str(&hash) else {
+        return (StatusCode::BAD_REQUEST, "Invalid block hash").into_response();
+    };
+    match state.rpc.get_block_info(&block_hash).await {
+        Ok(block) => match block.tx.get(index) {
+            Some(txid) => txid.to_string().into_response(),
+            None => (StatusCode::NOT_FOUND, "Transaction index out of range").into_response(),
+        },
+        Err(e) => {
+            warn!("Failed to get txids of block {}: {}", hash, e);
+            (StatusCode::NOT_FOUND, "Block not found").into_response()
+        }
+    }
+}
+
+/// mempool.space passes the node's answer on unchanged
+async fn get_validate_address(
+    State(state): State<AppState>,
+    Path(address): Path<String>,
+) -> impl IntoResponse {
+    match state
+        .rpc
+        .call::<Value>("validateaddress", &[json!(address)])
+        .await
+    {
+        Ok(validation) => Json(validation).into_response(),
+        Err(e) => {
+            warn!("Failed to validate address {}: {}", address, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "RPC error").into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden;
+
+    #[test]
+    fn golden_validate_address() {
+        // The node's answer, whose keys come out sorted once passed on
+        let validation: Value = serde_json::from_str(
+            r#"{
+                "isvalid": true,
+                "address": "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq",
+                "scriptPubKey": "0014e8df018c7e326cc253faac7e46cdc51e68542c42",
+                "isscript": false,
+                "iswitness": true,
+                "witness_version": 0,
+                "witness_program": "e8df018c7e326cc253faac7e46cdc51e68542c42"
+            }"#,
+        )
+        .unwrap();
+        golden::check("validate_address", &validation);
+    }
+}
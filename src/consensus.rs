@@ -0,0 +1,390 @@
+//! Agreement of the node's chain with the wider network (`REFERENCE_APIS`).
+//!
+//! Every `REFERENCE_CHECK_INTERVAL` the node's tip is compared to the tip of
+//! each external esplora-compatible API, such as blockstream.info or
+//! mempool.space. A reference on the node's chain agrees, or is behind/ahead
+//! once its height differs by more than `REFERENCE_MAX_LAG`, and a reference
+//! whose tip isn't on the node's chain (or the other way round) has diverged:
+//! the node may be on the wrong side of a chain split. Changes are logged,
+//! exported as `consensus_*` metrics and diverging references fail the
+//! `consensus` health component.
+
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, bail, Context, Result};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use bitcoincore_rpc::bitcoin::BlockHash;
+use futures_util::future::join_all;
+use minipool_client::paths;
+use reqwest::{Method, Url};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::chain;
+use crate::health::{Health, Severity};
+use crate::outbound::OutboundClient;
+use crate::rpc::Rpc;
+use crate::AppState;
+
+const HEALTH_COMPONENT: &str = "consensus";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum ReferenceStatus {
+    Agrees,
+    Behind,
+    Ahead,
+    Diverged,
+    Unreachable,
+}
+
+#[derive(Clone, Serialize)]
+struct ReferenceReport {
+    name: String,
+    /// `agrees`, `behind`, `ahead`, `diverged` or `unreachable`
+    status: ReferenceStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    height: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tip: Option<BlockHash>,
+    /// Blocks the node is behind the reference, negative when ahead
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lag: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Clone, Default, Serialize)]
+struct ConsensusCheck {
+    /// Seconds since epoch, unset until the first check
+    checked_at: Option<u64>,
+    /// Whether no reference has diverged from the node's chain
+    agrees: bool,
+    /// The node's tip when it was compared
+    height: Option<u64>,
+    tip: Option<BlockHash>,
+    references: Vec<ReferenceReport>,
+}
+
+#[derive(Deserialize)]
+struct ReferenceBlock {
+    height: u64,
+}
+
+struct Reference {
+    /// Host of the API, as reported and in metrics
+    name: String,
+    /// Base URL the `/api/...` paths are appended to
+    base: String,
+}
+
+impl Reference {
+    fn new(url: &Url) -> Self {
+        let name = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_owned(),
+            (None, _) => url.to_string(),
+        };
+        Self {
+            name,
+            base: url.as_str().trim_end_matches('/').to_owned(),
+        }
+    }
+
+    /// Body of `path` with `{name}` placeholders filled in from `args` in order
+    async fn get(&self, http: &OutboundClient, path: &str, args: &[&str]) -> Result<String> {
+        let mut path = path.to_owned();
+        for arg in args {
+            let start = path.find('{').context("Too many path arguments")?;
+            let end = start + path[start..].find('}').context("Unclosed placeholder")?;
+            path.replace_range(start..=end, arg);
+        }
+        let url = format!("{}{}", self.base, path);
+        let response = http
+            .send("consensus_check", http.request(Method::GET, &url))
+            .await
+            .map_err(|e| anyhow!("{}", e))?;
+        let status = response.status();
+        if !status.is_success() {
+            bail!("{} answered {}", path, status);
+        }
+        Ok(response.text().await?)
+    }
+
+    /// Tip of the reference, hash first so the height is that of the same block
+    async fn tip(&self, http: &OutboundClient) -> Result<(u64, BlockHash)> {
+        let hash: BlockHash = self
+            .get(http, paths::TIP_HASH, &[])
+            .await?
+            .trim()
+            .parse()
+            .context("Invalid tip hash")?;
+        let block: ReferenceBlock =
+            serde_json::from_str(&self.get(http, paths::BLOCK, &[&hash.to_string()]).await?)
+                .context("Invalid block")?;
+        Ok((block.height, hash))
+    }
+
+    async fn block_hash(&self, http: &OutboundClient, height: u64) -> Result<BlockHash> {
+        self.get(http, paths::BLOCK_HEIGHT, &[&height.to_string()])
+            .await?
+            .trim()
+            .parse()
+            .context("Invalid block hash")
+    }
+}
+
+pub struct ConsensusMonitor {
+    references: Vec<Reference>,
+    http: OutboundClient,
+    max_lag: u64,
+    latest: RwLock<ConsensusCheck>,
+}
+
+impl ConsensusMonitor {
+    pub fn new(urls: &[Url], http: OutboundClient, max_lag: u64) -> Self {
+        Self {
+            references: urls.iter().map(Reference::new).collect(),
+            http,
+            max_lag,
+            latest: RwLock::new(ConsensusCheck::default()),
+        }
+    }
+
+    pub async fn run(self: Arc<Self>, rpc: Arc<Rpc>, interval: Duration, health: Arc<Health>) {
+        health.register(HEALTH_COMPONENT, Severity::Soft, Some(interval * 3));
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let (height, tip) = match chain::tip(&rpc).await {
+                Ok(tip) => tip,
+                Err(e) => {
+                    warn!("Failed to get the tip to check consensus: {}", e);
+                    continue;
+                }
+            };
+            // References are checked in parallel so an unreachable one doesn't delay the rest
+            let checks = join_all(
+                self.references
+                    .iter()
+                    .map(|reference| self.check(&rpc, reference, height, tip)),
+            )
+            .await;
+            let previous = self.latest.read().expect("consensus lock poisoned").clone();
+            let mut references = Vec::with_capacity(checks.len());
+            for (reference, check) in self.references.iter().zip(checks) {
+                let report = report(&reference.name, height, check);
+                let before = previous
+                    .references
+                    .iter()
+                    .find(|previous| previous.name == report.name)
+                    .map(|previous| previous.status);
+                log_change(&report, before);
+                record_metrics(&report);
+                references.push(report);
+            }
+            let diverged: Vec<&str> = references
+                .iter()
+                .filter(|reference| reference.status == ReferenceStatus::Diverged)
+                .map(|reference| reference.name.as_str())
+                .collect();
+            if diverged.is_empty() {
+                health.success(HEALTH_COMPONENT);
+            } else {
+                health.failure(
+                    HEALTH_COMPONENT,
+                    &format!("Diverged from references: {}", diverged.join(", ")),
+                );
+            }
+            metrics::gauge!("consensus_agrees").set(f64::from(diverged.is_empty()));
+            let check = ConsensusCheck {
+                checked_at: Some(
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs(),
+                ),
+                agrees: diverged.is_empty(),
+                height: Some(height),
+                tip: Some(tip),
+                references,
+            };
+            *self.latest.write().expect("consensus lock poisoned") = check;
+        }
+    }
+
+    /// Places a reference relative to the node's tip. Of two chains, the one
+    /// with the higher tip has the other's tip at its height if they agree.
+    async fn check(
+        &self,
+        rpc: &Rpc,
+        reference: &Reference,
+        height: u64,
+        tip: BlockHash,
+    ) -> Result<(u64, BlockHash, ReferenceStatus)> {
+        let (reference_height, reference_tip) = reference.tip(&self.http).await?;
+        let status = if reference_height == height {
+            if reference_tip == tip {
+                ReferenceStatus::Agrees
+            } else {
+                ReferenceStatus::Diverged
+            }
+        } else if reference_height < height {
+            if rpc.get_block_hash(reference_height).await? != reference_tip {
+                ReferenceStatus::Diverged
+            } else if height - reference_height > self.max_lag {
+                ReferenceStatus::Behind
+            } else {
+                ReferenceStatus::Agrees
+            }
+        } else if reference.block_hash(&self.http, height).await? != tip {
+            ReferenceStatus::Diverged
+        } else if reference_height - height > self.max_lag {
+            ReferenceStatus::Ahead
+        } else {
+            ReferenceStatus::Agrees
+        };
+        Ok((reference_height, reference_tip, status))
+    }
+}
+
+fn report(
+    name: &str,
+    height: u64,
+    check: Result<(u64, BlockHash, ReferenceStatus)>,
+) -> ReferenceReport {
+    match check {
+        Ok((reference_height, tip, status)) => ReferenceReport {
+            name: name.to_owned(),
+            status,
+            height: Some(reference_height),
+            tip: Some(tip),
+            lag: Some(reference_height as i64 - height as i64),
+            error: None,
+        },
+        Err(error) => ReferenceReport {
+            name: name.to_owned(),
+            status: ReferenceStatus::Unreachable,
+            height: None,
+            tip: None,
+            lag: None,
+            error: Some(format!("{:#}", error)),
+        },
+    }
+}
+
+fn log_change(report: &ReferenceReport, before: Option<ReferenceStatus>) {
+    if before == Some(report.status) {
+        return;
+    }
+    let height = report.height.unwrap_or_default();
+    match report.status {
+        ReferenceStatus::Diverged => warn!(
+            "Node disagrees with reference {}, whose tip {} (height {}) isn't on the node's chain",
+            report.name,
+            report.tip.map(|tip| tip.to_string()).unwrap_or_default(),
+            height
+        ),
+        ReferenceStatus::Behind => warn!(
+            "Reference {} is {} blocks behind the node",
+            report.name,
+            -report.lag.unwrap_or_default()
+        ),
+        ReferenceStatus::Ahead => warn!(
+            "Reference {} is {} blocks ahead of the node",
+            report.name,
+            report.lag.unwrap_or_default()
+        ),
+        ReferenceStatus::Unreachable => warn!(
+            "Reference {} is unreachable: {}",
+            report.name,
+            report.error.as_deref().unwrap_or_default()
+        ),
+        ReferenceStatus::Agrees if before.is_some() => {
+            info!(
+                "Node agrees with reference {} at height {}",
+                report.name, height
+            )
+        }
+        ReferenceStatus::Agrees => {}
+    }
+}
+
+fn record_metrics(report: &ReferenceReport) {
+    let reference = report.name.clone();
+    metrics::gauge!("consensus_reference_reachable", "reference" => reference.clone())
+        .set(f64::from(report.status != ReferenceStatus::Unreachable));
+    metrics::gauge!("consensus_reference_diverged", "reference" => reference.clone())
+        .set(f64::from(report.status == ReferenceStatus::Diverged));
+    if let Some(lag) = report.lag {
+        metrics::gauge!("consensus_reference_lag_blocks", "reference" => reference).set(lag as f64);
+    }
+}
+
+pub async fn get_consensus_check(State(state): State<AppState>) -> impl IntoResponse {
+    match &state.consensus {
+        Some(monitor) => Json(
+            monitor
+                .latest
+                .read()
+                .expect("consensus lock poisoned")
+                .clone(),
+        )
+        .into_response(),
+        None => (StatusCode::NOT_FOUND, "No reference APIs configured").into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden;
+
+    #[test]
+    fn golden_consensus_check() {
+        let hash = |hash: &str| hash.parse::<BlockHash>().unwrap();
+        golden::check(
+            "consensus_check",
+            &ConsensusCheck {
+                checked_at: Some(1713571780),
+                agrees: false,
+                height: Some(840000),
+                tip: Some(hash(
+                    "0000000000000000000320283a032748cef8227873ff4872689bf23f1cda83a5",
+                )),
+                references: vec![
+                    report(
+                        "blockstream.info",
+                        840000,
+                        Ok((
+                            840001,
+                            hash(
+                                "00000000000000000001b48a75d5a3077913f3f441eb7e08c13c43f768db2463",
+                            ),
+                            ReferenceStatus::Agrees,
+                        )),
+                    ),
+                    report(
+                        "esplora.example.org:3000",
+                        840000,
+                        Ok((
+                            840000,
+                            hash(
+                                "000000000000000000014b4c4dd9d7e0c3b0e2ae60f0d3b49fbb4fd87a8bd6b0",
+                            ),
+                            ReferenceStatus::Diverged,
+                        )),
+                    ),
+                    report(
+                        "mempool.space",
+                        840000,
+                        Err(anyhow!(
+                            "/api/blocks/tip/hash answered 503 Service Unavailable"
+                        )),
+                    ),
+                ],
+            },
+        );
+    }
+}
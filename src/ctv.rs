@@ -0,0 +1,258 @@
+//! BIP119 (`OP_CHECKTEMPLATEVERIFY`) template hashes, for vault experiments on
+//! signet and other chains enforcing it.
+//!
+//! Only compiled with the `ctv` feature. `/api/v1/ctv/template-hash` computes
+//! the default template hash each input of a transaction commits to, and
+//! `/api/v1/ctv/{hash}/spends` scans a bounded range of blocks for inputs whose
+//! transaction matches a template. Hashes are in the byte order the script
+//! pushes them, not reversed like txids.
+
+use std::str::FromStr;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{get, post},
+    Json,
+};
+use bitcoincore_rpc::bitcoin::consensus::encode::deserialize_hex;
+use bitcoincore_rpc::bitcoin::consensus::Encodable;
+use bitcoincore_rpc::bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoincore_rpc::bitcoin::{BlockHash, Transaction, Txid};
+use minipool_client::{paths, types};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use utoipa::ToSchema;
+
+use crate::rpc::Rpc;
+use crate::{openapi, AppState, RouteInfo};
+
+/// Blocks scanned when no `start_height` is given, about a day
+const DEFAULT_SCAN_BLOCKS: u64 = 144;
+
+/// Upper bound on blocks scanned per request, about two weeks
+const MAX_SCAN_BLOCKS: u64 = 2016;
+
+pub fn routes() -> Vec<RouteInfo> {
+    vec![
+        RouteInfo::post(
+            paths::CTV_TEMPLATE_HASH,
+            "Compute the BIP119 default template hash each input of a transaction commits to.",
+            post(post_template_hash),
+        )
+        .accepts(openapi::json::<TemplateHashRequest>)
+        .returns(openapi::json::<types::TemplateHashes>),
+        RouteInfo::new(
+            paths::CTV_SPENDS,
+            "Find confirmed inputs whose transaction matches a BIP119 template hash, scanning a range of blocks.",
+            get(get_spends),
+        )
+        .returns(openapi::json::<Vec<types::TemplateSpend>>)
+        .query(
+            "start_height",
+            "First block scanned, 143 blocks below end_height by default",
+        )
+        .query("end_height", "Last block scanned, the tip by default"),
+    ]
+}
+
+/// Engine fed with everything but the input index, shared by all inputs
+fn template_prefix(tx: &Transaction) -> sha256::HashEngine {
+    let mut engine = sha256::Hash::engine();
+    engine.input(&tx.version.0.to_le_bytes());
+    engine.input(&tx.lock_time.to_consensus_u32().to_le_bytes());
+    if tx.input.iter().any(|input| !input.script_sig.is_empty()) {
+        let mut script_sigs = sha256::Hash::engine();
+        for input in &tx.input {
+            input
+                .script_sig
+                .consensus_encode(&mut script_sigs)
+                .expect("hash engines don't fail");
+        }
+        engine.input(sha256::Hash::from_engine(script_sigs).as_byte_array());
+    }
+    engine.input(&(tx.input.len() as u32).to_le_bytes());
+    let mut sequences = sha256::Hash::engine();
+    for input in &tx.input {
+        sequences.input(&input.sequence.0.to_le_bytes());
+    }
+    engine.input(sha256::Hash::from_engine(sequences).as_byte_array());
+    engine.input(&(tx.output.len() as u32).to_le_bytes());
+    let mut outputs = sha256::Hash::engine();
+    for output in &tx.output {
+        output
+            .consensus_encode(&mut outputs)
+            .expect("hash engines don't fail");
+    }
+    engine.input(sha256::Hash::from_engine(outputs).as_byte_array());
+    engine
+}
+
+/// `DefaultCheckTemplateVerifyHash` of every input of `tx`, in input order
+fn template_hashes(tx: &Transaction) -> Vec<sha256::Hash> {
+    let prefix = template_prefix(tx);
+    (0..tx.input.len() as u32)
+        .map(|index| {
+            let mut engine = prefix.clone();
+            engine.input(&index.to_le_bytes());
+            sha256::Hash::from_engine(engine)
+        })
+        .collect()
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct TemplateHashRequest {
+    /// Serialized transaction, hex
+    tx: String,
+}
+
+#[derive(Serialize)]
+struct TemplateHashes {
+    txid: Txid,
+    /// Hash committed to by each input, in input order
+    template_hashes: Vec<sha256::Hash>,
+}
+
+async fn post_template_hash(Json(request): Json<TemplateHashRequest>) -> impl IntoResponse {
+    let Ok(tx) = deserialize_hex::<Transaction>(request.tx.trim()) else {
+        return (StatusCode::BAD_REQUEST, "Invalid transaction").into_response();
+    };
+    Json(TemplateHashes {
+        txid: tx.compute_txid(),
+        template_hashes: template_hashes(&tx),
+    })
+    .into_response()
+}
+
+#[derive(Deserialize)]
+struct SpendsQuery {
+    start_height: Option<u64>,
+    end_height: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct TemplateSpend {
+    txid: Txid,
+    /// Input whose template hash matches
+    vin: u32,
+    height: u64,
+    block_hash: BlockHash,
+}
+
+/// Inputs matching `template` in the blocks from `start` to `end`, oldest first.
+/// Coinbase transactions can't spend a template and are skipped.
+async fn spends(
+    rpc: &Rpc,
+    template: &sha256::Hash,
+    start: u64,
+    end: u64,
+) -> Result<Vec<TemplateSpend>, bitcoincore_rpc::Error> {
+    let mut spends = Vec::new();
+    for height in start..=end {
+        let block_hash = rpc.get_block_hash(height).await?;
+        let block = rpc.get_block(&block_hash).await?;
+        for tx in block.txdata.iter().skip(1) {
+            for (vin, hash) in template_hashes(tx).iter().enumerate() {
+                if hash == template {
+                    spends.push(TemplateSpend {
+                        txid: tx.compute_txid(),
+                        vin: vin as u32,
+                        height,
+                        block_hash,
+                    });
+                }
+            }
+        }
+    }
+    Ok(spends)
+}
+
+async fn get_spends(
+    State(state): State<AppState>,
+    Path(hash): Path<String>,
+    Query(query): Query<SpendsQuery>,
+) -> impl IntoResponse {
+    let Ok(template) = sha256::Hash::from_str(&hash) else {
+        return (StatusCode::BAD_REQUEST, "Invalid template hash").into_response();
+    };
+    let scan = async {
+        let tip = state.rpc.get_block_count().await?;
+        let end = query.end_height.map_or(tip, |height| height.min(tip));
+        let start = query
+            .start_height
+            .unwrap_or_else(|| end.saturating_sub(DEFAULT_SCAN_BLOCKS - 1));
+        if start > end || end - start >= MAX_SCAN_BLOCKS {
+            return Ok(None);
+        }
+        spends(&state.rpc, &template, start, end).await.map(Some)
+    };
+    match scan.await {
+        Ok(Some(spends)) => Json(spends).into_response(),
+        Ok(None) => (
+            StatusCode::BAD_REQUEST,
+            "Invalid block range, at most 2016 blocks are scanned per request",
+        )
+            .into_response(),
+        Err(e) => {
+            warn!("Failed to scan for spends of template {}: {}", hash, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "RPC error").into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoincore_rpc::bitcoin::absolute::LockTime;
+    use bitcoincore_rpc::bitcoin::transaction::Version;
+    use bitcoincore_rpc::bitcoin::{Amount, OutPoint, ScriptBuf, TxIn, TxOut};
+
+    use super::*;
+    use crate::golden;
+
+    #[test]
+    fn golden_ctv_template_hash() {
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(
+                    "0e3e2357e806b6cdb1f70b54c3a3a17b6714ee1f0e68bebb44a74b1efd512098"
+                        .parse()
+                        .unwrap(),
+                    0,
+                ),
+                ..TxIn::default()
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(99000),
+                script_pubkey: ScriptBuf::from_hex("0014d0c4a3ef09e997b6e99e397e518fe3e41a118ca1")
+                    .unwrap(),
+            }],
+        };
+        golden::check(
+            "ctv_template_hash",
+            &TemplateHashes {
+                txid: tx.compute_txid(),
+                template_hashes: template_hashes(&tx),
+            },
+        );
+    }
+
+    #[test]
+    fn golden_ctv_spends() {
+        golden::check(
+            "ctv_spends",
+            &[TemplateSpend {
+                txid: "0e3e2357e806b6cdb1f70b54c3a3a17b6714ee1f0e68bebb44a74b1efd512098"
+                    .parse()
+                    .unwrap(),
+                vin: 0,
+                height: 840010,
+                block_hash: "00000000000000000002a7c4c1e48d76c5a37902165a270156b7a8d72728a054"
+                    .parse()
+                    .unwrap(),
+            }],
+        );
+    }
+}
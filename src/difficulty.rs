@@ -0,0 +1,203 @@
+//! Progress through the current difficulty retarget period.
+//!
+//! The estimate extrapolates the average block interval since the period
+//! started, the same way the next retarget will compute it, clamped to the
+//! factor of four consensus allows in either direction.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde::Serialize;
+use tracing::warn;
+
+use crate::json;
+use crate::rpc::Rpc;
+use crate::AppState;
+
+/// Blocks between difficulty retargets
+const RETARGET_INTERVAL: u64 = 2016;
+
+/// Target block interval in seconds
+const TARGET_SPACING: f64 = 600.0;
+
+/// mempool.space's difficulty adjustment, times in milliseconds
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DifficultyAdjustment {
+    /// Share of the period's blocks already mined, in percent
+    #[serde(serialize_with = "json::rate")]
+    progress_percent: f64,
+    /// Estimated change of the next retarget, in percent
+    #[serde(serialize_with = "json::rate")]
+    difficulty_change: f64,
+    estimated_retarget_date: u64,
+    remaining_blocks: u64,
+    remaining_time: u64,
+    /// Change of the last retarget, in percent
+    #[serde(serialize_with = "json::rate")]
+    previous_retarget: f64,
+    /// Timestamp of the first block of the period, in seconds
+    previous_time: u64,
+    next_retarget_height: u64,
+    /// Average block interval of the period so far
+    time_avg: u64,
+}
+
+async fn difficulty_adjustment(rpc: &Rpc) -> Result<DifficultyAdjustment, bitcoincore_rpc::Error> {
+    let info = rpc.get_blockchain_info().await?;
+    let tip = rpc.get_block_header_info(&info.best_block_hash).await?;
+    let height = tip.height as u64;
+    let start_height = height - height % RETARGET_INTERVAL;
+    let start = rpc
+        .get_block_header_info(&rpc.get_block_hash(start_height).await?)
+        .await?;
+
+    let previous_retarget = match start_height.checked_sub(RETARGET_INTERVAL) {
+        Some(previous_height) => {
+            let previous = rpc
+                .get_block_header_info(&rpc.get_block_hash(previous_height).await?)
+                .await?;
+            (start.difficulty / previous.difficulty - 1.0) * 100.0
+        }
+        None => 0.0,
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis() as u64);
+    Ok(estimate(
+        height,
+        tip.time as u64,
+        start.time as u64,
+        previous_retarget,
+        now,
+    ))
+}
+
+/// Extrapolates the period of the tip at `height`, mined at `tip_time`, whose
+/// first block was mined at `start_time`; `now` is in milliseconds
+fn estimate(
+    height: u64,
+    tip_time: u64,
+    start_time: u64,
+    previous_retarget: f64,
+    now: u64,
+) -> DifficultyAdjustment {
+    let start_height = height - height % RETARGET_INTERVAL;
+    let mined = height - start_height;
+    let time_avg = if mined == 0 {
+        TARGET_SPACING
+    } else {
+        tip_time.saturating_sub(start_time) as f64 / mined as f64
+    };
+    let difficulty_change = if time_avg > 0.0 {
+        ((TARGET_SPACING / time_avg).clamp(0.25, 4.0) - 1.0) * 100.0
+    } else {
+        300.0
+    };
+
+    let remaining_blocks = RETARGET_INTERVAL - mined;
+    let remaining_time = (remaining_blocks as f64 * time_avg * 1000.0) as u64;
+    DifficultyAdjustment {
+        progress_percent: mined as f64 * 100.0 / RETARGET_INTERVAL as f64,
+        difficulty_change,
+        estimated_retarget_date: now + remaining_time,
+        remaining_blocks,
+        remaining_time,
+        previous_retarget,
+        previous_time: start_time,
+        next_retarget_height: start_height + RETARGET_INTERVAL,
+        time_avg: (time_avg * 1000.0) as u64,
+    }
+}
+
+pub async fn get_difficulty_adjustment(State(state): State<AppState>) -> impl IntoResponse {
+    match difficulty_adjustment(&state.rpc).await {
+        Ok(adjustment) => Json(adjustment).into_response(),
+        Err(e) => {
+            warn!("Failed to get difficulty adjustment: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "RPC error").into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden;
+
+    /// First block of the period after the 2024 halving
+    const START: u64 = 840_672;
+    const START_TIME: u64 = 1_713_800_000;
+    const NOW: u64 = 1_714_400_000_000;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    /// Estimate `mined` blocks into the period, mined every `spacing` seconds
+    fn after(mined: u64, spacing: u64) -> DifficultyAdjustment {
+        estimate(
+            START + mined,
+            START_TIME + mined * spacing,
+            START_TIME,
+            1.5,
+            NOW,
+        )
+    }
+
+    #[test]
+    fn extrapolates_the_average_block_interval() {
+        let adjustment = after(1008, 500);
+        assert_eq!(adjustment.progress_percent, 50.0);
+        assert_close(adjustment.difficulty_change, 20.0);
+        assert_eq!(adjustment.remaining_blocks, 1008);
+        assert_eq!(adjustment.remaining_time, 504_000_000);
+        assert_eq!(adjustment.estimated_retarget_date, NOW + 504_000_000);
+        assert_eq!(adjustment.time_avg, 500_000);
+        assert_eq!(adjustment.previous_time, START_TIME);
+        assert_eq!(adjustment.previous_retarget, 1.5);
+        assert_eq!(adjustment.next_retarget_height, START + 2016);
+
+        // Slower blocks lower the difficulty
+        assert_close(after(504, 750).difficulty_change, -20.0);
+    }
+
+    #[test]
+    fn clamps_to_a_factor_of_four() {
+        assert_close(after(100, 100).difficulty_change, 300.0);
+        assert_close(after(100, 6000).difficulty_change, -75.0);
+        // Timestamps may not move at all, or even go back, within a period
+        assert_close(after(100, 0).difficulty_change, 300.0);
+        let backwards = estimate(START + 10, START_TIME - 60, START_TIME, 0.0, NOW);
+        assert_close(backwards.difficulty_change, 300.0);
+    }
+
+    #[test]
+    fn assumes_the_target_spacing_at_the_start_of_a_period() {
+        let adjustment = after(0, 0);
+        assert_eq!(adjustment.progress_percent, 0.0);
+        assert_close(adjustment.difficulty_change, 0.0);
+        assert_eq!(adjustment.remaining_blocks, 2016);
+        assert_eq!(adjustment.remaining_time, 1_209_600_000);
+        assert_eq!(adjustment.time_avg, 600_000);
+        assert_eq!(adjustment.next_retarget_height, START + 2016);
+    }
+
+    #[test]
+    fn golden_difficulty_adjustment() {
+        // 889 blocks into the period before the 2024 halving
+        let adjustment = estimate(
+            839_545,
+            1_713_576_710,
+            1_713_049_815,
+            (86_388_558_925_171.02 / 87_698_740_014_107.26 - 1.0) * 100.0,
+            1_713_577_000_000,
+        );
+        golden::check("difficulty_adjustment", &adjustment);
+    }
+}
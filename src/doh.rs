@@ -0,0 +1,221 @@
+//! DNS-over-HTTPS resolution of outbound hostnames (`OUTBOUND_DOH_URL`).
+//!
+//! Webhook receivers, price feeds and the other hosts minipool calls are looked
+//! up with RFC 8484 queries POSTed to the configured DoH server instead of the
+//! system resolver, so the local network doesn't see which hosts are
+//! contacted. A and AAAA records are asked for together and answers are cached
+//! for their TTL. The node's own hosts and `localhost` still go through the
+//! system resolver, since they're usually local names no public resolver
+//! knows. The DoH server's hostname is itself resolved by the system resolver
+//! once per connection; an IP address in its URL avoids even that lookup.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, ensure, Result};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use reqwest::Url;
+
+const DNS_MESSAGE: &str = "application/dns-message";
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+
+/// Bounds on how long an answer is cached, whatever its TTL says
+const MIN_TTL: Duration = Duration::from_secs(30);
+const MAX_TTL: Duration = Duration::from_secs(3600);
+
+/// Wire format query for `name`, with id 0 as RFC 8484 recommends for
+/// cacheability
+fn encode_query(name: &str, record_type: u16) -> Result<Vec<u8>> {
+    // Header: id, flags with recursion desired, one question
+    let mut query = vec![0, 0, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+    for label in name.trim_end_matches('.').split('.') {
+        ensure!(
+            !label.is_empty() && label.len() < 64,
+            "Invalid hostname {:?}",
+            name
+        );
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&record_type.to_be_bytes());
+    query.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(query)
+}
+
+fn read_u16(message: &[u8], at: usize) -> Result<u16> {
+    let bytes = message
+        .get(at..at + 2)
+        .ok_or_else(|| anyhow!("Truncated DNS answer"))?;
+    Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+/// Position after the name at `at`, which ends in a zero label or a
+/// compression pointer
+fn skip_name(message: &[u8], mut at: usize) -> Result<usize> {
+    loop {
+        let len = *message
+            .get(at)
+            .ok_or_else(|| anyhow!("Truncated DNS answer"))?;
+        match len {
+            0 => return Ok(at + 1),
+            _ if len & 0xc0 == 0xc0 => return Ok(at + 2),
+            _ => at += 1 + len as usize,
+        }
+    }
+}
+
+/// Addresses in the answer section with the smallest TTL among them
+fn decode_answer(message: &[u8]) -> Result<(Vec<IpAddr>, Duration)> {
+    let flags = read_u16(message, 2)?;
+    match flags & 0x000f {
+        0 => {}
+        3 => bail!("No such domain"),
+        rcode => bail!("DNS server answered with error code {}", rcode),
+    }
+    let questions = read_u16(message, 4)?;
+    let answers = read_u16(message, 6)?;
+    let mut at = 12;
+    for _ in 0..questions {
+        at = skip_name(message, at)? + 4;
+    }
+    let mut addresses = Vec::new();
+    let mut ttl = MAX_TTL;
+    for _ in 0..answers {
+        at = skip_name(message, at)?;
+        let record_type = read_u16(message, at)?;
+        let record_ttl =
+            u32::from(read_u16(message, at + 4)?) << 16 | u32::from(read_u16(message, at + 6)?);
+        let len = read_u16(message, at + 8)? as usize;
+        at += 10;
+        let data = message
+            .get(at..at + len)
+            .ok_or_else(|| anyhow!("Truncated DNS answer"))?;
+        at += len;
+        let address = match (record_type, data.len()) {
+            (TYPE_A, 4) => IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(data)?)),
+            (TYPE_AAAA, 16) => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(data)?)),
+            // CNAMEs and the like, whose targets' addresses follow
+            _ => continue,
+        };
+        addresses.push(address);
+        ttl = ttl.min(Duration::from_secs(record_ttl.into()));
+    }
+    Ok((addresses, ttl.max(MIN_TTL)))
+}
+
+struct Cached {
+    addresses: Vec<IpAddr>,
+    expires: Instant,
+}
+
+#[derive(Clone)]
+pub struct DohResolver {
+    /// Plain client for the queries themselves, resolving through the system
+    http: reqwest::Client,
+    url: Url,
+    /// Hosts left to the system resolver, lowercase
+    local_hosts: Arc<Vec<String>>,
+    cache: Arc<Mutex<HashMap<String, Cached>>>,
+}
+
+impl DohResolver {
+    pub fn new(http: reqwest::Client, url: Url, local_hosts: &[&str]) -> Self {
+        Self {
+            http,
+            url,
+            local_hosts: Arc::new(
+                local_hosts
+                    .iter()
+                    .map(|host| host.to_ascii_lowercase())
+                    .chain(["localhost".to_owned()])
+                    .collect(),
+            ),
+            cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    async fn query(&self, name: &str, record_type: u16) -> Result<(Vec<IpAddr>, Duration)> {
+        let response = self
+            .http
+            .post(self.url.clone())
+            .header(CONTENT_TYPE, DNS_MESSAGE)
+            .header(ACCEPT, DNS_MESSAGE)
+            .body(encode_query(name, record_type)?)
+            .send()
+            .await?
+            .error_for_status()?;
+        decode_answer(&response.bytes().await?)
+    }
+
+    async fn lookup(&self, name: &str) -> Result<Vec<IpAddr>> {
+        let name = name.to_ascii_lowercase();
+        if let Some(cached) = self
+            .cache
+            .lock()
+            .expect("DoH cache lock poisoned")
+            .get(&name)
+            .filter(|cached| cached.expires > Instant::now())
+        {
+            return Ok(cached.addresses.clone());
+        }
+        let (v4, v6) = tokio::join!(self.query(&name, TYPE_A), self.query(&name, TYPE_AAAA));
+        let status = if v4.is_ok() || v6.is_ok() {
+            "ok"
+        } else {
+            "error"
+        };
+        metrics::counter!("outbound_doh_lookups_total", "status" => status).increment(1);
+        let (addresses, ttl) = match (v4, v6) {
+            (Err(e), Err(_)) => return Err(e.context(format!("Failed to resolve {}", name))),
+            (v4, v6) => {
+                let (mut v4, v4_ttl) = v4.unwrap_or((Vec::new(), MAX_TTL));
+                let (v6, v6_ttl) = v6.unwrap_or((Vec::new(), MAX_TTL));
+                v4.extend(v6);
+                (v4, v4_ttl.min(v6_ttl))
+            }
+        };
+        ensure!(!addresses.is_empty(), "No address for {}", name);
+        let now = Instant::now();
+        let mut cache = self.cache.lock().expect("DoH cache lock poisoned");
+        cache.retain(|_, cached| cached.expires > now);
+        cache.insert(
+            name,
+            Cached {
+                addresses: addresses.clone(),
+                expires: now + ttl,
+            },
+        );
+        Ok(addresses)
+    }
+}
+
+impl Resolve for DohResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolver = self.clone();
+        Box::pin(async move {
+            let host = name.as_str();
+            let addresses: Vec<SocketAddr> = if resolver
+                .local_hosts
+                .iter()
+                .any(|local| local.eq_ignore_ascii_case(host))
+            {
+                tokio::net::lookup_host((host, 0)).await?.collect()
+            } else {
+                resolver
+                    .lookup(host)
+                    .await?
+                    .into_iter()
+                    .map(|address| SocketAddr::new(address, 0))
+                    .collect()
+            };
+            Ok(Box::new(addresses.into_iter()) as Addrs)
+        })
+    }
+}
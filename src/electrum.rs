@@ -0,0 +1,844 @@
+//! Electrum protocol server.
+//!
+//! Wallets like Electrum, Sparrow and BlueWallet talk to their server with
+//! newline-delimited JSON-RPC over a raw TCP or TLS socket. Requests are answered
+//! from the same address index, mempool mirror and node RPC as the HTTP API,
+//! and `blockchain.headers.subscribe` and `blockchain.scripthash.subscribe`
+//! notifications follow the chain watcher, the address index and the mempool
+//! mirror. Scripthash methods need the address index; the rest work without it.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use bitcoincore_rpc::bitcoin::consensus::encode::serialize_hex;
+use bitcoincore_rpc::bitcoin::constants::genesis_block;
+use bitcoincore_rpc::bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoincore_rpc::bitcoin::hex::FromHex;
+use bitcoincore_rpc::bitcoin::{BlockHash, OutPoint, Txid};
+use bitcoincore_rpc::jsonrpc::error::{Error as JsonRpcError, RpcError};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, info, warn};
+
+use crate::addresses::{ready_index, AddressIndex, ScriptHash};
+use crate::audit::Interface;
+use crate::chain::{self, ChainWatcher};
+use crate::fees;
+use crate::listeners::Listener;
+use crate::mempool::{self, MempoolTx};
+use crate::merkle;
+use crate::AppState;
+
+const SERVER_VERSION: &str = concat!("minipool ", env!("CARGO_PKG_VERSION"));
+
+const PROTOCOL_VERSION: &str = "1.4";
+
+/// Longest request line, enough for the hex of a block-sized transaction
+const MAX_REQUEST_BYTES: u64 = 8 << 20;
+
+/// Requests read ahead of the one being answered
+const REQUEST_BUFFER: usize = 16;
+
+/// Most headers returned by one `blockchain.block.headers`
+const MAX_HEADERS: u64 = 2016;
+
+/// Most scripthashes one connection can subscribe to
+const MAX_SUBSCRIPTIONS: usize = 10_000;
+
+/// Pause after a failed accept, e.g. when out of file descriptors
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// How often subscribed scripthashes are checked against the index and mempool
+const STATUS_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+// Error codes of the Electrum protocol and JSON-RPC
+const BAD_REQUEST: i64 = 1;
+const DAEMON_ERROR: i64 = 2;
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INTERNAL_ERROR: i64 = -32603;
+
+/// Error answered to a request
+struct Failure {
+    code: i64,
+    message: String,
+}
+
+impl Failure {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(BAD_REQUEST, message)
+    }
+
+    fn internal() -> Self {
+        Self::new(INTERNAL_ERROR, "internal error")
+    }
+
+    fn to_json(&self) -> Value {
+        json!({ "code": self.code, "message": self.message })
+    }
+}
+
+/// Rejections by the node are passed on, everything else is logged
+impl From<bitcoincore_rpc::Error> for Failure {
+    fn from(error: bitcoincore_rpc::Error) -> Self {
+        match error {
+            bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(RpcError { message, .. })) => {
+                Self::new(DAEMON_ERROR, message)
+            }
+            e => {
+                warn!("Failed to answer Electrum request: {}", e);
+                Self::internal()
+            }
+        }
+    }
+}
+
+impl From<anyhow::Error> for Failure {
+    fn from(error: anyhow::Error) -> Self {
+        warn!("Failed to answer Electrum request: {:#}", error);
+        Self::internal()
+    }
+}
+
+/// Positional parameter `index`, `None` when it's missing
+fn optional_param<T: DeserializeOwned>(
+    params: &[Value],
+    index: usize,
+) -> Result<Option<T>, Failure> {
+    match params.get(index) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => serde_json::from_value(value.clone())
+            .map(Some)
+            .map_err(|_| Failure::bad_request(format!("invalid parameter {}", index))),
+    }
+}
+
+fn param<T: DeserializeOwned>(params: &[Value], index: usize) -> Result<T, Failure> {
+    optional_param(params, index)?
+        .ok_or_else(|| Failure::bad_request(format!("missing parameter {}", index)))
+}
+
+/// Index key of an Electrum scripthash, which is the script's SHA256 reversed
+fn parse_scripthash(hex: &str) -> Result<ScriptHash, Failure> {
+    let mut script =
+        <[u8; 32]>::from_hex(hex).map_err(|_| Failure::bad_request("invalid scripthash"))?;
+    script.reverse();
+    Ok(script)
+}
+
+fn parse_txid(hex: &str) -> Result<Txid, Failure> {
+    hex.parse()
+        .map_err(|_| Failure::bad_request("invalid transaction hash"))
+}
+
+fn index(state: &AppState) -> Result<Arc<AddressIndex>, Failure> {
+    ready_index(state).map_err(|(_, message)| Failure::bad_request(message))
+}
+
+/// SHA256 of the `tx_hash:height:` entries of a scripthash's history, fed
+/// incrementally so the confirmed part can be kept between mempool changes
+#[derive(Clone, Default)]
+struct StatusHasher {
+    engine: sha256::HashEngine,
+    entries: usize,
+}
+
+impl StatusHasher {
+    fn add(&mut self, txid: &Txid, height: i64) {
+        self.engine
+            .input(format!("{}:{}:", txid, height).as_bytes());
+        self.entries += 1;
+    }
+
+    /// Status of the history, `None` when it's empty
+    fn finish(self) -> Option<String> {
+        (self.entries > 0).then(|| sha256::Hash::from_engine(self.engine).to_string())
+    }
+}
+
+/// Mempool transactions of `script` with their Electrum height: -1 when they
+/// spend unconfirmed outputs and 0 otherwise. Height 0 comes first, each
+/// height ordered by txid so statuses don't depend on arrival order.
+fn mempool_history(state: &AppState, script: &ScriptHash) -> Vec<(Arc<MempoolTx>, i64)> {
+    let mut history: Vec<_> = state
+        .mempool
+        .script_txs(script)
+        .into_iter()
+        .map(|tx| {
+            let height = if tx
+                .inputs
+                .iter()
+                .any(|input| state.mempool.get(&input.txid).is_some())
+            {
+                -1
+            } else {
+                0
+            };
+            (tx, height)
+        })
+        .collect();
+    history.sort_by_key(|(tx, height)| (-height, tx.txid));
+    history
+}
+
+fn chain_status_blocking(
+    index: &AddressIndex,
+    script: &ScriptHash,
+) -> anyhow::Result<StatusHasher> {
+    let mut hasher = StatusHasher::default();
+    for (txid, height) in index.chain_txs_from(script, 0)? {
+        hasher.add(&txid, height as i64);
+    }
+    Ok(hasher)
+}
+
+fn status(state: &AppState, script: &ScriptHash, chain: &StatusHasher) -> Option<String> {
+    let mut hasher = chain.clone();
+    for (tx, height) in mempool_history(state, script) {
+        hasher.add(&tx.txid, height);
+    }
+    hasher.finish()
+}
+
+fn history_blocking(state: &AppState, script: &ScriptHash) -> Result<Value, Failure> {
+    let index = index(state)?;
+    let mut history: Vec<Value> = index
+        .chain_txs_from(script, 0)?
+        .into_iter()
+        .map(|(txid, height)| json!({ "tx_hash": txid, "height": height }))
+        .collect();
+    history.extend(mempool_entries(state, script));
+    Ok(Value::Array(history))
+}
+
+fn mempool_entries(state: &AppState, script: &ScriptHash) -> Vec<Value> {
+    mempool_history(state, script)
+        .into_iter()
+        .map(|(tx, height)| json!({ "tx_hash": tx.txid, "height": height, "fee": tx.fee.to_sat() }))
+        .collect()
+}
+
+fn balance_blocking(state: &AppState, script: &ScriptHash) -> Result<Value, Failure> {
+    let index = index(state)?;
+    let confirmed: u64 = index
+        .chain_utxos(script)?
+        .iter()
+        .map(|(_, value, _)| value)
+        .sum();
+    let mut unconfirmed = 0i64;
+    for tx in state.mempool.script_txs(script) {
+        for (output_script, value) in &tx.outputs {
+            if output_script == script {
+                unconfirmed += value.to_sat() as i64;
+            }
+        }
+        for (prevout_script, value) in &tx.prevouts {
+            if prevout_script == script {
+                unconfirmed -= value.to_sat() as i64;
+            }
+        }
+    }
+    Ok(json!({ "confirmed": confirmed, "unconfirmed": unconfirmed }))
+}
+
+/// Confirmed and mempool outputs of `script` not spent in the mempool
+fn unspent_blocking(state: &AppState, script: &ScriptHash) -> Result<Value, Failure> {
+    let index = index(state)?;
+    let mut unspent: Vec<(OutPoint, u64, u64)> = index
+        .chain_utxos(script)?
+        .into_iter()
+        .filter(|(outpoint, _, _)| state.mempool.spent_by(outpoint).is_none())
+        .collect();
+    unspent.sort_by_key(|(outpoint, _, height)| (*height, *outpoint));
+    let mut entries: Vec<Value> = unspent
+        .into_iter()
+        .map(|(outpoint, value, height)| {
+            json!({
+                "tx_hash": outpoint.txid,
+                "tx_pos": outpoint.vout,
+                "height": height,
+                "value": value,
+            })
+        })
+        .collect();
+    for tx in state.mempool.script_txs(script) {
+        for (vout, (output_script, value)) in tx.outputs.iter().enumerate() {
+            let outpoint = OutPoint::new(tx.txid, vout as u32);
+            if output_script == script && state.mempool.spent_by(&outpoint).is_none() {
+                entries.push(json!({
+                    "tx_hash": tx.txid,
+                    "tx_pos": outpoint.vout,
+                    "height": 0,
+                    "value": value.to_sat(),
+                }));
+            }
+        }
+    }
+    Ok(Value::Array(entries))
+}
+
+/// Serialized headers of up to `count` blocks from `start`, from the verified
+/// header chain where it has them and the node otherwise
+async fn headers(state: &AppState, start: u64, count: u64) -> Result<Vec<String>, Failure> {
+    let (tip, _) = chain::tip(&state.rpc).await?;
+    if start > tip {
+        return Ok(Vec::new());
+    }
+    let count = count.min(tip - start + 1);
+    let mut headers: Vec<String> = match &state.headers {
+        Some(chain) => chain
+            .headers_from(start as usize, count as usize)
+            .iter()
+            .map(serialize_hex)
+            .collect(),
+        None => Vec::new(),
+    };
+    for height in start + headers.len() as u64..start + count {
+        let hash = state.rpc.get_block_hash(height).await?;
+        headers.push(serialize_hex(&state.rpc.get_block_header(&hash).await?));
+    }
+    Ok(headers)
+}
+
+/// Current tip and its `blockchain.headers.subscribe` notification
+async fn tip_notification(state: &AppState) -> Result<(BlockHash, Value), Failure> {
+    let (height, hash) = chain::tip(&state.rpc).await?;
+    let hex = serialize_hex(&state.rpc.get_block_header(&hash).await?);
+    Ok((hash, json!({ "height": height, "hex": hex })))
+}
+
+async fn merkle(state: &AppState, txid: &Txid, height: u64) -> Result<Value, Failure> {
+    let hash = state.rpc.get_block_hash(height).await?;
+    let txids = state.rpc.get_block_info(&hash).await?.tx;
+    let pos = txids.iter().position(|id| id == txid).ok_or_else(|| {
+        Failure::bad_request(format!("tx {} not in block at height {}", txid, height))
+    })?;
+    Ok(json!({
+        "block_height": height,
+        "merkle": merkle::merkle_branch(&txids, pos),
+        "pos": pos,
+    }))
+}
+
+/// A scripthash a connection subscribed to
+struct Subscription {
+    /// As the client sent it, for notifications
+    scripthash: String,
+    /// Confirmed part of the status as of `Session::indexed`
+    chain: StatusHasher,
+    status: Option<String>,
+}
+
+struct Session {
+    state: AppState,
+    peer: SocketAddr,
+    headers: bool,
+    /// Last tip announced to a `blockchain.headers.subscribe` subscriber
+    notified_tip: Option<BlockHash>,
+    subscriptions: HashMap<ScriptHash, Subscription>,
+    /// Address index tip the confirmed statuses were computed at
+    indexed: Option<BlockHash>,
+    mempool_seq: u64,
+}
+
+impl Session {
+    fn new(state: AppState, peer: SocketAddr) -> Self {
+        let mempool_seq = state.mempool.seq();
+        Self {
+            state,
+            peer,
+            headers: false,
+            notified_tip: None,
+            subscriptions: HashMap::new(),
+            indexed: None,
+            mempool_seq,
+        }
+    }
+
+    /// Runs `work` off the async runtime
+    async fn blocking<T: Send + 'static>(
+        &self,
+        work: impl FnOnce(&AppState) -> Result<T, Failure> + Send + 'static,
+    ) -> Result<T, Failure> {
+        let state = self.state.clone();
+        match tokio::task::spawn_blocking(move || work(&state)).await {
+            Ok(result) => result,
+            Err(e) => {
+                warn!("Task failed when answering Electrum request: {}", e);
+                Err(Failure::internal())
+            }
+        }
+    }
+
+    /// Answers one request line, a single request or a batch
+    async fn handle_line(&mut self, line: &[u8]) -> Option<Value> {
+        let request: Value = match serde_json::from_slice(line) {
+            Ok(request) => request,
+            Err(_) => {
+                return Some(response(
+                    Value::Null,
+                    Err(Failure::new(PARSE_ERROR, "parse error")),
+                ))
+            }
+        };
+        match request {
+            Value::Array(batch) => {
+                let mut responses = Vec::with_capacity(batch.len());
+                for request in batch {
+                    responses.extend(self.handle(request).await);
+                }
+                (!responses.is_empty()).then_some(Value::Array(responses))
+            }
+            request => self.handle(request).await,
+        }
+    }
+
+    /// Answers a request, `None` for notifications (requests without an id)
+    async fn handle(&mut self, request: Value) -> Option<Value> {
+        let id = request.get("id").cloned();
+        let Some(method) = request.get("method").and_then(Value::as_str) else {
+            return Some(response(
+                id.unwrap_or_default(),
+                Err(Failure::new(INVALID_REQUEST, "invalid request")),
+            ));
+        };
+        let params = match request.get("params") {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::Array(params)) => params.clone(),
+            Some(_) => {
+                return Some(response(
+                    id.unwrap_or_default(),
+                    Err(Failure::new(INVALID_REQUEST, "params must be an array")),
+                ))
+            }
+        };
+        let result = self.call(method, &params).await;
+        let known = result
+            .as_ref()
+            .err()
+            .is_none_or(|failure| failure.code != METHOD_NOT_FOUND);
+        metrics::counter!(
+            "electrum_requests_total",
+            "method" => if known { method.to_owned() } else { "unknown".to_owned() },
+        )
+        .increment(1);
+        id.map(|id| response(id, result))
+    }
+
+    async fn call(&mut self, method: &str, params: &[Value]) -> Result<Value, Failure> {
+        match method {
+            "server.version" => Ok(json!([SERVER_VERSION, PROTOCOL_VERSION])),
+            "server.banner" => Ok(json!(SERVER_VERSION)),
+            "server.donation_address" => Ok(json!("")),
+            "server.peers.subscribe" => Ok(json!([])),
+            "server.ping" => Ok(Value::Null),
+            "server.features" => Ok(json!({
+                "genesis_hash": genesis_block(self.state.network).block_hash(),
+                "hosts": {},
+                "protocol_max": PROTOCOL_VERSION,
+                "protocol_min": PROTOCOL_VERSION,
+                "pruning": null,
+                "server_version": SERVER_VERSION,
+                "hash_function": "sha256",
+            })),
+            "blockchain.headers.subscribe" => {
+                let (tip, notification) = tip_notification(&self.state).await?;
+                self.headers = true;
+                self.notified_tip = Some(tip);
+                Ok(notification)
+            }
+            "blockchain.block.header" => {
+                let height: u64 = param(params, 0)?;
+                check_cp_height(params, 1)?;
+                let mut headers = headers(&self.state, height, 1).await?;
+                headers
+                    .pop()
+                    .map(Value::from)
+                    .ok_or_else(|| Failure::bad_request(format!("height {} out of range", height)))
+            }
+            "blockchain.block.headers" => {
+                let start: u64 = param(params, 0)?;
+                let count: u64 = param(params, 1)?;
+                check_cp_height(params, 2)?;
+                let headers = headers(&self.state, start, count.min(MAX_HEADERS)).await?;
+                Ok(json!({ "count": headers.len(), "hex": headers.concat(), "max": MAX_HEADERS }))
+            }
+            "blockchain.estimatefee" => {
+                let blocks: u16 = param(params, 0)?;
+                let rate =
+                    fees::get_fee_rate(&self.state.rpc, &self.state.fee_limits, blocks.max(1))
+                        .await?;
+                Ok(json!(fees::sat_vb_to_btc_kvb(rate)))
+            }
+            "blockchain.relayfee" => {
+                let info = self.state.rpc.get_network_info().await?;
+                Ok(json!(info.relay_fee.to_btc()))
+            }
+            "blockchain.scripthash.get_balance" => {
+                let script = parse_scripthash(&param::<String>(params, 0)?)?;
+                self.blocking(move |state| balance_blocking(state, &script))
+                    .await
+            }
+            "blockchain.scripthash.get_history" => {
+                let script = parse_scripthash(&param::<String>(params, 0)?)?;
+                self.blocking(move |state| history_blocking(state, &script))
+                    .await
+            }
+            "blockchain.scripthash.get_mempool" => {
+                let script = parse_scripthash(&param::<String>(params, 0)?)?;
+                self.blocking(move |state| {
+                    index(state)?;
+                    Ok(Value::Array(mempool_entries(state, &script)))
+                })
+                .await
+            }
+            "blockchain.scripthash.listunspent" => {
+                let script = parse_scripthash(&param::<String>(params, 0)?)?;
+                self.blocking(move |state| unspent_blocking(state, &script))
+                    .await
+            }
+            "blockchain.scripthash.subscribe" => {
+                let scripthash: String = param(params, 0)?;
+                let script = parse_scripthash(&scripthash)?;
+                self.subscribe(scripthash.to_lowercase(), script).await
+            }
+            "blockchain.scripthash.unsubscribe" => {
+                let script = parse_scripthash(&param::<String>(params, 0)?)?;
+                Ok(json!(self.subscriptions.remove(&script).is_some()))
+            }
+            "blockchain.transaction.broadcast" => {
+                let hex: String = param(params, 0)?;
+                let result = self.state.rpc.send_raw_transaction(&hex).await;
+                self.state
+                    .audit
+                    .record(
+                        &self.state.rpc,
+                        Interface::Electrum,
+                        Some(self.peer),
+                        &hex,
+                        &result,
+                    )
+                    .await;
+                let txid = result?;
+                info!("Broadcast transaction {} from Electrum client", txid);
+                self.state.hooks.broadcast(txid, hex);
+                Ok(json!(txid))
+            }
+            "blockchain.transaction.get" => {
+                let txid = parse_txid(&param::<String>(params, 0)?)?;
+                let verbose = optional_param(params, 1)?.unwrap_or(false);
+                Ok(self
+                    .state
+                    .rpc
+                    .call("getrawtransaction", &[json!(txid), json!(verbose)])
+                    .await?)
+            }
+            "blockchain.transaction.get_merkle" => {
+                let txid = parse_txid(&param::<String>(params, 0)?)?;
+                let height: u64 = param(params, 1)?;
+                merkle(&self.state, &txid, height).await
+            }
+            "mempool.get_fee_histogram" => {
+                let rates = self
+                    .state
+                    .mempool
+                    .filter(|_| true)
+                    .iter()
+                    .map(|tx| (tx.fee.to_sat() as f64 / tx.vsize.max(1) as f64, tx.vsize))
+                    .collect();
+                Ok(json!(mempool::fee_histogram(rates)))
+            }
+            _ => Err(Failure::new(
+                METHOD_NOT_FOUND,
+                format!("unknown method {:?}", method),
+            )),
+        }
+    }
+
+    async fn subscribe(
+        &mut self,
+        scripthash: String,
+        script: ScriptHash,
+    ) -> Result<Value, Failure> {
+        if !self.subscriptions.contains_key(&script)
+            && self.subscriptions.len() >= MAX_SUBSCRIPTIONS
+        {
+            return Err(Failure::bad_request("too many subscriptions"));
+        }
+        let (indexed, chain, status) = self
+            .blocking(move |state| {
+                let index = index(state)?;
+                let indexed = index.tip()?.map(|(_, hash)| hash);
+                let chain = chain_status_blocking(&index, &script)?;
+                let status = status(state, &script, &chain);
+                Ok((indexed, chain, status))
+            })
+            .await?;
+        // Statuses computed at another index tip are brought up to date by the next check
+        if self.subscriptions.is_empty() {
+            self.indexed = indexed;
+        }
+        self.subscriptions.insert(
+            script,
+            Subscription {
+                scripthash,
+                chain,
+                status: status.clone(),
+            },
+        );
+        Ok(json!(status))
+    }
+
+    /// Notification of the new tip for a headers subscriber, unless it was announced already
+    async fn on_block(&mut self) -> Result<Option<Value>, Failure> {
+        if !self.headers {
+            return Ok(None);
+        }
+        let (hash, header) = tip_notification(&self.state).await?;
+        if self.notified_tip == Some(hash) {
+            return Ok(None);
+        }
+        self.notified_tip = Some(hash);
+        Ok(Some(notification(
+            "blockchain.headers.subscribe",
+            json!([header]),
+        )))
+    }
+
+    /// Notifications of subscribed scripthashes whose status changed since the last
+    /// check. Confirmed parts are recomputed when the index moved, mempool parts
+    /// when the mempool mirror changed.
+    async fn check_statuses(&mut self) -> Result<Vec<Value>, Failure> {
+        if self.subscriptions.is_empty() {
+            return Ok(Vec::new());
+        }
+        let seq = self.state.mempool.seq();
+        let indexed = self.indexed;
+        let mempool_changed = seq != self.mempool_seq;
+        let subscribed: Vec<(ScriptHash, StatusHasher)> = self
+            .subscriptions
+            .iter()
+            .map(|(script, subscription)| (*script, subscription.chain.clone()))
+            .collect();
+        let checked = self
+            .blocking(move |state| {
+                let index = index(state)?;
+                let tip = index.tip()?.map(|(_, hash)| hash);
+                if tip == indexed && !mempool_changed {
+                    return Ok(None);
+                }
+                let mut statuses = Vec::with_capacity(subscribed.len());
+                for (script, mut chain) in subscribed {
+                    if tip != indexed {
+                        chain = chain_status_blocking(&index, &script)?;
+                    }
+                    let status = status(state, &script, &chain);
+                    statuses.push((script, chain, status));
+                }
+                Ok(Some((tip, statuses)))
+            })
+            .await;
+        let (tip, statuses) = match checked {
+            Ok(Some(checked)) => checked,
+            Ok(None) => return Ok(Vec::new()),
+            // Keep the subscriptions while the index syncs, notifying once it's back
+            Err(failure) if failure.code == BAD_REQUEST => return Ok(Vec::new()),
+            Err(failure) => return Err(failure),
+        };
+        self.indexed = tip;
+        self.mempool_seq = seq;
+        let mut notifications = Vec::new();
+        for (script, chain, status) in statuses {
+            // Unsubscribed while the check ran
+            let Some(subscription) = self.subscriptions.get_mut(&script) else {
+                continue;
+            };
+            subscription.chain = chain;
+            if subscription.status != status {
+                subscription.status = status.clone();
+                notifications.push(notification(
+                    "blockchain.scripthash.subscribe",
+                    json!([subscription.scripthash, status]),
+                ));
+            }
+        }
+        Ok(notifications)
+    }
+}
+
+/// Merkle proofs to a checkpoint aren't kept, so only `cp_height` 0 is accepted
+fn check_cp_height(params: &[Value], index: usize) -> Result<(), Failure> {
+    match optional_param::<u64>(params, index)? {
+        None | Some(0) => Ok(()),
+        Some(_) => Err(Failure::bad_request("cp_height is not supported")),
+    }
+}
+
+fn response(id: Value, result: Result<Value, Failure>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(failure) => json!({ "jsonrpc": "2.0", "id": id, "error": failure.to_json() }),
+    }
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+/// Reads request lines into `requests` until the client disconnects or sends a
+/// line over `MAX_REQUEST_BYTES`
+async fn read_requests<R: AsyncRead + Unpin>(reader: R, requests: mpsc::Sender<Vec<u8>>) {
+    let mut reader = BufReader::new(reader);
+    loop {
+        let mut line = Vec::new();
+        match (&mut reader)
+            .take(MAX_REQUEST_BYTES)
+            .read_until(b'\n', &mut line)
+            .await
+        {
+            Ok(0) => return,
+            Ok(read) => {
+                if line.last() != Some(&b'\n') && read as u64 == MAX_REQUEST_BYTES {
+                    debug!("Closing Electrum connection after an oversized request");
+                    return;
+                }
+                if line.iter().all(u8::is_ascii_whitespace) {
+                    continue;
+                }
+                if requests.send(line).await.is_err() {
+                    return;
+                }
+            }
+            Err(e) => {
+                debug!("Failed to read Electrum request: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+async fn write_message<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    message: &Value,
+) -> anyhow::Result<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    writer.write_all(&line).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+async fn serve_connection<S>(
+    stream: S,
+    peer: SocketAddr,
+    state: AppState,
+    watcher: Arc<ChainWatcher>,
+) -> anyhow::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let (sender, mut requests) = mpsc::channel(REQUEST_BUFFER);
+    let reader = tokio::spawn(read_requests(reader, sender));
+    let mut session = Session::new(state, peer);
+    let mut blocks = watcher.subscribe();
+    let mut ticker = tokio::time::interval(STATUS_CHECK_INTERVAL);
+    let result = async {
+        loop {
+            tokio::select! {
+                request = requests.recv() => {
+                    let Some(line) = request else {
+                        return Ok(());
+                    };
+                    if let Some(response) = session.handle_line(&line).await {
+                        write_message(&mut writer, &response).await?;
+                    }
+                }
+                block = blocks.recv() => match block {
+                    Ok(_) | Err(RecvError::Lagged(_)) => {
+                        if let Ok(Some(notification)) = session.on_block().await {
+                            write_message(&mut writer, &notification).await?;
+                        }
+                    }
+                    Err(RecvError::Closed) => return Ok(()),
+                },
+                _ = ticker.tick() => {
+                    if let Ok(notifications) = session.check_statuses().await {
+                        for notification in notifications {
+                            write_message(&mut writer, &notification).await?;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    .await;
+    reader.abort();
+    result
+}
+
+/// Binds every listener and serves Electrum clients on them in the background
+pub async fn serve(
+    listeners: Vec<Listener>,
+    state: AppState,
+    watcher: Arc<ChainWatcher>,
+) -> anyhow::Result<()> {
+    for listener in listeners {
+        let tls = listener.tls_config().await?.map(TlsAcceptor::from);
+        let tcp = TcpListener::bind(listener.addr())
+            .await
+            .with_context(|| format!("Failed to bind Electrum listener {}", listener.addr()))?;
+        info!(
+            "Serving Electrum protocol on {}{}",
+            listener.addr(),
+            if tls.is_some() { " (TLS)" } else { "" }
+        );
+        let state = state.clone();
+        let watcher = watcher.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, peer) = match tcp.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        warn!("Failed to accept Electrum connection: {}", e);
+                        tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                        continue;
+                    }
+                };
+                metrics::counter!("electrum_connections_total").increment(1);
+                let tls = tls.clone();
+                let state = state.clone();
+                let watcher = watcher.clone();
+                tokio::spawn(async move {
+                    let served = match tls {
+                        Some(tls) => match tls.accept(stream).await {
+                            Ok(stream) => serve_connection(stream, peer, state, watcher).await,
+                            Err(e) => Err(anyhow::Error::new(e).context("TLS handshake failed")),
+                        },
+                        None => serve_connection(stream, peer, state, watcher).await,
+                    };
+                    if let Err(e) = served {
+                        debug!("Electrum connection from {} closed: {:#}", peer, e);
+                    }
+                });
+            }
+        });
+    }
+    Ok(())
+}
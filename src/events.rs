@@ -0,0 +1,202 @@
+//! Server-Sent Events stream of chain and fee events.
+//!
+//! `/api/events` streams `block` events for every new block, a `reorg` event
+//! before the first block of a new best chain, and `fees` events when a
+//! recommended fee rate moved by at least the configured share since the last
+//! `fees` event. Heartbeat comments keep proxies from closing idle streams.
+//!
+//! Events carry their [`journal`](crate::journal) sequence number as SSE id, and
+//! a client reconnecting with `Last-Event-ID` first gets the retained events it
+//! missed.
+
+use std::convert::Infallible;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, UNIX_EPOCH};
+
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use futures_util::{Stream, StreamExt};
+use serde_json::{json, Value};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::warn;
+
+use crate::chain::ChainWatcher;
+use crate::fees::{self, FeeLimits};
+use crate::journal::{EventJournal, JournalEvent};
+use crate::rpc::Rpc;
+use crate::AppState;
+
+/// Events buffered per client before slow ones start skipping
+const EVENT_BUFFER: usize = 64;
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+pub struct EventStream {
+    sender: broadcast::Sender<JournalEvent>,
+    journal: Arc<EventJournal>,
+    /// Recommended fees at the last `fees` event
+    last_fees: RwLock<Option<Value>>,
+}
+
+/// Whether any rate of `current` moved by at least `threshold` (a share of the
+/// previous rate) from `previous`
+fn fees_changed(previous: &Value, current: &Value, threshold: f64) -> bool {
+    let (Some(previous), Some(current)) = (previous.as_object(), current.as_object()) else {
+        return true;
+    };
+    current.iter().any(|(name, rate)| {
+        match (previous.get(name).and_then(Value::as_f64), rate.as_f64()) {
+            (Some(before), Some(after)) => (after - before).abs() >= before.abs() * threshold,
+            _ => true,
+        }
+    })
+}
+
+impl EventStream {
+    pub fn new(journal: Arc<EventJournal>) -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUFFER);
+        Self {
+            sender,
+            journal,
+            last_fees: RwLock::new(None),
+        }
+    }
+
+    /// Live events, as journaled
+    pub fn subscribe(&self) -> broadcast::Receiver<JournalEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Journals the event, then streams it
+    async fn send(&self, name: &'static str, data: Value) {
+        let journal = self.journal.clone();
+        match tokio::task::spawn_blocking(move || journal.append_blocking(name, data)).await {
+            // Nobody listening is fine, clients come and go
+            Ok(event) => _ = self.sender.send(event),
+            Err(e) => warn!("Task failed when journaling {} event: {}", name, e),
+        }
+    }
+
+    /// Sends block and reorg events as the watcher announces blocks, and checks
+    /// the recommended fees for changes every `interval`
+    pub async fn run(
+        self: Arc<Self>,
+        rpc: Arc<Rpc>,
+        watcher: Arc<ChainWatcher>,
+        fee_limits: FeeLimits,
+        interval: Duration,
+        fee_change: f64,
+    ) {
+        let mut blocks = watcher.subscribe();
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                block = blocks.recv() => match block {
+                    Ok(block) => {
+                        if !block.disconnected.is_empty() {
+                            self.send(
+                                "reorg",
+                                json!({
+                                    "fork_height": block.height - 1,
+                                    "disconnected": block.disconnected.len(),
+                                }),
+                            )
+                            .await;
+                        }
+                        let seen_at = block
+                            .seen_at
+                            .duration_since(UNIX_EPOCH)
+                            .map_or(0, |since| since.as_secs());
+                        self.send(
+                            "block",
+                            json!({
+                                "height": block.height,
+                                "hash": block.hash.to_string(),
+                                "seen_at": seen_at,
+                            }),
+                        )
+                        .await;
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Event stream skipped {} blocks", skipped);
+                    }
+                    Err(RecvError::Closed) => return,
+                },
+                _ = ticker.tick() => self.check_fees(&rpc, fee_limits, fee_change).await,
+            }
+        }
+    }
+
+    async fn check_fees(&self, rpc: &Rpc, fee_limits: FeeLimits, fee_change: f64) {
+        let fees = match fees::recommended_fees(rpc, &fee_limits).await {
+            Ok(fees) => serde_json::to_value(fees).expect("fees always serialize"),
+            Err(e) => {
+                warn!("Failed to get recommended fees for the event stream: {}", e);
+                return;
+            }
+        };
+        {
+            let mut last_fees = self.last_fees.write().expect("event stream lock poisoned");
+            if last_fees
+                .as_ref()
+                .is_some_and(|previous| !fees_changed(previous, &fees, fee_change))
+            {
+                return;
+            }
+            *last_fees = Some(fees.clone());
+        }
+        self.send("fees", fees).await;
+    }
+}
+
+fn sse_event(event: &JournalEvent) -> Event {
+    Event::default()
+        .id(event.seq.to_string())
+        .event(&event.event)
+        .data(event.data.to_string())
+}
+
+pub async fn get_events(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    metrics::counter!("event_stream_clients_total").increment(1);
+    // Subscribing before reading the journal leaves no gap between the replayed
+    // events and the live ones, and the overlap is skipped by sequence number
+    let receiver = state.events.sender.subscribe();
+    let last_seen = headers
+        .get("last-event-id")
+        .and_then(|id| id.to_str().ok()?.parse::<u64>().ok());
+    // Live events up to the newest journaled one are replayed or were seen,
+    // even when the id is from before a restart of an in-memory journal
+    let (missed, last_seen) = match last_seen {
+        Some(seq) => {
+            let newest = state.journal.last_seq();
+            let missed = state.journal.since(seq);
+            let last_seen = missed.last().map(|event| event.seq).or(newest);
+            (missed, last_seen)
+        }
+        None => (Vec::new(), None),
+    };
+    let replay =
+        futures_util::stream::iter(missed.iter().map(sse_event).map(Ok).collect::<Vec<_>>());
+    let live = futures_util::stream::unfold(receiver, move |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) if last_seen.is_some_and(|seq| event.seq <= seq) => {}
+                Ok(event) => return Some((Ok(sse_event(&event)), receiver)),
+                Err(RecvError::Lagged(skipped)) => {
+                    metrics::counter!("event_stream_skipped_total").increment(skipped);
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    Sse::new(replay.chain(live)).keep_alive(
+        KeepAlive::new()
+            .interval(HEARTBEAT_INTERVAL)
+            .text("heartbeat"),
+    )
+}
@@ -0,0 +1,445 @@
+//! Failover and read balancing between several bitcoind nodes, when
+//! `BITCOIN_RPC_URL` lists more than one.
+//!
+//! Calls go to the first node in the configured order that is healthy:
+//! reachable at its last check and no more than `RPC_MAX_LAG` blocks behind
+//! the highest node. Every `RPC_CHECK_INTERVAL` each node is asked for its
+//! block count. A call that can't reach its node marks the node down right
+//! away and is retried on the next one, so a node going away costs a single
+//! failed attempt rather than a check interval; errors the node answers with
+//! are passed on as they are. The preferred node takes over again once it's
+//! back and caught up. Switches are logged, exported as `rpc_node_*` metrics
+//! and degrade the `failover` health component while a fallback node serves.
+//!
+//! With `RPC_BALANCE`, reads whose answer is the same on every synced node,
+//! like blocks, transactions and fee estimates, are spread over the healthy
+//! nodes instead, round-robin or to the one with the fewest calls in flight.
+//! Tip and mempool queries stay on the active node so minipool's view of the
+//! chain doesn't jump between nodes. `RPC_WRITE` decides where broadcasts go:
+//! the active node, every healthy node, or one designated node, never failing
+//! over from it.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::bail;
+use bitcoincore_rpc::jsonrpc::error::Error as JsonRpcError;
+use bitcoincore_rpc::jsonrpc::{Request, Response};
+use tracing::{info, warn};
+
+use crate::health::{Health, Severity};
+use crate::rpc::{self, RpcFuture, RpcTransport};
+
+const HEALTH_COMPONENT: &str = "failover";
+
+/// Calls answered alike by every node on the same chain, which `RPC_BALANCE`
+/// spreads
+const BALANCED_METHODS: &[&str] = &[
+    "getblock",
+    "getblockhash",
+    "getblockheader",
+    "getblockstats",
+    "getblockfilter",
+    "getrawtransaction",
+    "gettxout",
+    "estimatesmartfee",
+    "decoderawtransaction",
+    "decodescript",
+    "testmempoolaccept",
+];
+
+/// Broadcasts, routed by `RPC_WRITE`
+const WRITE_METHODS: &[&str] = &["sendrawtransaction", "submitpackage"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadBalance {
+    /// Every call goes to the active node
+    Failover,
+    RoundRobin,
+    LeastInFlight,
+}
+
+impl FromStr for ReadBalance {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "failover" => Ok(Self::Failover),
+            "round-robin" => Ok(Self::RoundRobin),
+            "least-in-flight" => Ok(Self::LeastInFlight),
+            _ => bail!(
+                "Unknown balancing policy {:?}, expected failover, round-robin or least-in-flight",
+                s
+            ),
+        }
+    }
+}
+
+impl fmt::Display for ReadBalance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Failover => "failover",
+            Self::RoundRobin => "round-robin",
+            Self::LeastInFlight => "least-in-flight",
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WriteTarget {
+    /// The active node, failing over like reads
+    Active,
+    /// Every healthy node, answering with the active node's result
+    All,
+    /// Only the node of this name, `host:port`
+    Node(String),
+}
+
+impl FromStr for WriteTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "active" => Ok(Self::Active),
+            "all" => Ok(Self::All),
+            "" => bail!("Empty write target, expected active, all or a node's host:port"),
+            _ => Ok(Self::Node(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for WriteTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Active => f.write_str("active"),
+            Self::All => f.write_str("all"),
+            Self::Node(name) => f.write_str(name),
+        }
+    }
+}
+
+struct RpcNode {
+    name: String,
+    transport: Box<dyn RpcTransport>,
+    healthy: AtomicBool,
+    in_flight: AtomicUsize,
+}
+
+/// A call in flight on a node, counted until it's answered or given up on
+struct InFlight<'a>(&'a AtomicUsize);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl RpcNode {
+    async fn send<'a, T>(
+        &'a self,
+        send: &impl Fn(&'a dyn RpcTransport) -> RpcFuture<'a, T>,
+    ) -> Result<T, JsonRpcError> {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        let _in_flight = InFlight(&self.in_flight);
+        metrics::counter!("rpc_node_calls_total", "node" => self.name.clone()).increment(1);
+        send(self.transport.as_ref()).await
+    }
+}
+
+pub struct Failover {
+    /// In order of preference
+    nodes: Vec<RpcNode>,
+    max_lag: u64,
+    /// Index of the node calls go to first
+    active: AtomicUsize,
+    balance: ReadBalance,
+    /// Index of the node writes are pinned to, or all of them with `None`;
+    /// writes follow the active node when `pin_writes` is false
+    write_node: Option<usize>,
+    pin_writes: bool,
+    /// Balanced calls so far, for round-robin
+    turn: AtomicUsize,
+}
+
+impl Failover {
+    /// Starts out on the first node, assuming all are healthy until checked.
+    /// Fails when `write` names a node that isn't among `nodes`.
+    pub fn new(
+        nodes: Vec<(String, Box<dyn RpcTransport>)>,
+        max_lag: u64,
+        balance: ReadBalance,
+        write: &WriteTarget,
+    ) -> anyhow::Result<Arc<Self>> {
+        let (write_node, pin_writes) = match write {
+            WriteTarget::Active => (None, false),
+            WriteTarget::All => (None, true),
+            WriteTarget::Node(target) => match nodes.iter().position(|(name, _)| name == target) {
+                Some(index) => (Some(index), true),
+                None => bail!(
+                    "Write node {} isn't one of the BITCOIN_RPC_URL nodes",
+                    target
+                ),
+            },
+        };
+        Ok(Arc::new(Self {
+            nodes: nodes
+                .into_iter()
+                .map(|(name, transport)| RpcNode {
+                    name,
+                    transport,
+                    healthy: AtomicBool::new(true),
+                    in_flight: AtomicUsize::new(0),
+                })
+                .collect(),
+            max_lag,
+            active: AtomicUsize::new(0),
+            balance,
+            write_node,
+            pin_writes,
+            turn: AtomicUsize::new(0),
+        }))
+    }
+
+    /// Transport sending each call to the active node
+    pub fn transport(self: &Arc<Self>) -> Arc<dyn RpcTransport> {
+        Arc::new(FailoverTransport(self.clone()))
+    }
+
+    pub async fn run(self: Arc<Self>, interval: Duration, health: Arc<Health>) {
+        health.register(HEALTH_COMPONENT, Severity::Soft, Some(interval * 3));
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            // Nodes are checked in parallel so an unreachable one doesn't delay the rest
+            let checks: Vec<_> = (0..self.nodes.len())
+                .map(|index| {
+                    let failover = self.clone();
+                    tokio::spawn(async move {
+                        let node = &failover.nodes[index];
+                        rpc::call::<u64>(node.transport.as_ref(), "getblockcount", &[]).await
+                    })
+                })
+                .collect();
+            let mut heights = Vec::with_capacity(checks.len());
+            for (node, check) in self.nodes.iter().zip(checks) {
+                let height = match check.await {
+                    Ok(Ok(height)) => Some(height),
+                    Ok(Err(e)) => {
+                        if node.healthy.load(Ordering::Relaxed) {
+                            warn!("Node {} is unreachable: {}", node.name, e);
+                        }
+                        None
+                    }
+                    Err(e) => {
+                        warn!("Task failed when checking node {}: {}", node.name, e);
+                        None
+                    }
+                };
+                heights.push(height);
+            }
+            let best = heights.iter().flatten().copied().max().unwrap_or_default();
+            for (node, height) in self.nodes.iter().zip(heights) {
+                let healthy = height.is_some_and(|height| height + self.max_lag >= best);
+                let was_healthy = node.healthy.swap(healthy, Ordering::Relaxed);
+                if let Some(height) = height {
+                    metrics::gauge!("rpc_node_height", "node" => node.name.clone())
+                        .set(height as f64);
+                    if !healthy && was_healthy {
+                        warn!(
+                            "Node {} fell {} blocks behind at height {}",
+                            node.name,
+                            best - height,
+                            height
+                        );
+                    } else if healthy && !was_healthy {
+                        info!("Node {} is healthy again at height {}", node.name, height);
+                    }
+                }
+                metrics::gauge!("rpc_node_healthy", "node" => node.name.clone())
+                    .set(f64::from(healthy));
+            }
+            let active = self.select();
+            if !self
+                .nodes
+                .iter()
+                .any(|node| node.healthy.load(Ordering::Relaxed))
+            {
+                health.failure(HEALTH_COMPONENT, &"No healthy node");
+            } else if active != 0 {
+                health.failure(
+                    HEALTH_COMPONENT,
+                    &format!("Serving from fallback node {}", self.nodes[active].name),
+                );
+            } else {
+                health.success(HEALTH_COMPONENT);
+            }
+        }
+    }
+
+    /// Makes the first healthy node the active one, keeping the current one
+    /// when none is healthy, and returns its index
+    fn select(&self) -> usize {
+        let current = self.active.load(Ordering::Relaxed);
+        let Some(preferred) = self
+            .nodes
+            .iter()
+            .position(|node| node.healthy.load(Ordering::Relaxed))
+        else {
+            return current;
+        };
+        if self
+            .active
+            .compare_exchange(current, preferred, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+            && preferred != current
+        {
+            let (from, to) = (&self.nodes[current].name, &self.nodes[preferred].name);
+            if preferred < current {
+                info!("Switching back from node {} to node {}", from, to);
+            } else {
+                warn!("Failing over from node {} to node {}", from, to);
+                metrics::counter!("rpc_failovers_total").increment(1);
+            }
+            for (index, node) in self.nodes.iter().enumerate() {
+                metrics::gauge!("rpc_node_active", "node" => node.name.clone())
+                    .set(f64::from(index == preferred));
+            }
+        }
+        preferred
+    }
+
+    fn mark_down(&self, index: usize, error: &JsonRpcError) {
+        let node = &self.nodes[index];
+        if node.healthy.swap(false, Ordering::Relaxed) {
+            warn!("Node {} is unreachable: {}", node.name, error);
+            metrics::gauge!("rpc_node_healthy", "node" => node.name.clone()).set(0.0);
+        }
+        self.select();
+    }
+
+    fn is_healthy(&self, index: usize) -> bool {
+        self.nodes[index].healthy.load(Ordering::Relaxed)
+    }
+
+    /// Node a balanced call goes to first, the active one when no node is
+    /// healthy
+    fn balanced_node(&self) -> usize {
+        let healthy: Vec<usize> = (0..self.nodes.len())
+            .filter(|&index| self.is_healthy(index))
+            .collect();
+        let picked = match self.balance {
+            ReadBalance::Failover => None,
+            ReadBalance::RoundRobin => healthy
+                .get(self.turn.fetch_add(1, Ordering::Relaxed) % healthy.len().max(1))
+                .copied(),
+            // Ties go to the preferred node
+            ReadBalance::LeastInFlight => healthy
+                .iter()
+                .copied()
+                .min_by_key(|&index| self.nodes[index].in_flight.load(Ordering::Relaxed)),
+        };
+        picked.unwrap_or_else(|| self.active.load(Ordering::Relaxed))
+    }
+
+    /// Indexes of the nodes a call is tried on: `first`, the other healthy
+    /// ones in order of preference, then the unhealthy ones as a last resort
+    fn attempt_order(&self, first: usize) -> Vec<usize> {
+        let others = (0..self.nodes.len()).filter(|&index| index != first);
+        std::iter::once(first)
+            .chain(others.clone().filter(|&index| self.is_healthy(index)))
+            .chain(others.filter(|&index| !self.is_healthy(index)))
+            .collect()
+    }
+
+    /// Runs `send` on nodes, starting with `first`, until one is reached
+    async fn send<'a, T>(
+        &'a self,
+        first: usize,
+        send: impl Fn(&'a dyn RpcTransport) -> RpcFuture<'a, T>,
+    ) -> Result<T, JsonRpcError> {
+        let mut last_error = None;
+        for index in self.attempt_order(first) {
+            match self.nodes[index].send(&send).await {
+                Err(e @ JsonRpcError::Transport(_)) => {
+                    self.mark_down(index, &e);
+                    last_error = Some(e);
+                }
+                result => return result,
+            }
+        }
+        Err(last_error.expect("at least one node is configured"))
+    }
+
+    /// Sends a write to every healthy node and answers with the active node's
+    /// result, or the first one reached when the active node wasn't
+    async fn send_everywhere<'a>(
+        &'a self,
+        request: &Request<'a>,
+    ) -> Result<Response, JsonRpcError> {
+        let active = self.active.load(Ordering::Relaxed);
+        let mut answer = None;
+        let mut last_error = None;
+        for index in self.attempt_order(active) {
+            if index != active && !self.is_healthy(index) {
+                continue;
+            }
+            match self.nodes[index]
+                .send(&|transport| transport.send_request(request.clone()))
+                .await
+            {
+                Err(e @ JsonRpcError::Transport(_)) => {
+                    self.mark_down(index, &e);
+                    last_error = Some(e);
+                }
+                result => {
+                    if answer.is_none() {
+                        answer = Some(result);
+                    }
+                }
+            }
+        }
+        answer.unwrap_or_else(|| Err(last_error.expect("at least one node is tried")))
+    }
+
+    async fn send_request<'a>(&'a self, request: Request<'a>) -> Result<Response, JsonRpcError> {
+        if self.pin_writes && WRITE_METHODS.contains(&request.method) {
+            return match self.write_node {
+                // A pinned node is the only one allowed to see the call
+                Some(index) => {
+                    self.nodes[index]
+                        .send(&|transport| transport.send_request(request.clone()))
+                        .await
+                }
+                None => self.send_everywhere(&request).await,
+            };
+        }
+        let first = if BALANCED_METHODS.contains(&request.method) {
+            self.balanced_node()
+        } else {
+            self.active.load(Ordering::Relaxed)
+        };
+        self.send(first, |transport| transport.send_request(request.clone()))
+            .await
+    }
+}
+
+struct FailoverTransport(Arc<Failover>);
+
+impl RpcTransport for FailoverTransport {
+    fn send_request<'a>(&'a self, request: Request<'a>) -> RpcFuture<'a, Response> {
+        Box::pin(self.0.send_request(request))
+    }
+
+    /// Batches go to the active node, which answers them from one view of the chain
+    fn send_batch<'a>(&'a self, requests: &'a [Request<'a>]) -> RpcFuture<'a, Vec<Response>> {
+        Box::pin(
+            self.0
+                .send(self.0.active.load(Ordering::Relaxed), move |transport| {
+                    transport.send_batch(requests)
+                }),
+        )
+    }
+}
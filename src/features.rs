@@ -0,0 +1,144 @@
+//! Runtime feature discovery for client libraries.
+//!
+//! `/api/v1/features` lists the routes this deployment mounts, with their page
+//! sizes and the timeout, retry and response size policy they run under, so
+//! clients can check what is available instead of assuming a configuration.
+//! Admin routes are only listed to callers holding the admin token.
+
+use std::sync::Arc;
+
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::response::IntoResponse;
+use axum::Json;
+use serde::Serialize;
+
+use crate::admin::AdminToken;
+use crate::warmup::Warmup;
+use crate::{AppState, RouteInfo};
+
+#[derive(Clone, Serialize)]
+struct Endpoint {
+    method: String,
+    path: &'static str,
+    /// `N` of `/api/vN/` paths, unset for the esplora-compatible routes
+    version: Option<u32>,
+    /// Most items of a list per response
+    page_size: Option<usize>,
+    timeout_ms: u64,
+    /// Retries of idempotent requests failing with a server error
+    retries: u32,
+    max_response_bytes: Option<usize>,
+    admin: bool,
+}
+
+/// Request rate accepted right now, across all callers
+#[derive(Serialize)]
+struct RateLimit {
+    requests_per_second: f64,
+}
+
+#[derive(Serialize)]
+struct FeaturesResponse {
+    /// Version of minipool
+    version: &'static str,
+    /// Whether the request carried the admin token
+    admin: bool,
+    /// Unset when requests aren't throttled
+    rate_limit: Option<RateLimit>,
+    endpoints: Vec<Endpoint>,
+}
+
+pub struct Features {
+    endpoints: Vec<Endpoint>,
+    admin_token: AdminToken,
+    warmup: Option<Arc<Warmup>>,
+}
+
+/// `N` of a path under `/api/vN/`
+fn api_version(path: &str) -> Option<u32> {
+    path.strip_prefix("/api/v")?.split('/').next()?.parse().ok()
+}
+
+impl Features {
+    pub fn new(routes: &[RouteInfo], admin_token: AdminToken, warmup: Option<Arc<Warmup>>) -> Self {
+        let endpoints = routes
+            .iter()
+            .map(|route| Endpoint {
+                method: route.method.to_string(),
+                path: route.path,
+                version: api_version(route.path),
+                page_size: route.page_size,
+                timeout_ms: route.policy.timeout.as_millis() as u64,
+                retries: route.policy.retries,
+                max_response_bytes: route.response_limit,
+                admin: route.admin,
+            })
+            .collect();
+        Self {
+            endpoints,
+            admin_token,
+            warmup,
+        }
+    }
+}
+
+pub async fn get_features(State(state): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
+    let features = &state.features;
+    let admin = features.admin_token.authorizes(&headers);
+    Json(FeaturesResponse {
+        version: env!("CARGO_PKG_VERSION"),
+        admin,
+        rate_limit: features
+            .warmup
+            .as_ref()
+            .and_then(|warmup| warmup.current_limit())
+            .map(|requests_per_second| RateLimit {
+                requests_per_second,
+            }),
+        endpoints: features
+            .endpoints
+            .iter()
+            .filter(|endpoint| admin || !endpoint.admin)
+            .cloned()
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden;
+
+    fn endpoint(path: &'static str, page_size: Option<usize>, timeout_ms: u64) -> Endpoint {
+        Endpoint {
+            method: "GET".to_owned(),
+            path,
+            version: api_version(path),
+            page_size,
+            timeout_ms,
+            retries: if page_size.is_some() { 0 } else { 1 },
+            max_response_bytes: page_size.map(|_| 1 << 20),
+            admin: false,
+        }
+    }
+
+    #[test]
+    fn golden_features() {
+        golden::check(
+            "features",
+            &FeaturesResponse {
+                version: "0.1.0",
+                admin: false,
+                rate_limit: Some(RateLimit {
+                    requests_per_second: 37.5,
+                }),
+                endpoints: vec![
+                    endpoint("/health", None, 10_000),
+                    endpoint("/api/v1/features", None, 10_000),
+                    endpoint("/api/block/{hash}/txs/{start_index}", Some(25), 30_000),
+                ],
+            },
+        );
+    }
+}
@@ -0,0 +1,261 @@
+//! Scores fee estimates against the fee rates blocks actually included.
+//!
+//! At every new block both estimator modes are asked for a set of targets. Once
+//! `target` more blocks have arrived, a prediction counts as a hit if it pays at
+//! least the lowest fee rate any of those blocks included.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use axum::{extract::State, response::IntoResponse, Json};
+use bitcoincore_rpc::json::{EstimateMode, GetBlockStatsResultPartial};
+use serde::Serialize;
+use serde_json::json;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::warn;
+
+use crate::chain::{BlockEvent, ChainWatcher};
+use crate::health::{Health, Severity};
+use crate::json;
+use crate::rpc::Rpc;
+use crate::AppState;
+
+const HEALTH_COMPONENT: &str = "fee_accuracy";
+
+/// Confirmation targets scored by the tracker
+const TRACKED_TARGETS: &[u16] = &[1, 2, 3, 6, 12, 24, 144];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    Economical,
+    Conservative,
+}
+
+impl Mode {
+    const ALL: [Mode; 2] = [Mode::Economical, Mode::Conservative];
+
+    fn as_rpc(self) -> EstimateMode {
+        match self {
+            Mode::Economical => EstimateMode::Economical,
+            Mode::Conservative => EstimateMode::Conservative,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Mode::Economical => "economical",
+            Mode::Conservative => "conservative",
+        }
+    }
+}
+
+struct Prediction {
+    mode: Mode,
+    target: u16,
+    sat_vb: f64,
+}
+
+#[derive(Default)]
+struct Score {
+    samples: u64,
+    hits: u64,
+    error_sum: f64,
+    abs_error_sum: f64,
+}
+
+#[derive(Default)]
+struct TrackerState {
+    /// Predictions keyed by the tip height they were made at
+    predictions: BTreeMap<u64, Vec<Prediction>>,
+    /// Lowest included fee rate per block, for blocks with non-coinbase transactions
+    min_fee_rates: BTreeMap<u64, f64>,
+    scores: BTreeMap<(Mode, u16), Score>,
+}
+
+#[derive(Serialize)]
+pub struct AccuracyEntry {
+    mode: Mode,
+    target: u16,
+    samples: u64,
+    #[serde(serialize_with = "json::rate")]
+    hit_rate: f64,
+    #[serde(serialize_with = "json::rate")]
+    mean_error_sat_vb: f64,
+    #[serde(serialize_with = "json::rate")]
+    mean_abs_error_sat_vb: f64,
+}
+
+#[derive(Default)]
+pub struct FeeAccuracyTracker {
+    state: Mutex<TrackerState>,
+}
+
+impl FeeAccuracyTracker {
+    /// Scores and records predictions for every block announced by the watcher
+    pub async fn run(
+        self: Arc<Self>,
+        rpc: Arc<Rpc>,
+        watcher: Arc<ChainWatcher>,
+        health: Arc<Health>,
+    ) {
+        health.register(HEALTH_COMPONENT, Severity::Soft, None);
+        let mut blocks = watcher.subscribe();
+        loop {
+            let block = match blocks.recv().await {
+                Ok(block) => block,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Fee accuracy tracker skipped {} blocks", skipped);
+                    continue;
+                }
+                Err(RecvError::Closed) => return,
+            };
+            match self.process_block(&rpc, &block).await {
+                Ok(()) => health.success(HEALTH_COMPONENT),
+                Err(e) => {
+                    warn!(
+                        "Failed to score fee estimates at height {}: {}",
+                        block.height, e
+                    );
+                    health.failure(HEALTH_COMPONENT, &e);
+                }
+            }
+        }
+    }
+
+    async fn process_block(
+        &self,
+        rpc: &Rpc,
+        block: &BlockEvent,
+    ) -> Result<(), bitcoincore_rpc::Error> {
+        let height = block.height;
+        // By hash rather than height, so a reorg racing us can't mix up blocks
+        let stats: GetBlockStatsResultPartial = rpc
+            .call(
+                "getblockstats",
+                &[json!(block.hash), json!(["minfeerate", "txs"])],
+            )
+            .await?;
+
+        let mut predictions = Vec::with_capacity(TRACKED_TARGETS.len() * Mode::ALL.len());
+        for mode in Mode::ALL {
+            let estimates = rpc
+                .estimate_smart_fees(TRACKED_TARGETS, Some(mode.as_rpc()))
+                .await?;
+            for (&target, estimate) in TRACKED_TARGETS.iter().zip(estimates) {
+                if let Some(fee_rate) = estimate.fee_rate {
+                    predictions.push(Prediction {
+                        mode,
+                        target,
+                        sat_vb: fee_rate.to_sat() as f64 / 1000.0,
+                    });
+                }
+            }
+        }
+
+        let mut state = self.state.lock().expect("fee accuracy lock poisoned");
+        if stats.txs.unwrap_or(0) > 1 {
+            if let Some(min_fee_rate) = stats.min_fee_rate {
+                state
+                    .min_fee_rates
+                    .insert(height, min_fee_rate.to_sat() as f64);
+            }
+        }
+        state.score_due(height);
+        state.predictions.insert(height, predictions);
+
+        let max_target = *TRACKED_TARGETS.last().expect("targets not empty") as u64;
+        let horizon = height.saturating_sub(max_target);
+        state.predictions.retain(|&made_at, _| made_at >= horizon);
+        state.min_fee_rates.retain(|&at, _| at > horizon);
+        Ok(())
+    }
+
+    pub fn entries(&self) -> Vec<AccuracyEntry> {
+        let state = self.state.lock().expect("fee accuracy lock poisoned");
+        state
+            .scores
+            .iter()
+            .map(|(&(mode, target), score)| AccuracyEntry {
+                mode,
+                target,
+                samples: score.samples,
+                hit_rate: score.hits as f64 / score.samples as f64,
+                mean_error_sat_vb: score.error_sum / score.samples as f64,
+                mean_abs_error_sat_vb: score.abs_error_sum / score.samples as f64,
+            })
+            .collect()
+    }
+}
+
+impl TrackerState {
+    /// Scores every prediction whose target window ends at `height`
+    fn score_due(&mut self, height: u64) {
+        let mut updated = Vec::new();
+        for (&made_at, predictions) in &self.predictions {
+            for prediction in predictions {
+                if made_at + prediction.target as u64 != height {
+                    continue;
+                }
+                let window_min = self
+                    .min_fee_rates
+                    .range(made_at + 1..=height)
+                    .map(|(_, &rate)| rate)
+                    .reduce(f64::min);
+                // Windows made of empty blocks say nothing about the estimate
+                let Some(window_min) = window_min else {
+                    continue;
+                };
+
+                let error = prediction.sat_vb - window_min;
+                let score = self
+                    .scores
+                    .entry((prediction.mode, prediction.target))
+                    .or_default();
+                score.samples += 1;
+                score.hits += u64::from(error >= 0.0);
+                score.error_sum += error;
+                score.abs_error_sum += error.abs();
+                updated.push((prediction.mode, prediction.target));
+            }
+        }
+
+        for (mode, target) in updated {
+            let score = &self.scores[&(mode, target)];
+            let labels = [
+                ("mode", mode.as_str().to_string()),
+                ("target", target.to_string()),
+            ];
+            metrics::counter!("fee_estimate_evaluations_total", &labels).increment(1);
+            metrics::gauge!("fee_estimate_hit_ratio", &labels)
+                .set(score.hits as f64 / score.samples as f64);
+            metrics::gauge!("fee_estimate_mean_error_sat_vb", &labels)
+                .set(score.error_sum / score.samples as f64);
+        }
+    }
+}
+
+pub async fn get_fee_accuracy(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.fee_accuracy.entries())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden;
+
+    #[test]
+    fn golden_fee_accuracy() {
+        let tracker = FeeAccuracyTracker::default();
+        tracker.state.lock().unwrap().scores.insert(
+            (Mode::Economical, 2),
+            Score {
+                samples: 144,
+                hits: 118,
+                error_sum: 450.0,
+                abs_error_sum: 1056.0,
+            },
+        );
+        golden::check("fee_accuracy", &tracker.entries());
+    }
+}
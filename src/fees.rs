@@ -0,0 +1,406 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use bitcoincore_rpc::bitcoin::BlockHash;
+use bitcoincore_rpc::json::EstimateSmartFeeResult;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::cache::BlockCache;
+use crate::health::{Health, Severity};
+use crate::json;
+use crate::rpc::Rpc;
+use crate::AppState;
+
+const HEALTH_COMPONENT: &str = "fee_cache";
+
+/// Confirmation targets for fee estimation offered by mempool.space and blockstream.info
+pub const CONFIRMATION_TARGETS: &[u16] = &[
+    1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 144,
+    504, 1008,
+];
+
+/// Confirmation targets behind mempool.space's fastest, half hour, hour and
+/// economy recommendations
+const RECOMMENDED_TARGETS: [u16; 4] = [1, 3, 6, 144];
+
+/// Fee rate in sat/vB for targets the node has no estimate for, bitcoind's
+/// old 0.0001 BTC/kvB fallback, within the configured limits
+const FALLBACK_SAT_VB: f64 = 10.0;
+
+/// Bounds every fee rate served by minipool is clamped into
+#[derive(Clone, Copy, Debug)]
+pub struct FeeLimits {
+    pub floor_sat_vb: f64,
+    pub ceiling_sat_vb: f64,
+}
+
+impl FeeLimits {
+    /// Clamps the fee rate in sat/vB of `what` (like `6 blocks`), counting how
+    /// often each bound kicks in
+    pub fn clamp(&self, what: impl fmt::Display, sat_vb: f64) -> f64 {
+        if sat_vb < self.floor_sat_vb {
+            warn!(
+                "Fee estimate {} sat/vB for {} below floor, clamping to {}",
+                sat_vb, what, self.floor_sat_vb
+            );
+            metrics::counter!("fee_estimates_clamped_total", "bound" => "floor").increment(1);
+            self.floor_sat_vb
+        } else if sat_vb > self.ceiling_sat_vb {
+            warn!(
+                "Fee estimate {} sat/vB for {} above ceiling, clamping to {}",
+                sat_vb, what, self.ceiling_sat_vb
+            );
+            metrics::counter!("fee_estimates_clamped_total", "bound" => "ceiling").increment(1);
+            self.ceiling_sat_vb
+        } else {
+            sat_vb
+        }
+    }
+}
+
+/// Converts sat/vB into the BTC/kvB unit bitcoind uses
+pub fn sat_vb_to_btc_kvb(sat_vb: f64) -> f64 {
+    sat_vb * 1000.0 / 100_000_000.0
+}
+
+/// Clamped fee rate in sat/vB of the node's estimate for `blocks`
+fn fee_rate(limits: &FeeLimits, blocks: u16, estimate: &EstimateSmartFeeResult) -> f64 {
+    match estimate.fee_rate {
+        Some(fee_rate) => limits.clamp(
+            format_args!("{} blocks", blocks),
+            fee_rate.to_sat() as f64 / 1000.0,
+        ),
+        None => {
+            warn!(
+                "No fee rate estimate available for {} blocks, using fallback",
+                blocks
+            );
+            metrics::counter!("fee_estimates_fallback_total").increment(1);
+            FALLBACK_SAT_VB
+                .max(limits.floor_sat_vb)
+                .min(limits.ceiling_sat_vb)
+        }
+    }
+}
+
+/// Returns the clamped fee rate for `blocks` in sat/vB
+pub async fn get_fee_rate(
+    rpc: &Rpc,
+    limits: &FeeLimits,
+    blocks: u16,
+) -> Result<f64, bitcoincore_rpc::Error> {
+    let estimate = rpc.estimate_smart_fee(blocks, None).await?;
+    Ok(fee_rate(limits, blocks, &estimate))
+}
+
+/// Returns the clamped fee rates for each of `targets` in sat/vB, estimated in
+/// one batch
+pub async fn get_fee_rates(
+    rpc: &Rpc,
+    limits: &FeeLimits,
+    targets: &[u16],
+) -> Result<Vec<f64>, bitcoincore_rpc::Error> {
+    let estimates = rpc.estimate_smart_fees(targets, None).await?;
+    Ok(targets
+        .iter()
+        .zip(&estimates)
+        .map(|(&blocks, estimate)| fee_rate(limits, blocks, estimate))
+        .collect())
+}
+
+/// Fee rates of every confirmation target and the mempool's minimum fee rate,
+/// as of one refresh
+struct FeeSnapshot {
+    /// Clamped rate in sat/vB of each of `CONFIRMATION_TARGETS`
+    rates: Vec<f64>,
+    /// `mempoolminfee` in sat/vB
+    minimum: f64,
+    /// Best block when the refresh started, the estimates are dropped if a
+    /// reorg disconnects it
+    tip: BlockHash,
+    fetched_at: Instant,
+}
+
+/// Shortest `FEE_CACHE_TTL` besides 0s, keeping refreshes a reasonable load on the node
+pub const MIN_CACHE_TTL: Duration = Duration::from_secs(1);
+
+/// Fee estimates kept in memory, so fee endpoints don't call the node on every
+/// request. Refreshed three times per `ttl`, which lets them ride out a couple
+/// of failed refreshes; once a snapshot expires they call the node again.
+pub struct FeeCache {
+    /// Zero disables the cache, otherwise at least `MIN_CACHE_TTL`
+    ttl: Duration,
+    snapshot: RwLock<Option<Arc<FeeSnapshot>>>,
+}
+
+impl FeeCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            snapshot: RwLock::new(None),
+        }
+    }
+
+    pub async fn run(self: Arc<Self>, rpc: Arc<Rpc>, limits: FeeLimits, health: Arc<Health>) {
+        health.register(HEALTH_COMPONENT, Severity::Soft, Some(self.ttl));
+        let mut ticker = tokio::time::interval(self.ttl / 3);
+        loop {
+            ticker.tick().await;
+            match self.refresh(&rpc, &limits).await {
+                Ok(()) => health.success(HEALTH_COMPONENT),
+                Err(e) => {
+                    warn!("Failed to refresh fee estimates: {}", e);
+                    health.failure(HEALTH_COMPONENT, &e);
+                }
+            }
+        }
+    }
+
+    async fn refresh(&self, rpc: &Rpc, limits: &FeeLimits) -> Result<(), bitcoincore_rpc::Error> {
+        let tip = rpc.get_best_block_hash().await?;
+        let (rates, info) = tokio::try_join!(
+            get_fee_rates(rpc, limits, CONFIRMATION_TARGETS),
+            rpc.get_mempool_info()
+        )?;
+        let snapshot = FeeSnapshot {
+            rates,
+            minimum: info.mempool_min_fee.to_sat() as f64 / 1000.0,
+            tip,
+            fetched_at: Instant::now(),
+        };
+        *self.snapshot.write().expect("fee cache lock poisoned") = Some(Arc::new(snapshot));
+        Ok(())
+    }
+
+    /// The latest snapshot unless it's older than the TTL
+    fn fresh(&self) -> Option<Arc<FeeSnapshot>> {
+        let snapshot = self
+            .snapshot
+            .read()
+            .expect("fee cache lock poisoned")
+            .clone()
+            .filter(|snapshot| snapshot.fetched_at.elapsed() < self.ttl);
+        let outcome = if snapshot.is_some() { "hit" } else { "miss" };
+        metrics::counter!("fee_cache_requests_total", "outcome" => outcome).increment(1);
+        snapshot
+    }
+
+    /// Clamped fee rates of `CONFIRMATION_TARGETS` in sat/vB, from the cache
+    /// while it's fresh
+    pub async fn fee_rates(
+        &self,
+        rpc: &Rpc,
+        limits: &FeeLimits,
+    ) -> Result<Vec<f64>, bitcoincore_rpc::Error> {
+        match self.fresh() {
+            Some(snapshot) => Ok(snapshot.rates.clone()),
+            None => get_fee_rates(rpc, limits, CONFIRMATION_TARGETS).await,
+        }
+    }
+
+    /// Recommended fees from the cache while it's fresh
+    pub async fn recommended_fees(
+        &self,
+        rpc: &Rpc,
+        limits: &FeeLimits,
+    ) -> Result<RecommendedFees, bitcoincore_rpc::Error> {
+        let Some(snapshot) = self.fresh() else {
+            return recommended_fees(rpc, limits).await;
+        };
+        let rates = RECOMMENDED_TARGETS.map(|blocks| {
+            let index = CONFIRMATION_TARGETS
+                .iter()
+                .position(|&target| target == blocks)
+                .expect("recommended targets are confirmation targets");
+            snapshot.rates[index]
+        });
+        Ok(recommend(limits, rates, snapshot.minimum))
+    }
+}
+
+impl BlockCache for FeeCache {
+    fn evict(&self, hash: &BlockHash) {
+        let mut snapshot = self.snapshot.write().expect("fee cache lock poisoned");
+        if snapshot
+            .as_ref()
+            .is_some_and(|snapshot| snapshot.tip == *hash)
+        {
+            *snapshot = None;
+        }
+    }
+
+    fn clear(&self) {
+        *self.snapshot.write().expect("fee cache lock poisoned") = None;
+    }
+}
+
+/// Fee rates per confirmation target in BTC/kvB, or in sat/vB like esplora in
+/// a compatibility mode
+pub async fn get_fee_estimates(State(state): State<AppState>) -> impl IntoResponse {
+    let sat_vb_units = state.compat.is_some();
+    match state
+        .fee_cache
+        .fee_rates(&state.rpc, &state.fee_limits)
+        .await
+    {
+        Ok(rates) => Json(fee_estimates(rates, sat_vb_units)).into_response(),
+        Err(e) => {
+            warn!("Failed to get fee estimates: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "RPC error").into_response()
+        }
+    }
+}
+
+/// `rates` keyed by their confirmation target, in the units of [`get_fee_estimates`]
+fn fee_estimates(
+    rates: impl IntoIterator<Item = f64>,
+    sat_vb_units: bool,
+) -> BTreeMap<String, f64> {
+    CONFIRMATION_TARGETS
+        .iter()
+        .zip(rates)
+        .map(|(blocks, sat_vb)| {
+            let rate = if sat_vb_units {
+                json::round(sat_vb)
+            } else {
+                sat_vb_to_btc_kvb(sat_vb)
+            };
+            (blocks.to_string(), rate)
+        })
+        .collect()
+}
+
+/// Fee rates in sat/vB in the mempool.space format
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecommendedFees {
+    #[serde(serialize_with = "json::rate")]
+    fastest_fee: f64,
+    #[serde(serialize_with = "json::rate")]
+    half_hour_fee: f64,
+    #[serde(serialize_with = "json::rate")]
+    hour_fee: f64,
+    #[serde(serialize_with = "json::rate")]
+    economy_fee: f64,
+    #[serde(serialize_with = "json::rate")]
+    minimum_fee: f64,
+}
+
+pub async fn recommended_fees(
+    rpc: &Rpc,
+    limits: &FeeLimits,
+) -> Result<RecommendedFees, bitcoincore_rpc::Error> {
+    let minimum = rpc.get_mempool_info().await?.mempool_min_fee.to_sat() as f64 / 1000.0;
+    let mut rates = [0.0; 4];
+    rates.copy_from_slice(&get_fee_rates(rpc, limits, &RECOMMENDED_TARGETS).await?);
+    Ok(recommend(limits, rates, minimum))
+}
+
+/// Recommendations from the clamped rates of `RECOMMENDED_TARGETS` and the
+/// mempool's minimum fee rate, all in sat/vB
+fn recommend(limits: &FeeLimits, mut rates: [f64; 4], minimum: f64) -> RecommendedFees {
+    let minimum = limits.clamp("the mempool minimum", minimum);
+    // Longer targets never pay more than shorter ones, nor less than the node accepts
+    let mut previous = f64::INFINITY;
+    for rate in rates.iter_mut() {
+        *rate = rate.max(minimum);
+        *rate = rate.min(previous);
+        previous = *rate;
+    }
+    let [fastest_fee, half_hour_fee, hour_fee, economy_fee] = rates;
+    RecommendedFees {
+        fastest_fee,
+        half_hour_fee,
+        hour_fee,
+        economy_fee,
+        minimum_fee: minimum,
+    }
+}
+
+pub async fn get_recommended_fees(State(state): State<AppState>) -> impl IntoResponse {
+    match state
+        .fee_cache
+        .recommended_fees(&state.rpc, &state.fee_limits)
+        .await
+    {
+        Ok(fees) => Json(fees).into_response(),
+        Err(e) => {
+            warn!("Failed to get recommended fees: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "RPC error").into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden;
+    use crate::mock::MockNode;
+
+    const LIMITS: FeeLimits = FeeLimits {
+        floor_sat_vb: 1.0,
+        ceiling_sat_vb: 1000.0,
+    };
+
+    #[tokio::test]
+    async fn reorg_drops_estimates_from_the_stale_tip() {
+        let node = MockNode::new(10);
+        let rpc = node.rpc();
+        let cache = FeeCache::new(Duration::from_secs(60));
+        node.set_fee_rate(20.0);
+        cache.refresh(&rpc, &LIMITS).await.unwrap();
+
+        let disconnected = node.reorg(1, 2);
+        node.set_fee_rate(50.0);
+        assert_eq!(cache.fee_rates(&rpc, &LIMITS).await.unwrap()[0], 20.0);
+        for hash in &disconnected {
+            cache.evict(hash);
+        }
+        assert_eq!(cache.fee_rates(&rpc, &LIMITS).await.unwrap()[0], 50.0);
+    }
+
+    #[tokio::test]
+    async fn keeps_estimates_when_another_block_is_evicted() {
+        let node = MockNode::new(10);
+        let rpc = node.rpc();
+        let cache = FeeCache::new(Duration::from_secs(60));
+        node.set_fee_rate(20.0);
+        cache.refresh(&rpc, &LIMITS).await.unwrap();
+
+        cache.evict(&node.block_hash(9));
+        node.set_fee_rate(50.0);
+        assert_eq!(cache.fee_rates(&rpc, &LIMITS).await.unwrap()[0], 20.0);
+    }
+
+    #[test]
+    fn no_floor_serves_estimates_unchanged() {
+        let limits = FeeLimits {
+            floor_sat_vb: 0.0,
+            ..LIMITS
+        };
+        assert_eq!(limits.clamp("1 block", 0.5), 0.5);
+        assert_eq!(LIMITS.clamp("1 block", 0.5), 1.0);
+    }
+
+    #[test]
+    fn golden_fee_estimates() {
+        let rates = [
+            45.0, 31.82, 25.981, 22.5, 20.125, 18.371, 17.008, 15.91, 15.0, 14.23, 13.568, 12.99,
+            12.481, 12.027, 11.619, 11.25, 10.914, 10.607, 10.324, 10.062, 9.82, 9.594, 9.383,
+            9.186, 9.0, 3.75, 2.004, 1.417,
+        ];
+        golden::check("fee_estimates", &fee_estimates(rates, false));
+    }
+
+    #[test]
+    fn golden_recommended_fees() {
+        golden::check(
+            "recommended_fees",
+            &recommend(&LIMITS, [45.1234, 32.0, 25.5, 11.0], 1.0),
+        );
+    }
+}
@@ -0,0 +1,216 @@
+//! BIP158 compact block filters for light clients, from the node's block filter
+//! index (`blockfilterindex=1`).
+//!
+//! Filter headers commit to every filter before them, so a client that checks
+//! the header chain against several sources can trust the filters it downloads.
+
+use std::str::FromStr;
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use bitcoincore_rpc::bitcoin::hashes::Hash;
+use bitcoincore_rpc::bitcoin::hex::DisplayHex;
+use bitcoincore_rpc::bitcoin::{BlockHash, FilterHeader};
+use bitcoincore_rpc::json::GetBlockFilterResult;
+use bitcoincore_rpc::jsonrpc::error::{Error as JsonRpcError, RpcError};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::rpc::Rpc;
+use crate::AppState;
+
+/// Most filter headers per request, as many as a BIP157 `cfheaders` message
+pub const MAX_FILTER_HEADERS: u64 = 2000;
+
+/// Warns when the node has no block filter index, the filter endpoints then
+/// answer 503
+pub async fn check_index(rpc: Arc<Rpc>) {
+    match rpc.get_index_info().await {
+        Ok(info) => match info.basic_block_filter_index {
+            Some(status) if status.synced => info!("Serving block filters"),
+            Some(status) => info!(
+                "Serving block filters, the node's filter index is at height {}",
+                status.best_block_height
+            ),
+            None => warn!(
+                "The node has no block filter index, block filter endpoints are unavailable; run it with blockfilterindex=1"
+            ),
+        },
+        Err(e) => warn!(
+            "Failed to get the node's indexes, block filter endpoints may be unavailable: {}",
+            e
+        ),
+    }
+}
+
+/// Maps `getblockfilter` errors to client errors, passing the node's message on
+fn filter_error(error: &bitcoincore_rpc::Error) -> Option<(StatusCode, String)> {
+    // Error codes from Bitcoin Core's rpc/protocol.h
+    const RPC_MISC_ERROR: i32 = -1;
+    const RPC_INVALID_ADDRESS_OR_KEY: i32 = -5;
+
+    let bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(RpcError { code, message, .. })) = error
+    else {
+        return None;
+    };
+    let status = match *code {
+        // The index is disabled or hasn't reached the block yet
+        RPC_MISC_ERROR => StatusCode::SERVICE_UNAVAILABLE,
+        RPC_INVALID_ADDRESS_OR_KEY => StatusCode::NOT_FOUND,
+        _ => return None,
+    };
+    Some((status, message.clone()))
+}
+
+fn rpc_failure(action: &str, e: bitcoincore_rpc::Error) -> (StatusCode, String) {
+    filter_error(&e).unwrap_or_else(|| {
+        warn!("Failed to {}: {}", action, e);
+        (StatusCode::INTERNAL_SERVER_ERROR, "RPC error".to_string())
+    })
+}
+
+/// The filter header of a `getblockfilter` result, which the RPC crate types as
+/// a filter hash
+fn header_of(result: &GetBlockFilterResult) -> FilterHeader {
+    FilterHeader::from_raw_hash(result.header.to_raw_hash())
+}
+
+#[derive(Serialize)]
+struct BlockFilter {
+    /// Serialized BIP158 basic filter, hex
+    filter: String,
+    header: FilterHeader,
+}
+
+pub async fn get_block_filter(
+    State(state): State<AppState>,
+    Path(hash): Path<String>,
+) -> impl IntoResponse {
+    let Ok(block_hash) = BlockHash::from_str(&hash) else {
+        return (StatusCode::BAD_REQUEST, "Invalid block hash").into_response();
+    };
+    match state.rpc.get_block_filter(&block_hash).await {
+        Ok(result) => Json(BlockFilter {
+            filter: result.filter.to_lower_hex_string(),
+            header: header_of(&result),
+        })
+        .into_response(),
+        Err(e) => rpc_failure("get block filter", e).into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct FilterHeadersQuery {
+    count: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct FilterHeaders {
+    start_height: u64,
+    /// Header of the filter below `start_height`, all zeros for genesis
+    previous_header: FilterHeader,
+    /// Headers from `start_height` up, at most to the tip
+    headers: Vec<FilterHeader>,
+}
+
+async fn filter_header(rpc: &Rpc, height: u64) -> Result<FilterHeader, (StatusCode, String)> {
+    let hash = rpc
+        .get_block_hash(height)
+        .await
+        .map_err(|e| rpc_failure("get block hash", e))?;
+    rpc.get_block_filter(&hash)
+        .await
+        .map(|result| header_of(&result))
+        .map_err(|e| rpc_failure("get block filter", e))
+}
+
+async fn filter_headers(
+    rpc: &Rpc,
+    start_height: u64,
+    count: u64,
+) -> Result<FilterHeaders, (StatusCode, String)> {
+    let tip = rpc
+        .get_block_count()
+        .await
+        .map_err(|e| rpc_failure("get chain tip", e))?;
+    if start_height > tip {
+        return Err((StatusCode::NOT_FOUND, "Block not found".to_string()));
+    }
+    let previous_header = match start_height.checked_sub(1) {
+        Some(previous) => filter_header(rpc, previous).await?,
+        None => FilterHeader::all_zeros(),
+    };
+    let end = tip.min(start_height + count - 1);
+    let mut headers = Vec::with_capacity((end + 1).saturating_sub(start_height) as usize);
+    for height in start_height..=end {
+        headers.push(filter_header(rpc, height).await?);
+    }
+    Ok(FilterHeaders {
+        start_height,
+        previous_header,
+        headers,
+    })
+}
+
+/// Filter header chain from a height, up to `?count=` headers (default and at
+/// most 2000)
+pub async fn get_filter_headers(
+    State(state): State<AppState>,
+    Path(start_height): Path<u64>,
+    Query(query): Query<FilterHeadersQuery>,
+) -> impl IntoResponse {
+    let count = query.count.unwrap_or(MAX_FILTER_HEADERS);
+    if count == 0 || count > MAX_FILTER_HEADERS {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("count must be between 1 and {}", MAX_FILTER_HEADERS),
+        )
+            .into_response();
+    }
+    match filter_headers(&state.rpc, start_height, count).await {
+        Ok(headers) => Json(headers).into_response(),
+        Err(response) => response.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden;
+
+    #[test]
+    fn golden_block_filter() {
+        golden::check(
+            "block_filter",
+            &BlockFilter {
+                filter: "fd6f0b3d1cbd0ec1e1c6b2c1e7a80be0".to_owned(),
+                header: "0000000000000000000320283a032748cef8227873ff4872689bf23f1cda83a5"
+                    .parse()
+                    .unwrap(),
+            },
+        );
+    }
+
+    #[test]
+    fn golden_filter_headers() {
+        golden::check(
+            "filter_headers",
+            &FilterHeaders {
+                start_height: 840000,
+                previous_header: "0000000000000000000320283a032748cef8227873ff4872689bf23f1cda83a5"
+                    .parse()
+                    .unwrap(),
+                headers: vec![
+                    "000000000000000000024bead8df69990852c202db0e0097c1a12ea637d7e96d"
+                        .parse()
+                        .unwrap(),
+                ],
+            },
+        );
+    }
+}
@@ -0,0 +1,102 @@
+//! Server side of the golden files in `minipool-client/tests/golden`.
+//!
+//! Each endpoint's module builds a response with the same values as its golden
+//! file, using the server's own types, and [`check`]s that it serializes to the
+//! file's exact bytes, so a reordered field or a float that stops going through
+//! [`json`](crate::json) fails here while the client's tests parse the same
+//! files. Files are pretty-printed for readable diffs; the server writes the
+//! same fields and numbers without the whitespace.
+//!
+//! After an intended change of a response, `UPDATE_GOLDEN=1 cargo test`
+//! rewrites the files it touches.
+
+use std::fs;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+/// Every golden file, each checked by a test of its endpoint's module. CTV
+/// endpoints are only checked with the `ctv` feature enabled.
+const FILES: &[&str] = &[
+    "address",
+    "address_utxos",
+    "addresses_activity",
+    "backend_consistency",
+    "block",
+    "block_fee_histogram",
+    "block_filter",
+    "block_status",
+    "block_txids",
+    "block_utilization",
+    "broadcasts",
+    "coin_select",
+    "consensus_check",
+    "ctv_spends",
+    "ctv_template_hash",
+    "difficulty_adjustment",
+    "estimate_size",
+    "event_journal",
+    "features",
+    "fee_accuracy",
+    "fee_estimates",
+    "filter_headers",
+    "hashrate",
+    "health_details",
+    "mempool",
+    "mempool_blocks",
+    "mempool_diff",
+    "mempool_min_fee",
+    "mempool_recent",
+    "mempool_summary",
+    "mempool_txids",
+    "node_info",
+    "outpoint_watches",
+    "propagation",
+    "recommended_fees",
+    "script_types",
+    "tx",
+    "tx_conflicts",
+    "tx_merkle_proof",
+    "tx_outspends",
+    "tx_status",
+    "v1_blocks",
+    "validate_address",
+    "verify_message",
+    "webhooks",
+];
+
+fn golden_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("minipool-client/tests/golden")
+}
+
+/// Asserts that `body` serializes to golden file `name`
+pub fn check<T: Serialize>(name: &str, body: &T) {
+    assert!(FILES.contains(&name), "{} isn't a listed golden file", name);
+    let path = golden_dir().join(format!("{}.json", name));
+    let mut serialized = serde_json::to_string_pretty(body).expect("responses serialize");
+    serialized.push('\n');
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, &serialized)
+            .unwrap_or_else(|e| panic!("failed to write {}: {}", path.display(), e));
+        return;
+    }
+    let golden = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("failed to read {}: {}", path.display(), e));
+    assert!(
+        golden == serialized,
+        "{} differs from the server's response, which serializes to:\n{}",
+        path.display(),
+        serialized
+    );
+}
+
+#[test]
+fn every_golden_file_is_listed() {
+    let mut files: Vec<String> = fs::read_dir(golden_dir())
+        .expect("golden directory is readable")
+        .map(|entry| entry.expect("golden entry is readable").file_name())
+        .map(|name| name.to_string_lossy().trim_end_matches(".json").to_owned())
+        .collect();
+    files.sort();
+    assert_eq!(files, FILES);
+}
@@ -0,0 +1,394 @@
+//! GraphQL endpoint for composite queries.
+//!
+//! A block with its transactions and their statuses, or a batch of
+//! transactions, comes back from one request instead of one REST call per
+//! object. Resolvers build the same esplora objects as the REST handlers, from
+//! the same caches, and expose them field by field.
+
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::LazyLock;
+
+use async_graphql::http::GraphiQLSource;
+use async_graphql::{Context, EmptyMutation, EmptySubscription, Object, Schema};
+use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use axum::extract::State;
+use axum::response::{Html, IntoResponse};
+use bitcoincore_rpc::bitcoin::{BlockHash, Txid};
+use bitcoincore_rpc::jsonrpc::error::{Error as JsonRpcError, RpcError};
+use minipool_client::paths;
+use tracing::warn;
+
+use crate::blocks::{self, EsploraBlock};
+use crate::chain;
+use crate::tx::{self, EsploraStatus, EsploraTx, EsploraVin, EsploraVout};
+use crate::AppState;
+
+/// Most transactions a single `transactions` field resolves
+const MAX_TXS: usize = 100;
+
+/// Default page size of a block's transactions, as in the REST API
+const DEFAULT_BLOCK_TXS: usize = 25;
+
+type MinipoolSchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+// Every resolver reads the state from the request data, so one schema serves all requests
+static SCHEMA: LazyLock<MinipoolSchema> = LazyLock::new(|| {
+    Schema::build(Query, EmptyMutation, EmptySubscription)
+        .limit_depth(12)
+        .limit_complexity(5_000)
+        .finish()
+});
+
+/// Resolves an RPC query, to null when the node doesn't know the block or
+/// transaction
+async fn resolve<T>(
+    action: &str,
+    query: impl Future<Output = Result<T, bitcoincore_rpc::Error>>,
+) -> async_graphql::Result<Option<T>> {
+    // Error codes from Bitcoin Core's rpc/protocol.h
+    const RPC_INVALID_ADDRESS_OR_KEY: i32 = -5;
+    const RPC_INVALID_PARAMETER: i32 = -8;
+
+    match query.await {
+        Ok(value) => Ok(Some(value)),
+        Err(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(RpcError {
+            code: RPC_INVALID_ADDRESS_OR_KEY | RPC_INVALID_PARAMETER,
+            ..
+        }))) => Ok(None),
+        Err(e) => {
+            warn!("Failed to {}: {}", action, e);
+            Err("RPC error".into())
+        }
+    }
+}
+
+fn parse<T: FromStr>(value: &str, what: &str) -> async_graphql::Result<T> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid {}", what).into())
+}
+
+async fn lookup_tx(state: &AppState, txid: &Txid) -> Result<EsploraTx, bitcoincore_rpc::Error> {
+    tx::esplora_tx(&state.rpc, state.network, &state.mempool, txid).await
+}
+
+async fn lookup_block(state: &AppState, hash: &BlockHash) -> Result<Block, bitcoincore_rpc::Error> {
+    blocks::esplora_block(&state.rpc, &state.block_summaries, hash)
+        .await
+        .map(Block)
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    /// Best block of the node
+    async fn tip(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Block>> {
+        let state = ctx.data::<AppState>()?;
+        resolve("get the tip", async {
+            let (_, hash) = chain::tip(&state.rpc).await?;
+            lookup_block(state, &hash).await
+        })
+        .await
+    }
+
+    /// A block by hash, or the best chain's block at `height`
+    async fn block(
+        &self,
+        ctx: &Context<'_>,
+        hash: Option<String>,
+        height: Option<u64>,
+    ) -> async_graphql::Result<Option<Block>> {
+        let state = ctx.data::<AppState>()?;
+        match (hash, height) {
+            (Some(hash), None) => {
+                let hash: BlockHash = parse(&hash, "block hash")?;
+                resolve("get block", lookup_block(state, &hash)).await
+            }
+            (None, Some(height)) => {
+                resolve("get block", async {
+                    let hash = state.rpc.get_block_hash(height).await?;
+                    lookup_block(state, &hash).await
+                })
+                .await
+            }
+            _ => Err("Exactly one of hash and height is needed".into()),
+        }
+    }
+
+    /// A transaction by txid; confirmed transactions need txindex on the node
+    async fn transaction(
+        &self,
+        ctx: &Context<'_>,
+        txid: String,
+    ) -> async_graphql::Result<Option<Transaction>> {
+        let state = ctx.data::<AppState>()?;
+        let txid: Txid = parse(&txid, "txid")?;
+        Ok(resolve("get transaction", lookup_tx(state, &txid))
+            .await?
+            .map(Transaction))
+    }
+
+    /// Several transactions by txid, null for unknown ones
+    async fn transactions(
+        &self,
+        ctx: &Context<'_>,
+        txids: Vec<String>,
+    ) -> async_graphql::Result<Vec<Option<Transaction>>> {
+        if txids.len() > MAX_TXS {
+            return Err(format!("At most {} txids per query", MAX_TXS).into());
+        }
+        let txids = txids
+            .iter()
+            .map(|txid| parse(txid, "txid"))
+            .collect::<async_graphql::Result<Vec<Txid>>>()?;
+        let state = ctx.data::<AppState>()?;
+        let mut txs = Vec::with_capacity(txids.len());
+        for txid in txids {
+            txs.push(
+                resolve("get transaction", lookup_tx(state, &txid))
+                    .await?
+                    .map(Transaction),
+            );
+        }
+        Ok(txs)
+    }
+}
+
+pub struct Block(EsploraBlock);
+
+#[Object]
+impl Block {
+    async fn hash(&self) -> String {
+        self.0.id.to_string()
+    }
+
+    async fn height(&self) -> u64 {
+        self.0.height
+    }
+
+    async fn version(&self) -> i32 {
+        self.0.version
+    }
+
+    /// Header timestamp in seconds since epoch
+    async fn timestamp(&self) -> u64 {
+        self.0.timestamp
+    }
+
+    async fn mediantime(&self) -> Option<u64> {
+        self.0.mediantime
+    }
+
+    async fn tx_count(&self) -> usize {
+        self.0.tx_count
+    }
+
+    async fn size(&self) -> usize {
+        self.0.size
+    }
+
+    async fn weight(&self) -> usize {
+        self.0.weight
+    }
+
+    async fn merkle_root(&self) -> &str {
+        &self.0.merkle_root
+    }
+
+    async fn previous_block_hash(&self) -> Option<String> {
+        self.0.previousblockhash.map(|hash| hash.to_string())
+    }
+
+    async fn nonce(&self) -> u32 {
+        self.0.nonce
+    }
+
+    async fn bits(&self) -> u32 {
+        self.0.bits
+    }
+
+    async fn difficulty(&self) -> f64 {
+        self.0.difficulty
+    }
+
+    /// The block before this one
+    async fn previous(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Block>> {
+        let Some(hash) = self.0.previousblockhash else {
+            return Ok(None);
+        };
+        let state = ctx.data::<AppState>()?;
+        resolve("get block", lookup_block(state, &hash)).await
+    }
+
+    /// Transactions of the block from `start`, 25 unless `limit` asks for more
+    /// (up to 100)
+    async fn transactions(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 0)] start: usize,
+        #[graphql(default_with = "DEFAULT_BLOCK_TXS")] limit: usize,
+    ) -> async_graphql::Result<Vec<Transaction>> {
+        if limit > MAX_TXS {
+            return Err(format!("At most {} transactions per block", MAX_TXS).into());
+        }
+        let state = ctx.data::<AppState>()?;
+        let txs = resolve(
+            "get block transactions",
+            blocks::block_txs(&state.rpc, state.network, &self.0.id, start, limit),
+        )
+        .await?;
+        Ok(txs
+            .flatten()
+            .unwrap_or_default()
+            .into_iter()
+            .map(Transaction)
+            .collect())
+    }
+}
+
+pub struct Transaction(EsploraTx);
+
+#[Object]
+impl Transaction {
+    async fn txid(&self) -> String {
+        self.0.txid.to_string()
+    }
+
+    async fn version(&self) -> i32 {
+        self.0.version
+    }
+
+    async fn locktime(&self) -> u32 {
+        self.0.locktime
+    }
+
+    async fn size(&self) -> usize {
+        self.0.size
+    }
+
+    async fn weight(&self) -> u64 {
+        self.0.weight
+    }
+
+    async fn vsize(&self) -> u64 {
+        self.0.weight.div_ceil(4)
+    }
+
+    /// Fee in sats, 0 for coinbase transactions
+    async fn fee(&self) -> u64 {
+        self.0.fee
+    }
+
+    async fn status(&self) -> TxStatus {
+        TxStatus(self.0.status.clone())
+    }
+
+    async fn inputs(&self) -> Vec<Input<'_>> {
+        self.0.vin.iter().map(Input).collect()
+    }
+
+    async fn outputs(&self) -> Vec<Output<'_>> {
+        self.0.vout.iter().map(Output).collect()
+    }
+}
+
+pub struct TxStatus(EsploraStatus);
+
+#[Object]
+impl TxStatus {
+    async fn confirmed(&self) -> bool {
+        self.0.confirmed
+    }
+
+    async fn block_height(&self) -> Option<u64> {
+        self.0.block_height
+    }
+
+    async fn block_hash(&self) -> Option<String> {
+        self.0.block_hash.map(|hash| hash.to_string())
+    }
+
+    /// Header timestamp of the confirming block in seconds since epoch
+    async fn block_time(&self) -> Option<u64> {
+        self.0.block_time
+    }
+
+    /// When an unconfirmed transaction was first seen, in seconds since epoch
+    async fn first_seen(&self) -> Option<u64> {
+        self.0.first_seen
+    }
+}
+
+pub struct Input<'a>(&'a EsploraVin);
+
+#[Object]
+impl<'a> Input<'a> {
+    async fn txid(&self) -> String {
+        self.0.txid.to_string()
+    }
+
+    async fn vout(&self) -> u32 {
+        self.0.vout
+    }
+
+    /// Spent output, null for coinbase inputs
+    async fn prevout(&self) -> Option<Output<'_>> {
+        self.0.prevout.as_ref().map(Output)
+    }
+
+    async fn scriptsig(&self) -> &str {
+        &self.0.scriptsig
+    }
+
+    async fn witness(&self) -> &[String] {
+        &self.0.witness
+    }
+
+    async fn is_coinbase(&self) -> bool {
+        self.0.is_coinbase
+    }
+
+    async fn sequence(&self) -> u32 {
+        self.0.sequence
+    }
+}
+
+pub struct Output<'a>(&'a EsploraVout);
+
+#[Object]
+impl<'a> Output<'a> {
+    async fn scriptpubkey(&self) -> &str {
+        &self.0.scriptpubkey
+    }
+
+    /// Script type as named by esplora, e.g. `v0_p2wpkh`
+    async fn scriptpubkey_type(&self) -> &str {
+        self.0.scriptpubkey_type
+    }
+
+    async fn address(&self) -> Option<&str> {
+        self.0.scriptpubkey_address.as_deref()
+    }
+
+    /// Value in sats
+    async fn value(&self) -> u64 {
+        self.0.value
+    }
+}
+
+pub async fn post_graphql(
+    State(state): State<AppState>,
+    request: GraphQLRequest,
+) -> GraphQLResponse {
+    SCHEMA
+        .execute(request.into_inner().data(state))
+        .await
+        .into()
+}
+
+/// GraphiQL explorer for the schema
+pub async fn get_graphiql() -> impl IntoResponse {
+    Html(GraphiQLSource::build().endpoint(paths::GRAPHQL).finish())
+}
@@ -0,0 +1,293 @@
+//! gRPC API for the core queries, described by `proto/minipool.proto`.
+//!
+//! Answers come from the same RPC calls as the HTTP endpoints, so both APIs
+//! agree; only the encoding differs, with raw blocks and transactions sent as
+//! bytes instead of hex.
+
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+use anyhow::{Context, Result};
+use bitcoincore_rpc::bitcoin::consensus::encode::serialize;
+use bitcoincore_rpc::bitcoin::hex::DisplayHex;
+use bitcoincore_rpc::bitcoin::{BlockHash, Txid};
+use bitcoincore_rpc::jsonrpc::error::{Error as JsonRpcError, RpcError};
+use futures_util::Stream;
+use tokio::sync::broadcast::error::RecvError;
+use tonic::transport::server::TcpIncoming;
+use tonic::{Request, Response, Status};
+use tracing::{info, warn};
+
+use crate::audit::Interface;
+use crate::chain::{self, ChainWatcher};
+use crate::fees::CONFIRMATION_TARGETS;
+use crate::rpc::Rpc;
+use crate::tx::broadcast_rejection;
+use crate::AppState;
+
+pub mod pb {
+    tonic::include_proto!("minipool.v1");
+}
+
+use pb::minipool_server::{Minipool, MinipoolServer};
+
+/// Maps RPC errors to gRPC statuses, passing the node's message on for lookups
+/// of unknown blocks and transactions
+fn rpc_status(action: &str, error: bitcoincore_rpc::Error) -> Status {
+    // Error codes from Bitcoin Core's rpc/protocol.h
+    const RPC_INVALID_ADDRESS_OR_KEY: i32 = -5;
+    const RPC_INVALID_PARAMETER: i32 = -8;
+
+    if let bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(RpcError { code, message, .. })) =
+        &error
+    {
+        if matches!(*code, RPC_INVALID_ADDRESS_OR_KEY | RPC_INVALID_PARAMETER) {
+            return Status::not_found(message.clone());
+        }
+    }
+    warn!("Failed to {}: {}", action, error);
+    Status::internal("RPC error")
+}
+
+/// The block a request refers to, or why it doesn't refer to any
+fn parse_block_id(id: pb::BlockId) -> Result<BlockRef, &'static str> {
+    match id.id {
+        Some(pb::block_id::Id::Height(height)) => Ok(BlockRef::Height(height)),
+        Some(pb::block_id::Id::Hash(hash)) => hash
+            .parse()
+            .map(BlockRef::Hash)
+            .map_err(|_| "Invalid block hash"),
+        None => Err("Missing block height or hash"),
+    }
+}
+
+enum BlockRef {
+    Height(u64),
+    Hash(BlockHash),
+}
+
+impl BlockRef {
+    async fn resolve(self, rpc: &Rpc) -> Result<BlockHash, bitcoincore_rpc::Error> {
+        match self {
+            Self::Height(height) => rpc.get_block_hash(height).await,
+            Self::Hash(hash) => Ok(hash),
+        }
+    }
+}
+
+async fn lookup_block(rpc: &Rpc, block: BlockRef) -> Result<pb::Block, bitcoincore_rpc::Error> {
+    let hash = block.resolve(rpc).await?;
+    let info = rpc.get_block_info(&hash).await?;
+    Ok(pb::Block {
+        hash: info.hash.to_string(),
+        height: info.height as u64,
+        version: info.version,
+        previous_block_hash: info
+            .previousblockhash
+            .map(|hash| hash.to_string())
+            .unwrap_or_default(),
+        merkle_root: info.merkleroot.to_string(),
+        timestamp: info.time as u32,
+        bits: u32::from_str_radix(&info.bits, 16).unwrap_or_default(),
+        nonce: info.nonce,
+        difficulty: info.difficulty,
+        tx_count: info.n_tx as u32,
+        size: info.size as u64,
+        weight: info.weight as u64,
+        // Blocks off the best chain have -1 confirmations
+        in_best_chain: info.confirmations >= 0,
+    })
+}
+
+async fn lookup_transaction(
+    rpc: &Rpc,
+    txid: &Txid,
+) -> Result<pb::Transaction, bitcoincore_rpc::Error> {
+    let info = rpc.get_raw_transaction_info(txid, None).await?;
+    let confirmation = match (info.blockhash, info.blocktime) {
+        (Some(hash), Some(time)) => Some(pb::Confirmation {
+            block_height: rpc.get_block_header_info(&hash).await?.height as u64,
+            block_hash: hash.to_string(),
+            block_time: time as u64,
+        }),
+        _ => None,
+    };
+    Ok(pb::Transaction {
+        txid: txid.to_string(),
+        raw: info.hex,
+        confirmation,
+    })
+}
+
+pub struct GrpcApi {
+    state: AppState,
+    watcher: Arc<ChainWatcher>,
+}
+
+type BlockEvents = Pin<Box<dyn Stream<Item = Result<pb::BlockEvent, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl Minipool for GrpcApi {
+    async fn get_tip(&self, _: Request<pb::GetTipRequest>) -> Result<Response<pb::Tip>, Status> {
+        let (height, hash) = chain::tip(&self.state.rpc)
+            .await
+            .map_err(|e| rpc_status("get the tip", e))?;
+        Ok(Response::new(pb::Tip {
+            height,
+            hash: hash.to_string(),
+        }))
+    }
+
+    async fn get_block(
+        &self,
+        request: Request<pb::BlockId>,
+    ) -> Result<Response<pb::Block>, Status> {
+        let block = parse_block_id(request.into_inner()).map_err(Status::invalid_argument)?;
+        let block = lookup_block(&self.state.rpc, block)
+            .await
+            .map_err(|e| rpc_status("get block", e))?;
+        Ok(Response::new(block))
+    }
+
+    async fn get_raw_block(
+        &self,
+        request: Request<pb::BlockId>,
+    ) -> Result<Response<pb::RawBlock>, Status> {
+        let block = parse_block_id(request.into_inner()).map_err(Status::invalid_argument)?;
+        let rpc = &self.state.rpc;
+        let lookup = async {
+            let hash = block.resolve(rpc).await?;
+            rpc.get_block(&hash).await
+        };
+        let block = lookup.await.map_err(|e| rpc_status("get raw block", e))?;
+        Ok(Response::new(pb::RawBlock {
+            block: serialize(&block),
+        }))
+    }
+
+    async fn get_transaction(
+        &self,
+        request: Request<pb::GetTransactionRequest>,
+    ) -> Result<Response<pb::Transaction>, Status> {
+        let txid: Txid = request
+            .into_inner()
+            .txid
+            .parse()
+            .map_err(|_| Status::invalid_argument("Invalid txid"))?;
+        let tx = lookup_transaction(&self.state.rpc, &txid)
+            .await
+            .map_err(|e| rpc_status("get transaction", e))?;
+        Ok(Response::new(tx))
+    }
+
+    async fn get_fee_estimates(
+        &self,
+        _: Request<pb::GetFeeEstimatesRequest>,
+    ) -> Result<Response<pb::FeeEstimates>, Status> {
+        let rates = self
+            .state
+            .fee_cache
+            .fee_rates(&self.state.rpc, &self.state.fee_limits)
+            .await
+            .map_err(|e| rpc_status("get fee estimates", e))?;
+        let sat_per_vbyte = CONFIRMATION_TARGETS
+            .iter()
+            .map(|&blocks| blocks as u32)
+            .zip(rates)
+            .collect();
+        Ok(Response::new(pb::FeeEstimates { sat_per_vbyte }))
+    }
+
+    async fn broadcast(
+        &self,
+        request: Request<pb::BroadcastRequest>,
+    ) -> Result<Response<pb::BroadcastResponse>, Status> {
+        let client = request.remote_addr();
+        let hex = request.into_inner().raw.to_lower_hex_string();
+        let result = self.state.rpc.send_raw_transaction(&hex).await;
+        self.state
+            .audit
+            .record(&self.state.rpc, Interface::Grpc, client, &hex, &result)
+            .await;
+        match result {
+            Ok(txid) => {
+                info!("Broadcast transaction {} from gRPC client", txid);
+                self.state.hooks.broadcast(txid, hex);
+                Ok(Response::new(pb::BroadcastResponse {
+                    txid: txid.to_string(),
+                }))
+            }
+            Err(e) => match broadcast_rejection(&e) {
+                Some((status, rejection)) => {
+                    metrics::counter!("tx_broadcast_rejected_total", "reason" => rejection.error)
+                        .increment(1);
+                    let message = format!("{}: {}", rejection.error, rejection.message);
+                    Err(if status == axum::http::StatusCode::CONFLICT {
+                        Status::already_exists(message)
+                    } else {
+                        Status::invalid_argument(message)
+                    })
+                }
+                None => {
+                    warn!("Failed to broadcast transaction: {}", e);
+                    Err(Status::internal("RPC error"))
+                }
+            },
+        }
+    }
+
+    type SubscribeBlocksStream = BlockEvents;
+
+    async fn subscribe_blocks(
+        &self,
+        _: Request<pb::SubscribeBlocksRequest>,
+    ) -> Result<Response<Self::SubscribeBlocksStream>, Status> {
+        metrics::counter!("grpc_block_subscriptions_total").increment(1);
+        let receiver = self.watcher.subscribe();
+        let stream = futures_util::stream::unfold(receiver, |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(block) => {
+                        let event = pb::BlockEvent {
+                            height: block.height,
+                            hash: block.hash.to_string(),
+                            seen_at: block
+                                .seen_at
+                                .duration_since(UNIX_EPOCH)
+                                .map(|elapsed| elapsed.as_secs())
+                                .unwrap_or_default(),
+                            disconnected: block.disconnected.len() as u64,
+                        };
+                        return Some((Ok(event), receiver));
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        metrics::counter!("grpc_block_events_skipped_total").increment(skipped);
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+/// Serves the gRPC API on `addr` in the background
+pub async fn serve(addr: SocketAddr, state: AppState, watcher: Arc<ChainWatcher>) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to bind gRPC listener on {}", addr))?;
+    info!("Serving gRPC on {}", addr);
+    let api = GrpcApi { state, watcher };
+    tokio::spawn(async move {
+        if let Err(e) = tonic::transport::Server::builder()
+            .add_service(MinipoolServer::new(api))
+            .serve_with_incoming(TcpIncoming::from(listener))
+            .await
+        {
+            warn!("gRPC server failed: {}", e);
+        }
+    });
+    Ok(())
+}
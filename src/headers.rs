@@ -0,0 +1,361 @@
+//! Locally verified header chain.
+//!
+//! When enabled, every header from genesis to the tip is fetched at startup and
+//! checked for proof of work, linkage to its parent and the difficulty
+//! adjustment rules, so headers handed to SPV clients don't rely on the node
+//! alone. The chain is kept in memory (about 80 bytes per block) and extended as
+//! the chain watcher announces new blocks.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+
+use anyhow::{bail, ensure, Context};
+use bitcoincore_rpc::bitcoin::block::Header;
+use bitcoincore_rpc::bitcoin::consensus::encode::{deserialize_hex, serialize_hex};
+use bitcoincore_rpc::bitcoin::consensus::Params;
+use bitcoincore_rpc::bitcoin::constants::genesis_block;
+use bitcoincore_rpc::bitcoin::{BlockHash, CompactTarget, Network};
+use serde_json::json;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, warn};
+
+use crate::chain::ChainWatcher;
+use crate::health::{Health, Severity};
+use crate::rpc::Rpc;
+
+const HEALTH_COMPONENT: &str = "header_chain";
+
+/// Verified headers between progress logs during catch-up
+const PROGRESS_INTERVAL: usize = 10_000;
+
+#[derive(Default)]
+struct Chain {
+    /// Verified headers by height
+    headers: Vec<Header>,
+    heights: HashMap<BlockHash, usize>,
+}
+
+pub struct HeaderChain {
+    params: Params,
+    chain: RwLock<Chain>,
+    /// Set once the startup verification pass reached the tip
+    synced: AtomicBool,
+}
+
+fn record_failure(stage: &'static str) {
+    metrics::counter!("header_verification_failures_total", "stage" => stage).increment(1);
+}
+
+impl HeaderChain {
+    pub fn new(network: Network) -> Self {
+        Self {
+            params: Params::new(network),
+            chain: RwLock::new(Chain::default()),
+            synced: AtomicBool::new(false),
+        }
+    }
+
+    pub async fn run(
+        self: Arc<Self>,
+        rpc: Arc<Rpc>,
+        watcher: Arc<ChainWatcher>,
+        health: Arc<Health>,
+    ) {
+        health.register(HEALTH_COMPONENT, Severity::Soft, None);
+        let mut blocks = watcher.subscribe();
+        loop {
+            match self.sync(&rpc).await {
+                Ok(()) => {
+                    if !self.synced.swap(true, Ordering::Relaxed) {
+                        info!("Header chain verified up to the tip");
+                    }
+                    health.success(HEALTH_COMPONENT);
+                }
+                Err(e) => {
+                    warn!("Failed to extend verified header chain: {:#}", e);
+                    health.failure(HEALTH_COMPONENT, &e);
+                }
+            }
+            // Every sync catches up to the tip, so lagging behind the watcher is harmless
+            match blocks.recv().await {
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => return,
+            }
+        }
+    }
+
+    async fn sync(&self, rpc: &Rpc) -> anyhow::Result<()> {
+        // Drop verified headers that are no longer part of the best chain
+        loop {
+            let tip = {
+                let chain = self.chain.read().expect("header chain lock poisoned");
+                chain
+                    .headers
+                    .last()
+                    .map(|header| (chain.headers.len() - 1, header.block_hash()))
+            };
+            let Some((height, hash)) = tip else {
+                break;
+            };
+            if rpc.get_block_hash(height as u64).await.ok() == Some(hash) {
+                break;
+            }
+            let mut chain = self.chain.write().expect("header chain lock poisoned");
+            chain.headers.pop();
+            chain.heights.remove(&hash);
+            info!(
+                "Header chain disconnected block {} at height {}",
+                hash, height
+            );
+        }
+
+        let from = self
+            .chain
+            .read()
+            .expect("header chain lock poisoned")
+            .headers
+            .len();
+        let tip = rpc.get_block_count().await? as usize;
+        for height in from..=tip {
+            let hash = rpc.get_block_hash(height as u64).await?;
+            let hex: String = rpc
+                .call("getblockheader", &[json!(hash), json!(false)])
+                .await?;
+            let header: Header = deserialize_hex(&hex)
+                .with_context(|| format!("Node returned an invalid header at height {}", height))?;
+            let mut chain = self.chain.write().expect("header chain lock poisoned");
+            if let Err(e) = self.verify(&chain, height, &header) {
+                record_failure("chain");
+                return Err(e.context(format!(
+                    "Header {} at height {} failed verification",
+                    hash, height
+                )));
+            }
+            chain.heights.insert(header.block_hash(), height);
+            chain.headers.push(header);
+            if (height - from) % PROGRESS_INTERVAL == PROGRESS_INTERVAL - 1 {
+                info!("Header chain verified up to height {} of {}", height, tip);
+            }
+        }
+        metrics::gauge!("header_chain_height").set(tip as f64);
+        Ok(())
+    }
+
+    /// Checks `header` as the successor of the verified chain at `height`
+    fn verify(&self, chain: &Chain, height: usize, header: &Header) -> anyhow::Result<()> {
+        let target = header.target();
+        ensure!(
+            target <= self.params.max_attainable_target,
+            "Target above the network's proof of work limit"
+        );
+        header
+            .validate_pow(target)
+            .context("Hash doesn't meet its own target")?;
+
+        let Some(prev) = height.checked_sub(1).and_then(|h| chain.headers.get(h)) else {
+            ensure!(
+                header.block_hash() == genesis_block(&self.params).block_hash(),
+                "Not the network's genesis block"
+            );
+            return Ok(());
+        };
+        ensure!(
+            header.prev_blockhash == prev.block_hash(),
+            "Doesn't link to the previous header"
+        );
+
+        let interval = self.params.difficulty_adjustment_interval() as usize;
+        if height.is_multiple_of(interval) {
+            let first = &chain.headers[height - interval];
+            let timespan = u64::from(prev.time.saturating_sub(first.time));
+            let expected =
+                CompactTarget::from_next_work_required(prev.bits, timespan, &self.params);
+            if header.bits != expected {
+                bail!("Unexpected difficulty adjustment");
+            }
+        } else if !self.params.allow_min_difficulty_blocks && header.bits != prev.bits {
+            // Test networks may drop to minimum difficulty, they only get the other checks
+            bail!("Difficulty changed outside of an adjustment");
+        }
+        Ok(())
+    }
+
+    /// Serialized header of a block in the verified chain
+    pub fn header_hex(&self, hash: &BlockHash) -> Option<String> {
+        let chain = self.chain.read().expect("header chain lock poisoned");
+        let height = *chain.heights.get(hash)?;
+        Some(serialize_hex(&chain.headers[height]))
+    }
+
+    /// Verified headers of up to `count` blocks from `start`, fewer when the
+    /// chain doesn't reach that far yet
+    pub fn headers_from(&self, start: usize, count: usize) -> Vec<Header> {
+        let chain = self.chain.read().expect("header chain lock poisoned");
+        let end = chain.headers.len().min(start.saturating_add(count));
+        chain.headers.get(start..end).unwrap_or_default().to_vec()
+    }
+
+    /// Checks a header from the node that isn't in the verified chain (stale or
+    /// just found) on its own: it must hash to `hash` and carry valid proof of work
+    pub fn verify_unknown(&self, hash: &BlockHash, hex: &str) -> anyhow::Result<()> {
+        let check = || -> anyhow::Result<()> {
+            let header: Header = deserialize_hex(hex).context("Invalid header")?;
+            ensure!(
+                header.block_hash() == *hash,
+                "Header doesn't hash to {}",
+                hash
+            );
+            ensure!(
+                header.target() <= self.params.max_attainable_target,
+                "Target above the network's proof of work limit"
+            );
+            header
+                .validate_pow(header.target())
+                .context("Hash doesn't meet its own target")?;
+            Ok(())
+        };
+        check().inspect_err(|_| record_failure("serve"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoincore_rpc::bitcoin::block::Version;
+    use bitcoincore_rpc::bitcoin::hashes::Hash;
+    use bitcoincore_rpc::bitcoin::TxMerkleNode;
+
+    /// Lowest regtest difficulty
+    const REGTEST_BITS: u32 = 0x207f_ffff;
+
+    /// A target of `0x0fffff << 224`, low enough to be scaled without overflow
+    const PERIOD_BITS: u32 = 0x1f0f_ffff;
+
+    fn header_chain(params: Params) -> HeaderChain {
+        HeaderChain {
+            params,
+            chain: RwLock::new(Chain::default()),
+            synced: AtomicBool::new(false),
+        }
+    }
+
+    /// Regtest with retargets every 10 blocks and no minimum difficulty blocks
+    fn retargeting_regtest() -> Params {
+        let mut params = Params::new(Network::Regtest);
+        params.pow_target_timespan = 10 * params.pow_target_spacing;
+        params.no_pow_retargeting = false;
+        params.allow_min_difficulty_blocks = false;
+        params
+    }
+
+    /// Verifies `header` as the next one of `chain`, appending it when valid
+    fn extend(chain: &HeaderChain, header: Header) -> anyhow::Result<()> {
+        let mut headers = chain.chain.write().unwrap();
+        let height = headers.headers.len();
+        chain.verify(&headers, height, &header)?;
+        headers.heights.insert(header.block_hash(), height);
+        headers.headers.push(header);
+        Ok(())
+    }
+
+    /// A header on top of `prev` meeting the target of `bits`
+    fn mine(prev: &Header, bits: u32, time: u32) -> Header {
+        let mut header = Header {
+            version: Version::TWO,
+            prev_blockhash: prev.block_hash(),
+            merkle_root: TxMerkleNode::all_zeros(),
+            time,
+            bits: CompactTarget::from_consensus(bits),
+            nonce: 0,
+        };
+        while header.validate_pow(header.target()).is_err() {
+            header.nonce += 1;
+        }
+        header
+    }
+
+    fn tip(chain: &HeaderChain) -> Header {
+        *chain.chain.read().unwrap().headers.last().unwrap()
+    }
+
+    #[test]
+    fn verifies_the_first_mainnet_blocks() {
+        let chain = header_chain(Params::new(Network::Bitcoin));
+        let genesis = genesis_block(&chain.params).header;
+        let block_1: Header = deserialize_hex("010000006fe28c0ab6f1b372c1a6a246ae63f74f931e8365e15a089c68d6190000000000982051fd1e4ba744bbbe680e1fee14677ba1a3c3540bf7b1cdb606e857233e0e61bc6649ffff001d01e36299").unwrap();
+        assert_eq!(
+            block_1.block_hash().to_string(),
+            "00000000839a8e6886ab5951d76f411475428afc90947ee320161bbf18eb6048"
+        );
+        let mut forged = block_1;
+        forged.nonce += 1;
+
+        assert!(
+            extend(&chain, block_1).is_err(),
+            "block 1 isn't a genesis block"
+        );
+        extend(&chain, genesis).unwrap();
+        assert!(extend(&chain, forged).is_err());
+        extend(&chain, block_1).unwrap();
+        assert_eq!(chain.headers_from(0, 10), [genesis, block_1]);
+    }
+
+    #[test]
+    fn rejects_headers_breaking_the_chain() {
+        let chain = header_chain(retargeting_regtest());
+        let genesis = genesis_block(&chain.params).header;
+        extend(&chain, genesis).unwrap();
+        let time = genesis.time + 600;
+
+        let mut unlinked = mine(&genesis, REGTEST_BITS, time);
+        unlinked.prev_blockhash = BlockHash::all_zeros();
+        while unlinked.validate_pow(unlinked.target()).is_err() {
+            unlinked.nonce += 1;
+        }
+        let error = extend(&chain, unlinked).unwrap_err();
+        assert_eq!(error.to_string(), "Doesn't link to the previous header");
+
+        let easier = mine(&genesis, 0x2100_ffff, time);
+        let error = extend(&chain, easier).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Target above the network's proof of work limit"
+        );
+
+        let harder = mine(&genesis, 0x203f_ffff, time);
+        let error = extend(&chain, harder).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Difficulty changed outside of an adjustment"
+        );
+
+        extend(&chain, mine(&genesis, REGTEST_BITS, time)).unwrap();
+    }
+
+    #[test]
+    fn checks_difficulty_adjustments() {
+        let chain = header_chain(retargeting_regtest());
+        // The regtest limit would overflow 256 bits when scaled by the timespan,
+        // so the period starts from a made-up block at a harder target
+        let first = mine(
+            &genesis_block(&chain.params).header,
+            PERIOD_BITS,
+            1_700_000_000,
+        );
+        chain.chain.write().unwrap().headers.push(first);
+        // Blocks twice as fast as the target spacing
+        for height in 1..10 {
+            let time = first.time + height * 300;
+            extend(&chain, mine(&tip(&chain), PERIOD_BITS, time)).unwrap();
+        }
+
+        // Like Core, the period spans from its first to its last block, 9 * 300
+        // seconds, so the target shrinks to 2700 / 6000 of what it was
+        let time = first.time + 10 * 300;
+        let unchanged = mine(&tip(&chain), PERIOD_BITS, time);
+        let error = extend(&chain, unchanged).unwrap_err();
+        assert_eq!(error.to_string(), "Unexpected difficulty adjustment");
+        extend(&chain, mine(&tip(&chain), 0x1f07_3332, time)).unwrap();
+    }
+}
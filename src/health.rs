@@ -0,0 +1,220 @@
+//! Component-level health.
+//!
+//! Hard components (the node RPC) make the instance unready when they fail.
+//! Soft ones (background pipelines) only degrade it: `/readyz` keeps passing
+//! while `/api/v1/health/details` and the `health_component_*` metrics show
+//! which part is lagging, so alerts can be tiered.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde::Serialize;
+
+use crate::AppState;
+
+/// Name of the component tracking the node RPC itself
+pub const RPC: &str = "rpc";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Severity {
+    Hard,
+    Soft,
+}
+
+struct Component {
+    severity: Severity,
+    /// Considered stale without a success for this long
+    stale_after: Option<Duration>,
+    last_success: Option<SystemTime>,
+    /// Error of the latest attempt, cleared by the next success
+    last_error: Option<String>,
+}
+
+#[derive(Default)]
+pub struct Health {
+    components: RwLock<BTreeMap<&'static str, Component>>,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Ok,
+    /// Hasn't reported yet
+    Starting,
+    Stale,
+    Failing,
+}
+
+#[derive(Serialize)]
+struct ComponentStatus {
+    name: &'static str,
+    hard: bool,
+    status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// Seconds since epoch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_success: Option<u64>,
+}
+
+fn epoch_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+impl Health {
+    pub fn register(&self, name: &'static str, severity: Severity, stale_after: Option<Duration>) {
+        let mut components = self.components.write().expect("health lock poisoned");
+        components.insert(
+            name,
+            Component {
+                severity,
+                stale_after,
+                last_success: None,
+                last_error: None,
+            },
+        );
+    }
+
+    pub fn success(&self, name: &'static str) {
+        let now = SystemTime::now();
+        if let Some(component) = self
+            .components
+            .write()
+            .expect("health lock poisoned")
+            .get_mut(name)
+        {
+            component.last_success = Some(now);
+            component.last_error = None;
+        }
+        metrics::gauge!("health_component_failing", "component" => name).set(0.0);
+        metrics::gauge!("health_component_last_success_seconds", "component" => name)
+            .set(epoch_seconds(now) as f64);
+    }
+
+    pub fn failure(&self, name: &'static str, error: &dyn Display) {
+        if let Some(component) = self
+            .components
+            .write()
+            .expect("health lock poisoned")
+            .get_mut(name)
+        {
+            component.last_error = Some(error.to_string());
+        }
+        metrics::gauge!("health_component_failing", "component" => name).set(1.0);
+    }
+
+    fn snapshot(&self) -> Vec<ComponentStatus> {
+        let now = SystemTime::now();
+        let components = self.components.read().expect("health lock poisoned");
+        components
+            .iter()
+            .map(|(&name, component)| {
+                let stale = match (component.last_success, component.stale_after) {
+                    (Some(last), Some(limit)) => {
+                        now.duration_since(last).unwrap_or_default() > limit
+                    }
+                    _ => false,
+                };
+                let status = if component.last_error.is_some() {
+                    Status::Failing
+                } else if stale {
+                    Status::Stale
+                } else if component.last_success.is_none() {
+                    Status::Starting
+                } else {
+                    Status::Ok
+                };
+                ComponentStatus {
+                    name,
+                    hard: component.severity == Severity::Hard,
+                    status,
+                    error: component.last_error.clone(),
+                    last_success: component.last_success.map(epoch_seconds),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Probes the node so the hard dependency is always judged on fresh data
+async fn probe_rpc(state: &AppState) {
+    match state.rpc.get_block_count().await {
+        Ok(_) => state.health.success(RPC),
+        Err(e) => state.health.failure(RPC, &e),
+    }
+}
+
+pub async fn get_readyz(State(state): State<AppState>) -> impl IntoResponse {
+    probe_rpc(&state).await;
+    let ready = state
+        .health
+        .snapshot()
+        .iter()
+        .all(|component| !component.hard || component.status == Status::Ok);
+    if ready {
+        (StatusCode::OK, "ready")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "not ready")
+    }
+}
+
+#[derive(Serialize)]
+struct HealthDetails {
+    /// `down` when a hard component fails, `degraded` when a soft one does
+    status: &'static str,
+    components: Vec<ComponentStatus>,
+}
+
+pub async fn get_health_details(State(state): State<AppState>) -> impl IntoResponse {
+    probe_rpc(&state).await;
+    let components = state.health.snapshot();
+    let unhealthy =
+        |component: &&ComponentStatus| matches!(component.status, Status::Stale | Status::Failing);
+    let status = if components
+        .iter()
+        .filter(unhealthy)
+        .any(|component| component.hard)
+    {
+        "down"
+    } else if components.iter().any(|component| unhealthy(&component)) {
+        "degraded"
+    } else {
+        "ok"
+    };
+    Json(HealthDetails { status, components })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden;
+
+    #[test]
+    fn golden_health_details() {
+        let health = Health::default();
+        health.register(RPC, Severity::Hard, None);
+        health.register("mempool", Severity::Soft, None);
+        health.failure(
+            "mempool",
+            &"JSON-RPC error: transport error: Couldn't connect to host",
+        );
+        {
+            let mut components = health.components.write().unwrap();
+            let at = |seconds| Some(UNIX_EPOCH + Duration::from_secs(seconds));
+            components.get_mut(RPC).unwrap().last_success = at(1713571780);
+            components.get_mut("mempool").unwrap().last_success = at(1713571700);
+        }
+        golden::check(
+            "health_details",
+            &HealthDetails {
+                status: "degraded",
+                components: health.snapshot(),
+            },
+        );
+    }
+}
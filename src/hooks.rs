@@ -0,0 +1,278 @@
+//! Operator scripts run on chain and API events.
+//!
+//! A hook script is a rhai file defining any of `on_block(event)`,
+//! `on_broadcast(event)` and `on_watch(event)`, each called with the event as
+//! a map. Scripts can `notify(url, payload)` to POST a JSON payload to a
+//! webhook of their choosing, and `print` to the log. `on_watch` also shapes
+//! the outpoint watch notification: returning `false` drops it, returning a map
+//! attaches the map as its `annotations`.
+//!
+//! Scripts run on the blocking pool with an operation budget, and a failing
+//! script is logged without affecting the event or the other scripts.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Result};
+use reqwest::{Method, Url};
+use rhai::{Dynamic, Engine, EvalAltResult, Scope, AST};
+use serde_json::{json, Map, Value};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, info, warn};
+
+use crate::chain::{BlockEvent, ChainWatcher};
+use crate::outbound::OutboundClient;
+use crate::trace_context;
+
+/// Operations a single hook call may run before it is aborted
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// Webhooks a single hook call may notify
+const MAX_NOTIFICATIONS: usize = 16;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Event {
+    Block,
+    Broadcast,
+    Watch,
+}
+
+impl Event {
+    fn function(self) -> &'static str {
+        match self {
+            Event::Block => "on_block",
+            Event::Broadcast => "on_broadcast",
+            Event::Watch => "on_watch",
+        }
+    }
+}
+
+struct Script {
+    path: PathBuf,
+    ast: AST,
+}
+
+impl Script {
+    fn defines(&self, event: Event) -> bool {
+        self.ast
+            .iter_functions()
+            .any(|function| function.name == event.function() && function.params.len() == 1)
+    }
+}
+
+/// A webhook call requested by a script
+struct Notify {
+    url: Url,
+    payload: Value,
+}
+
+pub struct Hooks {
+    scripts: Vec<Script>,
+    http: OutboundClient,
+}
+
+fn engine(path: &Path, notifications: Arc<Mutex<Vec<Notify>>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    let name = path.display().to_string();
+    engine.on_print(move |message| info!(hook = name.as_str(), "{}", message));
+    let name = path.display().to_string();
+    engine.on_debug(move |message, _, position| {
+        debug!(hook = name.as_str(), "{} at {}", message, position)
+    });
+    engine.register_fn(
+        "notify",
+        move |url: &str, payload: Dynamic| -> Result<(), Box<EvalAltResult>> {
+            let url = match Url::parse(url) {
+                Ok(url) if matches!(url.scheme(), "http" | "https") => url,
+                _ => return Err(format!("invalid webhook URL {:?}", url).into()),
+            };
+            let payload: Value = rhai::serde::from_dynamic(&payload)?;
+            let mut notifications = notifications.lock().expect("hook lock poisoned");
+            if notifications.len() >= MAX_NOTIFICATIONS {
+                return Err(format!("more than {} notifications", MAX_NOTIFICATIONS).into());
+            }
+            notifications.push(Notify { url, payload });
+            Ok(())
+        },
+    );
+    engine
+}
+
+impl Hooks {
+    pub fn load(paths: &[PathBuf], http: OutboundClient) -> Result<Self> {
+        let mut scripts = Vec::with_capacity(paths.len());
+        for path in paths {
+            let ast = Engine::new()
+                .compile_file(path.clone())
+                .map_err(|e| anyhow!("Failed to compile hook script {}: {}", path.display(), e))?;
+            let script = Script {
+                path: path.clone(),
+                ast,
+            };
+            let events: Vec<_> = [Event::Block, Event::Broadcast, Event::Watch]
+                .into_iter()
+                .filter(|&event| script.defines(event))
+                .map(Event::function)
+                .collect();
+            if events.is_empty() {
+                warn!("Hook script {} defines no event function", path.display());
+            }
+            info!(
+                "Loaded hook script {} ({})",
+                path.display(),
+                events.join(", ")
+            );
+            scripts.push(script);
+        }
+        Ok(Self { scripts, http })
+    }
+
+    /// Whether any script handles `event`, so callers can skip building its payload
+    pub fn handles(&self, event: Event) -> bool {
+        self.scripts.iter().any(|script| script.defines(event))
+    }
+
+    /// Calls `event`'s function of every script defining it, returning their results
+    /// and the webhook calls they requested
+    fn run_blocking(&self, event: Event, payload: &Value) -> (Vec<Dynamic>, Vec<Notify>) {
+        let argument = match rhai::serde::to_dynamic(payload) {
+            Ok(argument) => argument,
+            Err(e) => {
+                warn!("Failed to pass {:?} event to hooks: {}", event, e);
+                return (Vec::new(), Vec::new());
+            }
+        };
+        let notifications = Arc::new(Mutex::new(Vec::new()));
+        let mut results = Vec::new();
+        for script in self.scripts.iter().filter(|script| script.defines(event)) {
+            let engine = engine(&script.path, notifications.clone());
+            let result = engine.call_fn::<Dynamic>(
+                &mut Scope::new(),
+                &script.ast,
+                event.function(),
+                (argument.clone(),),
+            );
+            let outcome = match result {
+                Ok(result) => {
+                    results.push(result);
+                    "ok"
+                }
+                Err(e) => {
+                    warn!(
+                        "Hook script {} failed in {}: {}",
+                        script.path.display(),
+                        event.function(),
+                        e
+                    );
+                    "error"
+                }
+            };
+            metrics::counter!("hook_calls_total", "event" => event.function(), "outcome" => outcome)
+                .increment(1);
+        }
+        let notifications = std::mem::take(&mut *notifications.lock().expect("hook lock poisoned"));
+        (results, notifications)
+    }
+
+    /// Runs the hooks of `event` and delivers the webhook calls they request in
+    /// the background, on behalf of the current request if any
+    async fn fire(self: &Arc<Self>, event: Event, payload: Value) -> Vec<Dynamic> {
+        let hooks = self.clone();
+        let (results, notifications) =
+            match tokio::task::spawn_blocking(move || hooks.run_blocking(event, &payload)).await {
+                Ok(run) => run,
+                Err(e) => {
+                    warn!("Task failed when running {:?} hooks: {}", event, e);
+                    return Vec::new();
+                }
+            };
+        if !notifications.is_empty() {
+            let http = self.http.clone();
+            tokio::spawn(trace_context::scope(
+                trace_context::current(),
+                deliver(http, notifications),
+            ));
+        }
+        results
+    }
+
+    /// Runs the broadcast hooks of a transaction without holding up the response
+    pub fn broadcast(self: &Arc<Self>, txid: impl ToString, hex: String) {
+        if !self.handles(Event::Broadcast) {
+            return;
+        }
+        let hooks = self.clone();
+        let payload = json!({ "txid": txid.to_string(), "hex": hex });
+        tokio::spawn(trace_context::scope(trace_context::current(), async move {
+            hooks.fire(Event::Broadcast, payload).await
+        }));
+    }
+
+    /// Runs the watch hooks of a notification: `None` when a script dropped it,
+    /// otherwise the annotations the scripts returned
+    pub async fn watch(self: &Arc<Self>, notification: Value) -> Option<Map<String, Value>> {
+        let mut annotations = Map::new();
+        for result in self.fire(Event::Watch, notification).await {
+            if result.as_bool() == Ok(false) {
+                return None;
+            }
+            if result.is_map() {
+                match rhai::serde::from_dynamic::<Map<String, Value>>(&result) {
+                    Ok(map) => annotations.extend(map),
+                    Err(e) => warn!("Ignoring watch hook annotations: {}", e),
+                }
+            }
+        }
+        Some(annotations)
+    }
+
+    /// Runs the block hooks of every new block
+    pub async fn run(self: Arc<Self>, watcher: Arc<ChainWatcher>) {
+        let mut blocks = watcher.subscribe();
+        loop {
+            let BlockEvent {
+                height,
+                hash,
+                seen_at,
+                ..
+            } = match blocks.recv().await {
+                Ok(block) => block,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Block hooks skipped {} blocks", skipped);
+                    continue;
+                }
+                Err(RecvError::Closed) => return,
+            };
+            let seen_at = seen_at
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs());
+            let payload = json!({
+                "height": height,
+                "hash": hash.to_string(),
+                "seen_at": seen_at,
+            });
+            self.fire(Event::Block, payload).await;
+        }
+    }
+}
+
+async fn deliver(http: OutboundClient, notifications: Vec<Notify>) {
+    for notify in notifications {
+        let request = http
+            .request(Method::POST, notify.url.clone())
+            .json(&notify.payload);
+        let outcome = match http.send("hook", request).await {
+            Ok(response) if response.status().is_success() => "delivered",
+            Ok(response) => {
+                debug!("Hook webhook {} answered {}", notify.url, response.status());
+                "rejected"
+            }
+            Err(e) => {
+                debug!("Failed to deliver hook webhook to {}: {}", notify.url, e);
+                "error"
+            }
+        };
+        metrics::counter!("hook_notifications_total", "outcome" => outcome).increment(1);
+    }
+}
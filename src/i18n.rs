@@ -0,0 +1,101 @@
+//! Minimal localization of the server-rendered pages.
+//!
+//! The language comes from a `?lang=` override, then the `Accept-Language`
+//! header, then falls back to English. Only page chrome is translated; route
+//! descriptions stay in English alongside the route definitions.
+
+use axum::http::{header, HeaderMap};
+use serde::Deserialize;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Lang {
+    En,
+    De,
+    Es,
+    Pt,
+}
+
+/// Every user-visible string of the HTML pages
+pub struct Strings {
+    pub title: &'static str,
+}
+
+const EN: Strings = Strings {
+    title: "Minipool API Documentation",
+};
+
+const DE: Strings = Strings {
+    title: "Minipool API-Dokumentation",
+};
+
+const ES: Strings = Strings {
+    title: "Documentación de la API de Minipool",
+};
+
+const PT: Strings = Strings {
+    title: "Documentação da API do Minipool",
+};
+
+#[derive(Deserialize)]
+pub struct LangQuery {
+    lang: Option<String>,
+}
+
+impl Lang {
+    pub fn code(self) -> &'static str {
+        match self {
+            Lang::En => "en",
+            Lang::De => "de",
+            Lang::Es => "es",
+            Lang::Pt => "pt",
+        }
+    }
+
+    pub fn strings(self) -> &'static Strings {
+        match self {
+            Lang::En => &EN,
+            Lang::De => &DE,
+            Lang::Es => &ES,
+            Lang::Pt => &PT,
+        }
+    }
+
+    /// Matches on the primary subtag, so `pt-BR` picks Portuguese
+    fn from_tag(tag: &str) -> Option<Self> {
+        let primary = tag.split('-').next()?.trim().to_ascii_lowercase();
+        match primary.as_str() {
+            "en" => Some(Lang::En),
+            "de" => Some(Lang::De),
+            "es" => Some(Lang::Es),
+            "pt" => Some(Lang::Pt),
+            _ => None,
+        }
+    }
+
+    pub fn negotiate(query: &LangQuery, headers: &HeaderMap) -> Self {
+        if let Some(lang) = query.lang.as_deref().and_then(Self::from_tag) {
+            return lang;
+        }
+        let Some(accept) = headers
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+        else {
+            return Lang::En;
+        };
+
+        let mut candidates: Vec<(f32, Lang)> = accept
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let lang = Self::from_tag(parts.next()?)?;
+                let quality = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.parse().ok())?;
+                (quality > 0.0).then_some((quality, lang))
+            })
+            .collect();
+        // Stable, so equal weights keep the client's order
+        candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+        candidates.first().map_or(Lang::En, |&(_, lang)| lang)
+    }
+}
@@ -0,0 +1,250 @@
+//! Sequence-numbered journal of the `/api/events` stream.
+//!
+//! Every block, reorg and fees event is numbered and appended to the journal
+//! before it's streamed, so a consumer back from downtime reads what it missed
+//! from `/api/v1/events?since=<seq>` over plain HTTP, and an SSE client
+//! reconnecting with `Last-Event-ID` gets it replayed. The newest
+//! `EVENT_JOURNAL_RETENTION` events are kept, persisted in the configured store
+//! under the data directory when there is one. Without a data directory the
+//! journal lives in memory and numbering starts over at 1 on restart; like a
+//! sequence number older than the retained events, a number from before the
+//! restart gets a 410 and the consumer resyncs without `since`.
+
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{info, warn};
+
+use crate::migrations::{self, Migration, META};
+use crate::storage::{Backend, Batch, Store, Table};
+use crate::AppState;
+
+/// Big-endian sequence number → JSON event
+const EVENTS: Table = "events";
+
+/// Schema changes since the first release, see [`migrations`]
+const MIGRATIONS: &[Migration] = &[];
+
+/// Events returned by one `/api/v1/events` request
+const PAGE_SIZE: usize = 500;
+
+pub const SINCE: &str =
+    "Sequence number of the last event seen; without it events start at the oldest retained one";
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct JournalEvent {
+    pub seq: u64,
+    /// `block`, `reorg` or `fees`
+    pub event: String,
+    /// Seconds since epoch when the event was journaled
+    pub time: u64,
+    pub data: Value,
+}
+
+#[derive(Serialize)]
+struct JournalPage {
+    /// Newest journaled event, 0 before the first
+    last_seq: u64,
+    /// Oldest first, at most [`PAGE_SIZE`]
+    events: Vec<JournalEvent>,
+}
+
+pub struct EventJournal {
+    store: Option<Arc<dyn Store>>,
+    retention: usize,
+    /// Retained events, oldest first
+    recent: RwLock<VecDeque<JournalEvent>>,
+}
+
+impl EventJournal {
+    pub fn in_memory(retention: usize) -> Self {
+        Self {
+            store: None,
+            retention,
+            recent: RwLock::new(VecDeque::new()),
+        }
+    }
+
+    /// Opens the journal at `path`, loading the retained events and dropping
+    /// older ones, as after lowering the retention
+    pub fn open(backend: Backend, path: &Path, retention: usize, migrate: bool) -> Result<Self> {
+        let store = backend
+            .open(path, &[EVENTS, META])
+            .with_context(|| format!("Failed to open event journal at {}", path.display()))?;
+        migrations::migrate(
+            store.as_ref(),
+            "event journal",
+            &[EVENTS],
+            MIGRATIONS,
+            migrate,
+        )?;
+        let mut recent = VecDeque::with_capacity(retention);
+        let mut expired = Batch::default();
+        store.scan(
+            EVENTS,
+            &0u64.to_be_bytes(),
+            &u64::MAX.to_be_bytes(),
+            true,
+            &mut |key, value| {
+                if recent.len() < retention {
+                    let event: JournalEvent =
+                        serde_json::from_slice(value).context("Corrupt event journal entry")?;
+                    recent.push_front(event);
+                } else {
+                    expired.delete(EVENTS, key);
+                }
+                Ok(true)
+            },
+        )?;
+        store.write(expired, true)?;
+        if let Some(last) = recent.back() {
+            info!("Event journal resumes after event {}", last.seq);
+        }
+        Ok(Self {
+            store: Some(store),
+            retention,
+            recent: RwLock::new(recent),
+        })
+    }
+
+    /// Numbers and journals an event. An event that fails to persist is still
+    /// kept in memory, so live consumers don't see a gap.
+    pub fn append_blocking(&self, event: &str, data: Value) -> JournalEvent {
+        let mut recent = self.recent.write().expect("event journal lock poisoned");
+        let event = JournalEvent {
+            seq: recent.back().map_or(1, |last| last.seq + 1),
+            event: event.to_owned(),
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            data,
+        };
+        if let Some(store) = &self.store {
+            let mut batch = Batch::default();
+            batch.put(
+                EVENTS,
+                &event.seq.to_be_bytes(),
+                &serde_json::to_vec(&event).expect("events always serialize"),
+            );
+            if let Some(expired) = event.seq.checked_sub(self.retention as u64) {
+                batch.delete(EVENTS, &expired.to_be_bytes());
+            }
+            if let Err(e) = store.write(batch, true) {
+                warn!(
+                    "Failed to journal {} event {}: {}",
+                    event.event, event.seq, e
+                );
+                metrics::counter!("event_journal_write_failures_total").increment(1);
+            }
+        }
+        recent.push_back(event.clone());
+        if recent.len() > self.retention {
+            recent.pop_front();
+        }
+        metrics::gauge!("event_journal_last_seq").set(event.seq as f64);
+        event
+    }
+
+    pub fn last_seq(&self) -> Option<u64> {
+        let recent = self.recent.read().expect("event journal lock poisoned");
+        recent.back().map(|event| event.seq)
+    }
+
+    /// Retained events after `seq`, oldest first
+    pub fn since(&self, seq: u64) -> Vec<JournalEvent> {
+        let recent = self.recent.read().expect("event journal lock poisoned");
+        let start = recent.partition_point(|event| event.seq <= seq);
+        recent.range(start..).cloned().collect()
+    }
+
+    /// Events after `since`, or from the oldest retained one without it;
+    /// `None` when events after `since` were dropped or it's from before a
+    /// restart
+    fn page(&self, since: Option<u64>) -> Option<JournalPage> {
+        let recent = self.recent.read().expect("event journal lock poisoned");
+        let last_seq = recent.back().map_or(0, |event| event.seq);
+        let start = match since {
+            Some(seq) if seq > last_seq => return None,
+            Some(seq) if recent.front().is_some_and(|oldest| seq + 1 < oldest.seq) => return None,
+            Some(seq) => recent.partition_point(|event| event.seq <= seq),
+            None => 0,
+        };
+        Some(JournalPage {
+            last_seq,
+            events: recent.range(start..).take(PAGE_SIZE).cloned().collect(),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct JournalQuery {
+    since: Option<u64>,
+}
+
+pub async fn get_journal(
+    State(state): State<AppState>,
+    Query(query): Query<JournalQuery>,
+) -> impl IntoResponse {
+    match state.journal.page(query.since) {
+        Some(page) => Json(page).into_response(),
+        None => (
+            StatusCode::GONE,
+            "Sequence number not retained, resync without since",
+        )
+            .into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::fees::{self, FeeLimits};
+    use crate::golden;
+    use crate::mock::MockNode;
+
+    #[tokio::test]
+    async fn golden_event_journal() {
+        let node = MockNode::new(10);
+        node.set_fee_rate(45.123);
+        let limits = FeeLimits {
+            floor_sat_vb: 1.0,
+            ceiling_sat_vb: 1000.0,
+        };
+        let fees = fees::recommended_fees(&node.rpc(), &limits).await.unwrap();
+
+        let journal = EventJournal::in_memory(3);
+        journal.append_blocking("block", json!({}));
+        journal.append_blocking("reorg", json!({ "fork_height": 847999, "disconnected": 1 }));
+        journal.append_blocking(
+            "block",
+            json!({
+                "height": 848000,
+                "hash": "00000000000000000001a2b3c4d5e6f708192a3b4c5d6e7f8091a2b3c4d5e6f7",
+                "seen_at": 1718000455,
+            }),
+        );
+        journal.append_blocking("fees", serde_json::to_value(fees).unwrap());
+        for (event, time) in journal
+            .recent
+            .write()
+            .unwrap()
+            .iter_mut()
+            .zip([1718000456, 1718000456, 1718000470])
+        {
+            event.time = time;
+        }
+        golden::check("event_journal", &journal.page(Some(1)).unwrap());
+    }
+}
@@ -0,0 +1,56 @@
+//! Serialization conventions shared by all JSON response bodies.
+//!
+//! Fields of response structs come out in declaration order and objects built
+//! with `json!` with their keys sorted, as long as serde_json's
+//! `preserve_order` feature stays off. Fee rates, shares and percentages are
+//! computed in floating point, so they go through the serializers below to
+//! keep rounding noise like `2.0000000000000004` out of responses: the same
+//! inputs give byte-identical bodies across releases and platforms.
+//!
+//! `minipool-client/tests/golden` records the body of every typed endpoint,
+//! and each endpoint's tests check that its response still serializes to those
+//! bytes through [`golden`](crate::golden); changing the shape of one means
+//! regenerating its file with `UPDATE_GOLDEN=1 cargo test` in the same change.
+
+use serde::ser::SerializeTuple;
+use serde::Serializer;
+
+/// Decimal places kept for fee rates, shares and percentages
+const DECIMALS: i32 = 3;
+
+/// Rounds to three decimals, for values that can't go through the serializers below
+pub fn round(value: f64) -> f64 {
+    let scale = 10f64.powi(DECIMALS);
+    let rounded = (value * scale).round() / scale;
+    // Don't let -0.0 through as a distinct value
+    if rounded == 0.0 {
+        0.0
+    } else {
+        rounded
+    }
+}
+
+/// Serializes a fee rate, share or percentage rounded to three decimals
+pub fn rate<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(round(*value))
+}
+
+/// [`rate`] for optional values, `null` when absent
+pub fn opt_rate<S: Serializer>(value: &Option<f64>, serializer: S) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(value) => serializer.serialize_some(&round(*value)),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// [`rate`] for every element of a fixed-size array
+pub fn rates<S: Serializer, const N: usize>(
+    values: &[f64; N],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut tuple = serializer.serialize_tuple(N)?;
+    for value in values {
+        tuple.serialize_element(&round(*value))?;
+    }
+    tuple.end()
+}
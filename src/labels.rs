@@ -0,0 +1,63 @@
+//! Operator-provided address labels (own cold wallets, known exchange addresses).
+//!
+//! Loaded once at startup from either a JSON object mapping address to label, or
+//! a CSV file with one `address,label` pair per line (`#` starts a comment).
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use axum::{extract::State, response::IntoResponse, Json};
+use bitcoincore_rpc::bitcoin::address::NetworkUnchecked;
+use bitcoincore_rpc::bitcoin::Address;
+use tracing::{info, warn};
+
+use crate::AppState;
+
+pub type Labels = BTreeMap<String, String>;
+
+pub fn load(path: &Path) -> Result<Labels> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read labels file {}", path.display()))?;
+    let parsed: Labels = if path.extension().is_some_and(|ext| ext == "json") {
+        serde_json::from_str(&contents)
+            .with_context(|| format!("Invalid labels JSON in {}", path.display()))?
+    } else {
+        contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| match line.split_once(',') {
+                Some((address, label)) => {
+                    Some((address.trim().to_string(), label.trim().to_string()))
+                }
+                None => {
+                    warn!("Skipping malformed label line: {}", line);
+                    None
+                }
+            })
+            .collect()
+    };
+
+    let labels: Labels = parsed
+        .into_iter()
+        .filter(|(address, _)| {
+            let valid = Address::<NetworkUnchecked>::from_str(address).is_ok();
+            if !valid {
+                warn!("Skipping label for invalid address {}", address);
+            }
+            valid
+        })
+        .collect();
+    info!(
+        "Loaded {} address labels from {}",
+        labels.len(),
+        path.display()
+    );
+    Ok(labels)
+}
+
+pub async fn get_labels(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.labels.as_ref().clone())
+}
@@ -0,0 +1,134 @@
+//! Per-route response size limits.
+//!
+//! A response over its route's limit is cut down when it is a JSON array: the
+//! leading elements that fit are kept and `X-Truncated: true` tells the client
+//! the list is incomplete. Any other response over the limit is replaced by a
+//! 413, without reading the body when its length is announced up front.
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context};
+use axum::body::{Body, Bytes};
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde_json::Value;
+use tracing::warn;
+
+const TRUNCATED_HEADER: &str = "x-truncated";
+
+/// Parses a byte count, optionally suffixed with `KB` or `MB` (1024-based)
+pub fn parse_size(s: &str) -> anyhow::Result<usize> {
+    let (digits, unit) = if let Some(kb) = s.strip_suffix("KB") {
+        (kb, 1024)
+    } else if let Some(mb) = s.strip_suffix("MB") {
+        (mb, 1024 * 1024)
+    } else {
+        (s, 1)
+    };
+    let count: usize = digits
+        .trim()
+        .parse()
+        .with_context(|| format!("invalid size {:?}", s))?;
+    count
+        .checked_mul(unit)
+        .ok_or_else(|| anyhow!("size {:?} is too large", s))
+}
+
+/// A `--response-limit` override, written as `<path>=<size>`
+#[derive(Clone, Debug)]
+pub struct RouteResponseLimit {
+    pub path: String,
+    pub max_bytes: usize,
+}
+
+impl FromStr for RouteResponseLimit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (path, size) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow!("expected <path>=<size>, got {:?}", s))?;
+        Ok(Self {
+            path: path.trim().to_owned(),
+            max_bytes: parse_size(size.trim())?,
+        })
+    }
+}
+
+fn too_large(path: &str, max_bytes: usize) -> Response {
+    warn!("Response to {} exceeds {} bytes", path, max_bytes);
+    metrics::counter!("http_responses_over_limit_total", "outcome" => "rejected").increment(1);
+    (
+        StatusCode::PAYLOAD_TOO_LARGE,
+        format!("Response exceeds {} bytes", max_bytes),
+    )
+        .into_response()
+}
+
+/// Leading elements of a JSON array that serialize to at most `max_bytes`,
+/// or `None` when `body` is not an array
+fn truncate_array(body: &[u8], max_bytes: usize) -> Option<Vec<u8>> {
+    let Ok(Value::Array(elements)) = serde_json::from_slice(body) else {
+        return None;
+    };
+    let mut truncated = b"[".to_vec();
+    for element in elements {
+        let element = serde_json::to_vec(&element).expect("JSON values always serialize");
+        // Room for the separator and the closing bracket
+        if truncated.len() + element.len() + 2 > max_bytes {
+            break;
+        }
+        if truncated.len() > 1 {
+            truncated.push(b',');
+        }
+        truncated.extend(element);
+    }
+    truncated.push(b']');
+    Some(truncated)
+}
+
+/// Keeps responses of a route within `max_bytes`
+pub async fn limit_response(State(max_bytes): State<usize>, req: Request, next: Next) -> Response {
+    let path = req.uri().path().to_owned();
+    let response = next.run(req).await;
+    let announced = response
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|length| length.to_str().ok())
+        .and_then(|length| length.parse::<usize>().ok());
+    if announced.is_some_and(|length| length <= max_bytes) {
+        return response;
+    }
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|content_type| content_type.as_bytes().starts_with(b"application/json"));
+    if announced.is_some() && !is_json {
+        return too_large(&path, max_bytes);
+    }
+
+    let (mut parts, body) = response.into_parts();
+    // JSON bodies are built in memory anyway, others are only read up to the limit
+    let read_limit = if is_json { usize::MAX } else { max_bytes };
+    let body: Bytes = match axum::body::to_bytes(body, read_limit).await {
+        Ok(body) => body,
+        Err(_) => return too_large(&path, max_bytes),
+    };
+    if body.len() <= max_bytes {
+        return Response::from_parts(parts, Body::from(body));
+    }
+    if parts.status.is_success() && is_json {
+        if let Some(truncated) = truncate_array(&body, max_bytes) {
+            metrics::counter!("http_responses_over_limit_total", "outcome" => "truncated")
+                .increment(1);
+            parts.headers.remove(header::CONTENT_LENGTH);
+            parts
+                .headers
+                .insert(TRUNCATED_HEADER, HeaderValue::from_static("true"));
+            return Response::from_parts(parts, Body::from(truncated));
+        }
+    }
+    too_large(&path, max_bytes)
+}
@@ -0,0 +1,137 @@
+//! API listeners.
+//!
+//! The API can be bound to several addresses at once, each optionally serving
+//! TLS with its own certificate, e.g. plain HTTP on loopback next to HTTPS on an
+//! internal VLAN address.
+
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Context, Result};
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use rustls::ServerConfig;
+use tokio::task::JoinSet;
+use tracing::info;
+
+#[derive(Clone, Debug)]
+pub struct Listener {
+    addr: SocketAddr,
+    tls: Option<Tls>,
+}
+
+#[derive(Clone, Debug)]
+struct Tls {
+    cert: PathBuf,
+    key: PathBuf,
+}
+
+/// Parses `<addr>[;cert=<path>;key=<path>]`
+impl FromStr for Listener {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(';').map(str::trim);
+        let addr = parts.next().unwrap_or_default();
+        let addr = addr
+            .parse()
+            .map_err(|e| anyhow!("Invalid listen address {:?}: {}", addr, e))?;
+        let (mut cert, mut key) = (None, None);
+        for option in parts {
+            match option.split_once('=') {
+                Some(("cert", path)) => cert = Some(PathBuf::from(path)),
+                Some(("key", path)) => key = Some(PathBuf::from(path)),
+                _ => bail!(
+                    "Unknown listener option {:?}, expected cert=<path> or key=<path>",
+                    option
+                ),
+            }
+        }
+        let tls = match (cert, key) {
+            (Some(cert), Some(key)) => Some(Tls { cert, key }),
+            (None, None) => None,
+            _ => bail!("Listener {} needs both cert= and key= for TLS", addr),
+        };
+        Ok(Self { addr, tls })
+    }
+}
+
+impl Listener {
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn is_tls(&self) -> bool {
+        self.tls.is_some()
+    }
+
+    /// Server-side TLS configuration of the listener, `None` for plain TCP
+    pub async fn tls_config(&self) -> Result<Option<Arc<ServerConfig>>> {
+        let Some(tls) = &self.tls else {
+            return Ok(None);
+        };
+        install_crypto_provider();
+        let config = RustlsConfig::from_pem_file(&tls.cert, &tls.key)
+            .await
+            .with_context(|| format!("Failed to load TLS certificate for {}", self))?;
+        Ok(Some(config.get_inner()))
+    }
+}
+
+/// Several providers are compiled in through dependencies, so rustls needs to be told.
+/// Failing means one was installed already, which is just as good.
+fn install_crypto_provider() {
+    let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+}
+
+impl fmt::Display for Listener {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.tls {
+            Some(_) => write!(f, "https://{}", self.addr),
+            None => write!(f, "http://{}", self.addr),
+        }
+    }
+}
+
+/// Serves `app` on every listener, with the client's address as `ConnectInfo`,
+/// returning when any of them fails
+pub async fn serve(listeners: Vec<Listener>, app: Router) -> Result<()> {
+    install_crypto_provider();
+    let mut servers = JoinSet::new();
+    for listener in listeners {
+        let app = app.clone();
+        match &listener.tls {
+            Some(tls) => {
+                let config = RustlsConfig::from_pem_file(&tls.cert, &tls.key)
+                    .await
+                    .with_context(|| format!("Failed to load TLS certificate for {}", listener))?;
+                let server = axum_server::bind_rustls(listener.addr, config);
+                info!("Listening on {}", listener);
+                servers.spawn(async move {
+                    server
+                        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                        .await
+                        .with_context(|| format!("Listener {} failed", listener))
+                });
+            }
+            None => {
+                let tcp = tokio::net::TcpListener::bind(listener.addr)
+                    .await
+                    .with_context(|| format!("Failed to bind {}", listener))?;
+                info!("Listening on {}", listener);
+                servers.spawn(async move {
+                    axum::serve(tcp, app.into_make_service_with_connect_info::<SocketAddr>())
+                        .await
+                        .with_context(|| format!("Listener {} failed", listener))
+                });
+            }
+        }
+    }
+    while let Some(result) = servers.join_next().await {
+        result??;
+    }
+    Ok(())
+}
@@ -0,0 +1,359 @@
+//! Live updates over WebSocket, in the mempool.space message format.
+//!
+//! Clients pick topics with `{"action": "want", "data": ["blocks", "stats",
+//! "mempool-blocks"]}` and from then on receive `{"block": ...}` for every new
+//! block, `{"mempoolInfo": ..., "fees": ...}` and `{"mempool-blocks": [...]}`
+//! every update interval. The latest message of a topic is sent as soon as it
+//! is wanted, so clients don't wait a full interval for their first update.
+//!
+//! A single hub task builds every message once and fans it out to all
+//! connections; a connection that falls behind skips the updates it missed.
+//!
+//! The `events` topic carries the [`journal`](crate::journal)'s block, reorg
+//! and fees events as `{"event": {"seq": ..., ...}}` and skips none: a client
+//! reconnecting to `/ws?since=<seq>` with the last `seq` it got is first sent
+//! the retained events it missed, as SSE clients are with `Last-Event-ID`, and
+//! a connection that falls behind catches up from the journal.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, UNIX_EPOCH};
+
+use axum::extract::ws::{Message, Utf8Bytes, WebSocket, WebSocketUpgrade};
+use axum::extract::{Query, State};
+use axum::response::IntoResponse;
+use bitcoincore_rpc::bitcoin::BlockHash;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, warn};
+
+use crate::blocks::{self, EsploraBlock};
+use crate::cache::BoundedCache;
+use crate::chain::{BlockEvent, ChainWatcher};
+use crate::fees::{self, FeeLimits};
+use crate::journal::{EventJournal, JournalEvent};
+use crate::mempool_blocks::MempoolProjection;
+use crate::rpc::Rpc;
+use crate::AppState;
+
+/// Updates buffered per connection before slow ones start skipping
+const UPDATE_BUFFER: usize = 16;
+
+pub const SINCE: &str =
+    "Sequence number of the last `events` message received; retained events after it are sent first";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Topic {
+    Blocks,
+    Stats,
+    MempoolBlocks,
+    Events,
+}
+
+impl Topic {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "blocks" => Some(Topic::Blocks),
+            "stats" => Some(Topic::Stats),
+            "mempool-blocks" => Some(Topic::MempoolBlocks),
+            "events" => Some(Topic::Events),
+            _ => None,
+        }
+    }
+}
+
+/// A message for the clients wanting `topic`, serialized once for all of them
+#[derive(Clone)]
+struct Update {
+    topic: Topic,
+    message: Utf8Bytes,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "action", content = "data", rename_all = "kebab-case")]
+enum ClientMessage {
+    /// Topic names, including ones of mempool.space that aren't served here
+    Want(Vec<String>),
+}
+
+/// Where a connection is in the event journal, so it's sent every event once
+struct EventCursor {
+    /// Newest event sent or skipped as seen before
+    last_seq: Option<u64>,
+}
+
+impl EventCursor {
+    /// Retained events after the last one sent, none without one
+    fn catch_up(&mut self, journal: &EventJournal) -> Vec<JournalEvent> {
+        let newest = journal.last_seq();
+        let Some(seq) = self.last_seq else {
+            self.last_seq = newest;
+            return Vec::new();
+        };
+        let missed = journal.since(seq);
+        // Events up to the newest journaled one are sent now or were seen, even
+        // when `since` is from before a restart of an in-memory journal
+        self.last_seq = missed.last().map(|event| event.seq).or(newest);
+        missed
+    }
+
+    /// Whether a live event is yet to be sent, one already caught up on isn't
+    fn advance(&mut self, event: &JournalEvent) -> bool {
+        if self.last_seq.is_some_and(|seq| event.seq <= seq) {
+            return false;
+        }
+        self.last_seq = Some(event.seq);
+        true
+    }
+}
+
+fn event_message(event: &JournalEvent) -> Utf8Bytes {
+    Utf8Bytes::from(json!({ "event": event }).to_string())
+}
+
+pub struct LiveHub {
+    sender: broadcast::Sender<Update>,
+    latest: RwLock<HashMap<Topic, Utf8Bytes>>,
+}
+
+impl LiveHub {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(UPDATE_BUFFER);
+        Self {
+            sender,
+            latest: RwLock::new(HashMap::new()),
+        }
+    }
+
+    fn publish(&self, topic: Topic, message: Value) {
+        let message = Utf8Bytes::from(message.to_string());
+        self.latest
+            .write()
+            .expect("live hub lock poisoned")
+            .insert(topic, message.clone());
+        // Nobody connected is fine, clients come and go
+        let _ = self.sender.send(Update { topic, message });
+    }
+
+    fn latest(&self, topic: Topic) -> Option<Utf8Bytes> {
+        self.latest
+            .read()
+            .expect("live hub lock poisoned")
+            .get(&topic)
+            .cloned()
+    }
+
+    /// Publishes every new block, and mempool stats and projected blocks every
+    /// `interval`
+    pub async fn run(
+        self: Arc<Self>,
+        rpc: Arc<Rpc>,
+        watcher: Arc<ChainWatcher>,
+        block_summaries: Arc<BoundedCache<BlockHash, EsploraBlock>>,
+        mempool_blocks: Arc<MempoolProjection>,
+        fee_limits: FeeLimits,
+        interval: Duration,
+    ) {
+        let mut blocks = watcher.subscribe();
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                block = blocks.recv() => match block {
+                    Ok(block) => self.publish_block(&rpc, &block_summaries, block).await,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Live updates skipped {} blocks", skipped);
+                    }
+                    Err(RecvError::Closed) => return,
+                },
+                _ = ticker.tick() => {
+                    self.publish_stats(&rpc, fee_limits).await;
+                    if let Some(projected) = mempool_blocks.blocks() {
+                        self.publish(
+                            Topic::MempoolBlocks,
+                            json!({ "mempool-blocks": projected.as_slice() }),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    async fn publish_block(
+        &self,
+        rpc: &Rpc,
+        cache: &BoundedCache<BlockHash, EsploraBlock>,
+        event: BlockEvent,
+    ) {
+        match blocks::esplora_block(rpc, cache, &event.hash).await {
+            Ok(mut block) => {
+                block.seen_at = event
+                    .seen_at
+                    .duration_since(UNIX_EPOCH)
+                    .ok()
+                    .map(|since| since.as_secs());
+                self.publish(Topic::Blocks, json!({ "block": block }));
+            }
+            Err(e) => warn!("Failed to get block {} for live updates: {}", event.hash, e),
+        }
+    }
+
+    async fn publish_stats(&self, rpc: &Rpc, fee_limits: FeeLimits) {
+        let stats = async {
+            let info = rpc.get_mempool_info().await?;
+            let fees = fees::recommended_fees(rpc, &fee_limits).await?;
+            Ok::<_, bitcoincore_rpc::Error>(json!({ "mempoolInfo": info, "fees": fees }))
+        };
+        match stats.await {
+            Ok(stats) => self.publish(Topic::Stats, stats),
+            Err(e) => warn!("Failed to get mempool stats for live updates: {}", e),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct LiveQuery {
+    since: Option<u64>,
+}
+
+pub async fn get_ws(
+    State(state): State<AppState>,
+    Query(query): Query<LiveQuery>,
+    upgrade: WebSocketUpgrade,
+) -> impl IntoResponse {
+    // Subscribing before reading the journal leaves no gap between the replayed
+    // events and the live ones, and the overlap is skipped by sequence number
+    let events = state.events.subscribe();
+    let cursor = EventCursor {
+        last_seq: query.since,
+    };
+    upgrade.on_upgrade(move |socket| serve(socket, state.live, state.journal, events, cursor))
+}
+
+async fn serve(
+    mut socket: WebSocket,
+    hub: Arc<LiveHub>,
+    journal: Arc<EventJournal>,
+    mut events: broadcast::Receiver<JournalEvent>,
+    mut cursor: EventCursor,
+) {
+    metrics::gauge!("live_connections").increment(1);
+    let mut updates = hub.sender.subscribe();
+    let mut wanted: Vec<Topic> = Vec::new();
+    'connection: loop {
+        tokio::select! {
+            message = socket.recv() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | None => break,
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => {
+                        debug!("Live update connection failed: {}", e);
+                        break;
+                    }
+                };
+                // Messages we don't know about are ignored, as mempool.space does
+                let Ok(ClientMessage::Want(names)) = serde_json::from_str(&text) else {
+                    continue;
+                };
+                let had_events = wanted.contains(&Topic::Events);
+                wanted = names.iter().filter_map(|name| Topic::from_name(name)).collect();
+                let mut messages: Vec<Utf8Bytes> =
+                    wanted.iter().filter_map(|&topic| hub.latest(topic)).collect();
+                if wanted.contains(&Topic::Events) && !had_events {
+                    messages.extend(cursor.catch_up(&journal).iter().map(event_message));
+                }
+                for message in messages {
+                    if socket.send(Message::Text(message)).await.is_err() {
+                        break 'connection;
+                    }
+                }
+            }
+            update = updates.recv() => match update {
+                Ok(update) if wanted.contains(&update.topic) => {
+                    if socket.send(Message::Text(update.message)).await.is_err() {
+                        break;
+                    }
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(skipped)) => {
+                    metrics::counter!("live_updates_skipped_total").increment(skipped);
+                }
+                Err(RecvError::Closed) => break,
+            },
+            event = events.recv() => match event {
+                Ok(event) if wanted.contains(&Topic::Events) && cursor.advance(&event) => {
+                    if socket.send(Message::Text(event_message(&event))).await.is_err() {
+                        break;
+                    }
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(_)) if wanted.contains(&Topic::Events) => {
+                    for event in cursor.catch_up(&journal) {
+                        if socket.send(Message::Text(event_message(&event))).await.is_err() {
+                            break 'connection;
+                        }
+                    }
+                }
+                Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            }
+        }
+    }
+    metrics::gauge!("live_connections").decrement(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seqs(events: &[JournalEvent]) -> Vec<u64> {
+        events.iter().map(|event| event.seq).collect()
+    }
+
+    #[test]
+    fn reconnect_replays_missed_events() {
+        let journal = EventJournal::in_memory(10);
+        let mut first = EventCursor { last_seq: None };
+        assert!(first.catch_up(&journal).is_empty());
+        for _ in 0..2 {
+            let event = journal.append_blocking("block", json!({}));
+            assert!(first.advance(&event));
+        }
+
+        // Journaled while the client was disconnected
+        journal.append_blocking("reorg", json!({}));
+        let during_reconnect = journal.append_blocking("block", json!({}));
+
+        let mut resumed = EventCursor { last_seq: Some(2) };
+        assert_eq!(seqs(&resumed.catch_up(&journal)), [3, 4]);
+        // Also received live, having subscribed before the catch up
+        assert!(!resumed.advance(&during_reconnect));
+        assert!(resumed.advance(&journal.append_blocking("fees", json!({}))));
+    }
+
+    #[test]
+    fn falling_behind_catches_up_from_the_journal() {
+        let journal = EventJournal::in_memory(10);
+        let mut cursor = EventCursor { last_seq: None };
+        cursor.catch_up(&journal);
+        assert!(cursor.advance(&journal.append_blocking("block", json!({}))));
+        for _ in 0..3 {
+            journal.append_blocking("block", json!({}));
+        }
+        assert_eq!(seqs(&cursor.catch_up(&journal)), [2, 3, 4]);
+        assert!(cursor.catch_up(&journal).is_empty());
+    }
+
+    #[test]
+    fn since_from_before_a_restart_skips_nothing_new() {
+        let journal = EventJournal::in_memory(10);
+        journal.append_blocking("block", json!({}));
+        let mut cursor = EventCursor {
+            last_seq: Some(4120),
+        };
+        assert!(cursor.catch_up(&journal).is_empty());
+        assert!(cursor.advance(&journal.append_blocking("block", json!({}))));
+    }
+}
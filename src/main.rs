@@ -1,54 +1,180 @@
 use std::collections::BTreeMap;
-use std::fmt::Write;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use axum::middleware;
 use axum::routing::MethodRouter;
 use axum::{
     extract::{Path, State},
-    http::StatusCode,
-    response::{Html, IntoResponse, Redirect},
-    routing::get,
-    Json, Router,
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode, Uri},
+    response::{IntoResponse, Redirect, Response},
+    routing::{delete, get, post},
+    Router,
 };
-use bitcoincore_rpc::bitcoin::BlockHash;
-use bitcoincore_rpc::{Auth, Client, RpcApi};
-use clap::Parser;
+use bitcoincore_rpc::bitcoin::{BlockHash, Network};
+use clap::{CommandFactory, FromArgMatches, Parser};
+use minipool_client::{paths, types};
 use std::convert::Infallible;
 use tower_http::trace::TraceLayer;
 use tracing::{info, warn};
+use tracing_subscriber::EnvFilter;
 
+use self::addresses::AddressIndex;
+use self::admin::AdminToken;
+use self::audit::BroadcastAudit;
+use self::backends::{BackendMonitor, BackendNode};
+use self::blocks::{EsploraBlock, ExtendedBlock, FeeBucket, NodeRest};
+use self::cache::BoundedCache;
+use self::chain::ChainWatcher;
+use self::checkpoints::{parse_checkpoints, CheckpointGuard, Checkpoints};
+use self::compat::Compat;
+use self::consensus::ConsensusMonitor;
+use self::events::EventStream;
+use self::failover::{Failover, ReadBalance, WriteTarget};
+use self::features::Features;
+use self::fee_accuracy::FeeAccuracyTracker;
+use self::fees::{FeeCache, FeeLimits};
+use self::headers::HeaderChain;
+use self::health::{Health, Severity};
+use self::hooks::{Event, Hooks};
+use self::journal::EventJournal;
+use self::labels::Labels;
+use self::limits::RouteResponseLimit;
+use self::listeners::Listener;
+use self::live::LiveHub;
+use self::mempool::{FirstSeenStore, MempoolTracker};
+use self::mempool_blocks::MempoolProjection;
 use self::metrics::track_metrics;
+use self::min_fee::MinFeeTracker;
+use self::mining::HashrateSample;
+use self::outbound::{OutboundClient, OutboundConfig};
+use self::policy::{parse_duration, RoutePolicy, RoutePolicyOverride};
+use self::propagation::PropagationTracker;
+use self::proxy::Proxy;
+use self::redact::{Redactor, Secret};
+use self::registry::WatchRegistry;
+use self::rpc::{Rpc, RpcTransport};
+use self::sampling::{RouteSampleRate, TraceSampler};
+use self::shadow::Shadow;
+use self::spends::SpendIndex;
+use self::stats::BlockStatsPipeline;
+use self::storage::Backend;
+use self::summary::ConfigSummary;
+use self::transport::NodeBackend;
+use self::warmup::Warmup;
+use self::watch::OutpointWatches;
+use self::webhooks::Webhooks;
 
+mod addresses;
+mod admin;
+mod assets;
+mod audit;
+mod backends;
+mod blocks;
+mod cache;
+mod chain;
+mod checkpoints;
+mod coin_select;
+mod compat;
+mod consensus;
+#[cfg(feature = "ctv")]
+mod ctv;
+mod difficulty;
+mod doh;
+mod electrum;
+mod events;
+mod failover;
+mod features;
+mod fee_accuracy;
+mod fees;
+mod filters;
+#[cfg(test)]
+mod golden;
+#[cfg(feature = "graphql")]
+mod graphql;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod headers;
+mod health;
+mod hooks;
+mod i18n;
+mod journal;
+mod json;
+mod labels;
+mod limits;
+mod listeners;
+mod live;
+mod mempool;
+mod mempool_blocks;
+mod merkle;
+mod message;
 mod metrics;
-
-/// Confirmation targets for fee estimation offered by mempool.space and blockstream.info
-const CONFIRMATION_TARGETS: &[u16] = &[
-    1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 144,
-    504, 1008,
-];
+mod migrations;
+mod min_fee;
+mod mining;
+#[cfg(test)]
+mod mock;
+mod node_info;
+mod openapi;
+mod outbound;
+mod peers;
+mod policy;
+mod pool;
+mod propagation;
+mod proxy;
+mod redact;
+mod registry;
+#[cfg(feature = "regtest")]
+mod regtest;
+mod rpc;
+mod sampling;
+mod shadow;
+mod spends;
+mod stats;
+mod storage;
+mod summary;
+mod trace_context;
+mod transport;
+mod tx;
+mod tx_size;
+mod warmup;
+mod watch;
+mod webhooks;
+mod zmq;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Config {
-    /// Bitcoin RPC URL
-    #[arg(long, env = "BITCOIN_RPC_URL")]
-    bitcoin_rpc_url: String,
+    /// Comma-separated Bitcoin RPC URLs in order of preference, failed over between, each
+    /// optionally `;user=<user>;pass=<pass>` when its credentials differ
+    #[arg(
+        long,
+        env = "BITCOIN_RPC_URL",
+        value_delimiter = ',',
+        required_unless_present = "upstream_esplora"
+    )]
+    bitcoin_rpc_url: Vec<BackendNode>,
 
-    /// Bitcoin RPC username
+    /// Bitcoin RPC username, not needed with the rest backend
     #[arg(long, env = "BITCOIN_RPC_USER")]
-    bitcoin_rpc_user: String,
+    bitcoin_rpc_user: Option<String>,
 
     /// Bitcoin RPC password
     #[arg(long, env = "BITCOIN_RPC_PASS")]
-    bitcoin_rpc_pass: String,
+    bitcoin_rpc_pass: Option<Secret>,
 
-    /// Bind address for the HTTP server
-    #[arg(long, env = "BIND_ADDR", default_value = "127.0.0.1:3000")]
-    bind_addr: SocketAddr,
+    /// Comma-separated addresses for the HTTP server, each optionally `;cert=<path>;key=<path>` for TLS
+    #[arg(
+        long = "bind-addr",
+        env = "BIND_ADDR",
+        value_delimiter = ',',
+        default_value = "127.0.0.1:3000"
+    )]
+    listeners: Vec<Listener>,
 
     #[arg(
         long,
@@ -57,154 +183,1588 @@ struct Config {
         help = "Prometheus address to bind/listen to"
     )]
     prometheus_bind_addr: SocketAddr,
+
+    /// Per-route timeout and retry budget override, as `<path>=<timeout>/<retries>`
+    /// (e.g. `/api/fee-estimates=5s/2`). May be given multiple times.
+    #[arg(long = "route-policy", env = "ROUTE_POLICIES", value_delimiter = ',')]
+    route_policies: Vec<RoutePolicyOverride>,
+
+    /// Per-route response size limit, as `<path>=<size>` with an optional `KB`/`MB` suffix
+    /// (e.g. `/api/blocks=256KB`); larger JSON lists are truncated, other responses get a 413.
+    /// May be given multiple times.
+    #[arg(
+        long = "response-limit",
+        env = "RESPONSE_LIMITS",
+        value_delimiter = ','
+    )]
+    response_limits: Vec<RouteResponseLimit>,
+
+    /// Share of requests traced and access-logged, between 0 and 1; requests with a `traceparent` header always are
+    #[arg(long, env = "TRACE_SAMPLE_RATE", default_value_t = 1.0)]
+    trace_sample_rate: f64,
+
+    /// Per-route trace sample rate override, as `<path>=<rate>` (e.g. `/api/fee-estimates=0.01`). May be given multiple times.
+    #[arg(
+        long = "trace-sample-route",
+        env = "TRACE_SAMPLE_ROUTES",
+        value_delimiter = ','
+    )]
+    trace_sample_routes: Vec<RouteSampleRate>,
+
+    /// Lowest fee rate served by any fee endpoint; estimates below it are clamped.
+    /// 0 (the default) serves the node's estimates as they are
+    #[arg(long, env = "FEE_FLOOR_SAT_VB", default_value_t = 0.0)]
+    fee_floor_sat_vb: f64,
+
+    /// Highest fee rate served by any fee endpoint; estimates above it are clamped
+    #[arg(long, env = "FEE_CEILING_SAT_VB", default_value_t = 10_000.0)]
+    fee_ceiling_sat_vb: f64,
+
+    /// How long fee estimates refreshed in the background are served from memory; 0s disables it
+    #[arg(long, env = "FEE_CACHE_TTL", default_value = "30s", value_parser = parse_duration)]
+    fee_cache_ttl: Duration,
+
+    /// How often the node is polled for new blocks
+    #[arg(long, env = "CHAIN_POLL_INTERVAL", default_value = "10s", value_parser = parse_duration)]
+    chain_poll_interval: Duration,
+
+    /// Bearer token required by admin routes; admin routes are disabled without it
+    #[arg(long, env = "ADMIN_TOKEN")]
+    admin_token: Option<Secret>,
+
+    /// CSV (`address,label` per line) or JSON (address to label object) file of known address labels
+    #[arg(long, env = "LABELS_FILE")]
+    labels_file: Option<PathBuf>,
+
+    /// Base URL of a canary minipool instance that receives a sample of read requests
+    #[arg(long, env = "SHADOW_URL")]
+    shadow_url: Option<reqwest::Url>,
+
+    /// Share of read requests mirrored to the shadow URL, between 0 and 1
+    #[arg(long, env = "SHADOW_SAMPLE_RATE", default_value_t = 0.01)]
+    shadow_sample_rate: f64,
+
+    /// Run as a caching proxy in front of this esplora instance instead of a node; the /api paths are appended to it
+    #[arg(long, env = "UPSTREAM_ESPLORA")]
+    upstream_esplora: Option<reqwest::Url>,
+
+    /// Responses kept in the proxy cache
+    #[arg(long, env = "PROXY_CACHE_ENTRIES", default_value_t = 4096)]
+    proxy_cache_entries: usize,
+
+    /// How long proxied responses that follow the tip or the mempool are served from the cache
+    #[arg(long, env = "PROXY_CACHE_TTL", default_value = "5s", value_parser = parse_duration)]
+    proxy_cache_ttl: Duration,
+
+    /// Length of the post-startup window during which the request rate is ramped up; 0s disables it
+    #[arg(long, env = "WARMUP_DURATION", default_value = "0s", value_parser = parse_duration)]
+    warmup_duration: Duration,
+
+    /// Requests per second accepted right after startup
+    #[arg(long, env = "WARMUP_INITIAL_RPS", default_value_t = 5.0)]
+    warmup_initial_rps: f64,
+
+    /// Requests per second accepted at the end of the warm-up window
+    #[arg(long, env = "WARMUP_TARGET_RPS", default_value_t = 200.0)]
+    warmup_target_rps: f64,
+
+    /// Number of recent blocks kept by the statistics pipeline, persisted under DATA_DIR; 0 disables it
+    #[arg(long, env = "STATS_RETENTION_BLOCKS", default_value_t = 1008)]
+    stats_retention_blocks: u64,
+
+    /// How often the mempool mirror is resynced with the node; 0s disables it
+    #[arg(long, env = "MEMPOOL_POLL_INTERVAL", default_value = "5s", value_parser = parse_duration)]
+    mempool_poll_interval: Duration,
+
+    /// How often the mempool is projected into the next blocks; 0s disables it
+    #[arg(long, env = "MEMPOOL_BLOCKS_INTERVAL", default_value = "10s", value_parser = parse_duration)]
+    mempool_blocks_interval: Duration,
+
+    /// Input witness size, in bytes, from which a transaction counts as large-witness
+    #[arg(long, env = "LARGE_WITNESS_BYTES", default_value_t = 1000)]
+    large_witness_bytes: u64,
+
+    /// Directory for persistent state such as indexes
+    #[arg(long, env = "DATA_DIR")]
+    data_dir: Option<PathBuf>,
+
+    /// Known-good block hashes as comma-separated height:hash pairs; requests are refused while the node contradicts one
+    #[arg(long, env = "CHECKPOINTS", value_parser = parse_checkpoints)]
+    checkpoints: Option<Checkpoints>,
+
+    /// Answer exactly like esplora or mempool.space where minipool's own format differs,
+    /// and serve the rest of their endpoints
+    #[arg(long, env = "COMPAT")]
+    compat: Option<Compat>,
+
+    /// Where block, header and transaction fetches go: `rpc`, `rest` (the node's REST
+    /// interface only, no credentials needed) or `hybrid` (REST, RPC for everything else)
+    #[arg(long, env = "BACKEND", default_value = "rpc")]
+    backend: NodeBackend,
+
+    /// Base URL of the node's REST interface (needs -rest), used to stream binary raw blocks
+    /// and by the rest and hybrid backends (default: the RPC URL's host)
+    #[arg(long, env = "BITCOIN_REST_URL")]
+    bitcoin_rest_url: Option<reqwest::Url>,
+
+    /// Comma-separated RPC URLs of further bitcoind nodes compared to the primary one, each
+    /// optionally `;user=<user>;pass=<pass>` when its credentials differ from the primary's
+    #[arg(long = "backend-node", env = "BACKEND_NODES", value_delimiter = ',')]
+    backend_nodes: Vec<BackendNode>,
+
+    /// How often backend nodes are compared to the primary node
+    #[arg(long, env = "BACKEND_CHECK_INTERVAL", default_value = "30s", value_parser = parse_duration)]
+    backend_check_interval: Duration,
+
+    /// Blocks a backend node may be behind or ahead of the primary node while still in sync
+    #[arg(long, env = "BACKEND_MAX_LAG", default_value_t = 2)]
+    backend_max_lag: u64,
+
+    /// Comma-separated base URLs of esplora-compatible APIs on the node's network, such as
+    /// https://blockstream.info, whose tips the node's is compared to
+    #[arg(long = "reference-api", env = "REFERENCE_APIS", value_delimiter = ',')]
+    reference_apis: Vec<reqwest::Url>,
+
+    /// How often the node's tip is compared to the reference APIs
+    #[arg(long, env = "REFERENCE_CHECK_INTERVAL", default_value = "300s", value_parser = parse_duration)]
+    reference_check_interval: Duration,
+
+    /// Blocks a reference API may be behind or ahead of the node while still agreeing
+    #[arg(long, env = "REFERENCE_MAX_LAG", default_value_t = 2)]
+    reference_max_lag: u64,
+
+    /// Connections kept to each RPC node, so that many calls run at once
+    #[arg(long, env = "RPC_POOL_SIZE", default_value_t = 4)]
+    rpc_pool_size: usize,
+
+    /// How often each of several RPC nodes is checked for failover
+    #[arg(long, env = "RPC_CHECK_INTERVAL", default_value = "5s", value_parser = parse_duration)]
+    rpc_check_interval: Duration,
+
+    /// Blocks an RPC node may be behind the highest one before calls fail over from it
+    #[arg(long, env = "RPC_MAX_LAG", default_value_t = 1)]
+    rpc_max_lag: u64,
+
+    /// How reads are spread over several RPC nodes: failover (all to the active node),
+    /// round-robin or least-in-flight
+    #[arg(long, env = "RPC_BALANCE", default_value = "failover")]
+    rpc_balance: ReadBalance,
+
+    /// Where broadcasts go with several RPC nodes: active, all, or one node's host:port
+    #[arg(long, env = "RPC_WRITE", default_value = "active")]
+    rpc_write: WriteTarget,
+
+    /// Verify proof of work and linkage of every header from genesis and check the headers served
+    #[arg(long, env = "VERIFY_HEADERS")]
+    verify_headers: bool,
+
+    /// Index which transaction spends each output, serving the outspend endpoints; needs DATA_DIR
+    #[arg(long, env = "SPEND_INDEX")]
+    spend_index: bool,
+
+    /// First block scanned by the spend index; spends in earlier blocks are not known
+    #[arg(long, env = "SPEND_INDEX_START_HEIGHT", default_value_t = 0)]
+    spend_index_start_height: u64,
+
+    /// Storage of the spend and address indexes: redb, or sled, rocksdb and sqlite when built with their feature
+    #[arg(long, env = "STORAGE_BACKEND", default_value = "redb")]
+    storage_backend: Backend,
+
+    /// Refuse to start instead of upgrading indexes written by an older version
+    #[arg(long, env = "NO_MIGRATE")]
+    no_migrate: bool,
+
+    /// Index the transactions of every address, serving the address endpoints; needs DATA_DIR and Bitcoin Core 23+
+    #[arg(long, env = "ADDRESS_INDEX")]
+    address_index: bool,
+
+    /// First block scanned by the address index; earlier activity is not included
+    #[arg(long, env = "ADDRESS_INDEX_START_HEIGHT", default_value_t = 0)]
+    address_index_start_height: u64,
+
+    /// Serve address UTXOs by scanning the node's UTXO set when there is no address index
+    #[arg(long, env = "UTXO_SCAN")]
+    utxo_scan: bool,
+
+    /// Rhai script run on new blocks, broadcasts and watch notifications. May be given multiple times.
+    #[arg(long = "hook-script", env = "HOOK_SCRIPTS", value_delimiter = ',')]
+    hook_scripts: Vec<PathBuf>,
+
+    /// How often mempool stats and projected blocks are pushed to `/ws` clients and fee changes
+    /// are checked for `/api/events`; 0s disables both endpoints
+    #[arg(long, env = "LIVE_UPDATE_INTERVAL", default_value = "10s", value_parser = parse_duration)]
+    live_update_interval: Duration,
+
+    /// Share by which a recommended fee rate must move for `/api/events` to send a fee event
+    #[arg(long, env = "EVENTS_FEE_CHANGE", default_value_t = 0.1)]
+    events_fee_change: f64,
+
+    /// Newest `/api/events` events kept for `/api/v1/events` and SSE reconnects, persisted under
+    /// DATA_DIR when set
+    #[arg(long, env = "EVENT_JOURNAL_RETENTION", default_value_t = 10_000)]
+    event_journal_retention: usize,
+
+    /// URL POSTed every block and reorg event. May be given multiple times.
+    #[arg(long = "webhook", env = "WEBHOOKS", value_delimiter = ',')]
+    webhooks: Vec<reqwest::Url>,
+
+    /// Secret signing webhook payloads with HMAC-SHA256
+    #[arg(long, env = "WEBHOOK_SECRET")]
+    webhook_secret: Option<Secret>,
+
+    /// Delivery attempts of a webhook event before it is dropped
+    #[arg(long, env = "WEBHOOK_MAX_ATTEMPTS", default_value_t = 5)]
+    webhook_max_attempts: u32,
+
+    /// bitcoind `zmqpubrawblock` endpoint, e.g. tcp://127.0.0.1:28332; new blocks are picked up on notification
+    #[arg(long, env = "ZMQ_BLOCK")]
+    zmq_block: Option<String>,
+
+    /// bitcoind `zmqpubrawtx` endpoint; the mempool mirror resyncs on notification
+    #[arg(long, env = "ZMQ_TX")]
+    zmq_tx: Option<String>,
+
+    /// Comma-separated addresses to serve the Electrum protocol on, each optionally `;cert=<path>;key=<path>` for TLS
+    #[arg(
+        long = "electrum-listen",
+        env = "ELECTRUM_LISTEN",
+        value_delimiter = ','
+    )]
+    electrum_listeners: Vec<Listener>,
+
+    /// Address to serve the gRPC API on, when built with the `grpc` feature
+    #[arg(long, env = "GRPC_LISTEN")]
+    grpc_listen: Option<SocketAddr>,
+
+    #[command(flatten)]
+    outbound: OutboundConfig,
+
+    /// Comma-separated parameter names whose values are masked in logs, wherever they
+    /// appear as `name=value` (query strings, `;pass=` node options)
+    #[arg(
+        long = "log-redact-param",
+        env = "LOG_REDACT_PARAMS",
+        value_delimiter = ',',
+        default_value = "pass,password,token,secret,apikey,api_key"
+    )]
+    log_redact_params: Vec<String>,
+
+    /// Comma-separated further values masked in logs as parameter values, such as credentials used by hook scripts
+    #[arg(
+        long = "log-redact-value",
+        env = "LOG_REDACT_VALUES",
+        value_delimiter = ','
+    )]
+    log_redact_values: Vec<Secret>,
+}
+
+impl Config {
+    /// Every configured secret, including the passwords of configured URLs
+    fn secrets(&self) -> Vec<&str> {
+        let urls = self
+            .shadow_url
+            .iter()
+            .chain(&self.upstream_esplora)
+            .chain(&self.bitcoin_rest_url)
+            .chain(&self.reference_apis)
+            .chain(&self.webhooks)
+            .chain(&self.outbound.outbound_doh_url);
+        self.bitcoin_rpc_pass
+            .iter()
+            .chain(&self.admin_token)
+            .chain(&self.webhook_secret)
+            .chain(&self.log_redact_values)
+            .map(Secret::expose)
+            .chain(
+                self.bitcoin_rpc_url
+                    .iter()
+                    .chain(&self.backend_nodes)
+                    .filter_map(BackendNode::password),
+            )
+            .chain(urls.filter_map(reqwest::Url::password))
+            .collect()
+    }
 }
 
 #[derive(Clone)]
 struct AppState {
-    rpc: Arc<Client>,
-    routes: Arc<Vec<RouteInfo>>,
+    rpc: Arc<Rpc>,
+    network: Network,
+    openapi: Arc<utoipa::openapi::OpenApi>,
+    features: Arc<Features>,
+    compat: Option<Compat>,
+    fee_limits: FeeLimits,
+    fee_cache: Arc<FeeCache>,
+    fee_accuracy: Arc<FeeAccuracyTracker>,
+    labels: Arc<Labels>,
+    fee_histograms: Arc<BoundedCache<BlockHash, Arc<Vec<FeeBucket>>>>,
+    block_summaries: Arc<BoundedCache<BlockHash, EsploraBlock>>,
+    extended_blocks: Arc<BoundedCache<BlockHash, ExtendedBlock>>,
+    hashrate_samples: Arc<BoundedCache<BlockHash, HashrateSample>>,
+    block_stats: Arc<BlockStatsPipeline>,
+    mempool: Arc<MempoolTracker>,
+    config_summary: Arc<ConfigSummary>,
+    mempool_blocks: Arc<MempoolProjection>,
+    min_fee: Arc<MinFeeTracker>,
+    health: Arc<Health>,
+    spends: Option<Arc<SpendIndex>>,
+    addresses: Option<Arc<AddressIndex>>,
+    /// Answer address UTXO lookups with `scantxoutset` when there is no address index
+    utxo_scan: bool,
+    propagation: Arc<PropagationTracker>,
+    watches: Arc<OutpointWatches>,
+    webhooks: Arc<Webhooks>,
+    hooks: Arc<Hooks>,
+    headers: Option<Arc<HeaderChain>>,
+    rest: Option<Arc<NodeRest>>,
+    live: Arc<LiveHub>,
+    events: Arc<EventStream>,
+    journal: Arc<EventJournal>,
+    audit: Arc<BroadcastAudit>,
+    backends: Option<Arc<BackendMonitor>>,
+    consensus: Option<Arc<ConsensusMonitor>>,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
-
-    let config = Config::parse();
+    let command = Config::command();
+    let matches = command.clone().get_matches();
+    let config = Config::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let redactor = Redactor::new(config.secrets(), &config.log_redact_params);
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_writer(redactor.clone())
+        .init();
     info!(
         "Starting minipool with config: bitcoin-user={:?}, bitcoin-url={:?}",
         config.bitcoin_rpc_user, config.bitcoin_rpc_url
     );
 
     let metrics_server = metrics::start_metrics_server(config.prometheus_bind_addr);
-    let main_server = start_main_server(config);
+    let summary = Arc::new(ConfigSummary::new(&config, &command, &matches, &redactor));
+    let main_server = start_main_server(config, summary);
 
     tokio::try_join!(metrics_server, main_server)?;
     Ok(())
 }
 
-async fn start_main_server(config: Config) -> Result<()> {
-    let rpc = Client::new(
-        &config.bitcoin_rpc_url,
-        Auth::UserPass(config.bitcoin_rpc_user, config.bitcoin_rpc_pass),
-    )?;
+/// `config_summary` is the startup summary served to admins
+async fn start_main_server(mut config: Config, config_summary: Arc<ConfigSummary>) -> Result<()> {
+    if let Some(upstream) = config.upstream_esplora.take() {
+        return start_proxy_server(config, upstream).await;
+    }
+    let rpc_nodes = std::mem::take(&mut config.bitcoin_rpc_url);
+    let Some(rpc_url) = rpc_nodes.first().map(|node| node.url().to_owned()) else {
+        bail!("BITCOIN_RPC_URL is needed without UPSTREAM_ESPLORA");
+    };
+    let credentials = config.bitcoin_rpc_user.take().zip(
+        config
+            .bitcoin_rpc_pass
+            .take()
+            .map(|pass| pass.expose().to_owned()),
+    );
+    let Some((rpc_user, rpc_pass)) =
+        credentials.or_else(|| (config.backend == NodeBackend::Rest).then(Default::default))
+    else {
+        bail!("BITCOIN_RPC_USER and BITCOIN_RPC_PASS are needed unless BACKEND is rest");
+    };
+    if config.event_journal_retention == 0 {
+        bail!("EVENT_JOURNAL_RETENTION must keep at least one event");
+    }
+    if config.fee_floor_sat_vb > config.fee_ceiling_sat_vb {
+        bail!(
+            "Fee floor ({} sat/vB) must not exceed fee ceiling ({} sat/vB)",
+            config.fee_floor_sat_vb,
+            config.fee_ceiling_sat_vb
+        );
+    }
+    if !config.fee_cache_ttl.is_zero() && config.fee_cache_ttl < fees::MIN_CACHE_TTL {
+        bail!(
+            "FEE_CACHE_TTL must be 0s or at least {:?}",
+            fees::MIN_CACHE_TTL
+        );
+    }
+    let fee_limits = FeeLimits {
+        floor_sat_vb: config.fee_floor_sat_vb,
+        ceiling_sat_vb: config.fee_ceiling_sat_vb,
+    };
+
+    let labels = match &config.labels_file {
+        Some(path) => labels::load(path)?,
+        None => Labels::new(),
+    };
+    let admin_token = AdminToken(
+        config
+            .admin_token
+            .as_ref()
+            .map(|token| Arc::from(token.expose())),
+    );
+
+    // REST is served on the RPC port, so the RPC URL points at it too
+    let rest_url = match (config.bitcoin_rest_url.take(), config.backend) {
+        (Some(url), _) => Some(url),
+        (None, NodeBackend::Rpc) => None,
+        (None, _) => {
+            let mut url = reqwest::Url::parse(&rpc_url).context("Invalid BITCOIN_RPC_URL")?;
+            url.set_path("/");
+            Some(url)
+        }
+    };
+
+    // RPC goes through its own client, only the node's REST interface is called over HTTP
+    let backends: Vec<&str> = rest_url.iter().filter_map(|url| url.host_str()).collect();
+    let http = OutboundClient::new(&config.outbound, &backends)?;
 
-    let routes = vec![
-        RouteInfo::new("/health", "Useful for health check", get(get_tip_height)),
+    let backend_monitor = if config.backend_nodes.is_empty() {
+        None
+    } else {
+        let mut nodes = Vec::with_capacity(config.backend_nodes.len());
+        for node in &config.backend_nodes {
+            nodes.push((node.name(), node.connect(&rpc_user, &rpc_pass)?));
+        }
+        Some(Arc::new(BackendMonitor::new(nodes, config.backend_max_lag)))
+    };
+    let consensus_monitor = (!config.reference_apis.is_empty()).then(|| {
+        Arc::new(ConsensusMonitor::new(
+            &config.reference_apis,
+            http.clone(),
+            config.reference_max_lag,
+        ))
+    });
+    if config.backend != NodeBackend::Rpc {
+        info!(
+            "Fetching from the node's REST interface ({} backend)",
+            config.backend
+        );
+    }
+    // The rest backend doesn't call the RPC, so there's nothing to fail over
+    let failover = if rpc_nodes.len() > 1 && config.backend != NodeBackend::Rest {
+        let mut nodes = Vec::with_capacity(rpc_nodes.len());
+        for node in &rpc_nodes {
+            let transport = node.pooled(&rpc_user, &rpc_pass, config.rpc_pool_size)?;
+            nodes.push((node.name(), Box::new(transport) as Box<dyn RpcTransport>));
+        }
+        info!(
+            "Failing over between {} RPC nodes, preferring {}, balancing reads by {}, writing to {}",
+            nodes.len(),
+            nodes[0].0,
+            config.rpc_balance,
+            config.rpc_write
+        );
+        Some(Failover::new(
+            nodes,
+            config.rpc_max_lag,
+            config.rpc_balance,
+            &config.rpc_write,
+        )?)
+    } else {
+        None
+    };
+    let node_rpc: Arc<dyn RpcTransport> = match &failover {
+        Some(failover) => failover.transport(),
+        None => Arc::new(rpc_nodes[0].pooled(&rpc_user, &rpc_pass, config.rpc_pool_size)?),
+    };
+    let rpc = Arc::new(Rpc::new(transport::transport(
+        config.backend,
+        node_rpc,
+        http.clone(),
+        rest_url.clone(),
+    )?));
+    let network = chain::node_network(&rpc).await?;
+    filters::check_index(rpc.clone()).await;
+
+    let mut routes = vec![
+        RouteInfo::new(paths::HEALTH, "Useful for health check", get(get_tip_height))
+            .returns(openapi::text),
         RouteInfo::new(
-            "/api/blocks/tip/height",
+            paths::READYZ,
+            "Readiness check, fails only when the node RPC is unreachable.",
+            get(health::get_readyz),
+        ),
+        RouteInfo::new(
+            paths::HEALTH_DETAILS,
+            "Get per-component health (ok, starting, stale, failing) and the overall ok/degraded/down status.",
+            get(health::get_health_details),
+        )
+        .returns(openapi::json::<types::HealthDetails>),
+        RouteInfo::new(
+            paths::CONSENSUS_CHECK,
+            "Compare the node's tip to that of the configured reference APIs, to detect chain splits.",
+            get(consensus::get_consensus_check),
+        )
+        .returns(openapi::json::<types::ConsensusCheck>),
+        RouteInfo::new(
+            paths::FEATURES,
+            "List the routes this deployment serves, with their page sizes and timeout, retry and response size policies.",
+            get(features::get_features),
+        )
+        .returns(openapi::json::<types::Features>),
+        RouteInfo::new(
+            paths::NODE_INFO,
+            "Get the node's version and indexes, and whether confirmed transaction lookups need a block hint.",
+            get(node_info::get_node_info),
+        )
+        .returns(openapi::json::<types::NodeInfo>),
+        RouteInfo::new(
+            paths::TIP_HEIGHT,
             "Get the current blockchain tip height.",
             get(get_tip_height),
-        ),
+        )
+        .returns(openapi::text),
+        RouteInfo::new(
+            paths::TIP_HASH,
+            "Get the current blockchain tip hash.",
+            get(get_tip_hash),
+        )
+        .returns(openapi::text),
         RouteInfo::new(
-            "/api/block-height/{height}",
+            paths::BLOCK_HEIGHT,
             "Get the block hash for a specific height.",
             get(get_block_by_height),
-        ),
+        )
+        .returns(openapi::text),
         RouteInfo::new(
-            "/api/fee-estimates",
+            paths::FEE_ESTIMATES,
             "Get fee estimates for different confirmation targets.",
-            get(get_fee_estimates),
-        ),
+            get(fees::get_fee_estimates),
+        )
+        .returns(openapi::json::<BTreeMap<String, f64>>),
+        RouteInfo::new(
+            paths::FEES_RECOMMENDED,
+            "Get fastest, half hour, hour, economy and minimum fee rates in the mempool.space format.",
+            get(fees::get_recommended_fees),
+        )
+        .returns(openapi::json::<types::RecommendedFees>),
+        RouteInfo::new(
+            paths::MEMPOOL_BLOCKS,
+            "Get the next blocks projected from the mempool, with their fee rates, fees and transaction count, optionally counting only the transactions in the given categories and fee rate band.",
+            get(mempool_blocks::get_mempool_blocks),
+        )
+        .returns(openapi::json::<Vec<types::ProjectedBlock>>)
+        .query("filter", mempool::FILTER)
+        .query("min_fee_rate", mempool::MIN_FEE_RATE)
+        .query("max_fee_rate", mempool::MAX_FEE_RATE),
+        RouteInfo::new(
+            paths::FEE_ACCURACY,
+            "Get hit rate and error of past fee estimates per estimator mode and target.",
+            get(fee_accuracy::get_fee_accuracy),
+        )
+        .returns(openapi::json::<Vec<types::FeeAccuracy>>),
+        RouteInfo::new(
+            paths::BLOCK_UTILIZATION,
+            "Get weight utilization and segwit/taproot transaction share per block over a period (24h, 3d, 1w, ...).",
+            get(stats::get_block_utilization),
+        )
+        .returns(openapi::json::<Vec<types::BlockUtilization>>),
+        RouteInfo::new(
+            paths::SCRIPT_TYPES,
+            "Get created output counts by script type per day over a period.",
+            get(stats::get_script_types),
+        )
+        .returns(openapi::json::<Vec<types::DailyScriptTypes>>),
+        RouteInfo::post(
+            paths::BROADCAST,
+            "Broadcast a hex encoded raw transaction, returning its txid.",
+            post(tx::post_tx),
+        )
+        .accepts(openapi::text)
+        .returns(openapi::text),
+        RouteInfo::post(
+            paths::TX_ESTIMATE_SIZE,
+            "Estimate the weight and vsize of a transaction from its input and output types.",
+            post(tx_size::post_estimate_size),
+        )
+        .accepts(openapi::json::<tx_size::EstimateSizeRequest>)
+        .returns(openapi::json::<types::SizeEstimate>),
+        RouteInfo::post(
+            paths::VERIFY_MESSAGE,
+            "Verify a BIP137 or BIP322 message signature by an address.",
+            post(message::post_verify_message),
+        )
+        .accepts(openapi::json::<message::VerifyMessageRequest>)
+        .returns(openapi::json::<types::MessageVerification>),
+        RouteInfo::new(
+            paths::TX,
+            "Get a transaction in the esplora format, with prevouts, fee and confirmation status.",
+            get(tx::get_tx),
+        )
+        .returns(openapi::json::<types::Tx>)
+        .query("block", tx::BLOCK_HINT),
+        RouteInfo::new(
+            paths::TX_STATUS,
+            "Get the confirmation status of a transaction.",
+            get(tx::get_tx_status),
+        )
+        .returns(openapi::json::<types::TxStatus>)
+        .query("block", tx::BLOCK_HINT),
+        RouteInfo::new(
+            paths::TX_HEX,
+            "Get the raw transaction as hex.",
+            get(tx::get_tx_hex),
+        )
+        .returns(openapi::text)
+        .query("block", tx::BLOCK_HINT),
+        RouteInfo::new(
+            paths::TX_RAW,
+            "Get the raw transaction as binary.",
+            get(tx::get_tx_raw),
+        )
+        .returns(openapi::binary)
+        .query("block", tx::BLOCK_HINT),
+        RouteInfo::new(
+            paths::TX_MERKLE_PROOF,
+            "Get the merkle branch of a confirmed transaction in the Electrum format.",
+            get(merkle::get_merkle_proof),
+        )
+        .returns(openapi::json::<types::MerkleProof>)
+        .query("block", tx::BLOCK_HINT),
+        RouteInfo::new(
+            paths::TX_MERKLEBLOCK_PROOF,
+            "Get the BIP37 merkleblock proving a confirmed transaction, as hex.",
+            get(merkle::get_merkleblock_proof),
+        )
+        .returns(openapi::text)
+        .query("block", tx::BLOCK_HINT),
+        RouteInfo::post(
+            paths::WATCH_OUTPOINT,
+            "Register a webhook notified when an outpoint is spent in the mempool and in a block.",
+            post(watch::post_watch_outpoint),
+        )
+        .accepts(openapi::json::<watch::WatchRequest>)
+        .returns(openapi::json::<watch::WatchCreated>)
+        .admin(),
+        RouteInfo::new(
+            paths::WATCH_OUTPOINT,
+            "List outpoint watches.",
+            get(watch::get_watches),
+        )
+        .returns(openapi::json::<Vec<types::OutpointWatch>>)
+        .admin(),
+        RouteInfo::post(
+            paths::WEBHOOKS,
+            "Subscribe a webhook to block, reorg or address events.",
+            post(webhooks::post_webhook),
+        )
+        .accepts(openapi::json::<webhooks::SubscribeRequest>)
+        .returns(openapi::json::<webhooks::SubscriptionCreated>)
+        .admin(),
+        RouteInfo::new(
+            paths::WEBHOOKS,
+            "List webhook subscriptions.",
+            get(webhooks::get_webhooks),
+        )
+        .returns(openapi::json::<Vec<types::Webhook>>)
+        .admin(),
+        RouteInfo::delete(
+            paths::WEBHOOK,
+            "Remove a webhook subscription.",
+            delete(webhooks::delete_webhook),
+        )
+        .admin(),
+        RouteInfo::new(
+            paths::TX_CONFLICTS,
+            "List transactions spending the same inputs as a mempool transaction.",
+            get(mempool::get_tx_conflicts),
+        )
+        .returns(openapi::json::<types::Conflicts>),
+        RouteInfo::new(
+            paths::PROPAGATION,
+            "Get the delay between block header timestamps and their local arrival.",
+            get(propagation::get_propagation),
+        )
+        .returns(openapi::json::<types::Propagation>),
+        RouteInfo::new(
+            paths::MEMPOOL_SUMMARY,
+            "Get mempool transaction count, total vsize and fees, and the fee rate histogram.",
+            get(mempool::get_mempool_summary),
+        )
+        .returns(openapi::json::<types::MempoolSummary>),
+        RouteInfo::new(
+            paths::MEMPOOL_RECENT,
+            "Get the 10 transactions that entered the mempool last, with fee, vsize and value, optionally only those in the given categories and fee rate band.",
+            get(mempool::get_mempool_recent),
+        )
+        .returns(openapi::json::<Vec<types::RecentTx>>)
+        .query("filter", mempool::FILTER)
+        .query("min_fee_rate", mempool::MIN_FEE_RATE)
+        .query("max_fee_rate", mempool::MAX_FEE_RATE),
+        RouteInfo::new(
+            paths::MEMPOOL_TXIDS,
+            "Get the txids in the node's mempool, optionally at most `?limit=` of them.",
+            get(mempool::get_mempool_txids),
+        )
+        .returns(openapi::json::<Vec<String>>)
+        .query("limit", "Most txids to return"),
+        RouteInfo::new(
+            paths::MEMPOOL_DIFF,
+            "Get mempool txids added and removed since a sequence number (`?since=`), or all of them without one.",
+            get(mempool::get_mempool_diff),
+        )
+        .returns(openapi::json::<types::MempoolDiff>)
+        .query("since", "Sequence number of a previous diff"),
+        RouteInfo::new(
+            paths::MEMPOOL_MIN_FEE,
+            "Get the node's mempool minimum and relay fee rates, and the minimum over the last day.",
+            get(min_fee::get_min_fee),
+        )
+        .returns(openapi::json::<types::MinFee>),
+        RouteInfo::new(
+            paths::MEMPOOL,
+            "List mempool transactions in the given categories and fee rate band, with their count.",
+            get(mempool::get_mempool),
+        )
+        .returns(openapi::json::<types::FilteredMempool>)
+        .query("filter", mempool::FILTER)
+        .query("min_fee_rate", mempool::MIN_FEE_RATE)
+        .query("max_fee_rate", mempool::MAX_FEE_RATE),
         RouteInfo::new(
-            "/api/block/{hash}/raw",
+            paths::LABELS,
+            "List operator-provided address labels.",
+            get(labels::get_labels),
+        )
+        .returns(openapi::json::<BTreeMap<String, String>>)
+        .admin(),
+        RouteInfo::new(
+            paths::ADMIN_CONFIG,
+            "Summarize the settings, features, listeners and node minipool runs with, secrets redacted.",
+            get(summary::get_config),
+        )
+        .returns(openapi::json::<types::ConfigSummary>)
+        .admin(),
+        RouteInfo::new(
+            paths::ADMIN_CONFIG_V1,
+            "Same as /admin/config.",
+            get(summary::get_config),
+        )
+        .returns(openapi::json::<types::ConfigSummary>)
+        .admin(),
+        RouteInfo::new(
+            paths::ADMIN_BROADCASTS,
+            "Page through the audit log of transaction broadcasts, newest first.",
+            get(audit::get_broadcasts),
+        )
+        .returns(openapi::json::<types::BroadcastAuditPage>)
+        .query("before", audit::BEFORE)
+        .query("txid", audit::TXID)
+        .admin(),
+        RouteInfo::new(
+            paths::ADMIN_BANNED,
+            "List the node's banned addresses and subnets.",
+            get(peers::get_banned),
+        )
+        .returns(openapi::json::<Vec<types::Ban>>)
+        .admin(),
+        RouteInfo::new(
+            paths::BACKENDS_CONSISTENCY,
+            "Compare the tip and block template of every backend node to the primary node's.",
+            get(backends::get_consistency),
+        )
+        .returns(openapi::json::<types::BackendConsistency>)
+        .admin(),
+        RouteInfo::post(
+            paths::ADMIN_BAN,
+            "Ban an address or subnet from connecting to the node.",
+            post(peers::post_ban),
+        )
+        .accepts(openapi::json::<peers::BanRequest>)
+        .admin(),
+        RouteInfo::post(
+            paths::ADMIN_UNBAN,
+            "Lift the node's ban of an address or subnet.",
+            post(peers::post_unban),
+        )
+        .accepts(openapi::json::<peers::UnbanRequest>)
+        .admin(),
+        RouteInfo::post(
+            paths::ADMIN_DISCONNECT,
+            "Disconnect a peer of the node by address or node id.",
+            post(peers::post_disconnect),
+        )
+        .accepts(openapi::json::<peers::DisconnectRequest>)
+        .admin(),
+        RouteInfo::new(
+            paths::BLOCK,
+            "Get a block summary in the esplora format.",
+            get(blocks::get_block),
+        )
+        .returns(openapi::json::<types::Block>),
+        RouteInfo::new(
+            paths::BLOCKS,
+            "Get the 10 most recent blocks in the esplora format.",
+            get(blocks::get_blocks),
+        )
+        .page_size(blocks::ESPLORA_BLOCKS_PER_PAGE)
+        .returns(openapi::json::<Vec<types::Block>>),
+        RouteInfo::new(
+            paths::BLOCKS_FROM,
+            "Get 10 blocks in the esplora format, descending from a height.",
+            get(blocks::get_blocks_from),
+        )
+        .page_size(blocks::ESPLORA_BLOCKS_PER_PAGE)
+        .returns(openapi::json::<Vec<types::Block>>),
+        RouteInfo::new(
+            paths::BLOCK_HEADER,
+            "Get the serialized 80-byte block header as hex.",
+            get(blocks::get_block_header),
+        )
+        .returns(openapi::text),
+        RouteInfo::new(
+            paths::BLOCK_STATUS,
+            "Get whether a block is on the active chain, with its height and successor.",
+            get(blocks::get_block_status),
+        )
+        .returns(openapi::json::<types::BlockStatus>),
+        RouteInfo::new(
+            paths::BLOCK_TXIDS,
+            "Get the txids of a block, in block order.",
+            get(blocks::get_block_txids),
+        )
+        .returns(openapi::json::<Vec<String>>),
+        RouteInfo::new(
+            paths::BLOCK_TXS,
+            "Get 25 transactions of a block in the esplora format, from an index that is a multiple of 25.",
+            get(blocks::get_block_txs),
+        )
+        .page_size(blocks::TXS_PER_PAGE)
+        .returns(openapi::json::<Vec<types::Tx>>)
+        .with_policy(RoutePolicy::new(Duration::from_secs(30), 0)),
+        RouteInfo::new(
+            paths::BLOCK_RAW,
             "Get the raw block data for a specific block hash.",
             get(get_block_raw),
-        ),
+        )
+        .returns(openapi::binary)
+        .with_policy(RoutePolicy::new(Duration::from_secs(30), 0)),
+        RouteInfo::new(
+            paths::BLOCK_FILTER,
+            "Get the BIP158 basic filter of a block and its filter header.",
+            get(filters::get_block_filter),
+        )
+        .returns(openapi::json::<types::BlockFilter>),
+        RouteInfo::new(
+            paths::FILTER_HEADERS,
+            "Get up to 2000 block filter headers from a height (`?count=`).",
+            get(filters::get_filter_headers),
+        )
+        .page_size(filters::MAX_FILTER_HEADERS as usize)
+        .returns(openapi::json::<types::FilterHeaders>)
+        .query("count", "Number of filter headers, at most 2000")
+        .with_policy(RoutePolicy::new(Duration::from_secs(30), 0)),
+        RouteInfo::new(
+            paths::V1_BLOCKS,
+            "Get the 15 most recent blocks with fee statistics and mining pool.",
+            get(blocks::get_v1_blocks),
+        )
+        .page_size(blocks::BLOCKS_PER_PAGE as usize)
+        .returns(openapi::json::<Vec<types::ExtendedBlock>>)
+        .with_policy(RoutePolicy::new(Duration::from_secs(30), 1)),
+        RouteInfo::new(
+            paths::V1_BLOCKS_FROM,
+            "Get 15 blocks with fee statistics and mining pool, descending from a height.",
+            get(blocks::get_v1_blocks_from),
+        )
+        .page_size(blocks::BLOCKS_PER_PAGE as usize)
+        .returns(openapi::json::<Vec<types::ExtendedBlock>>)
+        .with_policy(RoutePolicy::new(Duration::from_secs(30), 1)),
+        RouteInfo::new(
+            paths::BLOCK_FEE_HISTOGRAM,
+            "Get the fee rate histogram of a block's transactions, by hash or height.",
+            get(blocks::get_block_fee_histogram),
+        )
+        .returns(openapi::json::<Vec<types::FeeBucket>>)
+        .with_policy(RoutePolicy::new(Duration::from_secs(30), 0)),
+        RouteInfo::new(
+            paths::DIFFICULTY_ADJUSTMENT,
+            "Get progress through the retarget period and the estimated difficulty change.",
+            get(difficulty::get_difficulty_adjustment),
+        )
+        .returns(openapi::json::<types::DifficultyAdjustment>),
+        RouteInfo::new(
+            paths::MINING_HASHRATE,
+            "Get network hashrate and difficulty over a period (24h, 3d, 1w, ...), and the current hashrate over several windows.",
+            get(mining::get_hashrate),
+        )
+        .returns(openapi::json::<types::Hashrates>)
+        .with_policy(RoutePolicy::new(Duration::from_secs(30), 0)),
     ];
 
+    let spends = if config.spend_index {
+        let Some(data_dir) = &config.data_dir else {
+            bail!("The spend index needs a data directory (DATA_DIR)");
+        };
+        std::fs::create_dir_all(data_dir)?;
+        let index = SpendIndex::open(
+            config.storage_backend,
+            &config.storage_backend.path(data_dir, "spends"),
+            config.spend_index_start_height,
+            !config.no_migrate,
+        )?;
+        routes.extend([
+            RouteInfo::new(
+                paths::TX_OUTSPEND,
+                "Get the transaction input spending an output, confirmed or in the mempool.",
+                get(spends::get_outspend),
+            )
+            .returns(openapi::json::<types::Outspend>),
+            RouteInfo::new(
+                paths::TX_OUTSPENDS,
+                "Get the spending status of every output of a transaction.",
+                get(spends::get_outspends),
+            )
+            .returns(openapi::json::<Vec<types::Outspend>>)
+            .query("block", tx::BLOCK_HINT),
+        ]);
+        Some(Arc::new(index))
+    } else {
+        None
+    };
+
+    let addresses = if config.address_index {
+        let Some(data_dir) = &config.data_dir else {
+            bail!("The address index needs a data directory (DATA_DIR)");
+        };
+        std::fs::create_dir_all(data_dir)?;
+        let index = AddressIndex::open(
+            config.storage_backend,
+            &config.storage_backend.path(data_dir, "addresses"),
+            config.address_index_start_height,
+            !config.no_migrate,
+        )?;
+        routes.extend([
+            RouteInfo::new(
+                paths::ADDRESS,
+                "Get confirmed and mempool funding and spending totals of an address.",
+                get(addresses::get_address),
+            )
+            .returns(openapi::json::<types::AddressSummary>),
+            RouteInfo::new(
+                paths::ADDRESS_TXS,
+                "Get up to 50 mempool and the 25 newest confirmed transactions of an address.",
+                get(addresses::get_address_txs),
+            )
+            .returns(openapi::json::<Vec<types::Tx>>)
+            .with_policy(RoutePolicy::new(Duration::from_secs(30), 0)),
+            RouteInfo::new(
+                paths::ADDRESS_CHAIN_TXS,
+                "Get the 25 newest confirmed transactions of an address.",
+                get(addresses::get_address_chain_txs),
+            )
+            .page_size(addresses::CHAIN_TXS_PER_PAGE)
+            .returns(openapi::json::<Vec<types::Tx>>)
+            .with_policy(RoutePolicy::new(Duration::from_secs(30), 0)),
+            RouteInfo::new(
+                paths::ADDRESS_CHAIN_TXS_AFTER,
+                "Get the next 25 confirmed transactions of an address, older than a txid.",
+                get(addresses::get_address_chain_txs_after),
+            )
+            .page_size(addresses::CHAIN_TXS_PER_PAGE)
+            .returns(openapi::json::<Vec<types::Tx>>)
+            .with_policy(RoutePolicy::new(Duration::from_secs(30), 0)),
+            RouteInfo::new(
+                paths::ADDRESS_MEMPOOL_TXS,
+                "Get up to 50 mempool transactions of an address, newest first.",
+                get(addresses::get_address_mempool_txs),
+            )
+            .page_size(addresses::MEMPOOL_TXS_LIMIT)
+            .returns(openapi::json::<Vec<types::Tx>>),
+            RouteInfo::post(
+                paths::ADDRESSES_ACTIVITY,
+                "Get new confirmed and mempool transactions of up to 100 addresses and scripthashes since a cursor.",
+                post(addresses::post_addresses_activity),
+            )
+            .accepts(openapi::json::<addresses::ActivityRequest>)
+            .returns(openapi::json::<types::AddressActivity>)
+            .with_policy(RoutePolicy::new(Duration::from_secs(30), 0)),
+            RouteInfo::post(
+                paths::COIN_SELECT,
+                "Suggest inputs among the unspent outputs of up to 100 addresses for paying an amount at a fee rate.",
+                post(coin_select::post_coin_select),
+            )
+            .accepts(openapi::json::<coin_select::CoinSelectRequest>)
+            .returns(openapi::json::<types::CoinSelection>)
+            .with_policy(RoutePolicy::new(Duration::from_secs(30), 0)),
+            RouteInfo::new(
+                paths::SCRIPTHASH,
+                "Get confirmed and mempool funding and spending totals of a script by its SHA256.",
+                get(addresses::get_scripthash),
+            )
+            .returns(openapi::json::<types::AddressSummary>),
+            RouteInfo::new(
+                paths::SCRIPTHASH_TXS,
+                "Get up to 50 mempool and the 25 newest confirmed transactions of a script.",
+                get(addresses::get_scripthash_txs),
+            )
+            .returns(openapi::json::<Vec<types::Tx>>)
+            .with_policy(RoutePolicy::new(Duration::from_secs(30), 0)),
+            RouteInfo::new(
+                paths::SCRIPTHASH_CHAIN_TXS,
+                "Get the 25 newest confirmed transactions of a script.",
+                get(addresses::get_scripthash_chain_txs),
+            )
+            .page_size(addresses::CHAIN_TXS_PER_PAGE)
+            .returns(openapi::json::<Vec<types::Tx>>)
+            .with_policy(RoutePolicy::new(Duration::from_secs(30), 0)),
+            RouteInfo::new(
+                paths::SCRIPTHASH_CHAIN_TXS_AFTER,
+                "Get the next 25 confirmed transactions of a script, older than a txid.",
+                get(addresses::get_scripthash_chain_txs_after),
+            )
+            .page_size(addresses::CHAIN_TXS_PER_PAGE)
+            .returns(openapi::json::<Vec<types::Tx>>)
+            .with_policy(RoutePolicy::new(Duration::from_secs(30), 0)),
+            RouteInfo::new(
+                paths::SCRIPTHASH_MEMPOOL_TXS,
+                "Get up to 50 mempool transactions of a script, newest first.",
+                get(addresses::get_scripthash_mempool_txs),
+            )
+            .page_size(addresses::MEMPOOL_TXS_LIMIT)
+            .returns(openapi::json::<Vec<types::Tx>>),
+            RouteInfo::new(
+                paths::SCRIPTHASH_UTXO,
+                "Get the unspent outputs of a script, confirmed and in the mempool, newest first.",
+                get(addresses::get_scripthash_utxos),
+            )
+            .returns(openapi::json::<Vec<types::Utxo>>)
+            .with_policy(RoutePolicy::new(Duration::from_secs(30), 0)),
+        ]);
+        Some(Arc::new(index))
+    } else {
+        None
+    };
+    if addresses.is_some() || config.utxo_scan {
+        routes.push(
+            RouteInfo::new(
+                paths::ADDRESS_UTXO,
+                "Get the unspent outputs of an address, confirmed and in the mempool, newest first.",
+                get(addresses::get_address_utxos),
+            )
+            .returns(openapi::json::<Vec<types::Utxo>>)
+            .with_policy(RoutePolicy::new(Duration::from_secs(120), 0)),
+        );
+    }
+
+    let journal = Arc::new(match &config.data_dir {
+        Some(data_dir) if !config.live_update_interval.is_zero() => {
+            std::fs::create_dir_all(data_dir)?;
+            EventJournal::open(
+                config.storage_backend,
+                &config.storage_backend.path(data_dir, "events"),
+                config.event_journal_retention,
+                !config.no_migrate,
+            )?
+        }
+        _ => EventJournal::in_memory(config.event_journal_retention),
+    });
+    if !config.live_update_interval.is_zero() {
+        routes.push(
+            RouteInfo::new(
+                paths::LIVE,
+                "WebSocket pushing new blocks, mempool stats and projected blocks in the mempool.space format.",
+                get(live::get_ws),
+            )
+            .query("since", live::SINCE)
+            .with_policy(RoutePolicy::new(Duration::from_secs(10), 0)),
+        );
+        routes.push(RouteInfo::new(
+            paths::EVENTS,
+            "Server-Sent Events stream of new blocks, reorgs and recommended fee changes.",
+            get(events::get_events),
+        ));
+        routes.push(
+            RouteInfo::new(
+                paths::EVENT_JOURNAL,
+                "Get the journaled events after a sequence number, to catch up after downtime.",
+                get(journal::get_journal),
+            )
+            .returns(openapi::json::<types::EventJournal>)
+            .query("since", journal::SINCE),
+        );
+    }
+
+    #[cfg(feature = "graphql")]
+    routes.push(RouteInfo::new(
+        paths::GRAPHQL,
+        "GraphQL queries of blocks with their transactions and of transaction batches; GraphiQL on GET.",
+        get(graphql::get_graphiql).post(graphql::post_graphql),
+    ));
+
+    if let Some(compat) = config.compat {
+        info!("Serving the {} API surface", compat);
+        routes.extend(compat::routes(compat));
+    }
+
+    #[cfg(feature = "ctv")]
+    routes.extend(ctv::routes());
+
+    #[cfg(feature = "regtest")]
+    if regtest::is_regtest(network) {
+        routes.extend(regtest::routes());
+    }
+
+    for route_policy in &config.route_policies {
+        match routes
+            .iter_mut()
+            .find(|route| route.path == route_policy.path)
+        {
+            Some(route) => route.policy = route_policy.policy,
+            None => bail!("Unknown route in route policy: {}", route_policy.path),
+        }
+    }
+    for response_limit in &config.response_limits {
+        match routes
+            .iter_mut()
+            .find(|route| route.path == response_limit.path)
+        {
+            Some(route) => route.response_limit = Some(response_limit.max_bytes),
+            None => bail!("Unknown route in response limit: {}", response_limit.path),
+        }
+    }
+    for route_rate in &config.trace_sample_routes {
+        if !routes.iter().any(|route| route.path == route_rate.path) {
+            bail!("Unknown route in trace sample rate: {}", route_rate.path);
+        }
+    }
+    let sampler = Arc::new(TraceSampler::new(
+        config.trace_sample_rate,
+        &config.trace_sample_routes,
+    ));
+
+    let health = Arc::new(Health::default());
+    health.register(health::RPC, Severity::Hard, None);
+    let watcher = Arc::new(ChainWatcher::new());
+    let fee_accuracy = Arc::new(FeeAccuracyTracker::default());
+    tokio::spawn(
+        fee_accuracy
+            .clone()
+            .run(rpc.clone(), watcher.clone(), health.clone()),
+    );
+    let block_stats = Arc::new(match &config.data_dir {
+        Some(data_dir) if config.stats_retention_blocks > 0 => {
+            std::fs::create_dir_all(data_dir)?;
+            BlockStatsPipeline::open(
+                config.storage_backend,
+                &config.storage_backend.path(data_dir, "block_stats"),
+                config.stats_retention_blocks,
+                config.large_witness_bytes,
+                !config.no_migrate,
+            )?
+        }
+        _ => {
+            BlockStatsPipeline::in_memory(config.stats_retention_blocks, config.large_witness_bytes)
+        }
+    });
+    if config.stats_retention_blocks > 0 {
+        tokio::spawn(
+            block_stats
+                .clone()
+                .run(rpc.clone(), watcher.clone(), health.clone()),
+        );
+    }
+    let audit = Arc::new(match &config.data_dir {
+        Some(data_dir) => {
+            std::fs::create_dir_all(data_dir)?;
+            BroadcastAudit::open(
+                config.storage_backend,
+                &config.storage_backend.path(data_dir, "broadcasts"),
+                !config.no_migrate,
+            )?
+        }
+        None => BroadcastAudit::in_memory(),
+    });
+    let first_seen = match &config.data_dir {
+        Some(data_dir) => {
+            std::fs::create_dir_all(data_dir)?;
+            Some(FirstSeenStore::open(&data_dir.join("first_seen.redb"))?)
+        }
+        None => None,
+    };
+    let hooks = Arc::new(Hooks::load(&config.hook_scripts, http.clone())?);
+    if hooks.handles(Event::Block) {
+        tokio::spawn(hooks.clone().run(watcher.clone()));
+    }
+    let registry = match &config.data_dir {
+        Some(data_dir) => {
+            std::fs::create_dir_all(data_dir)?;
+            Some(Arc::new(WatchRegistry::open(
+                config.storage_backend,
+                &config.storage_backend.path(data_dir, "watches"),
+                !config.no_migrate,
+            )?))
+        }
+        None => None,
+    };
+    let (watches, notifications) = OutpointWatches::new(registry.clone())?;
+    let watches = Arc::new(watches);
+    tokio::spawn(watch::run_notifier(
+        notifications,
+        http.clone(),
+        hooks.clone(),
+    ));
+    tokio::spawn(watches.clone().run(rpc.clone(), watcher.clone()));
+    let (webhooks, deliveries) = Webhooks::new(&config.webhooks, registry)?;
+    let webhooks = Arc::new(webhooks);
+    tokio::spawn(webhooks::run_deliverer(
+        deliveries,
+        http.clone(),
+        config
+            .webhook_secret
+            .as_ref()
+            .map(|secret| Arc::from(secret.expose())),
+        config.webhook_max_attempts.max(1),
+    ));
+    tokio::spawn(webhooks.clone().run(rpc.clone(), watcher.clone()));
+    let mempool = Arc::new(MempoolTracker::new(
+        config.large_witness_bytes,
+        first_seen,
+        watches.clone(),
+        webhooks.clone(),
+        addresses.is_some(),
+    ));
+    if !config.mempool_poll_interval.is_zero() {
+        tokio::spawn(mempool.clone().run(
+            rpc.clone(),
+            config.mempool_poll_interval,
+            health.clone(),
+        ));
+    }
+    let mempool_blocks = Arc::new(MempoolProjection::default());
+    if !config.mempool_blocks_interval.is_zero() {
+        tokio::spawn(mempool_blocks.clone().run(
+            rpc.clone(),
+            config.mempool_blocks_interval,
+            health.clone(),
+        ));
+    }
+    let min_fee = Arc::new(MinFeeTracker::default());
+    tokio::spawn(min_fee.clone().run(rpc.clone(), health.clone()));
+    let fee_cache = Arc::new(FeeCache::new(config.fee_cache_ttl));
+    if !config.fee_cache_ttl.is_zero() {
+        tokio::spawn(
+            fee_cache
+                .clone()
+                .run(rpc.clone(), fee_limits, health.clone()),
+        );
+    }
+    let propagation = Arc::new(PropagationTracker::default());
+    tokio::spawn(propagation.clone().run(rpc.clone(), watcher.clone()));
+    if let Some(index) = &spends {
+        tokio::spawn(
+            index
+                .clone()
+                .run(rpc.clone(), watcher.clone(), health.clone()),
+        );
+    }
+    if let Some(index) = &addresses {
+        tokio::spawn(
+            index
+                .clone()
+                .run(rpc.clone(), watcher.clone(), health.clone()),
+        );
+    }
+    let headers = config
+        .verify_headers
+        .then(|| Arc::new(HeaderChain::new(network)));
+    if let Some(chain) = &headers {
+        tokio::spawn(
+            chain
+                .clone()
+                .run(rpc.clone(), watcher.clone(), health.clone()),
+        );
+    }
+    let checkpoint_guard = config
+        .checkpoints
+        .map(|checkpoints| Arc::new(CheckpointGuard::new(checkpoints)));
+    if let Some(guard) = &checkpoint_guard {
+        tokio::spawn(
+            guard
+                .clone()
+                .run(rpc.clone(), watcher.clone(), health.clone()),
+        );
+    }
+    if let Some(failover) = failover {
+        tokio::spawn(failover.run(config.rpc_check_interval, health.clone()));
+    }
+    if let Some(monitor) = &backend_monitor {
+        tokio::spawn(monitor.clone().run(
+            rpc.clone(),
+            config.backend_check_interval,
+            health.clone(),
+        ));
+    }
+    if let Some(monitor) = &consensus_monitor {
+        tokio::spawn(monitor.clone().run(
+            rpc.clone(),
+            config.reference_check_interval,
+            health.clone(),
+        ));
+    }
+    let block_summaries = Arc::new(BoundedCache::new(64));
+    let live = Arc::new(LiveHub::new());
+    if !config.live_update_interval.is_zero() {
+        tokio::spawn(live.clone().run(
+            rpc.clone(),
+            watcher.clone(),
+            block_summaries.clone(),
+            mempool_blocks.clone(),
+            fee_limits,
+            config.live_update_interval,
+        ));
+    }
+    let events = Arc::new(EventStream::new(journal.clone()));
+    if !config.live_update_interval.is_zero() {
+        tokio::spawn(events.clone().run(
+            rpc.clone(),
+            watcher.clone(),
+            fee_limits,
+            config.live_update_interval,
+            config.events_fee_change,
+        ));
+    }
+    if let Some(endpoint) = config.zmq_block.clone() {
+        tokio::spawn(zmq::run(endpoint, "rawblock", watcher.waker()));
+    }
+    if let Some(endpoint) = config.zmq_tx.clone() {
+        tokio::spawn(zmq::run(endpoint, "rawtx", mempool.waker()));
+    }
+    let fee_histograms = Arc::new(BoundedCache::new(64));
+    let extended_blocks = Arc::new(BoundedCache::new(64));
+    let hashrate_samples = Arc::new(BoundedCache::new(4096));
+    tokio::spawn(cache::evict_reorged(
+        watcher.subscribe(),
+        vec![
+            fee_histograms.clone(),
+            block_summaries.clone(),
+            extended_blocks.clone(),
+            hashrate_samples.clone(),
+            fee_cache.clone(),
+        ],
+    ));
+    tokio::spawn(
+        watcher
+            .clone()
+            .run(rpc.clone(), config.chain_poll_interval, health.clone()),
+    );
+
+    let warmup = (!config.warmup_duration.is_zero()).then(|| {
+        Arc::new(Warmup::new(
+            config.warmup_duration,
+            config.warmup_initial_rps,
+            config.warmup_target_rps,
+        ))
+    });
     let state = AppState {
-        rpc: Arc::new(rpc),
-        routes: Arc::new(routes.clone()),
+        rpc,
+        network,
+        openapi: Arc::new(openapi::spec(&routes)),
+        features: Arc::new(Features::new(&routes, admin_token.clone(), warmup.clone())),
+        fee_limits,
+        fee_cache,
+        fee_accuracy: fee_accuracy.clone(),
+        labels: Arc::new(labels),
+        fee_histograms,
+        block_summaries,
+        extended_blocks,
+        hashrate_samples,
+        block_stats: block_stats.clone(),
+        mempool,
+        config_summary,
+        mempool_blocks,
+        min_fee,
+        health,
+        spends,
+        addresses,
+        utxo_scan: config.utxo_scan,
+        propagation,
+        watches,
+        webhooks,
+        hooks,
+        headers,
+        rest: rest_url.map(|base| Arc::new(NodeRest::new(http.clone(), base))),
+        live,
+        events,
+        journal,
+        audit,
+        backends: backend_monitor,
+        consensus: consensus_monitor,
+        compat: config.compat,
     };
 
-    let mut app = Router::new().route("/", get(index));
+    if let Some(addr) = config.grpc_listen {
+        #[cfg(feature = "grpc")]
+        grpc::serve(addr, state.clone(), watcher.clone()).await?;
+        #[cfg(not(feature = "grpc"))]
+        bail!(
+            "GRPC_LISTEN is set to {} but minipool was built without the `grpc` feature",
+            addr
+        );
+    }
+    electrum::serve(config.electrum_listeners, state.clone(), watcher).await?;
+
+    let mut app = Router::new()
+        .route("/", get(index))
+        .route("/docs", get(openapi::get_docs))
+        .route("/openapi.json", get(openapi::get_openapi))
+        .route("/static/{file}", get(assets::serve));
 
     // Add all routes from the routes vec
     for route in routes {
-        app = app.route(route.path, route.handler);
+        let policy = middleware::from_fn_with_state(route.policy, policy::apply_policy);
+        let mut handler = route.handler;
+        if let Some(max_bytes) = route.response_limit {
+            handler = handler.layer(middleware::from_fn_with_state(
+                max_bytes,
+                limits::limit_response,
+            ));
+        }
+        let mut handler = handler.layer(policy);
+        if route.admin {
+            handler = handler.layer(middleware::from_fn_with_state(
+                admin_token.clone(),
+                admin::require_admin,
+            ));
+        }
+        app = app.route(route.path, handler);
+    }
+
+    if let Some(shadow_url) = config.shadow_url {
+        info!(
+            "Mirroring {}% of read requests to {}",
+            config.shadow_sample_rate * 100.0,
+            shadow_url
+        );
+        let shadow = Arc::new(Shadow::new(
+            shadow_url,
+            config.shadow_sample_rate,
+            http.clone(),
+        ));
+        app = app.route_layer(middleware::from_fn_with_state(
+            shadow,
+            shadow::shadow_requests,
+        ));
+    }
+
+    if let Some(warmup) = warmup {
+        app = app.route_layer(middleware::from_fn_with_state(
+            warmup,
+            warmup::limit_during_warmup,
+        ));
+    }
+
+    if let Some(guard) = checkpoint_guard {
+        app = app.route_layer(middleware::from_fn_with_state(
+            guard,
+            checkpoints::require_trusted_chain,
+        ));
     }
 
     let app = app
         .fallback(fallback)
-        .layer(TraceLayer::new_for_http())
-        .route_layer(middleware::from_fn(track_metrics))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(sampling::make_span)
+                .on_response(sampling::log_response),
+        )
+        .layer(middleware::from_fn_with_state(
+            sampler,
+            trace_context::propagate,
+        ))
+        .layer(middleware::from_fn(track_metrics))
         .with_state(state);
 
-    let listener = tokio::net::TcpListener::bind(config.bind_addr).await?;
-    info!("Listening on {}", config.bind_addr);
-    axum::serve(listener, app).await?;
-
-    Ok(())
+    listeners::serve(config.listeners, app).await
 }
 
-async fn get_tip_height(State(state): State<AppState>) -> impl IntoResponse {
-    let rpc = state.rpc.clone();
-    match tokio::task::spawn_blocking(move || rpc.get_block_count()).await {
-        Ok(Ok(height)) => (StatusCode::OK, height.to_string()).into_response(),
-        Ok(Err(e)) => {
-            warn!("Failed to get block count from RPC: {}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, "RPC error").into_response()
+/// Serves the esplora routes from `upstream` without a node, see [`proxy`]
+async fn start_proxy_server(config: Config, upstream: reqwest::Url) -> Result<()> {
+    let http = OutboundClient::new(&config.outbound, &[upstream.host_str().unwrap_or_default()])?;
+    info!("Proxying esplora routes to {}", upstream);
+    let proxy = Arc::new(Proxy::new(
+        upstream,
+        http,
+        config.proxy_cache_entries,
+        config.proxy_cache_ttl,
+    ));
+    tokio::spawn(proxy.clone().run(config.chain_poll_interval));
+
+    let mut routes: Vec<(&str, MethodRouter<Arc<Proxy>>)> = proxy::ROUTES
+        .iter()
+        .map(|route| (route.path, proxy::handler(*route)))
+        .collect();
+    routes.push((paths::BROADCAST, proxy::broadcast_handler()));
+    for route_policy in &config.route_policies {
+        if !routes.iter().any(|(path, _)| *path == route_policy.path) {
+            bail!("Unknown route in route policy: {}", route_policy.path);
         }
-        Err(e) => {
-            warn!("Task failed when getting block count: {}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, "RPC error").into_response()
+    }
+    for route_rate in &config.trace_sample_routes {
+        if !routes.iter().any(|(path, _)| *path == route_rate.path) {
+            bail!("Unknown route in trace sample rate: {}", route_rate.path);
         }
     }
+    let sampler = Arc::new(TraceSampler::new(
+        config.trace_sample_rate,
+        &config.trace_sample_routes,
+    ));
+
+    let mut app = Router::new();
+    for (path, handler) in routes {
+        let policy = config
+            .route_policies
+            .iter()
+            .find(|route_policy| route_policy.path == path)
+            .map_or_else(RoutePolicy::default, |route_policy| route_policy.policy);
+        app = app.route(
+            path,
+            handler.layer(middleware::from_fn_with_state(policy, policy::apply_policy)),
+        );
+    }
+    let app = app
+        .fallback(|| async { (StatusCode::NOT_FOUND, "Not found") })
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(sampling::make_span)
+                .on_response(sampling::log_response),
+        )
+        .layer(middleware::from_fn_with_state(
+            sampler,
+            trace_context::propagate,
+        ))
+        .layer(middleware::from_fn(track_metrics))
+        .with_state(proxy);
+
+    listeners::serve(config.listeners, app).await
 }
 
-async fn get_block_by_height(
-    State(state): State<AppState>,
-    Path(height): Path<u64>,
-) -> impl IntoResponse {
-    let rpc = state.rpc.clone();
-    match tokio::task::spawn_blocking(move || rpc.get_block_hash(height)).await {
-        Ok(Ok(hash)) => (StatusCode::OK, hash.to_string()).into_response(),
-        Ok(Err(e)) => {
-            warn!("Failed to get block hash for height {}: {}", height, e);
-            (StatusCode::NOT_FOUND, "Block not found").into_response()
-        }
-        Err(e) => {
-            warn!(
-                "Task failed when getting block hash for height {}: {}",
-                height, e
-            );
-            (StatusCode::INTERNAL_SERVER_ERROR, "RPC error").into_response()
-        }
+async fn get_tip(state: &AppState) -> Result<(u64, BlockHash), Response> {
+    chain::tip(&state.rpc).await.map_err(|e| {
+        warn!("Failed to get chain tip from RPC: {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, "RPC error").into_response()
+    })
+}
+
+async fn get_tip_height(State(state): State<AppState>) -> Response {
+    match get_tip(&state).await {
+        Ok((height, _)) => (StatusCode::OK, height.to_string()).into_response(),
+        Err(response) => response,
     }
 }
 
-fn get_fee_rate_blocking(client: &Client, blocks: u16) -> Result<f64, bitcoincore_rpc::Error> {
-    let estimate = client.estimate_smart_fee(blocks, None)?;
-    Ok(estimate
-        .fee_rate
-        .map(|fee_rate| fee_rate.to_btc())
-        .unwrap_or_else(|| {
-            warn!(
-                "No fee rate estimate available for {} blocks, using default",
-                blocks
-            );
-            0.0001
-        }))
+async fn get_tip_hash(State(state): State<AppState>) -> Response {
+    match get_tip(&state).await {
+        Ok((_, hash)) => (StatusCode::OK, hash.to_string()).into_response(),
+        Err(response) => response,
+    }
 }
 
-async fn get_fee_estimates(State(state): State<AppState>) -> impl IntoResponse {
-    let rpc = state.rpc.clone();
-    match tokio::task::spawn_blocking(move || {
-        CONFIRMATION_TARGETS
-            .iter()
-            .map(|&blocks| Ok((blocks.to_string(), get_fee_rate_blocking(&rpc, blocks)?)))
-            .collect::<Result<BTreeMap<_, _>, bitcoincore_rpc::Error>>()
-    })
-    .await
-    {
-        Ok(Ok(estimates)) => Json(estimates).into_response(),
-        Ok(Err(e)) => {
-            warn!("Failed to get fee estimates: {}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, "RPC error").into_response()
-        }
+async fn get_block_by_height(
+    State(state): State<AppState>,
+    Path(height): Path<u64>,
+) -> impl IntoResponse {
+    match state.rpc.get_block_hash(height).await {
+        Ok(hash) => (StatusCode::OK, hash.to_string()).into_response(),
         Err(e) => {
-            warn!("Task failed when getting fee estimates: {}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, "RPC error").into_response()
+            warn!("Failed to get block hash for height {}: {}", height, e);
+            (StatusCode::NOT_FOUND, "Block not found").into_response()
         }
     }
 }
@@ -212,25 +1772,30 @@ async fn get_fee_estimates(State(state): State<AppState>) -> impl IntoResponse {
 async fn get_block_raw(
     State(state): State<AppState>,
     Path(hash): Path<String>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
+    // Hex stays the default for existing clients, binary is opted into
+    let binary = headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("application/octet-stream"));
     match BlockHash::from_str(&hash) {
-        Ok(block_hash) => {
-            let rpc = state.rpc.clone();
-            match tokio::task::spawn_blocking(move || rpc.get_block_hex(&block_hash)).await {
-                Ok(Ok(block_hex)) => (StatusCode::OK, block_hex).into_response(),
-                Ok(Err(e)) => {
-                    warn!("Failed to get raw block for hash {}: {}", hash, e);
-                    (StatusCode::NOT_FOUND, "Block not found").into_response()
-                }
-                Err(e) => {
-                    warn!(
-                        "Task failed when getting raw block for hash {}: {}",
-                        hash, e
-                    );
-                    (StatusCode::INTERNAL_SERVER_ERROR, "RPC error").into_response()
-                }
-            }
+        Ok(block_hash) if binary => {
+            let mut response = blocks::raw_block_binary(&state, block_hash).await;
+            response
+                .headers_mut()
+                .insert(header::VARY, HeaderValue::from_static("accept"));
+            response
         }
+        Ok(block_hash) => match state.rpc.get_block_hex(&block_hash).await {
+            Ok(block_hex) => {
+                (StatusCode::OK, [(header::VARY, "accept")], block_hex).into_response()
+            }
+            Err(e) => {
+                warn!("Failed to get raw block for hash {}: {}", hash, e);
+                (StatusCode::NOT_FOUND, "Block not found").into_response()
+            }
+        },
         Err(e) => {
             warn!("Invalid block hash provided {}: {}", hash, e);
             (StatusCode::BAD_REQUEST, "Invalid block hash").into_response()
@@ -240,9 +1805,23 @@ async fn get_block_raw(
 
 #[derive(Clone)]
 struct RouteInfo {
+    method: Method,
     path: &'static str,
     description: &'static str,
     handler: MethodRouter<AppState, Infallible>,
+    policy: RoutePolicy,
+    /// Largest response body in bytes, if limited
+    response_limit: Option<usize>,
+    /// Requires the admin token
+    admin: bool,
+    /// Body of successful responses, for the OpenAPI spec
+    response: Option<openapi::Body>,
+    /// Expected request body, for the OpenAPI spec
+    request: Option<openapi::Body>,
+    /// Optional query parameters with their descriptions
+    query: Vec<(&'static str, &'static str)>,
+    /// Most items of a list per response
+    page_size: Option<usize>,
 }
 
 impl RouteInfo {
@@ -252,61 +1831,79 @@ impl RouteInfo {
         handler: MethodRouter<AppState, Infallible>,
     ) -> Self {
         Self {
+            method: Method::GET,
             path,
             description,
             handler,
+            policy: RoutePolicy::default(),
+            response_limit: None,
+            admin: false,
+            response: None,
+            request: None,
+            query: Vec::new(),
+            page_size: None,
+        }
+    }
+
+    fn post(
+        path: &'static str,
+        description: &'static str,
+        handler: MethodRouter<AppState, Infallible>,
+    ) -> Self {
+        Self {
+            method: Method::POST,
+            ..Self::new(path, description, handler)
         }
     }
+
+    fn delete(
+        path: &'static str,
+        description: &'static str,
+        handler: MethodRouter<AppState, Infallible>,
+    ) -> Self {
+        Self {
+            method: Method::DELETE,
+            ..Self::new(path, description, handler)
+        }
+    }
+
+    fn with_policy(mut self, policy: RoutePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    fn admin(mut self) -> Self {
+        self.admin = true;
+        self
+    }
+
+    fn returns(mut self, body: openapi::Body) -> Self {
+        self.response = Some(body);
+        self
+    }
+
+    fn accepts(mut self, body: openapi::Body) -> Self {
+        self.request = Some(body);
+        self
+    }
+
+    fn query(mut self, name: &'static str, description: &'static str) -> Self {
+        self.query.push((name, description));
+        self
+    }
+
+    fn page_size(mut self, page_size: usize) -> Self {
+        self.page_size = Some(page_size);
+        self
+    }
 }
 
-async fn index(State(state): State<AppState>) -> impl IntoResponse {
-    let mut routes_html = String::with_capacity(1024);
-    for route in state.routes.iter() {
-        write!(
-            routes_html,
-            r#"
-            <div class="endpoint">
-                <div class="path">GET {}</div>
-                <p>{}</p>
-            </div>
-            "#,
-            route.path, route.description
-        )
-        .expect("writing to string cannot fail");
-    }
-
-    Html(format!(
-        r#"
-        <!DOCTYPE html>
-        <html>
-        <head>
-            <title>Minipool API Documentation</title>
-            <style>
-                body {{
-                    font-family: system-ui, -apple-system, sans-serif;
-                    max-width: 800px;
-                    margin: 0 auto;
-                    padding: 2rem;
-                    line-height: 1.6;
-                }}
-                h1 {{ color: #2563eb; }}
-                .endpoint {{
-                    background: #f1f5f9;
-                    padding: 1rem;
-                    border-radius: 0.5rem;
-                    margin: 1rem 0;
-                }}
-                .path {{ font-family: monospace; }}
-            </style>
-        </head>
-        <body>
-            <h1>Minipool API Endpoints</h1>
-            {}
-        </body>
-        </html>
-        "#,
-        routes_html
-    ))
+/// The Swagger UI documents the API, keeping a `?lang=` override
+async fn index(uri: Uri) -> Redirect {
+    match uri.query() {
+        Some(query) => Redirect::temporary(&format!("/docs?{}", query)),
+        None => Redirect::temporary("/docs"),
+    }
 }
 
 async fn fallback() -> impl IntoResponse {
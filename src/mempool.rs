@@ -0,0 +1,1176 @@
+//! Local mirror of the node's mempool.
+//!
+//! The node is polled for its transaction ids; entries and raw transactions are
+//! only fetched for ids that weren't seen before, and each transaction is
+//! classified once when it enters the mirror. The mempool listing, recent
+//! transactions and projected blocks are filtered by these categories and by
+//! fee rate band through [`TxFilter`].
+
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path as FsPath;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use anyhow::Context;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use bitcoincore_rpc::bitcoin::hashes::Hash;
+use bitcoincore_rpc::bitcoin::{Amount, OutPoint, Script, Transaction, TxIn, Txid};
+use redb::{Database, ReadableTable, TableDefinition};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+use tracing::{debug, info, warn};
+
+use crate::addresses::{script_hash, ScriptHash};
+use crate::health::{Health, Severity};
+use crate::json;
+use crate::rpc::Rpc;
+use crate::storage;
+use crate::watch::OutpointWatches;
+use crate::webhooks::Webhooks;
+use crate::AppState;
+
+const HEALTH_COMPONENT: &str = "mempool";
+
+/// New transactions fetched before they are made visible to readers
+const INSERT_BATCH: usize = 1000;
+
+/// Additions and removals remembered for mempool diffs
+const CHANGE_LOG_LIMIT: usize = 100_000;
+
+/// Transactions remembered after leaving the mempool (mined, replaced or
+/// evicted), so conflicts with them can still be reported
+const DEPARTED_LIMIT: usize = 10_000;
+
+/// Shortest time between two syncs triggered by transaction notifications, so
+/// a burst of them is picked up by one sync
+const WOKEN_SYNC_GAP: Duration = Duration::from_millis(500);
+
+/// Inputs from which a transaction paying to a single output is a consolidation
+const CONSOLIDATION_INPUTS: usize = 3;
+
+pub struct MempoolTx {
+    pub txid: Txid,
+    pub fee: Amount,
+    pub vsize: u64,
+    pub weight: u64,
+    /// Sum of output values
+    pub value: Amount,
+    /// Earliest time the node or minipool saw the transaction, in seconds since epoch
+    pub first_seen: u64,
+    /// Size of the largest input witness, in bytes of witness items
+    pub witness_bytes: u64,
+    pub large_witness: bool,
+    /// At least [`CONSOLIDATION_INPUTS`] inputs paying to a single output
+    pub consolidation: bool,
+    /// Carries data in an `OP_RETURN` output
+    pub op_return: bool,
+    /// Spends taproot outputs only and pays to taproot outputs only, `OP_RETURN`
+    /// outputs aside
+    pub taproot_only: bool,
+    /// Outpoints spent by the transaction's inputs, in input order
+    pub inputs: Vec<OutPoint>,
+    /// Script hash and value of each output, only kept for the address index
+    pub outputs: Vec<(ScriptHash, Amount)>,
+    /// Script hash and value of each spent output, only kept for the address index
+    pub prevouts: Vec<(ScriptHash, Amount)>,
+}
+
+impl MempoolTx {
+    /// sat/vB
+    pub fn fee_rate(&self) -> f64 {
+        self.fee.to_sat() as f64 / self.vsize.max(1) as f64
+    }
+
+    pub fn scripts(&self) -> impl Iterator<Item = &ScriptHash> {
+        self.outputs
+            .iter()
+            .chain(&self.prevouts)
+            .map(|(script, _)| script)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Change {
+    Added(Txid),
+    Removed(Txid),
+}
+
+#[derive(Default)]
+struct Mirror {
+    txs: HashMap<Txid, Arc<MempoolTx>>,
+    /// Spending txid and input index of every outpoint spent in the mempool
+    spends: HashMap<OutPoint, (Txid, u32)>,
+    /// Most recently departed transactions, oldest first
+    departed: VecDeque<Arc<MempoolTx>>,
+    /// Departed transactions spending each of their outpoints
+    departed_spends: HashMap<OutPoint, Vec<Txid>>,
+    /// Transactions funding or spending each script
+    by_script: HashMap<ScriptHash, HashSet<Txid>>,
+    /// Sequence number of the latest addition or removal, counted from startup
+    seq: u64,
+    /// Latest additions and removals with their sequence numbers, oldest first
+    changes: VecDeque<(u64, Change)>,
+}
+
+impl Mirror {
+    fn insert(&mut self, tx: MempoolTx) {
+        for (vin, outpoint) in tx.inputs.iter().enumerate() {
+            self.spends.insert(*outpoint, (tx.txid, vin as u32));
+        }
+        for script in tx.scripts() {
+            self.by_script.entry(*script).or_default().insert(tx.txid);
+        }
+        self.record(Change::Added(tx.txid));
+        self.txs.insert(tx.txid, Arc::new(tx));
+    }
+
+    fn record(&mut self, change: Change) {
+        self.seq += 1;
+        self.changes.push_back((self.seq, change));
+        if self.changes.len() > CHANGE_LOG_LIMIT {
+            self.changes.pop_front();
+        }
+    }
+
+    /// Net additions and removals after sequence number `since`, `None` once
+    /// they are no longer all remembered
+    fn diff(&self, since: u64) -> Option<(Vec<Txid>, Vec<Txid>)> {
+        if since > self.seq {
+            return None;
+        }
+        if since < self.seq
+            && self
+                .changes
+                .front()
+                .is_none_or(|&(oldest, _)| oldest > since + 1)
+        {
+            return None;
+        }
+        let mut added = HashSet::new();
+        let mut removed = HashSet::new();
+        for &(_, change) in self.changes.iter().filter(|(seq, _)| *seq > since) {
+            // A transaction that came and went in between is no change at all
+            match change {
+                Change::Added(txid) => {
+                    if !removed.remove(&txid) {
+                        added.insert(txid);
+                    }
+                }
+                Change::Removed(txid) => {
+                    if !added.remove(&txid) {
+                        removed.insert(txid);
+                    }
+                }
+            }
+        }
+        Some((added.into_iter().collect(), removed.into_iter().collect()))
+    }
+
+    /// Drops transactions no longer in `txids`, returning their ids
+    fn retain(&mut self, txids: &HashSet<Txid>) -> Vec<Txid> {
+        let spends = &mut self.spends;
+        let by_script = &mut self.by_script;
+        let mut removed = Vec::new();
+        self.txs.retain(|txid, tx| {
+            let keep = txids.contains(txid);
+            if !keep {
+                removed.push(tx.clone());
+                for script in tx.scripts() {
+                    if let Some(script_txs) = by_script.get_mut(script) {
+                        script_txs.remove(txid);
+                        if script_txs.is_empty() {
+                            by_script.remove(script);
+                        }
+                    }
+                }
+                for outpoint in &tx.inputs {
+                    // A replacement may already have claimed the outpoint
+                    if spends
+                        .get(outpoint)
+                        .is_some_and(|(spender, _)| spender == txid)
+                    {
+                        spends.remove(outpoint);
+                    }
+                }
+            }
+            keep
+        });
+        removed
+            .into_iter()
+            .map(|tx| {
+                self.record(Change::Removed(tx.txid));
+                self.depart(tx)
+            })
+            .collect()
+    }
+
+    fn depart(&mut self, tx: Arc<MempoolTx>) -> Txid {
+        for outpoint in &tx.inputs {
+            self.departed_spends
+                .entry(*outpoint)
+                .or_default()
+                .push(tx.txid);
+        }
+        let txid = tx.txid;
+        self.departed.push_back(tx);
+        while self.departed.len() > DEPARTED_LIMIT {
+            let Some(oldest) = self.departed.pop_front() else {
+                break;
+            };
+            for outpoint in &oldest.inputs {
+                if let Some(spenders) = self.departed_spends.get_mut(outpoint) {
+                    if let Some(position) = spenders.iter().position(|&t| t == oldest.txid) {
+                        spenders.remove(position);
+                    }
+                    if spenders.is_empty() {
+                        self.departed_spends.remove(outpoint);
+                    }
+                }
+            }
+        }
+        txid
+    }
+
+    /// Other transactions, in the mempool or departed, spending any input of `txid`
+    fn conflicts(&self, txid: &Txid) -> Option<Vec<Conflict>> {
+        let tx = self
+            .txs
+            .get(txid)
+            .or_else(|| self.departed.iter().rev().find(|tx| tx.txid == *txid))?;
+        let mut conflicts: Vec<Conflict> = Vec::new();
+        for outpoint in &tx.inputs {
+            let mempool_spender = self.spends.get(outpoint).map(|(spender, _)| spender);
+            let departed_spenders = self.departed_spends.get(outpoint).into_iter().flatten();
+            for spender in mempool_spender.into_iter().chain(departed_spenders) {
+                if spender == txid {
+                    continue;
+                }
+                match conflicts.iter_mut().find(|c| c.txid == *spender) {
+                    Some(conflict) if conflict.outpoints.contains(outpoint) => {}
+                    Some(conflict) => conflict.outpoints.push(*outpoint),
+                    None => conflicts.push(Conflict {
+                        txid: *spender,
+                        in_mempool: self.txs.contains_key(spender),
+                        outpoints: vec![*outpoint],
+                    }),
+                }
+            }
+        }
+        Some(conflicts)
+    }
+}
+
+/// First-seen times of mempool transactions persisted under the data directory,
+/// so restarts of minipool or the node don't reset them
+pub struct FirstSeenStore {
+    db: Database,
+    /// Entries of transactions that left the mempool while we were down are
+    /// pruned on the first sync
+    pruned: AtomicBool,
+}
+
+const FIRST_SEEN: TableDefinition<&[u8], u64> = TableDefinition::new("first_seen");
+
+impl FirstSeenStore {
+    pub fn open(path: &FsPath) -> anyhow::Result<Self> {
+        let db = Database::create(path)
+            .with_context(|| format!("Failed to open first-seen store at {}", path.display()))?;
+        let txn = db.begin_write()?;
+        txn.open_table(FIRST_SEEN)?;
+        txn.commit()?;
+        Ok(Self {
+            db,
+            pruned: AtomicBool::new(false),
+        })
+    }
+
+    /// Earliest of each node-reported time and the one persisted, storing new ones
+    fn resolve(&self, seen: &[(Txid, u64)]) -> anyhow::Result<Vec<u64>> {
+        let txn = self.db.begin_write()?;
+        let mut times = Vec::with_capacity(seen.len());
+        {
+            let mut table = txn.open_table(FIRST_SEEN)?;
+            for (txid, reported) in seen {
+                let key = txid.as_byte_array().as_slice();
+                let stored = table.get(key)?.map(|time| time.value());
+                match stored {
+                    Some(time) if time <= *reported => times.push(time),
+                    _ => {
+                        table.insert(key, *reported)?;
+                        times.push(*reported);
+                    }
+                }
+            }
+        }
+        txn.commit()?;
+        Ok(times)
+    }
+
+    fn forget(&self, removed: &[Txid], mempool: &HashSet<Txid>) -> anyhow::Result<()> {
+        let txn = self.db.begin_write()?;
+        {
+            let mut table = txn.open_table(FIRST_SEEN)?;
+            if self.pruned.swap(true, Ordering::Relaxed) {
+                for txid in removed {
+                    table.remove(txid.as_byte_array().as_slice())?;
+                }
+            } else {
+                table.retain(|key, _| {
+                    Txid::from_slice(key).is_ok_and(|txid| mempool.contains(&txid))
+                })?;
+            }
+        }
+        txn.commit()?;
+        Ok(())
+    }
+}
+
+pub struct MempoolTracker {
+    large_witness_bytes: u64,
+    mirror: RwLock<Mirror>,
+    first_seen: Option<Arc<FirstSeenStore>>,
+    watches: Arc<OutpointWatches>,
+    webhooks: Arc<Webhooks>,
+    /// Resolve the scripts each transaction funds and spends, for the address index
+    index_scripts: bool,
+    /// Triggers a sync before the next tick, for transaction notifications
+    wake: Arc<Notify>,
+}
+
+impl MempoolTracker {
+    pub fn new(
+        large_witness_bytes: u64,
+        first_seen: Option<FirstSeenStore>,
+        watches: Arc<OutpointWatches>,
+        webhooks: Arc<Webhooks>,
+        index_scripts: bool,
+    ) -> Self {
+        Self {
+            large_witness_bytes,
+            mirror: RwLock::new(Mirror::default()),
+            first_seen: first_seen.map(Arc::new),
+            watches,
+            webhooks,
+            index_scripts,
+            wake: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Handle that makes the tracker resync right away when notified
+    pub fn waker(&self) -> Arc<Notify> {
+        self.wake.clone()
+    }
+
+    /// Resyncs the mirror with the node every `interval`, and whenever woken
+    pub async fn run(self: Arc<Self>, rpc: Arc<Rpc>, interval: Duration, health: Arc<Health>) {
+        health.register(HEALTH_COMPONENT, Severity::Soft, Some(interval * 3));
+        let mut ticker = tokio::time::interval(interval);
+        let mut synced = false;
+        let mut last_sync = Instant::now();
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = self.wake.notified() => {
+                    tokio::time::sleep_until(last_sync + WOKEN_SYNC_GAP).await;
+                }
+            }
+            last_sync = Instant::now();
+            match self.sync(&rpc).await {
+                Ok(()) => {
+                    health.success(HEALTH_COMPONENT);
+                    if !synced {
+                        info!("Mempool mirror synced with {} transactions", self.len());
+                        synced = true;
+                    }
+                }
+                Err(e) => {
+                    warn!("Failed to sync mempool: {}", e);
+                    health.failure(HEALTH_COMPONENT, &e);
+                }
+            }
+        }
+    }
+
+    /// Mirrors the node's mempool. Transactions come over RPC on the async
+    /// runtime, first-seen times are read and written on the blocking pool.
+    async fn sync(&self, rpc: &Rpc) -> Result<(), bitcoincore_rpc::Error> {
+        let txids: Arc<HashSet<Txid>> =
+            Arc::new(rpc.get_raw_mempool().await?.into_iter().collect());
+        let (removed, missing): (Vec<Txid>, Vec<Txid>) = {
+            let mut mirror = self.mirror.write().expect("mempool lock poisoned");
+            let removed = mirror.retain(&txids);
+            let missing = txids
+                .iter()
+                .filter(|txid| !mirror.txs.contains_key(*txid))
+                .copied()
+                .collect();
+            (removed, missing)
+        };
+        if let Some(store) = self.first_seen.clone() {
+            let mempool = txids.clone();
+            if let Err(e) = storage::blocking(move || store.forget(&removed, &mempool)).await {
+                warn!("Failed to prune first-seen times: {}", e);
+            }
+        }
+
+        for batch in missing.chunks(INSERT_BATCH) {
+            let mut fetched = Vec::with_capacity(batch.len());
+            for txid in batch {
+                match self.fetch(rpc, txid, &txids).await {
+                    Ok(tx) => fetched.push(tx),
+                    // Mined or evicted since the listing, the next sync drops it anyway
+                    Err(e) => debug!("Skipping mempool transaction {}: {}", txid, e),
+                }
+            }
+            if let Some(store) = self.first_seen.clone() {
+                let seen: Vec<(Txid, u64)> =
+                    fetched.iter().map(|tx| (tx.txid, tx.first_seen)).collect();
+                match storage::blocking(move || store.resolve(&seen)).await {
+                    Ok(times) => {
+                        for (tx, time) in fetched.iter_mut().zip(times) {
+                            tx.first_seen = time;
+                        }
+                    }
+                    Err(e) => warn!("Failed to persist first-seen times: {}", e),
+                }
+            }
+            for tx in &fetched {
+                self.watches.spent_in_mempool(tx);
+                self.webhooks.seen_in_mempool(tx);
+            }
+            let mut mirror = self.mirror.write().expect("mempool lock poisoned");
+            for tx in fetched {
+                mirror.insert(tx);
+            }
+        }
+
+        let mirror = self.mirror.read().expect("mempool lock poisoned");
+        metrics::gauge!("mempool_transactions").set(mirror.txs.len() as f64);
+        metrics::gauge!("mempool_large_witness_transactions")
+            .set(mirror.txs.values().filter(|tx| tx.large_witness).count() as f64);
+        Ok(())
+    }
+
+    async fn fetch(
+        &self,
+        rpc: &Rpc,
+        txid: &Txid,
+        mempool: &HashSet<Txid>,
+    ) -> Result<MempoolTx, bitcoincore_rpc::Error> {
+        let entry = rpc.get_mempool_entry(txid).await?;
+        let tx = rpc.get_raw_transaction(txid, None).await?;
+        let witness_bytes = tx.input.iter().map(witness_bytes).max().unwrap_or(0);
+        let (outputs, prevouts) = if self.index_scripts {
+            let outputs = tx
+                .output
+                .iter()
+                .map(|output| (script_hash(&output.script_pubkey), output.value))
+                .collect();
+            let mut prevouts = Vec::with_capacity(tx.input.len());
+            for input in &tx.input {
+                if let Some(prevout) = self.prevout(rpc, &input.previous_output, mempool).await? {
+                    prevouts.push(prevout);
+                }
+            }
+            (outputs, prevouts)
+        } else {
+            (Vec::new(), Vec::new())
+        };
+        Ok(MempoolTx {
+            txid: *txid,
+            fee: entry.fees.base,
+            vsize: entry.vsize,
+            weight: entry.weight.unwrap_or(entry.vsize * 4),
+            value: tx.output.iter().map(|output| output.value).sum(),
+            first_seen: entry.time,
+            witness_bytes,
+            large_witness: witness_bytes >= self.large_witness_bytes,
+            consolidation: tx.input.len() >= CONSOLIDATION_INPUTS && tx.output.len() == 1,
+            op_return: tx
+                .output
+                .iter()
+                .any(|output| output.script_pubkey.is_op_return()),
+            taproot_only: taproot_only(&tx),
+            inputs: tx.input.iter().map(|input| input.previous_output).collect(),
+            outputs,
+            prevouts,
+        })
+    }
+
+    /// Script hash and value of a spent output, from the mirror, a mempool parent
+    /// or the node's UTXO set, none of which needs `txindex`
+    async fn prevout(
+        &self,
+        rpc: &Rpc,
+        outpoint: &OutPoint,
+        mempool: &HashSet<Txid>,
+    ) -> Result<Option<(ScriptHash, Amount)>, bitcoincore_rpc::Error> {
+        if let Some(parent) = self.get(&outpoint.txid) {
+            return Ok(parent.outputs.get(outpoint.vout as usize).copied());
+        }
+        if mempool.contains(&outpoint.txid) {
+            let parent = rpc.get_raw_transaction(&outpoint.txid, None).await?;
+            return Ok(parent
+                .output
+                .get(outpoint.vout as usize)
+                .map(|output| (script_hash(&output.script_pubkey), output.value)));
+        }
+        Ok(rpc
+            .get_tx_out(&outpoint.txid, outpoint.vout, Some(false))
+            .await?
+            .map(|output| {
+                (
+                    script_hash(Script::from_bytes(&output.script_pub_key.hex)),
+                    output.value,
+                )
+            }))
+    }
+
+    pub fn len(&self) -> usize {
+        self.mirror.read().expect("mempool lock poisoned").txs.len()
+    }
+
+    /// Snapshot of the mirrored transactions matching `filter`
+    pub fn filter(&self, filter: impl Fn(&MempoolTx) -> bool) -> Vec<Arc<MempoolTx>> {
+        let mirror = self.mirror.read().expect("mempool lock poisoned");
+        mirror
+            .txs
+            .values()
+            .filter(|tx| filter(tx))
+            .cloned()
+            .collect()
+    }
+
+    /// The `count` transactions matching `filter` that entered the mempool
+    /// last, newest first
+    pub fn recent(&self, count: usize, filter: &TxFilter) -> Vec<Arc<MempoolTx>> {
+        let mut txs = self.filter(|tx| filter.matches(tx));
+        if txs.len() > count {
+            txs.select_nth_unstable_by_key(count, |tx| Reverse(tx.first_seen));
+            txs.truncate(count);
+        }
+        txs.sort_by_key(|tx| Reverse(tx.first_seen));
+        txs
+    }
+
+    pub fn get(&self, txid: &Txid) -> Option<Arc<MempoolTx>> {
+        let mirror = self.mirror.read().expect("mempool lock poisoned");
+        mirror.txs.get(txid).cloned()
+    }
+
+    pub fn first_seen(&self, txid: &Txid) -> Option<u64> {
+        let mirror = self.mirror.read().expect("mempool lock poisoned");
+        mirror.txs.get(txid).map(|tx| tx.first_seen)
+    }
+
+    /// Mempool transactions funding or spending `script`, newest first
+    pub fn script_txs(&self, script: &ScriptHash) -> Vec<Arc<MempoolTx>> {
+        let mirror = self.mirror.read().expect("mempool lock poisoned");
+        let mut txs: Vec<_> = mirror
+            .by_script
+            .get(script)
+            .into_iter()
+            .flatten()
+            .filter_map(|txid| mirror.txs.get(txid).cloned())
+            .collect();
+        txs.sort_by_key(|tx| Reverse(tx.first_seen));
+        txs
+    }
+
+    /// Conflicting transactions of a mempool or recently departed transaction
+    pub fn conflicts(&self, txid: &Txid) -> Option<Vec<Conflict>> {
+        let mirror = self.mirror.read().expect("mempool lock poisoned");
+        mirror.conflicts(txid)
+    }
+
+    /// Current sequence number with the net changes since `since`, or with every
+    /// mirrored txid as an addition without it
+    pub fn diff(&self, since: Option<u64>) -> Option<MempoolDiff> {
+        let mirror = self.mirror.read().expect("mempool lock poisoned");
+        let (added, removed) = match since {
+            Some(since) => mirror.diff(since)?,
+            None => (mirror.txs.keys().copied().collect(), Vec::new()),
+        };
+        Some(MempoolDiff {
+            seq: mirror.seq,
+            added,
+            removed,
+        })
+    }
+
+    /// Sequence number of the latest mirror change
+    pub fn seq(&self) -> u64 {
+        self.mirror.read().expect("mempool lock poisoned").seq
+    }
+
+    /// Current sequence number with the transactions added after `since` that
+    /// are still in the mempool, oldest first
+    pub fn added_since(&self, since: u64) -> Option<(u64, Vec<Arc<MempoolTx>>)> {
+        let mirror = self.mirror.read().expect("mempool lock poisoned");
+        let (added, _) = mirror.diff(since)?;
+        let mut txs: Vec<_> = added
+            .iter()
+            .filter_map(|txid| mirror.txs.get(txid).cloned())
+            .collect();
+        txs.sort_by_key(|tx| tx.first_seen);
+        Some((mirror.seq, txs))
+    }
+
+    /// Mempool transaction and input index spending `outpoint`
+    pub fn spent_by(&self, outpoint: &OutPoint) -> Option<(Txid, u32)> {
+        let mirror = self.mirror.read().expect("mempool lock poisoned");
+        mirror.spends.get(outpoint).copied()
+    }
+}
+
+/// Total size of an input's witness items, leaving out length prefixes
+fn witness_bytes(input: &TxIn) -> u64 {
+    input.witness.iter().map(|item| item.len() as u64).sum()
+}
+
+/// Whether an input spends a taproot output, told from its witness alone: a
+/// single Schnorr signature for a key path spend, or a control block for a
+/// script path spend
+fn spends_taproot(input: &TxIn) -> bool {
+    let witness = &input.witness;
+    let items = witness.len() - usize::from(witness.taproot_annex().is_some());
+    match items {
+        0 => false,
+        1 => witness
+            .nth(0)
+            .is_some_and(|signature| matches!(signature.len(), 64 | 65)),
+        _ => witness.taproot_control_block().is_some_and(|control| {
+            control.len() >= 33 && (control.len() - 33) % 32 == 0 && control[0] & 0xfe == 0xc0
+        }),
+    }
+}
+
+fn taproot_only(tx: &Transaction) -> bool {
+    tx.input.iter().all(spends_taproot)
+        && tx
+            .output
+            .iter()
+            .filter(|output| !output.script_pubkey.is_op_return())
+            .all(|output| output.script_pubkey.is_p2tr())
+}
+
+/// A category transactions are classified into as they enter the mirror
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Category {
+    LargeWitness,
+    Consolidation,
+    OpReturn,
+    Taproot,
+}
+
+impl Category {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "large-witness" => Some(Category::LargeWitness),
+            "consolidation" => Some(Category::Consolidation),
+            "op-return" => Some(Category::OpReturn),
+            "taproot" => Some(Category::Taproot),
+            _ => None,
+        }
+    }
+
+    fn contains(self, tx: &MempoolTx) -> bool {
+        match self {
+            Category::LargeWitness => tx.large_witness,
+            Category::Consolidation => tx.consolidation,
+            Category::OpReturn => tx.op_return,
+            Category::Taproot => tx.taproot_only,
+        }
+    }
+}
+
+pub const FILTER: &str = "Comma-separated categories every transaction has to be in: `large-witness`, `consolidation`, `op-return` and `taproot`";
+pub const MIN_FEE_RATE: &str = "Lowest fee rate in sat/vB, inclusive";
+pub const MAX_FEE_RATE: &str = "Highest fee rate in sat/vB, inclusive";
+
+#[derive(Deserialize)]
+pub struct TxFilterQuery {
+    filter: Option<String>,
+    min_fee_rate: Option<f64>,
+    max_fee_rate: Option<f64>,
+}
+
+/// Categories and fee rate band of the transactions a mempool endpoint serves
+#[derive(Default)]
+pub struct TxFilter {
+    categories: Vec<Category>,
+    min_fee_rate: Option<f64>,
+    max_fee_rate: Option<f64>,
+}
+
+impl TxFilter {
+    pub fn parse(query: &TxFilterQuery) -> Result<Self, (StatusCode, String)> {
+        let mut categories = Vec::new();
+        for name in query.filter.iter().flat_map(|filter| filter.split(',')) {
+            match Category::from_name(name) {
+                Some(category) if !categories.contains(&category) => categories.push(category),
+                Some(_) => {}
+                None => {
+                    return Err((
+                        StatusCode::BAD_REQUEST,
+                        format!("Unknown filter {:?}", name),
+                    ))
+                }
+            }
+        }
+        Ok(Self {
+            categories,
+            min_fee_rate: query.min_fee_rate,
+            max_fee_rate: query.max_fee_rate,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.categories.is_empty() && self.min_fee_rate.is_none() && self.max_fee_rate.is_none()
+    }
+
+    pub fn matches(&self, tx: &MempoolTx) -> bool {
+        let rate = tx.fee_rate();
+        self.categories.iter().all(|category| category.contains(tx))
+            && self.min_fee_rate.is_none_or(|min| rate >= min)
+            && self.max_fee_rate.is_none_or(|max| rate <= max)
+    }
+}
+
+#[derive(Serialize)]
+struct FilteredMempool {
+    count: usize,
+    transactions: Vec<FilteredTx>,
+}
+
+#[derive(Serialize)]
+struct FilteredTx {
+    txid: Txid,
+    fee: u64,
+    vsize: u64,
+    weight: u64,
+    /// Largest input witness in bytes
+    witness_bytes: u64,
+    /// Seconds since epoch
+    first_seen: u64,
+}
+
+pub async fn get_mempool(
+    State(state): State<AppState>,
+    Query(query): Query<TxFilterQuery>,
+) -> impl IntoResponse {
+    let filter = match TxFilter::parse(&query) {
+        Ok(filter) if filter.is_empty() => {
+            return (StatusCode::BAD_REQUEST, "Missing filter").into_response()
+        }
+        Ok(filter) => filter,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let mut transactions = state.mempool.filter(|tx| filter.matches(tx));
+    if filter.categories.contains(&Category::LargeWitness) {
+        // Heaviest witnesses first, they are what operators are looking for
+        transactions.sort_by_key(|tx| Reverse(tx.witness_bytes));
+    } else {
+        transactions.sort_by(|a, b| b.fee_rate().total_cmp(&a.fee_rate()));
+    }
+    Json(FilteredMempool {
+        count: transactions.len(),
+        transactions: transactions
+            .iter()
+            .map(|tx| FilteredTx {
+                txid: tx.txid,
+                fee: tx.fee.to_sat(),
+                vsize: tx.vsize,
+                weight: tx.weight,
+                witness_bytes: tx.witness_bytes,
+                first_seen: tx.first_seen,
+            })
+            .collect(),
+    })
+    .into_response()
+}
+
+#[derive(Deserialize)]
+pub struct DiffQuery {
+    since: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct MempoolDiff {
+    /// Sequence number to pass as `since` next time
+    seq: u64,
+    added: Vec<Txid>,
+    removed: Vec<Txid>,
+}
+
+pub async fn get_mempool_diff(
+    State(state): State<AppState>,
+    Query(query): Query<DiffQuery>,
+) -> impl IntoResponse {
+    match state.mempool.diff(query.since) {
+        Some(diff) => Json(diff).into_response(),
+        // Too old, or from before a restart
+        None => (
+            StatusCode::GONE,
+            "Sequence number not retained, resync without since",
+        )
+            .into_response(),
+    }
+}
+
+/// Transactions served by the recent mempool endpoint, as on mempool.space
+const RECENT_TXS: usize = 10;
+
+#[derive(Serialize)]
+struct RecentTx {
+    txid: Txid,
+    fee: u64,
+    vsize: u64,
+    /// Sum of output values in sats
+    value: u64,
+}
+
+pub async fn get_mempool_recent(
+    State(state): State<AppState>,
+    Query(query): Query<TxFilterQuery>,
+) -> impl IntoResponse {
+    let filter = match TxFilter::parse(&query) {
+        Ok(filter) => filter,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let recent: Vec<_> = state
+        .mempool
+        .recent(RECENT_TXS, &filter)
+        .iter()
+        .map(|tx| RecentTx {
+            txid: tx.txid,
+            fee: tx.fee.to_sat(),
+            vsize: tx.vsize,
+            value: tx.value.to_sat(),
+        })
+        .collect();
+    Json(recent).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct TxidsQuery {
+    limit: Option<usize>,
+}
+
+/// Txids in the node's mempool, up to `?limit=`
+pub async fn get_mempool_txids(
+    State(state): State<AppState>,
+    Query(query): Query<TxidsQuery>,
+) -> impl IntoResponse {
+    match state.rpc.get_raw_mempool().await {
+        Ok(mut txids) => {
+            if let Some(limit) = query.limit {
+                txids.truncate(limit);
+            }
+            Json(txids).into_response()
+        }
+        Err(e) => {
+            warn!("Failed to get mempool txids: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "RPC error").into_response()
+        }
+    }
+}
+
+/// Vsize from which a fee histogram band is closed, the band width electrs uses
+const FEE_HISTOGRAM_BAND_VSIZE: u64 = 50_000;
+
+#[derive(Deserialize)]
+struct VerboseEntry {
+    vsize: u64,
+    fees: VerboseFees,
+}
+
+#[derive(Deserialize)]
+struct VerboseFees {
+    #[serde(with = "bitcoincore_rpc::bitcoin::amount::serde::as_btc")]
+    base: Amount,
+}
+
+#[derive(Serialize)]
+struct MempoolSummary {
+    count: usize,
+    vsize: u64,
+    /// Sum of fees in sats
+    total_fee: u64,
+    /// `[fee_rate, vsize]` bands from the highest fee rate down, each named
+    /// after the lowest fee rate in sat/vB it holds
+    fee_histogram: Vec<(f64, u64)>,
+}
+
+/// `[fee_rate, vsize]` bands of transactions given as `(fee_rate, vsize)`, from
+/// the highest fee rate down, rates rounded like [`json::rate`]
+pub fn fee_histogram(mut rates: Vec<(f64, u64)>) -> Vec<(f64, u64)> {
+    rates.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let mut fee_histogram = Vec::new();
+    let mut band_vsize = 0;
+    let mut last_rate = 0.0;
+    for (rate, vsize) in rates {
+        // Transactions of the same fee rate stay in one band
+        if band_vsize > FEE_HISTOGRAM_BAND_VSIZE && rate != last_rate {
+            fee_histogram.push((json::round(last_rate), band_vsize));
+            band_vsize = 0;
+        }
+        last_rate = rate;
+        band_vsize += vsize;
+    }
+    if band_vsize > 0 {
+        fee_histogram.push((json::round(last_rate), band_vsize));
+    }
+    fee_histogram
+}
+
+async fn mempool_summary(rpc: &Rpc) -> Result<MempoolSummary, bitcoincore_rpc::Error> {
+    let entries: HashMap<Txid, VerboseEntry> = rpc.call("getrawmempool", &[json!(true)]).await?;
+    let rates = entries
+        .values()
+        .map(|entry| {
+            (
+                entry.fees.base.to_sat() as f64 / entry.vsize.max(1) as f64,
+                entry.vsize,
+            )
+        })
+        .collect();
+
+    Ok(MempoolSummary {
+        count: entries.len(),
+        vsize: entries.values().map(|entry| entry.vsize).sum(),
+        total_fee: entries.values().map(|entry| entry.fees.base.to_sat()).sum(),
+        fee_histogram: fee_histogram(rates),
+    })
+}
+
+pub async fn get_mempool_summary(State(state): State<AppState>) -> impl IntoResponse {
+    match mempool_summary(&state.rpc).await {
+        Ok(summary) => Json(summary).into_response(),
+        Err(e) => {
+            warn!("Failed to get mempool summary: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "RPC error").into_response()
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Conflict {
+    txid: Txid,
+    /// Still in the mempool, as opposed to mined, replaced or evicted since
+    in_mempool: bool,
+    /// Outpoints both transactions spend
+    outpoints: Vec<OutPoint>,
+}
+
+#[derive(Serialize)]
+struct Conflicts {
+    txid: Txid,
+    conflicts: Vec<Conflict>,
+}
+
+pub async fn get_tx_conflicts(
+    State(state): State<AppState>,
+    Path(txid): Path<String>,
+) -> impl IntoResponse {
+    let Ok(parsed) = Txid::from_str(&txid) else {
+        return (StatusCode::BAD_REQUEST, "Invalid txid").into_response();
+    };
+    match state.mempool.conflicts(&parsed) {
+        Some(conflicts) => Json(Conflicts {
+            txid: parsed,
+            conflicts,
+        })
+        .into_response(),
+        None => (StatusCode::NOT_FOUND, "Transaction not in mempool").into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden;
+    use bitcoincore_rpc::bitcoin::{ScriptBuf, TxOut, Witness};
+
+    fn input(witness: &[Vec<u8>]) -> TxIn {
+        TxIn {
+            witness: Witness::from_slice(witness),
+            ..TxIn::default()
+        }
+    }
+
+    #[test]
+    fn tells_taproot_spends_from_witnesses() {
+        assert!(spends_taproot(&input(&[vec![1; 64]])));
+        assert!(spends_taproot(&input(&[vec![1; 65], vec![0x50, 2]])));
+        let control_block = [vec![0xc1], vec![2; 32], vec![3; 32]].concat();
+        assert!(spends_taproot(&input(&[
+            vec![4; 64],
+            vec![0x51],
+            control_block
+        ])));
+        // P2WPKH: a DER signature and a public key
+        assert!(!spends_taproot(&input(&[vec![0x30; 71], vec![2; 33]])));
+        assert!(!spends_taproot(&input(&[])));
+    }
+
+    #[test]
+    fn filters_by_category_and_fee_rate() {
+        let tx = |fee: u64, consolidation: bool| MempoolTx {
+            txid: Txid::all_zeros(),
+            fee: Amount::from_sat(fee),
+            vsize: 100,
+            weight: 400,
+            value: Amount::ZERO,
+            first_seen: 0,
+            witness_bytes: 0,
+            large_witness: false,
+            consolidation,
+            op_return: false,
+            taproot_only: false,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            prevouts: Vec::new(),
+        };
+        let query = TxFilterQuery {
+            filter: Some("consolidation".to_owned()),
+            min_fee_rate: Some(2.0),
+            max_fee_rate: Some(5.0),
+        };
+        let filter = TxFilter::parse(&query).unwrap();
+        assert!(filter.matches(&tx(200, true)));
+        assert!(filter.matches(&tx(500, true)));
+        assert!(!filter.matches(&tx(199, true)));
+        assert!(!filter.matches(&tx(501, true)));
+        assert!(!filter.matches(&tx(300, false)));
+
+        let query = TxFilterQuery {
+            filter: Some("consolidation,inscription".to_owned()),
+            min_fee_rate: None,
+            max_fee_rate: None,
+        };
+        assert!(TxFilter::parse(&query).is_err());
+    }
+
+    #[test]
+    fn taproot_only_allows_op_return_outputs() {
+        let output = |script_pubkey: ScriptBuf| TxOut {
+            value: Amount::ZERO,
+            script_pubkey,
+        };
+        let p2tr = ScriptBuf::from_bytes([vec![0x51, 0x20], vec![7; 32]].concat());
+        let mut tx = Transaction {
+            version: bitcoincore_rpc::bitcoin::transaction::Version::TWO,
+            lock_time: bitcoincore_rpc::bitcoin::absolute::LockTime::ZERO,
+            input: vec![input(&[vec![1; 64]])],
+            output: vec![output(p2tr), output(ScriptBuf::new_op_return([1, 2, 3]))],
+        };
+        assert!(taproot_only(&tx));
+        tx.output.push(output(ScriptBuf::from_bytes(
+            [vec![0x00, 0x14], vec![7; 20]].concat(),
+        )));
+        assert!(!taproot_only(&tx));
+    }
+
+    fn txid(hex: &str) -> Txid {
+        hex.parse().unwrap()
+    }
+
+    const TXID: &str = "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16";
+    const OTHER_TXID: &str = "a1075db55d416d3ca199f55b6084e2115b9345e16c5cf302fc80e9d5fbf5d48d";
+
+    #[test]
+    fn golden_mempool() {
+        golden::check(
+            "mempool",
+            &FilteredMempool {
+                count: 1,
+                transactions: vec![FilteredTx {
+                    txid: txid(TXID),
+                    fee: 49859,
+                    vsize: 141,
+                    weight: 561,
+                    witness_bytes: 1531,
+                    first_seen: 1713571500,
+                }],
+            },
+        );
+    }
+
+    #[test]
+    fn golden_mempool_diff() {
+        golden::check(
+            "mempool_diff",
+            &MempoolDiff {
+                seq: 1024,
+                added: vec![txid(TXID)],
+                removed: vec![txid(OTHER_TXID)],
+            },
+        );
+    }
+
+    #[test]
+    fn golden_mempool_recent() {
+        golden::check(
+            "mempool_recent",
+            &[RecentTx {
+                txid: txid(TXID),
+                fee: 49859,
+                vsize: 141,
+                value: 100000,
+            }],
+        );
+    }
+
+    #[test]
+    fn golden_mempool_txids() {
+        golden::check("mempool_txids", &[txid(TXID), txid(OTHER_TXID)]);
+    }
+
+    #[test]
+    fn golden_mempool_summary() {
+        let rates = vec![
+            (1.0, 28230434),
+            (5650.0 / 101.0, 101233),
+            (226.0 / 11.0, 1523456),
+        ];
+        golden::check(
+            "mempool_summary",
+            &MempoolSummary {
+                count: 48211,
+                vsize: 29855123,
+                total_fee: 201455312,
+                fee_histogram: fee_histogram(rates),
+            },
+        );
+    }
+
+    #[test]
+    fn golden_tx_conflicts() {
+        golden::check(
+            "tx_conflicts",
+            &Conflicts {
+                txid: txid(TXID),
+                conflicts: vec![Conflict {
+                    txid: txid(OTHER_TXID),
+                    in_mempool: false,
+                    outpoints: vec![
+                        "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87:0"
+                            .parse()
+                            .unwrap(),
+                    ],
+                }],
+            },
+        );
+    }
+}
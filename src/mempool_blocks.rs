@@ -0,0 +1,524 @@
+//! Projects the node's mempool into the next blocks.
+//!
+//! Transactions are packed the way the node's block assembler does it: the
+//! transaction with the highest fee rate counting its unconfirmed ancestors goes
+//! first along with those ancestors, after which the scores of its descendants
+//! are updated. Packages that don't fit the current block are set aside until it
+//! is full. The last projected block takes whatever remains, as on mempool.space.
+//!
+//! Filtered projections keep the blocks as packed and only count the
+//! transactions the mempool mirror classified into the requested categories and
+//! fee rate band.
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use axum::extract::{Query, State};
+use axum::{http::StatusCode, response::IntoResponse, Json};
+use bitcoincore_rpc::bitcoin::{Amount, Txid};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::warn;
+
+use crate::health::{Health, Severity};
+use crate::json;
+use crate::mempool::{TxFilter, TxFilterQuery};
+use crate::rpc::Rpc;
+use crate::AppState;
+
+const HEALTH_COMPONENT: &str = "mempool_blocks";
+
+/// Projected blocks, the last one holding the rest of the mempool
+const PROJECTED_BLOCKS: usize = 8;
+
+/// Block weight available to transactions, leaving room for the coinbase as
+/// the node's default `-blockmaxweight` does
+const BLOCK_TX_WEIGHT: u64 = 4_000_000 - 4_000;
+
+/// Packages that may fail to fit a nearly full block before it is closed
+const MAX_FIT_FAILURES: usize = 1_000;
+
+#[derive(Deserialize)]
+struct VerboseEntry {
+    vsize: u64,
+    weight: u64,
+    fees: VerboseFees,
+    depends: Vec<Txid>,
+}
+
+#[derive(Deserialize)]
+struct VerboseFees {
+    #[serde(with = "bitcoincore_rpc::bitcoin::amount::serde::as_btc")]
+    modified: Amount,
+}
+
+struct Entry {
+    txid: Txid,
+    vsize: u64,
+    weight: u64,
+    fee: u64,
+    parents: Vec<usize>,
+    children: Vec<usize>,
+    included: bool,
+    /// Bumped whenever the score changes, invalidating older heap candidates
+    generation: u32,
+}
+
+/// Fee rate of a transaction and its ancestors still to be included
+struct Candidate {
+    rate: f64,
+    generation: u32,
+    index: usize,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.rate
+            .total_cmp(&other.rate)
+            .then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+/// A projected block in the mempool.space format
+#[derive(Clone, Serialize)]
+pub struct ProjectedBlock {
+    #[serde(rename = "blockVSize")]
+    vsize: u64,
+    #[serde(rename = "nTx")]
+    tx_count: usize,
+    /// Sum of fees in sats
+    #[serde(rename = "totalFees")]
+    total_fees: u64,
+    /// Median package fee rate in sat/vB
+    #[serde(rename = "medianFee", serialize_with = "json::rate")]
+    median_fee: f64,
+    /// Min, 10th, 25th, 50th, 75th, 90th percentile and max package fee rate in sat/vB
+    #[serde(rename = "feeRange", serialize_with = "json::rates")]
+    fee_range: [f64; 7],
+}
+
+/// A transaction packed into a projected block
+#[derive(Clone, Copy)]
+struct Member {
+    txid: Txid,
+    vsize: u64,
+    weight: u64,
+    fee: u64,
+    /// Fee rate of the package it was included with, in sat/vB
+    rate: f64,
+}
+
+#[derive(Default)]
+struct BlockBuilder {
+    weight: u64,
+    members: Vec<Member>,
+}
+
+impl BlockBuilder {
+    fn add(&mut self, member: Member) {
+        self.weight += member.weight;
+        self.members.push(member);
+    }
+
+    fn summary(&self) -> ProjectedBlock {
+        let mut rates: Vec<f64> = self.members.iter().map(|member| member.rate).collect();
+        rates.sort_by(f64::total_cmp);
+        let percentile = |p: usize| match rates.len() {
+            0 => 0.0,
+            len => rates[(len - 1) * p / 100],
+        };
+        ProjectedBlock {
+            vsize: self.members.iter().map(|member| member.vsize).sum(),
+            tx_count: self.members.len(),
+            total_fees: self.members.iter().map(|member| member.fee).sum(),
+            median_fee: percentile(50),
+            fee_range: [0, 10, 25, 50, 75, 90, 100].map(percentile),
+        }
+    }
+}
+
+struct Packer {
+    entries: Vec<Entry>,
+    heap: BinaryHeap<Candidate>,
+}
+
+impl Packer {
+    fn new(mempool: HashMap<Txid, VerboseEntry>) -> Self {
+        let positions: HashMap<Txid, usize> = mempool
+            .keys()
+            .enumerate()
+            .map(|(i, txid)| (*txid, i))
+            .collect();
+        let mut entries: Vec<Entry> = mempool
+            .into_iter()
+            .map(|(txid, entry)| Entry {
+                txid,
+                vsize: entry.vsize,
+                weight: entry.weight,
+                fee: entry.fees.modified.to_sat(),
+                // Parents that left the mempool since the listing are left out
+                parents: entry
+                    .depends
+                    .iter()
+                    .filter_map(|txid| positions.get(txid).copied())
+                    .collect(),
+                children: Vec::new(),
+                included: false,
+                generation: 0,
+            })
+            .collect();
+        for child in 0..entries.len() {
+            for parent in entries[child].parents.clone() {
+                entries[parent].children.push(child);
+            }
+        }
+        let mut packer = Self {
+            entries,
+            heap: BinaryHeap::new(),
+        };
+        for index in 0..packer.entries.len() {
+            packer.push(index);
+        }
+        packer
+    }
+
+    /// `index` and its ancestors still to be included
+    fn package(&self, index: usize) -> Vec<usize> {
+        let mut package = vec![index];
+        let mut next = 0;
+        while next < package.len() {
+            for &parent in &self.entries[package[next]].parents {
+                if !self.entries[parent].included && !package.contains(&parent) {
+                    package.push(parent);
+                }
+            }
+            next += 1;
+        }
+        package
+    }
+
+    fn totals(&self, package: &[usize]) -> (u64, u64, u64) {
+        package.iter().fold((0, 0, 0), |(fee, vsize, weight), &i| {
+            let entry = &self.entries[i];
+            (fee + entry.fee, vsize + entry.vsize, weight + entry.weight)
+        })
+    }
+
+    fn push(&mut self, index: usize) {
+        let (fee, vsize, _) = self.totals(&self.package(index));
+        self.heap.push(Candidate {
+            rate: fee as f64 / vsize.max(1) as f64,
+            generation: self.entries[index].generation,
+            index,
+        });
+    }
+
+    /// Rescores the descendants still to be included of a newly included package
+    fn update_descendants(&mut self, package: &[usize]) {
+        let mut descendants: Vec<usize> = Vec::new();
+        let mut next: Vec<usize> = package.to_vec();
+        while let Some(index) = next.pop() {
+            for &child in &self.entries[index].children {
+                if !self.entries[child].included && !descendants.contains(&child) {
+                    descendants.push(child);
+                    next.push(child);
+                }
+            }
+        }
+        for index in descendants {
+            self.entries[index].generation += 1;
+            self.push(index);
+        }
+    }
+
+    fn pack(mut self) -> Vec<BlockBuilder> {
+        let mut blocks = Vec::new();
+        let mut block = BlockBuilder::default();
+        let mut deferred = Vec::new();
+        while let Some(candidate) = self.heap.pop() {
+            let entry = &self.entries[candidate.index];
+            if entry.included || entry.generation != candidate.generation {
+                continue;
+            }
+            let package = self.package(candidate.index);
+            let (fee, vsize, weight) = self.totals(&package);
+            let last = blocks.len() + 1 == PROJECTED_BLOCKS;
+            if !last && block.weight + weight > BLOCK_TX_WEIGHT {
+                deferred.push(candidate.index);
+                let full = BLOCK_TX_WEIGHT - block.weight < 4_000;
+                if full || deferred.len() > MAX_FIT_FAILURES || self.heap.is_empty() {
+                    blocks.push(std::mem::take(&mut block));
+                    for index in deferred.drain(..) {
+                        self.push(index);
+                    }
+                }
+                continue;
+            }
+            let rate = fee as f64 / vsize.max(1) as f64;
+            for &index in &package {
+                let entry = &mut self.entries[index];
+                entry.included = true;
+                block.add(Member {
+                    txid: entry.txid,
+                    vsize: entry.vsize,
+                    weight: entry.weight,
+                    fee: entry.fee,
+                    rate,
+                });
+            }
+            self.update_descendants(&package);
+            if self.heap.is_empty() && !deferred.is_empty() {
+                blocks.push(std::mem::take(&mut block));
+                for index in deferred.drain(..) {
+                    self.push(index);
+                }
+            }
+        }
+        if !block.members.is_empty() {
+            blocks.push(block);
+        }
+        blocks
+    }
+}
+
+struct Projection {
+    blocks: Arc<Vec<ProjectedBlock>>,
+    /// Transactions of each block, to summarize them again when filtered
+    members: Vec<Vec<Member>>,
+}
+
+#[derive(Default)]
+pub struct MempoolProjection {
+    projection: RwLock<Option<Arc<Projection>>>,
+}
+
+impl MempoolProjection {
+    /// Reprojects the node's mempool every `interval`
+    pub async fn run(self: Arc<Self>, rpc: Arc<Rpc>, interval: Duration, health: Arc<Health>) {
+        health.register(HEALTH_COMPONENT, Severity::Soft, Some(interval * 3));
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match project(&rpc).await {
+                Ok(projection) => {
+                    *self.projection.write().expect("projection lock poisoned") =
+                        Some(Arc::new(projection));
+                    health.success(HEALTH_COMPONENT);
+                }
+                Err(e) => {
+                    warn!("Failed to project mempool blocks: {}", e);
+                    health.failure(HEALTH_COMPONENT, &e);
+                }
+            }
+        }
+    }
+
+    fn projection(&self) -> Option<Arc<Projection>> {
+        self.projection
+            .read()
+            .expect("projection lock poisoned")
+            .clone()
+    }
+
+    pub fn blocks(&self) -> Option<Arc<Vec<ProjectedBlock>>> {
+        self.projection()
+            .map(|projection| projection.blocks.clone())
+    }
+
+    /// The projected blocks summarizing only the transactions `keep` selects,
+    /// blocks without any left included
+    fn filtered(&self, keep: impl Fn(&Txid) -> bool) -> Option<Vec<ProjectedBlock>> {
+        let projection = self.projection()?;
+        let blocks = projection
+            .members
+            .iter()
+            .map(|members| {
+                let mut block = BlockBuilder::default();
+                for member in members.iter().filter(|member| keep(&member.txid)) {
+                    block.add(*member);
+                }
+                block.summary()
+            })
+            .collect();
+        Some(blocks)
+    }
+}
+
+async fn project(rpc: &Rpc) -> Result<Projection, bitcoincore_rpc::Error> {
+    let mempool: HashMap<Txid, VerboseEntry> = rpc.call("getrawmempool", &[json!(true)]).await?;
+    let blocks = Packer::new(mempool).pack();
+    Ok(Projection {
+        blocks: Arc::new(blocks.iter().map(BlockBuilder::summary).collect()),
+        members: blocks.into_iter().map(|block| block.members).collect(),
+    })
+}
+
+pub async fn get_mempool_blocks(
+    State(state): State<AppState>,
+    Query(query): Query<TxFilterQuery>,
+) -> impl IntoResponse {
+    let filter = match TxFilter::parse(&query) {
+        Ok(filter) => filter,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let blocks = if filter.is_empty() {
+        state
+            .mempool_blocks
+            .blocks()
+            .map(|blocks| Json(blocks.as_slice()).into_response())
+    } else {
+        state
+            .mempool_blocks
+            .filtered(|txid| {
+                state
+                    .mempool
+                    .get(txid)
+                    .is_some_and(|tx| filter.matches(&tx))
+            })
+            .map(|blocks| Json(blocks).into_response())
+    };
+    match blocks {
+        Some(blocks) => blocks,
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Mempool blocks not projected yet",
+        )
+            .into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden;
+    use bitcoincore_rpc::bitcoin::hashes::Hash;
+
+    fn txid(n: u8) -> Txid {
+        Txid::from_byte_array([n; 32])
+    }
+
+    fn entry(vsize: u64, fee: u64, depends: &[Txid]) -> VerboseEntry {
+        VerboseEntry {
+            vsize,
+            weight: vsize * 4,
+            fees: VerboseFees {
+                modified: Amount::from_sat(fee),
+            },
+            depends: depends.to_vec(),
+        }
+    }
+
+    fn pack(mempool: Vec<(Txid, VerboseEntry)>) -> Vec<BlockBuilder> {
+        Packer::new(mempool.into_iter().collect()).pack()
+    }
+
+    /// Txids of each block in packing order
+    fn order(blocks: &[BlockBuilder]) -> Vec<Vec<Txid>> {
+        blocks
+            .iter()
+            .map(|block| block.members.iter().map(|member| member.txid).collect())
+            .collect()
+    }
+
+    #[test]
+    fn children_pay_for_their_parents() {
+        let (parent, child, other) = (txid(1), txid(2), txid(3));
+        let blocks = pack(vec![
+            (parent, entry(100, 100, &[])),
+            (child, entry(100, 1900, &[parent])),
+            (other, entry(100, 500, &[])),
+        ]);
+        // The 10 sat/vB package goes before the 5 sat/vB transaction
+        assert_eq!(order(&blocks), [vec![child, parent, other]]);
+        let block = blocks[0].summary();
+        assert_eq!(block.vsize, 300);
+        assert_eq!(block.tx_count, 3);
+        assert_eq!(block.total_fees, 2500);
+        assert_eq!(block.median_fee, 10.0);
+        assert_eq!(block.fee_range, [5.0, 5.0, 5.0, 10.0, 10.0, 10.0, 10.0]);
+    }
+
+    #[test]
+    fn rescores_descendants_of_included_parents() {
+        let (parent, child, other) = (txid(1), txid(2), txid(3));
+        let blocks = pack(vec![
+            (parent, entry(100, 2000, &[])),
+            (child, entry(100, 300, &[parent])),
+            (other, entry(100, 500, &[])),
+        ]);
+        // Once its parent is in, the child only has its own 3 sat/vB
+        assert_eq!(order(&blocks), [vec![parent, other, child]]);
+        assert_eq!(
+            blocks[0].summary().fee_range,
+            [3.0, 3.0, 3.0, 5.0, 5.0, 5.0, 20.0]
+        );
+    }
+
+    #[test]
+    fn fills_blocks_by_weight_and_leaves_the_rest_to_the_last() {
+        // A quarter of a block each, 3 of which fit next to the coinbase
+        let mempool: Vec<_> = (1..=30)
+            .map(|n| (txid(n), entry(250_000, 250_000 * n as u64, &[])))
+            .collect();
+        let blocks = pack(mempool);
+        let counts: Vec<usize> = blocks.iter().map(|block| block.members.len()).collect();
+        assert_eq!(counts, [3, 3, 3, 3, 3, 3, 3, 9]);
+        assert_eq!(order(&blocks)[0], [txid(30), txid(29), txid(28)]);
+        assert_eq!(blocks[0].summary().median_fee, 29.0);
+        assert_eq!(blocks[7].summary().vsize, 2_250_000);
+    }
+
+    #[test]
+    fn filtered_projections_keep_the_packing() {
+        let mempool: Vec<_> = (1..=7)
+            .map(|n| (txid(n), entry(250_000, 250_000 * n as u64, &[])))
+            .collect();
+        let blocks = pack(mempool);
+        let projection = MempoolProjection::default();
+        *projection.projection.write().unwrap() = Some(Arc::new(Projection {
+            blocks: Arc::new(blocks.iter().map(BlockBuilder::summary).collect()),
+            members: blocks.into_iter().map(|block| block.members).collect(),
+        }));
+
+        let even = projection
+            .filtered(|txid| txid.as_byte_array()[0] % 2 == 0)
+            .unwrap();
+        let counts: Vec<usize> = even.iter().map(|block| block.tx_count).collect();
+        assert_eq!(counts, [1, 2, 0]);
+        // 4 and 2 sat/vB of the second block's 4, 3 and 2
+        assert_eq!(even[1].fee_range, [2.0, 2.0, 2.0, 2.0, 2.0, 2.0, 4.0]);
+        assert_eq!(projection.blocks().unwrap()[1].tx_count, 3);
+    }
+
+    #[test]
+    fn golden_mempool_blocks() {
+        let mut block = BlockBuilder::default();
+        let fees = [3525, 3737, 3948, 4289, 4970, 8460, 72241];
+        for (n, fee) in (1..).zip(fees) {
+            block.add(Member {
+                txid: txid(n),
+                vsize: 141,
+                weight: 561,
+                fee,
+                rate: fee as f64 / 141.0,
+            });
+        }
+        golden::check("mempool_blocks", &[block.summary()]);
+    }
+}
@@ -0,0 +1,248 @@
+//! Merkle inclusion proofs of confirmed transactions, for SPV verifiers.
+//!
+//! Both formats are built from the txid list of the containing block: the
+//! esplora/Electrum branch of sibling hashes, and BIP37's `merkleblock`.
+
+use std::str::FromStr;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use bitcoincore_rpc::bitcoin::consensus::encode::serialize_hex;
+use bitcoincore_rpc::bitcoin::hashes::{sha256d, Hash, HashEngine};
+use bitcoincore_rpc::bitcoin::{block, MerkleBlock, TxMerkleNode, Txid};
+use serde::Serialize;
+use tracing::warn;
+
+use crate::rpc::Rpc;
+use crate::tx::{hinted_block, BlockHint, BlockHintQuery};
+use crate::AppState;
+
+/// Block containing a confirmed transaction
+struct Containing {
+    height: u64,
+    header: block::Header,
+    txids: Vec<Txid>,
+}
+
+/// The block of `txid`, `None` while it's unconfirmed
+async fn containing_block(
+    rpc: &Rpc,
+    txid: &Txid,
+    hint: Option<&BlockHint>,
+) -> Result<Option<Containing>, bitcoincore_rpc::Error> {
+    let block_hash = hinted_block(rpc, hint).await?;
+    let Some(hash) = rpc
+        .get_raw_transaction_info(txid, block_hash.as_ref())
+        .await?
+        .blockhash
+    else {
+        return Ok(None);
+    };
+    let info = rpc.get_block_info(&hash).await?;
+    Ok(Some(Containing {
+        height: info.height as u64,
+        header: rpc.get_block_header(&hash).await?,
+        txids: info.tx,
+    }))
+}
+
+#[derive(Serialize)]
+struct MerkleProof {
+    block_height: u64,
+    /// Sibling hashes from the transaction up to the root, in txid byte order
+    merkle: Vec<TxMerkleNode>,
+    /// Position of the transaction in the block
+    pos: usize,
+}
+
+/// Sibling hashes on the path from `txids[pos]` to the merkle root
+pub fn merkle_branch(txids: &[Txid], mut pos: usize) -> Vec<TxMerkleNode> {
+    let mut level: Vec<TxMerkleNode> = txids
+        .iter()
+        .map(|txid| TxMerkleNode::from_raw_hash(txid.to_raw_hash()))
+        .collect();
+    let mut branch = Vec::new();
+    while level.len() > 1 {
+        // Odd levels pair their last hash with itself
+        if level.len() % 2 == 1 {
+            level.push(*level.last().expect("level is not empty"));
+        }
+        branch.push(level[pos ^ 1]);
+        level = level
+            .chunks(2)
+            .map(|pair| {
+                let mut engine = sha256d::Hash::engine();
+                engine.input(pair[0].as_byte_array());
+                engine.input(pair[1].as_byte_array());
+                TxMerkleNode::from_raw_hash(sha256d::Hash::from_engine(engine))
+            })
+            .collect();
+        pos /= 2;
+    }
+    branch
+}
+
+/// Runs `build` on the block of `txid`, answering 404 for unknown and unconfirmed
+/// transactions
+async fn with_containing_block<T>(
+    state: &AppState,
+    txid: &str,
+    hint: BlockHintQuery,
+    build: impl FnOnce(&Txid, Containing) -> T,
+) -> Result<T, (StatusCode, &'static str)> {
+    let Ok(parsed) = Txid::from_str(txid) else {
+        return Err((StatusCode::BAD_REQUEST, "Invalid txid"));
+    };
+    let hint = hint.parse()?;
+    match containing_block(&state.rpc, &parsed, hint.as_ref()).await {
+        Ok(Some(block)) => Ok(build(&parsed, block)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Transaction not confirmed")),
+        Err(e) => {
+            warn!("Failed to get block of transaction {}: {}", txid, e);
+            Err((StatusCode::NOT_FOUND, "Transaction not found"))
+        }
+    }
+}
+
+pub async fn get_merkle_proof(
+    State(state): State<AppState>,
+    Path(txid): Path<String>,
+    Query(hint): Query<BlockHintQuery>,
+) -> impl IntoResponse {
+    let proof = with_containing_block(&state, &txid, hint, |txid, block| {
+        let pos = block.txids.iter().position(|id| id == txid)?;
+        Some(MerkleProof {
+            block_height: block.height,
+            merkle: merkle_branch(&block.txids, pos),
+            pos,
+        })
+    })
+    .await;
+    match proof {
+        Ok(Some(proof)) => Json(proof).into_response(),
+        Ok(None) => {
+            warn!("Transaction {} missing from its block", txid);
+            (StatusCode::INTERNAL_SERVER_ERROR, "RPC error").into_response()
+        }
+        Err(response) => response.into_response(),
+    }
+}
+
+/// BIP37 `merkleblock` of the transaction as hex, like `gettxoutproof`
+pub async fn get_merkleblock_proof(
+    State(state): State<AppState>,
+    Path(txid): Path<String>,
+    Query(hint): Query<BlockHintQuery>,
+) -> impl IntoResponse {
+    let proof = with_containing_block(&state, &txid, hint, |txid, block| {
+        let merkle_block =
+            MerkleBlock::from_header_txids_with_predicate(&block.header, &block.txids, |id| {
+                id == txid
+            });
+        serialize_hex(&merkle_block)
+    })
+    .await;
+    match proof {
+        Ok(hex) => ([(header::CONTENT_TYPE, "text/plain")], hex).into_response(),
+        Err(response) => response.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden;
+    use bitcoincore_rpc::bitcoin::merkle_tree;
+
+    fn txid(hex: &str) -> Txid {
+        Txid::from_str(hex).unwrap()
+    }
+
+    fn combine(left: &TxMerkleNode, right: &TxMerkleNode) -> TxMerkleNode {
+        let mut engine = sha256d::Hash::engine();
+        engine.input(left.as_byte_array());
+        engine.input(right.as_byte_array());
+        TxMerkleNode::from_raw_hash(sha256d::Hash::from_engine(engine))
+    }
+
+    /// Merkle root reached from `txid` at `pos` with `branch`, as an SPV client does
+    fn fold(txid: Txid, mut pos: usize, branch: &[TxMerkleNode]) -> TxMerkleNode {
+        let mut node = TxMerkleNode::from_raw_hash(txid.to_raw_hash());
+        for sibling in branch {
+            node = if pos.is_multiple_of(2) {
+                combine(&node, sibling)
+            } else {
+                combine(sibling, &node)
+            };
+            pos /= 2;
+        }
+        node
+    }
+
+    #[test]
+    fn branches_lead_to_the_root_of_block_100000() {
+        let txids = [
+            txid("8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87"),
+            txid("fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4"),
+            txid("6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4"),
+            txid("e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d"),
+        ];
+        let root = TxMerkleNode::from_str(
+            "f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766",
+        )
+        .unwrap();
+        for (pos, txid) in txids.iter().enumerate() {
+            let branch = merkle_branch(&txids, pos);
+            assert_eq!(branch.len(), 2);
+            assert_eq!(fold(*txid, pos, &branch), root);
+        }
+        assert_eq!(
+            merkle_branch(&txids, 0)[0],
+            TxMerkleNode::from_raw_hash(txids[1].to_raw_hash())
+        );
+    }
+
+    #[test]
+    fn odd_levels_duplicate_their_last_hash() {
+        let txids: Vec<Txid> = (1..=5u8).map(|i| Txid::from_byte_array([i; 32])).collect();
+        let root = merkle_tree::calculate_root(txids.iter().map(|txid| txid.to_raw_hash()))
+            .map(TxMerkleNode::from_raw_hash)
+            .unwrap();
+        for (pos, txid) in txids.iter().enumerate() {
+            assert_eq!(fold(*txid, pos, &merkle_branch(&txids, pos)), root);
+        }
+        // The fifth txid is paired with itself, then with the hash of that pair
+        let last = TxMerkleNode::from_raw_hash(txids[4].to_raw_hash());
+        let branch = merkle_branch(&txids, 4);
+        assert_eq!(branch.len(), 3);
+        assert_eq!(branch[0], last);
+        assert_eq!(branch[1], combine(&last, &last));
+    }
+
+    #[test]
+    fn a_lone_coinbase_has_an_empty_branch() {
+        let txids = [Txid::from_byte_array([1; 32])];
+        assert!(merkle_branch(&txids, 0).is_empty());
+    }
+
+    #[test]
+    fn golden_tx_merkle_proof() {
+        let txids = [
+            txid("a1075db55d416d3ca199f55b6084e2115b9345e16c5cf302fc80e9d5fbf5d48d"),
+            txid("f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16"),
+            txid("8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87"),
+        ];
+        golden::check(
+            "tx_merkle_proof",
+            &MerkleProof {
+                block_height: 840000,
+                merkle: merkle_branch(&txids, 1),
+                pos: 1,
+            },
+        );
+    }
+}
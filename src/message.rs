@@ -0,0 +1,330 @@
+//! Verification of signed messages proving control of an address.
+//!
+//! Two signature formats are accepted, both base64 encoded:
+//! - BIP137, the 65-byte recoverable signature of Bitcoin Core's
+//!   `signmessage`. The header byte names the address type, but wallets
+//!   disagree on it for segwit addresses, so any P2PKH, P2SH-P2WPKH or P2WPKH
+//!   address of the recovered key is accepted, like Electrum does.
+//! - BIP322 "simple", the witness stack spending the address's `to_spend`
+//!   transaction. Only P2WPKH and key path P2TR addresses can be checked
+//!   without a script interpreter, so others are rejected.
+//!
+//! Everything is checked locally, without the node's wallet.
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Json;
+use bitcoincore_rpc::bitcoin::absolute::LockTime;
+use bitcoincore_rpc::bitcoin::base64::prelude::{Engine as _, BASE64_STANDARD};
+use bitcoincore_rpc::bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoincore_rpc::bitcoin::opcodes::all::OP_RETURN;
+use bitcoincore_rpc::bitcoin::opcodes::OP_0;
+use bitcoincore_rpc::bitcoin::script::Builder;
+use bitcoincore_rpc::bitcoin::secp256k1::{Message, Secp256k1, XOnlyPublicKey};
+use bitcoincore_rpc::bitcoin::sighash::{Prevouts, SighashCache};
+use bitcoincore_rpc::bitcoin::sign_message::{signed_msg_hash, MessageSignature};
+use bitcoincore_rpc::bitcoin::transaction::Version;
+use bitcoincore_rpc::bitcoin::{
+    consensus, ecdsa, taproot, Address, Amount, EcdsaSighashType, OutPoint, PublicKey, ScriptBuf,
+    Sequence, Transaction, TxIn, TxOut, Txid, Witness,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::addresses::parse_address;
+use crate::AppState;
+
+/// Tag of the BIP340 tagged hash committing to the message in BIP322
+const BIP322_TAG: &[u8] = b"BIP0322-signed-message";
+
+#[derive(Deserialize, ToSchema)]
+pub struct VerifyMessageRequest {
+    address: String,
+    message: String,
+    /// Base64 BIP137 signature or BIP322 simple witness
+    signature: String,
+}
+
+#[derive(Serialize)]
+struct MessageVerification {
+    valid: bool,
+    /// `bip137` or `bip322`, the format the signature was read as
+    format: &'static str,
+}
+
+/// Whether a 65-byte signature recovers to a key of `address`
+fn verify_bip137(address: &Address, message: &str, mut signature: [u8; 65]) -> bool {
+    // Segwit headers (35-42) are the compressed key headers (31-34) shifted up
+    if (35..=42).contains(&signature[0]) {
+        signature[0] = 31 + (signature[0] - 35) % 4;
+    }
+    let Ok(signature) = MessageSignature::from_slice(&signature) else {
+        return false;
+    };
+    let secp = Secp256k1::verification_only();
+    let Ok(pubkey) = signature.recover_pubkey(&secp, signed_msg_hash(message)) else {
+        return false;
+    };
+    let script_pubkey = address.script_pubkey();
+    if script_pubkey == ScriptBuf::new_p2pkh(&pubkey.pubkey_hash()) {
+        return true;
+    }
+    let Ok(wpubkey_hash) = pubkey.wpubkey_hash() else {
+        return false;
+    };
+    let p2wpkh = ScriptBuf::new_p2wpkh(&wpubkey_hash);
+    script_pubkey == ScriptBuf::new_p2sh(&p2wpkh.script_hash()) || script_pubkey == p2wpkh
+}
+
+/// BIP322's `to_sign` transaction for `message` signed by `script_pubkey`
+fn to_sign(script_pubkey: &ScriptBuf, message: &str, witness: Witness) -> Transaction {
+    let tag = sha256::Hash::hash(BIP322_TAG);
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_ref());
+    engine.input(tag.as_ref());
+    engine.input(message.as_bytes());
+    let message_hash = sha256::Hash::from_engine(engine);
+
+    let to_spend = Transaction {
+        version: Version(0),
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(Txid::all_zeros(), u32::MAX),
+            script_sig: Builder::new()
+                .push_opcode(OP_0)
+                .push_slice(message_hash.to_byte_array())
+                .into_script(),
+            sequence: Sequence::ZERO,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: Amount::ZERO,
+            script_pubkey: script_pubkey.clone(),
+        }],
+    };
+    Transaction {
+        version: Version(0),
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(to_spend.compute_txid(), 0),
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ZERO,
+            witness,
+        }],
+        output: vec![TxOut {
+            value: Amount::ZERO,
+            script_pubkey: Builder::new().push_opcode(OP_RETURN).into_script(),
+        }],
+    }
+}
+
+/// Checks a P2WPKH witness, `[signature, pubkey]` signing with `SIGHASH_ALL`
+fn verify_p2wpkh(script_pubkey: &ScriptBuf, message: &str, witness: Witness) -> Option<()> {
+    let [signature, pubkey] = witness.to_vec().try_into().ok()?;
+    let signature = ecdsa::Signature::from_slice(&signature).ok()?;
+    let pubkey = PublicKey::from_slice(&pubkey).ok()?;
+    if signature.sighash_type != EcdsaSighashType::All
+        || ScriptBuf::new_p2wpkh(&pubkey.wpubkey_hash().ok()?) != *script_pubkey
+    {
+        return None;
+    }
+    let to_sign = to_sign(script_pubkey, message, witness);
+    let sighash = SighashCache::new(&to_sign)
+        .p2wpkh_signature_hash(0, script_pubkey, Amount::ZERO, signature.sighash_type)
+        .ok()?;
+    let message = Message::from_digest(sighash.to_byte_array());
+    Secp256k1::verification_only()
+        .verify_ecdsa(&message, &signature.signature, &pubkey.inner)
+        .ok()
+}
+
+/// Checks a key path P2TR witness, a lone Schnorr signature
+fn verify_p2tr(script_pubkey: &ScriptBuf, message: &str, witness: Witness) -> Option<()> {
+    let [signature] = witness.to_vec().try_into().ok()?;
+    let signature = taproot::Signature::from_slice(&signature).ok()?;
+    // The output key follows OP_1 and its 32 byte push
+    let output_key = XOnlyPublicKey::from_slice(&script_pubkey.as_bytes()[2..]).ok()?;
+    let to_sign = to_sign(script_pubkey, message, witness);
+    let prevouts = [TxOut {
+        value: Amount::ZERO,
+        script_pubkey: script_pubkey.clone(),
+    }];
+    let sighash = SighashCache::new(&to_sign)
+        .taproot_key_spend_signature_hash(0, &Prevouts::All(&prevouts), signature.sighash_type)
+        .ok()?;
+    let message = Message::from_digest(sighash.to_byte_array());
+    Secp256k1::verification_only()
+        .verify_schnorr(&signature.signature, &message, &output_key)
+        .ok()
+}
+
+/// Whether `witness` spends the `to_spend` output of `address`, `None` when the
+/// address type can't be checked
+fn verify_bip322(address: &Address, message: &str, witness: Witness) -> Option<bool> {
+    let script_pubkey = address.script_pubkey();
+    if script_pubkey.is_p2wpkh() {
+        Some(verify_p2wpkh(&script_pubkey, message, witness).is_some())
+    } else if script_pubkey.is_p2tr() {
+        Some(verify_p2tr(&script_pubkey, message, witness).is_some())
+    } else {
+        None
+    }
+}
+
+pub async fn post_verify_message(
+    State(state): State<AppState>,
+    Json(request): Json<VerifyMessageRequest>,
+) -> impl IntoResponse {
+    let address = match parse_address(&state, &request.address) {
+        Ok(address) => address,
+        Err(e) => return e.into_response(),
+    };
+    let Ok(signature) = BASE64_STANDARD.decode(request.signature.trim()) else {
+        return (StatusCode::BAD_REQUEST, "Invalid base64 signature").into_response();
+    };
+    let verification = match <[u8; 65]>::try_from(signature.as_slice()) {
+        Ok(signature) if (27..=42).contains(&signature[0]) => MessageVerification {
+            valid: verify_bip137(&address, &request.message, signature),
+            format: "bip137",
+        },
+        _ => {
+            let Ok(witness) = consensus::deserialize::<Witness>(&signature) else {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    "Signature is neither BIP137 nor a BIP322 witness",
+                )
+                    .into_response();
+            };
+            let Some(valid) = verify_bip322(&address, &request.message, witness) else {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    "BIP322 signatures are only verified for P2WPKH and P2TR addresses",
+                )
+                    .into_response();
+            };
+            MessageVerification {
+                valid,
+                format: "bip322",
+            }
+        }
+    };
+    Json(verification).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::golden;
+    use bitcoincore_rpc::bitcoin::secp256k1::SecretKey;
+    use bitcoincore_rpc::bitcoin::{CompressedPublicKey, Network};
+
+    fn address(address: &str) -> Address {
+        Address::from_str(address).unwrap().assume_checked()
+    }
+
+    fn witness(base64: &str) -> Witness {
+        consensus::deserialize(&BASE64_STANDARD.decode(base64).unwrap()).unwrap()
+    }
+
+    // Test vectors of BIP322
+    const P2WPKH: &str = "bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l";
+    const P2TR: &str = "bc1ppv609nr0vr25u07u95waq5lucwfm6tde4nydujnu8npg4q75mr5sxq8lt3";
+
+    #[test]
+    fn verifies_bip322_p2wpkh_vectors() {
+        let empty = witness("AkcwRAIgM2gBAQqvZX15ZiysmKmQpDrG83avLIT492QBzLnQIxYCIBaTpOaD20qRlEylyxFSeEA2ba9YOixpX8z46TSDtS40ASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=");
+        let hello = witness("AkcwRAIgZRfIY3p7/DoVTty6YZbWS71bc5Vct9p9Fia83eRmw2QCICK/ENGfwLtptFluMGs2KsqoNSk89pO7F29zJLUx9a/sASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=");
+        assert_eq!(
+            verify_bip322(&address(P2WPKH), "", empty.clone()),
+            Some(true)
+        );
+        assert_eq!(
+            verify_bip322(&address(P2WPKH), "Hello World", hello.clone()),
+            Some(true)
+        );
+        // Each signature only covers its own message
+        assert_eq!(
+            verify_bip322(&address(P2WPKH), "Hello World", empty),
+            Some(false)
+        );
+        assert_eq!(verify_bip322(&address(P2WPKH), "", hello), Some(false));
+    }
+
+    #[test]
+    fn verifies_bip322_p2tr_vector() {
+        let signature = witness("AUHd69PrJQEv+oKTfZ8l+WROBHuy9HKrbFCJu7U1iK2iiEy1vMU5EfMtjc+VSHM7aU0SDbak5IUZRVno2P5mjSafAQ==");
+        assert_eq!(
+            verify_bip322(&address(P2TR), "Hello World", signature.clone()),
+            Some(true)
+        );
+        assert_eq!(
+            verify_bip322(&address(P2TR), "Hello world", signature),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn rejects_bip322_for_other_address_types() {
+        let signature = witness("AUHd69PrJQEv+oKTfZ8l+WROBHuy9HKrbFCJu7U1iK2iiEy1vMU5EfMtjc+VSHM7aU0SDbak5IUZRVno2P5mjSafAQ==");
+        let p2pkh = address("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa");
+        assert_eq!(verify_bip322(&p2pkh, "Hello World", signature), None);
+    }
+
+    /// `signmessage` signature of `message` by `secret`, with `header_base` the
+    /// header for recovery id 0 of the address type
+    fn bip137(secret: &SecretKey, message: &str, header_base: u8) -> [u8; 65] {
+        let secp = Secp256k1::new();
+        let digest = Message::from_digest(signed_msg_hash(message).to_byte_array());
+        let (recovery_id, compact) = secp
+            .sign_ecdsa_recoverable(&digest, secret)
+            .serialize_compact();
+        let mut signature = [0; 65];
+        signature[0] = header_base + recovery_id.to_i32() as u8;
+        signature[1..].copy_from_slice(&compact);
+        signature
+    }
+
+    #[test]
+    fn accepts_bip137_signatures_for_every_address_of_the_key() {
+        let secret = SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let pubkey = CompressedPublicKey::from_private_key(
+            &Secp256k1::new(),
+            &bitcoincore_rpc::bitcoin::PrivateKey::new(secret, Network::Bitcoin),
+        )
+        .unwrap();
+        let addresses = [
+            Address::p2pkh(pubkey, Network::Bitcoin),
+            Address::p2shwpkh(&pubkey, Network::Bitcoin),
+            Address::p2wpkh(&pubkey, Network::Bitcoin),
+        ];
+        // Compressed P2PKH, P2SH-P2WPKH and P2WPKH headers
+        for header_base in [31, 35, 39] {
+            let signature = bip137(&secret, "minipool", header_base);
+            for address in &addresses {
+                assert!(verify_bip137(address, "minipool", signature));
+                assert!(!verify_bip137(address, "minipool!", signature));
+            }
+        }
+        let other = address("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa");
+        assert!(!verify_bip137(
+            &other,
+            "minipool",
+            bip137(&secret, "minipool", 31)
+        ));
+    }
+
+    #[test]
+    fn golden_verify_message() {
+        let hello = witness("AkcwRAIgZRfIY3p7/DoVTty6YZbWS71bc5Vct9p9Fia83eRmw2QCICK/ENGfwLtptFluMGs2KsqoNSk89pO7F29zJLUx9a/sASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=");
+        golden::check(
+            "verify_message",
+            &MessageVerification {
+                valid: verify_bip322(&address(P2WPKH), "Hello World", hello).unwrap(),
+                format: "bip322",
+            },
+        );
+    }
+}
@@ -1,8 +1,10 @@
 use anyhow::Result;
 use axum::Router;
 use axum::{response::IntoResponse, routing::get};
+use std::collections::BTreeSet;
 use std::future::ready;
 use std::net::SocketAddr;
+use std::sync::Mutex;
 use std::time::Instant;
 
 use axum::extract::{MatchedPath, Request};
@@ -22,34 +24,74 @@ fn setup_metrics_recorder() -> anyhow::Result<PrometheusHandle> {
     const EXPONENTIAL_SECONDS: &[f64] = &[
         0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
     ];
+    // Block delays are bounded below by the chain poll interval
+    const PROPAGATION_SECONDS: &[f64] = &[
+        0.0, 5.0, 10.0, 20.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1800.0, 3600.0,
+    ];
 
     Ok(PrometheusBuilder::new()
         .set_buckets_for_metric(
             Matcher::Full("http_requests_duration_seconds".to_string()),
             EXPONENTIAL_SECONDS,
         )?
+        .set_buckets_for_metric(
+            Matcher::Full("outbound_requests_duration_seconds".to_string()),
+            EXPONENTIAL_SECONDS,
+        )?
+        .set_buckets_for_metric(
+            Matcher::Full("block_propagation_delay_seconds".to_string()),
+            PROPAGATION_SECONDS,
+        )?
         .install_recorder()?)
 }
 
+/// Distinct values kept per request label, well above the number of routes
+const MAX_LABEL_VALUES: usize = 256;
+
+/// Label of requests that matched no route, so random paths share one series
+const UNMATCHED: &str = "unmatched";
+
+/// Label of values past `MAX_LABEL_VALUES`
+const OVERFLOW: &str = "other";
+
+static PATH_LABELS: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+static METHOD_LABELS: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// `value` if it's already a label value or there's room for another one,
+/// `OVERFLOW` otherwise, counting the dropped value
+fn capped(seen: &Mutex<BTreeSet<String>>, label: &'static str, value: String) -> String {
+    let mut seen = seen.lock().expect("metric labels lock poisoned");
+    if seen.contains(&value) {
+        return value;
+    }
+    if seen.len() < MAX_LABEL_VALUES {
+        seen.insert(value.clone());
+        return value;
+    }
+    metrics::counter!("metrics_label_values_dropped_total", "label" => label).increment(1);
+    OVERFLOW.to_string()
+}
+
+/// Route template of a request for metric labels, `unmatched` without one
+pub fn path_label(req: &Request) -> String {
+    let path = match req.extensions().get::<MatchedPath>() {
+        Some(matched_path) => matched_path.as_str().to_owned(),
+        None => UNMATCHED.to_string(),
+    };
+    capped(&PATH_LABELS, "path", path)
+}
+
 pub async fn track_metrics(req: Request, next: Next) -> impl IntoResponse {
     let start = Instant::now();
-    let path = if let Some(matched_path) = req.extensions().get::<MatchedPath>() {
-        matched_path.as_str().to_owned()
-    } else {
-        req.uri().path().to_owned()
-    };
-    let method = req.method().clone();
+    let path = path_label(&req);
+    let method = capped(&METHOD_LABELS, "method", req.method().to_string());
 
     let response = next.run(req).await;
 
     let latency = start.elapsed().as_secs_f64();
     let status = response.status().as_u16().to_string();
 
-    let labels = [
-        ("method", method.to_string()),
-        ("path", path),
-        ("status", status),
-    ];
+    let labels = [("method", method), ("path", path), ("status", status)];
 
     metrics::counter!("http_requests_total", &labels).increment(1);
     metrics::histogram!("http_requests_duration_seconds", &labels).record(latency);
@@ -0,0 +1,167 @@
+//! Versioned schema migrations of the persistent indexes.
+//!
+//! Every store records the schema version it was written with in its `meta`
+//! table. At startup a store behind the current version is upgraded in place by
+//! running the missing migrations in order, each written atomically together
+//! with the version it reaches, so an interrupted upgrade resumes at the step
+//! that didn't finish instead of forcing a reindex. A new store is stamped with
+//! the current version right away. With `NO_MIGRATE` a store that needs
+//! upgrading stops startup instead, and a store written by a newer minipool
+//! always does.
+
+use std::time::Instant;
+
+use anyhow::{bail, Context, Result};
+use tracing::{info, warn};
+
+use crate::storage::{decode_height, height_key, Batch, Store, Table};
+
+/// Schema version and other facts about the store as a whole
+pub const META: Table = "meta";
+
+const VERSION_KEY: &[u8] = b"schema_version";
+
+const START_HEIGHT_KEY: &[u8] = b"start_height";
+
+/// Big-endian height → block hash table every index keeps
+const BLOCKS: Table = "blocks";
+
+/// A step from one schema version to the next
+pub struct Migration {
+    /// What the step changes, for the logs
+    pub description: &'static str,
+    /// Reads the store at the previous version and returns the writes bringing
+    /// it to the next one
+    pub run: fn(&dyn Store) -> Result<Batch>,
+}
+
+/// Version 1 of both indexes: records the height indexing started from, taken
+/// from the lowest indexed block, so the configured start height can change
+/// without corrupting reorg handling
+pub const RECORD_START_HEIGHT: Migration = Migration {
+    description: "record the start height",
+    run: |store| {
+        let mut first = None;
+        store.scan(
+            BLOCKS,
+            &height_key(0),
+            &height_key(u64::MAX),
+            false,
+            &mut |key, _| {
+                first = Some(decode_height(key)?);
+                Ok(false)
+            },
+        )?;
+        let mut batch = Batch::default();
+        if let Some(height) = first {
+            batch.put(META, START_HEIGHT_KEY, &height.to_be_bytes());
+        }
+        Ok(batch)
+    },
+};
+
+fn read_u64(store: &dyn Store, key: &[u8]) -> Result<Option<u64>> {
+    match store.get(META, key)? {
+        Some(value) => Ok(Some(u64::from_be_bytes(
+            value
+                .as_slice()
+                .try_into()
+                .context("Corrupt store metadata")?,
+        ))),
+        None => Ok(None),
+    }
+}
+
+/// Brings the store of the index `name` to the version reached by the last of
+/// `migrations`, or fails without touching it when it's behind and `allowed` is
+/// false. A store without a version is new when `tables` are all empty and
+/// predates versioning otherwise.
+pub fn migrate(
+    store: &dyn Store,
+    name: &str,
+    tables: &[Table],
+    migrations: &[Migration],
+    allowed: bool,
+) -> Result<()> {
+    let current = migrations.len() as u64;
+    let version = match read_u64(store, VERSION_KEY)? {
+        Some(version) => version,
+        None => {
+            let mut empty = true;
+            for &table in tables {
+                empty &= store.last(table)?.is_none();
+            }
+            if empty {
+                let mut batch = Batch::default();
+                batch.put(META, VERSION_KEY, &current.to_be_bytes());
+                return store.write(batch, true);
+            }
+            0
+        }
+    };
+    if version > current {
+        bail!(
+            "The {} is at schema version {}, written by a newer minipool that knows up to {}; upgrade minipool or delete the index to rebuild it",
+            name,
+            version,
+            current
+        );
+    }
+    if version == current {
+        return Ok(());
+    }
+    if !allowed {
+        bail!(
+            "The {} needs migrating from schema version {} to {}; back it up and start without NO_MIGRATE to upgrade it",
+            name,
+            version,
+            current
+        );
+    }
+    info!(
+        "Migrating {} from schema version {} to {}",
+        name, version, current
+    );
+    for (step, migration) in migrations.iter().enumerate().skip(version as usize) {
+        let to = step as u64 + 1;
+        let started = Instant::now();
+        info!(
+            "Migrating {} to schema version {}/{}: {}",
+            name, to, current, migration.description
+        );
+        let mut batch = (migration.run)(store)
+            .with_context(|| format!("Failed to migrate {} to schema version {}", name, to))?;
+        batch.put(META, VERSION_KEY, &to.to_be_bytes());
+        store.write(batch, true)?;
+        info!(
+            "Migrated {} to schema version {} in {:.1?}",
+            name,
+            to,
+            started.elapsed()
+        );
+    }
+    Ok(())
+}
+
+/// Height the index was built from, recording `configured` for a new index.
+/// An index keeps the height it was built from, as blocks below it were never
+/// indexed.
+pub fn start_height(store: &dyn Store, name: &str, configured: u64) -> Result<u64> {
+    match read_u64(store, START_HEIGHT_KEY)? {
+        Some(recorded) => {
+            if recorded != configured {
+                warn!(
+                    "The {} was built from height {}, keeping it instead of the configured {}; delete the index to rebuild it from there",
+                    name, recorded, configured
+                );
+            }
+            Ok(recorded)
+        }
+        None => {
+            let mut batch = Batch::default();
+            batch.put(META, START_HEIGHT_KEY, &configured.to_be_bytes());
+            store.write(batch, true)?;
+            Ok(configured)
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! The fee rate below which the node turns transactions away.
+//!
+//! A full mempool raises `mempoolminfee` above `minrelaytxfee` as it purges
+//! its cheapest transactions. Sampling it shows whether the purge rate is
+//! climbing or easing off, which a single reading doesn't.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use bitcoincore_rpc::bitcoin::Amount;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::health::{Health, Severity};
+use crate::json;
+use crate::rpc::Rpc;
+use crate::AppState;
+
+const HEALTH_COMPONENT: &str = "min_fee";
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(300);
+
+/// A day of samples
+const RETAINED_SAMPLES: usize = 288;
+
+#[derive(Clone, Copy, Serialize)]
+struct Sample {
+    /// Seconds since epoch
+    timestamp: u64,
+    /// `mempoolminfee` in sat/vB
+    #[serde(serialize_with = "json::rate")]
+    mempool_min_fee: f64,
+}
+
+#[derive(Default)]
+pub struct MinFeeTracker {
+    samples: RwLock<VecDeque<Sample>>,
+}
+
+/// Converts a BTC/kvB rate into sat/vB
+fn sat_vb(rate: Amount) -> f64 {
+    rate.to_sat() as f64 / 1000.0
+}
+
+impl MinFeeTracker {
+    /// Samples the node's minimum mempool fee rate every five minutes
+    pub async fn run(self: Arc<Self>, rpc: Arc<Rpc>, health: Arc<Health>) {
+        health.register(HEALTH_COMPONENT, Severity::Soft, Some(SAMPLE_INTERVAL * 3));
+        let mut ticker = tokio::time::interval(SAMPLE_INTERVAL);
+        loop {
+            ticker.tick().await;
+            match rpc.get_mempool_info().await {
+                Ok(info) => {
+                    let timestamp = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(0, |since| since.as_secs());
+                    let mut samples = self.samples.write().expect("min fee lock poisoned");
+                    if samples.len() == RETAINED_SAMPLES {
+                        samples.pop_front();
+                    }
+                    samples.push_back(Sample {
+                        timestamp,
+                        mempool_min_fee: sat_vb(info.mempool_min_fee),
+                    });
+                    health.success(HEALTH_COMPONENT);
+                }
+                Err(e) => {
+                    warn!("Failed to sample mempool minimum fee: {}", e);
+                    health.failure(HEALTH_COMPONENT, &e);
+                }
+            }
+        }
+    }
+
+    fn samples(&self) -> Vec<Sample> {
+        let samples = self.samples.read().expect("min fee lock poisoned");
+        samples.iter().copied().collect()
+    }
+}
+
+#[derive(Serialize)]
+struct MinFee {
+    /// Lowest fee rate the mempool currently accepts, in sat/vB
+    #[serde(serialize_with = "json::rate")]
+    mempool_min_fee: f64,
+    /// Lowest fee rate the node relays at all, in sat/vB
+    #[serde(serialize_with = "json::rate")]
+    min_relay_tx_fee: f64,
+    /// Whether the mempool is full and purging its cheapest transactions
+    purging: bool,
+    /// `mempool_min_fee` every five minutes over the last day, oldest first
+    history: Vec<Sample>,
+}
+
+pub async fn get_min_fee(State(state): State<AppState>) -> impl IntoResponse {
+    match state.rpc.get_mempool_info().await {
+        Ok(info) => Json(MinFee {
+            mempool_min_fee: sat_vb(info.mempool_min_fee),
+            min_relay_tx_fee: sat_vb(info.min_relay_tx_fee),
+            purging: info.mempool_min_fee > info.min_relay_tx_fee,
+            history: state.min_fee.samples(),
+        })
+        .into_response(),
+        Err(e) => {
+            warn!("Failed to get mempool minimum fee: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "RPC error").into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden;
+
+    #[test]
+    fn golden_mempool_min_fee() {
+        let (mempool_min_fee, min_relay_tx_fee) = (Amount::from_sat(1500), Amount::from_sat(1000));
+        golden::check(
+            "mempool_min_fee",
+            &MinFee {
+                mempool_min_fee: sat_vb(mempool_min_fee),
+                min_relay_tx_fee: sat_vb(min_relay_tx_fee),
+                purging: mempool_min_fee > min_relay_tx_fee,
+                history: vec![Sample {
+                    timestamp: 1713571500,
+                    mempool_min_fee: sat_vb(Amount::from_sat(1250)),
+                }],
+            },
+        );
+    }
+}
@@ -0,0 +1,206 @@
+//! Network hashrate and difficulty over time.
+//!
+//! Hashrate is what `getnetworkhashps` derives from the work and timestamps of
+//! a window of blocks ending at a given height, so history needs no index.
+//! Samples are cached by block hash, so repeated queries only look up the
+//! hashes of their points.
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use bitcoincore_rpc::bitcoin::BlockHash;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::cache::BoundedCache;
+use crate::rpc::Rpc;
+use crate::stats::parse_period;
+use crate::AppState;
+
+/// Blocks between difficulty retargets
+const RETARGET_INTERVAL: u64 = 2016;
+
+/// About a day of blocks, the window behind each point of the series
+const SERIES_WINDOW: u64 = 144;
+
+/// Points of a hashrate series, spread evenly over the period
+const SERIES_POINTS: u64 = 100;
+
+/// Windows the current hashrate is reported over
+const CURRENT_WINDOWS: [u64; 4] = [1, 144, 1008, 2016];
+
+/// Block timestamp, difficulty and the hashrate over the day of blocks ending there
+#[derive(Clone, Copy)]
+pub struct HashrateSample {
+    time: u64,
+    difficulty: f64,
+    hashrate: f64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HashratePoint {
+    timestamp: u64,
+    avg_hashrate: f64,
+}
+
+#[derive(Serialize)]
+struct DifficultyPoint {
+    time: u64,
+    height: u64,
+    difficulty: f64,
+    /// Ratio to the previous period's difficulty
+    adjustment: f64,
+}
+
+#[derive(Serialize)]
+struct WindowHashrate {
+    blocks: u64,
+    hashrate: f64,
+}
+
+/// mempool.space's hashrate series, with the current hashrate over several windows
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Hashrates {
+    hashrates: Vec<HashratePoint>,
+    /// Retargets within the period, oldest first
+    difficulty: Vec<DifficultyPoint>,
+    current_hashrate: f64,
+    current_difficulty: f64,
+    windows: Vec<WindowHashrate>,
+}
+
+async fn hashrate_sample(
+    rpc: &Rpc,
+    cache: &BoundedCache<BlockHash, HashrateSample>,
+    height: u64,
+) -> Result<HashrateSample, bitcoincore_rpc::Error> {
+    let hash = rpc.get_block_hash(height).await?;
+    if let Some(sample) = cache.get(&hash) {
+        return Ok(sample);
+    }
+    let header = rpc.get_block_header_info(&hash).await?;
+    let sample = HashrateSample {
+        time: header.time as u64,
+        difficulty: header.difficulty,
+        hashrate: rpc
+            .get_network_hash_ps(Some(SERIES_WINDOW), Some(height))
+            .await?,
+    };
+    cache.insert(hash, sample);
+    Ok(sample)
+}
+
+async fn hashrates(
+    rpc: &Rpc,
+    cache: &BoundedCache<BlockHash, HashrateSample>,
+    seconds: u64,
+) -> Result<Hashrates, bitcoincore_rpc::Error> {
+    let tip = rpc.get_block_count().await?;
+    // Blocks come every ten minutes on average
+    let start = tip.saturating_sub(seconds / 600);
+    let step = ((tip - start) / SERIES_POINTS).max(1);
+
+    let mut hashrates = Vec::new();
+    let mut height = start;
+    while height <= tip {
+        let sample = hashrate_sample(rpc, cache, height).await?;
+        hashrates.push(HashratePoint {
+            timestamp: sample.time,
+            avg_hashrate: sample.hashrate,
+        });
+        // The tip is always the last point
+        height = if height < tip {
+            (height + step).min(tip)
+        } else {
+            tip + 1
+        };
+    }
+
+    let mut difficulty = Vec::new();
+    let first_retarget = start.div_ceil(RETARGET_INTERVAL) * RETARGET_INTERVAL;
+    for retarget in (first_retarget..=tip).step_by(RETARGET_INTERVAL as usize) {
+        let sample = hashrate_sample(rpc, cache, retarget).await?;
+        let adjustment = match retarget.checked_sub(RETARGET_INTERVAL) {
+            Some(previous) => {
+                sample.difficulty / hashrate_sample(rpc, cache, previous).await?.difficulty
+            }
+            None => 1.0,
+        };
+        difficulty.push(DifficultyPoint {
+            time: sample.time,
+            height: retarget,
+            difficulty: sample.difficulty,
+            adjustment,
+        });
+    }
+
+    let mut windows = Vec::with_capacity(CURRENT_WINDOWS.len());
+    for blocks in CURRENT_WINDOWS {
+        windows.push(WindowHashrate {
+            blocks,
+            hashrate: rpc.get_network_hash_ps(Some(blocks), None).await?,
+        });
+    }
+    let current = hashrate_sample(rpc, cache, tip).await?;
+    Ok(Hashrates {
+        hashrates,
+        difficulty,
+        current_hashrate: current.hashrate,
+        current_difficulty: current.difficulty,
+        windows,
+    })
+}
+
+pub async fn get_hashrate(
+    State(state): State<AppState>,
+    Path(period): Path<String>,
+) -> impl IntoResponse {
+    let seconds = match parse_period(&period) {
+        Ok(seconds) => seconds,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    match hashrates(&state.rpc, &state.hashrate_samples, seconds).await {
+        Ok(hashrates) => Json(hashrates).into_response(),
+        Err(e) => {
+            warn!("Failed to get hashrate: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "RPC error".to_string()).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden;
+
+    #[test]
+    fn golden_hashrate() {
+        let difficulty = 86388558925171.02;
+        golden::check(
+            "hashrate",
+            &Hashrates {
+                hashrates: vec![HashratePoint {
+                    timestamp: 1713484800,
+                    avg_hashrate: 6.183546171219862e20,
+                }],
+                difficulty: vec![DifficultyPoint {
+                    time: 1713049815,
+                    height: 838656,
+                    difficulty,
+                    adjustment: difficulty / 87698740014107.26,
+                }],
+                current_hashrate: 6.267340135014393e20,
+                current_difficulty: difficulty,
+                windows: vec![WindowHashrate {
+                    blocks: 144,
+                    hashrate: 6.267340135014393e20,
+                }],
+            },
+        );
+    }
+}
@@ -0,0 +1,229 @@
+//! In-memory node for tests.
+//!
+//! [`MockNode`] answers the RPC calls minipool makes to follow the chain and
+//! estimate fees from a made-up chain of blocks, which tests extend with
+//! [`MockNode::mine`] and reorg with [`MockNode::reorg`] to check that nothing
+//! derived from a disconnected block is served afterwards.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use bitcoincore_rpc::bitcoin::hashes::Hash;
+use bitcoincore_rpc::bitcoin::BlockHash;
+use bitcoincore_rpc::jsonrpc::error::RpcError;
+use bitcoincore_rpc::jsonrpc::{Request, Response};
+use serde_json::value::to_raw_value;
+use serde_json::{json, Value};
+
+use crate::rpc::{Rpc, RpcFuture, RpcTransport};
+
+/// `RPC_INVALID_PARAMETER`, what bitcoind answers for heights past its tip
+const INVALID_PARAMETER: i32 = -8;
+
+struct MockBlock {
+    height: u64,
+    previous: Option<BlockHash>,
+}
+
+struct Chain {
+    /// Hash of the best chain's block at each height
+    best: Vec<BlockHash>,
+    /// Every block ever mined, stale ones included
+    blocks: HashMap<BlockHash, MockBlock>,
+    mined: u64,
+    fee_rate_sat_vb: f64,
+}
+
+impl Chain {
+    fn mine(&mut self) -> BlockHash {
+        self.mined += 1;
+        let mut bytes = [0; 32];
+        bytes[..8].copy_from_slice(&self.mined.to_le_bytes());
+        let hash = BlockHash::from_byte_array(bytes);
+        self.blocks.insert(
+            hash,
+            MockBlock {
+                height: self.best.len() as u64,
+                previous: self.best.last().copied(),
+            },
+        );
+        self.best.push(hash);
+        hash
+    }
+
+    fn header(&self, hash: &BlockHash) -> Option<Value> {
+        let block = self.blocks.get(hash)?;
+        let tip_height = self.best.len() as u64 - 1;
+        let confirmations = if self.best.get(block.height as usize) == Some(hash) {
+            (tip_height - block.height + 1) as i64
+        } else {
+            -1
+        };
+        Some(json!({
+            "hash": hash,
+            "confirmations": confirmations,
+            "height": block.height,
+            "version": 0x2000_0000,
+            "merkleroot": "00".repeat(32),
+            "time": 1_700_000_000 + block.height * 600,
+            "mediantime": 1_700_000_000 + block.height * 600,
+            "nonce": 0,
+            "bits": "207fffff",
+            "difficulty": 1.0,
+            "chainwork": "00".repeat(32),
+            "nTx": 1,
+            "previousblockhash": block.previous,
+        }))
+    }
+
+    fn answer(&self, method: &str, params: &[Value]) -> Result<Value, RpcError> {
+        let hash = || {
+            params
+                .first()
+                .and_then(|param| serde_json::from_value::<BlockHash>(param.clone()).ok())
+                .and_then(|hash| self.header(&hash).map(|header| (hash, header)))
+                .ok_or_else(|| error(INVALID_PARAMETER, "Block not found"))
+        };
+        match method {
+            "getbestblockhash" => Ok(json!(self.best.last())),
+            "getblockcount" => Ok(json!(self.best.len() - 1)),
+            "getblockhash" => params
+                .first()
+                .and_then(Value::as_u64)
+                .and_then(|height| self.best.get(height as usize))
+                .map(|hash| json!(hash))
+                .ok_or_else(|| error(INVALID_PARAMETER, "Block height out of range")),
+            "getblockheader" => hash().map(|(_, header)| header),
+            "getblock" => hash().map(|(hash, mut block)| {
+                block["size"] = json!(285);
+                block["strippedsize"] = json!(285);
+                block["weight"] = json!(1140);
+                block["tx"] = json!([hash]);
+                block
+            }),
+            "estimatesmartfee" => Ok(json!({
+                "feerate": self.fee_rate_sat_vb * 1000.0 / 100_000_000.0,
+                "blocks": params.first().and_then(Value::as_u64).unwrap_or(1),
+            })),
+            "getmempoolinfo" => Ok(json!({
+                "loaded": true,
+                "size": 0,
+                "bytes": 0,
+                "usage": 0,
+                "total_fee": 0.0,
+                "maxmempool": 300_000_000,
+                "mempoolminfee": 0.00001,
+                "minrelaytxfee": 0.00001,
+                "incrementalrelayfee": 0.00001,
+                "unbroadcastcount": 0,
+                "fullrbf": false,
+            })),
+            _ => Err(error(-32601, "Method not found")),
+        }
+    }
+}
+
+fn error(code: i32, message: &str) -> RpcError {
+    RpcError {
+        code,
+        message: message.to_owned(),
+        data: None,
+    }
+}
+
+pub struct MockNode {
+    chain: Mutex<Chain>,
+}
+
+impl MockNode {
+    /// A node whose best chain runs from a genesis block up to `height`
+    pub fn new(height: u64) -> Arc<Self> {
+        let mut chain = Chain {
+            best: Vec::new(),
+            blocks: HashMap::new(),
+            mined: 0,
+            fee_rate_sat_vb: 1.0,
+        };
+        for _ in 0..=height {
+            chain.mine();
+        }
+        Arc::new(Self {
+            chain: Mutex::new(chain),
+        })
+    }
+
+    pub fn rpc(self: &Arc<Self>) -> Rpc {
+        Rpc::new(self.clone())
+    }
+
+    pub fn tip(&self) -> BlockHash {
+        let chain = self.chain.lock().expect("mock node lock poisoned");
+        *chain.best.last().expect("the chain has a genesis block")
+    }
+
+    pub fn block_hash(&self, height: u64) -> BlockHash {
+        let chain = self.chain.lock().expect("mock node lock poisoned");
+        chain.best[height as usize]
+    }
+
+    /// Extends the best chain by `count` blocks, returning their hashes
+    pub fn mine(&self, count: u64) -> Vec<BlockHash> {
+        let mut chain = self.chain.lock().expect("mock node lock poisoned");
+        (0..count).map(|_| chain.mine()).collect()
+    }
+
+    /// Disconnects the top `depth` blocks and mines `count` others in their
+    /// place, returning the disconnected hashes, tip first
+    pub fn reorg(&self, depth: u64, count: u64) -> Vec<BlockHash> {
+        let mut chain = self.chain.lock().expect("mock node lock poisoned");
+        let fork = chain.best.len() - depth as usize;
+        let mut disconnected = chain.best.split_off(fork);
+        disconnected.reverse();
+        for _ in 0..count {
+            chain.mine();
+        }
+        disconnected
+    }
+
+    /// Fee rate every `estimatesmartfee` answers with, in sat/vB
+    pub fn set_fee_rate(&self, sat_vb: f64) {
+        let mut chain = self.chain.lock().expect("mock node lock poisoned");
+        chain.fee_rate_sat_vb = sat_vb;
+    }
+
+    fn respond(&self, request: &Request) -> Response {
+        let params: Vec<Value> = request
+            .params
+            .map(|params| serde_json::from_str(params.get()).expect("params are an array"))
+            .unwrap_or_default();
+        let chain = self.chain.lock().expect("mock node lock poisoned");
+        let (result, error) = match chain.answer(request.method, &params) {
+            Ok(result) => (
+                Some(to_raw_value(&result).expect("results serialize")),
+                None,
+            ),
+            Err(error) => (None, Some(error)),
+        };
+        Response {
+            result,
+            error,
+            id: request.id.clone(),
+            jsonrpc: Some("2.0".to_owned()),
+        }
+    }
+}
+
+impl RpcTransport for MockNode {
+    fn send_request<'a>(&'a self, request: Request<'a>) -> RpcFuture<'a, Response> {
+        Box::pin(async move { Ok(self.respond(&request)) })
+    }
+
+    fn send_batch<'a>(&'a self, requests: &'a [Request<'a>]) -> RpcFuture<'a, Vec<Response>> {
+        Box::pin(async move {
+            Ok(requests
+                .iter()
+                .map(|request| self.respond(request))
+                .collect())
+        })
+    }
+}
@@ -0,0 +1,81 @@
+//! What the node behind minipool can answer, for clients deciding how to query it.
+//!
+//! Without txindex, Bitcoin Core only finds confirmed transactions in a block it
+//! is told about, so the `/api/tx/{txid}` endpoints take a `?block=` hint and
+//! `tx_lookup` tells clients whether they need to send it.
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Json;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::rpc::Rpc;
+use crate::AppState;
+
+#[derive(Serialize)]
+pub struct NodeInfo {
+    /// Bitcoin Core version, like 270100 for 27.1.0
+    version: usize,
+    subversion: String,
+    chain: String,
+    pruned: bool,
+    txindex: bool,
+    block_filter_index: bool,
+    /// `txindex` when any transaction is found by txid alone, `block-hint` when
+    /// confirmed ones need `?block=`
+    tx_lookup: &'static str,
+}
+
+pub async fn node_info(rpc: &Rpc) -> Result<NodeInfo, bitcoincore_rpc::Error> {
+    let network = rpc.get_network_info().await?;
+    let chain = rpc.get_blockchain_info().await?;
+    let indexes = rpc.get_index_info().await?;
+    let txindex_synced = indexes.txindex.as_ref().is_some_and(|index| index.synced);
+    Ok(NodeInfo {
+        version: network.version,
+        subversion: network.subversion,
+        chain: chain.chain.to_core_arg().to_string(),
+        pruned: chain.pruned,
+        txindex: indexes.txindex.is_some(),
+        block_filter_index: indexes.basic_block_filter_index.is_some(),
+        tx_lookup: if txindex_synced {
+            "txindex"
+        } else {
+            "block-hint"
+        },
+    })
+}
+
+pub async fn get_node_info(State(state): State<AppState>) -> impl IntoResponse {
+    match node_info(&state.rpc).await {
+        Ok(info) => Json(info).into_response(),
+        Err(e) => {
+            warn!("Failed to get node info: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "RPC error").into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden;
+
+    #[test]
+    fn golden_node_info() {
+        golden::check(
+            "node_info",
+            &NodeInfo {
+                version: 270100,
+                subversion: "/Satoshi:27.1.0/".to_owned(),
+                chain: "main".to_owned(),
+                pruned: false,
+                txindex: false,
+                block_filter_index: true,
+                tx_lookup: "block-hint",
+            },
+        );
+    }
+}
@@ -0,0 +1,209 @@
+//! OpenAPI description of the API, served at `/openapi.json` and browsable with
+//! Swagger UI at `/docs`.
+//!
+//! The description is built from the same route list the router is, so it
+//! documents exactly the mounted routes. Response schemas are the client's
+//! types, which the golden files keep in line with what the server returns.
+
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap, Method};
+use axum::response::{Html, IntoResponse, Response};
+use axum::Json;
+use utoipa::openapi::path::{HttpMethod, OperationBuilder, ParameterBuilder, ParameterIn};
+use utoipa::openapi::request_body::RequestBodyBuilder;
+use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityRequirement, SecurityScheme};
+use utoipa::openapi::{
+    ComponentsBuilder, ContentBuilder, InfoBuilder, OpenApi, OpenApiBuilder, Paths, RefOr,
+    Required, ResponseBuilder, Schema,
+};
+use utoipa::{PartialSchema, ToSchema};
+
+use crate::i18n::{Lang, LangQuery};
+use crate::{assets, AppState, RouteInfo};
+
+/// Named schemas a body refers to, collected into the spec's components
+pub type Schemas = Vec<(String, RefOr<Schema>)>;
+
+/// Media type and schema of a request or response body
+pub type Body = fn(&mut Schemas) -> (&'static str, RefOr<Schema>);
+
+/// JSON body shaped like `T`
+pub fn json<T: ToSchema>(schemas: &mut Schemas) -> (&'static str, RefOr<Schema>) {
+    T::schemas(schemas);
+    ("application/json", T::schema())
+}
+
+/// Plain text body, like a hash, a height or hex
+pub fn text(_: &mut Schemas) -> (&'static str, RefOr<Schema>) {
+    ("text/plain", String::schema())
+}
+
+/// Binary body, like a raw block or transaction
+pub fn binary(_: &mut Schemas) -> (&'static str, RefOr<Schema>) {
+    ("application/octet-stream", Vec::<u8>::schema())
+}
+
+/// Name of the security scheme of admin routes
+const ADMIN_AUTH: &str = "admin_token";
+
+fn path_param_description(path: &str, name: &str) -> &'static str {
+    match name {
+        "hash" if path.starts_with("/api/scripthash") => {
+            "SHA256 of the output script in reversed byte order, hex"
+        }
+        "hash" if path.starts_with("/api/v1/ctv") => "BIP119 template hash, hex",
+        "hash" => "Block hash, hex",
+        "txid" | "last_seen_txid" => "Transaction id, hex",
+        "address" => "Bitcoin address",
+        "height" | "start_height" => "Block height",
+        "start_index" => "Index of the first transaction, a multiple of 25",
+        "index" => "Position of the transaction in the block",
+        "vout" => "Output index",
+        "period" => "Time period, like `1m`, `3m`, `1y` or `all`",
+        "id" if path.contains("/webhooks/") => "Subscription id",
+        "id" => "Block hash or height",
+        "n" => "Number of blocks",
+        _ => "",
+    }
+}
+
+fn http_method(method: &Method) -> HttpMethod {
+    match *method {
+        Method::POST => HttpMethod::Post,
+        Method::PUT => HttpMethod::Put,
+        Method::DELETE => HttpMethod::Delete,
+        Method::PATCH => HttpMethod::Patch,
+        _ => HttpMethod::Get,
+    }
+}
+
+/// Parameters in `{name}` segments of an axum path, which OpenAPI templates share
+fn path_params(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter_map(|segment| {
+        segment
+            .strip_prefix('{')
+            .and_then(|segment| segment.strip_suffix('}'))
+    })
+}
+
+/// OpenAPI description of `routes`
+pub fn spec(routes: &[RouteInfo]) -> OpenApi {
+    let mut schemas = Schemas::new();
+    let mut paths = Paths::new();
+    for route in routes {
+        let mut operation = OperationBuilder::new().summary(Some(route.description));
+        for name in path_params(route.path) {
+            operation = operation.parameter(
+                ParameterBuilder::new()
+                    .name(name)
+                    .parameter_in(ParameterIn::Path)
+                    .required(Required::True)
+                    .description(Some(path_param_description(route.path, name)))
+                    .schema(Some(String::schema())),
+            );
+        }
+        for (name, description) in &route.query {
+            operation = operation.parameter(
+                ParameterBuilder::new()
+                    .name(*name)
+                    .parameter_in(ParameterIn::Query)
+                    .required(Required::False)
+                    .description(Some(*description))
+                    .schema(Some(String::schema())),
+            );
+        }
+        if let Some(body) = route.request {
+            let (media_type, schema) = body(&mut schemas);
+            operation = operation.request_body(Some(
+                RequestBodyBuilder::new()
+                    .required(Some(Required::True))
+                    .content(
+                        media_type,
+                        ContentBuilder::new().schema(Some(schema)).build(),
+                    )
+                    .build(),
+            ));
+        }
+        let mut ok = ResponseBuilder::new().description("Success");
+        if let Some(body) = route.response {
+            let (media_type, schema) = body(&mut schemas);
+            ok = ok.content(
+                media_type,
+                ContentBuilder::new().schema(Some(schema)).build(),
+            );
+        }
+        operation = operation.response("200", ok.build());
+        if route.admin {
+            operation = operation
+                .security(SecurityRequirement::new(ADMIN_AUTH, Vec::<String>::new()))
+                .response(
+                    "401",
+                    ResponseBuilder::new()
+                        .description("Missing or wrong admin token")
+                        .build(),
+                );
+        }
+        paths.add_path_operation(route.path, vec![http_method(&route.method)], operation);
+    }
+
+    let components = ComponentsBuilder::new()
+        .schemas_from_iter(schemas)
+        .security_scheme(
+            ADMIN_AUTH,
+            SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+        )
+        .build();
+    OpenApiBuilder::new()
+        .info(
+            InfoBuilder::new()
+                .title("minipool")
+                .version(env!("CARGO_PKG_VERSION"))
+                .description(Some(
+                    "Esplora and mempool.space compatible API in front of a Bitcoin Core node",
+                ))
+                .build(),
+        )
+        .paths(paths)
+        .components(Some(components))
+        .build()
+}
+
+pub async fn get_openapi(State(state): State<AppState>) -> Response {
+    Json(state.openapi.as_ref()).into_response()
+}
+
+/// Swagger UI for the spec, with the page chrome localized like the rest of the HTML
+pub async fn get_docs(
+    Query(lang_query): Query<LangQuery>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let lang = Lang::negotiate(&lang_query, &headers);
+    let html = format!(
+        r#"
+        <!DOCTYPE html>
+        <html lang="{}">
+        <head>
+            <title>{}</title>
+            <link rel="stylesheet" href="{}">
+        </head>
+        <body>
+            <div id="swagger-ui"></div>
+            <script src="{}"></script>
+            <script src="{}"></script>
+        </body>
+        </html>
+        "#,
+        lang.code(),
+        lang.strings().title,
+        assets::url("swagger-ui.css"),
+        assets::url("swagger-ui-bundle.js"),
+        assets::url("docs.js"),
+    );
+    (
+        [
+            (header::CONTENT_LANGUAGE, lang.code()),
+            (header::VARY, "Accept-Language"),
+        ],
+        Html(html),
+    )
+}
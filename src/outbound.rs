@@ -0,0 +1,327 @@
+//! Shared client for every HTTP call minipool makes to the outside world.
+//!
+//! Configured once at startup and handed to the subsystems that need it, so proxy,
+//! TLS, timeout and pooling settings apply uniformly and every call is measured.
+//! In strict mode it's also the one place refusing calls to hosts other than
+//! the node's and the allowlisted ones, redirects included, and with `OUTBOUND_DOH_URL` the one
+//! place hostnames are resolved over [DNS-over-HTTPS](crate::doh).
+
+use std::collections::HashSet;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use reqwest::redirect::Policy;
+use reqwest::{Certificate, ClientBuilder, IntoUrl, Method, Proxy, RequestBuilder, Response, Url};
+use tracing::{info, warn};
+
+use crate::doh::DohResolver;
+use crate::policy::parse_duration;
+use crate::trace_context::{self, TRACEPARENT};
+
+#[derive(Args, Debug, Clone)]
+pub struct OutboundConfig {
+    /// Proxy for outbound HTTP calls (http://, https:// or socks5:// URL)
+    #[arg(long, env = "OUTBOUND_PROXY")]
+    pub outbound_proxy: Option<String>,
+
+    /// Total timeout for a single outbound HTTP call
+    #[arg(long, env = "OUTBOUND_TIMEOUT", default_value = "10s", value_parser = parse_duration)]
+    pub outbound_timeout: Duration,
+
+    /// Connect timeout for outbound HTTP calls
+    #[arg(long, env = "OUTBOUND_CONNECT_TIMEOUT", default_value = "5s", value_parser = parse_duration)]
+    pub outbound_connect_timeout: Duration,
+
+    /// Maximum idle pooled connections kept per outbound host
+    #[arg(long, env = "OUTBOUND_POOL_MAX_IDLE_PER_HOST", default_value_t = 8)]
+    pub outbound_pool_max_idle_per_host: usize,
+
+    /// Additional PEM-encoded CA certificate trusted for outbound TLS
+    #[arg(long, env = "OUTBOUND_CA_CERT")]
+    pub outbound_ca_cert: Option<PathBuf>,
+
+    /// DNS-over-HTTPS endpoint resolving outbound hostnames, e.g. https://1.1.1.1/dns-query
+    #[arg(long, env = "OUTBOUND_DOH_URL")]
+    pub outbound_doh_url: Option<Url>,
+
+    /// Refuse outbound HTTP calls to any host but the node's and the allowlisted ones
+    #[arg(long, env = "STRICT")]
+    pub strict: bool,
+
+    /// Comma-separated hosts outbound calls may reach in strict mode, such as webhook receivers
+    #[arg(long, env = "STRICT_ALLOWED_HOSTS", value_delimiter = ',')]
+    pub strict_allowed_hosts: Vec<String>,
+}
+
+/// Failure of an outbound call
+#[derive(Debug)]
+pub enum OutboundError {
+    /// Strict mode refused to contact the host
+    Blocked(String),
+    Http(reqwest::Error),
+}
+
+impl fmt::Display for OutboundError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutboundError::Blocked(host) => {
+                write!(f, "host {} is not allowed in strict mode", host)
+            }
+            OutboundError::Http(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for OutboundError {}
+
+impl From<reqwest::Error> for OutboundError {
+    fn from(e: reqwest::Error) -> Self {
+        OutboundError::Http(e)
+    }
+}
+
+#[derive(Clone)]
+pub struct OutboundClient {
+    inner: reqwest::Client,
+    /// Hosts calls may reach, any host when not in strict mode
+    allowed_hosts: Option<Arc<HashSet<String>>>,
+}
+
+/// Redirects allowed before a call fails, as with reqwest's default policy
+const MAX_REDIRECTS: usize = 10;
+
+/// Redirect policy following only redirects to `allowed` hosts, so an allowed
+/// host can't send a call on to one strict mode refuses
+fn strict_redirects(allowed: Arc<HashSet<String>>) -> Policy {
+    Policy::custom(move |attempt| {
+        let host = attempt.url().host_str().unwrap_or_default();
+        if !allowed.contains(host) {
+            let host = host.to_string();
+            attempt.error(OutboundError::Blocked(host))
+        } else if attempt.previous().len() > MAX_REDIRECTS {
+            attempt.error("too many redirects")
+        } else {
+            attempt.follow()
+        }
+    })
+}
+
+/// Strict mode's refusal behind a failed call, when it was a redirect to a
+/// host that isn't allowed
+fn blocked_redirect(e: &reqwest::Error) -> Option<String> {
+    let mut source = std::error::Error::source(e);
+    while let Some(error) = source {
+        if let Some(OutboundError::Blocked(host)) = error.downcast_ref::<OutboundError>() {
+            return Some(host.clone());
+        }
+        source = error.source();
+    }
+    None
+}
+
+/// Client builder with the proxy, TLS and timeout settings every outbound
+/// client shares
+fn builder(config: &OutboundConfig) -> Result<ClientBuilder> {
+    let mut builder = reqwest::Client::builder()
+        .user_agent(concat!("minipool/", env!("CARGO_PKG_VERSION")))
+        .timeout(config.outbound_timeout)
+        .connect_timeout(config.outbound_connect_timeout)
+        .pool_max_idle_per_host(config.outbound_pool_max_idle_per_host);
+
+    if let Some(proxy) = &config.outbound_proxy {
+        builder = builder.proxy(Proxy::all(proxy).context("Invalid outbound proxy URL")?);
+    }
+    if let Some(path) = &config.outbound_ca_cert {
+        let pem = std::fs::read(path)
+            .with_context(|| format!("Failed to read CA certificate {}", path.display()))?;
+        builder = builder.add_root_certificate(
+            Certificate::from_pem(&pem).context("Invalid outbound CA certificate")?,
+        );
+    }
+    Ok(builder)
+}
+
+impl OutboundClient {
+    /// `backends` are the hosts of the node, always allowed and resolved by
+    /// the system
+    pub fn new(config: &OutboundConfig, backends: &[&str]) -> Result<Self> {
+        let allowed_hosts = config.strict.then(|| {
+            let hosts: HashSet<String> = backends
+                .iter()
+                .copied()
+                .chain(config.strict_allowed_hosts.iter().map(String::as_str))
+                .map(str::to_ascii_lowercase)
+                .collect();
+            info!("Strict mode, outbound calls are limited to {:?}", hosts);
+            Arc::new(hosts)
+        });
+
+        let mut builder = builder(config)?;
+        if let Some(url) = &config.outbound_doh_url {
+            // Lookups bypass `send`, so strict mode checks the DoH server once here
+            if let Some(allowed) = &allowed_hosts {
+                let host = url.host_str().unwrap_or_default();
+                if !allowed.contains(host) {
+                    bail!(
+                        "DNS-over-HTTPS host {} is not allowed in strict mode, add it to STRICT_ALLOWED_HOSTS",
+                        host
+                    );
+                }
+            }
+            let http = self::builder(config)?
+                .redirect(Policy::none())
+                .build()
+                .context("Failed to build DNS-over-HTTPS client")?;
+            info!(
+                "Resolving outbound hostnames over DNS-over-HTTPS at {}",
+                url
+            );
+            builder = builder.dns_resolver(Arc::new(DohResolver::new(http, url.clone(), backends)));
+        }
+
+        if let Some(allowed) = &allowed_hosts {
+            builder = builder.redirect(strict_redirects(allowed.clone()));
+        }
+
+        Ok(Self {
+            inner: builder
+                .build()
+                .context("Failed to build outbound HTTP client")?,
+            allowed_hosts,
+        })
+    }
+
+    pub fn request(&self, method: Method, url: impl IntoUrl) -> RequestBuilder {
+        self.inner.request(method, url)
+    }
+
+    /// Sends the request, recording count and latency under the caller-provided `purpose`.
+    /// Calls made on behalf of a request carry its trace context.
+    pub async fn send(
+        &self,
+        purpose: &'static str,
+        mut request: RequestBuilder,
+    ) -> Result<Response, OutboundError> {
+        if let Some(context) = trace_context::current() {
+            request = request.header(TRACEPARENT, context.to_string());
+        }
+        let request = request.build()?;
+        if let Some(allowed) = &self.allowed_hosts {
+            let host = request.url().host_str().unwrap_or_default();
+            if !allowed.contains(host) {
+                warn!("Strict mode blocked {} call to {}", purpose, host);
+                metrics::counter!("outbound_strict_violations_total", "purpose" => purpose)
+                    .increment(1);
+                return Err(OutboundError::Blocked(host.to_string()));
+            }
+        }
+        let start = Instant::now();
+        let result = self.inner.execute(request).await;
+        let latency = start.elapsed().as_secs_f64();
+        if let Some(host) = result.as_ref().err().and_then(blocked_redirect) {
+            warn!(
+                "Strict mode blocked {} call redirected to {}",
+                purpose, host
+            );
+            metrics::counter!("outbound_strict_violations_total", "purpose" => purpose)
+                .increment(1);
+            return Err(OutboundError::Blocked(host));
+        }
+
+        let status = match &result {
+            Ok(response) => response.status().as_u16().to_string(),
+            Err(_) => "error".to_string(),
+        };
+        let labels = [("purpose", purpose.to_string()), ("status", status)];
+        metrics::counter!("outbound_requests_total", &labels).increment(1);
+        metrics::histogram!("outbound_requests_duration_seconds", &labels).record(latency);
+
+        Ok(result?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::response::Redirect;
+    use axum::routing::get;
+
+    /// Serves `/ok` and `/redirect`, which redirects to `/ok` on `localhost`,
+    /// returning the server's port
+    async fn serve() -> u16 {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let app = axum::Router::new()
+            .route("/ok", get(|| async { "ok" }))
+            .route(
+                "/redirect",
+                get(move || async move {
+                    Redirect::temporary(&format!("http://localhost:{}/ok", port))
+                }),
+            );
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        port
+    }
+
+    /// Configuration of a strict client allowing only `127.0.0.1`, so
+    /// `localhost` is blocked without leaving the machine
+    fn strict_config() -> OutboundConfig {
+        OutboundConfig {
+            outbound_proxy: None,
+            outbound_timeout: Duration::from_secs(5),
+            outbound_connect_timeout: Duration::from_secs(5),
+            outbound_pool_max_idle_per_host: 1,
+            outbound_ca_cert: None,
+            outbound_doh_url: None,
+            strict: true,
+            strict_allowed_hosts: vec!["127.0.0.1".to_owned()],
+        }
+    }
+
+    fn strict_client() -> OutboundClient {
+        OutboundClient::new(&strict_config(), &[]).unwrap()
+    }
+
+    async fn get_url(client: &OutboundClient, url: &str) -> Result<Response, OutboundError> {
+        client
+            .send("test", client.request(Method::GET, url.to_owned()))
+            .await
+    }
+
+    #[tokio::test]
+    async fn strict_mode_allows_allowlisted_hosts() {
+        let port = serve().await;
+        let response = get_url(&strict_client(), &format!("http://127.0.0.1:{}/ok", port))
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "ok");
+    }
+
+    #[tokio::test]
+    async fn strict_mode_blocks_other_hosts() {
+        let port = serve().await;
+        let result = get_url(&strict_client(), &format!("http://localhost:{}/ok", port)).await;
+        assert!(matches!(result, Err(OutboundError::Blocked(host)) if host == "localhost"));
+    }
+
+    #[tokio::test]
+    async fn strict_mode_blocks_redirects_to_other_hosts() {
+        let port = serve().await;
+        let url = format!("http://127.0.0.1:{}/redirect", port);
+        let result = get_url(&strict_client(), &url).await;
+        assert!(matches!(result, Err(OutboundError::Blocked(host)) if host == "localhost"));
+    }
+
+    #[test]
+    fn strict_mode_requires_an_allowed_doh_host() {
+        let mut config = strict_config();
+        config.outbound_doh_url = Some(Url::parse("https://1.1.1.1/dns-query").unwrap());
+        assert!(OutboundClient::new(&config, &[]).is_err());
+        config.strict_allowed_hosts.push("1.1.1.1".to_owned());
+        assert!(OutboundClient::new(&config, &[]).is_ok());
+    }
+}
@@ -0,0 +1,145 @@
+//! Operator passthrough for the node's ban list and peer connections.
+//!
+//! Every change is logged, so the admin API stays the one audited way to
+//! manage the node's peers without handing out RPC credentials.
+
+use std::future::Future;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use bitcoincore_rpc::json::ListBannedResult;
+use bitcoincore_rpc::jsonrpc::error::{Error as JsonRpcError, RpcError};
+use serde::Deserialize;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::AppState;
+
+/// Default ban duration of `setban`, a day
+const DEFAULT_BAN_SECONDS: u64 = 24 * 3600;
+
+/// Maps the node's peer management errors to client errors, passing its message on
+fn peer_error(error: &bitcoincore_rpc::Error) -> Option<(StatusCode, String)> {
+    // Error codes from Bitcoin Core's rpc/protocol.h
+    const RPC_INVALID_PARAMETER: i32 = -8;
+    const RPC_CLIENT_NODE_ALREADY_ADDED: i32 = -23;
+    const RPC_CLIENT_NODE_NOT_CONNECTED: i32 = -29;
+    const RPC_CLIENT_INVALID_IP_OR_SUBNET: i32 = -30;
+
+    let bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(RpcError { code, message, .. })) = error
+    else {
+        return None;
+    };
+    let status = match *code {
+        RPC_INVALID_PARAMETER | RPC_CLIENT_INVALID_IP_OR_SUBNET => StatusCode::BAD_REQUEST,
+        RPC_CLIENT_NODE_ALREADY_ADDED => StatusCode::CONFLICT,
+        RPC_CLIENT_NODE_NOT_CONNECTED => StatusCode::NOT_FOUND,
+        _ => return None,
+    };
+    Some((status, message.clone()))
+}
+
+/// Awaits a peer management RPC, answering 204 on success
+async fn apply(
+    action: &'static str,
+    call: impl Future<Output = Result<(), bitcoincore_rpc::Error>>,
+) -> Response {
+    match call.await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => match peer_error(&e) {
+            Some(rejection) => rejection.into_response(),
+            None => {
+                warn!("Failed to {}: {}", action, e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "RPC error").into_response()
+            }
+        },
+    }
+}
+
+pub async fn get_banned(State(state): State<AppState>) -> impl IntoResponse {
+    match state.rpc.list_banned().await {
+        Ok(banned) => Json::<Vec<ListBannedResult>>(banned).into_response(),
+        Err(e) => {
+            warn!("Failed to list banned peers: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "RPC error").into_response()
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct BanRequest {
+    /// IP address or subnet, like `192.0.2.1` or `192.0.2.0/24`
+    subnet: String,
+    /// Ban duration in seconds, or the end of the ban in seconds since epoch
+    /// when `absolute`
+    bantime: Option<u64>,
+    #[serde(default)]
+    absolute: bool,
+}
+
+pub async fn post_ban(
+    State(state): State<AppState>,
+    Json(request): Json<BanRequest>,
+) -> impl IntoResponse {
+    let bantime = request.bantime.unwrap_or(DEFAULT_BAN_SECONDS);
+    info!(
+        "Admin banning {} (bantime {}, absolute {})",
+        request.subnet, bantime, request.absolute
+    );
+    apply(
+        "ban peer",
+        state
+            .rpc
+            .add_ban(&request.subnet, bantime, request.absolute),
+    )
+    .await
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct UnbanRequest {
+    subnet: String,
+}
+
+pub async fn post_unban(
+    State(state): State<AppState>,
+    Json(request): Json<UnbanRequest>,
+) -> impl IntoResponse {
+    info!("Admin unbanning {}", request.subnet);
+    apply("unban peer", state.rpc.remove_ban(&request.subnet)).await
+}
+
+/// Peer to disconnect, by address or by the node's peer id
+#[derive(Deserialize, ToSchema)]
+pub struct DisconnectRequest {
+    address: Option<String>,
+    nodeid: Option<u32>,
+}
+
+pub async fn post_disconnect(
+    State(state): State<AppState>,
+    Json(request): Json<DisconnectRequest>,
+) -> impl IntoResponse {
+    match (request.address, request.nodeid) {
+        (Some(address), None) => {
+            info!("Admin disconnecting peer {}", address);
+            apply("disconnect peer", state.rpc.disconnect_node(&address))
+                .await
+                .into_response()
+        }
+        (None, Some(nodeid)) => {
+            info!("Admin disconnecting peer id {}", nodeid);
+            apply("disconnect peer", state.rpc.disconnect_node_by_id(nodeid))
+                .await
+                .into_response()
+        }
+        _ => (
+            StatusCode::BAD_REQUEST,
+            "Expected exactly one of address and nodeid",
+        )
+            .into_response(),
+    }
+}
@@ -0,0 +1,187 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context};
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use tracing::warn;
+
+/// Wait before the first retry of a request, doubled for every further one
+const RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Client-facing timeout and internal retry budget applied to a single route
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RoutePolicy {
+    /// Upper bound for the whole request, retries included
+    pub timeout: Duration,
+    /// How many times a failed (5xx) idempotent request is re-run before giving up
+    pub retries: u32,
+}
+
+impl RoutePolicy {
+    pub const fn new(timeout: Duration, retries: u32) -> Self {
+        Self { timeout, retries }
+    }
+}
+
+impl Default for RoutePolicy {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(10), 1)
+    }
+}
+
+impl FromStr for RoutePolicy {
+    type Err = anyhow::Error;
+
+    /// Parses `<timeout>/<retries>`, e.g. `2s/2` or `500ms/0`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (timeout, retries) = s
+            .split_once('/')
+            .ok_or_else(|| anyhow!("expected <timeout>/<retries>, got {:?}", s))?;
+        let retries = retries
+            .trim()
+            .parse()
+            .with_context(|| format!("invalid retry count {:?}", retries))?;
+        Ok(Self::new(parse_duration(timeout.trim())?, retries))
+    }
+}
+
+/// Parses a duration with an `ms` or `s` suffix
+pub fn parse_duration(s: &str) -> anyhow::Result<Duration> {
+    if let Some(ms) = s.strip_suffix("ms") {
+        Ok(Duration::from_millis(
+            ms.parse()
+                .with_context(|| format!("invalid duration {:?}", s))?,
+        ))
+    } else if let Some(secs) = s.strip_suffix('s') {
+        let secs: f64 = secs
+            .parse()
+            .with_context(|| format!("invalid duration {:?}", s))?;
+        Duration::try_from_secs_f64(secs).with_context(|| format!("invalid duration {:?}", s))
+    } else {
+        bail!("duration {:?} must end in `ms` or `s`", s)
+    }
+}
+
+/// A `--route-policy` override, written as `<path>=<timeout>/<retries>`
+#[derive(Clone, Debug)]
+pub struct RoutePolicyOverride {
+    pub path: String,
+    pub policy: RoutePolicy,
+}
+
+impl FromStr for RoutePolicyOverride {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (path, policy) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow!("expected <path>=<timeout>/<retries>, got {:?}", s))?;
+        Ok(Self {
+            path: path.trim().to_string(),
+            policy: policy.parse()?,
+        })
+    }
+}
+
+/// Enforces the route's timeout and re-runs idempotent requests that fail with a
+/// server error, backing off between attempts, as long as the retry budget
+/// allows it.
+pub async fn apply_policy(State(policy): State<RoutePolicy>, req: Request, next: Next) -> Response {
+    let path = req.uri().path().to_owned();
+    match tokio::time::timeout(policy.timeout, run_with_retries(policy, req, next)).await {
+        Ok(response) => response,
+        Err(_) => {
+            warn!("Request to {} timed out after {:?}", path, policy.timeout);
+            metrics::counter!("http_route_timeouts_total").increment(1);
+            (StatusCode::GATEWAY_TIMEOUT, "Request timed out").into_response()
+        }
+    }
+}
+
+async fn run_with_retries(policy: RoutePolicy, req: Request, next: Next) -> Response {
+    // Only requests without a body can be replayed safely
+    if policy.retries == 0 || !matches!(*req.method(), Method::GET | Method::HEAD) {
+        return next.run(req).await;
+    }
+
+    let (parts, _body) = req.into_parts();
+    let mut attempt = 0;
+    loop {
+        let req = Request::from_parts(parts.clone(), Body::empty());
+        let response = next.clone().run(req).await;
+        if !is_retryable(response.status()) || attempt >= policy.retries {
+            return response;
+        }
+        attempt += 1;
+        warn!(
+            "Retrying {} after status {} (attempt {}/{})",
+            parts.uri.path(),
+            response.status(),
+            attempt,
+            policy.retries
+        );
+        metrics::counter!("http_route_retries_total").increment(1);
+        tokio::time::sleep(RETRY_BACKOFF * 2u32.saturating_pow(attempt - 1)).await;
+    }
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("2s").unwrap(), Duration::from_secs(2));
+        assert_eq!(parse_duration("1.5s").unwrap(), Duration::from_millis(1500));
+        assert_eq!(parse_duration("0s").unwrap(), Duration::ZERO);
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        for s in [
+            "", "10", "2m", "-1s", "-1ms", "NaNs", "infs", "1e30s", "s", "1.5ms",
+        ] {
+            assert!(parse_duration(s).is_err(), "{:?} parsed", s);
+        }
+    }
+
+    #[test]
+    fn parses_route_policies() {
+        assert_eq!(
+            "2s/2".parse::<RoutePolicy>().unwrap(),
+            RoutePolicy::new(Duration::from_secs(2), 2)
+        );
+        assert_eq!(
+            " 500ms / 0 ".parse::<RoutePolicy>().unwrap(),
+            RoutePolicy::new(Duration::from_millis(500), 0)
+        );
+        for s in ["2s", "2s/", "2s/-1", "2/1", "/1"] {
+            assert!(s.parse::<RoutePolicy>().is_err(), "{:?} parsed", s);
+        }
+    }
+
+    #[test]
+    fn parses_route_policy_overrides() {
+        let route: RoutePolicyOverride = "/api/block/{hash}/raw=30s/0".parse().unwrap();
+        assert_eq!(route.path, "/api/block/{hash}/raw");
+        assert_eq!(route.policy, RoutePolicy::new(Duration::from_secs(30), 0));
+        assert!("/api/tx/{txid}".parse::<RoutePolicyOverride>().is_err());
+        assert!("/api/tx/{txid}=fast"
+            .parse::<RoutePolicyOverride>()
+            .is_err());
+    }
+}
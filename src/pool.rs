@@ -0,0 +1,91 @@
+//! Concurrent calls to one node (`RPC_POOL_SIZE`).
+//!
+//! Calls to a node share keep-alive connections, opening another whenever all
+//! are busy, but bitcoind only works on `-rpcthreads` calls at a time and turns
+//! calls away once its `-rpcworkqueue` is full. The pool lets `RPC_POOL_SIZE`
+//! calls through to the node at once and keeps as many connections open; the
+//! others wait their turn. How long they wait is exported as
+//! `rpc_pool_wait_seconds`, a sign the pool or bitcoind's `-rpcthreads` is too
+//! small when it grows.
+
+use std::time::Instant;
+
+use anyhow::Result;
+use bitcoincore_rpc::jsonrpc::{Request, Response};
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::rpc::{self, HttpTransport, RpcFuture, RpcTransport};
+
+pub struct ConnectionPool {
+    /// Label of the node in metrics
+    name: String,
+    transport: HttpTransport,
+    size: usize,
+    permits: Semaphore,
+}
+
+/// A turn to call the node, given back when dropped
+struct Lease<'a> {
+    pool: &'a ConnectionPool,
+    _permit: SemaphorePermit<'a>,
+}
+
+impl Drop for Lease<'_> {
+    fn drop(&mut self) {
+        // The permit is released after this, so it still counts as busy here
+        metrics::gauge!("rpc_pool_busy", "node" => self.pool.name.clone())
+            .set((self.pool.size - self.pool.permits.available_permits() - 1) as f64);
+    }
+}
+
+impl ConnectionPool {
+    /// Transport letting `size` calls at a time through to `url`
+    pub fn new(name: String, url: &str, user: &str, pass: &str, size: usize) -> Result<Self> {
+        let size = size.max(1);
+        let http = reqwest::Client::builder()
+            .no_proxy()
+            .timeout(rpc::TIMEOUT)
+            .pool_max_idle_per_host(size)
+            .build()?;
+        Ok(Self {
+            name,
+            transport: HttpTransport::new(http, url, user, pass)?,
+            size,
+            permits: Semaphore::new(size),
+        })
+    }
+
+    /// Waits for a turn to call the node
+    async fn lease(&self) -> Lease<'_> {
+        let start = Instant::now();
+        let permit = self
+            .permits
+            .acquire()
+            .await
+            .expect("connection pool is never closed");
+        metrics::gauge!("rpc_pool_busy", "node" => self.name.clone())
+            .set((self.size - self.permits.available_permits()) as f64);
+        metrics::histogram!("rpc_pool_wait_seconds", "node" => self.name.clone())
+            .record(start.elapsed().as_secs_f64());
+        Lease {
+            pool: self,
+            _permit: permit,
+        }
+    }
+}
+
+impl RpcTransport for ConnectionPool {
+    fn send_request<'a>(&'a self, request: Request<'a>) -> RpcFuture<'a, Response> {
+        Box::pin(async move {
+            let _lease = self.lease().await;
+            self.transport.send_request(request).await
+        })
+    }
+
+    fn send_batch<'a>(&'a self, requests: &'a [Request<'a>]) -> RpcFuture<'a, Vec<Response>> {
+        Box::pin(async move {
+            let _lease = self.lease().await;
+            self.transport.send_batch(requests).await
+        })
+    }
+}
@@ -0,0 +1,173 @@
+//! Local arrival time of blocks versus their header timestamp.
+//!
+//! Arrival is when the chain watcher first saw a block, so delays are only as
+//! precise as the poll interval. Header timestamps are chosen by miners and
+//! can be off by minutes, which shows up as outliers on single blocks.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, RwLock};
+use std::time::UNIX_EPOCH;
+
+use axum::{extract::State, response::IntoResponse, Json};
+use bitcoincore_rpc::bitcoin::BlockHash;
+use serde::Serialize;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::warn;
+
+use crate::chain::ChainWatcher;
+use crate::json;
+use crate::rpc::Rpc;
+use crate::AppState;
+
+/// About a week of blocks
+const RETAINED_BLOCKS: usize = 1008;
+
+/// Blocks listed individually by the statistics endpoint
+const RECENT_BLOCKS: usize = 15;
+
+#[derive(Clone, Serialize)]
+struct Sighting {
+    height: u64,
+    hash: BlockHash,
+    /// Header timestamp
+    timestamp: u64,
+    /// Local arrival, in seconds since epoch
+    seen_at: u64,
+    /// `seen_at - timestamp`
+    delay: i64,
+}
+
+#[derive(Default)]
+pub struct PropagationTracker {
+    sightings: RwLock<VecDeque<Sighting>>,
+}
+
+impl PropagationTracker {
+    pub async fn run(self: Arc<Self>, rpc: Arc<Rpc>, watcher: Arc<ChainWatcher>) {
+        let mut blocks = watcher.subscribe();
+        loop {
+            let block = match blocks.recv().await {
+                Ok(block) => block,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Propagation tracker skipped {} blocks", skipped);
+                    continue;
+                }
+                Err(RecvError::Closed) => return,
+            };
+            let header = match rpc.get_block_header_info(&block.hash).await {
+                Ok(header) => header,
+                Err(e) => {
+                    warn!("Failed to get header of block {}: {}", block.hash, e);
+                    continue;
+                }
+            };
+            let seen_at = block
+                .seen_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let timestamp = header.time as u64;
+            let delay = seen_at as i64 - timestamp as i64;
+            metrics::histogram!("block_propagation_delay_seconds").record(delay as f64);
+
+            let mut sightings = self.sightings.write().expect("propagation lock poisoned");
+            // Drop blocks a reorg replaced
+            sightings.retain(|sighting| sighting.height < block.height);
+            sightings.push_back(Sighting {
+                height: block.height,
+                hash: block.hash,
+                timestamp,
+                seen_at,
+                delay,
+            });
+            while sightings.len() > RETAINED_BLOCKS {
+                sightings.pop_front();
+            }
+        }
+    }
+
+    /// Arrival time of a block seen since startup
+    pub fn seen_at(&self, hash: &BlockHash) -> Option<u64> {
+        let sightings = self.sightings.read().expect("propagation lock poisoned");
+        sightings
+            .iter()
+            .rev()
+            .find(|sighting| sighting.hash == *hash)
+            .map(|sighting| sighting.seen_at)
+    }
+}
+
+#[derive(Serialize)]
+struct PropagationStats {
+    /// Blocks seen since startup, up to a week's worth
+    blocks: usize,
+    #[serde(serialize_with = "json::opt_rate")]
+    mean_delay_seconds: Option<f64>,
+    median_delay_seconds: Option<i64>,
+    p90_delay_seconds: Option<i64>,
+    /// Latest blocks first
+    recent: Vec<Sighting>,
+}
+
+impl PropagationTracker {
+    fn stats(&self) -> PropagationStats {
+        let sightings = self.sightings.read().expect("propagation lock poisoned");
+        let mut delays: Vec<i64> = sightings.iter().map(|sighting| sighting.delay).collect();
+        delays.sort_unstable();
+        let percentile = |p: usize| {
+            delays
+                .get((delays.len() * p / 100).min(delays.len().saturating_sub(1)))
+                .copied()
+        };
+        PropagationStats {
+            blocks: delays.len(),
+            mean_delay_seconds: (!delays.is_empty())
+                .then(|| delays.iter().sum::<i64>() as f64 / delays.len() as f64),
+            median_delay_seconds: percentile(50),
+            p90_delay_seconds: percentile(90),
+            recent: sightings
+                .iter()
+                .rev()
+                .take(RECENT_BLOCKS)
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+pub async fn get_propagation(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.propagation.stats())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden;
+
+    #[test]
+    fn golden_propagation() {
+        let tracker = PropagationTracker::default();
+        let sighting = |height, hash: &str, timestamp, delay| Sighting {
+            height,
+            hash: hash.parse().unwrap(),
+            timestamp,
+            seen_at: (timestamp as i64 + delay) as u64,
+            delay,
+        };
+        tracker.sightings.write().unwrap().extend([
+            sighting(
+                839999,
+                "0000000000000000000320283a032748cef8227873ff4872689bf23f1cda83a5",
+                1713571000,
+                7,
+            ),
+            sighting(
+                840000,
+                "000000000000000000024bead8df69990852c202db0e0097c1a12ea637d7e96d",
+                1713571767,
+                8,
+            ),
+        ]);
+        golden::check("propagation", &tracker.stats());
+    }
+}
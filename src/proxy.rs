@@ -0,0 +1,525 @@
+//! Caching proxy mode in front of an upstream esplora.
+//!
+//! With `UPSTREAM_ESPLORA` set, minipool runs without a node and forwards the
+//! esplora routes to the upstream instance, serving repeated reads from a local
+//! cache behind the same route policies, listeners and request metrics as a
+//! node-backed instance, so an edge deployment's clients don't spend the
+//! upstream's rate limits. Path segments are checked before anything is
+//! forwarded, and bodies that can be checked against the request (raw and hex
+//! transactions, raw blocks, headers, tip height and hash, broadcast txids) are
+//! verified before they are cached or passed on.
+//!
+//! Responses that follow the chain are tied to the upstream tip they were
+//! fetched under, polled every `CHAIN_POLL_INTERVAL` and picked up from tip
+//! hash requests passing through, and stop being served as soon as the tip
+//! moves, so a reorg never leaves stale transaction statuses or address pages
+//! in the cache for the rest of `PROXY_CACHE_TTL`.
+
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{header, HeaderValue, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post, MethodRouter};
+use bitcoincore_rpc::bitcoin::address::NetworkUnchecked;
+use bitcoincore_rpc::bitcoin::consensus::encode::{deserialize, deserialize_hex};
+use bitcoincore_rpc::bitcoin::{block, Address, Block, BlockHash, Transaction, Txid};
+use reqwest::{Method, Url};
+use tracing::{debug, warn};
+
+use crate::cache::BoundedCache;
+use crate::outbound::OutboundClient;
+use minipool_client::paths;
+
+/// Largest body kept in the cache; bigger ones, like most raw blocks, are passed
+/// through so the cache stays within entries × this
+const MAX_CACHED_BYTES: usize = 256 * 1024;
+
+/// How long a response may be served from the cache
+#[derive(Clone, Copy)]
+enum Freshness {
+    /// Addressed by block hash or txid, so it never changes
+    Immutable,
+    /// Follows the chain tip or the mempool, cached for `PROXY_CACHE_TTL`
+    /// while the tip stays the same
+    Expiring,
+}
+
+/// What a successful upstream body is checked against before it's used
+#[derive(Clone, Copy)]
+enum Check {
+    None,
+    Height,
+    BlockHash,
+    /// Hex header hashing to the `hash` segment
+    Header,
+    /// Binary block hashing to the `hash` segment, with a matching merkle root
+    RawBlock,
+    /// Binary transaction hashing to the `txid` segment
+    RawTx,
+    /// Hex transaction hashing to the `txid` segment
+    TxHex,
+}
+
+#[derive(Clone, Copy)]
+pub struct ProxyRoute {
+    pub path: &'static str,
+    /// Upstream path, when esplora serves the route under another one
+    upstream: Option<&'static str>,
+    freshness: Freshness,
+    check: Check,
+}
+
+const fn route(path: &'static str, freshness: Freshness, check: Check) -> ProxyRoute {
+    ProxyRoute {
+        path,
+        upstream: None,
+        freshness,
+        check,
+    }
+}
+
+/// Read routes forwarded to the upstream; `POST /api/tx` is forwarded on top
+pub const ROUTES: &[ProxyRoute] = &[
+    ProxyRoute {
+        path: paths::HEALTH,
+        upstream: Some(paths::TIP_HEIGHT),
+        freshness: Freshness::Expiring,
+        check: Check::Height,
+    },
+    route(paths::TIP_HEIGHT, Freshness::Expiring, Check::Height),
+    route(paths::TIP_HASH, Freshness::Expiring, Check::BlockHash),
+    route(paths::BLOCK_HEIGHT, Freshness::Expiring, Check::BlockHash),
+    route(paths::BLOCK, Freshness::Immutable, Check::None),
+    route(paths::BLOCKS, Freshness::Expiring, Check::None),
+    route(paths::BLOCKS_FROM, Freshness::Expiring, Check::None),
+    route(paths::BLOCK_HEADER, Freshness::Immutable, Check::Header),
+    route(paths::BLOCK_STATUS, Freshness::Expiring, Check::None),
+    route(paths::BLOCK_TXIDS, Freshness::Immutable, Check::None),
+    route(paths::BLOCK_TXS, Freshness::Immutable, Check::None),
+    route(paths::BLOCK_RAW, Freshness::Immutable, Check::RawBlock),
+    route(paths::FEE_ESTIMATES, Freshness::Expiring, Check::None),
+    route(paths::TX, Freshness::Expiring, Check::None),
+    route(paths::TX_STATUS, Freshness::Expiring, Check::None),
+    route(paths::TX_HEX, Freshness::Immutable, Check::TxHex),
+    route(paths::TX_RAW, Freshness::Immutable, Check::RawTx),
+    route(paths::TX_MERKLE_PROOF, Freshness::Expiring, Check::None),
+    route(
+        paths::TX_MERKLEBLOCK_PROOF,
+        Freshness::Expiring,
+        Check::None,
+    ),
+    route(paths::TX_OUTSPEND, Freshness::Expiring, Check::None),
+    route(paths::TX_OUTSPENDS, Freshness::Expiring, Check::None),
+    route(paths::MEMPOOL_SUMMARY, Freshness::Expiring, Check::None),
+    route(paths::MEMPOOL_TXIDS, Freshness::Expiring, Check::None),
+    route(paths::MEMPOOL_RECENT, Freshness::Expiring, Check::None),
+    route(paths::ADDRESS, Freshness::Expiring, Check::None),
+    route(paths::ADDRESS_TXS, Freshness::Expiring, Check::None),
+    route(paths::ADDRESS_CHAIN_TXS, Freshness::Expiring, Check::None),
+    route(
+        paths::ADDRESS_CHAIN_TXS_AFTER,
+        Freshness::Expiring,
+        Check::None,
+    ),
+    route(paths::ADDRESS_MEMPOOL_TXS, Freshness::Expiring, Check::None),
+    route(paths::ADDRESS_UTXO, Freshness::Expiring, Check::None),
+    route(paths::SCRIPTHASH, Freshness::Expiring, Check::None),
+    route(paths::SCRIPTHASH_TXS, Freshness::Expiring, Check::None),
+    route(
+        paths::SCRIPTHASH_CHAIN_TXS,
+        Freshness::Expiring,
+        Check::None,
+    ),
+    route(
+        paths::SCRIPTHASH_CHAIN_TXS_AFTER,
+        Freshness::Expiring,
+        Check::None,
+    ),
+    route(
+        paths::SCRIPTHASH_MEMPOOL_TXS,
+        Freshness::Expiring,
+        Check::None,
+    ),
+    route(paths::SCRIPTHASH_UTXO, Freshness::Expiring, Check::None),
+];
+
+#[derive(Clone)]
+struct Cached {
+    content_type: Option<HeaderValue>,
+    body: Bytes,
+    stored: Instant,
+    /// Upstream tip when it was stored, unset before the tip is known
+    tip: Option<BlockHash>,
+}
+
+impl IntoResponse for Cached {
+    fn into_response(self) -> Response {
+        let mut response = self.body.into_response();
+        match self.content_type {
+            Some(content_type) => response
+                .headers_mut()
+                .insert(header::CONTENT_TYPE, content_type),
+            None => response.headers_mut().remove(header::CONTENT_TYPE),
+        };
+        response
+    }
+}
+
+pub struct Proxy {
+    /// Base URL the `/api/...` paths are appended to
+    upstream: String,
+    http: OutboundClient,
+    cache: BoundedCache<String, Cached>,
+    ttl: Duration,
+    /// Latest upstream tip seen
+    tip: RwLock<Option<BlockHash>>,
+}
+
+/// Segments of `path` filled into the `{name}` placeholders of `template`
+fn segments<'a>(template: &'static str, path: &'a str) -> Vec<(&'static str, &'a str)> {
+    template
+        .split('/')
+        .zip(path.split('/'))
+        .filter_map(|(name, value)| {
+            let name = name.strip_prefix('{')?.strip_suffix('}')?;
+            Some((name, value))
+        })
+        .collect()
+}
+
+/// Name of the first segment that can't be valid, refused before it reaches the upstream
+fn invalid_segment(segments: &[(&'static str, &str)]) -> Option<&'static str> {
+    segments.iter().find_map(|&(name, value)| {
+        let valid = match name {
+            "hash" => BlockHash::from_str(value).is_ok(),
+            "txid" | "last_seen_txid" => Txid::from_str(value).is_ok(),
+            "height" | "start_height" | "start_index" => value.parse::<u64>().is_ok(),
+            "vout" => value.parse::<u32>().is_ok(),
+            "address" => Address::<NetworkUnchecked>::from_str(value).is_ok(),
+            _ => true,
+        };
+        (!valid).then_some(name)
+    })
+}
+
+fn segment<'a>(segments: &[(&str, &'a str)], name: &str) -> &'a str {
+    segments
+        .iter()
+        .find(|(segment, _)| *segment == name)
+        .map_or("", |(_, value)| value)
+}
+
+/// Whether a successful upstream body holds what the request asked for
+fn verify(check: Check, segments: &[(&str, &str)], body: &[u8]) -> bool {
+    let text = || std::str::from_utf8(body).map(str::trim).unwrap_or_default();
+    let hash = || BlockHash::from_str(segment(segments, "hash")).ok();
+    let txid = || Txid::from_str(segment(segments, "txid")).ok();
+    match check {
+        Check::None => true,
+        Check::Height => text().parse::<u64>().is_ok(),
+        Check::BlockHash => BlockHash::from_str(text()).is_ok(),
+        Check::Header => deserialize_hex::<block::Header>(text())
+            .is_ok_and(|header| Some(header.block_hash()) == hash()),
+        Check::RawBlock => deserialize::<Block>(body)
+            .is_ok_and(|block| Some(block.block_hash()) == hash() && block.check_merkle_root()),
+        Check::RawTx => {
+            deserialize::<Transaction>(body).is_ok_and(|tx| Some(tx.compute_txid()) == txid())
+        }
+        Check::TxHex => {
+            deserialize_hex::<Transaction>(text()).is_ok_and(|tx| Some(tx.compute_txid()) == txid())
+        }
+    }
+}
+
+impl Proxy {
+    pub fn new(upstream: Url, http: OutboundClient, entries: usize, ttl: Duration) -> Self {
+        Self {
+            upstream: upstream.as_str().trim_end_matches('/').to_owned(),
+            http,
+            cache: BoundedCache::new(entries),
+            ttl,
+            tip: RwLock::new(None),
+        }
+    }
+
+    /// Polls the upstream tip every `interval`, so responses that follow the
+    /// chain expire as soon as it moves
+    pub async fn run(self: Arc<Self>, interval: Duration) {
+        let url = format!("{}{}", self.upstream, paths::TIP_HASH);
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let Ok((status, _, body)) = self.send(Method::GET, &url, None).await else {
+                continue;
+            };
+            if status.is_success() && verify(Check::BlockHash, &[], &body) {
+                self.set_tip(&body);
+            }
+        }
+    }
+
+    fn tip(&self) -> Option<BlockHash> {
+        *self.tip.read().expect("proxy tip lock poisoned")
+    }
+
+    /// Records the tip from a verified tip hash body
+    fn set_tip(&self, body: &[u8]) {
+        let Some(tip) = std::str::from_utf8(body)
+            .ok()
+            .and_then(|text| BlockHash::from_str(text.trim()).ok())
+        else {
+            return;
+        };
+        let mut current = self.tip.write().expect("proxy tip lock poisoned");
+        if *current != Some(tip) {
+            debug!("Upstream tip moved to {}, expiring cached chain data", tip);
+            *current = Some(tip);
+        }
+    }
+
+    fn cached(&self, key: &String, freshness: Freshness) -> Option<Cached> {
+        let cached = self.cache.get(key)?;
+        match freshness {
+            Freshness::Immutable => Some(cached),
+            Freshness::Expiring => {
+                (cached.stored.elapsed() < self.ttl && cached.tip == self.tip()).then_some(cached)
+            }
+        }
+    }
+
+    async fn get(&self, route: ProxyRoute, uri: Uri) -> Response {
+        let segments = segments(route.path, uri.path());
+        if let Some(name) = invalid_segment(&segments) {
+            return (StatusCode::BAD_REQUEST, format!("Invalid {}", name)).into_response();
+        }
+        let path_and_query = match (route.upstream, uri.query()) {
+            (Some(path), _) => path.to_owned(),
+            (None, Some(query)) => format!("{}?{}", uri.path(), query),
+            (None, None) => uri.path().to_owned(),
+        };
+        if let Some(cached) = self.cached(&path_and_query, route.freshness) {
+            metrics::counter!("proxy_cache_requests_total", "outcome" => "hit").increment(1);
+            return cached.into_response();
+        }
+        metrics::counter!("proxy_cache_requests_total", "outcome" => "miss").increment(1);
+
+        let url = format!("{}{}", self.upstream, path_and_query);
+        let (status, content_type, body) = match self.send(Method::GET, &url, None).await {
+            Ok(response) => response,
+            Err(response) => return response,
+        };
+        if !status.is_success() {
+            return passthrough(status, content_type, body);
+        }
+        if !verify(route.check, &segments, &body) {
+            warn!("Upstream returned an invalid response for {}", uri.path());
+            metrics::counter!("proxy_validation_failures_total", "path" => route.path).increment(1);
+            return (StatusCode::BAD_GATEWAY, "Invalid upstream response").into_response();
+        }
+        if route.path == paths::TIP_HASH {
+            self.set_tip(&body);
+        }
+        let cached = Cached {
+            content_type,
+            body,
+            stored: Instant::now(),
+            tip: self.tip(),
+        };
+        if cached.body.len() <= MAX_CACHED_BYTES {
+            self.cache.insert(path_and_query, cached.clone());
+        }
+        cached.into_response()
+    }
+
+    /// Forwards a broadcast once the body decodes as a transaction, checking the
+    /// upstream answers with its txid
+    async fn broadcast(&self, body: String) -> Response {
+        let Ok(tx) = deserialize_hex::<Transaction>(body.trim()) else {
+            return (StatusCode::BAD_REQUEST, "Invalid transaction").into_response();
+        };
+        let url = format!("{}{}", self.upstream, paths::BROADCAST);
+        let (status, content_type, response) = match self.send(Method::POST, &url, Some(body)).await
+        {
+            Ok(response) => response,
+            Err(response) => return response,
+        };
+        if status.is_success()
+            && std::str::from_utf8(&response).map(str::trim)
+                != Ok(tx.compute_txid().to_string().as_str())
+        {
+            warn!(
+                "Upstream answered a broadcast of {} with another txid",
+                tx.compute_txid()
+            );
+            metrics::counter!("proxy_validation_failures_total", "path" => paths::BROADCAST)
+                .increment(1);
+            return (StatusCode::BAD_GATEWAY, "Invalid upstream response").into_response();
+        }
+        passthrough(status, content_type, response)
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        url: &str,
+        body: Option<String>,
+    ) -> Result<(StatusCode, Option<HeaderValue>, Bytes), Response> {
+        let mut request = self.http.request(method, url);
+        if let Some(body) = body {
+            request = request.body(body);
+        }
+        let failed = |e: &dyn std::fmt::Display| {
+            warn!("Failed to reach upstream esplora at {}: {}", url, e);
+            (StatusCode::BAD_GATEWAY, "Upstream error").into_response()
+        };
+        let response = self
+            .http
+            .send("proxy", request)
+            .await
+            .map_err(|e| failed(&e))?;
+        let status =
+            StatusCode::from_u16(response.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| HeaderValue::from_bytes(value.as_bytes()).ok());
+        let body = response.bytes().await.map_err(|e| failed(&e))?;
+        Ok((status, content_type, body))
+    }
+}
+
+fn passthrough(status: StatusCode, content_type: Option<HeaderValue>, body: Bytes) -> Response {
+    let mut response = (status, body).into_response();
+    if let Some(content_type) = content_type {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_TYPE, content_type);
+    }
+    response
+}
+
+/// Handler forwarding `route` to the upstream
+pub fn handler(route: ProxyRoute) -> MethodRouter<Arc<Proxy>> {
+    get(move |State(proxy): State<Arc<Proxy>>, uri: Uri| async move { proxy.get(route, uri).await })
+}
+
+pub fn broadcast_handler() -> MethodRouter<Arc<Proxy>> {
+    post(|State(proxy): State<Arc<Proxy>>, body: String| async move { proxy.broadcast(body).await })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::outbound::OutboundConfig;
+    use bitcoincore_rpc::bitcoin::hashes::Hash;
+
+    const TXID: &str = "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b";
+
+    /// What the upstream answers: its tip and the status of [`TXID`]
+    type Upstream = Arc<RwLock<(BlockHash, &'static str)>>;
+
+    async fn serve_upstream(upstream: Upstream) -> Url {
+        let app = axum::Router::new()
+            .route(
+                paths::TIP_HASH,
+                get(|State(upstream): State<Upstream>| async move {
+                    upstream.read().unwrap().0.to_string()
+                }),
+            )
+            .route(
+                paths::TX_STATUS,
+                get(|State(upstream): State<Upstream>| async move { upstream.read().unwrap().1 }),
+            )
+            .with_state(upstream);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        Url::parse(&format!("http://{}", addr)).unwrap()
+    }
+
+    fn proxy(upstream: Url) -> Proxy {
+        let config = OutboundConfig {
+            outbound_proxy: None,
+            outbound_timeout: Duration::from_secs(5),
+            outbound_connect_timeout: Duration::from_secs(5),
+            outbound_pool_max_idle_per_host: 1,
+            outbound_ca_cert: None,
+            outbound_doh_url: None,
+            strict: false,
+            strict_allowed_hosts: Vec::new(),
+        };
+        let http = OutboundClient::new(&config, &[]).unwrap();
+        Proxy::new(upstream, http, 64, Duration::from_secs(3600))
+    }
+
+    async fn fetch(proxy: &Proxy, path: &'static str, uri: &str) -> String {
+        let route = *ROUTES.iter().find(|route| route.path == path).unwrap();
+        let response = proxy.get(route, uri.parse().unwrap()).await;
+        assert!(response.status().is_success());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn tip_change_expires_chain_data() {
+        let before = BlockHash::from_byte_array([1; 32]);
+        let after = BlockHash::from_byte_array([2; 32]);
+        let upstream: Upstream = Arc::new(RwLock::new((before, "confirmed in the stale block")));
+        let proxy = proxy(serve_upstream(upstream.clone()).await);
+        let status_uri = format!("/api/tx/{}/status", TXID);
+
+        assert_eq!(
+            fetch(&proxy, paths::TIP_HASH, paths::TIP_HASH).await,
+            before.to_string()
+        );
+        assert_eq!(
+            fetch(&proxy, paths::TX_STATUS, &status_uri).await,
+            "confirmed in the stale block"
+        );
+
+        // A reorg upstream, served from the cache until the proxy sees the new tip
+        *upstream.write().unwrap() = (after, "unconfirmed");
+        assert_eq!(
+            fetch(&proxy, paths::TX_STATUS, &status_uri).await,
+            "confirmed in the stale block"
+        );
+        proxy.set_tip(after.to_string().as_bytes());
+        assert_eq!(
+            fetch(&proxy, paths::TX_STATUS, &status_uri).await,
+            "unconfirmed"
+        );
+    }
+
+    #[tokio::test]
+    async fn polled_tip_expires_chain_data() {
+        let before = BlockHash::from_byte_array([1; 32]);
+        let after = BlockHash::from_byte_array([2; 32]);
+        let upstream: Upstream = Arc::new(RwLock::new((before, "confirmed in the stale block")));
+        let proxy = Arc::new(proxy(serve_upstream(upstream.clone()).await));
+        tokio::spawn(proxy.clone().run(Duration::from_millis(10)));
+        let status_uri = format!("/api/tx/{}/status", TXID);
+
+        assert_eq!(
+            fetch(&proxy, paths::TX_STATUS, &status_uri).await,
+            "confirmed in the stale block"
+        );
+        *upstream.write().unwrap() = (after, "unconfirmed");
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while proxy.tip() != Some(after) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("the proxy never saw the new tip");
+        assert_eq!(
+            fetch(&proxy, paths::TX_STATUS, &status_uri).await,
+            "unconfirmed"
+        );
+    }
+}
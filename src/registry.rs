@@ -0,0 +1,114 @@
+//! Persistence of the watches registered through the admin API.
+//!
+//! Outpoint watches and webhook subscriptions are written to the configured
+//! store under the data directory as they're made, updated and dropped, and
+//! loaded back at startup, so a restart doesn't silently stop notifications a
+//! client registered for. Without a data directory they live in memory only.
+//! Either kind can be registered with a TTL, after which it's pruned like an
+//! outpoint watch whose spend confirmed.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::migrations::{self, Migration, META};
+use crate::storage::{Backend, Batch, Store, Table};
+
+/// Big-endian watch id → JSON outpoint watch
+pub const OUTPOINT_WATCHES: Table = "outpoint_watches";
+
+/// Big-endian subscription id → JSON webhook subscription
+pub const SUBSCRIPTIONS: Table = "subscriptions";
+
+/// Schema changes since the first release, see [`migrations`]
+const MIGRATIONS: &[Migration] = &[];
+
+/// How often expired watches are looked for
+pub const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Seconds since epoch
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// When a watch registered now with a TTL of `ttl` seconds expires
+pub fn expires_at(ttl: Option<u64>) -> Option<u64> {
+    ttl.map(|ttl| now().saturating_add(ttl))
+}
+
+pub struct WatchRegistry {
+    store: Arc<dyn Store>,
+}
+
+impl WatchRegistry {
+    pub fn open(backend: Backend, path: &Path, migrate: bool) -> Result<Self> {
+        let store = backend
+            .open(path, &[OUTPOINT_WATCHES, SUBSCRIPTIONS, META])
+            .with_context(|| format!("Failed to open watch registry at {}", path.display()))?;
+        migrations::migrate(
+            store.as_ref(),
+            "watch registry",
+            &[OUTPOINT_WATCHES, SUBSCRIPTIONS],
+            MIGRATIONS,
+            migrate,
+        )?;
+        Ok(Self { store })
+    }
+
+    /// Every entry of `table`, oldest first
+    pub fn load<T: DeserializeOwned>(&self, table: Table) -> Result<Vec<T>> {
+        let mut entries = Vec::new();
+        self.store.scan(
+            table,
+            &0u64.to_be_bytes(),
+            &u64::MAX.to_be_bytes(),
+            false,
+            &mut |_, value| {
+                entries
+                    .push(serde_json::from_slice(value).context("Corrupt watch registry entry")?);
+                Ok(true)
+            },
+        )?;
+        if !entries.is_empty() {
+            info!(
+                "Loaded {} entries of {} from the watch registry",
+                entries.len(),
+                table
+            );
+        }
+        Ok(entries)
+    }
+
+    /// Writes or overwrites entry `id`. A failed write is logged and counted,
+    /// the watch still works until the next restart.
+    pub fn put<T: Serialize>(&self, table: Table, id: u64, entry: &T) {
+        let mut batch = Batch::default();
+        batch.put(
+            table,
+            &id.to_be_bytes(),
+            &serde_json::to_vec(entry).expect("watches always serialize"),
+        );
+        self.write(table, id, batch);
+    }
+
+    pub fn delete(&self, table: Table, id: u64) {
+        let mut batch = Batch::default();
+        batch.delete(table, &id.to_be_bytes());
+        self.write(table, id, batch);
+    }
+
+    fn write(&self, table: Table, id: u64, batch: Batch) {
+        if let Err(e) = self.store.write(batch, true) {
+            warn!("Failed to persist {} entry {}: {}", table, id, e);
+            metrics::counter!("watch_registry_write_failures_total").increment(1);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! Chain-driving helpers for end-to-end test suites running against a regtest node.
+//!
+//! Only compiled with the `regtest` feature, and only mounted when the backend
+//! node reports that it is running on regtest.
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::post,
+    Json,
+};
+use bitcoincore_rpc::bitcoin::address::NetworkUnchecked;
+use bitcoincore_rpc::bitcoin::{Address, Amount, BlockHash, Network, Txid};
+use minipool_client::paths;
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::policy::RoutePolicy;
+use crate::{openapi, rpc, AppState, RouteInfo};
+
+/// Upper bound on blocks mined per request, so a typo can't stall the node
+const MAX_BLOCKS_PER_REQUEST: u64 = 1000;
+
+/// Longest mining `MAX_BLOCKS_PER_REQUEST` blocks may take, far beyond the
+/// default route and RPC timeouts
+const MINE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Amount sent by `/regtest/fund/{address}` when no `amount` is given
+const DEFAULT_FUND_AMOUNT_BTC: f64 = 1.0;
+
+/// Returns whether the backend node's network is regtest
+pub fn is_regtest(network: Network) -> bool {
+    if network == Network::Regtest {
+        info!("Backend node is on regtest, enabling /regtest helper endpoints");
+        true
+    } else {
+        warn!(
+            "Built with the regtest feature but the node is on {}, /regtest endpoints disabled",
+            network
+        );
+        false
+    }
+}
+
+pub fn routes() -> Vec<RouteInfo> {
+    vec![
+        RouteInfo::post(
+            paths::REGTEST_MINE,
+            "Mine n blocks to the given address (or a fresh wallet address), returning their hashes.",
+            post(mine_blocks),
+        )
+        .returns(openapi::json::<Vec<String>>)
+        .query("address", "Address receiving the coinbase outputs")
+        .with_policy(RoutePolicy::new(MINE_TIMEOUT, 0)),
+        RouteInfo::post(
+            paths::REGTEST_FUND,
+            "Send coins from the node wallet to an address, returning the txid.",
+            post(fund_address),
+        )
+        .returns(openapi::text)
+        .query("amount", "Amount in BTC, 1 by default"),
+    ]
+}
+
+#[derive(Deserialize)]
+struct MineParams {
+    address: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct FundParams {
+    /// Amount in BTC
+    amount: Option<f64>,
+}
+
+fn parse_regtest_address(address: &str) -> Option<Address> {
+    Address::<NetworkUnchecked>::from_str(address)
+        .ok()?
+        .require_network(Network::Regtest)
+        .ok()
+}
+
+async fn mine_blocks(
+    State(state): State<AppState>,
+    Path(n): Path<u64>,
+    Query(params): Query<MineParams>,
+) -> impl IntoResponse {
+    if n == 0 || n > MAX_BLOCKS_PER_REQUEST {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Block count must be between 1 and {}",
+                MAX_BLOCKS_PER_REQUEST
+            ),
+        )
+            .into_response();
+    }
+    let address = match params.address.as_deref().map(parse_regtest_address) {
+        Some(Some(address)) => Some(address),
+        Some(None) => return (StatusCode::BAD_REQUEST, "Invalid address").into_response(),
+        None => None,
+    };
+
+    let mine = async {
+        let address = match address {
+            Some(address) => address,
+            None => state
+                .rpc
+                .call::<Address<NetworkUnchecked>>("getnewaddress", &[])
+                .await?
+                .assume_checked(),
+        };
+        state
+            .rpc
+            .call::<Vec<BlockHash>>("generatetoaddress", &[json!(n), json!(address.to_string())])
+            .await
+    };
+    match rpc::with_timeout(MINE_TIMEOUT, mine).await {
+        Ok(hashes) => Json(hashes).into_response(),
+        Err(e) => {
+            warn!("Failed to mine {} blocks: {}", n, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "RPC error").into_response()
+        }
+    }
+}
+
+async fn fund_address(
+    State(state): State<AppState>,
+    Path(address): Path<String>,
+    Query(params): Query<FundParams>,
+) -> impl IntoResponse {
+    let Some(checked) = parse_regtest_address(&address) else {
+        return (StatusCode::BAD_REQUEST, "Invalid address").into_response();
+    };
+    let Ok(amount) = Amount::from_btc(params.amount.unwrap_or(DEFAULT_FUND_AMOUNT_BTC)) else {
+        return (StatusCode::BAD_REQUEST, "Invalid amount").into_response();
+    };
+
+    match state
+        .rpc
+        .call::<Txid>(
+            "sendtoaddress",
+            &[json!(checked.to_string()), json!(amount.to_btc())],
+        )
+        .await
+    {
+        Ok(txid) => (StatusCode::OK, txid.to_string()).into_response(),
+        Err(e) => {
+            warn!("Failed to fund address {}: {}", address, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "RPC error").into_response()
+        }
+    }
+}
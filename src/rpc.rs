@@ -0,0 +1,421 @@
+//! Async JSON-RPC client of the node.
+//!
+//! Calls are HTTP requests on the server's runtime, so handlers await them like
+//! any other I/O rather than holding a blocking thread for the length of the
+//! call. REST, failover and pooling are layers of [`RpcTransport`] stacked on
+//! [`HttpTransport`].
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use bitcoincore_rpc::bitcoin::block::Header;
+use bitcoincore_rpc::bitcoin::consensus::encode;
+use bitcoincore_rpc::bitcoin::{Block, BlockHash, Transaction, Txid};
+use bitcoincore_rpc::jsonrpc::error::Error as JsonRpcError;
+use bitcoincore_rpc::jsonrpc::{Request, Response};
+use bitcoincore_rpc::{json, Error};
+use reqwest::{StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde_json::value::to_raw_value;
+use serde_json::{json, Value};
+
+/// Longest a call may take, as long as bitcoincore-rpc's own client waited
+pub const TIMEOUT: Duration = Duration::from_secs(15);
+
+tokio::task_local! {
+    /// Overrides [`TIMEOUT`] for the calls of a task, see [`with_timeout`]
+    static CALL_TIMEOUT: Duration;
+}
+
+/// Runs `future` with its calls allowed `timeout` instead of [`TIMEOUT`], for
+/// calls known to take long like mining many regtest blocks
+#[cfg(feature = "regtest")]
+pub async fn with_timeout<F: Future>(timeout: Duration, future: F) -> F::Output {
+    CALL_TIMEOUT.scope(timeout, future).await
+}
+
+/// Ids of the requests, so batched answers can be told apart
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+pub type RpcFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, JsonRpcError>> + Send + 'a>>;
+
+/// Async counterpart of the `jsonrpc` crate's `Transport`
+pub trait RpcTransport: Send + Sync + 'static {
+    fn send_request<'a>(&'a self, request: Request<'a>) -> RpcFuture<'a, Response>;
+
+    /// Answers in any order, matched to the requests by id
+    fn send_batch<'a>(&'a self, requests: &'a [Request<'a>]) -> RpcFuture<'a, Vec<Response>>;
+}
+
+fn transport_error(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> JsonRpcError {
+    JsonRpcError::Transport(error.into())
+}
+
+/// JSON-RPC over HTTP to one node
+pub struct HttpTransport {
+    http: reqwest::Client,
+    url: Url,
+    user: String,
+    pass: String,
+}
+
+impl HttpTransport {
+    pub fn new(http: reqwest::Client, url: &str, user: &str, pass: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            http,
+            url: Url::parse(url)?,
+            user: user.to_owned(),
+            pass: pass.to_owned(),
+        })
+    }
+
+    /// Body of the node's answer. Bitcoin Core answers errors of a call with a
+    /// JSON body and a 404 or 500 status, so only other statuses fail.
+    async fn post<T: DeserializeOwned>(
+        &self,
+        body: &impl serde::Serialize,
+    ) -> Result<T, JsonRpcError> {
+        let mut request = self
+            .http
+            .post(self.url.clone())
+            .basic_auth(&self.user, Some(&self.pass))
+            .json(body);
+        if let Ok(timeout) = CALL_TIMEOUT.try_with(|timeout| *timeout) {
+            request = request.timeout(timeout);
+        }
+        let response = request.send().await.map_err(transport_error)?;
+        let status = response.status();
+        if status == StatusCode::UNAUTHORIZED {
+            return Err(transport_error("Node rejected the RPC credentials"));
+        }
+        let body = response.bytes().await.map_err(transport_error)?;
+        serde_json::from_slice(&body).map_err(|e| {
+            if status.is_success() {
+                JsonRpcError::Json(e)
+            } else {
+                transport_error(format!(
+                    "Node answered {}: {}",
+                    status,
+                    String::from_utf8_lossy(&body).trim()
+                ))
+            }
+        })
+    }
+}
+
+impl RpcTransport for HttpTransport {
+    fn send_request<'a>(&'a self, request: Request<'a>) -> RpcFuture<'a, Response> {
+        Box::pin(async move { self.post(&request).await })
+    }
+
+    fn send_batch<'a>(&'a self, requests: &'a [Request<'a>]) -> RpcFuture<'a, Vec<Response>> {
+        Box::pin(async move { self.post(&requests).await })
+    }
+}
+
+/// Sends one call over `transport`
+pub async fn call<T: DeserializeOwned>(
+    transport: &dyn RpcTransport,
+    method: &str,
+    args: &[Value],
+) -> Result<T, Error> {
+    let params = to_raw_value(args)?;
+    let request = Request {
+        method,
+        params: Some(&params),
+        id: json!(NEXT_ID.fetch_add(1, Ordering::Relaxed)),
+        jsonrpc: Some("2.0"),
+    };
+    Ok(transport.send_request(request).await?.result()?)
+}
+
+/// Sends a call to `method` for each set of arguments in `args` as one batch,
+/// answering in the same order. A call failing doesn't fail the others.
+pub async fn batch<T: DeserializeOwned>(
+    transport: &dyn RpcTransport,
+    method: &str,
+    args: &[Vec<Value>],
+) -> Result<Vec<Result<T, Error>>, Error> {
+    if args.is_empty() {
+        return Ok(Vec::new());
+    }
+    let params = args
+        .iter()
+        .map(to_raw_value)
+        .collect::<Result<Vec<_>, _>>()?;
+    let first_id = NEXT_ID.fetch_add(args.len(), Ordering::Relaxed);
+    let requests: Vec<Request> = params
+        .iter()
+        .enumerate()
+        .map(|(index, params)| Request {
+            method,
+            params: Some(params),
+            id: json!(first_id + index),
+            jsonrpc: Some("2.0"),
+        })
+        .collect();
+    let responses = transport.send_batch(&requests).await?;
+    if responses.len() != requests.len() {
+        return Err(JsonRpcError::WrongBatchResponseSize.into());
+    }
+    let mut answers: Vec<Option<Response>> = (0..requests.len()).map(|_| None).collect();
+    for response in responses {
+        let index = response
+            .id
+            .as_u64()
+            .and_then(|id| (id as usize).checked_sub(first_id))
+            .filter(|index| *index < answers.len());
+        let Some(index) = index else {
+            return Err(JsonRpcError::WrongBatchResponseId(response.id).into());
+        };
+        if answers[index].is_some() {
+            return Err(JsonRpcError::BatchDuplicateResponseId(response.id).into());
+        }
+        answers[index] = Some(response);
+    }
+    Ok(answers
+        .into_iter()
+        .map(|response| {
+            let response = response.expect("one answer per request");
+            Ok(response.result()?)
+        })
+        .collect())
+}
+
+/// The node's RPC, with the calls minipool makes. Arguments and results are
+/// those of bitcoincore-rpc's `RpcApi`.
+pub struct Rpc {
+    transport: Arc<dyn RpcTransport>,
+}
+
+impl Rpc {
+    pub fn new(transport: Arc<dyn RpcTransport>) -> Self {
+        Self { transport }
+    }
+
+    pub async fn call<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        args: &[Value],
+    ) -> Result<T, Error> {
+        call(self.transport.as_ref(), method, args).await
+    }
+
+    pub async fn batch<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        args: &[Vec<Value>],
+    ) -> Result<Vec<Result<T, Error>>, Error> {
+        batch(self.transport.as_ref(), method, args).await
+    }
+
+    pub async fn get_blockchain_info(&self) -> Result<json::GetBlockchainInfoResult, Error> {
+        self.call("getblockchaininfo", &[]).await
+    }
+
+    pub async fn get_network_info(&self) -> Result<json::GetNetworkInfoResult, Error> {
+        self.call("getnetworkinfo", &[]).await
+    }
+
+    pub async fn get_index_info(&self) -> Result<json::GetIndexInfoResult, Error> {
+        self.call("getindexinfo", &[]).await
+    }
+
+    pub async fn get_block_count(&self) -> Result<u64, Error> {
+        self.call("getblockcount", &[]).await
+    }
+
+    pub async fn get_best_block_hash(&self) -> Result<BlockHash, Error> {
+        self.call("getbestblockhash", &[]).await
+    }
+
+    pub async fn get_block_hash(&self, height: u64) -> Result<BlockHash, Error> {
+        self.call("getblockhash", &[json!(height)]).await
+    }
+
+    pub async fn get_block(&self, hash: &BlockHash) -> Result<Block, Error> {
+        let hex: String = self.get_block_hex(hash).await?;
+        Ok(encode::deserialize_hex(&hex)?)
+    }
+
+    pub async fn get_block_hex(&self, hash: &BlockHash) -> Result<String, Error> {
+        self.call("getblock", &[json!(hash), json!(0)]).await
+    }
+
+    pub async fn get_block_info(&self, hash: &BlockHash) -> Result<json::GetBlockResult, Error> {
+        self.call("getblock", &[json!(hash), json!(1)]).await
+    }
+
+    pub async fn get_block_header_info(
+        &self,
+        hash: &BlockHash,
+    ) -> Result<json::GetBlockHeaderResult, Error> {
+        self.call("getblockheader", &[json!(hash), json!(true)])
+            .await
+    }
+
+    pub async fn get_network_hash_ps(
+        &self,
+        blocks: Option<u64>,
+        height: Option<u64>,
+    ) -> Result<f64, Error> {
+        self.call("getnetworkhashps", &[json!(blocks), json!(height)])
+            .await
+    }
+
+    pub async fn get_block_header(&self, hash: &BlockHash) -> Result<Header, Error> {
+        let hex: String = self
+            .call("getblockheader", &[json!(hash), json!(false)])
+            .await?;
+        Ok(encode::deserialize_hex(&hex)?)
+    }
+
+    pub async fn get_block_stats(
+        &self,
+        hash: &BlockHash,
+    ) -> Result<json::GetBlockStatsResult, Error> {
+        self.call("getblockstats", &[json!(hash)]).await
+    }
+
+    pub async fn get_block_filter(
+        &self,
+        hash: &BlockHash,
+    ) -> Result<json::GetBlockFilterResult, Error> {
+        self.call("getblockfilter", &[json!(hash)]).await
+    }
+
+    pub async fn get_mempool_info(&self) -> Result<json::GetMempoolInfoResult, Error> {
+        self.call("getmempoolinfo", &[]).await
+    }
+
+    pub async fn get_raw_mempool(&self) -> Result<Vec<Txid>, Error> {
+        self.call("getrawmempool", &[]).await
+    }
+
+    pub async fn get_mempool_entry(
+        &self,
+        txid: &Txid,
+    ) -> Result<json::GetMempoolEntryResult, Error> {
+        self.call("getmempoolentry", &[json!(txid)]).await
+    }
+
+    pub async fn get_tx_out(
+        &self,
+        txid: &Txid,
+        vout: u32,
+        include_mempool: Option<bool>,
+    ) -> Result<Option<json::GetTxOutResult>, Error> {
+        let mut args = vec![json!(txid), json!(vout)];
+        if let Some(include_mempool) = include_mempool {
+            args.push(json!(include_mempool));
+        }
+        self.call("gettxout", &args).await
+    }
+
+    pub async fn estimate_smart_fee(
+        &self,
+        conf_target: u16,
+        estimate_mode: Option<json::EstimateMode>,
+    ) -> Result<json::EstimateSmartFeeResult, Error> {
+        let mut args = vec![json!(conf_target)];
+        if let Some(estimate_mode) = estimate_mode {
+            args.push(json!(estimate_mode));
+        }
+        self.call("estimatesmartfee", &args).await
+    }
+
+    /// [`Rpc::estimate_smart_fee`] for several targets in one round trip
+    pub async fn estimate_smart_fees(
+        &self,
+        conf_targets: &[u16],
+        estimate_mode: Option<json::EstimateMode>,
+    ) -> Result<Vec<json::EstimateSmartFeeResult>, Error> {
+        let args: Vec<Vec<Value>> = conf_targets
+            .iter()
+            .map(|conf_target| {
+                let mut args = vec![json!(conf_target)];
+                if let Some(estimate_mode) = estimate_mode {
+                    args.push(json!(estimate_mode));
+                }
+                args
+            })
+            .collect();
+        self.batch("estimatesmartfee", &args)
+            .await?
+            .into_iter()
+            .collect()
+    }
+
+    pub async fn scan_tx_out_set(
+        &self,
+        descriptors: &[json::ScanTxOutRequest],
+    ) -> Result<json::ScanTxOutResult, Error> {
+        self.call("scantxoutset", &[json!("start"), json!(descriptors)])
+            .await
+    }
+
+    pub async fn list_banned(&self) -> Result<Vec<json::ListBannedResult>, Error> {
+        self.call("listbanned", &[]).await
+    }
+
+    pub async fn add_ban(&self, subnet: &str, bantime: u64, absolute: bool) -> Result<(), Error> {
+        self.call(
+            "setban",
+            &[json!(subnet), json!("add"), json!(bantime), json!(absolute)],
+        )
+        .await
+    }
+
+    pub async fn remove_ban(&self, subnet: &str) -> Result<(), Error> {
+        self.call("setban", &[json!(subnet), json!("remove")]).await
+    }
+
+    pub async fn disconnect_node(&self, address: &str) -> Result<(), Error> {
+        self.call("disconnectnode", &[json!(address)]).await
+    }
+
+    pub async fn disconnect_node_by_id(&self, node_id: u32) -> Result<(), Error> {
+        self.call("disconnectnode", &[json!(""), json!(node_id)])
+            .await
+    }
+
+    pub async fn get_raw_transaction(
+        &self,
+        txid: &Txid,
+        block_hash: Option<&BlockHash>,
+    ) -> Result<Transaction, Error> {
+        let hex = self.get_raw_transaction_hex(txid, block_hash).await?;
+        Ok(encode::deserialize_hex(&hex)?)
+    }
+
+    pub async fn get_raw_transaction_hex(
+        &self,
+        txid: &Txid,
+        block_hash: Option<&BlockHash>,
+    ) -> Result<String, Error> {
+        let mut args = vec![json!(txid), json!(false)];
+        if let Some(block_hash) = block_hash {
+            args.push(json!(block_hash));
+        }
+        self.call("getrawtransaction", &args).await
+    }
+
+    pub async fn get_raw_transaction_info(
+        &self,
+        txid: &Txid,
+        block_hash: Option<&BlockHash>,
+    ) -> Result<json::GetRawTransactionResult, Error> {
+        let mut args = vec![json!(txid), json!(true)];
+        if let Some(block_hash) = block_hash {
+            args.push(json!(block_hash));
+        }
+        self.call("getrawtransaction", &args).await
+    }
+
+    pub async fn send_raw_transaction(&self, tx_hex: &str) -> Result<Txid, Error> {
+        self.call("sendrawtransaction", &[json!(tx_hex)]).await
+    }
+}
@@ -0,0 +1,123 @@
+//! Head-based sampling of request traces.
+//!
+//! The decision is made once per request, when it arrives, and covers both its
+//! tracing span and its access log line. It is kept in the request's trace
+//! context; requests carrying a W3C `traceparent` header are always sampled,
+//! since the caller is already tracing them.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use axum::body::Body;
+use axum::extract::MatchedPath;
+use axum::http::{Request, Response};
+use tracing::{info, info_span, Span};
+
+use crate::trace_context::TraceContext;
+
+/// A `--trace-sample-route` override, written as `<path>=<rate>`
+#[derive(Clone, Debug)]
+pub struct RouteSampleRate {
+    pub path: String,
+    pub rate: f64,
+}
+
+impl FromStr for RouteSampleRate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (path, rate) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow!("expected <path>=<rate>, got {:?}", s))?;
+        let rate = rate
+            .trim()
+            .parse()
+            .with_context(|| format!("invalid sample rate {:?}", rate))?;
+        Ok(Self {
+            path: path.trim().to_owned(),
+            rate,
+        })
+    }
+}
+
+struct Rate {
+    rate: f64,
+    seen: AtomicU64,
+}
+
+impl Rate {
+    fn new(rate: f64) -> Self {
+        Self {
+            rate: rate.clamp(0.0, 1.0),
+            seen: AtomicU64::new(0),
+        }
+    }
+
+    /// Picks every n-th request so the sampled share matches the rate
+    fn sample(&self) -> bool {
+        let n = self.seen.fetch_add(1, Ordering::Relaxed) as f64;
+        ((n + 1.0) * self.rate).floor() > (n * self.rate).floor()
+    }
+}
+
+pub struct TraceSampler {
+    default: Rate,
+    routes: HashMap<String, Rate>,
+}
+
+impl TraceSampler {
+    pub fn new(default_rate: f64, routes: &[RouteSampleRate]) -> Self {
+        Self {
+            default: Rate::new(default_rate),
+            routes: routes
+                .iter()
+                .map(|route| (route.path.clone(), Rate::new(route.rate)))
+                .collect(),
+        }
+    }
+
+    pub fn sampled<B>(&self, req: &Request<B>) -> bool {
+        req.extensions()
+            .get::<MatchedPath>()
+            .and_then(|path| self.routes.get(path.as_str()))
+            .unwrap_or(&self.default)
+            .sample()
+    }
+}
+
+/// Span of a sampled request; unsampled requests get a disabled span
+pub fn make_span(req: &Request<Body>) -> Span {
+    let Some(context) = req
+        .extensions()
+        .get::<TraceContext>()
+        .filter(|context| context.sampled)
+    else {
+        return Span::none();
+    };
+    let path = match req.extensions().get::<MatchedPath>() {
+        Some(matched_path) => matched_path.as_str(),
+        None => "",
+    };
+    info_span!(
+        "request",
+        method = %req.method(),
+        uri = %req.uri(),
+        route = path,
+        trace_id = context.trace_id(),
+    )
+}
+
+/// Access log line of a sampled request
+pub fn log_response(response: &Response<Body>, latency: Duration, span: &Span) {
+    if span.is_disabled() {
+        return;
+    }
+    info!(
+        status = response.status().as_u16(),
+        latency_ms = latency.as_millis() as u64,
+        "request completed"
+    );
+}
@@ -0,0 +1,103 @@
+//! Mirrors a sample of read requests to a canary instance and compares outcomes.
+//!
+//! The client always gets the primary response; the mirrored request runs in the
+//! background and only shows up in metrics.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::extract::{Request, State};
+use axum::http::{header, Method};
+use axum::middleware::Next;
+use axum::response::Response;
+use reqwest::Url;
+use tracing::debug;
+
+use crate::metrics::path_label;
+use crate::outbound::OutboundClient;
+use crate::trace_context;
+
+pub struct Shadow {
+    target: Url,
+    sample_rate: f64,
+    client: OutboundClient,
+    seen: AtomicU64,
+}
+
+impl Shadow {
+    pub fn new(target: Url, sample_rate: f64, client: OutboundClient) -> Self {
+        Self {
+            target,
+            sample_rate: sample_rate.clamp(0.0, 1.0),
+            client,
+            seen: AtomicU64::new(0),
+        }
+    }
+
+    /// Picks every n-th request so the mirrored share matches the sample rate
+    fn should_sample(&self) -> bool {
+        let n = self.seen.fetch_add(1, Ordering::Relaxed) as f64;
+        ((n + 1.0) * self.sample_rate).floor() > (n * self.sample_rate).floor()
+    }
+}
+
+/// Mirrors sampled anonymous GET requests to the shadow target
+pub async fn shadow_requests(
+    State(shadow): State<Arc<Shadow>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    // Credentials are never forwarded, so authenticated requests aren't comparable
+    if req.method() != Method::GET
+        || req.headers().contains_key(header::AUTHORIZATION)
+        || !shadow.should_sample()
+    {
+        return next.run(req).await;
+    }
+
+    let path = path_label(&req);
+    let path_and_query = req
+        .uri()
+        .path_and_query()
+        .map(|pq| pq.as_str().to_owned())
+        .unwrap_or_default();
+
+    let start = Instant::now();
+    let response = next.run(req).await;
+    let primary_latency = start.elapsed().as_secs_f64();
+    let primary_status = response.status();
+
+    let context = trace_context::current();
+    tokio::spawn(trace_context::scope(context, async move {
+        let Ok(url) = shadow.target.join(&path_and_query) else {
+            return;
+        };
+        let start = Instant::now();
+        let result = shadow
+            .client
+            .send("shadow", shadow.client.request(reqwest::Method::GET, url))
+            .await;
+        let shadow_latency = start.elapsed().as_secs_f64();
+
+        let outcome = match &result {
+            Ok(shadow_response) if shadow_response.status() == primary_status => "match",
+            Ok(shadow_response) => {
+                debug!(
+                    "Shadow status mismatch on {}: primary {}, shadow {}",
+                    path_and_query,
+                    primary_status,
+                    shadow_response.status()
+                );
+                "mismatch"
+            }
+            Err(_) => "error",
+        };
+        metrics::counter!("shadow_requests_total", "path" => path.clone(), "outcome" => outcome)
+            .increment(1);
+        metrics::histogram!("shadow_latency_delta_seconds", "path" => path)
+            .record(shadow_latency - primary_latency);
+    }));
+
+    response
+}
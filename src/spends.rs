@@ -0,0 +1,410 @@
+//! Outpoint spend index behind the esplora outspend endpoints.
+//!
+//! Bitcoin Core doesn't track who spent an output, so every block from the
+//! configured start height on is scanned and the outpoint → spending input
+//! mapping is persisted in the configured store under the data directory.
+//! Unconfirmed spends are answered from the mempool mirror.
+
+use std::path::Path as FsPath;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use anyhow::Context;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use bitcoincore_rpc::bitcoin::hashes::Hash;
+use bitcoincore_rpc::bitcoin::{Block, BlockHash, OutPoint, Txid};
+use serde::Serialize;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, warn};
+
+use crate::chain::ChainWatcher;
+use crate::health::{Health, Severity};
+use crate::migrations::{self, Migration, META, RECORD_START_HEIGHT};
+use crate::rpc::Rpc;
+use crate::storage::{self, decode_height, height_key, Backend, Batch, Store, Table};
+use crate::tx::{block_status, hinted_block, BlockHintQuery, EsploraStatus};
+use crate::AppState;
+
+const HEALTH_COMPONENT: &str = "spend_index";
+
+/// Outpoint (txid, big-endian vout) → spending txid, input index and height
+const SPENDS: Table = "spends";
+
+/// Big-endian height → hash of every indexed block, used to detect and undo reorgs
+const BLOCKS: Table = "blocks";
+
+/// Schema changes since the first release, see [`migrations`]
+const MIGRATIONS: &[Migration] = &[RECORD_START_HEIGHT];
+
+/// Indexed blocks between progress logs during catch-up
+const PROGRESS_INTERVAL: u64 = 1000;
+
+struct Spend {
+    txid: Txid,
+    vin: u32,
+    height: u64,
+}
+
+fn outpoint_key(outpoint: &OutPoint) -> [u8; 36] {
+    let mut key = [0; 36];
+    key[..32].copy_from_slice(outpoint.txid.as_byte_array());
+    key[32..].copy_from_slice(&outpoint.vout.to_be_bytes());
+    key
+}
+
+fn encode_spend(spend: &Spend) -> [u8; 44] {
+    let mut value = [0; 44];
+    value[..32].copy_from_slice(spend.txid.as_byte_array());
+    value[32..36].copy_from_slice(&spend.vin.to_be_bytes());
+    value[36..].copy_from_slice(&spend.height.to_be_bytes());
+    value
+}
+
+fn decode_spend(value: &[u8]) -> anyhow::Result<Spend> {
+    anyhow::ensure!(value.len() == 44, "Corrupt spend index entry");
+    Ok(Spend {
+        txid: Txid::from_slice(&value[..32])?,
+        vin: u32::from_be_bytes(value[32..36].try_into()?),
+        height: u64::from_be_bytes(value[36..].try_into()?),
+    })
+}
+
+pub struct SpendIndex {
+    store: Arc<dyn Store>,
+    start_height: u64,
+    /// Set once the initial catch-up reached the tip
+    synced: AtomicBool,
+}
+
+impl SpendIndex {
+    pub fn open(
+        backend: Backend,
+        path: &FsPath,
+        start_height: u64,
+        migrate: bool,
+    ) -> anyhow::Result<Self> {
+        let store = backend
+            .open(path, &[SPENDS, BLOCKS, META])
+            .with_context(|| format!("Failed to open spend index at {}", path.display()))?;
+        migrations::migrate(
+            store.as_ref(),
+            "spend index",
+            &[SPENDS, BLOCKS],
+            MIGRATIONS,
+            migrate,
+        )?;
+        let start_height = migrations::start_height(store.as_ref(), "spend index", start_height)?;
+        Ok(Self {
+            store,
+            start_height,
+            synced: AtomicBool::new(false),
+        })
+    }
+
+    pub async fn run(
+        self: Arc<Self>,
+        rpc: Arc<Rpc>,
+        watcher: Arc<ChainWatcher>,
+        health: Arc<Health>,
+    ) {
+        health.register(HEALTH_COMPONENT, Severity::Soft, None);
+        let mut blocks = watcher.subscribe();
+        loop {
+            match self.sync(&rpc).await {
+                Ok(()) => {
+                    if !self.synced.swap(true, Ordering::Relaxed) {
+                        info!("Spend index caught up with the chain tip");
+                    }
+                    health.success(HEALTH_COMPONENT);
+                }
+                Err(e) => {
+                    warn!("Failed to update spend index: {}", e);
+                    health.failure(HEALTH_COMPONENT, &e);
+                }
+            }
+            // Every sync catches up to the tip, so lagging behind the watcher is harmless
+            match blocks.recv().await {
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => return,
+            }
+        }
+    }
+
+    /// Last indexed block
+    fn tip(&self) -> anyhow::Result<Option<(u64, BlockHash)>> {
+        let Some((height, hash)) = self.store.last(BLOCKS)? else {
+            return Ok(None);
+        };
+        let tip = (decode_height(&height)?, BlockHash::from_slice(&hash)?);
+        Ok(Some(tip))
+    }
+
+    /// Catches up with the node's best chain. Blocks come over RPC on the
+    /// async runtime, index reads and writes run on the blocking pool.
+    async fn sync(self: &Arc<Self>, rpc: &Rpc) -> anyhow::Result<()> {
+        // Undo indexed blocks that are no longer part of the best chain
+        while let Some((height, hash)) = self.blocking(|index| index.tip()).await? {
+            if rpc.get_block_hash(height).await.ok() == Some(hash) {
+                break;
+            }
+            let block = rpc.get_block(&hash).await?;
+            self.blocking(move |index| index.disconnect(height, &block))
+                .await?;
+            info!(
+                "Spend index disconnected block {} at height {}",
+                hash, height
+            );
+        }
+
+        let from = match self.blocking(|index| index.tip()).await? {
+            Some((height, _)) => height + 1,
+            None => self.start_height,
+        };
+        let tip = rpc.get_block_count().await?;
+        for height in from..=tip {
+            let hash = rpc.get_block_hash(height).await?;
+            let block = rpc.get_block(&hash).await?;
+            // Only the last block of a catch-up pays for an fsync
+            self.blocking(move |index| index.connect(height, &hash, &block, height == tip))
+                .await?;
+            if (height - from) % PROGRESS_INTERVAL == PROGRESS_INTERVAL - 1 {
+                info!("Spend index reached height {} of {}", height, tip);
+            }
+        }
+        metrics::gauge!("spend_index_height").set(tip as f64);
+        Ok(())
+    }
+
+    /// Runs `work` on the index off the async runtime
+    async fn blocking<T: Send + 'static>(
+        self: &Arc<Self>,
+        work: impl FnOnce(&Self) -> anyhow::Result<T> + Send + 'static,
+    ) -> anyhow::Result<T> {
+        let index = self.clone();
+        storage::blocking(move || work(&index)).await
+    }
+
+    fn connect(
+        &self,
+        height: u64,
+        hash: &BlockHash,
+        block: &Block,
+        durable: bool,
+    ) -> anyhow::Result<()> {
+        let mut batch = Batch::default();
+        for tx in block.txdata.iter().skip(1) {
+            let txid = tx.compute_txid();
+            for (vin, input) in tx.input.iter().enumerate() {
+                let spend = Spend {
+                    txid,
+                    vin: vin as u32,
+                    height,
+                };
+                batch.put(
+                    SPENDS,
+                    &outpoint_key(&input.previous_output),
+                    &encode_spend(&spend),
+                );
+            }
+        }
+        batch.put(BLOCKS, &height_key(height), hash.as_byte_array());
+        self.store.write(batch, durable)
+    }
+
+    fn disconnect(&self, height: u64, block: &Block) -> anyhow::Result<()> {
+        let mut batch = Batch::default();
+        for input in block.txdata.iter().skip(1).flat_map(|tx| &tx.input) {
+            batch.delete(SPENDS, &outpoint_key(&input.previous_output));
+        }
+        batch.delete(BLOCKS, &height_key(height));
+        self.store.write(batch, true)
+    }
+
+    /// Confirmed spend of `outpoint`, with the hash of the block it is in
+    fn confirmed_spend(&self, outpoint: &OutPoint) -> anyhow::Result<Option<(Spend, BlockHash)>> {
+        let Some(value) = self.store.get(SPENDS, &outpoint_key(outpoint))? else {
+            return Ok(None);
+        };
+        let spend = decode_spend(&value)?;
+        let hash = self
+            .store
+            .get(BLOCKS, &height_key(spend.height))?
+            .context("Spend index entry without its block")?;
+        let hash = BlockHash::from_slice(&hash)?;
+        Ok(Some((spend, hash)))
+    }
+}
+
+#[derive(Serialize)]
+struct Outspend {
+    spent: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    txid: Option<Txid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vin: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<EsploraStatus>,
+}
+
+/// Spends of `outpoints`, confirmed ones looked up in the index
+async fn outspends(
+    state: &AppState,
+    index: Arc<SpendIndex>,
+    outpoints: Vec<OutPoint>,
+) -> anyhow::Result<Vec<Outspend>> {
+    let lookup = outpoints.clone();
+    let confirmed = index
+        .blocking(move |index| {
+            lookup
+                .iter()
+                .map(|outpoint| index.confirmed_spend(outpoint))
+                .collect::<anyhow::Result<Vec<_>>>()
+        })
+        .await?;
+    let mut outspends = Vec::with_capacity(outpoints.len());
+    for (outpoint, confirmed) in outpoints.iter().zip(confirmed) {
+        if let Some((spend, hash)) = confirmed {
+            outspends.push(Outspend {
+                spent: true,
+                txid: Some(spend.txid),
+                vin: Some(spend.vin),
+                status: Some(block_status(&state.rpc, &hash).await?),
+            });
+            continue;
+        }
+        outspends.push(match state.mempool.spent_by(outpoint) {
+            Some((txid, vin)) => Outspend {
+                spent: true,
+                txid: Some(txid),
+                vin: Some(vin),
+                status: Some(EsploraStatus::unconfirmed()),
+            },
+            None => Outspend {
+                spent: false,
+                txid: None,
+                vin: None,
+                status: None,
+            },
+        });
+    }
+    Ok(outspends)
+}
+
+/// Index of the request, unless it can't answer yet
+fn ready_index(state: &AppState) -> Result<Arc<SpendIndex>, (StatusCode, &'static str)> {
+    match &state.spends {
+        Some(index) if index.synced.load(Ordering::Relaxed) => Ok(index.clone()),
+        Some(_) => Err((StatusCode::SERVICE_UNAVAILABLE, "Spend index is syncing")),
+        None => Err((StatusCode::NOT_FOUND, "Spend index disabled")),
+    }
+}
+
+pub async fn get_outspend(
+    State(state): State<AppState>,
+    Path((txid, vout)): Path<(String, u32)>,
+) -> impl IntoResponse {
+    let Ok(parsed) = Txid::from_str(&txid) else {
+        return (StatusCode::BAD_REQUEST, "Invalid txid").into_response();
+    };
+    let index = match ready_index(&state) {
+        Ok(index) => index,
+        Err(response) => return response.into_response(),
+    };
+    let outpoint = OutPoint::new(parsed, vout);
+    match outspends(&state, index, vec![outpoint]).await {
+        Ok(mut outspends) => Json(outspends.remove(0)).into_response(),
+        Err(e) => {
+            warn!("Failed to look up spend of {}:{}: {:#}", txid, vout, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Index error").into_response()
+        }
+    }
+}
+
+pub async fn get_outspends(
+    State(state): State<AppState>,
+    Path(txid): Path<String>,
+    Query(hint): Query<BlockHintQuery>,
+) -> impl IntoResponse {
+    let Ok(parsed) = Txid::from_str(&txid) else {
+        return (StatusCode::BAD_REQUEST, "Invalid txid").into_response();
+    };
+    let hint = match hint.parse() {
+        Ok(hint) => hint,
+        Err(response) => return response.into_response(),
+    };
+    let index = match ready_index(&state) {
+        Ok(index) => index,
+        Err(response) => return response.into_response(),
+    };
+    let lookup = async {
+        let block_hash = hinted_block(&state.rpc, hint.as_ref()).await?;
+        state
+            .rpc
+            .get_raw_transaction(&parsed, block_hash.as_ref())
+            .await
+    };
+    let outputs = match lookup.await {
+        Ok(tx) => tx.output.len() as u32,
+        Err(e) => {
+            warn!("Failed to get transaction {}: {}", txid, e);
+            return (StatusCode::NOT_FOUND, "Transaction not found").into_response();
+        }
+    };
+    let outpoints = (0..outputs)
+        .map(|vout| OutPoint::new(parsed, vout))
+        .collect();
+    match outspends(&state, index, outpoints).await {
+        Ok(outspends) => Json(outspends).into_response(),
+        Err(e) => {
+            warn!("Failed to look up spends of {}: {:#}", txid, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Index error").into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden;
+
+    #[test]
+    fn golden_tx_outspends() {
+        golden::check(
+            "tx_outspends",
+            &[
+                Outspend {
+                    spent: true,
+                    txid: Some(
+                        "a1075db55d416d3ca199f55b6084e2115b9345e16c5cf302fc80e9d5fbf5d48d"
+                            .parse()
+                            .unwrap(),
+                    ),
+                    vin: Some(0),
+                    status: Some(EsploraStatus {
+                        confirmed: true,
+                        block_height: Some(840001),
+                        block_hash: Some(
+                            "000000000000000000024bead8df69990852c202db0e0097c1a12ea637d7e96d"
+                                .parse()
+                                .unwrap(),
+                        ),
+                        block_time: Some(1713571767),
+                        first_seen: None,
+                    }),
+                },
+                Outspend {
+                    spent: false,
+                    txid: None,
+                    vin: None,
+                    status: None,
+                },
+            ],
+        );
+    }
+}
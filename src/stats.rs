@@ -0,0 +1,743 @@
+//! Per-block statistics pipeline.
+//!
+//! Keeps a compact record for each of the most recent `retention` blocks: the
+//! window is backfilled at startup and extended as the chain watcher announces
+//! new blocks. The `/api/v1/statistics/*` endpoints aggregate these records.
+//!
+//! With a data directory the records are persisted in the configured store, so
+//! a restart only fetches the blocks found while minipool was down instead of
+//! the whole window.
+
+use std::collections::BTreeMap;
+use std::path::Path as FsPath;
+use std::sync::{Arc, RwLock};
+
+use anyhow::{bail, Context};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use bitcoincore_rpc::bitcoin::{BlockHash, Txid, Wtxid};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, warn};
+
+use crate::chain::ChainWatcher;
+use crate::health::{Health, Severity};
+use crate::json;
+use crate::migrations::{self, Migration, META};
+use crate::rpc::Rpc;
+use crate::storage::{self, decode_height, height_key, Backend, Batch, Store, Table};
+use crate::AppState;
+
+const HEALTH_COMPONENT: &str = "block_stats";
+
+/// Big-endian height → JSON block record
+const BLOCK_STATS: Table = "block_stats";
+
+/// Schema changes since the first release, see [`migrations`]
+const MIGRATIONS: &[Migration] = &[];
+
+/// Consensus limit on block weight
+const MAX_BLOCK_WEIGHT: f64 = 4_000_000.0;
+
+/// Target seconds between blocks, what the retention is expected to cover
+const BLOCK_INTERVAL: u64 = 600;
+
+#[derive(Clone, Serialize, Deserialize)]
+struct BlockRecord {
+    /// Tells whether the block is still in the best chain after a restart
+    hash: BlockHash,
+    time: u64,
+    weight: u64,
+    /// Transactions including the coinbase
+    txs: u64,
+    /// Transactions carrying witness data
+    segwit_txs: u64,
+    /// Transactions spending at least one taproot output
+    taproot_txs: u64,
+    /// Transactions with an input witness of at least `large_witness_bytes`
+    large_witness_txs: u64,
+    /// Created outputs by script type, as named by bitcoind
+    output_types: BTreeMap<String, u64>,
+}
+
+#[derive(Deserialize)]
+struct VerboseBlock {
+    hash: BlockHash,
+    height: u64,
+    time: u64,
+    weight: u64,
+    tx: Vec<VerboseTx>,
+}
+
+#[derive(Deserialize)]
+struct VerboseTx {
+    txid: Txid,
+    hash: Wtxid,
+    vin: Vec<VerboseInput>,
+    vout: Vec<VerboseOutput>,
+}
+
+#[derive(Deserialize)]
+struct VerboseInput {
+    /// Hex encoded witness items
+    #[serde(default)]
+    txinwitness: Vec<String>,
+    /// Only present at verbosity 3, on nodes that still have undo data
+    prevout: Option<VerbosePrevout>,
+}
+
+#[derive(Deserialize)]
+struct VerboseOutput {
+    #[serde(rename = "scriptPubKey")]
+    script_pubkey: VerboseScript,
+}
+
+#[derive(Deserialize)]
+struct VerbosePrevout {
+    #[serde(rename = "scriptPubKey")]
+    script_pubkey: VerboseScript,
+}
+
+#[derive(Deserialize)]
+struct VerboseScript {
+    #[serde(rename = "type")]
+    script_type: String,
+}
+
+pub struct BlockStatsPipeline {
+    retention: u64,
+    large_witness_bytes: u64,
+    store: Option<Arc<dyn Store>>,
+    blocks: RwLock<BTreeMap<u64, BlockRecord>>,
+}
+
+impl BlockStatsPipeline {
+    pub fn in_memory(retention: u64, large_witness_bytes: u64) -> Self {
+        Self {
+            retention,
+            large_witness_bytes,
+            store: None,
+            blocks: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// Opens the records persisted at `path`, dropping those older than the
+    /// retention, as after lowering it
+    pub fn open(
+        backend: Backend,
+        path: &FsPath,
+        retention: u64,
+        large_witness_bytes: u64,
+        migrate: bool,
+    ) -> anyhow::Result<Self> {
+        let store = backend
+            .open(path, &[BLOCK_STATS, META])
+            .with_context(|| format!("Failed to open block statistics at {}", path.display()))?;
+        migrations::migrate(
+            store.as_ref(),
+            "block statistics",
+            &[BLOCK_STATS],
+            MIGRATIONS,
+            migrate,
+        )?;
+        let mut blocks = BTreeMap::new();
+        let mut horizon = None;
+        let mut expired = Batch::default();
+        store.scan(
+            BLOCK_STATS,
+            &height_key(0),
+            &height_key(u64::MAX),
+            true,
+            &mut |key, value| {
+                let height = decode_height(key)?;
+                if height >= *horizon.get_or_insert((height + 1).saturating_sub(retention)) {
+                    let record: BlockRecord =
+                        serde_json::from_slice(value).context("Corrupt block statistics record")?;
+                    blocks.insert(height, record);
+                } else {
+                    expired.delete(BLOCK_STATS, key);
+                }
+                Ok(true)
+            },
+        )?;
+        store.write(expired, true)?;
+        if let Some(height) = blocks.keys().next_back() {
+            info!(
+                "Loaded statistics of {} blocks up to height {}",
+                blocks.len(),
+                height
+            );
+        }
+        Ok(Self {
+            retention,
+            large_witness_bytes,
+            store: Some(store),
+            blocks: RwLock::new(blocks),
+        })
+    }
+
+    pub async fn run(
+        self: Arc<Self>,
+        rpc: Arc<Rpc>,
+        watcher: Arc<ChainWatcher>,
+        health: Arc<Health>,
+    ) {
+        health.register(HEALTH_COMPONENT, Severity::Soft, None);
+        // Subscribe first so blocks found during the backfill aren't missed
+        let mut blocks = watcher.subscribe();
+
+        match self.backfill(&rpc).await {
+            Ok(()) => health.success(HEALTH_COMPONENT),
+            Err(e) => {
+                warn!("Failed to backfill block statistics: {}", e);
+                health.failure(HEALTH_COMPONENT, &e);
+            }
+        }
+
+        loop {
+            let block = match blocks.recv().await {
+                Ok(block) => block,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Block statistics pipeline skipped {} blocks", skipped);
+                    continue;
+                }
+                Err(RecvError::Closed) => return,
+            };
+            match self.process_block(&rpc, &block.hash, true).await {
+                Ok(()) => health.success(HEALTH_COMPONENT),
+                Err(e) => {
+                    warn!(
+                        "Failed to compute statistics for block {}: {}",
+                        block.hash, e
+                    );
+                    health.failure(HEALTH_COMPONENT, &e);
+                }
+            }
+        }
+    }
+
+    /// Computes the records missing from the retention window, after dropping
+    /// those of blocks that left the best chain while minipool was down
+    async fn backfill(&self, rpc: &Rpc) -> anyhow::Result<()> {
+        let tip = rpc.get_block_count().await?;
+        while let Some((height, hash)) = self.newest() {
+            if height <= tip && rpc.get_block_hash(height).await? == hash {
+                break;
+            }
+            let batch = self.remove(height);
+            self.write(batch).await?;
+        }
+
+        let from = (tip + 1).saturating_sub(self.retention);
+        let missing: Vec<u64> = {
+            let blocks = self.blocks.read().expect("block stats lock poisoned");
+            (from..=tip)
+                .filter(|height| !blocks.contains_key(height))
+                .collect()
+        };
+        if missing.is_empty() {
+            return Ok(());
+        }
+        info!(
+            "Backfilling statistics of {} blocks between heights {} and {}",
+            missing.len(),
+            from,
+            tip
+        );
+        for (done, &height) in missing.iter().enumerate() {
+            let hash = rpc.get_block_hash(height).await?;
+            self.process_block(rpc, &hash, false).await?;
+            if done % 100 == 99 {
+                info!("Block statistics backfilled up to height {}", height);
+            }
+        }
+        Ok(())
+    }
+
+    /// Computes and keeps the record of block `hash`. A new `tip` replaces the
+    /// records above it, as after a reorg.
+    async fn process_block(&self, rpc: &Rpc, hash: &BlockHash, tip: bool) -> anyhow::Result<()> {
+        // Verbosity 3 includes prevouts, older nodes fall back to verbosity 2 output
+        let block: VerboseBlock = rpc.call("getblock", &[json!(hash), json!(3)]).await?;
+        let batch = self.add(&block, tip);
+        self.write(batch).await
+    }
+
+    fn record(&self, block: &VerboseBlock) -> BlockRecord {
+        let mut output_types = BTreeMap::new();
+        for output in block.tx.iter().flat_map(|tx| &tx.vout) {
+            *output_types
+                .entry(output.script_pubkey.script_type.clone())
+                .or_insert(0) += 1;
+        }
+        BlockRecord {
+            hash: block.hash,
+            time: block.time,
+            weight: block.weight,
+            txs: block.tx.len() as u64,
+            segwit_txs: block
+                .tx
+                .iter()
+                .filter(|tx| tx.txid.to_raw_hash() != tx.hash.to_raw_hash())
+                .count() as u64,
+            taproot_txs: block
+                .tx
+                .iter()
+                .filter(|tx| {
+                    tx.vin.iter().any(|input| {
+                        input.prevout.as_ref().is_some_and(|prevout| {
+                            prevout.script_pubkey.script_type == "witness_v1_taproot"
+                        })
+                    })
+                })
+                .count() as u64,
+            large_witness_txs: block
+                .tx
+                .iter()
+                .filter(|tx| {
+                    tx.vin.iter().any(|input| {
+                        let bytes: usize =
+                            input.txinwitness.iter().map(|item| item.len() / 2).sum();
+                        bytes as u64 >= self.large_witness_bytes
+                    })
+                })
+                .count() as u64,
+            output_types,
+        }
+    }
+
+    /// Keeps the record of `block`, returning the changes to persist
+    fn add(&self, block: &VerboseBlock, tip: bool) -> Batch {
+        let record = self.record(block);
+        let mut batch = Batch::default();
+        let mut blocks = self.blocks.write().expect("block stats lock poisoned");
+        if tip {
+            // A reorg may have replaced blocks above this one
+            for height in blocks.split_off(&(block.height + 1)).into_keys() {
+                batch.delete(BLOCK_STATS, &height_key(height));
+            }
+        }
+        batch.put(
+            BLOCK_STATS,
+            &height_key(block.height),
+            &serde_json::to_vec(&record).expect("block records always serialize"),
+        );
+        blocks.insert(block.height, record);
+        let newest = *blocks.keys().next_back().expect("a record was just added");
+        let kept = blocks.split_off(&(newest + 1).saturating_sub(self.retention));
+        for height in std::mem::replace(&mut *blocks, kept).into_keys() {
+            batch.delete(BLOCK_STATS, &height_key(height));
+        }
+        metrics::gauge!("block_stats_pipeline_height").set(newest as f64);
+        batch
+    }
+
+    /// Height and hash of the newest record
+    fn newest(&self) -> Option<(u64, BlockHash)> {
+        let blocks = self.blocks.read().expect("block stats lock poisoned");
+        blocks
+            .iter()
+            .next_back()
+            .map(|(&height, record)| (height, record.hash))
+    }
+
+    /// Drops the record at `height`, returning the change to persist
+    fn remove(&self, height: u64) -> Batch {
+        let mut blocks = self.blocks.write().expect("block stats lock poisoned");
+        blocks.remove(&height);
+        let mut batch = Batch::default();
+        batch.delete(BLOCK_STATS, &height_key(height));
+        batch
+    }
+
+    /// Persists `batch` without an fsync: records lost in a crash are computed
+    /// again by the next backfill
+    async fn write(&self, batch: Batch) -> anyhow::Result<()> {
+        let Some(store) = self.store.clone() else {
+            return Ok(());
+        };
+        storage::blocking(move || store.write(batch, false)).await
+    }
+
+    /// Seconds in `period`, unless it's longer than the retained blocks cover
+    fn period(&self, period: &str) -> Result<u64, String> {
+        let seconds = parse_period(period).map_err(|e| e.to_string())?;
+        if seconds > self.retention * BLOCK_INTERVAL {
+            return Err(format!(
+                "Period {} is longer than the {} blocks kept (STATS_RETENTION_BLOCKS)",
+                period, self.retention
+            ));
+        }
+        Ok(seconds)
+    }
+
+    /// Records of the blocks mined within `seconds` of the latest one
+    fn recent(&self, seconds: u64) -> Vec<(u64, BlockRecord)> {
+        let blocks = self.blocks.read().expect("block stats lock poisoned");
+        let Some(latest) = blocks.values().next_back() else {
+            return Vec::new();
+        };
+        let since = latest.time.saturating_sub(seconds);
+        blocks
+            .iter()
+            .filter(|(_, record)| record.time >= since)
+            .map(|(&height, record)| (height, record.clone()))
+            .collect()
+    }
+}
+
+/// Parses mempool.space statistics periods (`24h`, `3d`, `1w`, `1m`, ...) into seconds
+pub fn parse_period(period: &str) -> anyhow::Result<u64> {
+    const HOUR: u64 = 3600;
+    const DAY: u64 = 24 * HOUR;
+    Ok(match period {
+        "24h" => DAY,
+        "3d" => 3 * DAY,
+        "1w" => 7 * DAY,
+        "1m" => 30 * DAY,
+        "3m" => 90 * DAY,
+        "6m" => 180 * DAY,
+        "1y" => 365 * DAY,
+        "2y" => 2 * 365 * DAY,
+        "3y" => 3 * 365 * DAY,
+        _ => bail!("Unknown period {:?}", period),
+    })
+}
+
+#[derive(Serialize)]
+struct BlockUtilization {
+    height: u64,
+    timestamp: u64,
+    weight: u64,
+    /// Share of the 4M weight limit used
+    #[serde(serialize_with = "json::rate")]
+    weight_utilization: f64,
+    /// Share of transactions carrying witness data
+    #[serde(serialize_with = "json::rate")]
+    segwit_share: f64,
+    /// Share of transactions spending a taproot output
+    #[serde(serialize_with = "json::rate")]
+    taproot_share: f64,
+    /// Transactions carrying an unusually large input witness (inscriptions and the like)
+    large_witness_txs: u64,
+}
+
+pub async fn get_block_utilization(
+    State(state): State<AppState>,
+    Path(period): Path<String>,
+) -> Response {
+    block_utilization(&state.block_stats, &period)
+}
+
+fn block_utilization(stats: &BlockStatsPipeline, period: &str) -> Response {
+    let seconds = match stats.period(period) {
+        Ok(seconds) => seconds,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    let utilization: Vec<_> = stats
+        .recent(seconds)
+        .into_iter()
+        .map(|(height, record)| {
+            let txs = record.txs.max(1) as f64;
+            BlockUtilization {
+                height,
+                timestamp: record.time,
+                weight: record.weight,
+                weight_utilization: record.weight as f64 / MAX_BLOCK_WEIGHT,
+                segwit_share: record.segwit_txs as f64 / txs,
+                taproot_share: record.taproot_txs as f64 / txs,
+                large_witness_txs: record.large_witness_txs,
+            }
+        })
+        .collect();
+    Json(utilization).into_response()
+}
+
+#[derive(Serialize)]
+struct DailyScriptTypes {
+    /// Start of the UTC day
+    timestamp: u64,
+    blocks: u64,
+    /// Created outputs by script type
+    outputs: BTreeMap<String, u64>,
+}
+
+pub async fn get_script_types(
+    State(state): State<AppState>,
+    Path(period): Path<String>,
+) -> Response {
+    script_types(&state.block_stats, &period)
+}
+
+fn script_types(stats: &BlockStatsPipeline, period: &str) -> Response {
+    const DAY: u64 = 24 * 3600;
+    let seconds = match stats.period(period) {
+        Ok(seconds) => seconds,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    let mut days: BTreeMap<u64, DailyScriptTypes> = BTreeMap::new();
+    for (_, record) in stats.recent(seconds) {
+        let day_start = record.time - record.time % DAY;
+        let day = days.entry(day_start).or_insert_with(|| DailyScriptTypes {
+            timestamp: day_start,
+            blocks: 0,
+            outputs: BTreeMap::new(),
+        });
+        day.blocks += 1;
+        for (script_type, count) in record.output_types {
+            *day.outputs.entry(script_type).or_insert(0) += count;
+        }
+    }
+    Json(days.into_values().collect::<Vec<_>>()).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoincore_rpc::bitcoin::hashes::Hash;
+
+    use super::*;
+    use crate::golden;
+
+    /// Block `height` whose hash starts with `fork`, so competing blocks at
+    /// the same height differ
+    fn block(height: u64, fork: u8, txs: serde_json::Value) -> VerboseBlock {
+        let mut hash = [fork; 32];
+        hash[..8].copy_from_slice(&height.to_le_bytes());
+        serde_json::from_value(json!({
+            "hash": BlockHash::from_byte_array(hash),
+            "height": height,
+            "time": 1_713_571_767 + height * 600,
+            "weight": 3_993_281,
+            "tx": txs,
+        }))
+        .unwrap()
+    }
+
+    fn tx(id: u8, witness: Option<u8>, vin: serde_json::Value, vout: &[&str]) -> serde_json::Value {
+        let outputs: Vec<_> = vout
+            .iter()
+            .map(|script_type| json!({ "scriptPubKey": { "type": script_type } }))
+            .collect();
+        json!({
+            "txid": format!("{:02x}", id).repeat(32),
+            "hash": format!("{:02x}", witness.unwrap_or(id)).repeat(32),
+            "vin": vin,
+            "vout": outputs,
+        })
+    }
+
+    fn input(prevout: &str, witness: &[usize]) -> serde_json::Value {
+        let items: Vec<String> = witness.iter().map(|bytes| "00".repeat(*bytes)).collect();
+        json!({
+            "txinwitness": items,
+            "prevout": { "scriptPubKey": { "type": prevout } },
+        })
+    }
+
+    fn heights(pipeline: &BlockStatsPipeline) -> Vec<(u64, BlockHash)> {
+        let blocks = pipeline.blocks.read().unwrap();
+        blocks
+            .iter()
+            .map(|(&height, record)| (height, record.hash))
+            .collect()
+    }
+
+    #[test]
+    fn counts_segwit_taproot_and_large_witness_transactions() {
+        let pipeline = BlockStatsPipeline::in_memory(10, 500);
+        let txs = json!([
+            tx(1, None, json!([{}]), &["witness_v0_keyhash", "nulldata"]),
+            tx(2, None, json!([input("pubkeyhash", &[])]), &["pubkeyhash"]),
+            tx(
+                3,
+                Some(4),
+                json!([input("witness_v0_keyhash", &[72, 33])]),
+                &["witness_v1_taproot", "witness_v0_keyhash"],
+            ),
+            tx(
+                5,
+                Some(6),
+                json!([input("witness_v1_taproot", &[64])]),
+                &["witness_v0_keyhash"]
+            ),
+            // An inscription reveal: one large witness among the inputs
+            tx(
+                7,
+                Some(8),
+                json!([
+                    input("witness_v0_keyhash", &[72, 33]),
+                    input("witness_v1_taproot", &[64, 450, 33])
+                ]),
+                &["witness_v1_taproot"],
+            ),
+        ]);
+        pipeline.add(&block(840_000, 0, txs), true);
+
+        let record = &pipeline.blocks.read().unwrap()[&840_000];
+        assert_eq!(record.txs, 5);
+        assert_eq!(record.segwit_txs, 3);
+        assert_eq!(record.taproot_txs, 2);
+        assert_eq!(record.large_witness_txs, 1);
+        let output_types: Vec<(&str, u64)> = record
+            .output_types
+            .iter()
+            .map(|(script_type, &count)| (script_type.as_str(), count))
+            .collect();
+        assert_eq!(
+            output_types,
+            [
+                ("nulldata", 1),
+                ("pubkeyhash", 1),
+                ("witness_v0_keyhash", 3),
+                ("witness_v1_taproot", 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn new_tip_replaces_reorged_blocks() {
+        let pipeline = BlockStatsPipeline::in_memory(10, 500);
+        for height in 100..=102 {
+            pipeline.add(&block(height, 0, json!([])), true);
+        }
+        let replacement = block(101, 1, json!([]));
+        pipeline.add(&replacement, true);
+        assert_eq!(
+            heights(&pipeline),
+            [
+                (100, block(100, 0, json!([])).hash),
+                (101, replacement.hash)
+            ]
+        );
+
+        // Backfilled blocks fill gaps without touching the blocks above
+        pipeline.add(&block(99, 1, json!([])), false);
+        let kept: Vec<u64> = heights(&pipeline)
+            .iter()
+            .map(|(height, _)| *height)
+            .collect();
+        assert_eq!(kept, [99, 100, 101]);
+    }
+
+    #[test]
+    fn keeps_the_retention_window() {
+        let pipeline = BlockStatsPipeline::in_memory(2, 500);
+        for height in 100..=102 {
+            pipeline.add(&block(height, 0, json!([])), true);
+        }
+        let kept: Vec<u64> = heights(&pipeline)
+            .iter()
+            .map(|(height, _)| *height)
+            .collect();
+        assert_eq!(kept, [101, 102]);
+    }
+
+    #[tokio::test]
+    async fn records_survive_a_restart() {
+        let dir = std::env::temp_dir().join(format!("minipool-stats-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = Backend::Redb.path(&dir, "block_stats");
+        {
+            let pipeline = BlockStatsPipeline::open(Backend::Redb, &path, 3, 500, true).unwrap();
+            for height in 100..=102 {
+                let batch = pipeline.add(&block(height, 0, json!([])), true);
+                pipeline.write(batch).await.unwrap();
+            }
+            let batch = pipeline.remove(102);
+            pipeline.write(batch).await.unwrap();
+        }
+
+        let pipeline = BlockStatsPipeline::open(Backend::Redb, &path, 3, 500, true).unwrap();
+        assert_eq!(
+            heights(&pipeline),
+            [
+                (100, block(100, 0, json!([])).hash),
+                (101, block(101, 0, json!([])).hash)
+            ]
+        );
+        drop(pipeline);
+
+        // A lower retention drops the older records
+        let pipeline = BlockStatsPipeline::open(Backend::Redb, &path, 1, 500, true).unwrap();
+        assert_eq!(
+            pipeline.newest(),
+            Some((101, block(101, 0, json!([])).hash))
+        );
+        assert_eq!(heights(&pipeline).len(), 1);
+        drop(pipeline);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn golden_block_utilization() {
+        // Shares as computed from the block's counts, rounded on the way out
+        golden::check(
+            "block_utilization",
+            &[BlockUtilization {
+                height: 840000,
+                timestamp: 1713571767,
+                weight: 3993281,
+                weight_utilization: 3993281.0 / MAX_BLOCK_WEIGHT,
+                segwit_share: 2666.0 / 3050.0,
+                taproot_share: 857.0 / 3050.0,
+                large_witness_txs: 12,
+            }],
+        );
+    }
+
+    #[test]
+    fn golden_script_types() {
+        let outputs = [
+            ("multisig", 3),
+            ("nulldata", 9120),
+            ("pubkeyhash", 41230),
+            ("scripthash", 30211),
+            ("witness_v0_keyhash", 210345),
+            ("witness_v0_scripthash", 12006),
+            ("witness_v1_taproot", 98210),
+        ];
+        golden::check(
+            "script_types",
+            &[DailyScriptTypes {
+                timestamp: 1713484800,
+                blocks: 143,
+                outputs: outputs
+                    .iter()
+                    .map(|&(script_type, count)| (script_type.to_owned(), count))
+                    .collect(),
+            }],
+        );
+    }
+
+    async fn body(response: Response) -> (StatusCode, String) {
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn periods_longer_than_the_retention_are_rejected() {
+        let pipeline = BlockStatsPipeline::in_memory(1008, 500);
+        let txs = json!([tx(1, None, json!([{}]), &["witness_v0_keyhash"])]);
+        pipeline.add(&block(840_000, 0, txs), true);
+
+        let (status, types) = body(script_types(&pipeline, "1w")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(types.contains("witness_v0_keyhash"));
+        let (status, _) = body(block_utilization(&pipeline, "1w")).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, error) = body(script_types(&pipeline, "1y")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error.contains("STATS_RETENTION_BLOCKS"));
+        let (status, _) = body(block_utilization(&pipeline, "1m")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
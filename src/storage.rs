@@ -0,0 +1,594 @@
+//! Key-value storage behind the persistent indexes.
+//!
+//! Indexes see a store as named tables of byte keys and values, kept in key
+//! order, which every backend provides: redb (the default, built in), sled,
+//! RocksDB and SQLite, the last three behind cargo features of the same name.
+//! Writes go through a [`Batch`] applied atomically, so a block is either fully
+//! indexed or not at all whatever the backend.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+
+/// Name of a table, fixed by the index using it
+pub type Table = &'static str;
+
+enum Op {
+    Put(Table, Vec<u8>, Vec<u8>),
+    Delete(Table, Vec<u8>),
+}
+
+impl Op {
+    fn table(&self) -> Table {
+        match self {
+            Op::Put(table, ..) | Op::Delete(table, _) => table,
+        }
+    }
+}
+
+/// Writes applied together, in order, by [`Store::write`]
+#[derive(Default)]
+pub struct Batch {
+    ops: Vec<Op>,
+}
+
+impl Batch {
+    pub fn put(&mut self, table: Table, key: &[u8], value: &[u8]) {
+        self.ops.push(Op::Put(table, key.to_vec(), value.to_vec()));
+    }
+
+    pub fn delete(&mut self, table: Table, key: &[u8]) {
+        self.ops.push(Op::Delete(table, key.to_vec()));
+    }
+}
+
+/// Visits entries of a scan, returning whether to go on
+pub type Visitor<'a> = dyn FnMut(&[u8], &[u8]) -> Result<bool> + 'a;
+
+pub trait Store: Send + Sync {
+    fn get(&self, table: Table, key: &[u8]) -> Result<Option<Vec<u8>>>;
+
+    /// Calls `visit` with the entries whose keys are between `first` and `last`
+    /// inclusive, in key order or reversed, until it returns false
+    fn scan(
+        &self,
+        table: Table,
+        first: &[u8],
+        last: &[u8],
+        reverse: bool,
+        visit: &mut Visitor,
+    ) -> Result<()>;
+
+    /// Entry with the greatest key
+    fn last(&self, table: Table) -> Result<Option<(Vec<u8>, Vec<u8>)>>;
+
+    /// Applies `batch` atomically; a non-`durable` write may be lost on a crash
+    /// until a later durable one
+    fn write(&self, batch: Batch, durable: bool) -> Result<()>;
+}
+
+/// Key of a height in tables keyed by height, ordered like the height
+pub fn height_key(height: u64) -> [u8; 8] {
+    height.to_be_bytes()
+}
+
+/// Runs `work`, which reads or writes a store, on the blocking pool so a slow
+/// disk doesn't hold up the connections served by the async runtime
+pub async fn blocking<T: Send + 'static>(
+    work: impl FnOnce() -> Result<T> + Send + 'static,
+) -> Result<T> {
+    tokio::task::spawn_blocking(work).await?
+}
+
+pub fn decode_height(key: &[u8]) -> Result<u64> {
+    Ok(u64::from_be_bytes(
+        key.try_into().context("Corrupt height key")?,
+    ))
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    #[default]
+    Redb,
+    #[cfg(feature = "sled")]
+    Sled,
+    #[cfg(feature = "rocksdb")]
+    RocksDb,
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+}
+
+impl FromStr for Backend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "redb" => Ok(Self::Redb),
+            #[cfg(feature = "sled")]
+            "sled" => Ok(Self::Sled),
+            #[cfg(feature = "rocksdb")]
+            "rocksdb" => Ok(Self::RocksDb),
+            #[cfg(feature = "sqlite")]
+            "sqlite" => Ok(Self::Sqlite),
+            _ if ["sled", "rocksdb", "sqlite"].contains(&s) => {
+                bail!("minipool was built without the `{}` feature", s)
+            }
+            _ => bail!(
+                "Unknown storage backend {:?}, expected redb, sled, rocksdb or sqlite",
+                s
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Redb => "redb",
+            #[cfg(feature = "sled")]
+            Self::Sled => "sled",
+            #[cfg(feature = "rocksdb")]
+            Self::RocksDb => "rocksdb",
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite => "sqlite",
+        })
+    }
+}
+
+impl Backend {
+    /// Location of the store `name` under `data_dir`; each backend gets its own
+    /// so switching backends starts a fresh index instead of misreading one
+    pub fn path(self, data_dir: &Path, name: &str) -> PathBuf {
+        data_dir.join(format!("{}.{}", name, self))
+    }
+
+    pub fn open(self, path: &Path, tables: &[Table]) -> Result<Arc<dyn Store>> {
+        Ok(match self {
+            Self::Redb => Arc::new(redb_store::RedbStore::open(path, tables)?),
+            #[cfg(feature = "sled")]
+            Self::Sled => Arc::new(sled_store::SledStore::open(path, tables)?),
+            #[cfg(feature = "rocksdb")]
+            Self::RocksDb => Arc::new(rocksdb_store::RocksDbStore::open(path, tables)?),
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite => Arc::new(sqlite_store::SqliteStore::open(path, tables)?),
+        })
+    }
+}
+
+mod redb_store {
+    use std::collections::hash_map::Entry;
+    use std::collections::HashMap;
+    use std::path::Path;
+
+    use anyhow::{Context, Result};
+    use redb::{
+        Database, Durability, ReadableDatabase, ReadableTable, TableDefinition, TableError,
+        WriteTransaction,
+    };
+
+    use super::{Batch, Op, Store, Table, Visitor};
+
+    fn definition(table: Table) -> TableDefinition<'static, &'static [u8], &'static [u8]> {
+        TableDefinition::new(table)
+    }
+
+    /// Earlier versions keyed height tables by `u64`; rewrites such a table with
+    /// big-endian keys, which sort the same
+    fn convert_legacy_table(txn: &WriteTransaction, table: Table) -> Result<()> {
+        let legacy: TableDefinition<u64, &[u8]> = TableDefinition::new(table);
+        let entries = {
+            let legacy = txn.open_table(legacy)?;
+            let mut entries = Vec::new();
+            for entry in legacy.iter()? {
+                let (key, value) = entry?;
+                entries.push((key.value(), value.value().to_vec()));
+            }
+            entries
+        };
+        txn.delete_table(legacy)?;
+        let mut converted = txn.open_table(definition(table))?;
+        for (key, value) in entries {
+            converted.insert(key.to_be_bytes().as_slice(), value.as_slice())?;
+        }
+        Ok(())
+    }
+
+    pub struct RedbStore {
+        db: Database,
+    }
+
+    impl RedbStore {
+        pub fn open(path: &Path, tables: &[Table]) -> Result<Self> {
+            let db = Database::create(path)
+                .with_context(|| format!("Failed to open {}", path.display()))?;
+            let txn = db.begin_write()?;
+            for &table in tables {
+                match txn.open_table(definition(table)) {
+                    Ok(_) => {}
+                    Err(TableError::TableTypeMismatch { .. }) => convert_legacy_table(&txn, table)?,
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            txn.commit()?;
+            Ok(Self { db })
+        }
+    }
+
+    impl Store for RedbStore {
+        fn get(&self, table: Table, key: &[u8]) -> Result<Option<Vec<u8>>> {
+            let txn = self.db.begin_read()?;
+            let table = txn.open_table(definition(table))?;
+            Ok(table.get(key)?.map(|value| value.value().to_vec()))
+        }
+
+        fn scan(
+            &self,
+            table: Table,
+            first: &[u8],
+            last: &[u8],
+            reverse: bool,
+            visit: &mut Visitor,
+        ) -> Result<()> {
+            let txn = self.db.begin_read()?;
+            let table = txn.open_table(definition(table))?;
+            let range = table.range(first..=last)?;
+            let entries: Box<dyn Iterator<Item = _>> = if reverse {
+                Box::new(range.rev())
+            } else {
+                Box::new(range)
+            };
+            for entry in entries {
+                let (key, value) = entry?;
+                if !visit(key.value(), value.value())? {
+                    break;
+                }
+            }
+            Ok(())
+        }
+
+        fn last(&self, table: Table) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+            let txn = self.db.begin_read()?;
+            let table = txn.open_table(definition(table))?;
+            let last = table
+                .last()?
+                .map(|(key, value)| (key.value().to_vec(), value.value().to_vec()));
+            Ok(last)
+        }
+
+        fn write(&self, batch: Batch, durable: bool) -> Result<()> {
+            let mut txn = self.db.begin_write()?;
+            if !durable {
+                txn.set_durability(Durability::None)?;
+            }
+            {
+                let mut tables = HashMap::new();
+                for op in batch.ops {
+                    let open = match tables.entry(op.table()) {
+                        Entry::Occupied(entry) => entry.into_mut(),
+                        Entry::Vacant(entry) => {
+                            entry.insert(txn.open_table(definition(op.table()))?)
+                        }
+                    };
+                    match op {
+                        Op::Put(_, key, value) => {
+                            open.insert(key.as_slice(), value.as_slice())?;
+                        }
+                        Op::Delete(_, key) => {
+                            open.remove(key.as_slice())?;
+                        }
+                    }
+                }
+            }
+            txn.commit()?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "sled")]
+mod sled_store {
+    use std::collections::HashMap;
+    use std::path::Path;
+
+    use anyhow::{anyhow, Context, Result};
+    use sled::transaction::{ConflictableTransactionError, TransactionError};
+    use sled::{Db, Transactional, Tree};
+
+    use super::{Batch, Op, Store, Table, Visitor};
+
+    pub struct SledStore {
+        db: Db,
+        tables: HashMap<Table, Tree>,
+    }
+
+    impl SledStore {
+        pub fn open(path: &Path, tables: &[Table]) -> Result<Self> {
+            let db =
+                sled::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+            let tables = tables
+                .iter()
+                .map(|&table| Ok((table, db.open_tree(table)?)))
+                .collect::<Result<_>>()?;
+            Ok(Self { db, tables })
+        }
+
+        fn tree(&self, table: Table) -> Result<&Tree> {
+            self.tables
+                .get(table)
+                .ok_or_else(|| anyhow!("Unknown table {}", table))
+        }
+    }
+
+    impl Store for SledStore {
+        fn get(&self, table: Table, key: &[u8]) -> Result<Option<Vec<u8>>> {
+            Ok(self.tree(table)?.get(key)?.map(|value| value.to_vec()))
+        }
+
+        fn scan(
+            &self,
+            table: Table,
+            first: &[u8],
+            last: &[u8],
+            reverse: bool,
+            visit: &mut Visitor,
+        ) -> Result<()> {
+            let range = self.tree(table)?.range(first..=last);
+            let entries: Box<dyn Iterator<Item = _>> = if reverse {
+                Box::new(range.rev())
+            } else {
+                Box::new(range)
+            };
+            for entry in entries {
+                let (key, value) = entry?;
+                if !visit(&key, &value)? {
+                    break;
+                }
+            }
+            Ok(())
+        }
+
+        fn last(&self, table: Table) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+            Ok(self
+                .tree(table)?
+                .last()?
+                .map(|(key, value)| (key.to_vec(), value.to_vec())))
+        }
+
+        fn write(&self, batch: Batch, durable: bool) -> Result<()> {
+            let mut names: Vec<Table> = Vec::new();
+            let mut batches: Vec<sled::Batch> = Vec::new();
+            for op in batch.ops {
+                let position = match names.iter().position(|&name| name == op.table()) {
+                    Some(position) => position,
+                    None => {
+                        names.push(op.table());
+                        batches.push(sled::Batch::default());
+                        names.len() - 1
+                    }
+                };
+                match op {
+                    Op::Put(_, key, value) => batches[position].insert(key, value),
+                    Op::Delete(_, key) => batches[position].remove(key),
+                }
+            }
+            let trees = names
+                .iter()
+                .map(|&table| self.tree(table).cloned())
+                .collect::<Result<Vec<Tree>>>()?;
+            trees
+                .as_slice()
+                .transaction(|trees| {
+                    for (tree, batch) in trees.iter().zip(&batches) {
+                        tree.apply_batch(batch)?;
+                    }
+                    Ok::<_, ConflictableTransactionError<()>>(())
+                })
+                .map_err(|e: TransactionError<()>| anyhow!("sled transaction failed: {:?}", e))?;
+            if durable {
+                self.db.flush()?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "rocksdb")]
+mod rocksdb_store {
+    use std::path::Path;
+
+    use anyhow::{anyhow, Context, Result};
+    use rocksdb::{ColumnFamily, Direction, IteratorMode, Options, WriteBatch, WriteOptions, DB};
+
+    use super::{Batch, Op, Store, Table, Visitor};
+
+    pub struct RocksDbStore {
+        db: DB,
+    }
+
+    impl RocksDbStore {
+        pub fn open(path: &Path, tables: &[Table]) -> Result<Self> {
+            let mut options = Options::default();
+            options.create_if_missing(true);
+            options.create_missing_column_families(true);
+            let db = DB::open_cf(&options, path, tables)
+                .with_context(|| format!("Failed to open {}", path.display()))?;
+            Ok(Self { db })
+        }
+
+        fn column_family(&self, table: Table) -> Result<&ColumnFamily> {
+            self.db
+                .cf_handle(table)
+                .ok_or_else(|| anyhow!("Unknown table {}", table))
+        }
+    }
+
+    impl Store for RocksDbStore {
+        fn get(&self, table: Table, key: &[u8]) -> Result<Option<Vec<u8>>> {
+            Ok(self.db.get_cf(self.column_family(table)?, key)?)
+        }
+
+        fn scan(
+            &self,
+            table: Table,
+            first: &[u8],
+            last: &[u8],
+            reverse: bool,
+            visit: &mut Visitor,
+        ) -> Result<()> {
+            let mode = if reverse {
+                IteratorMode::From(last, Direction::Reverse)
+            } else {
+                IteratorMode::From(first, Direction::Forward)
+            };
+            for entry in self.db.iterator_cf(self.column_family(table)?, mode) {
+                let (key, value) = entry?;
+                let in_range = if reverse {
+                    *key >= *first
+                } else {
+                    *key <= *last
+                };
+                if !in_range || !visit(&key, &value)? {
+                    break;
+                }
+            }
+            Ok(())
+        }
+
+        fn last(&self, table: Table) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+            let mut entries = self
+                .db
+                .iterator_cf(self.column_family(table)?, IteratorMode::End);
+            Ok(entries
+                .next()
+                .transpose()?
+                .map(|(key, value)| (key.to_vec(), value.to_vec())))
+        }
+
+        fn write(&self, batch: Batch, durable: bool) -> Result<()> {
+            let mut write = WriteBatch::default();
+            for op in batch.ops {
+                match op {
+                    Op::Put(table, key, value) => {
+                        write.put_cf(self.column_family(table)?, key, value)
+                    }
+                    Op::Delete(table, key) => write.delete_cf(self.column_family(table)?, key),
+                }
+            }
+            let mut options = WriteOptions::default();
+            options.set_sync(durable);
+            self.db.write_opt(write, &options)?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+mod sqlite_store {
+    use std::path::Path;
+    use std::sync::Mutex;
+
+    use anyhow::{Context, Result};
+    use rusqlite::{params, Connection, OptionalExtension};
+
+    use super::{Batch, Op, Store, Table, Visitor};
+
+    /// One `key BLOB PRIMARY KEY, value BLOB` table per index table, whose key
+    /// order is the byte order the indexes rely on
+    pub struct SqliteStore {
+        connection: Mutex<Connection>,
+    }
+
+    impl SqliteStore {
+        pub fn open(path: &Path, tables: &[Table]) -> Result<Self> {
+            let connection = Connection::open(path)
+                .with_context(|| format!("Failed to open {}", path.display()))?;
+            connection.pragma_update(None, "journal_mode", "WAL")?;
+            for table in tables {
+                connection.execute_batch(&format!(
+                    "CREATE TABLE IF NOT EXISTS \"{}\" (key BLOB PRIMARY KEY, value BLOB NOT NULL) WITHOUT ROWID",
+                    table
+                ))?;
+            }
+            Ok(Self {
+                connection: Mutex::new(connection),
+            })
+        }
+
+        fn connection(&self) -> std::sync::MutexGuard<'_, Connection> {
+            self.connection.lock().expect("sqlite lock poisoned")
+        }
+    }
+
+    impl Store for SqliteStore {
+        fn get(&self, table: Table, key: &[u8]) -> Result<Option<Vec<u8>>> {
+            let connection = self.connection();
+            let mut statement = connection
+                .prepare_cached(&format!("SELECT value FROM \"{}\" WHERE key = ?1", table))?;
+            Ok(statement
+                .query_row(params![key], |row| row.get(0))
+                .optional()?)
+        }
+
+        fn scan(
+            &self,
+            table: Table,
+            first: &[u8],
+            last: &[u8],
+            reverse: bool,
+            visit: &mut Visitor,
+        ) -> Result<()> {
+            let connection = self.connection();
+            let mut statement = connection.prepare_cached(&format!(
+                "SELECT key, value FROM \"{}\" WHERE key >= ?1 AND key <= ?2 ORDER BY key {}",
+                table,
+                if reverse { "DESC" } else { "ASC" }
+            ))?;
+            let mut rows = statement.query(params![first, last])?;
+            while let Some(row) = rows.next()? {
+                let key: Vec<u8> = row.get(0)?;
+                let value: Vec<u8> = row.get(1)?;
+                if !visit(&key, &value)? {
+                    break;
+                }
+            }
+            Ok(())
+        }
+
+        fn last(&self, table: Table) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+            let connection = self.connection();
+            let mut statement = connection.prepare_cached(&format!(
+                "SELECT key, value FROM \"{}\" ORDER BY key DESC LIMIT 1",
+                table
+            ))?;
+            Ok(statement
+                .query_row([], |row| Ok((row.get(0)?, row.get(1)?)))
+                .optional()?)
+        }
+
+        fn write(&self, batch: Batch, durable: bool) -> Result<()> {
+            let mut connection = self.connection();
+            connection.pragma_update(None, "synchronous", if durable { "FULL" } else { "OFF" })?;
+            let txn = connection.transaction()?;
+            for op in batch.ops {
+                match op {
+                    Op::Put(table, key, value) => {
+                        txn.prepare_cached(&format!(
+                            "INSERT OR REPLACE INTO \"{}\" (key, value) VALUES (?1, ?2)",
+                            table
+                        ))?
+                        .execute(params![key, value])?;
+                    }
+                    Op::Delete(table, key) => {
+                        txn.prepare_cached(&format!("DELETE FROM \"{}\" WHERE key = ?1", table))?
+                            .execute(params![key])?;
+                    }
+                }
+            }
+            txn.commit()?;
+            Ok(())
+        }
+    }
+}
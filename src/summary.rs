@@ -0,0 +1,161 @@
+//! Structured summary of how minipool was started, for `/admin/config`.
+//!
+//! Every setting is listed under its environment variable with the value it
+//! resolved to and where that came from (`cli`, `env` or `default`), with
+//! [`Secret`] settings shown as `[redacted]` and the rest passed through the
+//! log [`Redactor`]. Next to them are the compiled-in features, the addresses
+//! minipool listens on and what the node behind it can answer.
+
+use std::any::TypeId;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+
+use axum::{extract::State, response::IntoResponse, Json};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Command};
+use serde::Serialize;
+use tracing::warn;
+
+use crate::listeners::Listener;
+use crate::node_info::{node_info, NodeInfo};
+use crate::redact::{Redactor, Secret, REDACTED};
+use crate::{AppState, Config};
+
+/// Cargo features minipool was built with
+const FEATURES: &[&str] = &[
+    #[cfg(feature = "regtest")]
+    "regtest",
+    #[cfg(feature = "ctv")]
+    "ctv",
+    #[cfg(feature = "sled")]
+    "sled",
+    #[cfg(feature = "rocksdb")]
+    "rocksdb",
+    #[cfg(feature = "sqlite")]
+    "sqlite",
+    #[cfg(feature = "grpc")]
+    "grpc",
+    #[cfg(feature = "graphql")]
+    "graphql",
+];
+
+#[derive(Serialize)]
+struct Setting {
+    /// Comma-separated for settings taking several values
+    value: String,
+    /// `cli`, `env` or `default`
+    source: &'static str,
+}
+
+#[derive(Serialize)]
+struct ListenerSummary {
+    addr: SocketAddr,
+    tls: bool,
+}
+
+impl From<&Listener> for ListenerSummary {
+    fn from(listener: &Listener) -> Self {
+        Self {
+            addr: listener.addr(),
+            tls: listener.is_tls(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Listeners {
+    http: Vec<ListenerSummary>,
+    electrum: Vec<ListenerSummary>,
+    grpc: Option<SocketAddr>,
+    prometheus: SocketAddr,
+}
+
+#[derive(Serialize)]
+pub struct ConfigSummary {
+    version: &'static str,
+    features: &'static [&'static str],
+    /// By environment variable, or by flag for settings without one
+    settings: BTreeMap<String, Setting>,
+    listeners: Listeners,
+}
+
+impl ConfigSummary {
+    pub fn new(
+        config: &Config,
+        command: &Command,
+        matches: &ArgMatches,
+        redactor: &Redactor,
+    ) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            features: FEATURES,
+            settings: settings(command, matches, redactor),
+            listeners: Listeners {
+                http: config.listeners.iter().map(ListenerSummary::from).collect(),
+                electrum: config
+                    .electrum_listeners
+                    .iter()
+                    .map(ListenerSummary::from)
+                    .collect(),
+                grpc: config.grpc_listen,
+                prometheus: config.prometheus_bind_addr,
+            },
+        }
+    }
+}
+
+/// Resolved value and source of every argument that has one
+fn settings(
+    command: &Command,
+    matches: &ArgMatches,
+    redactor: &Redactor,
+) -> BTreeMap<String, Setting> {
+    command
+        .get_arguments()
+        .filter_map(|arg| {
+            let id = arg.get_id().as_str();
+            let source = match matches.value_source(id)? {
+                ValueSource::CommandLine => "cli",
+                ValueSource::EnvVariable => "env",
+                _ => "default",
+            };
+            let values: Vec<_> = matches
+                .get_raw(id)?
+                .map(|value| value.to_string_lossy())
+                .collect();
+            let name = match arg.get_env() {
+                Some(env) => env.to_string_lossy().into_owned(),
+                None => format!("--{}", arg.get_long().unwrap_or(id)),
+            };
+            let value = if arg.get_value_parser().type_id() == TypeId::of::<Secret>() {
+                REDACTED.to_owned()
+            } else {
+                redactor.redact(&values.join(",")).into_owned()
+            };
+            Some((name, Setting { value, source }))
+        })
+        .collect()
+}
+
+#[derive(Serialize)]
+struct ConfigReport<'a> {
+    #[serde(flatten)]
+    summary: &'a ConfigSummary,
+    /// What the node can answer, unset when it can't be reached
+    node: Option<NodeInfo>,
+}
+
+pub async fn get_config(State(state): State<AppState>) -> impl IntoResponse {
+    let node = match node_info(&state.rpc).await {
+        Ok(info) => Some(info),
+        Err(e) => {
+            warn!("Failed to get node info for the config summary: {}", e);
+            None
+        }
+    };
+    Json(ConfigReport {
+        summary: &state.config_summary,
+        node,
+    })
+    .into_response()
+}
@@ -0,0 +1,149 @@
+//! W3C trace context (`traceparent`) propagation.
+//!
+//! Every request gets a trace context: a child of the caller's `traceparent`
+//! when it sent a valid one, a new trace otherwise. The context is kept for the
+//! request's task, and outbound calls made on its behalf carry it on, so a
+//! distributed trace spans the caller, minipool and whatever minipool calls.
+//! Work that outlives the request (webhooks, shadow requests) takes the context
+//! along explicitly.
+
+use std::collections::hash_map::RandomState;
+use std::fmt::{self, Write};
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use axum::extract::{Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+
+use crate::sampling::TraceSampler;
+
+pub const TRACEPARENT: &str = "traceparent";
+
+const SAMPLED_FLAG: u8 = 0x01;
+
+tokio::task_local! {
+    static CURRENT: TraceContext;
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TraceContext {
+    trace_id: [u8; 16],
+    /// Id of minipool's span for the request, the parent of its outbound calls
+    span_id: [u8; 8],
+    flags: u8,
+    /// Whether the request is traced and access-logged locally
+    pub sampled: bool,
+}
+
+/// Ids only need to be unique, not unpredictable
+fn random_id<const N: usize>() -> [u8; N] {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut id = [0; N];
+    for chunk in id.chunks_mut(8) {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        chunk.copy_from_slice(&hasher.finish().to_le_bytes()[..chunk.len()]);
+    }
+    id
+}
+
+fn parse_hex<const N: usize>(s: &str) -> Option<[u8; N]> {
+    if s.len() != N * 2 || !s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+        return None;
+    }
+    let mut bytes = [0; N];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(bytes)
+}
+
+fn write_hex(f: &mut fmt::Formatter, bytes: &[u8]) -> fmt::Result {
+    bytes.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+}
+
+impl TraceContext {
+    /// Starts a new trace
+    fn root(sampled: bool) -> Self {
+        Self {
+            trace_id: random_id(),
+            span_id: random_id(),
+            flags: if sampled { SAMPLED_FLAG } else { 0 },
+            sampled,
+        }
+    }
+
+    /// Continues the caller's trace from a `traceparent` header; a caller that
+    /// propagates a trace always gets the request traced locally
+    fn child_of(traceparent: &str) -> Option<Self> {
+        let mut parts = traceparent.trim().split('-');
+        let version = parse_hex::<1>(parts.next()?)?;
+        let trace_id = parse_hex::<16>(parts.next()?)?;
+        let parent_id = parse_hex::<8>(parts.next()?)?;
+        let flags = parse_hex::<1>(parts.next()?)?;
+        // Later versions may append fields, version 00 may not
+        if version == [0xff]
+            || (version == [0] && parts.next().is_some())
+            || trace_id == [0; 16]
+            || parent_id == [0; 8]
+        {
+            return None;
+        }
+        Some(Self {
+            trace_id,
+            span_id: random_id(),
+            flags: flags[0],
+            sampled: true,
+        })
+    }
+
+    pub fn trace_id(&self) -> String {
+        self.trace_id.iter().fold(String::new(), |mut hex, byte| {
+            let _ = write!(hex, "{:02x}", byte);
+            hex
+        })
+    }
+}
+
+/// The `traceparent` value for calls made on behalf of the request
+impl fmt::Display for TraceContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("00-")?;
+        write_hex(f, &self.trace_id)?;
+        f.write_str("-")?;
+        write_hex(f, &self.span_id)?;
+        write!(f, "-{:02x}", self.flags)
+    }
+}
+
+/// Trace context of the request the current task serves, if any
+pub fn current() -> Option<TraceContext> {
+    CURRENT.try_with(|context| *context).ok()
+}
+
+/// Runs `future` on behalf of the request `context` was taken from
+pub async fn scope<F: Future>(context: Option<TraceContext>, future: F) -> F::Output {
+    match context {
+        Some(context) => CURRENT.scope(context, future).await,
+        None => future.await,
+    }
+}
+
+/// Assigns the request its trace context, sampling it unless the caller is tracing
+pub async fn propagate(
+    State(sampler): State<Arc<TraceSampler>>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let context = req
+        .headers()
+        .get(TRACEPARENT)
+        .and_then(|value| value.to_str().ok())
+        .and_then(TraceContext::child_of)
+        .unwrap_or_else(|| TraceContext::root(sampler.sampled(&req)));
+    req.extensions_mut().insert(context);
+    CURRENT.scope(context, next.run(req)).await
+}
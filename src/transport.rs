@@ -0,0 +1,353 @@
+//! How calls to the node travel (`BACKEND`).
+//!
+//! With `rest` or `hybrid`, the RPC client minipool hands around is built on a
+//! transport that answers block, header, transaction and chain info calls from
+//! the node's REST interface (`-rest`), which needs no credentials and skips
+//! JSON-RPC's overhead. REST answers are reshaped into what the RPC would have
+//! returned, so callers can't tell the difference. `hybrid` sends the calls REST
+//! has no equivalent for, like fee estimates and broadcasts, over RPC; `rest`
+//! fails them, for deployments that only serve the chain.
+//!
+//! Transactions need `txindex=1` or to be in the mempool, like over RPC.
+
+use std::fmt;
+use std::str::FromStr;
+
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use bitcoincore_rpc::jsonrpc::error::{Error as JsonRpcError, RpcError};
+use bitcoincore_rpc::jsonrpc::{Request, Response};
+use futures_util::future::try_join_all;
+use reqwest::{Method, StatusCode, Url};
+use serde_json::value::to_raw_value;
+use serde_json::{json, Value};
+use tracing::debug;
+
+use crate::outbound::OutboundClient;
+use crate::rpc::{RpcFuture, RpcTransport};
+
+// Error codes from Bitcoin Core's rpc/protocol.h
+const RPC_METHOD_NOT_FOUND: i32 = -32601;
+const RPC_INVALID_ADDRESS_OR_KEY: i32 = -5;
+const RPC_INVALID_PARAMETER: i32 = -8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeBackend {
+    Rpc,
+    Rest,
+    Hybrid,
+}
+
+impl FromStr for NodeBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rpc" => Ok(Self::Rpc),
+            "rest" => Ok(Self::Rest),
+            "hybrid" => Ok(Self::Hybrid),
+            _ => bail!("Unknown backend {:?}, expected rpc, rest or hybrid", s),
+        }
+    }
+}
+
+impl fmt::Display for NodeBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Rpc => "rpc",
+            Self::Rest => "rest",
+            Self::Hybrid => "hybrid",
+        })
+    }
+}
+
+/// Outcome of trying a call on the REST interface
+enum RestOutcome {
+    /// What the RPC would have returned
+    Answer(Value),
+    /// The error the RPC would have answered, like for an unknown block
+    Error(RpcError),
+    /// REST has no equivalent of the call
+    Unserved,
+    /// REST failed to answer
+    Failed(String),
+}
+
+impl From<Result<Option<Value>, String>> for RestOutcome {
+    fn from(result: Result<Option<Value>, String>) -> Self {
+        match result {
+            Ok(Some(value)) => Self::Answer(value),
+            Ok(None) => Self::Unserved,
+            Err(e) => Self::Failed(e),
+        }
+    }
+}
+
+fn rpc_error(code: i32, message: &str) -> RestOutcome {
+    RestOutcome::Error(RpcError {
+        code,
+        message: message.to_string(),
+        data: None,
+    })
+}
+
+fn block_not_found() -> RestOutcome {
+    rpc_error(RPC_INVALID_ADDRESS_OR_KEY, "Block not found")
+}
+
+/// JSON-RPC transport serving what it can from the node's REST interface
+struct RestTransport {
+    http: OutboundClient,
+    base: Url,
+    /// Takes the calls REST can't serve in hybrid mode
+    rpc: Option<Arc<dyn RpcTransport>>,
+}
+
+impl RestTransport {
+    /// Body of a REST resource, `None` when the node answers 404
+    async fn get(&self, path: &str) -> Result<Option<String>, String> {
+        let url = self.base.join(path).map_err(|e| e.to_string())?;
+        let request = self.http.request(Method::GET, url);
+        let response = self
+            .http
+            .send("node_rest", request)
+            .await
+            .map_err(|e| e.to_string())?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => {
+                response.text().await.map(Some).map_err(|e| e.to_string())
+            }
+            status => Err(format!("{} answered {}", path, status)),
+        }
+    }
+
+    /// JSON of a REST resource, `None` when the node answers 404
+    async fn get_json(&self, path: &str) -> Result<Option<Value>, String> {
+        match self.get(path).await? {
+            Some(body) => serde_json::from_str(&body)
+                .map(Some)
+                .map_err(|e| e.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    async fn chain_info(&self, field: Option<&str>) -> RestOutcome {
+        let info = match self.get_json("rest/chaininfo.json").await {
+            Ok(Some(info)) => info,
+            result => return result.into(),
+        };
+        match field {
+            Some(field) => RestOutcome::Answer(info[field].clone()),
+            None => RestOutcome::Answer(info),
+        }
+    }
+
+    async fn block_hash(&self, height: &Value) -> RestOutcome {
+        let Some(height) = height.as_u64() else {
+            return RestOutcome::Unserved;
+        };
+        match self
+            .get_json(&format!("rest/blockhashbyheight/{}.json", height))
+            .await
+        {
+            Ok(Some(hash)) => RestOutcome::Answer(hash["blockhash"].clone()),
+            Ok(None) => rpc_error(RPC_INVALID_PARAMETER, "Block height out of range"),
+            Err(e) => RestOutcome::Failed(e),
+        }
+    }
+
+    async fn block(&self, hash: &str, verbosity: u64) -> RestOutcome {
+        let path = match verbosity {
+            0 => format!("rest/block/{}.hex", hash),
+            1 => format!("rest/block/notxdetails/{}.json", hash),
+            // REST adds prevouts and fees to what verbosity 2 has
+            _ => format!("rest/block/{}.json", hash),
+        };
+        let result = if verbosity == 0 {
+            self.get(&path)
+                .await
+                .map(|hex| hex.map(|hex| Value::String(hex.trim().to_string())))
+        } else {
+            self.get_json(&path).await
+        };
+        match result {
+            Ok(None) => block_not_found(),
+            result => result.into(),
+        }
+    }
+
+    async fn block_header(&self, hash: &str, verbose: bool) -> RestOutcome {
+        if verbose {
+            // Unknown blocks get an empty list of headers
+            return match self
+                .get_json(&format!("rest/headers/1/{}.json", hash))
+                .await
+            {
+                Ok(Some(Value::Array(mut headers))) if !headers.is_empty() => {
+                    RestOutcome::Answer(headers.swap_remove(0))
+                }
+                Ok(_) => block_not_found(),
+                Err(e) => RestOutcome::Failed(e),
+            };
+        }
+        match self.get(&format!("rest/headers/1/{}.hex", hash)).await {
+            Ok(Some(hex)) if !hex.trim().is_empty() => {
+                RestOutcome::Answer(Value::String(hex.trim().to_string()))
+            }
+            Ok(_) => block_not_found(),
+            Err(e) => RestOutcome::Failed(e),
+        }
+    }
+
+    async fn transaction(&self, txid: &str, verbose: bool) -> RestOutcome {
+        let result = if verbose {
+            self.get_json(&format!("rest/tx/{}.json", txid)).await
+        } else {
+            self.get(&format!("rest/tx/{}.hex", txid))
+                .await
+                .map(|hex| hex.map(|hex| Value::String(hex.trim().to_string())))
+        };
+        let mut tx = match result {
+            Ok(Some(tx)) => tx,
+            Ok(None) => {
+                return rpc_error(
+                    RPC_INVALID_ADDRESS_OR_KEY,
+                    "No such mempool or blockchain transaction",
+                )
+            }
+            Err(e) => return RestOutcome::Failed(e),
+        };
+        // REST leaves out the confirmation details getrawtransaction adds
+        if let Some(block_hash) = tx.get("blockhash").and_then(Value::as_str) {
+            let header = match self.block_header(block_hash, true).await {
+                RestOutcome::Answer(header) => header,
+                outcome => return outcome,
+            };
+            tx["confirmations"] = json!(header["confirmations"].as_i64().unwrap_or(0).max(0));
+            tx["time"] = header["time"].clone();
+            tx["blocktime"] = header["time"].clone();
+        }
+        RestOutcome::Answer(tx)
+    }
+
+    async fn mempool(&self, verbose: bool) -> RestOutcome {
+        if verbose {
+            return self.get_json("rest/mempool/contents.json").await.into();
+        }
+        // Nodes before 25.0 ignore `verbose` and answer with the entries
+        match self
+            .get_json("rest/mempool/contents.json?verbose=false")
+            .await
+        {
+            Ok(Some(txids)) if txids.is_array() => RestOutcome::Answer(txids),
+            Ok(_) => RestOutcome::Unserved,
+            Err(e) => RestOutcome::Failed(e),
+        }
+    }
+
+    /// Answers the call from REST where there's an equivalent
+    async fn rest_call(&self, method: &str, params: &[Value]) -> RestOutcome {
+        let param = |index: usize| params.get(index).unwrap_or(&Value::Null);
+        // Verbosity flags are booleans or numbers depending on the call and node version
+        let flag = |index: usize, default: u64| match param(index) {
+            Value::Bool(flag) => u64::from(*flag),
+            Value::Number(n) => n.as_u64().unwrap_or(default),
+            _ => default,
+        };
+        match (method, param(0).as_str()) {
+            ("getblockchaininfo", _) => self.chain_info(None).await,
+            ("getbestblockhash", _) => self.chain_info(Some("bestblockhash")).await,
+            ("getblockcount", _) => self.chain_info(Some("blocks")).await,
+            ("getblockhash", _) => self.block_hash(param(0)).await,
+            ("getblock", Some(hash)) => self.block(hash, flag(1, 1)).await,
+            ("getblockheader", Some(hash)) => self.block_header(hash, flag(1, 1) != 0).await,
+            // Looking a transaction up in a given block needs the RPC
+            ("getrawtransaction", Some(txid)) if param(2).is_null() => {
+                self.transaction(txid, flag(1, 0) != 0).await
+            }
+            ("getmempoolinfo", _) => self.get_json("rest/mempool/info.json").await.into(),
+            ("getrawmempool", _) => self.mempool(flag(0, 0) != 0).await,
+            _ => RestOutcome::Unserved,
+        }
+    }
+}
+
+fn respond(request: &Request, result: Option<Value>, error: Option<RpcError>) -> Response {
+    Response {
+        result: result.and_then(|value| to_raw_value(&value).ok()),
+        error,
+        id: request.id.clone(),
+        jsonrpc: request.jsonrpc.map(String::from),
+    }
+}
+
+impl RestTransport {
+    async fn send_request<'a>(&'a self, request: Request<'a>) -> Result<Response, JsonRpcError> {
+        let params: Vec<Value> = match request.params {
+            Some(params) => serde_json::from_str(params.get())?,
+            None => Vec::new(),
+        };
+        let outcome = self.rest_call(request.method, &params).await;
+        if let RestOutcome::Failed(e) = &outcome {
+            debug!("Node REST failed for {}: {}", request.method, e);
+        }
+        match (outcome, &self.rpc) {
+            (RestOutcome::Answer(value), _) => Ok(respond(&request, Some(value), None)),
+            (RestOutcome::Error(error), _) => Ok(respond(&request, None, Some(error))),
+            (RestOutcome::Unserved | RestOutcome::Failed(_), Some(rpc)) => {
+                rpc.send_request(request).await
+            }
+            (RestOutcome::Unserved, None) => Ok(respond(
+                &request,
+                None,
+                Some(RpcError {
+                    code: RPC_METHOD_NOT_FOUND,
+                    message: format!(
+                        "{} is not served by the node's REST interface",
+                        request.method
+                    ),
+                    data: None,
+                }),
+            )),
+            (RestOutcome::Failed(e), None) => Err(JsonRpcError::Transport(e.into())),
+        }
+    }
+}
+
+impl RpcTransport for RestTransport {
+    fn send_request<'a>(&'a self, request: Request<'a>) -> RpcFuture<'a, Response> {
+        Box::pin(self.send_request(request))
+    }
+
+    fn send_batch<'a>(&'a self, requests: &'a [Request<'a>]) -> RpcFuture<'a, Vec<Response>> {
+        // Each call is answered on its own, so they are all sent at once
+        Box::pin(try_join_all(
+            requests
+                .iter()
+                .map(|request| self.send_request(request.clone())),
+        ))
+    }
+}
+
+/// Transport of the node's RPC over `rpc`, or in rest and hybrid mode of its
+/// REST interface at `rest`
+pub fn transport(
+    backend: NodeBackend,
+    rpc: Arc<dyn RpcTransport>,
+    http: OutboundClient,
+    rest: Option<Url>,
+) -> Result<Arc<dyn RpcTransport>> {
+    let rest = match (backend, rest) {
+        (NodeBackend::Rpc, _) => return Ok(rpc),
+        (_, Some(rest)) => rest,
+        (_, None) => bail!("The {} backend needs the node's REST URL", backend),
+    };
+    let rpc = (backend == NodeBackend::Hybrid).then_some(rpc);
+    Ok(Arc::new(RestTransport {
+        http,
+        base: rest,
+        rpc,
+    }))
+}
@@ -0,0 +1,735 @@
+//! Esplora-compatible transaction endpoints, so wallets built on esplora
+//! clients (BDK and the like) can use minipool as their backend.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::str::FromStr;
+
+use axum::{
+    extract::{ConnectInfo, Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use bitcoincore_rpc::bitcoin::absolute::LockTime;
+use bitcoincore_rpc::bitcoin::hex::{DisplayHex, FromHex};
+use bitcoincore_rpc::bitcoin::{
+    relative, Address, Amount, BlockHash, Network, OutPoint, Script, ScriptBuf, Sequence,
+    Transaction, TxIn, TxOut, Txid,
+};
+use bitcoincore_rpc::json::GetRawTransactionResult;
+use bitcoincore_rpc::jsonrpc::error::{Error as JsonRpcError, RpcError};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::audit::Interface;
+use crate::compat;
+use crate::mempool::MempoolTracker;
+use crate::rpc::Rpc;
+use crate::AppState;
+
+#[derive(Serialize)]
+pub struct EsploraTx {
+    pub txid: Txid,
+    pub version: i32,
+    pub locktime: u32,
+    pub vin: Vec<EsploraVin>,
+    pub vout: Vec<EsploraVout>,
+    pub size: usize,
+    pub weight: u64,
+    /// Fee in sats, 0 for coinbase transactions
+    pub fee: u64,
+    pub status: EsploraStatus,
+    locktime_info: LocktimeInfo,
+}
+
+/// What `locktime` means, at most one of `height` and `time` being set
+#[derive(Serialize)]
+struct LocktimeInfo {
+    /// Height before which the transaction can't be mined
+    #[serde(skip_serializing_if = "Option::is_none")]
+    height: Option<u32>,
+    /// Median time past, in seconds since epoch, before which the transaction
+    /// can't be mined
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time: Option<u32>,
+    /// The locktime only applies when an input has a sequence below `0xffffffff`
+    enforced: bool,
+}
+
+/// What an input's `sequence` means
+#[derive(Serialize)]
+struct SequenceInfo {
+    /// `0xffffffff`, ruling out replacement, relative locks and, if every input
+    /// is final, the locktime
+    #[serde(rename = "final")]
+    is_final: bool,
+    /// Signals BIP125 replaceability, which holds for the whole transaction
+    rbf: bool,
+    /// BIP68 relative lock in blocks since the spent output confirmed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    relative_blocks: Option<u16>,
+    /// BIP68 relative lock in seconds since the spent output confirmed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    relative_seconds: Option<u32>,
+}
+
+#[derive(Serialize)]
+pub struct EsploraVin {
+    pub txid: Txid,
+    pub vout: u32,
+    /// Spent output, absent for coinbase inputs
+    pub prevout: Option<EsploraVout>,
+    pub scriptsig: String,
+    pub scriptsig_asm: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub witness: Vec<String>,
+    pub is_coinbase: bool,
+    pub sequence: u32,
+    sequence_info: SequenceInfo,
+}
+
+#[derive(Serialize)]
+pub struct EsploraVout {
+    pub scriptpubkey: String,
+    pub scriptpubkey_asm: String,
+    pub scriptpubkey_type: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scriptpubkey_address: Option<String>,
+    pub value: u64,
+}
+
+#[derive(Clone, Serialize)]
+pub struct EsploraStatus {
+    pub confirmed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_height: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_hash: Option<BlockHash>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_time: Option<u64>,
+    /// When an unconfirmed transaction was first seen, in seconds since epoch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_seen: Option<u64>,
+}
+
+/// Script type names as used by esplora
+fn script_type(script: &Script) -> &'static str {
+    if script.is_empty() {
+        "empty"
+    } else if script.is_p2pk() {
+        "p2pk"
+    } else if script.is_p2pkh() {
+        "p2pkh"
+    } else if script.is_p2sh() {
+        "p2sh"
+    } else if script.is_p2wpkh() {
+        "v0_p2wpkh"
+    } else if script.is_p2wsh() {
+        "v0_p2wsh"
+    } else if script.is_p2tr() {
+        "v1_p2tr"
+    } else if script.is_op_return() {
+        "op_return"
+    } else if script.is_multisig() {
+        "multisig"
+    } else {
+        "unknown"
+    }
+}
+
+fn locktime_info(tx: &Transaction) -> LocktimeInfo {
+    let (height, time) = match tx.lock_time {
+        LockTime::Blocks(height) if height.to_consensus_u32() == 0 => (None, None),
+        LockTime::Blocks(height) => (Some(height.to_consensus_u32()), None),
+        LockTime::Seconds(time) => (None, Some(time.to_consensus_u32())),
+    };
+    LocktimeInfo {
+        height,
+        time,
+        enforced: tx.is_lock_time_enabled(),
+    }
+}
+
+fn sequence_info(tx: &Transaction, input: &TxIn) -> SequenceInfo {
+    let sequence = input.sequence;
+    // Relative locks take version 2 and don't apply to coinbase inputs
+    let relative = if tx.version.0 >= 2 && !input.previous_output.is_null() {
+        sequence.to_relative_lock_time()
+    } else {
+        None
+    };
+    let (relative_blocks, relative_seconds) = match relative {
+        Some(relative::LockTime::Blocks(height)) => (Some(height.value()), None),
+        Some(relative::LockTime::Time(time)) => (None, Some(time.value() as u32 * 512)),
+        None => (None, None),
+    };
+    SequenceInfo {
+        is_final: sequence == Sequence::MAX,
+        rbf: sequence.is_rbf(),
+        relative_blocks,
+        relative_seconds,
+    }
+}
+
+fn esplora_vout(output: &TxOut, network: Network) -> EsploraVout {
+    EsploraVout {
+        scriptpubkey: output.script_pubkey.to_hex_string(),
+        scriptpubkey_asm: output.script_pubkey.to_asm_string(),
+        scriptpubkey_type: script_type(&output.script_pubkey),
+        scriptpubkey_address: Address::from_script(&output.script_pubkey, network)
+            .ok()
+            .map(|address| address.to_string()),
+        value: output.value.to_sat(),
+    }
+}
+
+impl EsploraStatus {
+    pub fn unconfirmed() -> Self {
+        Self {
+            confirmed: false,
+            block_height: None,
+            block_hash: None,
+            block_time: None,
+            first_seen: None,
+        }
+    }
+
+    pub fn unconfirmed_since(first_seen: Option<u64>) -> Self {
+        Self {
+            first_seen,
+            ..Self::unconfirmed()
+        }
+    }
+}
+
+/// Status of something confirmed in the block `hash`
+pub async fn block_status(
+    rpc: &Rpc,
+    hash: &BlockHash,
+) -> Result<EsploraStatus, bitcoincore_rpc::Error> {
+    let header = rpc.get_block_header_info(hash).await?;
+    Ok(EsploraStatus {
+        confirmed: true,
+        block_height: Some(header.height as u64),
+        block_hash: Some(*hash),
+        block_time: Some(header.time as u64),
+        first_seen: None,
+    })
+}
+
+/// `?block=` on transaction lookups: the hash or height of the block holding
+/// the transaction, which lets nodes without txindex find confirmed ones
+#[derive(Deserialize)]
+pub struct BlockHintQuery {
+    block: Option<String>,
+}
+
+/// Description of the `block` query parameter in the API docs
+pub const BLOCK_HINT: &str =
+    "Hash or height of the block holding the transaction, needed without txindex";
+
+/// Block named by a `?block=` hint
+pub enum BlockHint {
+    Hash(BlockHash),
+    Height(u64),
+}
+
+impl BlockHintQuery {
+    pub fn parse(&self) -> Result<Option<BlockHint>, (StatusCode, &'static str)> {
+        let Some(block) = &self.block else {
+            return Ok(None);
+        };
+        if let Ok(hash) = BlockHash::from_str(block) {
+            return Ok(Some(BlockHint::Hash(hash)));
+        }
+        match block.parse() {
+            Ok(height) => Ok(Some(BlockHint::Height(height))),
+            Err(_) => Err((StatusCode::BAD_REQUEST, "Invalid block hint")),
+        }
+    }
+}
+
+/// Hash of the hinted block, if any
+pub async fn hinted_block(
+    rpc: &Rpc,
+    hint: Option<&BlockHint>,
+) -> Result<Option<BlockHash>, bitcoincore_rpc::Error> {
+    match hint {
+        Some(BlockHint::Hash(hash)) => Ok(Some(*hash)),
+        Some(BlockHint::Height(height)) => rpc.get_block_hash(*height).await.map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Confirmation status of a looked-up transaction; mempool transactions have no block
+async fn status(
+    rpc: &Rpc,
+    mempool: &MempoolTracker,
+    info: &GetRawTransactionResult,
+) -> Result<EsploraStatus, bitcoincore_rpc::Error> {
+    match info.blockhash {
+        Some(hash) => block_status(rpc, &hash).await,
+        None => Ok(EsploraStatus::unconfirmed_since(
+            mempool.first_seen(&info.txid),
+        )),
+    }
+}
+
+/// Looks up a transaction along with the outputs it spends. Needs `txindex`
+/// for confirmed transactions, like any `getrawtransaction` lookup.
+pub async fn esplora_tx(
+    rpc: &Rpc,
+    network: Network,
+    mempool: &MempoolTracker,
+    txid: &Txid,
+) -> Result<EsploraTx, bitcoincore_rpc::Error> {
+    let info = rpc.get_raw_transaction_info(txid, None).await?;
+    let tx = info
+        .transaction()
+        .map_err(|e| bitcoincore_rpc::Error::ReturnedError(e.to_string()))?;
+    let status = status(rpc, mempool, &info).await?;
+    esplora_tx_with_prevouts(rpc, network, &tx, status, &mut HashMap::new()).await
+}
+
+#[derive(Deserialize)]
+struct VerboseTx {
+    hex: String,
+    vin: Vec<VerboseVin>,
+}
+
+#[derive(Deserialize)]
+struct VerboseVin {
+    /// Only present when the node has undo data to resolve it
+    prevout: Option<VerbosePrevout>,
+}
+
+#[derive(Deserialize)]
+struct VerbosePrevout {
+    #[serde(with = "bitcoincore_rpc::bitcoin::amount::serde::as_btc")]
+    value: Amount,
+    #[serde(rename = "scriptPubKey")]
+    script_pub_key: VerboseScript,
+}
+
+#[derive(Deserialize)]
+struct VerboseScript {
+    hex: ScriptBuf,
+}
+
+/// Looks up a transaction confirmed in `block_hash`, which needs no txindex.
+/// The node resolves the prevouts from its undo data (verbosity 2, Bitcoin Core
+/// 25 and later), so parents are only fetched for those it leaves out.
+pub async fn esplora_tx_in_block(
+    rpc: &Rpc,
+    network: Network,
+    txid: &Txid,
+    block_hash: &BlockHash,
+) -> Result<EsploraTx, bitcoincore_rpc::Error> {
+    let verbose: VerboseTx = rpc
+        .call(
+            "getrawtransaction",
+            &[json!(txid), json!(2), json!(block_hash)],
+        )
+        .await?;
+    let tx: Transaction =
+        bitcoincore_rpc::bitcoin::consensus::encode::deserialize_hex(&verbose.hex)
+            .map_err(|e| bitcoincore_rpc::Error::ReturnedError(e.to_string()))?;
+    let mut known: HashMap<OutPoint, TxOut> = tx
+        .input
+        .iter()
+        .zip(verbose.vin)
+        .filter_map(|(input, vin)| {
+            let prevout = vin.prevout?;
+            Some((
+                input.previous_output,
+                TxOut {
+                    value: prevout.value,
+                    script_pubkey: prevout.script_pub_key.hex,
+                },
+            ))
+        })
+        .collect();
+    let status = block_status(rpc, block_hash).await?;
+    let mut parents = HashMap::new();
+    let mut prevouts = Vec::with_capacity(tx.input.len());
+    for input in tx
+        .input
+        .iter()
+        .filter(|input| !input.previous_output.is_null())
+    {
+        let outpoint = &input.previous_output;
+        prevouts.push(match known.remove(outpoint) {
+            Some(output) => output,
+            None => parent_output(rpc, &mut parents, outpoint).await?,
+        });
+    }
+    Ok(esplora_tx_from_prevouts(network, &tx, status, prevouts))
+}
+
+/// Output `outpoint` of a parent transaction, fetched unless it's already in `parents`
+async fn parent_output(
+    rpc: &Rpc,
+    parents: &mut HashMap<Txid, Transaction>,
+    outpoint: &OutPoint,
+) -> Result<TxOut, bitcoincore_rpc::Error> {
+    let parent = match parents.entry(outpoint.txid) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => entry.insert(rpc.get_raw_transaction(&outpoint.txid, None).await?),
+    };
+    parent
+        .output
+        .get(outpoint.vout as usize)
+        .cloned()
+        .ok_or_else(|| {
+            bitcoincore_rpc::Error::ReturnedError(format!("Missing prevout {}", outpoint))
+        })
+}
+
+/// Builds the esplora form of `tx`, fetching the transactions it spends from
+/// unless they're already in `parents`
+pub async fn esplora_tx_with_prevouts(
+    rpc: &Rpc,
+    network: Network,
+    tx: &Transaction,
+    status: EsploraStatus,
+    parents: &mut HashMap<Txid, Transaction>,
+) -> Result<EsploraTx, bitcoincore_rpc::Error> {
+    let mut prevouts = Vec::with_capacity(tx.input.len());
+    for input in tx
+        .input
+        .iter()
+        .filter(|input| !input.previous_output.is_null())
+    {
+        prevouts.push(parent_output(rpc, parents, &input.previous_output).await?);
+    }
+    Ok(esplora_tx_from_prevouts(network, tx, status, prevouts))
+}
+
+/// Builds the esplora form of `tx` from the outputs its non-coinbase inputs
+/// spend, in input order
+fn esplora_tx_from_prevouts(
+    network: Network,
+    tx: &Transaction,
+    status: EsploraStatus,
+    prevouts: Vec<TxOut>,
+) -> EsploraTx {
+    let mut prevouts = prevouts.into_iter();
+    let mut vin = Vec::with_capacity(tx.input.len());
+    let mut input_value = 0;
+    for input in &tx.input {
+        let is_coinbase = input.previous_output.is_null();
+        let prevout = if is_coinbase {
+            None
+        } else {
+            let output = prevouts
+                .next()
+                .expect("a prevout is given for every non-coinbase input");
+            input_value += output.value.to_sat();
+            Some(esplora_vout(&output, network))
+        };
+        vin.push(EsploraVin {
+            txid: input.previous_output.txid,
+            vout: input.previous_output.vout,
+            prevout,
+            scriptsig: input.script_sig.to_hex_string(),
+            scriptsig_asm: input.script_sig.to_asm_string(),
+            witness: input
+                .witness
+                .iter()
+                .map(|item| item.to_lower_hex_string())
+                .collect(),
+            is_coinbase,
+            sequence: input.sequence.0,
+            sequence_info: sequence_info(tx, input),
+        });
+    }
+
+    let output_value: u64 = tx.output.iter().map(|output| output.value.to_sat()).sum();
+
+    EsploraTx {
+        txid: tx.compute_txid(),
+        version: tx.version.0,
+        locktime: tx.lock_time.to_consensus_u32(),
+        vout: tx
+            .output
+            .iter()
+            .map(|output| esplora_vout(output, network))
+            .collect(),
+        vin,
+        size: tx.total_size(),
+        weight: tx.weight().to_wu(),
+        fee: if tx.is_coinbase() {
+            0
+        } else {
+            input_value.saturating_sub(output_value)
+        },
+        status,
+        locktime_info: locktime_info(tx),
+    }
+}
+
+/// A transaction in the esplora format; `?block=` finds confirmed ones without txindex
+pub async fn get_tx(
+    State(state): State<AppState>,
+    Path(txid): Path<String>,
+    Query(hint): Query<BlockHintQuery>,
+) -> impl IntoResponse {
+    let Ok(parsed) = Txid::from_str(&txid) else {
+        return (StatusCode::BAD_REQUEST, "Invalid txid").into_response();
+    };
+    let hint = match hint.parse() {
+        Ok(hint) => hint,
+        Err(response) => return response.into_response(),
+    };
+    let tx = match hinted_block(&state.rpc, hint.as_ref()).await {
+        Ok(Some(block_hash)) => {
+            esplora_tx_in_block(&state.rpc, state.network, &parsed, &block_hash).await
+        }
+        Ok(None) => esplora_tx(&state.rpc, state.network, &state.mempool, &parsed).await,
+        Err(e) => Err(e),
+    };
+    match tx {
+        Ok(tx) => Json(tx).into_response(),
+        Err(e) => {
+            warn!("Failed to get transaction {}: {}", txid, e);
+            (StatusCode::NOT_FOUND, "Transaction not found").into_response()
+        }
+    }
+}
+
+pub async fn get_tx_status(
+    State(state): State<AppState>,
+    Path(txid): Path<String>,
+    Query(hint): Query<BlockHintQuery>,
+) -> impl IntoResponse {
+    let Ok(parsed) = Txid::from_str(&txid) else {
+        return (StatusCode::BAD_REQUEST, "Invalid txid").into_response();
+    };
+    let hint = match hint.parse() {
+        Ok(hint) => hint,
+        Err(response) => return response.into_response(),
+    };
+    let lookup = async {
+        let block_hash = hinted_block(&state.rpc, hint.as_ref()).await?;
+        let info = state
+            .rpc
+            .get_raw_transaction_info(&parsed, block_hash.as_ref())
+            .await?;
+        status(&state.rpc, &state.mempool, &info).await
+    };
+    match lookup.await {
+        Ok(status) => Json(status).into_response(),
+        Err(e) => {
+            warn!("Failed to get status of transaction {}: {}", txid, e);
+            (StatusCode::NOT_FOUND, "Transaction not found").into_response()
+        }
+    }
+}
+
+/// Raw transaction hex shared by the hex and binary routes; errors are ready-made responses
+async fn lookup_raw_tx_hex(
+    state: AppState,
+    txid: &str,
+    hint: BlockHintQuery,
+) -> Result<String, Response> {
+    let Ok(parsed) = Txid::from_str(txid) else {
+        return Err((StatusCode::BAD_REQUEST, "Invalid txid").into_response());
+    };
+    let hint = hint.parse().map_err(IntoResponse::into_response)?;
+    let lookup = async {
+        let block_hash = hinted_block(&state.rpc, hint.as_ref()).await?;
+        state
+            .rpc
+            .get_raw_transaction_hex(&parsed, block_hash.as_ref())
+            .await
+    };
+    match lookup.await {
+        Ok(hex) => Ok(hex),
+        Err(e) => {
+            warn!("Failed to get raw transaction {}: {}", txid, e);
+            Err((StatusCode::NOT_FOUND, "Transaction not found").into_response())
+        }
+    }
+}
+
+pub async fn get_tx_hex(
+    State(state): State<AppState>,
+    Path(txid): Path<String>,
+    Query(hint): Query<BlockHintQuery>,
+) -> Response {
+    match lookup_raw_tx_hex(state, &txid, hint).await {
+        Ok(hex) => ([(header::CONTENT_TYPE, "text/plain")], hex).into_response(),
+        Err(response) => response,
+    }
+}
+
+pub async fn get_tx_raw(
+    State(state): State<AppState>,
+    Path(txid): Path<String>,
+    Query(hint): Query<BlockHintQuery>,
+) -> Response {
+    let hex = match lookup_raw_tx_hex(state, &txid, hint).await {
+        Ok(hex) => hex,
+        Err(response) => return response,
+    };
+    match Vec::<u8>::from_hex(&hex) {
+        Ok(bytes) => ([(header::CONTENT_TYPE, "application/octet-stream")], bytes).into_response(),
+        Err(e) => {
+            warn!("Node returned invalid hex for transaction {}: {}", txid, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "RPC error").into_response()
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct BroadcastError {
+    /// Stable machine-readable reason
+    pub error: &'static str,
+    /// Rejection message as reported by the node
+    pub message: String,
+}
+
+/// Maps `sendrawtransaction` rejections to client errors, `None` for anything else
+pub fn broadcast_rejection(error: &bitcoincore_rpc::Error) -> Option<(StatusCode, BroadcastError)> {
+    // Error codes from Bitcoin Core's rpc/protocol.h
+    const RPC_DESERIALIZATION_ERROR: i32 = -22;
+    const RPC_VERIFY_ERROR: i32 = -25;
+    const RPC_VERIFY_REJECTED: i32 = -26;
+    const RPC_VERIFY_ALREADY_IN_CHAIN: i32 = -27;
+
+    let bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(RpcError { code, message, .. })) = error
+    else {
+        return None;
+    };
+    let (status, reason) = match *code {
+        RPC_DESERIALIZATION_ERROR => (StatusCode::BAD_REQUEST, "invalid-transaction"),
+        RPC_VERIFY_ALREADY_IN_CHAIN => (StatusCode::CONFLICT, "already-in-chain"),
+        RPC_VERIFY_ERROR if message.contains("missing") => {
+            (StatusCode::BAD_REQUEST, "missing-inputs")
+        }
+        RPC_VERIFY_ERROR => (StatusCode::BAD_REQUEST, "verify-error"),
+        RPC_VERIFY_REJECTED if message.contains("min relay fee") || message.contains("min fee") => {
+            (StatusCode::BAD_REQUEST, "fee-too-low")
+        }
+        RPC_VERIFY_REJECTED if message.contains("txn-already-known") => {
+            (StatusCode::CONFLICT, "already-in-mempool")
+        }
+        RPC_VERIFY_REJECTED => (StatusCode::BAD_REQUEST, "rejected"),
+        _ => return None,
+    };
+    Some((
+        status,
+        BroadcastError {
+            error: reason,
+            message: message.clone(),
+        },
+    ))
+}
+
+/// Broadcasts a hex encoded transaction, answering with its txid like esplora.
+/// Rejections are JSON `{error, message}`, or esplora's plain text in a compatibility mode.
+pub async fn post_tx(
+    State(state): State<AppState>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    body: String,
+) -> impl IntoResponse {
+    let hex = body.trim().to_owned();
+    let result = state.rpc.send_raw_transaction(&hex).await;
+    state
+        .audit
+        .record(&state.rpc, Interface::Http, Some(client), &hex, &result)
+        .await;
+    match result {
+        Ok(txid) => {
+            info!("Broadcast transaction {}", txid);
+            state.hooks.broadcast(txid, hex);
+            (StatusCode::OK, txid.to_string()).into_response()
+        }
+        Err(e) => match broadcast_rejection(&e) {
+            Some((status, rejection)) => {
+                metrics::counter!("tx_broadcast_rejected_total", "reason" => rejection.error)
+                    .increment(1);
+                match state.compat.and_then(|_| compat::broadcast_error(&e)) {
+                    Some(response) => response,
+                    None => (status, Json(rejection)).into_response(),
+                }
+            }
+            None => {
+                warn!("Failed to broadcast transaction: {}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "RPC error").into_response()
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoincore_rpc::bitcoin::transaction::Version;
+    use bitcoincore_rpc::bitcoin::Witness;
+
+    use super::*;
+    use crate::golden;
+
+    #[test]
+    fn golden_tx() {
+        let script = |hex: &str| ScriptBuf::from_hex(hex).unwrap();
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::from_height(840000).unwrap(),
+            input: vec![TxIn {
+                previous_output: OutPoint::new(
+                    "a1075db55d416d3ca199f55b6084e2115b9345e16c5cf302fc80e9d5fbf5d48d"
+                        .parse()
+                        .unwrap(),
+                    1,
+                ),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::from_slice(&[
+                    Vec::from_hex("3044022059b3bd3c6a2cbd3c1a0bd02ccaa8d6c2fbbd4ef64a1f5e1d0b8f1e1c4e1f2a0b02204b4f6e2a49e0b1e4d0c7c1e0e3f4b2a1c9d8e7f6a5b4c3d2e1f0a9b8c7d6e5f401").unwrap(),
+                    Vec::from_hex("02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5").unwrap(),
+                ]),
+            }],
+            output: vec![
+                TxOut {
+                    value: Amount::from_sat(100000),
+                    script_pubkey: script("76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac"),
+                },
+                TxOut {
+                    value: Amount::ZERO,
+                    script_pubkey: script("6a0568656c6c6f"),
+                },
+            ],
+        };
+        let prevout = TxOut {
+            value: Amount::from_sat(150000),
+            script_pubkey: script("0014d0c4a3ef09e997b6e99e397e518fe3e41a118ca1"),
+        };
+        let status = EsploraStatus {
+            confirmed: true,
+            block_height: Some(840001),
+            block_hash: Some(
+                "000000000000000000024bead8df69990852c202db0e0097c1a12ea637d7e96d"
+                    .parse()
+                    .unwrap(),
+            ),
+            block_time: Some(1713571767),
+            first_seen: None,
+        };
+        golden::check(
+            "tx",
+            &esplora_tx_from_prevouts(Network::Bitcoin, &tx, status, vec![prevout]),
+        );
+    }
+
+    #[test]
+    fn golden_tx_status() {
+        golden::check(
+            "tx_status",
+            &EsploraStatus::unconfirmed_since(Some(1713571500)),
+        );
+    }
+}
@@ -0,0 +1,237 @@
+//! Transaction size estimation from input and output types.
+//!
+//! Weights come from rust-bitcoin's `predict_weight`, assuming the largest
+//! DER-encoded ECDSA signatures (72 bytes with the sighash flag), compressed
+//! keys and default sighash Schnorr signatures, so estimates never underpay.
+//! Multisig is the `OP_CHECKMULTISIG` kind, spent with its `m` signatures.
+
+use axum::{http::StatusCode, response::IntoResponse, Json};
+use bitcoincore_rpc::bitcoin::transaction::{predict_weight, InputWeightPrediction};
+use bitcoincore_rpc::bitcoin::VarInt;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Largest DER-encoded ECDSA signature plus the sighash flag
+const ECDSA_SIGNATURE_LEN: usize = 72;
+
+const COMPRESSED_PUBKEY_LEN: usize = 33;
+
+/// Length of the script of an output type
+fn output_script_len(kind: &str) -> Option<usize> {
+    match kind {
+        "p2pkh" => Some(25),
+        "p2sh" => Some(23),
+        "p2wpkh" => Some(22),
+        "p2wsh" | "p2tr" => Some(34),
+        _ => {
+            // OP_RETURN followed by a push of `len` bytes
+            let len = kind.strip_prefix("op_return:")?.parse().ok()?;
+            Some(1 + push_len(len))
+        }
+    }
+}
+
+/// Length of a push of `len` bytes in a script
+fn push_len(len: usize) -> usize {
+    match len {
+        0..=75 => 1 + len,
+        76..=255 => 2 + len,
+        _ => 3 + len,
+    }
+}
+
+/// Parses `<wrapper>:<m>-of-<n>`, returning the wrapper and the multisig script length
+fn parse_multisig(kind: &str) -> Option<(&str, usize, usize)> {
+    let (wrapper, threshold) = kind.split_once(':')?;
+    let (m, n) = threshold.split_once("-of-")?;
+    let (m, n): (usize, usize) = (m.parse().ok()?, n.parse().ok()?);
+    if m == 0 || m > n || n > 16 {
+        return None;
+    }
+    // OP_m <n keys> OP_n OP_CHECKMULTISIG
+    let script_len = 3 + n * (1 + COMPRESSED_PUBKEY_LEN);
+    Some((wrapper, m, script_len))
+}
+
+/// Prediction for spending an output of an input type
+fn input_prediction(kind: &str) -> Option<InputWeightPrediction> {
+    let signature_and_key = [ECDSA_SIGNATURE_LEN, COMPRESSED_PUBKEY_LEN];
+    match kind {
+        "p2pkh" => return Some(InputWeightPrediction::P2PKH_COMPRESSED_MAX),
+        "p2wpkh" => return Some(InputWeightPrediction::P2WPKH_MAX),
+        "p2tr" => return Some(InputWeightPrediction::P2TR_KEY_DEFAULT_SIGHASH),
+        // A push of the 22 byte witness program
+        "p2sh-p2wpkh" => return Some(InputWeightPrediction::new(23, signature_and_key)),
+        _ => {}
+    }
+    let (wrapper, m, script_len) = parse_multisig(kind)?;
+    // The extra empty element CHECKMULTISIG pops, then the signatures
+    let mut witness = vec![0];
+    witness.extend(std::iter::repeat_n(ECDSA_SIGNATURE_LEN, m));
+    match wrapper {
+        "p2wsh" => {
+            witness.push(script_len);
+            Some(InputWeightPrediction::new(0, witness))
+        }
+        // A push of the 34 byte witness program
+        "p2sh-p2wsh" => {
+            witness.push(script_len);
+            Some(InputWeightPrediction::new(35, witness))
+        }
+        // Redeem scripts are pushed, so they're limited to 520 bytes
+        "p2sh" if script_len <= 520 => {
+            let script_sig = 1 + m * push_len(ECDSA_SIGNATURE_LEN) + push_len(script_len);
+            Some(InputWeightPrediction::new(
+                script_sig,
+                std::iter::empty::<usize>(),
+            ))
+        }
+        _ => None,
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct EstimateSizeRequest {
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct SizeEstimate {
+    weight: u64,
+    vsize: u64,
+    /// Weight each input adds, outpoint and sequence included
+    input_weights: Vec<u64>,
+    /// Weight each output adds
+    output_weights: Vec<u64>,
+}
+
+pub async fn post_estimate_size(Json(request): Json<EstimateSizeRequest>) -> impl IntoResponse {
+    if request.inputs.is_empty() || request.outputs.is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            "Expected at least one input and one output".to_string(),
+        )
+            .into_response();
+    }
+    let mut inputs = Vec::with_capacity(request.inputs.len());
+    for kind in &request.inputs {
+        match input_prediction(kind) {
+            Some(prediction) => inputs.push(prediction),
+            None => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    format!("Unsupported input type {}", kind),
+                )
+                    .into_response()
+            }
+        }
+    }
+    let mut outputs = Vec::with_capacity(request.outputs.len());
+    for kind in &request.outputs {
+        match output_script_len(kind) {
+            Some(len) => outputs.push(len),
+            None => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    format!("Unsupported output type {}", kind),
+                )
+                    .into_response()
+            }
+        }
+    }
+
+    Json(size_estimate(&inputs, &outputs)).into_response()
+}
+
+/// Estimate for spending `inputs` to outputs with scripts of `outputs` bytes
+fn size_estimate(inputs: &[InputWeightPrediction], outputs: &[usize]) -> SizeEstimate {
+    let weight = predict_weight(inputs.iter().copied(), outputs.iter().copied());
+    SizeEstimate {
+        weight: weight.to_wu(),
+        vsize: weight.to_vbytes_ceil(),
+        // Txid, vout and sequence are 40 bytes of non-witness data
+        input_weights: inputs
+            .iter()
+            .map(|prediction| prediction.weight().to_wu() + 40 * 4)
+            .collect(),
+        output_weights: outputs
+            .iter()
+            .map(|&len| (8 + VarInt(len as u64).size() as u64 + len as u64) * 4)
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden;
+    use serde_json::Value;
+
+    async fn estimate(inputs: &[&str], outputs: &[&str]) -> (StatusCode, Value) {
+        let request = EstimateSizeRequest {
+            inputs: inputs.iter().map(|kind| kind.to_string()).collect(),
+            outputs: outputs.iter().map(|kind| kind.to_string()).collect(),
+        };
+        let response = post_estimate_size(Json(request)).await.into_response();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn matches_well_known_transaction_sizes() {
+        // (inputs, outputs, weight, vsize), with 72 byte ECDSA signatures
+        let vectors: [(&[&str], &[&str], u64, u64); 5] = [
+            (&["p2wpkh"], &["p2wpkh", "p2wpkh"], 562, 141),
+            (&["p2tr"], &["p2tr"], 444, 111),
+            (&["p2pkh"], &["p2pkh"], 768, 192),
+            (&["p2sh-p2wpkh"], &["p2wpkh"], 530, 133),
+            (&["p2wsh:2-of-3"], &["p2wsh"], 632, 158),
+        ];
+        for (inputs, outputs, weight, vsize) in vectors {
+            let (status, estimate) = estimate(inputs, outputs).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(estimate["weight"], weight, "{:?} to {:?}", inputs, outputs);
+            assert_eq!(estimate["vsize"], vsize, "{:?} to {:?}", inputs, outputs);
+        }
+    }
+
+    #[tokio::test]
+    async fn breaks_the_weight_down_per_input_and_output() {
+        let (_, estimate) = estimate(&["p2wpkh", "p2tr"], &["op_return:80", "p2tr"]).await;
+        // Outpoint and sequence, a 1 byte script length, then the witness
+        assert_eq!(estimate["input_weights"], serde_json::json!([272, 230]));
+        // Value, script length and OP_RETURN with an 80 byte OP_PUSHDATA1
+        assert_eq!(estimate["output_weights"], serde_json::json!([368, 172]));
+    }
+
+    #[tokio::test]
+    async fn rejects_unsupported_types() {
+        for kind in [
+            "p2wsh:0-of-3",
+            "p2wsh:3-of-2",
+            "p2wsh:1-of-17",
+            "p2sh:16-of-16",
+            "p2pk",
+        ] {
+            let (status, _) = estimate(&[kind], &["p2wpkh"]).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", kind);
+        }
+        let (status, _) = estimate(&["p2sh:15-of-15"], &["op_return:80"]).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = estimate(&["p2wpkh"], &["p2sh-p2wpkh"]).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn golden_estimate_size() {
+        let p2wpkh = output_script_len("p2wpkh").unwrap();
+        golden::check(
+            "estimate_size",
+            &size_estimate(&[InputWeightPrediction::P2WPKH_MAX], &[p2wpkh, p2wpkh]),
+        );
+    }
+}
@@ -0,0 +1,92 @@
+//! Throttles the accepted request rate right after startup.
+//!
+//! The allowed rate ramps linearly from `initial_rps` to `target_rps` over the
+//! warm-up window, after which requests are no longer limited. This keeps a
+//! restarted instance from forwarding a thundering herd straight to bitcoind.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::extract::{Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+pub struct Warmup {
+    started: Instant,
+    duration: Duration,
+    initial_rps: f64,
+    target_rps: f64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl Warmup {
+    pub fn new(duration: Duration, initial_rps: f64, target_rps: f64) -> Self {
+        let started = Instant::now();
+        Self {
+            started,
+            duration,
+            initial_rps,
+            target_rps,
+            bucket: Mutex::new(Bucket {
+                tokens: initial_rps,
+                refilled_at: started,
+            }),
+        }
+    }
+
+    /// Allowed requests per second at `now`, or `None` once warm-up is over
+    fn current_rate(&self, now: Instant) -> Option<f64> {
+        let elapsed = now.duration_since(self.started);
+        if elapsed >= self.duration {
+            return None;
+        }
+        let progress = elapsed.as_secs_f64() / self.duration.as_secs_f64();
+        Some(self.initial_rps + (self.target_rps - self.initial_rps) * progress)
+    }
+
+    /// Requests per second currently allowed, or `None` once warm-up is over
+    pub fn current_limit(&self) -> Option<f64> {
+        self.current_rate(Instant::now())
+    }
+
+    fn try_acquire(&self) -> bool {
+        let now = Instant::now();
+        let Some(rate) = self.current_rate(now) else {
+            return true;
+        };
+        let mut bucket = self.bucket.lock().expect("warmup lock poisoned");
+        let refill = now.duration_since(bucket.refilled_at).as_secs_f64() * rate;
+        // At most one second worth of burst
+        bucket.tokens = (bucket.tokens + refill).min(rate.max(1.0));
+        bucket.refilled_at = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+pub async fn limit_during_warmup(
+    State(warmup): State<Arc<Warmup>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if warmup.try_acquire() {
+        return next.run(req).await;
+    }
+    metrics::counter!("warmup_rejected_requests_total").increment(1);
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [(header::RETRY_AFTER, "1")],
+        "Warming up, retry shortly",
+    )
+        .into_response()
+}
@@ -0,0 +1,445 @@
+//! Webhook notifications for watched outpoints.
+//!
+//! Watches are kept in the [`WatchRegistry`] with a data directory, in memory
+//! otherwise. The mempool mirror reports spends as it picks up new transactions
+//! and the block pipeline reports confirmed ones; each watch is notified once
+//! per stage and dropped after the confirmed notification, or once its TTL runs
+//! out.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use anyhow::Result;
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use bitcoincore_rpc::bitcoin::{OutPoint, Txid};
+use reqwest::{Method, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tracing::{debug, warn};
+use utoipa::ToSchema;
+
+use crate::chain::ChainWatcher;
+use crate::hooks::{Event, Hooks};
+use crate::mempool::MempoolTx;
+use crate::outbound::OutboundClient;
+use crate::registry::{self, WatchRegistry, OUTPOINT_WATCHES, PRUNE_INTERVAL};
+use crate::rpc::Rpc;
+use crate::trace_context::{self, TraceContext};
+use crate::tx::{block_status, EsploraStatus};
+use crate::AppState;
+
+/// Registered watches across all outpoints
+const MAX_WATCHES: usize = 10_000;
+
+/// An outpoint watch as persisted and listed
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WatchEntry {
+    id: u64,
+    txid: Txid,
+    vout: u32,
+    webhook: String,
+    /// Seconds since epoch, unset for watches kept until the spend confirms
+    expires_at: Option<u64>,
+    /// Already notified of a mempool spend
+    mempool_notified: bool,
+}
+
+struct Watch {
+    entry: WatchEntry,
+    webhook: Url,
+    /// Trace context of the request that registered the watch, lost on restart
+    trace: Option<TraceContext>,
+}
+
+#[derive(Serialize)]
+pub struct Notification {
+    id: u64,
+    txid: Txid,
+    vout: u32,
+    /// Transaction and input index spending the outpoint
+    spending_txid: Txid,
+    vin: u32,
+    status: EsploraStatus,
+    /// Added by watch hooks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    annotations: Option<Map<String, Value>>,
+    #[serde(skip)]
+    webhook: Url,
+    #[serde(skip)]
+    trace: Option<TraceContext>,
+}
+
+pub struct OutpointWatches {
+    watches: RwLock<HashMap<OutPoint, Vec<Watch>>>,
+    next_id: AtomicU64,
+    notifications: UnboundedSender<Notification>,
+    registry: Option<Arc<WatchRegistry>>,
+}
+
+impl OutpointWatches {
+    /// Watches loaded from `registry` when there is one
+    pub fn new(
+        registry: Option<Arc<WatchRegistry>>,
+    ) -> Result<(Self, UnboundedReceiver<Notification>)> {
+        let entries: Vec<WatchEntry> = match &registry {
+            Some(registry) => registry.load(OUTPOINT_WATCHES)?,
+            None => Vec::new(),
+        };
+        let next_id = entries.iter().map(|entry| entry.id + 1).max().unwrap_or(1);
+        let mut watches: HashMap<OutPoint, Vec<Watch>> = HashMap::new();
+        for entry in entries {
+            let Ok(webhook) = Url::parse(&entry.webhook) else {
+                warn!("Dropping watch {} with invalid webhook", entry.id);
+                continue;
+            };
+            metrics::gauge!("outpoint_watches").increment(1.0);
+            watches
+                .entry(OutPoint::new(entry.txid, entry.vout))
+                .or_default()
+                .push(Watch {
+                    entry,
+                    webhook,
+                    trace: None,
+                });
+        }
+        let (notifications, receiver) = mpsc::unbounded_channel();
+        let watches = Self {
+            watches: RwLock::new(watches),
+            next_id: AtomicU64::new(next_id),
+            notifications,
+            registry,
+        };
+        Ok((watches, receiver))
+    }
+
+    fn add(&self, outpoint: OutPoint, webhook: Url, ttl: Option<u64>) -> Option<u64> {
+        let mut watches = self.watches.write().expect("watch lock poisoned");
+        if watches.values().map(Vec::len).sum::<usize>() >= MAX_WATCHES {
+            return None;
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let entry = WatchEntry {
+            id,
+            txid: outpoint.txid,
+            vout: outpoint.vout,
+            webhook: webhook.to_string(),
+            expires_at: registry::expires_at(ttl),
+            mempool_notified: false,
+        };
+        self.persist(&entry);
+        watches.entry(outpoint).or_default().push(Watch {
+            entry,
+            webhook,
+            trace: trace_context::current(),
+        });
+        metrics::gauge!("outpoint_watches").increment(1.0);
+        Some(id)
+    }
+
+    fn persist(&self, entry: &WatchEntry) {
+        if let Some(registry) = &self.registry {
+            registry.put(OUTPOINT_WATCHES, entry.id, entry);
+        }
+    }
+
+    fn forget(&self, watch: &Watch) {
+        if let Some(registry) = &self.registry {
+            registry.delete(OUTPOINT_WATCHES, watch.entry.id);
+        }
+    }
+
+    /// Drops the watches whose TTL ran out
+    fn prune(&self, now: u64) {
+        let mut watches = self.watches.write().expect("watch lock poisoned");
+        watches.retain(|_, watching| {
+            watching.retain(|watch| {
+                let expired = watch.entry.expires_at.is_some_and(|at| at <= now);
+                if expired {
+                    debug!("Outpoint watch {} expired", watch.entry.id);
+                    self.forget(watch);
+                    metrics::gauge!("outpoint_watches").decrement(1.0);
+                }
+                !expired
+            });
+            !watching.is_empty()
+        });
+    }
+
+    /// Watches, oldest first
+    fn list(&self) -> Vec<WatchEntry> {
+        let watches = self.watches.read().expect("watch lock poisoned");
+        let mut entries: Vec<WatchEntry> = watches
+            .values()
+            .flatten()
+            .map(|watch| watch.entry.clone())
+            .collect();
+        entries.sort_by_key(|entry| entry.id);
+        entries
+    }
+
+    /// Notifies watches of outpoints spent by a transaction newly seen in the mempool
+    pub fn spent_in_mempool(&self, tx: &MempoolTx) {
+        if self.watches.read().expect("watch lock poisoned").is_empty() {
+            return;
+        }
+        let mut watches = self.watches.write().expect("watch lock poisoned");
+        for (vin, outpoint) in tx.inputs.iter().enumerate() {
+            let Some(watching) = watches.get_mut(outpoint) else {
+                continue;
+            };
+            for watch in watching
+                .iter_mut()
+                .filter(|watch| !watch.entry.mempool_notified)
+            {
+                watch.entry.mempool_notified = true;
+                self.persist(&watch.entry);
+                self.notify(
+                    watch,
+                    outpoint,
+                    tx.txid,
+                    vin as u32,
+                    EsploraStatus::unconfirmed_since(Some(tx.first_seen)),
+                );
+            }
+        }
+    }
+
+    /// Notifies and drops watches of outpoints spent in a block
+    fn spent_in_block(&self, txid: Txid, inputs: &[OutPoint], status: &EsploraStatus) {
+        let mut watches = self.watches.write().expect("watch lock poisoned");
+        for (vin, outpoint) in inputs.iter().enumerate() {
+            let Some(watching) = watches.remove(outpoint) else {
+                continue;
+            };
+            metrics::gauge!("outpoint_watches").decrement(watching.len() as f64);
+            for watch in &watching {
+                self.forget(watch);
+                self.notify(watch, outpoint, txid, vin as u32, status.clone());
+            }
+        }
+    }
+
+    fn notify(
+        &self,
+        watch: &Watch,
+        outpoint: &OutPoint,
+        spending_txid: Txid,
+        vin: u32,
+        status: EsploraStatus,
+    ) {
+        // Only fails once the notifier is gone, i.e. on shutdown
+        let _ = self.notifications.send(Notification {
+            id: watch.entry.id,
+            txid: outpoint.txid,
+            vout: outpoint.vout,
+            spending_txid,
+            vin,
+            status,
+            annotations: None,
+            webhook: watch.webhook.clone(),
+            trace: watch.trace,
+        });
+    }
+
+    /// Checks every new block for spends of watched outpoints, and prunes
+    /// expired watches every [`PRUNE_INTERVAL`]
+    pub async fn run(self: Arc<Self>, rpc: Arc<Rpc>, watcher: Arc<ChainWatcher>) {
+        let mut blocks = watcher.subscribe();
+        let mut prune = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            let block = tokio::select! {
+                block = blocks.recv() => match block {
+                    Ok(block) => block,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Outpoint watches skipped {} blocks", skipped);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => return,
+                },
+                _ = prune.tick() => {
+                    self.prune(registry::now());
+                    continue;
+                }
+            };
+            if self.watches.read().expect("watch lock poisoned").is_empty() {
+                continue;
+            }
+            let check = async {
+                let status = block_status(&rpc, &block.hash).await?;
+                for tx in rpc.get_block(&block.hash).await?.txdata {
+                    let inputs: Vec<OutPoint> =
+                        tx.input.iter().map(|input| input.previous_output).collect();
+                    self.spent_in_block(tx.compute_txid(), &inputs, &status);
+                }
+                Ok::<_, bitcoincore_rpc::Error>(())
+            };
+            if let Err(e) = check.await {
+                warn!(
+                    "Failed to check block {} for watched spends: {}",
+                    block.hash, e
+                );
+            }
+        }
+    }
+}
+
+/// Delivers notifications to their webhooks, one at a time and in order, after
+/// the watch hooks had their say
+pub async fn run_notifier(
+    mut notifications: UnboundedReceiver<Notification>,
+    http: OutboundClient,
+    hooks: Arc<Hooks>,
+) {
+    while let Some(mut notification) = notifications.recv().await {
+        if hooks.handles(Event::Watch) {
+            let event = serde_json::to_value(&notification).unwrap_or_default();
+            match trace_context::scope(notification.trace, hooks.watch(event)).await {
+                Some(annotations) => {
+                    notification.annotations = (!annotations.is_empty()).then_some(annotations)
+                }
+                None => {
+                    metrics::counter!("outpoint_watch_notifications_total", "outcome" => "dropped")
+                        .increment(1);
+                    continue;
+                }
+            }
+        }
+        let request = http
+            .request(Method::POST, notification.webhook.clone())
+            .json(&notification);
+        let sent = trace_context::scope(notification.trace, http.send("outpoint_watch", request));
+        let outcome = match sent.await {
+            Ok(response) if response.status().is_success() => "delivered",
+            Ok(response) => {
+                debug!(
+                    "Webhook {} answered watch {} with {}",
+                    notification.webhook,
+                    notification.id,
+                    response.status()
+                );
+                "rejected"
+            }
+            Err(e) => {
+                debug!(
+                    "Failed to deliver watch {} to {}: {}",
+                    notification.id, notification.webhook, e
+                );
+                "error"
+            }
+        };
+        metrics::counter!("outpoint_watch_notifications_total", "outcome" => outcome).increment(1);
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct WatchRequest {
+    txid: String,
+    vout: u32,
+    webhook: String,
+    /// Seconds after which the watch is dropped even if the outpoint wasn't spent
+    ttl: Option<u64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct WatchCreated {
+    id: u64,
+}
+
+pub async fn post_watch_outpoint(
+    State(state): State<AppState>,
+    Json(request): Json<WatchRequest>,
+) -> impl IntoResponse {
+    let Ok(txid) = Txid::from_str(&request.txid) else {
+        return (StatusCode::BAD_REQUEST, "Invalid txid").into_response();
+    };
+    let webhook = match Url::parse(&request.webhook) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => url,
+        _ => return (StatusCode::BAD_REQUEST, "Invalid webhook URL").into_response(),
+    };
+    let outpoint = OutPoint::new(txid, request.vout);
+    let Some(id) = state.watches.add(outpoint, webhook, request.ttl) else {
+        return (StatusCode::SERVICE_UNAVAILABLE, "Watch limit reached").into_response();
+    };
+    // The spend may already be in the mempool
+    if let Some(tx) = state
+        .mempool
+        .spent_by(&outpoint)
+        .and_then(|(txid, _)| state.mempool.get(&txid))
+    {
+        state.watches.spent_in_mempool(&tx);
+    }
+    (StatusCode::CREATED, Json(WatchCreated { id })).into_response()
+}
+
+pub async fn get_watches(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.watches.list())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden;
+    use crate::storage::Backend;
+
+    fn open_registry(dir: &std::path::Path) -> Arc<WatchRegistry> {
+        let path = Backend::Redb.path(dir, "watches");
+        Arc::new(WatchRegistry::open(Backend::Redb, &path, true).unwrap())
+    }
+
+    fn outpoint(vout: u32) -> OutPoint {
+        OutPoint::new(Txid::from_str(&"11".repeat(32)).unwrap(), vout)
+    }
+
+    #[test]
+    fn watches_survive_a_restart_until_they_expire() {
+        let dir = std::env::temp_dir().join(format!("minipool-watches-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let webhook = Url::parse("https://hooks.example.com/spends").unwrap();
+        {
+            let (watches, _) = OutpointWatches::new(Some(open_registry(&dir))).unwrap();
+            assert_eq!(watches.add(outpoint(0), webhook.clone(), None), Some(1));
+            assert_eq!(watches.add(outpoint(1), webhook.clone(), Some(60)), Some(2));
+        }
+
+        let (watches, _) = OutpointWatches::new(Some(open_registry(&dir))).unwrap();
+        let listed: Vec<u64> = watches.list().iter().map(|entry| entry.id).collect();
+        assert_eq!(listed, [1, 2]);
+        assert_eq!(watches.add(outpoint(2), webhook, None), Some(3));
+        watches.prune(registry::now() + 61);
+        let listed: Vec<u64> = watches.list().iter().map(|entry| entry.id).collect();
+        assert_eq!(listed, [1, 3]);
+        drop(watches);
+
+        let (watches, _) = OutpointWatches::new(Some(open_registry(&dir))).unwrap();
+        let listed: Vec<u64> = watches.list().iter().map(|entry| entry.id).collect();
+        assert_eq!(listed, [1, 3]);
+        drop(watches);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn golden_outpoint_watches() {
+        let (watches, _) = OutpointWatches::new(None).unwrap();
+        let webhook = Url::parse("https://hooks.example.com/spends").unwrap();
+        let spent = OutPoint::new(
+            Txid::from_str("4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b")
+                .unwrap(),
+            0,
+        );
+        let unspent = OutPoint::new(
+            Txid::from_str("f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16")
+                .unwrap(),
+            1,
+        );
+        watches.add(spent, webhook.clone(), None);
+        watches.add(unspent, webhook, Some(3600));
+        let mut entries = watches.list();
+        entries[0].mempool_notified = true;
+        entries[1].expires_at = Some(1700086400);
+        golden::check("outpoint_watches", &entries);
+    }
+}
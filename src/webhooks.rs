@@ -0,0 +1,591 @@
+//! Webhook subscriptions for blocks, reorgs and address activity.
+//!
+//! Subscriptions come from `--webhook`, receiving block and reorg events for the
+//! life of the process, or from the admin API, which can also watch addresses.
+//! API subscriptions are kept in the [`WatchRegistry`] with a data directory
+//! and may be given a TTL, after which they're dropped.
+//! Address events are sent when a transaction funding or spending a watched
+//! address confirms, and when it enters the mempool if the mempool mirror
+//! resolves scripts (with the address index).
+//!
+//! Every event is POSTed as `{"id", "subscription", "event", "data"}`. With a
+//! secret configured, `X-Minipool-Signature` carries the hex HMAC-SHA256 of
+//! `<timestamp>.<body>` under it, the timestamp being sent in
+//! `X-Minipool-Timestamp`. Failed deliveries are retried with exponential
+//! backoff; the delivery `id` stays the same across attempts so receivers can
+//! drop duplicates.
+
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use axum::extract::{Path, State};
+use axum::{http::StatusCode, response::IntoResponse, Json};
+use bitcoincore_rpc::bitcoin::hashes::{hmac, sha256, Hash, HashEngine};
+use bitcoincore_rpc::bitcoin::{Address, Txid};
+use reqwest::{Method, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tracing::{debug, warn};
+use utoipa::ToSchema;
+
+use crate::addresses::{self, script_hash, ScriptHash};
+use crate::chain::{BlockEvent, ChainWatcher};
+use crate::mempool::MempoolTx;
+use crate::outbound::OutboundClient;
+use crate::registry::{self, WatchRegistry, PRUNE_INTERVAL, SUBSCRIPTIONS};
+use crate::rpc::Rpc;
+use crate::tx::{block_status, EsploraStatus};
+use crate::AppState;
+
+/// Subscriptions registered through the API
+const MAX_SUBSCRIPTIONS: usize = 1000;
+
+/// Addresses watched by one subscription
+const MAX_ADDRESSES: usize = 1000;
+
+/// Delay before the first retry, doubled for every further one
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(300);
+
+const TIMESTAMP_HEADER: &str = "x-minipool-timestamp";
+const SIGNATURE_HEADER: &str = "x-minipool-signature";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Kind {
+    Block,
+    Reorg,
+    Address,
+}
+
+impl Kind {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "block" => Some(Kind::Block),
+            "reorg" => Some(Kind::Reorg),
+            "address" => Some(Kind::Address),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Block => "block",
+            Kind::Reorg => "reorg",
+            Kind::Address => "address",
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+struct Subscription {
+    id: u64,
+    url: String,
+    events: Vec<Kind>,
+    addresses: Vec<String>,
+    /// Set with `--webhook`, so not removable through the API
+    configured: bool,
+    /// Seconds since epoch, unset for subscriptions kept until removed
+    expires_at: Option<u64>,
+    #[serde(skip)]
+    target: Url,
+    /// Script hash of every watched address → the address
+    #[serde(skip)]
+    scripts: HashMap<ScriptHash, String>,
+}
+
+/// An API subscription as read back from the registry
+#[derive(Deserialize)]
+struct PersistedSubscription {
+    id: u64,
+    url: String,
+    events: Vec<Kind>,
+    addresses: Vec<String>,
+    expires_at: Option<u64>,
+}
+
+impl PersistedSubscription {
+    /// Addresses were checked against the network when subscribing
+    fn restore(self) -> Result<Subscription> {
+        let target = Url::parse(&self.url).context("Invalid webhook URL")?;
+        let mut scripts = HashMap::new();
+        for address in &self.addresses {
+            let parsed = Address::from_str(address)
+                .context("Invalid address")?
+                .assume_checked();
+            scripts.insert(script_hash(&parsed.script_pubkey()), address.clone());
+        }
+        Ok(Subscription {
+            id: self.id,
+            url: self.url,
+            events: self.events,
+            addresses: self.addresses,
+            configured: false,
+            expires_at: self.expires_at,
+            target,
+            scripts,
+        })
+    }
+}
+
+/// An event on its way to one subscriber
+pub struct Delivery {
+    url: Url,
+    event: Kind,
+    body: String,
+}
+
+pub struct Webhooks {
+    subscriptions: RwLock<Vec<Subscription>>,
+    next_subscription: AtomicU64,
+    next_delivery: AtomicU64,
+    deliveries: UnboundedSender<Delivery>,
+    registry: Option<Arc<WatchRegistry>>,
+}
+
+impl Webhooks {
+    /// Webhooks with the API subscriptions loaded from `registry` when there
+    /// is one, then block and reorg subscriptions for `urls` numbered after them
+    pub fn new(
+        urls: &[Url],
+        registry: Option<Arc<WatchRegistry>>,
+    ) -> Result<(Self, UnboundedReceiver<Delivery>)> {
+        let persisted: Vec<PersistedSubscription> = match &registry {
+            Some(registry) => registry.load(SUBSCRIPTIONS)?,
+            None => Vec::new(),
+        };
+        let mut subscriptions = Vec::new();
+        for subscription in persisted {
+            let id = subscription.id;
+            match subscription.restore() {
+                Ok(subscription) => subscriptions.push(subscription),
+                Err(e) => warn!("Dropping webhook subscription {}: {}", id, e),
+            }
+        }
+        metrics::gauge!("webhook_subscriptions").increment(subscriptions.len() as f64);
+        let first_configured = subscriptions
+            .iter()
+            .map(|subscription| subscription.id + 1)
+            .max()
+            .unwrap_or(1);
+        subscriptions.extend(urls.iter().enumerate().map(|(index, url)| Subscription {
+            id: first_configured + index as u64,
+            url: url.to_string(),
+            events: vec![Kind::Block, Kind::Reorg],
+            addresses: Vec::new(),
+            configured: true,
+            expires_at: None,
+            target: url.clone(),
+            scripts: HashMap::new(),
+        }));
+        let (deliveries, receiver) = mpsc::unbounded_channel();
+        let webhooks = Self {
+            subscriptions: RwLock::new(subscriptions),
+            next_subscription: AtomicU64::new(first_configured + urls.len() as u64),
+            next_delivery: AtomicU64::new(1),
+            deliveries,
+            registry,
+        };
+        Ok((webhooks, receiver))
+    }
+
+    /// Drops the API subscriptions whose TTL ran out
+    fn prune(&self, now: u64) {
+        let mut subscriptions = self.subscriptions.write().expect("webhook lock poisoned");
+        subscriptions.retain(|subscription| {
+            let expired = subscription.expires_at.is_some_and(|at| at <= now);
+            if expired {
+                debug!("Webhook subscription {} expired", subscription.id);
+                if let Some(registry) = &self.registry {
+                    registry.delete(SUBSCRIPTIONS, subscription.id);
+                }
+                metrics::gauge!("webhook_subscriptions").decrement(1.0);
+            }
+            !expired
+        });
+    }
+
+    fn deliver(&self, subscription: &Subscription, event: Kind, data: &Value) {
+        let id = self.next_delivery.fetch_add(1, Ordering::Relaxed);
+        let body = json!({
+            "id": id,
+            "subscription": subscription.id,
+            "event": event,
+            "data": data,
+        });
+        // Only fails once the deliverer is gone, i.e. on shutdown
+        let _ = self.deliveries.send(Delivery {
+            url: subscription.target.clone(),
+            event,
+            body: body.to_string(),
+        });
+    }
+
+    /// Sends `data` to every subscription of `event`
+    fn publish(&self, event: Kind, data: Value) {
+        let subscriptions = self.subscriptions.read().expect("webhook lock poisoned");
+        for subscription in subscriptions
+            .iter()
+            .filter(|subscription| subscription.events.contains(&event))
+        {
+            self.deliver(subscription, event, &data);
+        }
+    }
+
+    /// Sends an address event to every subscription watching one of `scripts`
+    fn address_activity<'a>(
+        &self,
+        txid: Txid,
+        scripts: impl Iterator<Item = &'a ScriptHash>,
+        status: &EsploraStatus,
+    ) {
+        let scripts: HashSet<&ScriptHash> = scripts.collect();
+        let subscriptions = self.subscriptions.read().expect("webhook lock poisoned");
+        for subscription in subscriptions.iter() {
+            for script in &scripts {
+                if let Some(address) = subscription.scripts.get(*script) {
+                    let data = json!({ "address": address, "txid": txid, "status": status });
+                    self.deliver(subscription, Kind::Address, &data);
+                }
+            }
+        }
+    }
+
+    fn watches_addresses(&self) -> bool {
+        self.subscriptions
+            .read()
+            .expect("webhook lock poisoned")
+            .iter()
+            .any(|subscription| !subscription.scripts.is_empty())
+    }
+
+    /// Reports a transaction newly seen in the mempool to the subscriptions
+    /// watching an address it funds or spends
+    pub fn seen_in_mempool(&self, tx: &MempoolTx) {
+        if tx.outputs.is_empty() || !self.watches_addresses() {
+            return;
+        }
+        let status = EsploraStatus::unconfirmed_since(Some(tx.first_seen));
+        self.address_activity(tx.txid, tx.scripts(), &status);
+    }
+
+    /// Sends block and reorg events as the watcher announces blocks, and address
+    /// events for the transactions they confirm. Prunes expired subscriptions
+    /// every [`PRUNE_INTERVAL`].
+    pub async fn run(self: Arc<Self>, rpc: Arc<Rpc>, watcher: Arc<ChainWatcher>) {
+        let mut blocks = watcher.subscribe();
+        let mut prune = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            let block = tokio::select! {
+                block = blocks.recv() => match block {
+                    Ok(block) => block,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Webhooks skipped {} blocks", skipped);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => return,
+                },
+                _ = prune.tick() => {
+                    self.prune(registry::now());
+                    continue;
+                }
+            };
+            self.publish_block(&block);
+            if !self.watches_addresses() {
+                continue;
+            }
+            let activity = async {
+                let status = block_status(&rpc, &block.hash).await?;
+                let txs = addresses::block_scripts(&rpc, &block.hash).await?;
+                Ok::<_, anyhow::Error>((status, txs))
+            };
+            match activity.await {
+                Ok((status, txs)) => {
+                    for (txid, scripts) in txs {
+                        self.address_activity(txid, scripts.iter(), &status);
+                    }
+                }
+                Err(e) => warn!(
+                    "Failed to check block {} for watched addresses: {}",
+                    block.hash, e
+                ),
+            }
+        }
+    }
+
+    fn publish_block(&self, block: &BlockEvent) {
+        if !block.disconnected.is_empty() {
+            self.publish(
+                Kind::Reorg,
+                json!({
+                    "fork_height": block.height - 1,
+                    "disconnected": block.disconnected.len(),
+                }),
+            );
+        }
+        let seen_at = block
+            .seen_at
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        self.publish(
+            Kind::Block,
+            json!({
+                "height": block.height,
+                "hash": block.hash.to_string(),
+                "seen_at": seen_at,
+            }),
+        );
+    }
+}
+
+/// Hex HMAC-SHA256 of `<timestamp>.<body>` under `secret`
+fn sign(secret: &str, timestamp: u64, body: &str) -> String {
+    let mut engine = hmac::HmacEngine::<sha256::Hash>::new(secret.as_bytes());
+    engine.input(timestamp.to_string().as_bytes());
+    engine.input(b".");
+    engine.input(body.as_bytes());
+    hmac::Hmac::<sha256::Hash>::from_engine(engine).to_string()
+}
+
+/// Delivers events, each in its own task so a slow or failing subscriber
+/// doesn't hold up the others
+pub async fn run_deliverer(
+    mut deliveries: UnboundedReceiver<Delivery>,
+    http: OutboundClient,
+    secret: Option<Arc<str>>,
+    max_attempts: u32,
+) {
+    while let Some(delivery) = deliveries.recv().await {
+        tokio::spawn(deliver(
+            delivery,
+            http.clone(),
+            secret.clone(),
+            max_attempts,
+        ));
+    }
+}
+
+async fn deliver(
+    delivery: Delivery,
+    http: OutboundClient,
+    secret: Option<Arc<str>>,
+    max_attempts: u32,
+) {
+    let mut delay = FIRST_RETRY_DELAY;
+    for attempt in 1..=max_attempts {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let mut request = http
+            .request(Method::POST, delivery.url.clone())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(TIMESTAMP_HEADER, timestamp)
+            .body(delivery.body.clone());
+        if let Some(secret) = &secret {
+            request = request.header(
+                SIGNATURE_HEADER,
+                format!("sha256={}", sign(secret, timestamp, &delivery.body)),
+            );
+        }
+        match http.send("webhook", request).await {
+            Ok(response) if response.status().is_success() => {
+                metrics::counter!("webhook_deliveries_total", "event" => delivery.event.name(), "outcome" => "delivered")
+                    .increment(1);
+                return;
+            }
+            Ok(response) => debug!(
+                "Webhook {} answered attempt {} with {}",
+                delivery.url,
+                attempt,
+                response.status()
+            ),
+            Err(e) => debug!(
+                "Failed to deliver attempt {} to webhook {}: {}",
+                attempt, delivery.url, e
+            ),
+        }
+        if attempt < max_attempts {
+            metrics::counter!("webhook_retries_total").increment(1);
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(MAX_RETRY_DELAY);
+        }
+    }
+    warn!(
+        "Giving up on {} webhook {} after {} attempts",
+        delivery.event.name(),
+        delivery.url,
+        max_attempts
+    );
+    metrics::counter!("webhook_deliveries_total", "event" => delivery.event.name(), "outcome" => "failed")
+        .increment(1);
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct SubscribeRequest {
+    url: String,
+    /// `block`, `reorg` and `address`
+    events: Vec<String>,
+    /// Watched by `address` subscriptions
+    #[serde(default)]
+    addresses: Vec<String>,
+    /// Seconds after which the subscription is dropped
+    ttl: Option<u64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SubscriptionCreated {
+    id: u64,
+}
+
+pub async fn post_webhook(
+    State(state): State<AppState>,
+    Json(request): Json<SubscribeRequest>,
+) -> impl IntoResponse {
+    let target = match Url::parse(&request.url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => url,
+        _ => return (StatusCode::BAD_REQUEST, "Invalid webhook URL").into_response(),
+    };
+    let mut events = Vec::new();
+    for name in &request.events {
+        match Kind::from_name(name) {
+            Some(kind) if !events.contains(&kind) => events.push(kind),
+            Some(_) => {}
+            None => return (StatusCode::BAD_REQUEST, "Unknown event").into_response(),
+        }
+    }
+    if events.is_empty() {
+        return (StatusCode::BAD_REQUEST, "Expected at least one event").into_response();
+    }
+    if events.contains(&Kind::Address) == request.addresses.is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            "Addresses go with, and only with, the address event",
+        )
+            .into_response();
+    }
+    if request.addresses.len() > MAX_ADDRESSES {
+        return (StatusCode::BAD_REQUEST, "Too many addresses").into_response();
+    }
+    let mut scripts = HashMap::new();
+    for address in &request.addresses {
+        match addresses::parse_address(&state, address) {
+            Ok(parsed) => {
+                scripts.insert(script_hash(&parsed.script_pubkey()), address.clone());
+            }
+            Err(rejection) => return rejection.into_response(),
+        }
+    }
+
+    let webhooks = &state.webhooks;
+    let mut subscriptions = webhooks
+        .subscriptions
+        .write()
+        .expect("webhook lock poisoned");
+    if subscriptions
+        .iter()
+        .filter(|subscription| !subscription.configured)
+        .count()
+        >= MAX_SUBSCRIPTIONS
+    {
+        return (StatusCode::SERVICE_UNAVAILABLE, "Webhook limit reached").into_response();
+    }
+    let id = webhooks.next_subscription.fetch_add(1, Ordering::Relaxed);
+    let subscription = Subscription {
+        id,
+        url: target.to_string(),
+        events,
+        addresses: request.addresses,
+        configured: false,
+        expires_at: registry::expires_at(request.ttl),
+        target,
+        scripts,
+    };
+    if let Some(registry) = &webhooks.registry {
+        registry.put(SUBSCRIPTIONS, id, &subscription);
+    }
+    subscriptions.push(subscription);
+    metrics::gauge!("webhook_subscriptions").increment(1.0);
+    (StatusCode::CREATED, Json(SubscriptionCreated { id })).into_response()
+}
+
+pub async fn get_webhooks(State(state): State<AppState>) -> impl IntoResponse {
+    let subscriptions = state
+        .webhooks
+        .subscriptions
+        .read()
+        .expect("webhook lock poisoned")
+        .clone();
+    Json(subscriptions)
+}
+
+pub async fn delete_webhook(
+    State(state): State<AppState>,
+    Path(id): Path<u64>,
+) -> impl IntoResponse {
+    let mut subscriptions = state
+        .webhooks
+        .subscriptions
+        .write()
+        .expect("webhook lock poisoned");
+    let Some(position) = subscriptions
+        .iter()
+        .position(|subscription| subscription.id == id)
+    else {
+        return (StatusCode::NOT_FOUND, "Unknown webhook").into_response();
+    };
+    if subscriptions[position].configured {
+        return (
+            StatusCode::FORBIDDEN,
+            "Webhooks set in the configuration can't be removed",
+        )
+            .into_response();
+    }
+    subscriptions.remove(position);
+    if let Some(registry) = &state.webhooks.registry {
+        registry.delete(SUBSCRIPTIONS, id);
+    }
+    metrics::gauge!("webhook_subscriptions").decrement(1.0);
+    StatusCode::NO_CONTENT.into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden;
+
+    #[test]
+    fn golden_webhooks() {
+        let subscribed = PersistedSubscription {
+            id: 1,
+            url: "https://hooks.example.com/payments".to_owned(),
+            events: vec![Kind::Address],
+            addresses: vec!["bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq".to_owned()],
+            expires_at: Some(1700086400),
+        };
+        let configured = Url::parse("https://hooks.example.com/blocks").unwrap();
+        golden::check(
+            "webhooks",
+            &[
+                subscribed.restore().unwrap(),
+                Subscription {
+                    id: 2,
+                    url: configured.to_string(),
+                    events: vec![Kind::Block, Kind::Reorg],
+                    addresses: Vec::new(),
+                    configured: true,
+                    expires_at: None,
+                    target: configured,
+                    scripts: HashMap::new(),
+                },
+            ],
+        );
+    }
+}
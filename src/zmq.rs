@@ -0,0 +1,54 @@
+//! Push notifications from bitcoind's ZMQ interface.
+//!
+//! bitcoind publishes `rawblock` when a block is connected and `rawtx` when a
+//! transaction enters the mempool or a block. Each notification wakes the chain
+//! watcher or the mempool mirror right away instead of at their next poll, so
+//! everything downstream of them (indexes, `/ws`, `/api/events`, webhooks)
+//! follows the node within a fraction of a second. Polling keeps running as a
+//! fallback for notifications lost while the socket reconnects.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::Notify;
+use tracing::{info, warn};
+use zeromq::{Socket, SocketRecv, SubSocket};
+
+/// Delay before setting up the subscription again after it failed
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Subscribes to `topic` at `endpoint` and wakes `wake` on every notification
+pub async fn run(endpoint: String, topic: &'static str, wake: Arc<Notify>) {
+    loop {
+        match subscribe(&endpoint, topic).await {
+            Ok(mut socket) => {
+                info!("Subscribed to {} notifications at {}", topic, endpoint);
+                loop {
+                    match socket.recv().await {
+                        Ok(_) => {
+                            metrics::counter!("zmq_notifications_total", "topic" => topic)
+                                .increment(1);
+                            wake.notify_one();
+                        }
+                        Err(e) => {
+                            warn!("Failed to receive {} notification: {}", topic, e);
+                            break;
+                        }
+                    }
+                }
+            }
+            Err(e) => warn!(
+                "Failed to subscribe to {} notifications at {}: {}",
+                topic, endpoint, e
+            ),
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn subscribe(endpoint: &str, topic: &str) -> zeromq::ZmqResult<SubSocket> {
+    let mut socket = SubSocket::new();
+    socket.connect(endpoint).await?;
+    socket.subscribe(topic).await?;
+    Ok(socket)
+}